@@ -1,37 +1,282 @@
-use wasm_bindgen::prelude::*;
-
 use serde::{Deserialize, Serialize};
 
-use crate::angle_fmt::AngleSerializeFmt;
-
 use super::color::ColorRGB;
+use super::hips::TransferFunction;
+
+/// How the sources of a catalog are drawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RenderMode {
+    /// A density map of the sources
+    #[default]
+    Heatmap,
+    /// A marker at the position of each source
+    Markers,
+}
+
+/// The shape of the markers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Shape {
+    Circle,
+    #[default]
+    Square,
+    Cross,
+    Plus,
+    Rhomb,
+    Triangle,
+}
+
+/// The unit of the size of the markers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SizeUnit {
+    /// Markers keeping the same size on the screen
+    #[default]
+    Pixel,
+    /// Markers covering the same angle on the sky
+    Arcsec,
+}
+
+/// The kernel splatted at the position of the sources of a heatmap
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Kernel {
+    /// A gaussian whose standard deviation is the bandwidth
+    #[default]
+    Gaussian,
+    /// A parabola falling to zero at the bandwidth
+    Epanechnikov,
+    /// A disk whose radius is the bandwidth
+    Tophat,
+}
+
+/// The density map of the sources
+///
+/// Without the EXT_float_blend WebGL extension, the kernels are summed in 16 bits floats:
+/// the density is then exact up to 2048 times the weight of the heaviest source.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Heatmap {
+    #[serde(default)]
+    pub kernel: Kernel,
+    #[serde(default = "default_bandwidth")]
+    pub bandwidth: f32,
+    #[serde(default)]
+    pub bandwidth_unit: SizeUnit,
+    /// The name of the column weighting the sources, e.g. a flux
+    #[serde(default)]
+    pub weight_column: Option<String>,
+    /// The function applied to the density before evaluating the colormap
+    #[serde(default)]
+    pub stretch: TransferFunction,
+    /// The densities mapped to both ends of the colormap.
+    /// It goes from zero to an estimation of the maximum density of the view if not given
+    #[serde(default)]
+    pub range: Option<[f32; 2]>,
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Self {
+            kernel: Kernel::default(),
+            bandwidth: default_bandwidth(),
+            bandwidth_unit: SizeUnit::default(),
+            weight_column: None,
+            stretch: TransferFunction::default(),
+            range: None,
+        }
+    }
+}
+
+/// The mapping of the values of a column to the size of the markers
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SizeMapping {
+    /// The name of the column
+    pub column: String,
+    /// The values mapped to both ends of `sizes`.
+    /// The range of the column is taken if not given
+    #[serde(default)]
+    pub range: Option<[f32; 2]>,
+    /// The sizes of the markers for the minimum and the maximum of the range.
+    /// Give a decreasing pair for the bright sources to be the largest
+    pub sizes: [f32; 2],
+}
+
+/// The mapping of the values of a column to the color of the markers
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ColorMapping {
+    /// The name of the column
+    pub column: String,
+    #[serde(default = "default_colormap")]
+    pub colormap: String,
+    /// The values mapped to both ends of the colormap.
+    /// The range of the column is taken if not given
+    #[serde(default)]
+    pub range: Option<[f32; 2]>,
+    #[serde(default)]
+    pub reversed: bool,
+}
+
+/// The grouping of the markers by HEALPix cells at wide fields of view
+///
+/// Each cell is drawn as a circle sized by its number of sources and colored by the
+/// mean of the color column of its sources
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Aggregation {
+    /// The width of the cells in pixels, giving their order
+    #[serde(default = "default_cell_size")]
+    pub cell_size: f32,
+    /// The deepest order of the cells, up to 7, beyond which the sources are drawn
+    /// individually
+    #[serde(default = "default_max_order")]
+    pub max_order: u8,
+    /// The sizes of the circles of the cells with the fewest and the most sources
+    #[serde(default = "default_aggregate_sizes")]
+    pub sizes: [f32; 2],
+}
+
+impl Default for Aggregation {
+    fn default() -> Self {
+        Self {
+            cell_size: default_cell_size(),
+            max_order: default_max_order(),
+            sizes: default_aggregate_sizes(),
+        }
+    }
+}
 
-#[wasm_bindgen]
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Catalog {
+    #[serde(default)]
+    pub mode: RenderMode,
+    /// The color of the markers whose color is not given by a column
     #[serde(default = "default_color")]
-    pub color: Option<ColorRGB>,
+    pub color: ColorRGB,
     #[serde(default = "default_opacity")]
-    pub opacity: Option<f32>,
-    #[serde(default = "default_shape")]
-    pub shape: Option<&'static str>,
+    pub opacity: f32,
+    #[serde(default)]
+    pub shape: Shape,
+    /// The size of the markers whose size is not given by a column
     #[serde(default = "default_size")]
-    pub size: Option<f32>,
+    pub size: f32,
+    #[serde(default)]
+    pub size_unit: SizeUnit,
+    #[serde(default)]
+    pub size_column: Option<SizeMapping>,
+    #[serde(default)]
+    pub color_column: Option<ColorMapping>,
+    #[serde(default)]
+    pub heatmap: Heatmap,
+    /// The markers are grouped at wide fields of view if given
+    #[serde(default)]
+    pub aggregation: Option<Aggregation>,
+    /// The color of the markers highlighting the selected sources
+    #[serde(default = "default_selection_color")]
+    pub selection_color: ColorRGB,
+    /// A condition on the numerical columns hiding the sources for which it is not true,
+    /// e.g. `mag < 12 and (isnull(plx) or plx > 0)`
+    #[serde(default)]
+    pub filter: Option<String>,
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self {
+            mode: RenderMode::default(),
+            color: default_color(),
+            opacity: default_opacity(),
+            shape: Shape::default(),
+            size: default_size(),
+            size_unit: SizeUnit::default(),
+            size_column: None,
+            color_column: None,
+            heatmap: Heatmap::default(),
+            aggregation: None,
+            selection_color: default_selection_color(),
+            filter: None,
+        }
+    }
+}
+
+/// The sources of the first catalog kept by a cross-match
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum XMatchMode {
+    /// Each source paired with its nearest counterpart
+    #[default]
+    Best,
+    /// Each source paired with all its counterparts
+    All,
+    /// The sources without any counterpart
+    NoMatch,
+}
+
+/// A positional cross-match between two catalogs
+///
+/// Two sources match if their separation is lower than the quadratic sum of `radius`
+/// and of their error radii
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct XMatch {
+    #[serde(default)]
+    pub mode: XMatchMode,
+    /// The radius in arcseconds
+    pub radius: f64,
+    /// The name of the column giving the error radii of the sources of the first
+    /// catalog in arcseconds
+    #[serde(default)]
+    pub error_column: Option<String>,
+    /// The name of the column giving the error radii of the sources of the second
+    /// catalog in arcseconds
+    #[serde(default)]
+    pub other_error_column: Option<String>,
+}
+
+fn default_color() -> ColorRGB {
+    ColorRGB {
+        r: 1.0,
+        g: 0.0,
+        b: 0.0,
+    }
+}
+
+fn default_selection_color() -> ColorRGB {
+    ColorRGB {
+        r: 0.0,
+        g: 1.0,
+        b: 0.0,
+    }
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
+
+fn default_size() -> f32 {
+    8.0
+}
+
+fn default_bandwidth() -> f32 {
+    8.0
 }
 
-fn default_color() -> Option<ColorRGB> {
-    None
+fn default_cell_size() -> f32 {
+    64.0
 }
 
-fn default_opacity() -> Option<f32> {
-    None
+fn default_max_order() -> u8 {
+    7
 }
 
-fn default_shape() -> Option<&'static str> {
-    None
+fn default_aggregate_sizes() -> [f32; 2] {
+    [12.0, 40.0]
 }
 
-fn default_size() -> Option<AngleSerializeFmt> {
-    None
+fn default_colormap() -> String {
+    String::from("viridis")
 }
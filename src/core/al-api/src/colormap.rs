@@ -7,3 +7,9 @@ impl AsRef<str> for CmapLabel {
         self.0.as_ref()
     }
 }
+
+impl From<&str> for CmapLabel {
+    fn from(label: &str) -> Self {
+        CmapLabel(label.to_string())
+    }
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+
+/// A grayscale layer mapped to one channel of a composite
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelCfg {
+    /// The FITS image or HiPS layer giving the channel values
+    pub layer: String,
+    /// Values mapped to 0 and 1 before the stretch.
+    /// The cuts of the source layer are taken if they are not given
+    #[serde(default)]
+    pub min_cut: Option<f32>,
+    #[serde(default)]
+    pub max_cut: Option<f32>,
+    /// Weight of the channel, to balance the colors
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+}
+
+/// A color composite of three grayscale layers
+///
+/// The channels are stretched jointly along their total intensity `I`
+/// following Lupton et al. (2004):
+/// `f(I) = asinh(Q * I / stretch) * 0.1 / asinh(0.1 * Q)`,
+/// so that the colors of the bright sources are preserved. The normalisation
+/// maps an intensity of `0.1 * stretch` to 0.1 whatever `Q`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RGBCompositeCfg {
+    /// Name of the layer of the composite
+    pub layer: String,
+
+    pub red: ChannelCfg,
+    pub green: ChannelCfg,
+    pub blue: ChannelCfg,
+
+    /// Softening parameter of the asinh stretch
+    #[serde(default = "default_q")]
+    pub q: f32,
+    /// Linear range of the stretch, in units of the normalized channels
+    #[serde(default = "default_stretch")]
+    pub stretch: f32,
+    #[serde(default = "default_opacity")]
+    pub opacity: f32,
+}
+
+impl RGBCompositeCfg {
+    pub fn channels(&self) -> [&ChannelCfg; 3] {
+        [&self.red, &self.green, &self.blue]
+    }
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn default_q() -> f32 {
+    8.0
+}
+
+fn default_stretch() -> f32 {
+    0.2
+}
+
+fn default_opacity() -> f32 {
+    1.0
+}
@@ -0,0 +1,65 @@
+use serde::{Deserialize, Serialize};
+
+use super::color::ColorRGBA;
+
+/// Values at which the contours are traced
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Levels {
+    /// An explicit list of values
+    Explicit { values: Vec<f64> },
+    /// `n` values evenly spaced between `min` and `max`.
+    /// The range of the data is taken if they are not given
+    Linear {
+        n: usize,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    /// `n` values logarithmically spaced between `min` and `max`.
+    /// The range of the strictly positive data is taken if they are not given
+    Log {
+        n: usize,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    /// Values lying at a number of standard deviations above the background
+    Sigma { values: Vec<f64> },
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContourCfg {
+    /// Unique name of the contour overlay
+    pub name: String,
+    /// The image or HiPS layer from which the contours are extracted
+    pub layer: String,
+    pub levels: Levels,
+    /// For HiPS layers, the order of the tiles on which the contours are
+    /// computed. The order of the view is taken if not given
+    #[serde(default)]
+    pub order: Option<u8>,
+
+    #[serde(default = "default_color")]
+    pub color: ColorRGBA,
+    #[serde(default = "default_thickness")]
+    pub thickness: f32,
+    #[serde(default = "default_show")]
+    pub show: bool,
+}
+
+fn default_color() -> ColorRGBA {
+    ColorRGBA {
+        r: 0.0,
+        g: 1.0,
+        b: 0.0,
+        a: 1.0,
+    }
+}
+
+fn default_thickness() -> f32 {
+    1.0
+}
+
+fn default_show() -> bool {
+    true
+}
@@ -1,3 +1,5 @@
+pub mod properties;
+
 use wasm_bindgen::JsValue;
 
 use super::blend::BlendCfg;
@@ -49,12 +51,24 @@ pub struct HiPSProperties {
     hips_initial_dec: Option<f64>,
 
     // Parametrable by the user
-    #[allow(unused)]
     min_cutout: Option<f32>,
-    #[allow(unused)]
     max_cutout: Option<f32>,
+    hips_data_range: Option<(f64, f64)>,
 
     creator_did: String,
+    // The mirrors serving the HiPS, given by hips_service_url and hips_service_url_N
+    hips_service_urls: Option<Vec<String>>,
+
+    dataproduct_type: Option<String>,
+    dataproduct_subtype: Option<Vec<String>>,
+
+    // HiPS cubes
+    hips_cube_depth: Option<u32>,
+    hips_cube_firstframe: Option<u32>,
+    hips_cube_crpix3: Option<f64>,
+    hips_cube_crval3: Option<f64>,
+    hips_cube_cdelt3: Option<f64>,
+    hips_cube_bunit3: Option<String>,
 }
 
 impl HiPSProperties {
@@ -117,6 +131,63 @@ impl HiPSProperties {
     pub fn get_initial_dec(&self) -> Option<f64> {
         self.hips_initial_dec
     }
+
+    /// The default cuts given by `hips_pixel_cut`
+    #[inline(always)]
+    pub fn get_cutouts(&self) -> Option<(f32, f32)> {
+        self.min_cutout.zip(self.max_cutout)
+    }
+
+    /// The range of the pixel values given by `hips_data_range`
+    #[inline(always)]
+    pub fn get_data_range(&self) -> Option<(f64, f64)> {
+        self.hips_data_range
+    }
+
+    /// The urls of all the mirrors serving the HiPS
+    #[inline(always)]
+    pub fn get_service_urls(&self) -> &[String] {
+        self.hips_service_urls.as_deref().unwrap_or(&[])
+    }
+
+    #[inline(always)]
+    pub fn get_dataproduct_type(&self) -> Option<&str> {
+        self.dataproduct_type.as_deref()
+    }
+
+    #[inline(always)]
+    pub fn get_dataproduct_subtype(&self) -> Option<&[String]> {
+        self.dataproduct_subtype.as_deref()
+    }
+
+    /// The number of frames of a HiPS cube, None if the HiPS is not a cube
+    #[inline(always)]
+    pub fn get_cube_depth(&self) -> Option<u32> {
+        self.hips_cube_depth.filter(|&depth| depth > 1)
+    }
+
+    /// The frame displayed first
+    #[inline(always)]
+    pub fn get_cube_first_frame(&self) -> u32 {
+        self.hips_cube_firstframe.unwrap_or(0)
+    }
+
+    /// The unit of the coordinate along the third axis of the cube
+    #[inline(always)]
+    pub fn get_cube_unit(&self) -> Option<&str> {
+        self.hips_cube_bunit3.as_deref()
+    }
+
+    /// The coordinate of a frame along the third axis of the cube
+    ///
+    /// The frames are the pixels of the axis, starting at 1 for the frame 0
+    pub fn get_cube_frame_coordinate(&self, frame: u32) -> Option<f64> {
+        let crval = self.hips_cube_crval3?;
+        let cdelt = self.hips_cube_cdelt3?;
+        let crpix = self.hips_cube_crpix3.unwrap_or(1.0);
+
+        Some(crval + (frame as f64 + 1.0 - crpix) * cdelt)
+    }
 }
 
 #[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
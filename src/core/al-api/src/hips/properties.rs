@@ -0,0 +1,571 @@
+//! Parser of the HiPS `properties` files
+//!
+//! The format is described in the IVOA HiPS recommendation (section 4.4). Each line is
+//! either a comment starting with `#`, a blank line or a `keyword = value` pair.
+//! Values may span several lines when a line ends with a backslash.
+use std::fmt;
+use std::str::FromStr;
+
+use wasm_bindgen::JsValue;
+
+use super::{HiPSProperties, ImageExt};
+use crate::coo_system::CooSystem;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Syntax {
+        line: usize,
+        content: String,
+    },
+    MissingKeyword {
+        keyword: &'static str,
+    },
+    InvalidValue {
+        keyword: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+    UnsupportedFrame {
+        frame: String,
+    },
+    NoImageFormat {
+        formats: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Syntax { line, content } => write!(
+                f,
+                "Line {} of the properties is not a `keyword = value` pair: {:?}",
+                line, content
+            ),
+            Error::MissingKeyword { keyword } => {
+                write!(
+                    f,
+                    "Mandatory keyword {} not found in the properties",
+                    keyword
+                )
+            }
+            Error::InvalidValue {
+                keyword,
+                value,
+                expected,
+            } => write!(
+                f,
+                "Invalid value {:?} for the keyword {}: expected {}",
+                value, keyword, expected
+            ),
+            Error::UnsupportedFrame { frame } => {
+                write!(f, "HiPS frame {:?} is not supported", frame)
+            }
+            Error::NoImageFormat { formats } => {
+                write!(f, "No image tile format among {:?}", formats)
+            }
+        }
+    }
+}
+
+impl From<Error> for JsValue {
+    fn from(err: Error) -> Self {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+/// The keyword/value pairs of a `properties` file in their order of appearance
+#[derive(Debug, Clone, Default)]
+pub struct Properties {
+    entries: Vec<(String, String)>,
+}
+
+impl Properties {
+    pub fn parse(text: &str) -> Result<Self, Error> {
+        let mut entries = vec![];
+
+        let mut lines = text.lines().enumerate();
+        while let Some((idx, line)) = lines.next() {
+            let mut line = line.trim().to_string();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // Join the continuation lines
+            while ends_with_continuation(&line) {
+                line.pop();
+                if let Some((_, next)) = lines.next() {
+                    line.push_str(next.trim_start());
+                } else {
+                    break;
+                }
+            }
+
+            let (keyword, value) = line.split_once('=').ok_or_else(|| Error::Syntax {
+                line: idx + 1,
+                content: line.clone(),
+            })?;
+            let keyword = keyword.trim();
+            if keyword.is_empty() || keyword.contains(char::is_whitespace) {
+                return Err(Error::Syntax {
+                    line: idx + 1,
+                    content: line.clone(),
+                });
+            }
+
+            entries.push((keyword.to_string(), unescape(value.trim())));
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// Get the value of a keyword. If it is given several times, the last one is returned
+    pub fn get(&self, keyword: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(k, _)| k == keyword)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Set the value of a keyword, replacing the ones given by the file
+    pub fn set(&mut self, keyword: &str, value: String) {
+        self.entries.push((keyword.to_string(), value));
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    fn parse_value<T: FromStr>(
+        &self,
+        keyword: &'static str,
+        expected: &'static str,
+    ) -> Result<Option<T>, Error> {
+        self.get(keyword)
+            .map(|value| {
+                value.parse::<T>().map_err(|_| Error::InvalidValue {
+                    keyword,
+                    value: value.to_string(),
+                    expected,
+                })
+            })
+            .transpose()
+    }
+
+    fn parse_range(&self, keyword: &'static str) -> Result<Option<(f64, f64)>, Error> {
+        self.get(keyword)
+            .map(|value| {
+                let err = || Error::InvalidValue {
+                    keyword,
+                    value: value.to_string(),
+                    expected: "two numbers",
+                };
+
+                let mut bounds = value.split_whitespace().map(|v| v.parse::<f64>());
+                match (bounds.next(), bounds.next(), bounds.next()) {
+                    (Some(Ok(min)), Some(Ok(max)), None) => Ok((min, max)),
+                    _ => Err(err()),
+                }
+            })
+            .transpose()
+    }
+}
+
+// A line ending with an odd number of backslashes continues on the next line
+fn ends_with_continuation(line: &str) -> bool {
+    line.chars().rev().take_while(|&c| c == '\\').count() % 2 == 1
+}
+
+// Only the escapes producing a control character are replaced so that
+// paths written in the hipsgen parameters are kept untouched
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.peek() {
+                Some('n') => unescaped.push('\n'),
+                Some('t') => unescaped.push('\t'),
+                Some('r') => unescaped.push('\r'),
+                Some('\\') => unescaped.push('\\'),
+                _ => {
+                    unescaped.push(c);
+                    continue;
+                }
+            }
+            chars.next();
+        } else {
+            unescaped.push(c);
+        }
+    }
+
+    unescaped
+}
+
+fn parse_frame(frame: &str) -> Result<CooSystem, Error> {
+    match frame.to_lowercase().as_str() {
+        "equatorial" | "icrs" | "icrsd" | "j2000" | "j2000d" | "fk5" | "c" => Ok(CooSystem::ICRS),
+        "galactic" | "gal" | "g" => Ok(CooSystem::GAL),
+        _ => Err(Error::UnsupportedFrame {
+            frame: frame.to_string(),
+        }),
+    }
+}
+
+fn parse_format(format: &str) -> Option<ImageExt> {
+    match format.to_lowercase().as_str() {
+        "jpeg" | "jpg" => Some(ImageExt::Jpeg),
+        "png" => Some(ImageExt::Png),
+        "fits" => Some(ImageExt::Fits),
+        "webp" => Some(ImageExt::Webp),
+        // e.g. tsv for catalogue HiPS
+        _ => None,
+    }
+}
+
+impl HiPSProperties {
+    /// Build the HiPS properties from the content of its `properties` file
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The root url the HiPS tiles are retrieved from
+    /// * `properties` - The parsed `properties` file
+    pub fn from_properties(url: &str, properties: &Properties) -> Result<Self, Error> {
+        let max_order = properties
+            .parse_value::<u8>("hips_order", "an order between 0 and 29")?
+            .ok_or(Error::MissingKeyword {
+                keyword: "hips_order",
+            })?;
+        if max_order > 29 {
+            return Err(Error::InvalidValue {
+                keyword: "hips_order",
+                value: max_order.to_string(),
+                expected: "an order between 0 and 29",
+            });
+        }
+
+        let min_order = properties.parse_value::<u8>("hips_order_min", "an order")?;
+        if let Some(min_order) = min_order {
+            if min_order > max_order {
+                return Err(Error::InvalidValue {
+                    keyword: "hips_order_min",
+                    value: min_order.to_string(),
+                    expected: "an order lower than hips_order",
+                });
+            }
+        }
+
+        // Planetary HiPS are defined in their body frame, which is rendered as an equatorial one
+        let is_planetary_body = properties.get("hips_body").is_some();
+        let coo_frame = if is_planetary_body {
+            CooSystem::ICRS
+        } else {
+            let frame = properties.get("hips_frame").ok_or(Error::MissingKeyword {
+                keyword: "hips_frame",
+            })?;
+            parse_frame(frame)?
+        };
+
+        let tile_size = properties
+            .parse_value::<i32>("hips_tile_width", "a power of two")?
+            .unwrap_or(512);
+        if tile_size <= 0 || tile_size & (tile_size - 1) != 0 {
+            return Err(Error::InvalidValue {
+                keyword: "hips_tile_width",
+                value: tile_size.to_string(),
+                expected: "a power of two",
+            });
+        }
+
+        let tile_formats = properties.get("hips_tile_format").unwrap_or("jpeg");
+        let formats = tile_formats
+            .split_whitespace()
+            .filter_map(parse_format)
+            .collect::<Vec<_>>();
+        if formats.is_empty() {
+            return Err(Error::NoImageFormat {
+                formats: tile_formats.to_string(),
+            });
+        }
+
+        let bitpix = properties.parse_value::<i32>("hips_pixel_bitpix", "a FITS bitpix")?;
+        if let Some(bitpix) = bitpix {
+            if ![8, 16, 32, 64, -32, -64].contains(&bitpix) {
+                return Err(Error::InvalidValue {
+                    keyword: "hips_pixel_bitpix",
+                    value: bitpix.to_string(),
+                    expected: "a FITS bitpix",
+                });
+            }
+        }
+
+        let sky_fraction =
+            properties.parse_value::<f32>("moc_sky_fraction", "a fraction between 0 and 1")?;
+
+        let hips_initial_fov = properties.parse_value::<f64>("hips_initial_fov", "degrees")?;
+        let hips_initial_ra = properties.parse_value::<f64>("hips_initial_ra", "degrees")?;
+        let hips_initial_dec = properties.parse_value::<f64>("hips_initial_dec", "degrees")?;
+
+        let cuts = properties.parse_range("hips_pixel_cut")?;
+        let hips_data_range = properties.parse_range("hips_data_range")?;
+
+        let creator_did = properties
+            .get("creator_did")
+            .or_else(|| properties.get("publisher_did"))
+            .unwrap_or(url)
+            .to_string();
+
+        // The mirrors are numbered from 1 after the main hips_service_url
+        let hips_service_urls = properties
+            .get("hips_service_url")
+            .into_iter()
+            .chain(
+                (1..)
+                    .map(|n| properties.get(&format!("hips_service_url_{}", n)))
+                    .take_while(Option::is_some)
+                    .flatten(),
+            )
+            .map(String::from)
+            .collect::<Vec<_>>();
+
+        let dataproduct_type = properties.get("dataproduct_type").map(String::from);
+        let dataproduct_subtype = properties
+            .get("dataproduct_subtype")
+            .map(|subtypes| subtypes.split_whitespace().map(String::from).collect());
+
+        let hips_cube_depth = properties.parse_value::<u32>("hips_cube_depth", "an integer")?;
+        let hips_cube_firstframe =
+            properties.parse_value::<u32>("hips_cube_firstframe", "an integer")?;
+        let hips_cube_crpix3 = properties.parse_value::<f64>("hips_cube_crpix3", "a number")?;
+        let hips_cube_crval3 = properties.parse_value::<f64>("hips_cube_crval3", "a number")?;
+        let hips_cube_cdelt3 = properties.parse_value::<f64>("hips_cube_cdelt3", "a number")?;
+        let hips_cube_bunit3 = properties.get("hips_cube_bunit3").map(String::from);
+
+        Ok(HiPSProperties {
+            url: url.to_string(),
+            max_order,
+            coo_frame,
+            tile_size,
+            formats,
+            is_planetary_body: Some(is_planetary_body),
+            bitpix,
+            sky_fraction,
+            min_order,
+            hips_initial_fov,
+            hips_initial_ra,
+            hips_initial_dec,
+            min_cutout: cuts.map(|(min, _)| min as f32),
+            max_cutout: cuts.map(|(_, max)| max as f32),
+            hips_data_range,
+            creator_did,
+            hips_service_urls: Some(hips_service_urls),
+            dataproduct_type,
+            dataproduct_subtype,
+            hips_cube_depth,
+            hips_cube_firstframe,
+            hips_cube_crpix3,
+            hips_cube_crval3,
+            hips_cube_cdelt3,
+            hips_cube_bunit3,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Properties};
+    use crate::coo_system::CooSystem;
+    use crate::hips::{HiPSProperties, ImageExt};
+
+    const CORPUS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/properties");
+
+    fn read_corpus(name: &str) -> HiPSProperties {
+        let text = std::fs::read_to_string(format!("{}/{}", CORPUS_DIR, name)).unwrap();
+        let properties = Properties::parse(&text).unwrap();
+
+        HiPSProperties::from_properties("http://hips", &properties).unwrap()
+    }
+
+    #[test]
+    fn corpus() {
+        let mut num_files = 0;
+        for entry in std::fs::read_dir(CORPUS_DIR).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension() != Some("properties".as_ref()) {
+                continue;
+            }
+
+            let text = std::fs::read_to_string(&path).unwrap();
+            let properties =
+                Properties::parse(&text).unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+            HiPSProperties::from_properties("http://hips", &properties)
+                .unwrap_or_else(|e| panic!("{}: {}", path.display(), e));
+
+            num_files += 1;
+        }
+
+        assert!(num_files >= 8);
+    }
+
+    #[test]
+    fn color_survey() {
+        let p = read_corpus("CDS_P_DSS2_color.properties");
+
+        assert_eq!(p.get_creator_did(), "ivo://CDS/P/DSS2/color");
+        assert_eq!(p.get_max_order(), 7);
+        assert_eq!(p.get_min_order(), Some(0));
+        assert_eq!(p.get_frame(), CooSystem::ICRS);
+        assert_eq!(p.get_tile_size(), 512);
+        assert_eq!(p.get_formats(), &[ImageExt::Jpeg]);
+        assert_eq!(p.get_initial_ra(), Some(85.30251));
+        assert_eq!(p.get_initial_dec(), Some(-2.25468));
+        assert_eq!(
+            p.get_dataproduct_subtype(),
+            Some(&["color".to_string()][..])
+        );
+        assert!(p.get_cutouts().is_none());
+    }
+
+    #[test]
+    fn fits_survey() {
+        let p = read_corpus("CDS_P_2MASS_K.properties");
+
+        assert_eq!(
+            p.get_formats(),
+            &[ImageExt::Jpeg, ImageExt::Fits, ImageExt::Png]
+        );
+        assert_eq!(p.get_bitpix(), Some(-32));
+        assert_eq!(p.get_cutouts(), Some((-0.5, 2.0)));
+        assert_eq!(p.get_data_range(), Some((-6.0, 450.0)));
+        assert_eq!(
+            p.get_service_urls(),
+            &[
+                "https://alasky.cds.unistra.fr/2MASS/K".to_string(),
+                "https://alaskybis.cds.unistra.fr/2MASS/K".to_string(),
+                "https://alasky.u-strasbg.fr/2MASS/K".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn galactic_survey() {
+        let p = read_corpus("CDS_P_Fermi_color.properties");
+
+        assert_eq!(p.get_frame(), CooSystem::GAL);
+        assert_eq!(p.get_max_order(), 3);
+        assert_eq!(p.get_tile_size(), 512);
+    }
+
+    #[test]
+    fn cube() {
+        let p = read_corpus("CDS_P_HI4PI_cube.properties");
+
+        assert_eq!(p.get_cube_depth(), Some(933));
+        assert_eq!(p.get_cube_first_frame(), 466);
+        assert_eq!(p.get_cube_unit(), Some("km/s"));
+        assert_eq!(p.get_cube_frame_coordinate(0), Some(-600.0));
+    }
+
+    #[test]
+    fn planetary_body() {
+        let p = read_corpus("CDS_P_Mars_MOLA.properties");
+
+        assert_eq!(p.get_frame(), CooSystem::ICRS);
+        assert_eq!(p.get_formats(), &[ImageExt::Jpeg, ImageExt::Png]);
+    }
+
+    #[test]
+    fn escapes_and_continuation() {
+        let text = "obs_description = first\\nsecond \\\n  third\n\
+                    hipsgen_params = in=C:\\data\\images\n";
+        let properties = Properties::parse(text).unwrap();
+
+        assert_eq!(
+            properties.get("obs_description"),
+            Some("first\nsecond third")
+        );
+        assert_eq!(
+            properties.get("hipsgen_params"),
+            Some("in=C:\\data\\images")
+        );
+    }
+
+    #[test]
+    fn frame_aliases() {
+        for (frame, coo_sys) in [
+            ("equatorial", CooSystem::ICRS),
+            ("ICRS", CooSystem::ICRS),
+            ("ICRSd", CooSystem::ICRS),
+            ("galactic", CooSystem::GAL),
+            ("GAL", CooSystem::GAL),
+        ] {
+            let text = format!("hips_order = 3\nhips_frame = {}\n", frame);
+            let properties = Properties::parse(&text).unwrap();
+            let p = HiPSProperties::from_properties("http://hips", &properties).unwrap();
+
+            assert_eq!(p.get_frame(), coo_sys);
+            // The url identifies the HiPS when it has no creator_did
+            assert_eq!(p.get_creator_did(), "http://hips");
+        }
+    }
+
+    #[test]
+    fn set_keyword() {
+        let text = "hips_order = 3\nhips_frame = equatorial\ncreator_did = ivo://CDS/P/A\n";
+        let mut properties = Properties::parse(text).unwrap();
+        properties.set("creator_did", "ivo://CDS/P/B".to_string());
+        let p = HiPSProperties::from_properties("http://hips", &properties).unwrap();
+
+        assert_eq!(p.get_creator_did(), "ivo://CDS/P/B");
+    }
+
+    #[test]
+    fn errors() {
+        let parse = |text: &str| {
+            let properties = Properties::parse(text)?;
+            HiPSProperties::from_properties("http://hips", &properties)
+        };
+
+        assert_eq!(
+            parse("# comment\nhips_order = 3\nhips_frame equatorial\n").unwrap_err(),
+            Error::Syntax {
+                line: 3,
+                content: "hips_frame equatorial".to_string()
+            }
+        );
+        assert_eq!(
+            parse("hips_frame = equatorial\n").unwrap_err(),
+            Error::MissingKeyword {
+                keyword: "hips_order"
+            }
+        );
+        assert_eq!(
+            parse("hips_order = 3\nhips_frame = ecliptic\n").unwrap_err(),
+            Error::UnsupportedFrame {
+                frame: "ecliptic".to_string()
+            }
+        );
+        assert_eq!(
+            parse("hips_order = 3\nhips_frame = galactic\nhips_tile_width = 300\n").unwrap_err(),
+            Error::InvalidValue {
+                keyword: "hips_tile_width",
+                value: "300".to_string(),
+                expected: "a power of two"
+            }
+        );
+        assert_eq!(
+            parse("hips_order = 3\nhips_frame = galactic\nhips_tile_format = tsv\n").unwrap_err(),
+            Error::NoImageFormat {
+                formats: "tsv".to_string()
+            }
+        );
+        assert!(matches!(
+            parse("hips_order = 3\nhips_frame = galactic\nhips_pixel_cut = 10\n"),
+            Err(Error::InvalidValue {
+                keyword: "hips_pixel_cut",
+                ..
+            })
+        ));
+    }
+}
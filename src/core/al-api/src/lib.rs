@@ -3,8 +3,11 @@
    the WASM core of aladin lite v3
 */
 pub mod blend;
+pub mod catalog;
 pub mod color;
 pub mod colormap;
+pub mod composite;
+pub mod contour;
 pub mod coo_system;
 pub mod grid;
 pub mod hips;
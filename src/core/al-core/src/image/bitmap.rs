@@ -18,6 +18,12 @@ where
             format: std::marker::PhantomData,
         }
     }
+
+    /// The size in bytes of the decoded image
+    pub fn byte_size(&self) -> usize {
+        let num_pixels = (self.image.width() * self.image.height()) as usize;
+        num_pixels * std::mem::size_of::<F::P>()
+    }
 }
 use wasm_bindgen::JsValue;
 use crate::texture::Texture2DArray;
@@ -15,6 +15,12 @@ where
             format: std::marker::PhantomData,
         }
     }
+
+    /// The size in bytes of the decoded image
+    pub fn byte_size(&self) -> usize {
+        let num_pixels = (self.image.natural_width() * self.image.natural_height()) as usize;
+        num_pixels * std::mem::size_of::<F::P>()
+    }
 }
 
 use cgmath::Vector3;
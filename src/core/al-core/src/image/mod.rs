@@ -325,3 +325,25 @@ impl Image for ImageType {
         Ok(())
     }
 }
+
+impl ImageType {
+    /// The size in bytes of the image kept in memory
+    ///
+    /// FITS tiles are stored undecoded, their size is the one of the raw file
+    pub fn byte_size(&self) -> usize {
+        match self {
+            ImageType::FitsImage { raw_bytes } => raw_bytes.length() as usize,
+            ImageType::Canvas { canvas } => canvas.byte_size(),
+            ImageType::ImageRgba8u { image } => image.byte_size(),
+            ImageType::ImageRgb8u { image } => image.byte_size(),
+            ImageType::HTMLImageRgba8u { image } => image.byte_size(),
+            ImageType::HTMLImageRgb8u { image } => image.byte_size(),
+            ImageType::RawRgb8u { image } => image.byte_size(),
+            ImageType::RawRgba8u { image } => image.byte_size(),
+            ImageType::RawR32f { image } => image.byte_size(),
+            ImageType::RawR32i { image } => image.byte_size(),
+            ImageType::RawR16i { image } => image.byte_size(),
+            ImageType::RawR8ui { image } => image.byte_size(),
+        }
+    }
+}
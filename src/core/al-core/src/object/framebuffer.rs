@@ -6,53 +6,100 @@ pub struct FrameBufferObject {
     gl: WebGlContext,
     fbo: WebGlFramebuffer,
     pub texture: Texture2D,
+    // The 32 bits float texture has been replaced by a 16 bits one
+    half_float: bool,
 }
 use crate::webgl_ctx::WebGlContext;
+use crate::image::format::ImageFormat;
 use crate::texture::Texture2D;
 
+const TEX_PARAMS: &[(u32, u32)] = &[
+    (
+        WebGlRenderingCtx::TEXTURE_MIN_FILTER,
+        WebGlRenderingCtx::LINEAR,
+    ),
+    (
+        WebGlRenderingCtx::TEXTURE_MAG_FILTER,
+        WebGlRenderingCtx::LINEAR,
+    ),
+    // Prevents s-coordinate wrapping (repeating)
+    (
+        WebGlRenderingCtx::TEXTURE_WRAP_S,
+        WebGlRenderingCtx::CLAMP_TO_EDGE,
+    ),
+    // Prevents t-coordinate wrapping (repeating)
+    (
+        WebGlRenderingCtx::TEXTURE_WRAP_T,
+        WebGlRenderingCtx::CLAMP_TO_EDGE,
+    ),
+];
+
 impl FrameBufferObject {
     pub fn new(gl: &WebGlContext, width: usize, height: usize) -> Result<Self, JsValue> {
+        Self::new_with_format::<crate::image::format::RGBA8U>(gl, width, height, TEX_PARAMS)
+    }
+
+    /// Create a framebuffer rendering onto a texture of a given format
+    ///
+    /// The float formats need the EXT_color_buffer_float extension. Without the
+    /// EXT_float_blend extension, the 32 bits float textures are replaced by 16 bits
+    /// float ones so that the framebuffer can still be blended onto.
+    pub fn new_with_format<F: ImageFormat>(
+        gl: &WebGlContext,
+        width: usize,
+        height: usize,
+        tex_params: &'static [(u32, u32)],
+    ) -> Result<Self, JsValue> {
         let fbo = gl
             .create_framebuffer()
             .ok_or("failed to create framebuffer")?;
         gl.bind_framebuffer(WebGlRenderingCtx::FRAMEBUFFER, Some(&fbo));
 
-        let texture = Texture2D::create_empty_with_format::<crate::image::format::RGBA8U>(
+        #[cfg(feature = "webgl2")]
+        let internal_format = if gl.float_blend {
+            F::INTERNAL_FORMAT
+        } else {
+            half_float_format(F::INTERNAL_FORMAT)
+        };
+        #[cfg(feature = "webgl1")]
+        let internal_format = F::INTERNAL_FORMAT;
+        let half_float = internal_format != F::INTERNAL_FORMAT;
+
+        let texture = Texture2D::create_empty_with_internal_format(
             gl,
             width as i32,
             height as i32,
-            &[
-                (
-                    WebGlRenderingCtx::TEXTURE_MIN_FILTER,
-                    WebGlRenderingCtx::LINEAR,
-                ),
-                (
-                    WebGlRenderingCtx::TEXTURE_MAG_FILTER,
-                    WebGlRenderingCtx::LINEAR,
-                ),
-                // Prevents s-coordinate wrapping (repeating)
-                (
-                    WebGlRenderingCtx::TEXTURE_WRAP_S,
-                    WebGlRenderingCtx::CLAMP_TO_EDGE,
-                ),
-                // Prevents t-coordinate wrapping (repeating)
-                (
-                    WebGlRenderingCtx::TEXTURE_WRAP_T,
-                    WebGlRenderingCtx::CLAMP_TO_EDGE,
-                ),
-            ],
+            tex_params,
+            internal_format,
+            F::FORMAT,
+            F::TYPE,
         )?;
         texture.attach_to_framebuffer();
 
+        let status = gl.check_framebuffer_status(WebGlRenderingCtx::FRAMEBUFFER);
         gl.bind_framebuffer(WebGlRenderingCtx::FRAMEBUFFER, None);
+        // e.g. a float texture without the EXT_color_buffer_float extension
+        if status != WebGlRenderingCtx::FRAMEBUFFER_COMPLETE {
+            gl.delete_framebuffer(Some(&fbo));
+            return Err(JsValue::from_str(&format!(
+                "Cannot render onto a texture of internal format {:#x}",
+                internal_format
+            )));
+        }
 
         Ok(Self {
             gl: gl.clone(),
             texture,
             fbo,
+            half_float,
         })
     }
 
+    /// Whether the texture stores 16 bits floats instead of the 32 bits ones asked for
+    pub fn is_half_float(&self) -> bool {
+        self.half_float
+    }
+
     pub fn resize(&mut self, width: usize, height: usize) {
         if (width, height)
             != (
@@ -124,6 +171,16 @@ impl FrameBufferObject {
     }
 }
 
+// The 16 bits float format of a 32 bits float format
+#[cfg(feature = "webgl2")]
+fn half_float_format(internal_format: i32) -> i32 {
+    match internal_format as u32 {
+        WebGlRenderingCtx::RGBA32F => WebGlRenderingCtx::RGBA16F as i32,
+        WebGlRenderingCtx::R32F => WebGlRenderingCtx::R16F as i32,
+        _ => internal_format,
+    }
+}
+
 impl Drop for FrameBufferObject {
     fn drop(&mut self) {
         self.gl.delete_framebuffer(Some(&self.fbo));
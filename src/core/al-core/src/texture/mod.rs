@@ -255,6 +255,28 @@ impl Texture2D {
         width: i32,
         height: i32,
         tex_params: &'static [(u32, u32)],
+    ) -> Result<Texture2D, JsValue> {
+        Self::create_empty_with_internal_format(
+            gl,
+            width,
+            height,
+            tex_params,
+            F::INTERNAL_FORMAT,
+            F::FORMAT,
+            F::TYPE,
+        )
+    }
+
+    /// Create an empty texture whose internal format differs from the one of its format,
+    /// e.g. a 16 bits float texture
+    pub fn create_empty_with_internal_format(
+        gl: &WebGlContext,
+        width: i32,
+        height: i32,
+        tex_params: &'static [(u32, u32)],
+        internal_format: i32,
+        format: u32,
+        type_: u32,
     ) -> Result<Texture2D, JsValue> {
         let texture = gl.create_texture();
 
@@ -267,12 +289,12 @@ impl Texture2D {
         gl.tex_image_2d_with_i32_and_i32_and_i32_and_format_and_type_and_opt_u8_array(
             WebGlRenderingCtx::TEXTURE_2D,
             0,
-            F::INTERNAL_FORMAT,
+            internal_format,
             width,
             height,
             0,
-            F::FORMAT,
-            F::TYPE,
+            format,
+            type_,
             None,
         )
         .expect("Texture 2D");
@@ -281,9 +303,9 @@ impl Texture2D {
         let metadata = Some(Rc::new(RefCell::new(Texture2DMeta {
             width: width as u32,
             height: height as u32,
-            internal_format: F::INTERNAL_FORMAT,
-            format: F::FORMAT,
-            type_: F::TYPE,
+            internal_format,
+            format,
+            type_,
         })));
         Ok(Texture2D {
             texture,
@@ -439,6 +461,118 @@ impl Texture2D {
             value
         }
     }
+
+    /// Read back a region of the texture with a single `readPixels`
+    ///
+    /// The values are given row by row, the channels of a pixel being interleaved
+    pub fn read_pixels(
+        &self,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    ) -> Result<Vec<f64>, JsValue> {
+        let reader = self.gl.create_framebuffer();
+        self.gl
+            .bind_framebuffer(WebGlRenderingCtx::FRAMEBUFFER, reader.as_ref());
+        self.gl.framebuffer_texture_2d(
+            WebGlRenderingCtx::READ_FRAMEBUFFER,
+            WebGlRenderingCtx::COLOR_ATTACHMENT0,
+            WebGlRenderingCtx::TEXTURE_2D,
+            self.texture.as_ref(),
+            0,
+        );
+
+        let status = self
+            .gl
+            .check_framebuffer_status(WebGlRenderingCtx::FRAMEBUFFER);
+        let values = if status != WebGlRenderingCtx::FRAMEBUFFER_COMPLETE {
+            Err(JsValue::from_str("incomplete framebuffer"))
+        } else {
+            let metadata = self.metadata.as_ref().unwrap_abort().borrow();
+            let num_pixels = (width * height) as u32;
+            let read = |format: u32, type_: u32, pixels: &js_sys::Object| {
+                self.gl.read_pixels_with_opt_array_buffer_view(
+                    x,
+                    y,
+                    width,
+                    height,
+                    format,
+                    type_,
+                    Some(pixels),
+                )
+            };
+
+            #[cfg(feature = "webgl2")]
+            let values = match (metadata.format, metadata.type_) {
+                (WebGlRenderingCtx::RED_INTEGER, WebGlRenderingCtx::UNSIGNED_BYTE) => {
+                    let p = js_sys::Uint8Array::new_with_length(num_pixels);
+                    read(metadata.format, metadata.type_, &p)
+                        .map(|_| p.to_vec().into_iter().map(f64::from).collect())
+                }
+                (WebGlRenderingCtx::RED_INTEGER, WebGlRenderingCtx::SHORT) => {
+                    let p = js_sys::Int16Array::new_with_length(num_pixels);
+                    read(metadata.format, metadata.type_, &p)
+                        .map(|_| p.to_vec().into_iter().map(f64::from).collect())
+                }
+                (WebGlRenderingCtx::RED_INTEGER, WebGlRenderingCtx::INT) => {
+                    let p = js_sys::Int32Array::new_with_length(num_pixels);
+                    read(metadata.format, metadata.type_, &p)
+                        .map(|_| p.to_vec().into_iter().map(f64::from).collect())
+                }
+                (WebGlRenderingCtx::RED, WebGlRenderingCtx::FLOAT) => {
+                    let p = js_sys::Float32Array::new_with_length(num_pixels);
+                    read(metadata.format, metadata.type_, &p)
+                        .map(|_| p.to_vec().into_iter().map(f64::from).collect())
+                }
+                (WebGlRenderingCtx::RGB, WebGlRenderingCtx::UNSIGNED_BYTE) => {
+                    let p = js_sys::Uint8Array::new_with_length(3 * num_pixels);
+                    read(metadata.format, metadata.type_, &p)
+                        .map(|_| p.to_vec().into_iter().map(f64::from).collect())
+                }
+                (WebGlRenderingCtx::RGBA, WebGlRenderingCtx::UNSIGNED_BYTE) => {
+                    let p = js_sys::Uint8Array::new_with_length(4 * num_pixels);
+                    read(metadata.format, metadata.type_, &p)
+                        .map(|_| p.to_vec().into_iter().map(f64::from).collect())
+                }
+                _ => Err(JsValue::from_str(
+                    "Pixel retrieval not implemented for that texture format.",
+                )),
+            };
+            #[cfg(feature = "webgl1")]
+            let values = match (metadata.format, metadata.type_) {
+                (WebGlRenderingCtx::LUMINANCE_ALPHA, WebGlRenderingCtx::FLOAT) => {
+                    let p = js_sys::Float32Array::new_with_length(2 * num_pixels);
+                    read(metadata.format, metadata.type_, &p).map(|_| {
+                        // The luminance of each pixel
+                        p.to_vec().into_iter().step_by(2).map(f64::from).collect()
+                    })
+                }
+                (WebGlRenderingCtx::RGB, WebGlRenderingCtx::UNSIGNED_BYTE) => {
+                    let p = js_sys::Uint8Array::new_with_length(3 * num_pixels);
+                    read(metadata.format, metadata.type_, &p)
+                        .map(|_| p.to_vec().into_iter().map(f64::from).collect())
+                }
+                (WebGlRenderingCtx::RGBA, WebGlRenderingCtx::UNSIGNED_BYTE) => {
+                    let p = js_sys::Uint8Array::new_with_length(4 * num_pixels);
+                    read(metadata.format, metadata.type_, &p)
+                        .map(|_| p.to_vec().into_iter().map(f64::from).collect())
+                }
+                _ => Err(JsValue::from_str(
+                    "Pixel retrieval not implemented for that texture format.",
+                )),
+            };
+
+            values
+        };
+
+        // Unbind and delete the framebuffer, readPixels does not depend on the viewport
+        self.gl
+            .bind_framebuffer(WebGlRenderingCtx::FRAMEBUFFER, None);
+        self.gl.delete_framebuffer(reader.as_ref());
+
+        values
+    }
 }
 
 impl Drop for Texture2D {
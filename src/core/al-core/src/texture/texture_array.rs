@@ -36,7 +36,8 @@ impl Texture2DArray {
     }
 }
 
-const TEX_UNIFORMS_NAME: &[&str] = &["tex1", "tex2", "tex3", "tex4", "tex5"];
+// The samplers of the slices declared in the HiPS shaders
+const TEX_UNIFORMS_NAME: &[&str] = &["tex1", "tex2", "tex3", "tex4"];
 
 use crate::shader::{SendUniforms, ShaderBound};
 impl SendUniforms for Texture2DArray {
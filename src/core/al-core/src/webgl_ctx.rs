@@ -13,6 +13,10 @@ pub type WebGlRenderingCtx = web_sys::WebGlRenderingContext;
 pub struct WebGlContext {
     inner: Rc<WebGlRenderingCtx>,
 
+    // Whether the 32 bits float textures can be blended onto (EXT_float_blend)
+    #[cfg(feature = "webgl2")]
+    pub float_blend: bool,
+
     #[cfg(feature = "webgl1")]
     pub ext: WebGlExt,
 }
@@ -59,8 +63,14 @@ impl WebGlContext {
             {
                 let _ = r;
             }
+            // Blending onto 32 bits float textures, e.g. to accumulate the heatmaps.
+            // The framebuffers render onto 16 bits float textures otherwise
+            let float_blend = gl.get_extension("EXT_float_blend").ok().flatten().is_some();
 
-            let ctx = WebGlContext { inner: gl };
+            let ctx = WebGlContext {
+                inner: gl,
+                float_blend,
+            };
             Ok(ctx)
         }
 
@@ -1,7 +1,12 @@
+use crate::renderable::image::{wcs_keywords::WCSKeywords, Image};
 use crate::renderable::ImageLayer;
-use crate::tile_fetcher::HiPSLocalFiles;
+use crate::lru_cache::CacheStats;
+use crate::tile_fetcher::{HiPSLocalFiles, TileFetcherMetrics};
 use crate::{
-    //async_task::{BuildCatalogIndex, ParseTableTask, TaskExecutor, TaskResult, TaskType},
+    async_task::{
+        get_columns, CatalogEvent, Coordinates, CrossMatchTask, HiPSGenTask, ParseTableTask,
+        TaskExecutor, TaskResult, TaskType,
+    },
     camera::CameraViewPort,
     downloader::Downloader,
     healpix::coverage::HEALPixCoverage,
@@ -13,12 +18,18 @@ use crate::{
     },
     renderable::grid::ProjetedGrid,
     renderable::Layers,
-    renderable::{catalog::Manager, moc::MOCRenderer},
+    renderable::{
+        catalog::{xmatch::Matches, HiPSCatalog, Manager, Source},
+        contour::ContourRenderer,
+        moc::MOCRenderer,
+    },
+    renderable::{line::RasterizedLineRenderer, Renderer},
+    reproject::{self, Sampler, TargetWCS},
     shader::ShaderManager,
+    table::{self, Table},
     tile_fetcher::TileFetcherQueue,
     time::DeltaTime,
 };
-use wcs::WCS;
 
 use wasm_bindgen::prelude::*;
 
@@ -28,7 +39,11 @@ use al_core::WebGlContext;
 use super::coosys;
 use crate::Abort;
 use al_api::{
+    catalog::{Catalog as CatalogCfg, XMatch as XMatchCfg},
+    composite::RGBCompositeCfg,
+    contour::ContourCfg,
     coo_system::CooSystem,
+    fov::CenteredFoV,
     grid::GridCfg,
     hips::{HiPSCfg, ImageMetadata},
 };
@@ -37,16 +52,38 @@ use fitsrs::{fits::AsyncFits, hdu::extension::AsyncXtensionHDU};
 
 use web_sys::{HtmlElement, WebGl2RenderingContext};
 
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::rc::Rc;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use crate::renderable::final_pass::RenderPass;
 use al_core::FrameBufferObject;
 
 use al_api::image::ImageParams;
 
+// A HiPS being generated in the background
+struct HiPSGeneration {
+    progress: Rc<Cell<f32>>,
+    // The progress last reported
+    reported: f32,
+    on_progress: Option<js_sys::Function>,
+    // The functions settling the promise returned to the javascript
+    resolve: js_sys::Function,
+    reject: js_sys::Function,
+}
+
+// A cross-match of two catalogs computed in the background
+struct CrossMatching {
+    // The names of the catalogs matched
+    names: (String, String),
+    // The name of the catalog of the pairs, if they are added as a catalog
+    matches_name: Option<String>,
+    // The functions settling the promise returned to the javascript
+    resolve: js_sys::Function,
+    reject: js_sys::Function,
+}
+
 pub struct App {
     pub gl: WebGlContext,
 
@@ -66,11 +103,23 @@ pub struct App {
     grid: ProjetedGrid,
     // The moc renderable
     moc: MOCRenderer,
+    // The contour overlays
+    contours: ContourRenderer,
     // Catalog manager
     manager: Manager,
 
     // Task executor
-    //exec: Rc<RefCell<TaskExecutor>>,
+    exec: Rc<RefCell<TaskExecutor>>,
+    // The catalogs whose table is being parsed, with the progress last reported
+    catalogs_loading: HashMap<String, (Rc<Cell<f32>>, f32)>,
+    // The catalog events not yet retrieved by the javascript
+    catalog_events: Vec<CatalogEvent>,
+    // The HiPS being generated, by order of creation
+    hips_generations: HashMap<u32, HiPSGeneration>,
+    num_hips_generations: u32,
+    // The cross-matches being computed, by order of creation
+    cross_matchings: HashMap<u32, CrossMatching>,
+    num_cross_matchings: u32,
     inertia: Option<Inertia>,
     disable_inertia: Rc<RefCell<bool>>,
     dist_dragging: f32,
@@ -81,8 +130,6 @@ pub struct App {
     prev_cam_position: Vector3<f64>,
     //prev_center: Vector3<f64>,
     out_of_fov: bool,
-    //tasks_finished: bool,
-    catalog_loaded: bool,
     start_time_frame: Time,
     last_time_request_for_new_tiles: Time,
     request_for_new_tiles: bool,
@@ -90,7 +137,7 @@ pub struct App {
     _final_rendering_pass: RenderPass,
     _fbo_view: FrameBufferObject,
     _fbo_ui: FrameBufferObject,
-    //line_renderer: RasterizedLineRenderer,
+    line_renderer: RasterizedLineRenderer,
     colormaps: Colormaps,
 
     pub projection: ProjectionType,
@@ -101,16 +148,30 @@ pub struct App {
 
     ack_img_send: async_channel::Sender<ImageParams>,
     ack_img_recv: async_channel::Receiver<ImageParams>,
+
+    // The tables found in the FITS files, added as catalogs
+    table_send: async_channel::Sender<(String, Table)>,
+    table_recv: async_channel::Receiver<(String, Table)>,
+
+    // The HiPS mirror changes not yet retrieved by the javascript
+    hips_mirror_switches: Vec<MirrorSwitch>,
+    // GPU memory shared by the textures of the HiPS layers, in bytes
+    gpu_texture_budget: usize,
+    // The number of HiPS still drawing the textures of their previous image format
+    num_hips_fading_out: usize,
     // callbacks
     //callback_position_changed: js_sys::Function,
 }
 
+use crate::hipsgen::{fits::FitsImage, HiPSGen, HiPSGenParams};
 use cgmath::{Vector2, Vector3};
 use futures::io::BufReader; // for `next`
+use std::future::Future;
 
 use crate::math::projection::*;
-pub const BLENDING_ANIM_DURATION: DeltaTime = DeltaTime::from_millis(200.0); // in ms
-                                                                             //use crate::buffer::Tile;
+// Number of frames downloaded in advance on each side of the current frame of a HiPS cube
+const NUM_CUBE_FRAMES_PREFETCHED: u32 = 1;
+//use crate::buffer::Tile;
 use crate::time::Time;
 use cgmath::InnerSpace;
 
@@ -122,13 +183,13 @@ impl App {
     pub fn new(
         gl: &WebGlContext,
         aladin_div: &HtmlElement,
-        mut shaders: ShaderManager,
-        resources: Resources,
+        shaders: ShaderManager,
+        _resources: Resources,
         // Callbacks
         //callback_position_changed: js_sys::Function,
     ) -> Result<Self, JsValue> {
         let gl = gl.clone();
-        //let exec = Rc::new(RefCell::new(TaskExecutor::new()));
+        let exec = Rc::new(RefCell::new(TaskExecutor::new()));
 
         let projection = ProjectionType::Sin(mapproj::zenithal::sin::Sin);
         gl.blend_func_separate(
@@ -162,7 +223,7 @@ impl App {
         let time_start_blending = Time::now();
 
         // Catalog definition
-        let manager = Manager::new(&gl, &mut shaders, &camera, &resources)?;
+        let manager = Manager::new(&gl, aladin_div)?;
 
         // Grid definition
         let grid = ProjetedGrid::new(gl.clone(), aladin_div)?;
@@ -177,7 +238,6 @@ impl App {
         let prev_cam_position = camera.get_center().truncate();
         //let prev_center = Vector3::new(0.0, 1.0, 0.0);
         let out_of_fov = false;
-        let catalog_loaded = false;
 
         let colormaps = Colormaps::new(&gl)?;
 
@@ -195,8 +255,10 @@ impl App {
 
         let (img_send, img_recv) = async_channel::unbounded::<ImageLayer>();
         let (ack_img_send, ack_img_recv) = async_channel::unbounded::<ImageParams>();
+        let (table_send, table_recv) = async_channel::unbounded::<(String, Table)>();
 
-        //let line_renderer = RasterizedLineRenderer::new(&gl)?;
+        let contours = ContourRenderer::new();
+        let line_renderer = RasterizedLineRenderer::new(&gl)?;
 
         let dist_dragging = 0.0;
         let time_start_dragging = Time::now();
@@ -223,15 +285,23 @@ impl App {
             grid,
             // MOCs renderable
             moc,
+            // Contours renderable
+            contours,
             // The catalog renderable
             manager,
-            //exec,
+            exec,
+            catalogs_loading: HashMap::new(),
+            catalog_events: vec![],
+            hips_generations: HashMap::new(),
+            num_hips_generations: 0,
+            cross_matchings: HashMap::new(),
+            num_cross_matchings: 0,
             //prev_center,
             _fbo_view,
             _fbo_ui,
             _final_rendering_pass,
 
-            //line_renderer,
+            line_renderer,
 
             // inertia
             inertia,
@@ -244,9 +314,6 @@ impl App {
             prev_cam_position,
             out_of_fov,
 
-            //tasks_finished,
-            catalog_loaded,
-
             tile_fetcher,
 
             colormaps,
@@ -256,9 +323,64 @@ impl App {
             img_recv,
             ack_img_send,
             ack_img_recv,
+
+            table_send,
+            table_recv,
+
+            hips_mirror_switches: vec![],
+            gpu_texture_budget: DEFAULT_GPU_TEXTURE_BUDGET,
+            num_hips_fading_out: 0,
         })
     }
 
+    // Adapt the number of textures of the HiPS to the size of the view and
+    // to their share of the GPU memory budget
+    fn update_texture_layouts(&mut self) -> Result<(), JsValue> {
+        let num_hips = self.layers.values_hips().count();
+        if num_hips == 0 {
+            return Ok(());
+        }
+
+        let num_pixels_view = (self.camera.get_width() * self.camera.get_height()) as f64;
+        let budget = self.gpu_texture_budget / num_hips;
+
+        for hips in self.layers.values_mut_hips() {
+            // The textures of the previous image format still take the memory of the HiPS,
+            // it is laid out again once they are discarded
+            if hips.is_fading_out() {
+                continue;
+            }
+
+            let cfg = hips.get_config();
+            let layout = TextureLayout::new(
+                num_pixels_view,
+                cfg.get_texture_size(),
+                cfg.get_texture_byte_size(),
+                budget,
+            );
+
+            if hips.set_texture_layout(layout)? {
+                // Relaunch the base tiles as the textures have been discarded
+                self.tile_fetcher
+                    .launch_starting_hips_requests(hips, self.downloader.clone());
+            }
+        }
+
+        self.request_for_new_tiles = true;
+        self.request_redraw = true;
+
+        Ok(())
+    }
+
+    fn look_for_new_catalog_files(&mut self) {
+        let queries = self.manager.get_hips_catalog_queries(&self.camera);
+
+        let mut downloader = self.downloader.borrow_mut();
+        for query in queries {
+            downloader.fetch(query);
+        }
+    }
+
     fn look_for_new_tiles(&mut self) -> Result<(), JsValue> {
         // Move the views of the different active surveys
         self.tile_fetcher.clear();
@@ -283,16 +405,54 @@ impl App {
             let creator_did = survey.get_config().get_creator_did().to_string();
             let root_url = survey.get_config().get_root_url().to_string();
             let format = survey.get_config().get_format();
+            let frame = survey.get_config().get_cube_frame();
+            // The tiles closest to the center of the view are fetched first
+            let view_center = coosys::apply_coo_system(
+                self.camera.get_coo_system(),
+                survey.get_config().get_frame(),
+                self.camera.get_center(),
+            )
+            .truncate();
+
+            // Prefetch the tiles of the neighbouring frames of a cube. Their lower priority
+            // makes them fetched after the tiles of the current frame
+            let neighbour_frames = survey
+                .get_config()
+                .get_cube_neighbour_frames(NUM_CUBE_FRAMES_PREFETCHED);
+            if !neighbour_frames.is_empty() {
+                let tile_cells = survey.tile_cells_in_view(&self.camera, &self.projection);
+                for &neighbour_frame in &neighbour_frames {
+                    for tile_cell in &tile_cells {
+                        if !survey.contains_cube_tile(tile_cell, neighbour_frame) {
+                            self.tile_fetcher.append_prefetched(
+                                query::Tile::new(
+                                    tile_cell,
+                                    creator_did.clone(),
+                                    root_url.clone(),
+                                    format,
+                                    neighbour_frame,
+                                ),
+                                &view_center,
+                                neighbour_frame.abs_diff(frame),
+                            );
+                        }
+                    }
+                }
+            }
 
             if let Some(tiles_iter) = survey.look_for_new_tiles(&mut self.camera, &self.projection)
             {
                 for tile_cell in tiles_iter.into_iter() {
-                    self.tile_fetcher.append(query::Tile::new(
-                        &tile_cell,
-                        creator_did.clone(),
-                        root_url.clone(),
-                        format,
-                    ));
+                    self.tile_fetcher.append(
+                        query::Tile::new(
+                            &tile_cell,
+                            creator_did.clone(),
+                            root_url.clone(),
+                            format,
+                            frame,
+                        ),
+                        &view_center,
+                    );
 
                     // check if we are starting aladin lite or not.
                     // If so we want to retrieve only the tiles in the view and access them
@@ -307,93 +467,122 @@ impl App {
             }
             // Request for ancestor
             for ancestor in ancestors {
-                if !survey.update_priority_tile(&ancestor) {
-                    self.tile_fetcher.append(query::Tile::new(
-                        &ancestor,
-                        creator_did.clone(),
-                        root_url.clone(),
-                        format,
-                    ));
+                if !survey.tile_available(&ancestor) {
+                    self.tile_fetcher.append(
+                        query::Tile::new(
+                            &ancestor,
+                            creator_did.clone(),
+                            root_url.clone(),
+                            format,
+                            frame,
+                        ),
+                        &view_center,
+                    );
                 }
             }
         }
 
+        // Abort the requests of the tiles that went out of the view
+        self.tile_fetcher.cancel_unwanted(&self.downloader);
+
         Ok(())
     }
 
     // Run async tasks:
     // - parsing catalogs
-    // - copying textures to GPU
-    // Return true when a task is complete. This always lead
+    // - generating HiPS
+    // - cross-matching catalogs
+    // Return true when a catalog has been loaded. This always lead
     // to a redraw of aladin lite
-    /*fn run_tasks(&mut self, dt: DeltaTime) -> Result<HashSet<Tile>, JsValue> {
+    fn run_tasks(&mut self, dt: DeltaTime) -> bool {
+        if self.catalogs_loading.is_empty()
+            && self.hips_generations.is_empty()
+            && self.cross_matchings.is_empty()
+        {
+            return false;
+        }
+
         let tasks_time = (dt.0 * 0.5).min(8.3);
         let results = self.exec.borrow_mut().run(tasks_time);
-        self.tasks_finished = !results.is_empty();
 
-        // Retrieve back all the tiles that have been
-        // copied to the GPU
-        // This is important for the tile buffer to know which
-        // requests can be reused to query more tiles
-        let mut tiles_available = HashSet::new();
-        for result in results {
-            match result {
-                TaskResult::TableParsed {
-                    name,
-                    sources,
-                    colormap,
-                } => {
-                    self.manager.add_catalog(
-                        name,
-                        sources,
-                        colormap,
-                        &mut self.shaders,
-                        &self.camera,
-                        self.surveys.get_view().unwrap_abort(),
-                    );
-                    self.catalog_loaded = true;
-                    self.request_redraw = true;
-                }
-                TaskResult::TileSentToGPU { tile } => {
-                    tiles_available.insert(tile);
-                }
+        for (name, (progress, reported)) in self.catalogs_loading.iter_mut() {
+            let progress = progress.get();
+            if progress > *reported {
+                *reported = progress;
+                self.catalog_events.push(CatalogEvent::Progress {
+                    name: name.clone(),
+                    progress,
+                });
             }
         }
 
-        Ok(tiles_available)
-    }*/
-    /*fn run_tasks(&mut self, dt: DeltaTime) -> Result<(), JsValue> {
-        let tasks_time = (dt.0 * 0.5).min(8.3);
-        let results = self.exec.borrow_mut().run(tasks_time);
-        self.tasks_finished = !results.is_empty();
+        for generation in self.hips_generations.values_mut() {
+            let progress = generation.progress.get();
+            if progress > generation.reported {
+                generation.reported = progress;
+                if let Some(on_progress) = &generation.on_progress {
+                    let _ = on_progress.call1(&JsValue::NULL, &JsValue::from_f64(progress as f64));
+                }
+            }
+        }
 
-        // Retrieve back all the tiles that have been
-        // copied to the GPU
-        // This is important for the tile buffer to know which
-        // requests can be reused to query more tiles
+        let mut catalog_loaded = false;
         for result in results {
             match result {
                 TaskResult::TableParsed {
                     name,
                     sources,
-                    colormap,
+                    positions,
                 } => {
-                    self.manager.add_catalog(
-                        name,
-                        sources,
-                        colormap,
-                        &mut self.shaders,
-                        &self.camera,
-                        self.surveys.get_view().unwrap_abort(),
-                    );
-                    self.catalog_loaded = true;
+                    catalog_loaded = true;
+                    self.catalogs_loading.remove(&name);
+
+                    let num_sources = sources.len();
+                    if let Ok(catalog) = self.manager.get_mut_catalog(&name) {
+                        catalog.set_sorted_sources(sources, positions);
+                    }
+
+                    self.catalog_events
+                        .push(CatalogEvent::Loaded { name, num_sources });
                     self.request_redraw = true;
-                } //TaskResult::TileSentToGPU { tile } => todo!()
+                }
+                TaskResult::HiPSGenerated { id, archive } => {
+                    if let Some(generation) = self.hips_generations.remove(&id) {
+                        // The tiles are read from the archive like the ones of a local HiPS
+                        let files = archive.map_err(JsValue::from).and_then(|archive| {
+                            HiPSLocalFiles::from_archive(archive.into_boxed_slice())
+                        });
+
+                        let _ = match files {
+                            Ok(files) => generation.resolve.call1(&JsValue::NULL, &files.into()),
+                            Err(err) => generation.reject.call1(&JsValue::NULL, &err),
+                        };
+                    }
+                }
+                TaskResult::CatalogsMatched { id, matches } => {
+                    if let Some(xmatch) = self.cross_matchings.remove(&id) {
+                        // The catalogs may have been removed in the meantime
+                        let added = if let Some(matches_name) = xmatch.matches_name {
+                            catalog_loaded = true;
+                            self.add_matches_catalog(matches_name, &xmatch.names, &matches)
+                        } else {
+                            Ok(())
+                        };
+                        let matches = added.and_then(|()| {
+                            serde_wasm_bindgen::to_value(&matches).map_err(JsValue::from)
+                        });
+
+                        let _ = match matches {
+                            Ok(matches) => xmatch.resolve.call1(&JsValue::NULL, &matches),
+                            Err(err) => xmatch.reject.call1(&JsValue::NULL, &err),
+                        };
+                    }
+                }
             }
         }
 
-        Ok(())
-    }*/
+        catalog_loaded
+    }
 }
 
 use crate::downloader::request::Resource;
@@ -401,6 +590,47 @@ use al_api::cell::HEALPixCellProjeted;
 
 use crate::downloader::request::tile::Tile;
 use crate::healpix::cell::HEALPixCell;
+use crate::survey::config::HiPSConfig;
+use crate::survey::buffer::TextureResidency;
+use crate::survey::mirror::MirrorSwitch;
+use crate::survey::texture_layout::{TextureLayout, DEFAULT_GPU_TEXTURE_BUDGET};
+
+// Measure the latency of the mirrors of a HiPS so that the fastest one can be chosen
+fn probe_hips_mirrors(downloader: &RefCell<Downloader>, cfg: &HiPSConfig) {
+    let mirrors = cfg.get_mirrors();
+    if mirrors.get_urls().count() < 2 {
+        return;
+    }
+
+    for url in mirrors.get_urls() {
+        downloader.borrow_mut().fetch(query::MirrorProbe::new(
+            url.to_string(),
+            cfg.get_creator_did().to_string(),
+        ));
+    }
+}
+
+// The keywords of the header of a FITS table read by fitsrs
+fn get_table_header<X>(header: &fitsrs::hdu::header::Header<X>) -> table::fits::Header
+where
+    X: fitsrs::hdu::header::extension::Xtension,
+{
+    use fitsrs::card::Value;
+
+    table::fits::Header::from_keywords(|key| {
+        let mut keyword = [b' '; 8];
+        let len = key.len().min(8);
+        keyword[..len].copy_from_slice(&key.as_bytes()[..len]);
+
+        match header.get(&keyword)? {
+            Value::Integer(value) => Some(value.to_string()),
+            Value::Float(value) => Some(value.to_string()),
+            Value::String(value) => Some(value.trim_end().to_string()),
+            Value::Logical(value) => Some(if *value { "T" } else { "F" }.to_string()),
+            _ => None,
+        }
+    })
+}
 
 use al_api::color::ColorRGB;
 
@@ -481,8 +711,14 @@ impl App {
         cells.into_boxed_slice()
     }
 
+    /// Whether catalogs have been added and all their tables have been parsed
     pub(crate) fn is_catalog_loaded(&self) -> bool {
-        self.catalog_loaded
+        !self.manager.is_empty() && self.catalogs_loading.is_empty()
+    }
+
+    /// The catalog events that occured since the last call
+    pub(crate) fn pop_catalog_events(&mut self) -> Vec<CatalogEvent> {
+        std::mem::take(&mut self.catalog_events)
     }
 
     pub(crate) fn get_moc(&self, cfg: &al_api::moc::MOC) -> Option<&HEALPixCoverage> {
@@ -528,10 +764,10 @@ impl App {
         return self.inertia.is_some();
     }
 
-    pub(crate) fn update(&mut self, _dt: DeltaTime) -> Result<bool, JsValue> {
+    pub(crate) fn update(&mut self, dt: DeltaTime) -> Result<bool, JsValue> {
         self.start_time_frame = Time::now();
 
-        //let available_tiles = self.run_tasks(dt)?;
+        let catalog_loaded = self.run_tasks(dt);
         if let Some(inertia) = self.inertia.as_mut() {
             inertia.apply(&mut self.camera, &self.projection);
             // Always request for new tiles while moving
@@ -576,6 +812,8 @@ impl App {
         //let has_camera_recently_moved =
         //    ;
         let _has_camera_zoomed = self.camera.has_zoomed();
+        // A file of a progressive catalogue has been received
+        let mut catalog_file_received = false;
         {
             // Newly available tiles must lead to
             // 1. Surveys must be aware of the new available tiles
@@ -591,13 +829,45 @@ impl App {
             for rsc in rscs_received {
                 match rsc {
                     Resource::Tile(tile) => {
+                        // The tile has been cancelled because it is not needed anymore
+                        if tile.failure() == Some(request::Failure::Aborted) {
+                            continue;
+                        }
+
                         //if !_has_camera_zoomed {
                         if let Some(survey) =
                             self.layers.get_mut_hips_from_cdid(&tile.get_hips_cdid())
                         {
-                            let cfg = survey.get_config_mut();
+                            // Switch to another mirror if the current one fails to deliver
+                            // the tiles. The tiles missing are requested again to the new one
+                            if let Some(switch) = survey
+                                .get_config_mut()
+                                .register_tile(tile.get_url(), tile.failure())
+                            {
+                                // The tiles that failed are requested again to the new mirror
+                                self.tile_fetcher.forget_failures(&switch.creator_did);
+                                self.hips_mirror_switches.push(switch);
+                                self.request_for_new_tiles = true;
+                            }
+                            // Retry the tile later if it failed because of a transient error
+                            self.tile_fetcher
+                                .register_tile(&tile, survey.get_config().get_root_url());
+
+                            let same_format = survey.get_config().get_format() == tile.format;
+                            // Keep the tiles of a cube for the time the user navigates
+                            // through its frames
+                            if same_format && survey.get_config().get_cube_depth().is_some() {
+                                survey.add_cube_tile(
+                                    tile.cell(),
+                                    tile.frame,
+                                    tile.image.clone(),
+                                    tile.time_req,
+                                );
+                            }
 
-                            if cfg.get_format() == tile.format {
+                            let cfg = survey.get_config_mut();
+                            // Tiles of the other frames of a cube are only prefetched
+                            if same_format && tile.frame == cfg.get_cube_frame() {
                                 let delta_depth = cfg.delta_depth();
                                 let fov_coverage = self.camera.get_cov(cfg.get_frame());
                                 let included_or_near_coverage = tile
@@ -691,6 +961,7 @@ impl App {
                                     match &*image.lock().unwrap_abort() {
                                         Some(img) => {
                                             survey.add_tile(&cell, img, time_req)?;
+                                            self.contours.invalidate_hips();
                                             self.request_redraw = true;
 
                                             self.time_start_blending = Time::now();
@@ -720,6 +991,7 @@ impl App {
                                             cfg.get_creator_did().to_string(),
                                             cfg.get_root_url().to_string(),
                                             cfg.get_format(),
+                                            cfg.get_cube_frame(),
                                         );
                                         self.tile_fetcher.append_base_tile(query);
                                     }
@@ -745,6 +1017,25 @@ impl App {
                             }
                         }
                     }
+                    Resource::MirrorProbe(probe) => {
+                        if let Some(hips) = self.layers.get_mut_hips_from_cdid(&probe.hips_cdid) {
+                            if let Some(switch) = hips
+                                .get_config_mut()
+                                .set_mirror_latency(&probe.mirror_url, probe.latency)
+                            {
+                                self.hips_mirror_switches.push(switch);
+                                self.request_for_new_tiles = true;
+                            }
+                        }
+                    }
+                    Resource::CatalogFile(file) => {
+                        match self.manager.add_hips_catalog_file(file) {
+                            Ok(sources_changed) => self.request_redraw |= sources_changed,
+                            Err(error) => al_core::log::console_warn(error),
+                        }
+
+                        catalog_file_received = true;
+                    }
                     Resource::Moc(moc) => {
                         let moc_hips_cdid = moc.get_hips_cdid();
                         //let url = &moc_url[..moc_url.find("/Moc.fits").unwrap_abort()];
@@ -782,18 +1073,48 @@ impl App {
         // The update from the camera
         //self.layers.update(&mut self.camera, &self.projection);
 
+        // The catalogues follow the view and their new tiles
+        if self.request_for_new_tiles || catalog_file_received {
+            self.look_for_new_catalog_files();
+        }
+        if has_camera_moved || catalog_file_received || catalog_loaded {
+            self.manager.update(&mut self.camera);
+        }
+
         if self.request_for_new_tiles
         //&& Time::now() - self.last_time_request_for_new_tiles > DeltaTime::from(200.0)
         {
             self.look_for_new_tiles()?;
+            self.contours.invalidate_hips();
 
             self.request_for_new_tiles = false;
             self.last_time_request_for_new_tiles = Time::now();
         }
 
+        // The memory of the textures of a previous image format discarded goes back
+        // to the textures of the current format
+        let num_hips_fading_out = self
+            .layers
+            .values_hips()
+            .filter(|hips| hips.is_fading_out())
+            .count();
+        if num_hips_fading_out < self.num_hips_fading_out {
+            self.update_texture_layouts()?;
+        }
+        self.num_hips_fading_out = num_hips_fading_out;
+
         // - there is at least one tile in its blending phase
-        let blending_anim_occuring =
-            (Time::now() - self.time_start_blending) < BLENDING_ANIM_DURATION;
+        // - or a HiPS still draws the textures of its previous image format
+        let blending_anim_occuring = (Time::now() - self.time_start_blending)
+            < self.layers.get_blending_duration()
+            || self.layers.is_fading_out();
+
+        // The tables are sent before the images of their FITS file
+        while let Ok((name, table)) = self.table_recv.try_recv() {
+            if let Err(error) = self.add_table_catalog(name, table) {
+                al_core::log::console_warn(error);
+            }
+        }
 
         // Check for async retrieval
         if let Ok(img) = self.img_recv.try_recv() {
@@ -813,6 +1134,11 @@ impl App {
             })
         }
 
+        // Trace again the contours whose source tiles have changed
+        if self.contours.update(&self.layers, &self.camera) {
+            self.request_redraw = true;
+        }
+
         self.rendering = blending_anim_occuring | has_camera_moved | self.request_redraw /*| start_fading*/;
         self.request_redraw = false;
 
@@ -823,22 +1149,109 @@ impl App {
 
     pub(crate) fn read_pixel(&self, pos: &Vector2<f64>, layer: &str) -> Result<JsValue, JsValue> {
         if let Some(lonlat) = self.screen_to_world(pos) {
-            if let Some(survey) = self.layers.get_hips_from_layer(layer) {
-                survey.read_pixel(&lonlat, &self.camera)
-            } else if let Some(_image) = self.layers.get_image_from_layer(layer) {
-                Err(JsValue::from_str("TODO: read pixel value"))
+            if let Some(composite) = self.layers.get_composite_from_layer(layer) {
+                // Report the values of the three channels
+                let pixel = js_sys::Object::new();
+                for (key, channel_layer) in ["r", "g", "b"].iter().zip(composite.channel_layers()) {
+                    let value = self
+                        .read_layer_pixel(&lonlat, channel_layer)
+                        .unwrap_or(JsValue::NULL);
+                    js_sys::Reflect::set(&pixel, &(*key).into(), &value)?;
+                }
+
+                Ok(pixel.into())
             } else {
-                Err(JsValue::from_str("Survey not found"))
+                self.read_layer_pixel(&lonlat, layer)
             }
         } else {
             Err(JsValue::from_str(&"position is out of projection"))
         }
     }
 
+    fn read_layer_pixel(&self, lonlat: &LonLatT<f64>, layer: &str) -> Result<JsValue, JsValue> {
+        if let Some(survey) = self.layers.get_hips_from_layer(layer) {
+            survey.read_pixel(lonlat, &self.camera)
+        } else if let Some(images) = self.layers.get_image_from_layer(layer) {
+            let pos = crate::coosys::apply_coo_system(
+                self.camera.get_coo_system(),
+                images.frame(),
+                &lonlat.vector(),
+            );
+            let mut pixel = vec![0.0; images.num_channels()];
+            let value = reproject::physical_value(images, &pos.lonlat(), &mut pixel);
+
+            if value.is_nan() {
+                Ok(JsValue::NULL)
+            } else {
+                Ok(JsValue::from_f64(value))
+            }
+        } else {
+            Err(JsValue::from_str("Survey not found"))
+        }
+    }
+
+    pub(crate) fn reproject_to_fits(
+        &self,
+        layer: &str,
+        wcs: Option<serde_json::Map<String, serde_json::Value>>,
+    ) -> Result<Vec<u8>, JsValue> {
+        let target = if let Some(keywords) = wcs {
+            TargetWCS::new(keywords)?
+        } else {
+            TargetWCS::from_view(&self.camera, &self.projection)?
+        };
+
+        if let Some(survey) = self.layers.get_hips_from_layer(layer) {
+            target.to_fits(&survey.sampler(&self.camera))
+        } else if let Some(images) = self.layers.get_image_from_layer(layer) {
+            let samplers = images.iter().map(Image::sampler).collect::<Vec<_>>();
+            target.to_fits(&samplers[..])
+        } else {
+            Err(JsValue::from_str("Survey not found"))
+        }
+    }
+
+    pub(crate) fn add_contour(&mut self, cfg: ContourCfg) -> Result<(), JsValue> {
+        self.contours.push_back(cfg, &self.layers, &self.camera)?;
+        self.request_redraw = true;
+
+        Ok(())
+    }
+
+    pub(crate) fn remove_contour(&mut self, name: &str) -> Result<(), JsValue> {
+        self.contours
+            .remove(name)
+            .ok_or_else(|| JsValue::from_str("Contour not found"))?;
+        self.request_redraw = true;
+
+        Ok(())
+    }
+
+    pub(crate) fn set_contour_cfg(&mut self, cfg: ContourCfg) -> Result<(), JsValue> {
+        self.contours
+            .set_cfg(cfg)
+            .ok_or_else(|| JsValue::from_str("Contour not found"))?;
+        self.request_redraw = true;
+
+        Ok(())
+    }
+
+    pub(crate) fn get_contour_polygons(&self, name: &str) -> Result<JsValue, JsValue> {
+        self.contours.get_polygons(name)
+    }
+
     pub(crate) fn draw_grid_labels(&mut self) -> Result<(), JsValue> {
         self.grid.draw_labels()
     }
 
+    pub(crate) fn has_catalog_labels(&self) -> bool {
+        self.manager.has_labels()
+    }
+
+    pub(crate) fn draw_catalog_labels(&mut self) -> Result<(), JsValue> {
+        self.manager.draw_labels(&self.camera, &self.projection)
+    }
+
     pub(crate) fn draw(&mut self, force_render: bool) -> Result<(), JsValue> {
         /*let scene_redraw = self.rendering | force_render;
         let mut ui = self.ui.lock();
@@ -919,7 +1332,14 @@ impl App {
             // Draw the catalog
             //let fbo_view = &self.fbo_view;
             //catalogs.draw(&gl, shaders, camera, colormaps, fbo_view)?;
-            //catalogs.draw(&gl, shaders, camera, colormaps, None, self.projection)?;
+            self.manager.draw(
+                &self.gl,
+                &mut self.shaders,
+                &self.camera,
+                &self.colormaps,
+                None,
+                &self.projection,
+            )?;
             self.moc.draw(
                 &mut self.camera,
                 &self.projection,
@@ -927,17 +1347,18 @@ impl App {
                 //&mut self.line_renderer,
             )?;
 
-            //self.line_renderer.begin();
+            self.line_renderer.begin();
             //Time::measure_perf("moc draw", || {
 
             //    Ok(())
             //})?;
+            self.contours.draw(&mut self.line_renderer);
 
             self.grid
                 .draw(&self.camera, &self.projection, &mut self.shaders)?;
-            //self.line_renderer.end();
-            //self.line_renderer
-            //    .draw(&mut self.shaders, &self.camera, &self.projection)?;
+            self.line_renderer.end();
+            self.line_renderer
+                .draw(&mut self.shaders, &self.camera, &self.projection)?;
 
             //let dpi  = self.camera.get_dpi();
             //ui.draw(&gl, dpi)?;
@@ -961,12 +1382,94 @@ impl App {
             &self.projection,
             &mut self.tile_fetcher,
         )?;
+        // The remaining HiPS share the GPU memory freed
+        self.update_texture_layouts()?;
+
+        self.request_redraw = true;
+
+        Ok(())
+    }
+
+    pub(crate) fn add_rgb_composite(&mut self, cfg: RGBCompositeCfg) -> Result<(), JsValue> {
+        self.layers.add_rgb_composite(
+            cfg,
+            &mut self.camera,
+            &self.projection,
+            &mut self.tile_fetcher,
+        )?;
+
+        self.request_redraw = true;
+
+        Ok(())
+    }
+
+    pub(crate) fn set_rgb_composite_cfg(&mut self, cfg: RGBCompositeCfg) -> Result<(), JsValue> {
+        self.layers.set_rgb_composite_cfg(cfg)?;
 
         self.request_redraw = true;
 
         Ok(())
     }
 
+    // Apply a WCS adjustment to all the images of a layer
+    // and return the new location of the first one
+    fn adjust_image_wcs<F>(&mut self, layer: &str, mut adjust: F) -> Result<CenteredFoV, JsValue>
+    where
+        F: FnMut(&mut Image) -> Result<(), JsValue>,
+    {
+        let images = self
+            .layers
+            .get_mut_image_from_layer(layer)
+            .ok_or_else(|| JsValue::from_str(&format!("Image layer {} not found", layer)))?;
+        images.iter_mut().try_for_each(|image| adjust(image))?;
+        let centered_fov = images[0].get_centered_fov().clone();
+
+        self.request_redraw = true;
+
+        Ok(centered_fov)
+    }
+
+    pub(crate) fn translate_image_wcs(
+        &mut self,
+        layer: &str,
+        from: LonLatT<f64>,
+        to: LonLatT<f64>,
+    ) -> Result<CenteredFoV, JsValue> {
+        self.adjust_image_wcs(layer, |image| image.translate_wcs(&from, &to))
+    }
+
+    pub(crate) fn rotate_image_wcs(
+        &mut self,
+        layer: &str,
+        pivot: LonLatT<f64>,
+        angle: Angle<f64>,
+    ) -> Result<CenteredFoV, JsValue> {
+        self.adjust_image_wcs(layer, |image| image.rotate_wcs(&pivot, angle))
+    }
+
+    pub(crate) fn scale_image_wcs(
+        &mut self,
+        layer: &str,
+        pivot: LonLatT<f64>,
+        factor: f64,
+    ) -> Result<CenteredFoV, JsValue> {
+        self.adjust_image_wcs(layer, |image| image.scale_wcs(&pivot, factor))
+    }
+
+    pub(crate) fn get_image_wcs_header(&self, layer: &str) -> Result<String, JsValue> {
+        let image = self
+            .layers
+            .get_image_from_layer(layer)
+            .and_then(|images| images.first())
+            .ok_or_else(|| JsValue::from_str(&format!("Image layer {} not found", layer)))?;
+
+        Ok(image
+            .get_wcs_header()
+            .cards()
+            .collect::<Vec<_>>()
+            .join("\n"))
+    }
+
     pub(crate) fn rename_layer(&mut self, layer: &str, new_layer: &str) -> Result<(), JsValue> {
         self.layers.rename_layer(&layer, &new_layer)
     }
@@ -989,6 +1492,7 @@ impl App {
         local_files: Option<HiPSLocalFiles>,
     ) -> Result<(), JsValue> {
         let cdid = hips_cfg.properties.get_creator_did().to_string();
+        let from_local_files = local_files.is_some();
 
         let hips = self.layers.add_image_hips(
             &self.gl,
@@ -1005,6 +1509,12 @@ impl App {
         self.tile_fetcher
             .launch_starting_hips_requests(hips, self.downloader.clone());
 
+        if !from_local_files {
+            probe_hips_mirrors(&self.downloader, hips.get_config());
+        }
+
+        self.update_texture_layouts()?;
+
         // Once its added, request the tiles in the view (unless the viewer is at depth 0)
         self.request_for_new_tiles = true;
         self.request_redraw = true;
@@ -1012,27 +1522,73 @@ impl App {
         Ok(())
     }
 
-    pub(crate) fn add_image_from_blob_and_wcs(
-        &mut self,
-        layer: String,
-        stream: web_sys::ReadableStream,
-        wcs: WCS,
-        cfg: ImageMetadata,
-    ) -> Result<js_sys::Promise, JsValue> {
-        let gl = self.gl.clone();
-
+    // Add an image layer once it has been loaded in the background
+    //
+    // The inertia is disabled while the image is loading. The promise returned resolves
+    // to the parameters of the layer once the image has been added.
+    fn load_image_layer<F>(&mut self, layer: String, cfg: ImageMetadata, load: F) -> js_sys::Promise
+    where
+        F: Future<Output = Result<Image, JsValue>> + 'static,
+    {
         let img_sender = self.img_send.clone();
         let ack_img_recv = self.ack_img_recv.clone();
         // Stop the current inertia
         self.inertia = None;
-        // And disable it while the fits has not been loaded
+        // And disable it while the image has not been loaded
         let disable_inertia = self.disable_inertia.clone();
         *(disable_inertia.borrow_mut()) = true;
 
+        let fut = async move {
+            let img = ImageLayer {
+                images: vec![load.await?],
+                id: layer.clone(),
+                layer,
+                meta: cfg,
+            };
+
+            img_sender
+                .send(img)
+                .await
+                .map_err(|_| JsValue::from_str("Problem sending the image"))?;
+
+            // Wait for the ack here
+            let image_params = ack_img_recv
+                .recv()
+                .await
+                .map_err(|_| JsValue::from_str("Problem receiving image"))?;
+
+            serde_wasm_bindgen::to_value(&image_params).map_err(|e| e.into())
+        };
+
+        let reenable_inertia = Closure::new(move || {
+            // renable inertia again
+            *(disable_inertia.borrow_mut()) = false;
+        });
+
+        let promise = wasm_bindgen_futures::future_to_promise(fut)
+            // Reenable inertia independantly from whether the
+            // image has been correctly parsed or not
+            .finally(&reenable_inertia);
+
+        // forget the closure, it is not very proper to do this as
+        // it won't be deallocated
+        reenable_inertia.forget();
+
+        promise
+    }
+
+    pub(crate) fn add_image_from_blob_and_wcs(
+        &mut self,
+        layer: String,
+        stream: web_sys::ReadableStream,
+        keywords: WCSKeywords,
+        cfg: ImageMetadata,
+    ) -> Result<js_sys::Promise, JsValue> {
+        let gl = self.gl.clone();
+        let wcs = keywords.wcs()?;
         let camera_coo_sys = self.camera.get_coo_system();
 
-        let fut = async move {
-            use crate::renderable::image::Image;
+        let load = async move {
             use futures::future::Either;
             use futures::TryStreamExt;
             use js_sys::Uint8Array;
@@ -1055,54 +1611,78 @@ impl App {
                 ),
             };
             use al_core::image::format::RGBA8U;
-            match Image::from_reader_and_wcs::<_, RGBA8U>(
+            Image::from_reader_and_wcs::<_, RGBA8U>(
                 &gl,
                 bytes_reader,
                 wcs,
+                keywords,
                 None,
                 None,
                 None,
                 camera_coo_sys,
             )
             .await
-            {
-                Ok(image) => {
-                    let img = ImageLayer {
-                        images: vec![image],
-                        id: layer.clone(),
-                        layer,
-                        meta: cfg,
-                    };
-
-                    img_sender.send(img).await.unwrap();
-
-                    // Wait for the ack here
-                    let image_params = ack_img_recv
-                        .recv()
-                        .await
-                        .map_err(|_| JsValue::from_str("Problem receiving fits"))?;
-
-                    serde_wasm_bindgen::to_value(&image_params).map_err(|e| e.into())
-                }
-                Err(error) => Err(error),
-            }
         };
 
-        let reenable_inertia = Closure::new(move || {
-            // renable inertia again
-            *(disable_inertia.borrow_mut()) = false;
-        });
+        Ok(self.load_image_layer(layer, cfg, load))
+    }
 
-        let promise = wasm_bindgen_futures::future_to_promise(fut)
-            // Reenable inertia independantly from whether the
-            // fits has been correctly parsed or not
-            .finally(&reenable_inertia);
+    pub(crate) fn add_image_with_avm(
+        &mut self,
+        layer: String,
+        blob: web_sys::Blob,
+        cfg: ImageMetadata,
+    ) -> Result<js_sys::Promise, JsValue> {
+        let gl = self.gl.clone();
+        let camera_coo_sys = self.camera.get_coo_system();
 
-        // forget the closure, it is not very proper to do this as
-        // it won't be deallocated
-        reenable_inertia.forget();
+        let load = async move {
+            use al_core::image::format::RGBA8U;
+            use wasm_bindgen_futures::JsFuture;
+
+            // The raw bytes of the file contain the XMP packet
+            let array_buffer = JsFuture::from(blob.array_buffer()).await?;
+            let bytes = js_sys::Uint8Array::new(&array_buffer).to_vec();
+
+            // Decode the image
+            let window = web_sys::window().unwrap_abort();
+            let bitmap = JsFuture::from(window.create_image_bitmap_with_blob(&blob)?)
+                .await?
+                .dyn_into::<web_sys::ImageBitmap>()?;
+            let (w, h) = (bitmap.width(), bitmap.height());
+
+            let document = window.document().unwrap_abort();
+            let canvas = document
+                .create_element("canvas")?
+                .dyn_into::<web_sys::HtmlCanvasElement>()?;
+            canvas.set_width(w);
+            canvas.set_height(h);
+            let context = canvas
+                .get_context("2d")?
+                .unwrap_abort()
+                .dyn_into::<web_sys::CanvasRenderingContext2d>()?;
+            context.draw_image_with_image_bitmap(&bitmap, 0.0, 0.0)?;
+            let raw_bytes = context
+                .get_image_data(0.0, 0.0, w as f64, h as f64)?
+                .data()
+                .0;
+
+            let (wcs, keywords) = crate::avm::parse_wcs(&bytes, w as u64, h as u64)?;
+
+            Image::from_reader_and_wcs::<_, RGBA8U>(
+                &gl,
+                futures::io::Cursor::new(raw_bytes),
+                wcs,
+                keywords,
+                None,
+                None,
+                None,
+                camera_coo_sys,
+            )
+            .await
+        };
 
-        Ok(promise)
+        Ok(self.load_image_layer(layer, cfg, load))
     }
 
     pub(crate) fn add_image_fits(
@@ -1115,6 +1695,7 @@ impl App {
 
         let fits_sender = self.img_send.clone();
         let ack_fits_recv = self.ack_img_recv.clone();
+        let table_sender = self.table_send.clone();
         // Stop the current inertia
         self.inertia = None;
         // And disable it while the fits has not been loaded
@@ -1160,75 +1741,87 @@ impl App {
             match Image::from_fits_hdu_async(&gl, &mut hdu.0, camera_coo_sys).await {
                 Ok(image) => {
                     images.push(image);
-
-                    let mut hdu_ext = hdu.next().await;
-
-                    // Continue parsing the file extensions here
-                    while let Ok(Some(mut xhdu)) = hdu_ext {
-                        match &mut xhdu {
-                            AsyncXtensionHDU::Image(xhdu_img) => {
-                                match Image::from_fits_hdu_async(&gl, xhdu_img, camera_coo_sys)
-                                    .await
-                                {
-                                    Ok(image) => {
-                                        images.push(image);
-                                    }
-                                    Err(error) => {
-                                        al_core::log::console_warn(&
-                                            format!("The extension {hdu_ext_idx} has not been parsed, reason:")
-                                        );
-
-                                        al_core::log::console_warn(error);
-                                    }
-                                }
-                            }
-                            _ => {
-                                al_core::log::console_warn(&
-                                    format!("The extension {hdu_ext_idx} is a BinTable/AsciiTable and is thus discarded")
-                                );
-                            }
-                        }
-
-                        hdu_ext_idx += 1;
-
-                        hdu_ext = xhdu.next().await;
-                    }
                 }
                 Err(error) => {
                     al_core::log::console_warn(error);
+                }
+            }
 
-                    let mut hdu_ext = hdu.next().await;
-
-                    while let Ok(Some(mut xhdu)) = hdu_ext {
-                        match &mut xhdu {
-                            AsyncXtensionHDU::Image(xhdu_img) => {
-                                match Image::from_fits_hdu_async(&gl, xhdu_img, camera_coo_sys)
-                                    .await
-                                {
-                                    Ok(image) => {
-                                        images.push(image);
-                                    }
-                                    Err(error) => {
-                                        al_core::log::console_warn(&
-                                            format!("The extension {hdu_ext_idx} has not been parsed, reason:")
-                                        );
-
-                                        al_core::log::console_warn(error);
-                                    }
-                                }
+            let mut hdu_ext = hdu.next().await;
+
+            // Continue parsing the file extensions here
+            while let Ok(Some(mut xhdu)) = hdu_ext {
+                // The primary HDU being 0
+                let extnum = hdu_ext_idx + 1;
+                let parsed = match &mut xhdu {
+                    AsyncXtensionHDU::Image(xhdu_img) => {
+                        match Image::from_fits_hdu_async(&gl, xhdu_img, camera_coo_sys).await {
+                            Ok(image) => {
+                                images.push(image);
                             }
-                            _ => {
-                                al_core::log::console_warn(&
-                                    format!("The extension {hdu_ext_idx} is a BinTable/AsciiTable and is thus discarded")
-                                );
+                            Err(error) => {
+                                al_core::log::console_warn(&format!(
+                                    "The extension {extnum} has not been parsed, reason:"
+                                ));
+
+                                al_core::log::console_warn(error);
                             }
                         }
 
-                        hdu_ext_idx += 1;
+                        None
+                    }
+                    AsyncXtensionHDU::BinTable(xhdu_table) => {
+                        let header = get_table_header(xhdu_table.get_header());
+                        let data = xhdu_table
+                            .get_data_mut()
+                            .map_ok(|v| v[0])
+                            .try_collect::<Vec<u8>>()
+                            .await;
+
+                        Some(data.map(|data| table::fits::read_bintable(&header, &data)))
+                    }
+                    AsyncXtensionHDU::AsciiTable(xhdu_table) => {
+                        let header = get_table_header(xhdu_table.get_header());
+                        let data = xhdu_table
+                            .get_data_mut()
+                            .map_ok(|v| v[0])
+                            .try_collect::<Vec<u8>>()
+                            .await;
+
+                        Some(data.map(|data| table::fits::read_ascii_table(&header, &data)))
+                    }
+                };
 
-                        hdu_ext = xhdu.next().await;
+                match parsed {
+                    Some(Ok(Ok(table))) => {
+                        // The catalog is named after the layer and the extension
+                        let name = format!(
+                            "{}/{}",
+                            layer,
+                            table.name.clone().unwrap_or_else(|| extnum.to_string())
+                        );
+                        if table_sender.send((name.clone(), table)).await.is_err() {
+                            al_core::log::console_warn(&format!(
+                                "The table {name} could not be added to the catalogs"
+                            ));
+                        }
+                    }
+                    Some(Ok(Err(error))) => {
+                        al_core::log::console_warn(&format!(
+                            "The table of the extension {extnum} has not been parsed: {error}"
+                        ));
+                    }
+                    Some(Err(error)) => {
+                        al_core::log::console_warn(&format!(
+                            "The table of the extension {extnum} has not been read: {error}"
+                        ));
                     }
+                    None => (),
                 }
+
+                hdu_ext_idx += 1;
+
+                hdu_ext = xhdu.next().await;
             }
 
             if images.is_empty() {
@@ -1286,6 +1879,55 @@ impl App {
         Ok(())
     }
 
+    pub(crate) fn set_hips_mirrors(
+        &mut self,
+        cdid: &str,
+        urls: Vec<String>,
+    ) -> Result<(), JsValue> {
+        let hips = self
+            .layers
+            .get_mut_hips_from_cdid(cdid)
+            .ok_or_else(|| JsValue::from_str("Survey not found"))?;
+        hips.get_config_mut().set_mirrors(&urls);
+
+        probe_hips_mirrors(&self.downloader, hips.get_config());
+
+        Ok(())
+    }
+
+    /// The mirror changes that occured since the last call
+    pub(crate) fn pop_hips_mirror_switches(&mut self) -> Vec<MirrorSwitch> {
+        std::mem::take(&mut self.hips_mirror_switches)
+    }
+
+    pub(crate) fn get_tile_fetcher_metrics(&self) -> TileFetcherMetrics {
+        self.tile_fetcher.get_metrics()
+    }
+
+    pub(crate) fn set_tile_blending_duration(&mut self, duration: DeltaTime) {
+        self.layers.set_blending_duration(duration);
+    }
+
+    pub(crate) fn set_resource_cache_budget(&mut self, budget: usize) {
+        self.downloader.borrow_mut().set_cache_budget(budget);
+    }
+
+    pub(crate) fn get_resource_cache_stats(&self) -> CacheStats {
+        self.downloader.borrow().get_cache_stats()
+    }
+
+    pub(crate) fn set_gpu_texture_budget(&mut self, budget: usize) -> Result<(), JsValue> {
+        self.gpu_texture_budget = budget;
+        self.update_texture_layouts()
+    }
+
+    pub(crate) fn get_texture_residency(&self, layer: &str) -> Result<TextureResidency, JsValue> {
+        self.layers
+            .get_hips_from_layer(layer)
+            .map(|hips| hips.get_texture_residency())
+            .ok_or_else(|| JsValue::from_str(&format!("HiPS layer {} not found", layer)))
+    }
+
     pub(crate) fn set_image_survey_color_cfg(
         &mut self,
         layer: String,
@@ -1319,6 +1961,46 @@ impl App {
         Ok(())
     }
 
+    pub(crate) fn set_hips_cube_frame(&mut self, layer: &str, frame: u32) -> Result<(), JsValue> {
+        let hips = self
+            .layers
+            .get_mut_hips_from_layer(layer)
+            .ok_or_else(|| JsValue::from_str("Layer not found"))?;
+        hips.get_config_mut().set_cube_frame(frame)?;
+
+        // The textures keep drawing the previous frame until the tiles of the new
+        // one are received or taken back from the cube tiles already downloaded
+        self.request_for_new_tiles = true;
+        self.request_redraw = true;
+
+        Ok(())
+    }
+
+    pub(crate) fn get_hips_cube_frame(&self, layer: &str) -> Result<u32, JsValue> {
+        let hips = self
+            .layers
+            .get_hips_from_layer(layer)
+            .ok_or_else(|| JsValue::from_str("Layer not found"))?;
+
+        Ok(hips.get_config().get_cube_frame())
+    }
+
+    pub(crate) fn get_hips_cube_frame_coordinate(
+        &self,
+        layer: &str,
+        frame: u32,
+    ) -> Result<Option<f64>, JsValue> {
+        let hips = self
+            .layers
+            .get_hips_from_layer(layer)
+            .ok_or_else(|| JsValue::from_str("Layer not found"))?;
+
+        Ok(hips
+            .get_config()
+            .get_properties()
+            .get_cube_frame_coordinate(frame))
+    }
+
     // Width and height given are in pixels
     pub(crate) fn set_projection(&mut self, projection: ProjectionType) -> Result<(), JsValue> {
         self.projection = projection;
@@ -1342,34 +2024,178 @@ impl App {
         self.camera.get_longitude_reversed()
     }
 
-    pub(crate) fn add_catalog(&mut self, _name: String, table: JsValue, _colormap: String) {
-        //let mut exec_ref = self.exec.borrow_mut();
-        let _table = table;
+    /// Cut a FITS image into the tiles of a HiPS in the background during the next frames
+    ///
+    /// The promise returned is resolved with the files of the HiPS, which can be displayed
+    /// as a local HiPS
+    pub(crate) fn generate_hips(
+        &mut self,
+        fits: &[u8],
+        params: HiPSGenParams,
+        on_progress: Option<js_sys::Function>,
+    ) -> Result<js_sys::Promise, JsValue> {
+        let image = FitsImage::new(fits)?;
+        let progress = Rc::new(Cell::new(0.0));
+        let task = HiPSGenTask::new(HiPSGen::new(image, params)?, progress.clone());
+
+        let id = self.num_hips_generations;
+        self.num_hips_generations += 1;
+        self.exec
+            .borrow_mut()
+            .spawner()
+            .spawn(TaskType::HiPSGenTask(id), async move {
+                let archive = task.await;
+
+                TaskResult::HiPSGenerated { id, archive }
+            });
+
+        let mut settle = None;
+        let promise = js_sys::Promise::new(&mut |resolve, reject| settle = Some((resolve, reject)));
+        let (resolve, reject) = settle.unwrap_abort();
+        self.hips_generations.insert(
+            id,
+            HiPSGeneration {
+                progress,
+                reported: 0.0,
+                on_progress,
+                resolve,
+                reject,
+            },
+        );
 
-        /*exec_ref
-        .spawner()
-        .spawn(TaskType::ParseTableTask, async move {
-            let mut stream = ParseTableTask::<[f32; 2]>::new(table);
-            let mut results: Vec<LonLatT<f32>> = vec![];
+        Ok(promise)
+    }
 
-            while let Some(item) = stream.next().await {
-                results.push(LonLatT::new(item[0].to_angle(), item[1].to_angle()));
-            }
+    /// Add a catalog, its table being parsed in the background during the next frames
+    ///
+    /// The catalog is added at once without sources so that it can be configured
+    /// while its table is parsed
+    pub(crate) fn add_catalog(
+        &mut self,
+        name: String,
+        table: JsValue,
+        colormap: String,
+    ) -> Result<(), JsValue> {
+        let columns = get_columns(&table)?;
+        let coordinates = Coordinates::new(table)?;
+        let progress = Rc::new(Cell::new(0.0));
 
-            let mut stream_sort = BuildCatalogIndex::new(results);
-            while stream_sort.next().await.is_some() {}
+        let task = ParseTableTask::new(coordinates, progress.clone());
+        let catalog_name = name.clone();
+        self.exec.borrow_mut().spawner().spawn(
+            TaskType::ParseTableTask(name.clone()),
+            async move {
+                let (sources, positions) = task.await;
 
-            // The stream is finished, we get the sorted sources
-            let results = stream_sort.sources;
+                TaskResult::TableParsed {
+                    name: catalog_name,
+                    sources,
+                    positions,
+                }
+            },
+        );
+        // A catalog being parsed with the same name is replaced
+        self.catalogs_loading.insert(name.clone(), (progress, 0.0));
+
+        // Replacing a catalog keeps the frame registered once
+        self.manager
+            .remove_catalog(name.clone(), &mut self.camera, &self.projection);
+        self.manager.add_catalog(
+            name,
+            Box::new([]),
+            Box::new([]),
+            columns,
+            colormap,
+            &mut self.camera,
+            &self.projection,
+        );
 
-            TaskResult::TableParsed {
-                name,
-                sources: results.into_boxed_slice(),
-            }
-        });*/
+        Ok(())
+    }
+
+    /// Add a catalog from a table read in Rust, e.g. from a FITS file
+    ///
+    /// A catalog of the same name is replaced, the catalog being loaded at once
+    pub(crate) fn add_table_catalog(&mut self, name: String, table: Table) -> Result<(), JsValue> {
+        let positions = table.get_positions().ok_or_else(|| {
+            JsValue::from_str(&format!("The coordinates of {} have not been found", name))
+        })?;
+        let sources = positions
+            .iter()
+            .map(|&(row, LonLatT(lon, lat))| {
+                Source::new(LonLatT::new(Angle(lon.0 as f32), Angle(lat.0 as f32)), row)
+            })
+            .collect::<Box<[_]>>();
+        let num_sources = sources.len();
+
+        // The positions in double precision are indexed by the rows
+        let num_rows = positions.last().map_or(0, |&(row, _)| row as usize + 1);
+        let mut lonlats = vec![LonLatT::new(Angle(f64::NAN), Angle(f64::NAN)); num_rows];
+        for (row, lonlat) in positions {
+            lonlats[row as usize] = lonlat;
+        }
+
+        // Stop parsing the table of a catalog being replaced
+        if self.catalogs_loading.remove(&name).is_some() {
+            self.exec
+                .borrow_mut()
+                .remove(&TaskType::ParseTableTask(name.clone()));
+        }
+        self.manager
+            .remove_catalog(name.clone(), &mut self.camera, &self.projection);
+        self.manager.add_catalog(
+            name.clone(),
+            sources,
+            lonlats.into_boxed_slice(),
+            table.get_numerical_columns(),
+            String::from("viridis"),
+            &mut self.camera,
+            &self.projection,
+        );
+        self.manager.get_mut_catalog(&name)?.set_table(table);
+        self.manager.update(&mut self.camera);
+
+        self.catalog_events
+            .push(CatalogEvent::Loaded { name, num_sources });
+        self.request_redraw = true;
+
+        Ok(())
     }
 
-    pub(crate) fn resize(&mut self, width: f32, height: f32) {
+    pub(crate) fn add_hips_catalog(
+        &mut self,
+        name: String,
+        url: String,
+        max_order: u8,
+        colormap: String,
+    ) {
+        let hips_catalog = HiPSCatalog::new(url, max_order);
+        self.manager.add_hips_catalog(
+            name,
+            hips_catalog,
+            colormap,
+            &mut self.camera,
+            &self.projection,
+        );
+
+        // Start with the Metadata.xml file
+        self.look_for_new_catalog_files();
+        self.request_redraw = true;
+    }
+
+    pub(crate) fn remove_catalog(&mut self, name: String) {
+        // Stop parsing its table if it is still being loaded
+        if self.catalogs_loading.remove(&name).is_some() {
+            self.exec
+                .borrow_mut()
+                .remove(&TaskType::ParseTableTask(name.clone()));
+        }
+        self.manager.remove_catalog(name, &mut self.camera, &self.projection);
+
+        self.request_redraw = true;
+    }
+
+    pub(crate) fn resize(&mut self, width: f32, height: f32) -> Result<(), JsValue> {
         self.camera.set_screen_size(width, height, &self.projection);
         self.camera
             .set_aperture(self.camera.get_aperture(), &self.projection);
@@ -1380,9 +2206,10 @@ impl App {
 
         // launch the new tile requests
         self.request_for_new_tiles = true;
-        //self.manager.set_kernel_size(&self.camera);
 
         self.request_redraw = true;
+
+        self.update_texture_layouts()
     }
 
     pub(crate) fn set_survey_url(&mut self, cdid: &String, new_url: String) -> Result<(), JsValue> {
@@ -1405,6 +2232,182 @@ impl App {
         Ok(())
     }
 
+    pub(crate) fn set_catalog_cfg(&mut self, name: String, cfg: CatalogCfg) -> Result<(), JsValue> {
+        self.manager.get_mut_catalog(&name)?.set_cfg(cfg)?;
+        // Select the sources of the heatmap in case the catalog is no longer drawn with markers
+        self.manager.update(&mut self.camera);
+
+        self.request_redraw = true;
+
+        Ok(())
+    }
+
+    /// The rows of the sources of a catalog lying within `radius` pixels of a screen
+    /// position, the nearest first
+    pub(crate) fn pick_catalog_sources(
+        &self,
+        name: &str,
+        pos: &Vector2<f64>,
+        radius: f64,
+    ) -> Result<Box<[u32]>, JsValue> {
+        let catalog = self.manager.get_catalog(name)?;
+
+        let center = if let Some(center) = self.screen_to_world(pos) {
+            center
+        } else {
+            // The position is out of the projection
+            return Ok(Box::new([]));
+        };
+        // The angle covered by the radius, given by the size of a pixel at the center of
+        // the view if the edge of the disk is out of the projection
+        let radius = self
+            .screen_to_world(&(pos + Vector2::new(radius, 0.0)))
+            .map(|edge| math::lonlat::ang_between_lonlat(center, edge).0)
+            .unwrap_or_else(|| {
+                let px_size =
+                    self.camera.get_aperture().to_radians() / (self.camera.get_width() as f64);
+                radius * (self.camera.get_dpi() as f64) * px_size
+            });
+
+        let center = self.view_to_icrs_coosys(&center);
+        Ok(catalog.pick(&center, radius).into_boxed_slice())
+    }
+
+    /// Select the sources of a catalog lying in a range of ICRS longitudes and latitudes
+    pub(crate) fn select_catalog_sources_in_zone(
+        &mut self,
+        name: &str,
+        min: &LonLatT<f64>,
+        max: &LonLatT<f64>,
+    ) -> Result<Box<[u32]>, JsValue> {
+        let catalog = self.manager.get_mut_catalog(name)?;
+        let rows = catalog.select_in_zone(
+            [min.lon().to_radians(), min.lat().to_radians()],
+            [max.lon().to_radians(), max.lat().to_radians()],
+        );
+
+        self.request_redraw = true;
+
+        Ok(rows.into())
+    }
+
+    /// Select the sources of a catalog lying in a polygon whose vertices are given in ICRS
+    pub(crate) fn select_catalog_sources_in_polygon(
+        &mut self,
+        name: &str,
+        vertices: &[LonLatT<f64>],
+    ) -> Result<Box<[u32]>, JsValue> {
+        let rows = self
+            .manager
+            .get_mut_catalog(name)?
+            .select_in_polygon(vertices);
+
+        self.request_redraw = true;
+
+        Ok(rows.into())
+    }
+
+    pub(crate) fn set_catalog_selection(
+        &mut self,
+        name: &str,
+        rows: Vec<u32>,
+    ) -> Result<(), JsValue> {
+        self.manager.get_mut_catalog(name)?.set_selection(rows);
+
+        self.request_redraw = true;
+
+        Ok(())
+    }
+
+    /// Cross-match the sources of two catalogs by their position in the background during
+    /// the next frames
+    ///
+    /// The promise returned is resolved with the pairs found, which are added as a new
+    /// catalog if a name is given for it
+    pub(crate) fn cross_match_catalogs(
+        &mut self,
+        name: &str,
+        other_name: &str,
+        cfg: XMatchCfg,
+        matches_name: Option<String>,
+    ) -> Result<js_sys::Promise, JsValue> {
+        let task = CrossMatchTask::new(self.manager.cross_match(name, other_name, &cfg)?);
+
+        let id = self.num_cross_matchings;
+        self.num_cross_matchings += 1;
+        self.exec
+            .borrow_mut()
+            .spawner()
+            .spawn(TaskType::CrossMatchTask(id), async move {
+                let matches = task.await;
+
+                TaskResult::CatalogsMatched { id, matches }
+            });
+
+        let mut settle = None;
+        let promise = js_sys::Promise::new(&mut |resolve, reject| settle = Some((resolve, reject)));
+        let (resolve, reject) = settle.unwrap_abort();
+        self.cross_matchings.insert(
+            id,
+            CrossMatching {
+                names: (name.to_string(), other_name.to_string()),
+                matches_name,
+                resolve,
+                reject,
+            },
+        );
+
+        Ok(promise)
+    }
+
+    // Add the pairs of a cross-match as a catalog
+    fn add_matches_catalog(
+        &mut self,
+        matches_name: String,
+        (name, other_name): &(String, String),
+        matches: &Matches,
+    ) -> Result<(), JsValue> {
+        self.manager.add_matches_catalog(
+            matches_name.clone(),
+            (name, other_name),
+            matches,
+            &mut self.camera,
+            &self.projection,
+        )?;
+        self.manager.update(&mut self.camera);
+        // Stop parsing the table of a catalog being replaced
+        if self.catalogs_loading.remove(&matches_name).is_some() {
+            self.exec
+                .borrow_mut()
+                .remove(&TaskType::ParseTableTask(matches_name.clone()));
+        }
+
+        self.catalog_events.push(CatalogEvent::Loaded {
+            name: matches_name,
+            num_sources: matches.rows.len(),
+        });
+        self.request_redraw = true;
+
+        Ok(())
+    }
+
+    pub(crate) fn get_catalog_selection(&self, name: &str) -> Result<Box<[u32]>, JsValue> {
+        Ok(self.manager.get_catalog(name)?.get_selection().into())
+    }
+
+    pub(crate) fn get_catalog_rows(&self, name: &str) -> Result<Box<[u32]>, JsValue> {
+        Ok(self.manager.get_catalog(name)?.get_shown_rows().into())
+    }
+
+    pub(crate) fn get_catalog_table(&self, name: &str) -> Result<&Table, JsValue> {
+        self.manager.get_catalog(name)?.get_table().ok_or_else(|| {
+            JsValue::from_str(&format!(
+                "The table of {} is not kept, write it with writeTable",
+                name
+            ))
+        })
+    }
+
     pub(crate) fn set_kernel_strength(
         &mut self,
         name: String,
@@ -3,260 +3,469 @@
 // can be run concurrently on one thread under a time limit period
 // When the time limit is reached, the executor stops polling the remaining
 // futures and return the results of the finished ones
-/*use al_task_exec::Executor;
+use al_task_exec::Executor;
 pub type TaskExecutor = Executor<TaskType, TaskResult>;
 
-use crate::math::lonlat::LonLat;
+use crate::healpix::index_vector::cell_d7;
+use crate::hipsgen::{self, HiPSGen};
+use crate::math::angle::Angle;
 use crate::math::lonlat::LonLatT;
+use crate::renderable::catalog::xmatch::{CrossMatch, Matches};
+use crate::renderable::catalog::{marker::Columns, Source};
+use crate::table::parse_coordinate;
 
 pub enum TaskResult {
     TableParsed {
         name: String,
-        sources: Box<[LonLatT<f32>]>,
+        sources: Box<[Source]>,
+        positions: Box<[LonLatT<f64>]>,
+    },
+    HiPSGenerated {
+        id: u32,
+        archive: Result<Vec<u8>, hipsgen::Error>,
+    },
+    CatalogsMatched {
+        id: u32,
+        matches: Matches,
     },
-    /*TileSentToGPU {
-        tile: Tile,
-    },*/
 }
 
 #[derive(Hash, Eq, PartialEq, Clone)]
 pub enum TaskType {
-    //ImageTile2GpuTask(Tile),
-    ParseTableTask,
+    // The parsing of the table of the catalog of that name
+    ParseTableTask(String),
+    // The generation of a HiPS, identified by its order of creation
+    HiPSGenTask(u32),
+    // The cross-match of two catalogs, identified by its order of creation
+    CrossMatchTask(u32),
 }
 
-use futures::stream::Stream;
+use serde::Serialize;
 
-use wasm_bindgen::JsValue;
+/// An event about the loading of a catalog, reported to the javascript
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum CatalogEvent {
+    /// A part of the rows of the table has been parsed
+    Progress { name: String, progress: f32 },
+    /// The catalog is ready to be drawn
+    Loaded {
+        name: String,
+        #[serde(rename = "numSources")]
+        num_sources: usize,
+    },
+}
 
-// Task that parse a table
-pub struct ParseTableTask<T>
-where
-    T: DeserializeOwned + AsRef<[f32]>,
-{
-    table: js_sys::Array,
-    idx: u32,
-    next_val_ready: Option<T>,
+use js_sys::{
+    Array, ArrayBuffer, BigInt64Array, BigUint64Array, DataView, Float64Array, Object, Reflect,
+    Uint8Array,
+};
+use wasm_bindgen::{JsCast, JsValue};
+
+// A coordinate given as a number or as a string, in decimal degrees or sexagesimal
+fn coordinate(value: &JsValue, hours: bool) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| parse_coordinate(&value.as_string()?, hours))
 }
 
-use wasm_bindgen::JsCast;
-impl<T> ParseTableTask<T>
-where
-    T: DeserializeOwned + AsRef<[f32]>,
-{
-    pub fn new(table: JsValue) -> Self {
-        let table = table.dyn_into().unwrap_abort();
-        let idx = 0;
-        let next_val_ready = None;
-        Self {
-            table,
-            idx,
-            next_val_ready,
+/// A column of coordinates
+pub enum Column {
+    // The typed arrays are copied at once
+    Values(Vec<f64>),
+    // The javascript arrays may contain sexagesimal strings
+    Array(Array),
+}
+
+impl Column {
+    fn new(column: JsValue) -> Result<Self, JsValue> {
+        if Array::is_array(&column) {
+            Ok(Column::Array(column.unchecked_into()))
+        } else if ArrayBuffer::is_view(&column) {
+            Ok(Column::Values(Float64Array::new(&column).to_vec()))
+        } else {
+            Err(JsValue::from_str(
+                "The coordinates must be given as arrays or typed arrays",
+            ))
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Column::Values(values) => values.len(),
+            Column::Array(array) => array.length() as usize,
+        }
+    }
+
+    fn get(&self, idx: usize, hours: bool) -> Option<f64> {
+        match self {
+            Column::Values(values) => Some(values[idx]),
+            Column::Array(array) => coordinate(&array.get(idx as u32), hours),
         }
     }
 }
 
-use serde::de::DeserializeOwned;
+/// The positions of the sources of a catalog given by the javascript
+///
+/// They can be given as:
+/// * an array of `[ra, dec]` arrays or of `{ra, dec}` objects
+/// * a typed array of interleaved right ascensions and declinations
+/// * an object whose `ra` and `dec` are arrays or typed arrays
+/// * a table returned by `parseVOTable`, `ra` and `dec` being the indices of its columns
+///
+/// The coordinates are in degrees, strings being parsed as sexagesimal coordinates
+pub enum Coordinates {
+    Rows(Array),
+    Columns { ra: Column, dec: Column },
+}
 
-use std::pin::Pin;
-use std::task::{Context, Poll};
-impl<T> Stream for ParseTableTask<T>
-where
-    T: DeserializeOwned + AsRef<[f32]> + Unpin,
-{
-    type Item = T;
-
-    /// Attempt to resolve the next item in the stream.
-    /// Returns `Poll::Pending` if not ready, `Poll::Ready(Some(x))` if a value
-    /// is ready, and `Poll::Ready(None)` if the stream has completed.
-    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        // Deserialize row by row.
-        let len = self.table.length();
-        if self.idx == len {
-            Poll::Ready(None)
-        } else {
-            // Check whether the next value has been parsed
-            if let Some(next_val) = self.next_val_ready.take() {
-                self.idx += 1;
-                Poll::Ready(Some(next_val))
+impl Coordinates {
+    pub fn new(table: JsValue) -> Result<Self, JsValue> {
+        if Array::is_array(&table) {
+            Ok(Coordinates::Rows(table.unchecked_into()))
+        } else if ArrayBuffer::is_view(&table) {
+            let values = Float64Array::new(&table).to_vec();
+            let ra = values.iter().step_by(2).cloned().collect();
+            let dec = values.iter().skip(1).step_by(2).cloned().collect();
+
+            Ok(Coordinates::Columns {
+                ra: Column::Values(ra),
+                dec: Column::Values(dec),
+            })
+        } else if table.is_object() {
+            let get = |key: &str| Reflect::get(&table, &JsValue::from_str(key));
+            let (ra, dec) = (get("ra")?, get("dec")?);
+
+            let (ra, dec) = if let (Some(ra), Some(dec)) = (ra.as_f64(), dec.as_f64()) {
+                let columns = get("columns")?
+                    .dyn_into::<Array>()
+                    .map_err(|_| JsValue::from_str("The table has no columns"))?;
+                (columns.get(ra as u32), columns.get(dec as u32))
             } else {
-                // Parse the next value and pends the stream
-                // if serde returns an error while parsing the row
-                // it will be converted to a None and discarded
-                self.next_val_ready = serde_wasm_bindgen::from_value(self.table.get(self.idx)).ok();
-                if self.next_val_ready.is_none() {
-                    // serde failed parsing the row
-                    self.idx += 1;
+                (ra, dec)
+            };
+
+            Ok(Coordinates::Columns {
+                ra: Column::new(ra)?,
+                dec: Column::new(dec)?,
+            })
+        } else {
+            Err(JsValue::from_str(
+                "The sources of the catalog cannot be read",
+            ))
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Coordinates::Rows(rows) => rows.length() as usize,
+            Coordinates::Columns { ra, dec } => ra.len().min(dec.len()),
+        }
+    }
+
+    // The position of a source in degrees
+    fn get(&self, idx: usize) -> Option<(f64, f64)> {
+        let (ra, dec) = match self {
+            Coordinates::Rows(rows) => {
+                let row = rows.get(idx as u32);
+                let (ra, dec) = if Array::is_array(&row) {
+                    let row: Array = row.unchecked_into();
+                    (row.get(0), row.get(1))
+                } else {
+                    let get = |key: &str| Reflect::get(&row, &JsValue::from_str(key)).ok();
+                    (get("ra")?, get("dec")?)
+                };
+
+                (coordinate(&ra, true)?, coordinate(&dec, false)?)
+            }
+            Coordinates::Columns { ra, dec } => (ra.get(idx, true)?, dec.get(idx, false)?),
+        };
+
+        Some((ra, dec)).filter(|&(ra, dec)| ra.is_finite() && dec.abs() <= 90.0)
+    }
+}
+
+// The values of a numerical column, None for the other columns
+fn numbers(column: &JsValue) -> Option<Box<[f32]>> {
+    if Array::is_array(column) {
+        let column: &Array = column.unchecked_ref();
+        column
+            .iter()
+            .map(|value| {
+                if value.is_null() || value.is_undefined() {
+                    Some(f32::NAN)
+                } else {
+                    value.as_f64().map(|value| value as f32)
+                }
+            })
+            .collect()
+    } else if ArrayBuffer::is_view(column)
+        // The 64 bits integers cannot be converted to numbers
+        && !column.is_instance_of::<BigInt64Array>()
+        && !column.is_instance_of::<BigUint64Array>()
+        && !column.is_instance_of::<DataView>()
+    {
+        let values = Float64Array::new(column).to_vec();
+        Some(values.into_iter().map(|value| value as f32).collect())
+    } else {
+        None
+    }
+}
+
+/// The numerical columns of the table of a catalog, indexed by their names
+///
+/// They are the other keys of an object of columns, or the fields of a table
+/// returned by `parseVOTable`, their null values being set to NaN. The tables
+/// given by rows have no columns.
+pub fn get_columns(table: &JsValue) -> Result<Columns, JsValue> {
+    let mut columns = Columns::new();
+    if !table.is_object() || Array::is_array(table) || ArrayBuffer::is_view(table) {
+        return Ok(columns);
+    }
+
+    let get = |key: &str| Reflect::get(table, &JsValue::from_str(key));
+    let (fields, values) = (get("fields")?, get("columns")?);
+    if Array::is_array(&fields) && Array::is_array(&values) {
+        let fields: Array = fields.unchecked_into();
+        let values: Array = values.unchecked_into();
+        let nulls = get("nulls")?;
+
+        for (idx, field) in fields.iter().enumerate() {
+            let name = Reflect::get(&field, &JsValue::from_str("name"))?;
+            let column = numbers(&values.get(idx as u32));
+            if let (Some(name), Some(mut column)) = (name.as_string(), column) {
+                let nulls = Reflect::get(&nulls, &JsValue::from(idx as u32))
+                    .ok()
+                    .filter(ArrayBuffer::is_view)
+                    .map(|nulls| Uint8Array::new(&nulls).to_vec());
+                if let Some(nulls) = nulls {
+                    for (value, null) in column.iter_mut().zip(nulls) {
+                        if null != 0 {
+                            *value = f32::NAN;
+                        }
+                    }
+                }
+
+                columns.insert(name, column);
+            }
+        }
+    } else {
+        for key in Object::keys(table.unchecked_ref::<Object>()).iter() {
+            let column = numbers(&Reflect::get(table, &key)?);
+            if let (Some(name), Some(column)) = (key.as_string(), column) {
+                if name != "ra" && name != "dec" {
+                    columns.insert(name, column);
                 }
-                Poll::Pending
             }
         }
     }
+
+    Ok(columns)
 }
-/*use rand::rngs::StdRng;
-use rand::Rng;
-use rand::SeedableRng;*/
-pub struct BuildCatalogIndex<T>
-where
-    T: LonLat<f32> + Clone,
-{
-    pub sources: Vec<T>,
-    num_sorted_sources: usize,
-    i: usize,
-    j: usize,
-    merging: bool,
-    new_sorted_sources: Vec<T>,
-    ready: bool,
-    chunk_size: usize,
-    prev_num_sorted_sources: usize,
+
+use crate::Abort;
+use std::cell::Cell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll};
+
+// Number of rows parsed each time the task is polled
+const CHUNK_OF_ROWS_TO_PARSE: usize = 5000;
+
+/// Task that parses the positions of the sources of a catalog
+///
+/// The rows are parsed by chunks, the task being pending in between. The sources
+/// are returned sorted by the HEALPix cell of depth 7 containing them so that
+/// they can be indexed by `IdxVec::from_sorted_coo`. Each chunk is sorted once
+/// parsed, the sorted chunks of the same length being merged so that a poll
+/// never sorts all the sources. The positions of the rows are returned in double
+/// precision too, to cross-match the sources.
+pub struct ParseTableTask {
+    coordinates: Coordinates,
+    idx: usize,
+    // The sources parsed with the index of their HEALPix cell of depth 7
+    sources: Vec<(u64, Source)>,
+    // The positions of the rows in double precision, NaN for the rows without a position
+    positions: Vec<LonLatT<f64>>,
+    // The lengths of the sorted runs of sources, decreasing
+    runs: Vec<usize>,
+    // The fraction of the rows parsed, shared with the app
+    progress: Rc<Cell<f32>>,
 }
-impl<T> BuildCatalogIndex<T>
-where
-    T: LonLat<f32> + Clone,
-{
-    pub fn new(sources: Vec<T>) -> Self {
-        let num_sorted_sources = 0;
-        let merging = false;
-        let new_sorted_sources = vec![];
-        let i = 0;
-        let j = 0;
-        let ready = false;
-        let prev_num_sorted_sources = 0;
-        let chunk_size = 0;
+
+impl ParseTableTask {
+    pub fn new(coordinates: Coordinates, progress: Rc<Cell<f32>>) -> Self {
+        let sources = Vec::with_capacity(coordinates.len());
+        let positions = Vec::with_capacity(coordinates.len());
+
         Self {
-            num_sorted_sources,
-            merging,
-            i,
-            j,
-            new_sorted_sources,
+            coordinates,
+            idx: 0,
             sources,
-            ready,
-            prev_num_sorted_sources,
-            chunk_size,
+            positions,
+            runs: vec![],
+            progress,
+        }
+    }
+
+    // Merge the two last sorted runs of sources
+    fn merge_last_runs(&mut self) {
+        let len_b = self.runs.pop().unwrap_abort();
+        let len_a = self.runs.pop().unwrap_abort();
+        let start = self.sources.len() - len_a - len_b;
+
+        let (a, b) = self.sources[start..].split_at(len_a);
+        let mut merged = Vec::with_capacity(len_a + len_b);
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            if a[i].0 <= b[j].0 {
+                merged.push(a[i]);
+                i += 1;
+            } else {
+                merged.push(b[j]);
+                j += 1;
+            }
         }
+        merged.extend_from_slice(&a[i..]);
+        merged.extend_from_slice(&b[j..]);
+
+        self.sources.truncate(start);
+        self.sources.extend(merged);
+        self.runs.push(len_a + len_b);
     }
 }
-const CHUNK_OF_SOURCES_TO_SORT: usize = 1000;
-const CHUNK_OF_SORTED_SOURCES_TO_MERGE: usize = 20000;
-use crate::Abort;
-impl<T> Unpin for BuildCatalogIndex<T> where T: LonLat<f32> + Clone {}
 
-impl<T> Stream for BuildCatalogIndex<T>
-where
-    T: LonLat<f32> + Clone,
-{
-    type Item = ();
-
-    /// Attempt to resolve the next item in the stream.
-    /// Returns `Poll::Pending` if not ready, `Poll::Ready(Some(x))` if a value
-    /// is ready, and `Poll::Ready(None)` if the stream has completed.
-    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        // The sources are split into equal sized chunks
-        if self.sources.len() == self.num_sorted_sources {
-            self.ready = true;
-            Poll::Ready(None)
-        } else {
-            let a = self.num_sorted_sources;
-            let b = (a + CHUNK_OF_SOURCES_TO_SORT).min(self.sources.len());
-            // Get a new chunk and sort it
-            if !self.merging {
-                //let mut rng = StdRng::seed_from_u64(0);
-                // Get the chunk to sort
-                (&mut self.sources[a..b]).sort_unstable_by(|s1, s2| {
-                    let s1_lonlat = s1.lonlat();
-                    let s2_lonlat = s2.lonlat();
-
-                    let (s1_lon, s1_lat) =
-                        (s1_lonlat.lon().to_radians(), s1_lonlat.lat().to_radians());
-                    let (s2_lon, s2_lat) =
-                        (s2_lonlat.lon().to_radians(), s2_lonlat.lat().to_radians());
-
-                    let idx1 = healpix::nested::hash(7, s1_lon as f64, s1_lat as f64);
-                    let idx2 = healpix::nested::hash(7, s2_lon as f64, s2_lat as f64);
-
-                    let ordering = idx1.partial_cmp(&idx2).unwrap_abort();
-                    match ordering {
-                        std::cmp::Ordering::Equal => {
-                            //rng.gen::<f64>().partial_cmp(&0.5).unwrap_abort()
-                            let a = (s1_lon * 100000.0).fract();
-                            let b = (s2_lon * 100000.0).fract();
-                            //s1.lon.partial_cmp(&s2.lon).unwrap_abort()
-                            a.partial_cmp(&b).unwrap_abort()
-                        }
-                        _ => ordering,
-                    }
-                    //ordering
-                });
+impl Future for ParseTableTask {
+    // The sources sorted by HEALPix cell and the positions of the rows
+    type Output = (Box<[Source]>, Box<[LonLatT<f64>]>);
 
-                self.chunk_size = b - a;
-                self.prev_num_sorted_sources = a;
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let task = &mut *self;
 
-                self.i = 0;
-                self.j = a;
-                self.num_sorted_sources = 0;
-                self.new_sorted_sources = Vec::with_capacity(b);
+        let len = task.coordinates.len();
+        let end = (task.idx + CHUNK_OF_ROWS_TO_PARSE).min(len);
+        let mut chunk = Vec::with_capacity(end - task.idx);
+        for idx in task.idx..end {
+            let (ra, dec) = task.coordinates.get(idx).unwrap_or((f64::NAN, f64::NAN));
+            let (lon, lat) = (ra.to_radians(), dec.to_radians());
+            task.positions.push(LonLatT::new(Angle(lon), Angle(lat)));
 
-                self.merging = true;
-            } else {
-                // Merge the sorted chunk with the sources already sorted
-                //let (sorted_sources, chunk) = (&self.sources[..b]).split_at(a);
-
-                // Merge the sorted chunk with sources that have been
-                // already sorted
-                let final_size = self.new_sorted_sources.capacity();
-                while self.num_sorted_sources < final_size {
-                    let v = if self.j == self.prev_num_sorted_sources + self.chunk_size {
-                        let v = self.sources[self.i].clone();
-                        self.i += 1;
-                        v
-                    } else if self.i == self.prev_num_sorted_sources {
-                        let v = self.sources[self.j].clone();
-                        self.j += 1;
-                        v
-                    } else {
-                        let s1 = &self.sources[self.j];
-                        let s2 = &self.sources[self.i];
-                        let s1_lonlat = s1.lonlat();
-                        let s2_lonlat = s2.lonlat();
-
-                        let (s1_lon, s1_lat) =
-                            (s1_lonlat.lon().to_radians(), s1_lonlat.lat().to_radians());
-                        let (s2_lon, s2_lat) =
-                            (s2_lonlat.lon().to_radians(), s2_lonlat.lat().to_radians());
-
-                        let p1 = healpix::nested::hash(7, s1_lon as f64, s1_lat as f64);
-                        let p2 = healpix::nested::hash(7, s2_lon as f64, s2_lat as f64);
-                        if p1 <= p2 {
-                            let v = self.sources[self.j].clone();
-                            self.j += 1;
-                            v
-                        } else {
-                            let v = self.sources[self.i].clone();
-                            self.i += 1;
-                            v
-                        }
-                    };
+            if !lon.is_nan() {
+                let source = Source::new(
+                    LonLatT::new(Angle(lon as f32), Angle(lat as f32)),
+                    idx as u32,
+                );
 
-                    self.new_sorted_sources.push(v);
-                    self.num_sorted_sources += 1;
+                chunk.push((cell_d7(&source), source));
+            }
+        }
+        task.idx = end;
 
-                    // Every 10000 items sorted, we do a pending
-                    if self.num_sorted_sources % CHUNK_OF_SORTED_SOURCES_TO_MERGE == 0 {
-                        return Poll::Pending;
-                    }
-                }
-                // replace 0 -> num_sorted_sources
-                let end = self.num_sorted_sources;
-                let new_sorted_sources = self.new_sorted_sources.clone();
-                self.sources.splice(..end, new_sorted_sources);
-                self.merging = false;
+        if !chunk.is_empty() {
+            chunk.sort_unstable_by_key(|&(hash, _)| hash);
+            task.runs.push(chunk.len());
+            task.sources.extend(chunk);
+        }
+        // Merge the runs like the digits of a binary counter, the work of a poll
+        // being linear in the number of sources at most
+        while let [.., len_a, len_b] = task.runs[..] {
+            if len_a > len_b && end < len {
+                break;
             }
+            task.merge_last_runs();
+        }
+
+        if end < len {
+            task.progress.set(end as f32 / len as f32);
+            return Poll::Pending;
+        }
+
+        let sources = std::mem::take(&mut task.sources);
+        let positions = std::mem::take(&mut task.positions);
+        task.progress.set(1.0);
+
+        let sources = sources.into_iter().map(|(_, source)| source).collect();
+        Poll::Ready((sources, positions.into_boxed_slice()))
+    }
+}
+
+// The number of cells of depth 7 containing sources matched by poll
+const NUM_CELLS_TO_MATCH: usize = 256;
+
+/// Task that cross-matches the sources of two catalogs
+///
+/// The sources of a few HEALPix cells are matched each time the task is polled
+pub struct CrossMatchTask {
+    xmatch: Option<CrossMatch>,
+}
+
+impl CrossMatchTask {
+    pub fn new(xmatch: CrossMatch) -> Self {
+        Self {
+            xmatch: Some(xmatch),
+        }
+    }
+}
 
+impl Future for CrossMatchTask {
+    type Output = Matches;
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let task = &mut *self;
+
+        if task.xmatch.as_mut().unwrap_abort().step(NUM_CELLS_TO_MATCH) {
+            Poll::Ready(task.xmatch.take().unwrap_abort().finish())
+        } else {
             Poll::Pending
         }
     }
 }
 
+/// Task that cuts an image into the tiles of a HiPS
+///
+/// A step of the generation is done each time the task is polled
+pub struct HiPSGenTask<S> {
+    generator: Option<HiPSGen<S>>,
+    // The fraction of the tiles computed, shared with the app
+    progress: Rc<Cell<f32>>,
+}
+
+impl<S> HiPSGenTask<S> {
+    pub fn new(generator: HiPSGen<S>, progress: Rc<Cell<f32>>) -> Self {
+        Self {
+            generator: Some(generator),
+            progress,
+        }
+    }
+}
+
+impl<S> Future for HiPSGenTask<S>
+where
+    S: hipsgen::Source + Unpin,
+{
+    type Output = Result<Vec<u8>, hipsgen::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let task = &mut *self;
+
+        let generator = task.generator.as_mut().unwrap_abort();
+        match generator.step() {
+            Ok(false) => {
+                task.progress.set(generator.progress());
+                Poll::Pending
+            }
+            Ok(true) => {
+                task.progress.set(1.0);
+                Poll::Ready(task.generator.take().unwrap_abort().finish())
+            }
+            Err(err) => Poll::Ready(Err(err)),
+        }
+    }
+}
+
+/*
+
 use cgmath::Vector3;
 /// Task that send a tile to the GPU
 pub struct ImageTile2GpuTask<I>
@@ -329,3 +538,70 @@ where
     }
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::{Column, Coordinates, ParseTableTask, CHUNK_OF_ROWS_TO_PARSE};
+    use futures::task::noop_waker;
+    use std::cell::Cell;
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{Context, Poll};
+
+    #[test]
+    fn parse_table_by_chunks() {
+        let num_rows = 2 * CHUNK_OF_ROWS_TO_PARSE + 10;
+        let ra = (0..num_rows)
+            .map(|i| (i * 7 % 360) as f64)
+            .collect::<Vec<_>>();
+        let mut dec = (0..num_rows)
+            .map(|i| (i % 180) as f64 - 89.5)
+            .collect::<Vec<_>>();
+        // Rows without a valid position are skipped
+        dec[0] = f64::NAN;
+        dec[1] = 95.0;
+
+        let coordinates = Coordinates::Columns {
+            ra: Column::Values(ra),
+            dec: Column::Values(dec),
+        };
+        let progress = Rc::new(Cell::new(0.0));
+        let mut task = ParseTableTask::new(coordinates, progress.clone());
+
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut num_polls = 0;
+        let (sources, positions) = loop {
+            num_polls += 1;
+            match Pin::new(&mut task).poll(&mut cx) {
+                Poll::Ready(parsed) => break parsed,
+                Poll::Pending => assert!(progress.get() < 1.0),
+            }
+        };
+
+        assert_eq!(num_polls, 3);
+        assert_eq!(progress.get(), 1.0);
+        assert_eq!(sources.len(), num_rows - 2);
+        // The positions are indexed by the rows
+        assert_eq!(positions.len(), num_rows);
+        assert!(positions[0].lon().to_radians().is_nan());
+        assert_eq!(
+            positions[2].lat().to_radians(),
+            (2.0 - 89.5_f64).to_radians()
+        );
+
+        let hashes = sources
+            .iter()
+            .map(|s| {
+                let (lon, lat) = (s.lonlat.lon(), s.lonlat.lat());
+                healpix::nested::hash(7, lon.to_radians() as f64, lat.to_radians() as f64)
+            })
+            .collect::<Vec<_>>();
+        assert!(hashes.windows(2).all(|w| w[0] <= w[1]));
+        // The sources refer to the rows of the table
+        let mut rows = sources.iter().map(|s| s.row).collect::<Vec<_>>();
+        rows.sort_unstable();
+        assert_eq!(rows, (2..num_rows as u32).collect::<Vec<_>>());
+    }
+}
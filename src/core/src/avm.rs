@@ -0,0 +1,553 @@
+//! Astronomy Visualization Metadata (AVM) reader
+//!
+//! Outreach JPEG/PNG images embed their astrometry as AVM tags inside the XMP
+//! packet of the file. This module extracts the XMP packet, reads the spatial
+//! AVM tags and converts them into the FITS WCS keywords understood by `wcs::WCS`.
+//!
+//! AVM is defined at: https://www.virtualastronomy.org/avm_metadata.php
+use std::collections::HashMap;
+use std::fmt;
+
+use serde_json::{Map, Value};
+use wasm_bindgen::JsValue;
+use wcs::WCS;
+
+use crate::renderable::image::wcs_keywords::WCSKeywords;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    XMPNotFound,
+    MissingTags { tags: Vec<&'static str> },
+    InvalidTag { tag: &'static str, value: String },
+    WCS { message: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::XMPNotFound => write!(f, "No XMP packet found in the image"),
+            Error::MissingTags { tags } => write!(f, "Missing AVM tags: {}", tags.join(", ")),
+            Error::InvalidTag { tag, value } => {
+                write!(f, "Invalid value for the AVM tag {}: {:?}", tag, value)
+            }
+            Error::WCS { message } => write!(f, "WCS parsing error: reason: {}", message),
+        }
+    }
+}
+
+impl From<Error> for JsValue {
+    fn from(err: Error) -> Self {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+const COORDINATE_FRAME: &str = "Spatial.CoordinateFrame";
+const EQUINOX: &str = "Spatial.Equinox";
+const REFERENCE_VALUE: &str = "Spatial.ReferenceValue";
+const REFERENCE_DIMENSION: &str = "Spatial.ReferenceDimension";
+const REFERENCE_PIXEL: &str = "Spatial.ReferencePixel";
+const SCALE: &str = "Spatial.Scale";
+const ROTATION: &str = "Spatial.Rotation";
+const PROJECTION: &str = "Spatial.CoordsystemProjection";
+const CD_MATRIX: &str = "Spatial.CDMatrix";
+const FITS_HEADER: &str = "Spatial.FITSheader";
+
+const SPATIAL_TAGS: &[&str] = &[
+    COORDINATE_FRAME,
+    EQUINOX,
+    REFERENCE_VALUE,
+    REFERENCE_DIMENSION,
+    REFERENCE_PIXEL,
+    SCALE,
+    ROTATION,
+    PROJECTION,
+    CD_MATRIX,
+    FITS_HEADER,
+];
+
+// Keywords looked for in a raw FITS header embedded in the XMP
+const FITS_KEYWORDS: &[&str] = &[
+    "CTYPE1", "CTYPE2", "CRPIX1", "CRPIX2", "CRVAL1", "CRVAL2", "LONPOLE", "LATPOLE", "CDELT1",
+    "CDELT2", "CROTA2", "PC1_1", "PC1_2", "PC2_1", "PC2_2", "CD1_1", "CD1_2", "CD2_1", "CD2_2",
+    "EQUINOX", "RADESYS",
+];
+
+/// Extract the XMP packet of a JPEG or PNG file
+///
+/// The packet is stored uncompressed in the APP1 segment of JPEG files
+/// and in an iTXt chunk of PNG files so it can be looked for directly in the bytes.
+pub fn find_xmp(bytes: &[u8]) -> Option<String> {
+    let (start, end) = [
+        (&b"<x:xmpmeta"[..], &b"</x:xmpmeta>"[..]),
+        (b"<x:xapmeta", b"</x:xapmeta>"),
+        (b"<rdf:RDF", b"</rdf:RDF>"),
+    ]
+    .iter()
+    .find_map(|(start, end)| {
+        let s = find(bytes, start)?;
+        let e = find(&bytes[s..], end)? + s + end.len();
+        Some((s, e))
+    })?;
+
+    Some(String::from_utf8_lossy(&bytes[start..end]).into_owned())
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// The spatial AVM tags found in a XMP packet
+#[derive(Debug, Default)]
+pub struct Tags {
+    values: HashMap<&'static str, Vec<String>>,
+}
+
+impl Tags {
+    /// Read the spatial tags of a XMP packet
+    ///
+    /// A tag can be given as an attribute (`avm:Spatial.Equinox="J2000"`) or as an element
+    /// whose value is either its text or the items of a `rdf:Seq`.
+    pub fn parse(xmp: &str) -> Self {
+        let mut values = HashMap::new();
+
+        for tag in SPATIAL_TAGS {
+            if let Some(v) = read_tag(xmp, &format!("avm:{}", tag)) {
+                values.insert(*tag, v);
+            }
+        }
+
+        Self { values }
+    }
+
+    pub fn get(&self, tag: &str) -> Option<&[String]> {
+        self.values.get(tag).map(|v| &v[..])
+    }
+
+    fn first(&self, tag: &str) -> Option<&str> {
+        self.get(tag).and_then(|v| v.first()).map(|v| v.as_str())
+    }
+
+    fn floats<const N: usize>(&self, tag: &'static str) -> Result<Option<[f64; N]>, Error> {
+        if let Some(values) = self.get(tag) {
+            let invalid = || Error::InvalidTag {
+                tag,
+                value: values.join(" "),
+            };
+
+            if values.len() < N {
+                return Err(invalid());
+            }
+
+            let mut floats = [0.0; N];
+            for (f, v) in floats.iter_mut().zip(values.iter()) {
+                *f = v.trim().parse::<f64>().map_err(|_| invalid())?;
+            }
+
+            Ok(Some(floats))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Convert the tags into FITS WCS keywords
+    ///
+    /// # Arguments
+    ///
+    /// * `width` - The width of the image in pixels
+    /// * `height` - The height of the image in pixels
+    pub fn to_wcs_keywords(&self, width: u64, height: u64) -> Result<Map<String, Value>, Error> {
+        let mut keywords = Map::new();
+        keywords.insert("NAXIS".to_string(), 2.into());
+        keywords.insert("NAXIS1".to_string(), width.into());
+        keywords.insert("NAXIS2".to_string(), height.into());
+
+        let reference_value = self.floats::<2>(REFERENCE_VALUE)?;
+        let reference_pixel = self.floats::<2>(REFERENCE_PIXEL)?;
+        let scale = self.floats::<2>(SCALE)?;
+        let cd = self.floats::<4>(CD_MATRIX)?;
+
+        let mut missing = vec![];
+        if reference_value.is_none() {
+            missing.push(REFERENCE_VALUE);
+        }
+        if reference_pixel.is_none() {
+            missing.push(REFERENCE_PIXEL);
+        }
+        if scale.is_none() && cd.is_none() {
+            missing.push(SCALE);
+        }
+
+        if !missing.is_empty() {
+            // Some images only give their astrometry as a raw FITS header
+            let cards = self
+                .first(FITS_HEADER)
+                .map(read_fits_cards)
+                .unwrap_or_default();
+
+            return if cards.contains_key("CTYPE1") && cards.contains_key("CRVAL1") {
+                keywords.extend(cards);
+                Ok(keywords)
+            } else {
+                Err(Error::MissingTags { tags: missing })
+            };
+        }
+
+        let frame = self.first(COORDINATE_FRAME).unwrap_or("ICRS").trim();
+        let (lon, lat, radesys) = match frame {
+            "ICRS" => ("RA---", "DEC--", Some("ICRS")),
+            "FK5" => ("RA---", "DEC--", Some("FK5")),
+            "FK4" => ("RA---", "DEC--", Some("FK4")),
+            "GAL" => ("GLON-", "GLAT-", None),
+            "ECL" => ("ELON-", "ELAT-", None),
+            "SGAL" => ("SLON-", "SLAT-", None),
+            _ => {
+                return Err(Error::InvalidTag {
+                    tag: COORDINATE_FRAME,
+                    value: frame.to_string(),
+                })
+            }
+        };
+
+        let projection = self.first(PROJECTION).unwrap_or("TAN").trim();
+        if projection.len() != 3 {
+            return Err(Error::InvalidTag {
+                tag: PROJECTION,
+                value: projection.to_string(),
+            });
+        }
+
+        keywords.insert(
+            "CTYPE1".to_string(),
+            format!("{}{}", lon, projection).into(),
+        );
+        keywords.insert(
+            "CTYPE2".to_string(),
+            format!("{}{}", lat, projection).into(),
+        );
+        if let Some(radesys) = radesys {
+            keywords.insert("RADESYS".to_string(), radesys.into());
+        }
+
+        if let Some(equinox) = self.first(EQUINOX) {
+            // e.g. "J2000", "B1950" or "2000.0"
+            let equinox = equinox
+                .trim()
+                .trim_start_matches(['J', 'B'])
+                .parse::<f64>()
+                .map_err(|_| Error::InvalidTag {
+                    tag: EQUINOX,
+                    value: equinox.to_string(),
+                })?;
+            keywords.insert("EQUINOX".to_string(), equinox.into());
+        }
+
+        // The reference pixel and the scale are given for an image of
+        // dimension Spatial.ReferenceDimension that can differ from the one received
+        let (fx, fy) = if let Some([w, h]) = self.floats::<2>(REFERENCE_DIMENSION)? {
+            (width as f64 / w, height as f64 / h)
+        } else {
+            (1.0, 1.0)
+        };
+
+        // The checks above ensure these exist
+        let [crval1, crval2] = reference_value.unwrap_or_default();
+        let [crpix1, crpix2] = reference_pixel.unwrap_or_default();
+        keywords.insert("CRVAL1".to_string(), crval1.into());
+        keywords.insert("CRVAL2".to_string(), crval2.into());
+        keywords.insert("CRPIX1".to_string(), ((crpix1 - 0.5) * fx + 0.5).into());
+        keywords.insert("CRPIX2".to_string(), ((crpix2 - 0.5) * fy + 0.5).into());
+
+        if let Some([cd11, cd12, cd21, cd22]) = cd {
+            keywords.insert("CD1_1".to_string(), (cd11 / fx).into());
+            keywords.insert("CD1_2".to_string(), (cd12 / fy).into());
+            keywords.insert("CD2_1".to_string(), (cd21 / fx).into());
+            keywords.insert("CD2_2".to_string(), (cd22 / fy).into());
+        } else if let Some([cdelt1, cdelt2]) = scale {
+            keywords.insert("CDELT1".to_string(), (cdelt1 / fx).into());
+            keywords.insert("CDELT2".to_string(), (cdelt2 / fy).into());
+
+            if let Some([rotation]) = self.floats::<1>(ROTATION)? {
+                keywords.insert("CROTA2".to_string(), rotation.into());
+            }
+        }
+
+        Ok(keywords)
+    }
+}
+
+// Read the value(s) of a tag given either as an attribute or as an element
+fn read_tag(xmp: &str, key: &str) -> Option<Vec<String>> {
+    let mut from = 0;
+    while let Some(idx) = xmp[from..].find(key) {
+        let start = from + idx;
+        let end = start + key.len();
+        from = end;
+
+        // Discard tags whose name only begins with the key
+        let rest = xmp[end..].trim_start();
+        let prev = xmp[..start].chars().last();
+
+        if prev == Some('<') {
+            // Element: <avm:Tag ...>content</avm:Tag>
+            if !(rest.starts_with('>') || xmp[end..].starts_with(char::is_whitespace)) {
+                continue;
+            }
+
+            let content_start = end + xmp[end..].find('>')? + 1;
+            if xmp[..content_start].ends_with("/>") {
+                continue;
+            }
+            let content_end = content_start + xmp[content_start..].find(&format!("</{}>", key))?;
+
+            let values = text_items(&xmp[content_start..content_end]);
+            if !values.is_empty() {
+                return Some(values);
+            }
+        } else if prev.map(|c| c.is_whitespace()).unwrap_or(false) && rest.starts_with('=') {
+            // Attribute: avm:Tag="value"
+            let rest = rest[1..].trim_start();
+            let quote = rest.chars().next()?;
+            if quote != '"' && quote != '\'' {
+                continue;
+            }
+
+            let value = &rest[1..];
+            let value = &value[..value.find(quote)?];
+            return Some(vec![unescape(value.trim())]);
+        }
+    }
+
+    None
+}
+
+// The non-empty texts between the markups of an element content
+fn text_items(content: &str) -> Vec<String> {
+    let mut items = vec![];
+    let mut text = String::new();
+    let mut in_markup = false;
+
+    for c in content.chars() {
+        match c {
+            '<' => {
+                in_markup = true;
+                if !text.trim().is_empty() {
+                    items.push(unescape(text.trim()));
+                }
+                text.clear();
+            }
+            '>' => in_markup = false,
+            _ if !in_markup => text.push(c),
+            _ => (),
+        }
+    }
+    if !text.trim().is_empty() {
+        items.push(unescape(text.trim()));
+    }
+
+    items
+}
+
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+// Look for `KEY = value` cards in a text
+fn read_fits_cards(text: &str) -> Map<String, Value> {
+    let mut cards = Map::new();
+    for key in FITS_KEYWORDS {
+        let mut from = 0;
+        while let Some(idx) = text[from..].find(key) {
+            let start = from + idx;
+            from = start + key.len();
+
+            // The keyword must be followed by spaces and an equal sign
+            let rest = text[from..].trim_start_matches(' ');
+            if let Some(rest) = rest.strip_prefix('=') {
+                let value = rest.split_whitespace().next().unwrap_or("");
+                let value = if let Ok(v) = value.parse::<f64>() {
+                    v.into()
+                } else {
+                    value.trim_matches(['\'', '"']).into()
+                };
+
+                cards.insert(key.to_string(), value);
+                break;
+            }
+        }
+    }
+
+    cards
+}
+
+/// Build the WCS of a JPEG/PNG image from the AVM tags it embeds, along with
+/// the keywords defining it
+///
+/// # Arguments
+///
+/// * `bytes` - The bytes of the image file
+/// * `width` - The width of the decoded image in pixels
+/// * `height` - The height of the decoded image in pixels
+pub fn parse_wcs(bytes: &[u8], width: u64, height: u64) -> Result<(WCS, WCSKeywords), Error> {
+    let xmp = find_xmp(bytes).ok_or(Error::XMPNotFound)?;
+    let keywords = WCSKeywords::new(Tags::parse(&xmp).to_wcs_keywords(width, height)?);
+
+    let params = keywords.params().map_err(|e| Error::WCS {
+        message: e.to_string(),
+    })?;
+    let wcs = WCS::new(&params).map_err(|e| Error::WCS {
+        message: format!("{:?}", e),
+    })?;
+
+    Ok((wcs, keywords))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_xmp, Error, Tags};
+
+    const XMP: &str = r#"<?xpacket begin="" id="W5M0MpCehiHzreSzNTczkc9d"?>
+<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:avm="http://www.communicatingastronomy.org/avm/1.0/"
+    avm:Spatial.CoordinateFrame="ICRS"
+    avm:Spatial.Equinox="J2000"
+    avm:Spatial.Rotation="-12.5">
+   <avm:Spatial.ReferenceValue>
+    <rdf:Seq>
+     <rdf:li>83.8221</rdf:li>
+     <rdf:li>-5.3911</rdf:li>
+    </rdf:Seq>
+   </avm:Spatial.ReferenceValue>
+   <avm:Spatial.ReferenceDimension>
+    <rdf:Seq>
+     <rdf:li>2000</rdf:li>
+     <rdf:li>1000</rdf:li>
+    </rdf:Seq>
+   </avm:Spatial.ReferenceDimension>
+   <avm:Spatial.ReferencePixel>
+    <rdf:Seq>
+     <rdf:li>1000.5</rdf:li>
+     <rdf:li>500.5</rdf:li>
+    </rdf:Seq>
+   </avm:Spatial.ReferencePixel>
+   <avm:Spatial.Scale>
+    <rdf:Seq>
+     <rdf:li>-0.0002</rdf:li>
+     <rdf:li>0.0002</rdf:li>
+    </rdf:Seq>
+   </avm:Spatial.Scale>
+   <avm:Spatial.CoordsystemProjection>TAN</avm:Spatial.CoordsystemProjection>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>
+<?xpacket end="w"?>"#;
+
+    #[test]
+    fn extract_xmp_from_jpeg_bytes() {
+        let mut bytes = vec![0xFF, 0xD8, 0xFF, 0xE1, 0x00, 0x10];
+        bytes.extend_from_slice(b"http://ns.adobe.com/xap/1.0/\0");
+        bytes.extend_from_slice(XMP.as_bytes());
+        bytes.extend_from_slice(&[0xFF, 0xD9]);
+
+        let xmp = find_xmp(&bytes).unwrap();
+        assert!(xmp.starts_with("<x:xmpmeta"));
+        assert!(xmp.ends_with("</x:xmpmeta>"));
+
+        assert_eq!(find_xmp(&[0xFF, 0xD8, 0xFF, 0xD9]), None);
+    }
+
+    #[test]
+    fn read_tags() {
+        let tags = Tags::parse(XMP);
+
+        assert_eq!(
+            tags.get("Spatial.CoordinateFrame"),
+            Some(&["ICRS".to_string()][..])
+        );
+        assert_eq!(
+            tags.get("Spatial.Rotation"),
+            Some(&["-12.5".to_string()][..])
+        );
+        assert_eq!(
+            tags.get("Spatial.ReferenceValue"),
+            Some(&["83.8221".to_string(), "-5.3911".to_string()][..])
+        );
+        assert_eq!(
+            tags.get("Spatial.CoordsystemProjection"),
+            Some(&["TAN".to_string()][..])
+        );
+        assert_eq!(tags.get("Spatial.CDMatrix"), None);
+    }
+
+    #[test]
+    fn avm_to_wcs_keywords() {
+        // The received image is half the size of the reference one
+        let keywords = Tags::parse(XMP).to_wcs_keywords(1000, 500).unwrap();
+
+        assert_eq!(keywords["CTYPE1"], "RA---TAN");
+        assert_eq!(keywords["CTYPE2"], "DEC--TAN");
+        assert_eq!(keywords["RADESYS"], "ICRS");
+        assert_eq!(keywords["EQUINOX"], 2000.0);
+        assert_eq!(keywords["NAXIS1"], 1000);
+        assert_eq!(keywords["CRVAL1"], 83.8221);
+        assert_eq!(keywords["CRPIX1"], 500.5);
+        assert_eq!(keywords["CRPIX2"], 250.5);
+        assert_eq!(keywords["CDELT1"], -0.0004);
+        assert_eq!(keywords["CDELT2"], 0.0004);
+        assert_eq!(keywords["CROTA2"], -12.5);
+    }
+
+    #[test]
+    fn missing_tags() {
+        let xmp = r#"<rdf:Description avm:Spatial.CoordinateFrame="GAL"
+            avm:Spatial.ReferencePixel="12"></rdf:Description>"#;
+
+        // A reference pixel with only one value is invalid
+        assert_eq!(
+            Tags::parse(xmp).to_wcs_keywords(10, 10),
+            Err(Error::InvalidTag {
+                tag: "Spatial.ReferencePixel",
+                value: "12".to_string()
+            })
+        );
+
+        let xmp = r#"<rdf:Description avm:Spatial.CoordinateFrame="GAL"></rdf:Description>"#;
+        assert_eq!(
+            Tags::parse(xmp).to_wcs_keywords(10, 10),
+            Err(Error::MissingTags {
+                tags: vec![
+                    "Spatial.ReferenceValue",
+                    "Spatial.ReferencePixel",
+                    "Spatial.Scale"
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn cd_matrix_and_fits_header() {
+        let xmp = r#"<rdf:Description avm:Spatial.CoordinateFrame="GAL">
+            <avm:Spatial.ReferenceValue><rdf:Seq><rdf:li>10</rdf:li><rdf:li>20</rdf:li></rdf:Seq></avm:Spatial.ReferenceValue>
+            <avm:Spatial.ReferencePixel><rdf:Seq><rdf:li>5</rdf:li><rdf:li>5</rdf:li></rdf:Seq></avm:Spatial.ReferencePixel>
+            <avm:Spatial.CDMatrix><rdf:Seq><rdf:li>-1</rdf:li><rdf:li>0</rdf:li><rdf:li>0</rdf:li><rdf:li>1</rdf:li></rdf:Seq></avm:Spatial.CDMatrix>
+        </rdf:Description>"#;
+        let keywords = Tags::parse(xmp).to_wcs_keywords(10, 10).unwrap();
+        assert_eq!(keywords["CTYPE1"], "GLON-TAN");
+        assert_eq!(keywords["CD1_1"], -1.0);
+        assert!(!keywords.contains_key("RADESYS"));
+
+        let xmp = r#"<avm:Spatial.FITSheader>CTYPE1  = 'RA---SIN'
+            CTYPE2  = 'DEC--SIN' CRVAL1  = 10.5 / ra CRVAL2 = -3 CRPIX1 = 1</avm:Spatial.FITSheader>"#;
+        let keywords = Tags::parse(xmp).to_wcs_keywords(10, 10).unwrap();
+        assert_eq!(keywords["CTYPE1"], "RA---SIN");
+        assert_eq!(keywords["CRVAL1"], 10.5);
+        assert_eq!(keywords["CRVAL2"], -3.0);
+    }
+}
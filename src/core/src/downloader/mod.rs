@@ -5,16 +5,23 @@ use std::collections::HashSet;
 
 use query::QueryId;
 
+/// Default memory budget of the resources kept once received, in bytes
+pub const DEFAULT_RESOURCE_CACHE_BUDGET: usize = 256 * 1024 * 1024;
+
 pub struct Downloader {
     // Current requests
     requests: Vec<RequestType>,
     queried_list: HashSet<QueryId>,
 
+    // The tiles received, kept so that they are not downloaded again
+    // when they come back into the view
     cache: Cache<QueryId, Resource>,
+    // The queries served by the cache, delivered with the next received resources
     queried_cached_ids: Vec<QueryId>,
 }
 
-use crate::fifo_cache::Cache;
+use crate::lru_cache::{Cache, CacheStats};
+use crate::time::Time;
 
 use query::Query;
 use request::{RequestType, Resource};
@@ -23,7 +30,7 @@ impl Downloader {
     pub fn new() -> Downloader {
         let requests = Vec::with_capacity(32);
         let queried_list = HashSet::with_capacity(64);
-        let cache = Cache::new();
+        let cache = Cache::new(DEFAULT_RESOURCE_CACHE_BUDGET);
         let queried_cached_ids = Vec::with_capacity(64);
         Self {
             requests,
@@ -38,25 +45,24 @@ impl Downloader {
     where
         T: Query,
     {
-        let id = query.id();
-        if self.cache.contains(id) {
-            //self.queried_cached_urls.push(url.clone());
-            false
-        } else {
-            let query_id = query.id();
+        let query_id = query.id();
 
-            let not_already_requested = !self.queried_list.contains(query_id);
+        let not_already_requested = !self.queried_list.contains(query_id);
 
-            // The cell is not already requested
-            if not_already_requested {
-                self.queried_list.insert(query_id.to_string());
+        // The cell is not already requested
+        if not_already_requested {
+            self.queried_list.insert(query_id.to_string());
 
+            if self.cache.get(query_id).is_some() {
+                // The resource has already been received
+                self.queried_cached_ids.push(query_id.to_string());
+            } else {
                 let request = T::Request::from(query);
                 self.requests.push(request.into());
             }
-
-            not_already_requested
         }
+
+        not_already_requested
     }
 
     pub fn get_received_resources(&mut self) -> Vec<Resource> {
@@ -84,9 +90,24 @@ impl Downloader {
             self.queried_list.remove(&query_id);
         }
 
-        while let Some(id) = self.queried_cached_ids.pop() {
-            if let Some(rsc) = self.cache.extract(&id) {
-                rscs.push(rsc);
+        // Keep the tiles received
+        for rsc in &rscs {
+            if let Resource::Tile(tile) = rsc {
+                if let (None, Some(size)) = (tile.failure(), tile.byte_size()) {
+                    self.cache
+                        .insert(tile.get_id().clone(), Resource::Tile(tile.clone()), size);
+                }
+            }
+        }
+
+        for id in self.queried_cached_ids.drain(..) {
+            self.queried_list.remove(&id);
+
+            if let Some(Resource::Tile(tile)) = self.cache.get(&id) {
+                let mut tile = tile.clone();
+                // Delivered now, so that the tile is blended as a newly received one
+                tile.time_req = Time::now();
+                rscs.push(Resource::Tile(tile));
             }
         }
 
@@ -97,8 +118,19 @@ impl Downloader {
         self.queried_list.contains(id)
     }
 
-    pub fn _delay_rsc(&mut self, rsc: Resource) {
-        self.queried_cached_ids.push(rsc.id().clone());
-        self.cache.insert(rsc.id().clone(), rsc);
+    /// Cancel a request not resolved yet. The request is resolved as failed
+    pub fn abort(&self, id: &QueryId) {
+        if let Some(request) = self.requests.iter().find(|request| request.id() == id) {
+            request.abort();
+        }
+    }
+
+    /// Change the memory budget of the resources kept once received, in bytes
+    pub fn set_cache_budget(&mut self, budget: usize) {
+        self.cache.set_budget(budget);
+    }
+
+    pub fn get_cache_stats(&self) -> CacheStats {
+        self.cache.get_stats()
     }
 }
@@ -20,6 +20,8 @@ pub struct Tile {
     // The total url of the query
     pub url: Url,
     pub id: QueryId,
+    // The frame of the cube, 0 for non cube HiPS
+    pub frame: u32,
 }
 
 use crate::renderable::CreatorDid;
@@ -30,6 +32,7 @@ impl Tile {
         hips_cdid: String,
         hips_url: String,
         format: ImageFormatType,
+        frame: u32,
     ) -> Self {
         let ext = format.get_ext_file();
 
@@ -37,12 +40,14 @@ impl Tile {
 
         let dir_idx = (idx / 10000) * 10000;
 
+        // Tiles of the first frame of a cube do not carry the frame suffix
+        let suffix = frame_suffix(frame);
         let url = format!(
-            "{}/Norder{}/Dir{}/Npix{}.{}",
-            hips_url, depth, dir_idx, idx, ext
+            "{}/Norder{}/Dir{}/Npix{}{}.{}",
+            hips_url, depth, dir_idx, idx, suffix, ext
         );
 
-        let id = format!("{}{}{}{}", hips_cdid, depth, idx, ext);
+        let id = format!("{}{}{}{}{}", hips_cdid, depth, idx, suffix, ext);
 
         Tile {
             hips_cdid,
@@ -50,10 +55,19 @@ impl Tile {
             cell: *cell,
             format,
             id,
+            frame,
         }
     }
 }
 
+fn frame_suffix(frame: u32) -> String {
+    if frame > 0 {
+        format!("_{}", frame)
+    } else {
+        String::new()
+    }
+}
+
 use super::request::tile::TileRequest;
 impl Query for Tile {
     type Request = TileRequest;
@@ -73,6 +87,7 @@ pub struct Allsky {
     // The total url of the query
     pub url: Url,
     pub id: QueryId,
+    pub frame: u32,
 }
 
 impl Allsky {
@@ -82,10 +97,12 @@ impl Allsky {
         let texture_size = cfg.get_texture_size();
         let format = cfg.get_format();
         let ext = format.get_ext_file();
+        let frame = cfg.get_cube_frame();
+        let suffix = frame_suffix(frame);
 
-        let url = format!("{}/Norder3/Allsky.{}", cfg.get_root_url(), ext);
+        let url = format!("{}/Norder3/Allsky{}.{}", cfg.get_root_url(), suffix, ext);
 
-        let id = format!("{}Allsky{}", cfg.get_creator_did(), ext);
+        let id = format!("{}Allsky{}{}", cfg.get_creator_did(), suffix, ext);
 
         Allsky {
             tile_size,
@@ -94,6 +111,7 @@ impl Allsky {
             url,
             format,
             id,
+            frame,
         }
     }
 }
@@ -144,6 +162,40 @@ impl Query for PixelMetadata {
     }
 }
 
+/* ---------------------------------- */
+pub struct MirrorProbe {
+    // The mirror probed
+    pub mirror_url: Url,
+    pub hips_cdid: CreatorDid,
+    // The total url of the query
+    pub url: Url,
+    pub id: QueryId,
+}
+
+impl MirrorProbe {
+    pub fn new(mirror_url: String, hips_cdid: CreatorDid) -> Self {
+        // The properties file is small and present on every mirror
+        let url = format!("{}/properties", mirror_url);
+        let id = format!("{}{}", hips_cdid, url);
+
+        MirrorProbe {
+            mirror_url,
+            hips_cdid,
+            url,
+            id,
+        }
+    }
+}
+
+use super::request::probe::MirrorProbeRequest;
+impl Query for MirrorProbe {
+    type Request = MirrorProbeRequest;
+
+    fn id(&self) -> &QueryId {
+        &self.id
+    }
+}
+
 /* ---------------------------------- */
 pub struct Moc {
     // The total url of the query
@@ -169,3 +221,64 @@ impl Query for Moc {
         &self.url
     }
 }
+
+/* ---------------------------------- */
+pub struct CatalogFile {
+    // The name of the progressive catalogue
+    pub name: String,
+    pub file: File,
+    // The total url of the query
+    pub url: Url,
+    pub id: QueryId,
+}
+
+use crate::renderable::catalog::hips_cat::File;
+impl CatalogFile {
+    pub fn new(name: String, root_url: &str, file: File) -> Self {
+        let url = format!("{}/{}", root_url, file.path());
+        let id = format!("{}{}", name, url);
+
+        CatalogFile {
+            name,
+            file,
+            url,
+            id,
+        }
+    }
+}
+
+use super::request::catalog::CatalogFileRequest;
+impl Query for CatalogFile {
+    type Request = CatalogFileRequest;
+
+    fn id(&self) -> &QueryId {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Tile;
+    use crate::healpix::cell::HEALPixCell;
+    use al_api::hips::ImageExt;
+    use al_core::image::format::{ChannelType, ImageFormatType};
+
+    #[test]
+    fn cube_tile_url() {
+        let format = ImageFormatType {
+            ext: ImageExt::Fits,
+            channel: ChannelType::R32F,
+        };
+        let cell = HEALPixCell(3, 12345);
+
+        let tile = Tile::new(&cell, "ivo://cube".into(), "http://hips".into(), format, 0);
+        assert_eq!(tile.url, "http://hips/Norder3/Dir10000/Npix12345.fits");
+
+        let tile_frame = Tile::new(&cell, "ivo://cube".into(), "http://hips".into(), format, 7);
+        assert_eq!(
+            tile_frame.url,
+            "http://hips/Norder3/Dir10000/Npix12345_7.fits"
+        );
+        assert_ne!(tile.id, tile_frame.id);
+    }
+}
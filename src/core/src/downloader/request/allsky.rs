@@ -14,6 +14,7 @@ pub struct AllskyRequest {
     pub url: Url,
     pub depth_tile: u8,
     pub id: QueryId,
+    pub frame: u32,
 
     request: Request<Vec<ImageType>>,
 }
@@ -80,6 +81,7 @@ impl From<query::Allsky> for AllskyRequest {
             hips_cdid,
             texture_size,
             id,
+            frame,
         } = query;
 
         let depth_tile = crate::math::utils::log_2_unchecked(texture_size / tile_size) as u8;
@@ -211,6 +213,7 @@ impl From<query::Allsky> for AllskyRequest {
             hips_cdid,
             depth_tile,
             url,
+            frame,
             request,
         }
     }
@@ -309,6 +312,7 @@ pub struct Allsky {
     pub image: Arc<Mutex<Option<Vec<ImageType>>>>,
     pub time_req: Time,
     pub depth_tile: u8,
+    pub frame: u32,
 
     pub hips_cdid: CreatorDid,
     url: Url,
@@ -337,6 +341,7 @@ impl<'a> From<&'a AllskyRequest> for Option<Allsky> {
             hips_cdid,
             depth_tile,
             url,
+            frame,
             ..
         } = request;
         if request.is_resolved() {
@@ -350,6 +355,7 @@ impl<'a> From<&'a AllskyRequest> for Option<Allsky> {
                 hips_cdid: hips_cdid.clone(),
                 url: url.clone(),
                 depth_tile: *depth_tile,
+                frame: *frame,
             })
         } else {
             None
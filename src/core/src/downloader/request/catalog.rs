@@ -0,0 +1,98 @@
+use crate::downloader::query;
+use crate::downloader::QueryId;
+use crate::renderable::catalog::hips_cat::File;
+
+use super::{http_error, Failure, Request, RequestType, ResolvedStatus};
+
+pub struct CatalogFileRequest {
+    pub id: QueryId,
+    pub name: String,
+    file: File,
+    request: Request<String>,
+}
+
+impl From<CatalogFileRequest> for RequestType {
+    fn from(request: CatalogFileRequest) -> Self {
+        RequestType::CatalogFile(request)
+    }
+}
+
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{RequestInit, RequestMode, Response};
+impl From<query::CatalogFile> for CatalogFileRequest {
+    // Fetch a text file of a progressive catalogue
+    fn from(query: query::CatalogFile) -> Self {
+        let query::CatalogFile {
+            name,
+            file,
+            url,
+            id,
+        } = query;
+
+        let window = web_sys::window().unwrap_abort();
+        let request = Request::new(async move {
+            let opts = RequestInit::new();
+            opts.set_method("GET");
+            opts.set_mode(RequestMode::Cors);
+
+            let request = web_sys::Request::new_with_str_and_init(&url, &opts)?;
+            let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
+            // `resp_value` is a `Response` object.
+            debug_assert!(resp_value.is_instance_of::<Response>());
+            let resp: Response = resp_value.dyn_into()?;
+
+            if resp.ok() {
+                let text = JsFuture::from(resp.text()?).await?;
+                Ok(text.as_string().unwrap_or_default())
+            } else {
+                Err(http_error(&url, resp.status()))
+            }
+        });
+
+        Self {
+            id,
+            name,
+            file,
+            request,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CatalogFile {
+    pub name: String,
+    pub file: File,
+    // The reason why the file could not be fetched otherwise
+    pub content: Result<String, Failure>,
+}
+
+use crate::Abort;
+impl<'a> From<&'a CatalogFileRequest> for Option<CatalogFile> {
+    fn from(request: &'a CatalogFileRequest) -> Self {
+        let CatalogFileRequest {
+            request,
+            name,
+            file,
+            ..
+        } = request;
+
+        match request.resolve_status() {
+            ResolvedStatus::NotResolved => None,
+            ResolvedStatus::Failed(failure) => Some(CatalogFile {
+                name: name.clone(),
+                file: *file,
+                content: Err(failure),
+            }),
+            ResolvedStatus::Found => {
+                let Request::<String> { data, .. } = request;
+
+                Some(CatalogFile {
+                    name: name.clone(),
+                    file: *file,
+                    content: Ok(data.lock().unwrap_abort().take().unwrap_or_default()),
+                })
+            }
+        }
+    }
+}
@@ -65,7 +65,12 @@ impl From<query::Moc> for MOCRequest {
             opts.set_mode(RequestMode::Cors);
 
             let request = web_sys::Request::new_with_str_and_init(&url_clone, &opts).unwrap_abort();
-            let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
+            let resp_value = JsFuture::from(window.fetch_with_request(&request)).await;
+            // The url created for a local MOC is not needed once it has been fetched
+            if url_clone.starts_with("blob:") {
+                let _ = web_sys::Url::revoke_object_url(&url_clone);
+            }
+            let resp_value = resp_value?;
             // `resp_value` is a `Response` object.
             debug_assert!(resp_value.is_instance_of::<Response>());
             let resp: Response = resp_value.dyn_into()?;
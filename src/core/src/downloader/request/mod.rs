@@ -2,7 +2,9 @@
 // but contained inside a more specific type of query (e.g. for a tile or allsky)
 pub mod allsky;
 pub mod blank;
+pub mod catalog;
 pub mod moc;
+pub mod probe;
 pub mod tile;
 
 /* ------------------------------------- */
@@ -19,22 +21,107 @@ pub struct Request<R> {
     // the HtmlImageElement can be reused to download another tile
     //ready: bool,
     resolved: Rc<Cell<ResolvedStatus>>,
+    // Cancels the request, None if it cannot be cancelled
+    controller: Option<AbortController>,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum ResolvedStatus {
     NotResolved,
-    Failed,
+    Failed(Failure),
     Found,
 }
+
+/// The reason why a request failed
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Failure {
+    // The request has been cancelled
+    Aborted,
+    // A network or server error that may not happen again
+    Transient,
+    // The resource does not exist, e.g. a tile missing from a HiPS, or the cause of the
+    // failure is unknown
+    Permanent,
+}
+
+impl Failure {
+    fn from_error(err: &JsValue) -> Self {
+        let property = |name: &str| js_sys::Reflect::get(err, &JsValue::from_str(name)).ok();
+
+        if property("name")
+            .and_then(|name| name.as_string())
+            .as_deref()
+            == Some("AbortError")
+        {
+            Failure::Aborted
+        } else if let Some(status) = property("status").and_then(|status| status.as_f64()) {
+            Failure::from_status(status as u16)
+        } else if err.is_instance_of::<js_sys::TypeError>() {
+            // The fetch API rejects with a TypeError on network errors
+            Failure::Transient
+        } else {
+            // The cause is unknown, e.g. a response that cannot be decoded, requesting
+            // the resource again would not help
+            Failure::Permanent
+        }
+    }
+
+    /// Classify the HTTP status code of a failed response
+    pub fn from_status(status: u16) -> Self {
+        match status {
+            // Request timeout, too early, too many requests and server errors
+            408 | 425 | 429 | 500..=599 => Failure::Transient,
+            _ => Failure::Permanent,
+        }
+    }
+}
+
+/// The error of a request whose response has an unsuccessful status code
+pub fn http_error(url: &str, status: u16) -> JsValue {
+    let err = js_sys::Error::new(&format!("{} responded with status {}", url, status));
+    let _ = js_sys::Reflect::set(&err, &JsValue::from_str("status"), &JsValue::from(status));
+
+    err.into()
+}
+
+/// The error of a cancelled request
+pub fn abort_error() -> JsValue {
+    let err = js_sys::Error::new("The request has been aborted");
+    err.set_name("AbortError");
+
+    err.into()
+}
+
 use crate::Abort;
 use std::future::Future;
-use wasm_bindgen::JsValue;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{AbortController, AbortSignal};
 impl<R> Request<R>
 where
     R: 'static,
 {
     pub fn new<F>(f: F) -> Self
+    where
+        F: Future<Output = Result<R, JsValue>> + 'static,
+    {
+        Self::spawn(f, None)
+    }
+
+    /// Create a request that can be cancelled with [`Request::abort`]
+    ///
+    /// The future is given the signal to pass to the fetch API
+    pub fn new_abortable<F, Fut>(f: F) -> Self
+    where
+        F: FnOnce(AbortSignal) -> Fut,
+        Fut: Future<Output = Result<R, JsValue>> + 'static,
+    {
+        let controller = AbortController::new().unwrap_abort();
+        let fut = f(controller.signal());
+
+        Self::spawn(fut, Some(controller))
+    }
+
+    fn spawn<F>(f: F, controller: Option<AbortController>) -> Self
     where
         F: Future<Output = Result<R, JsValue>> + 'static,
     {
@@ -49,12 +136,14 @@ where
             let resolved_cloned = resolved.clone();
 
             let fut = async move {
-                let resp = f.await;
-                if let Ok(resp) = resp {
-                    *(data_cloned.lock().unwrap_abort()) = Some(resp);
-                    resolved_cloned.set(ResolvedStatus::Found);
-                } else {
-                    resolved_cloned.set(ResolvedStatus::Failed);
+                match f.await {
+                    Ok(resp) => {
+                        *(data_cloned.lock().unwrap_abort()) = Some(resp);
+                        resolved_cloned.set(ResolvedStatus::Found);
+                    }
+                    Err(err) => {
+                        resolved_cloned.set(ResolvedStatus::Failed(Failure::from_error(&err)));
+                    }
                 }
 
                 Ok(JsValue::from_bool(true))
@@ -67,28 +156,41 @@ where
             data,
             resolved,
             time_request,
+            controller,
         }
     }
 
     pub fn is_resolved(&self) -> bool {
-        let resolved = self.resolve_status();
-        resolved == ResolvedStatus::Found || resolved == ResolvedStatus::Failed
+        self.resolve_status() != ResolvedStatus::NotResolved
     }
 
     pub fn resolve_status(&self) -> ResolvedStatus {
         self.resolved.get()
     }
+
+    /// Cancel the request if it is not resolved yet
+    pub fn abort(&self) {
+        if let Some(controller) = &self.controller {
+            if !self.is_resolved() {
+                controller.abort();
+            }
+        }
+    }
 }
 
 use allsky::AllskyRequest;
 use blank::PixelMetadataRequest;
+use catalog::CatalogFileRequest;
 use moc::MOCRequest;
+use probe::MirrorProbeRequest;
 use tile::TileRequest;
 pub enum RequestType {
     Tile(TileRequest),
     Allsky(AllskyRequest),
     PixelMetadata(PixelMetadataRequest),
-    Moc(MOCRequest), //..
+    Moc(MOCRequest),
+    MirrorProbe(MirrorProbeRequest),
+    CatalogFile(CatalogFileRequest), //..
 }
 
 use crate::downloader::QueryId;
@@ -99,6 +201,15 @@ impl RequestType {
             RequestType::Allsky(request) => &request.id,
             RequestType::PixelMetadata(request) => &request.id,
             RequestType::Moc(request) => &request.hips_cdid,
+            RequestType::MirrorProbe(request) => &request.id,
+            RequestType::CatalogFile(request) => &request.id,
+        }
+    }
+
+    /// Cancel the request, only the tile requests can be cancelled
+    pub fn abort(&self) {
+        if let RequestType::Tile(request) = self {
+            request.abort();
         }
     }
 }
@@ -112,19 +223,29 @@ impl<'a> From<&'a RequestType> for Option<Resource> {
                 Option::<PixelMetadata>::from(request).map(Resource::PixelMetadata)
             }
             RequestType::Moc(request) => Option::<Moc>::from(request).map(Resource::Moc),
+            RequestType::MirrorProbe(request) => {
+                Option::<MirrorProbe>::from(request).map(Resource::MirrorProbe)
+            }
+            RequestType::CatalogFile(request) => {
+                Option::<CatalogFile>::from(request).map(Resource::CatalogFile)
+            }
         }
     }
 }
 
 use allsky::Allsky;
 use blank::PixelMetadata;
+use catalog::CatalogFile;
 use moc::Moc;
+use probe::MirrorProbe;
 use tile::Tile;
 pub enum Resource {
     Tile(Tile),
     Allsky(Allsky),
     PixelMetadata(PixelMetadata),
     Moc(Moc),
+    MirrorProbe(MirrorProbe),
+    CatalogFile(CatalogFile),
 }
 
 impl Resource {
@@ -134,6 +255,8 @@ impl Resource {
             Resource::Allsky(allsky) => allsky.get_hips_cdid(),
             Resource::PixelMetadata(PixelMetadata { hips_cdid, .. }) => hips_cdid,
             Resource::Moc(moc) => moc.get_hips_cdid(),
+            Resource::MirrorProbe(probe) => &probe.hips_cdid,
+            Resource::CatalogFile(file) => &file.name,
         }
     }
 }
@@ -0,0 +1,111 @@
+use crate::downloader::query;
+use crate::downloader::QueryId;
+use crate::renderable::CreatorDid;
+use crate::time::Time;
+
+use super::{http_error, Request, RequestType, ResolvedStatus, Url};
+
+// Mirrors not responding within this delay are considered unreachable
+const PROBE_TIMEOUT_MS: i32 = 3_000;
+
+pub struct MirrorProbeRequest {
+    pub id: QueryId,
+    pub hips_cdid: CreatorDid,
+    mirror_url: Url,
+    // The duration of the request in ms
+    request: Request<f32>,
+}
+
+impl From<MirrorProbeRequest> for RequestType {
+    fn from(request: MirrorProbeRequest) -> Self {
+        RequestType::MirrorProbe(request)
+    }
+}
+
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{AbortController, RequestCache, RequestInit, RequestMode, Response};
+impl From<query::MirrorProbe> for MirrorProbeRequest {
+    // Measure the time a mirror takes to deliver a small file
+    fn from(query: query::MirrorProbe) -> Self {
+        let query::MirrorProbe {
+            mirror_url,
+            hips_cdid,
+            url,
+            id,
+        } = query;
+
+        let window = web_sys::window().unwrap_abort();
+        let request = Request::new(async move {
+            let controller = AbortController::new()?;
+
+            let opts = RequestInit::new();
+            opts.set_method("GET");
+            opts.set_mode(RequestMode::Cors);
+            // The latency of the server is wanted, not the one of the browser cache
+            opts.set_cache(RequestCache::NoCache);
+            opts.set_signal(Some(&controller.signal()));
+
+            let request = web_sys::Request::new_with_str_and_init(&url, &opts)?;
+
+            crate::utils::set_timeout(move || controller.abort(), PROBE_TIMEOUT_MS);
+
+            let start = Time::now();
+            let resp_value = JsFuture::from(window.fetch_with_request(&request)).await?;
+            // `resp_value` is a `Response` object.
+            debug_assert!(resp_value.is_instance_of::<Response>());
+            let resp: Response = resp_value.dyn_into()?;
+
+            if resp.ok() {
+                Ok((Time::now() - start).as_millis())
+            } else {
+                Err(http_error(&url, resp.status()))
+            }
+        });
+
+        Self {
+            id,
+            hips_cdid,
+            mirror_url,
+            request,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct MirrorProbe {
+    pub hips_cdid: CreatorDid,
+    pub mirror_url: Url,
+    // None if the mirror did not respond
+    pub latency: Option<f32>,
+}
+
+use crate::Abort;
+impl<'a> From<&'a MirrorProbeRequest> for Option<MirrorProbe> {
+    fn from(request: &'a MirrorProbeRequest) -> Self {
+        let MirrorProbeRequest {
+            request,
+            hips_cdid,
+            mirror_url,
+            ..
+        } = request;
+
+        match request.resolve_status() {
+            ResolvedStatus::NotResolved => None,
+            ResolvedStatus::Failed(_) => Some(MirrorProbe {
+                hips_cdid: hips_cdid.clone(),
+                mirror_url: mirror_url.clone(),
+                latency: None,
+            }),
+            ResolvedStatus::Found => {
+                let Request::<f32> { data, .. } = request;
+
+                Some(MirrorProbe {
+                    hips_cdid: hips_cdid.clone(),
+                    mirror_url: mirror_url.clone(),
+                    latency: *data.lock().unwrap_abort(),
+                })
+            }
+        }
+    }
+}
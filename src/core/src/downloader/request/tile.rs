@@ -6,7 +6,7 @@ use crate::downloader::query;
 use al_core::image::ImageType;
 
 use super::Url;
-use super::{Request, RequestType};
+use super::{abort_error, http_error, Failure, Request, RequestType, ResolvedStatus};
 use crate::downloader::QueryId;
 
 pub struct TileRequest {
@@ -16,40 +16,74 @@ pub struct TileRequest {
     hips_cdid: CreatorDid,
     url: Url,
     format: ImageFormatType,
+    frame: u32,
 
     request: Request<ImageType>,
 }
 
+impl TileRequest {
+    /// Cancel the download of the tile
+    pub fn abort(&self) {
+        self.request.abort();
+    }
+}
+
 impl From<TileRequest> for RequestType {
     fn from(request: TileRequest) -> Self {
         RequestType::Tile(request)
     }
 }
 
-async fn query_html_image(url: &str) -> Result<web_sys::HtmlImageElement, JsValue> {
+// The image is fetched before being decoded by an image element so that the status
+// of an unsuccessful response is known, a missing tile not being a transient failure
+async fn query_html_image(
+    url: &str,
+    signal: &AbortSignal,
+) -> Result<web_sys::HtmlImageElement, JsValue> {
+    let opts = RequestInit::new();
+    opts.set_method("GET");
+    opts.set_mode(RequestMode::Cors);
+    opts.set_signal(Some(signal));
+
+    let window = web_sys::window().unwrap_abort();
+    let request = web_sys::Request::new_with_str_and_init(url, &opts)?;
+    let resp: Response = JsFuture::from(window.fetch_with_request(&request))
+        .await?
+        .dyn_into()?;
+    if !resp.ok() {
+        return Err(http_error(url, resp.status()));
+    }
+    let blob: web_sys::Blob = JsFuture::from(resp.blob()?).await?.dyn_into()?;
+
     let image = web_sys::HtmlImageElement::new().unwrap_abort();
     let image_cloned = image.clone();
+    let blob_url = web_sys::Url::create_object_url_with_blob(&blob)?;
+    let blob_url_cloned = blob_url.clone();
 
     let promise = js_sys::Promise::new(
         &mut (Box::new(move |resolve, reject| {
-            // Ask for CORS permissions
-            image_cloned.set_cross_origin(Some(""));
             image_cloned.set_onload(Some(&resolve));
             image_cloned.set_onerror(Some(&reject));
-            image_cloned.set_src(&url);
+            image_cloned.set_src(&blob_url_cloned);
         }) as Box<dyn FnMut(js_sys::Function, js_sys::Function)>),
     );
-
-    let _ = JsFuture::from(promise).await?;
-
-    Ok(image)
+    let decoded = JsFuture::from(promise).await;
+    let _ = web_sys::Url::revoke_object_url(&blob_url);
+
+    if signal.aborted() {
+        Err(abort_error())
+    } else if decoded.is_err() {
+        Err(js_sys::Error::new(&format!("{} is not an image", url)).into())
+    } else {
+        Ok(image)
+    }
 }
 
 use al_core::image::html::HTMLImage;
 use wasm_bindgen::JsCast;
 use wasm_bindgen::JsValue;
 use wasm_bindgen_futures::JsFuture;
-use web_sys::{RequestInit, RequestMode, Response};
+use web_sys::{AbortSignal, RequestInit, RequestMode, Response};
 impl From<query::Tile> for TileRequest {
     // Create a tile request associated to a HiPS
     fn from(query: query::Tile) -> Self {
@@ -59,6 +93,7 @@ impl From<query::Tile> for TileRequest {
             url,
             hips_cdid,
             id,
+            frame,
         } = query;
 
         let url_clone = url.clone();
@@ -66,7 +101,7 @@ impl From<query::Tile> for TileRequest {
 
         let window = web_sys::window().unwrap_abort();
         let request = match channel {
-            ChannelType::RGB8U => Request::new(async move {
+            ChannelType::RGB8U => Request::new_abortable(|signal| async move {
                 /*let mut opts = RequestInit::new();
                 opts.method("GET");
                 opts.mode(RequestMode::Cors);
@@ -95,13 +130,13 @@ impl From<query::Tile> for TileRequest {
                 Ok(ImageType::RawRgb8u { image })
                 */
                 // HTMLImageElement
-                let image = query_html_image(&url_clone).await?;
+                let image = query_html_image(&url_clone, &signal).await?;
                 // The image has been resolved
                 Ok(ImageType::HTMLImageRgb8u {
                     image: HTMLImage::<RGB8U>::new(image),
                 })
             }),
-            ChannelType::RGBA8U => Request::new(async move {
+            ChannelType::RGBA8U => Request::new_abortable(|signal| async move {
                 /*let mut opts = RequestInit::new();
                 opts.method("GET");
                 opts.mode(RequestMode::Cors);
@@ -130,7 +165,7 @@ impl From<query::Tile> for TileRequest {
                 Ok(ImageType::RawRgba8u { image })
                 */
                 // HTMLImageElement
-                let image = query_html_image(&url_clone).await?;
+                let image = query_html_image(&url_clone, &signal).await?;
                 // The image has been resolved
                 Ok(ImageType::HTMLImageRgba8u {
                     image: HTMLImage::<RGBA8U>::new(image),
@@ -140,10 +175,11 @@ impl From<query::Tile> for TileRequest {
             | ChannelType::R64F
             | ChannelType::R32I
             | ChannelType::R16I
-            | ChannelType::R8UI => Request::new(async move {
+            | ChannelType::R8UI => Request::new_abortable(|signal| async move {
                 let opts = RequestInit::new();
                 opts.set_method("GET");
                 opts.set_mode(RequestMode::Cors);
+                opts.set_signal(Some(&signal));
 
                 let request =
                     web_sys::Request::new_with_str_and_init(&url_clone, &opts).unwrap_abort();
@@ -165,9 +201,7 @@ impl From<query::Tile> for TileRequest {
 
                     Ok(ImageType::FitsImage { raw_bytes })
                 } else {
-                    Err(JsValue::from_str(
-                        "Response status code not between 200-299.",
-                    ))
+                    Err(http_error(&url_clone, resp.status()))
                 }
             }),
             _ => todo!(),
@@ -179,6 +213,7 @@ impl From<query::Tile> for TileRequest {
             id,
             hips_cdid,
             url,
+            frame,
             request,
         }
     }
@@ -186,13 +221,18 @@ impl From<query::Tile> for TileRequest {
 
 use crate::time::Time;
 use std::sync::{Arc, Mutex};
+#[derive(Clone)]
 pub struct Tile {
     pub image: Arc<Mutex<Option<ImageType>>>,
     pub time_req: Time,
     pub cell: HEALPixCell,
     pub format: ImageFormatType,
+    pub frame: u32,
     hips_cdid: CreatorDid,
     url: Url,
+    id: QueryId,
+    // The reason why the tile could not be retrieved
+    failure: Option<Failure>,
 }
 
 use crate::Abort;
@@ -217,6 +257,25 @@ impl Tile {
         &self.cell
     }
 
+    #[inline(always)]
+    pub fn get_id(&self) -> &QueryId {
+        &self.id
+    }
+
+    #[inline(always)]
+    pub fn failure(&self) -> Option<Failure> {
+        self.failure
+    }
+
+    /// The size in bytes of the image received, None if it is missing
+    pub fn byte_size(&self) -> Option<usize> {
+        self.image
+            .lock()
+            .unwrap_abort()
+            .as_ref()
+            .map(|image| image.byte_size())
+    }
+
     /*#[inline(always)]
     pub fn query(&self) -> query::Tile {
         query::Tile::new(&self.cell, self.hips_url.clone(), self.format)
@@ -231,13 +290,20 @@ impl<'a> From<&'a TileRequest> for Option<Tile> {
             hips_cdid,
             url,
             format,
-            ..
+            frame,
+            id,
         } = request;
         if request.is_resolved() {
+            let failure = match request.resolve_status() {
+                ResolvedStatus::Failed(failure) => Some(failure),
+                _ => None,
+            };
             let Request::<ImageType> {
                 time_request, data, ..
             } = request;
             Some(Tile {
+                id: id.clone(),
+                failure,
                 cell: *cell,
                 time_req: *time_request,
                 // This is a clone on a Arc, it is supposed to be fast
@@ -245,6 +311,7 @@ impl<'a> From<&'a TileRequest> for Option<Tile> {
                 hips_cdid: hips_cdid.clone(),
                 url: url.clone(),
                 format: *format,
+                frame: *frame,
             })
         } else {
             None
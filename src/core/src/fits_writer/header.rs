@@ -0,0 +1,203 @@
+/// Size of a FITS block in bytes. Headers and data units are padded to it.
+pub const BLOCK_SIZE: usize = 2880;
+/// Size of a header card in bytes
+pub const CARD_SIZE: usize = 80;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Logical(bool),
+    Integer(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Self {
+        Value::Logical(v)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(v: i64) -> Self {
+        Value::Integer(v)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Self {
+        Value::Float(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Self {
+        Value::Str(v.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Self {
+        Value::Str(v)
+    }
+}
+
+impl Value {
+    // Fixed-format representation of the value.
+    // Logical and numerical values are right justified in columns 11-30.
+    fn format(&self) -> String {
+        match self {
+            Value::Logical(b) => format!("{:>20}", if *b { "T" } else { "F" }),
+            Value::Integer(i) => format!("{:>20}", i),
+            Value::Float(f) => format!("{:>20}", format_float(*f)),
+            Value::Str(s) => {
+                // Quotes are escaped by doubling them and the string is
+                // padded to at least 8 characters
+                let s = s.replace('\'', "''");
+                format!("'{:<8}'", s)
+            }
+        }
+    }
+}
+
+// The FITS standard requires a decimal point in the mantissa
+// and an upper case exponent letter
+fn format_float(f: f64) -> String {
+    let s = format!("{:E}", f);
+    if let Some((mantissa, exponent)) = s.split_once('E') {
+        if mantissa.contains('.') {
+            s
+        } else {
+            format!("{}.0E{}", mantissa, exponent)
+        }
+    } else {
+        s
+    }
+}
+
+/// A FITS header being built card by card.
+///
+/// The END card and the padding to a multiple of 2880 bytes are
+/// added when the header is serialized.
+#[derive(Debug, Clone, Default)]
+pub struct Header {
+    cards: Vec<[u8; CARD_SIZE]>,
+}
+
+impl Header {
+    pub fn new() -> Self {
+        Self { cards: vec![] }
+    }
+
+    /// Append a `KEYWORD = value / comment` card
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The keyword, truncated to 8 characters and upper-cased
+    /// * `value` - The value of the card
+    /// * `comment` - An optional comment, truncated to fit in the card
+    pub fn push<V: Into<Value>>(&mut self, key: &str, value: V, comment: Option<&str>) {
+        let mut card = format!("{:<8}= {}", Self::keyword(key), value.into().format());
+        if let Some(comment) = comment {
+            card.push_str(" / ");
+            card.push_str(comment);
+        }
+
+        self.push_raw(&card);
+    }
+
+    /// Append all the cards of another header
+    pub fn append(&mut self, other: &Header) {
+        self.cards.extend_from_slice(&other.cards);
+    }
+
+    /// The 80 characters cards, END card excluded
+    pub fn cards(&self) -> impl Iterator<Item = &str> + '_ {
+        // Only ASCII characters are pushed
+        self.cards
+            .iter()
+            .map(|card| std::str::from_utf8(card).unwrap_or_default())
+    }
+
+    /// The serialized header, END card and padding included
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity((self.cards.len() + 1) * CARD_SIZE);
+        for card in &self.cards {
+            bytes.extend_from_slice(card);
+        }
+
+        let mut end = [b' '; CARD_SIZE];
+        end[..3].copy_from_slice(b"END");
+        bytes.extend_from_slice(&end);
+
+        pad_to_block(&mut bytes, b' ');
+        bytes
+    }
+
+    fn keyword(key: &str) -> String {
+        key.chars().take(8).collect::<String>().to_uppercase()
+    }
+
+    fn push_raw(&mut self, card: &str) {
+        let mut c = [b' '; CARD_SIZE];
+        // Only ASCII characters are allowed in a header
+        for (dst, src) in c.iter_mut().zip(card.chars().filter(|c| c.is_ascii())) {
+            *dst = src as u8;
+        }
+
+        self.cards.push(c);
+    }
+}
+
+/// Pad a byte buffer to the next multiple of the FITS block size
+pub fn pad_to_block(bytes: &mut Vec<u8>, fill: u8) {
+    let rem = bytes.len() % BLOCK_SIZE;
+    if rem != 0 {
+        bytes.resize(bytes.len() + BLOCK_SIZE - rem, fill);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Header, BLOCK_SIZE, CARD_SIZE};
+
+    fn card(bytes: &[u8], idx: usize) -> &str {
+        std::str::from_utf8(&bytes[idx * CARD_SIZE..(idx + 1) * CARD_SIZE]).unwrap()
+    }
+
+    #[test]
+    fn fixed_format_cards() {
+        let mut header = Header::new();
+        header.push("SIMPLE", true, Some("conforms to FITS standard"));
+        header.push("naxis1", 512_i64, None);
+        header.push("CDELT1", -2.5e-4, None);
+        header.push("CRVAL1", 10.0, None);
+        header.push("CTYPE1", "RA---TAN", None);
+        header.push("OBJECT", "M'31", None);
+
+        let bytes = header.to_bytes();
+        assert_eq!(bytes.len(), BLOCK_SIZE);
+
+        assert_eq!(
+            card(&bytes, 0).trim_end(),
+            "SIMPLE  =                    T / conforms to FITS standard"
+        );
+        assert_eq!(card(&bytes, 1).trim_end(), "NAXIS1  =                  512");
+        assert_eq!(card(&bytes, 2).trim_end(), "CDELT1  =              -2.5E-4");
+        assert_eq!(card(&bytes, 3).trim_end(), "CRVAL1  =                1.0E1");
+        assert_eq!(card(&bytes, 4).trim_end(), "CTYPE1  = 'RA---TAN'");
+        assert_eq!(card(&bytes, 5).trim_end(), "OBJECT  = 'M''31   '");
+        assert_eq!(card(&bytes, 6).trim_end(), "END");
+        assert!(bytes[7 * CARD_SIZE..].iter().all(|b| *b == b' '));
+    }
+
+    #[test]
+    fn header_spanning_several_blocks() {
+        let mut header = Header::new();
+        for i in 0..36 {
+            header.push(&format!("KEY{}", i), i as i64, None);
+        }
+
+        // 36 cards + END do not fit in one block
+        assert_eq!(header.to_bytes().len(), 2 * BLOCK_SIZE);
+    }
+}
@@ -0,0 +1,284 @@
+use super::header::{pad_to_block, Header};
+
+/// Storage type of the data unit of an image HDU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bitpix {
+    U8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl Bitpix {
+    pub fn value(&self) -> i64 {
+        match self {
+            Bitpix::U8 => 8,
+            Bitpix::I16 => 16,
+            Bitpix::I32 => 32,
+            Bitpix::I64 => 64,
+            Bitpix::F32 => -32,
+            Bitpix::F64 => -64,
+        }
+    }
+
+    pub fn from_value(bitpix: i32) -> Option<Self> {
+        match bitpix {
+            8 => Some(Bitpix::U8),
+            16 => Some(Bitpix::I16),
+            32 => Some(Bitpix::I32),
+            64 => Some(Bitpix::I64),
+            -32 => Some(Bitpix::F32),
+            -64 => Some(Bitpix::F64),
+            _ => None,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.value() > 0
+    }
+
+    /// A BLANK value for the undefined pixels when none has been provided by the source
+    ///
+    /// For the 8-bit images, it is the highest value not taken by the defined pixels, None
+    /// if they take them all
+    fn unused_blank(&self, values: &[f64]) -> Option<i64> {
+        match self {
+            Bitpix::U8 => {
+                let mut used = [false; 256];
+                for v in values.iter().filter(|v| !v.is_nan()) {
+                    used[v.round().clamp(0.0, u8::MAX as f64) as usize] = true;
+                }
+
+                (0..=u8::MAX as i64).rev().find(|&i| !used[i as usize])
+            }
+            Bitpix::I16 => Some(i16::MIN as i64),
+            Bitpix::I32 => Some(i32::MIN as i64),
+            _ => Some(i64::MIN),
+        }
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>, v: f64, blank: i64) {
+        // For integer types, undefined values are replaced by BLANK
+        let i = if v.is_nan() { blank } else { v.round() as i64 };
+
+        match self {
+            Bitpix::U8 => bytes.push(i.clamp(0, u8::MAX as i64) as u8),
+            Bitpix::I16 => bytes.extend_from_slice(
+                &(i.clamp(i16::MIN as i64, i16::MAX as i64) as i16).to_be_bytes(),
+            ),
+            Bitpix::I32 => bytes.extend_from_slice(
+                &(i.clamp(i32::MIN as i64, i32::MAX as i64) as i32).to_be_bytes(),
+            ),
+            Bitpix::I64 => bytes.extend_from_slice(&i.to_be_bytes()),
+            Bitpix::F32 => bytes.extend_from_slice(&(v as f32).to_be_bytes()),
+            Bitpix::F64 => bytes.extend_from_slice(&v.to_be_bytes()),
+        }
+    }
+}
+
+/// Raw pixel values of an image with their scaling
+pub struct ImageData<'a> {
+    pub bitpix: Bitpix,
+    /// Dimensions of the image, fastest varying axis first
+    pub naxes: &'a [usize],
+    /// Raw values, NaN for undefined pixels
+    pub values: &'a [f64],
+
+    pub bscale: f64,
+    pub bzero: f64,
+    /// BLANK value for integer images
+    pub blank: Option<i64>,
+}
+
+/// Serialize a primary image HDU
+///
+/// # Arguments
+///
+/// * `data` - The image pixels with their storage type
+/// * `cards` - Additional cards (e.g. the WCS) appended after the mandatory keywords
+pub fn write_primary_hdu(data: &ImageData, cards: &Header) -> Result<Vec<u8>, &'static str> {
+    let num_values = data.naxes.iter().product::<usize>();
+    if num_values != data.values.len() {
+        return Err("the number of values does not match the image dimensions");
+    }
+
+    let mut header = Header::new();
+    header.push("SIMPLE", true, Some("conforms to FITS standard"));
+    header.push("BITPIX", data.bitpix.value(), None);
+    header.push("NAXIS", data.naxes.len() as i64, None);
+    for (i, naxis) in data.naxes.iter().enumerate() {
+        header.push(&format!("NAXIS{}", i + 1), *naxis as i64, None);
+    }
+
+    // BLANK is only written if the source has one or if some pixels are undefined
+    let blank = if !data.bitpix.is_integer() {
+        None
+    } else if data.blank.is_some() {
+        data.blank
+    } else if data.values.iter().any(|v| v.is_nan()) {
+        let blank = data
+            .bitpix
+            .unused_blank(data.values)
+            .ok_or("no value is left to mark the undefined pixels")?;
+        Some(blank)
+    } else {
+        None
+    };
+    if let Some(blank) = blank {
+        header.push("BLANK", blank, None);
+    }
+    if data.bscale != 1.0 {
+        header.push("BSCALE", data.bscale, None);
+    }
+    if data.bzero != 0.0 {
+        header.push("BZERO", data.bzero, None);
+    }
+
+    header.append(cards);
+
+    let mut bytes = header.to_bytes();
+
+    let header_len = bytes.len();
+    bytes.reserve(num_values * (data.bitpix.value().unsigned_abs() as usize / 8));
+    for v in data.values {
+        // Without BLANK, there is no undefined value to replace
+        data.bitpix.write(&mut bytes, *v, blank.unwrap_or_default());
+    }
+    if bytes.len() > header_len {
+        pad_to_block(&mut bytes, 0);
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_primary_hdu, Bitpix, ImageData};
+    use crate::fits_writer::header::{Header, BLOCK_SIZE};
+
+    fn has_card(bytes: &[u8], card: &str) -> bool {
+        bytes[..BLOCK_SIZE]
+            .chunks(80)
+            .any(|c| std::str::from_utf8(c).unwrap().trim_end() == card)
+    }
+
+    #[test]
+    fn write_i16_image() {
+        let values = [1.0, f64::NAN, -3.0, 40000.0, 2.4, 0.0];
+        let mut cards = Header::new();
+        cards.push("CTYPE1", "RA---TAN", None);
+
+        let bytes = write_primary_hdu(
+            &ImageData {
+                bitpix: Bitpix::I16,
+                naxes: &[3, 2],
+                values: &values,
+                bscale: 2.0,
+                bzero: 0.0,
+                blank: None,
+            },
+            &cards,
+        )
+        .unwrap();
+
+        assert_eq!(bytes.len(), 2 * BLOCK_SIZE);
+        assert!(has_card(&bytes, "BITPIX  =                   16"));
+        assert!(has_card(&bytes, "NAXIS2  =                    2"));
+        assert!(has_card(&bytes, "BLANK   =               -32768"));
+        assert!(has_card(&bytes, "BSCALE  =                2.0E0"));
+        assert!(has_card(&bytes, "CTYPE1  = 'RA---TAN'"));
+        assert!(has_card(&bytes, "END"));
+
+        let data = &bytes[BLOCK_SIZE..];
+        let read = data[..12]
+            .chunks(2)
+            .map(|b| i16::from_be_bytes([b[0], b[1]]))
+            .collect::<Vec<_>>();
+        assert_eq!(read, vec![1, i16::MIN, -3, i16::MAX, 2, 0]);
+        assert!(data[12..].iter().all(|b| *b == 0));
+    }
+
+    #[test]
+    fn write_f32_image() {
+        let values = [0.5, f64::NAN];
+        let bytes = write_primary_hdu(
+            &ImageData {
+                bitpix: Bitpix::F32,
+                naxes: &[2],
+                values: &values,
+                bscale: 1.0,
+                bzero: 0.0,
+                blank: None,
+            },
+            &Header::new(),
+        )
+        .unwrap();
+
+        assert!(!bytes[..BLOCK_SIZE]
+            .chunks(80)
+            .any(|c| c.starts_with(b"BLANK")));
+        let data = &bytes[BLOCK_SIZE..];
+        assert_eq!(
+            f32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            0.5
+        );
+        assert!(f32::from_be_bytes([data[4], data[5], data[6], data[7]]).is_nan());
+    }
+
+    #[test]
+    fn write_u8_image() {
+        let write = |values: &[f64], blank: Option<i64>| {
+            write_primary_hdu(
+                &ImageData {
+                    bitpix: Bitpix::U8,
+                    naxes: &[values.len()],
+                    values,
+                    bscale: 1.0,
+                    bzero: 0.0,
+                    blank,
+                },
+                &Header::new(),
+            )
+        };
+
+        // The zeros are defined pixels
+        let bytes = write(&[0.0, 255.0, 3.0], None).unwrap();
+        assert!(!bytes[..BLOCK_SIZE]
+            .chunks(80)
+            .any(|c| c.starts_with(b"BLANK")));
+        assert_eq!(&bytes[BLOCK_SIZE..BLOCK_SIZE + 3], &[0, 255, 3]);
+
+        // The undefined pixels take a value not used by the other ones
+        let bytes = write(&[0.0, f64::NAN, 255.0], None).unwrap();
+        assert!(has_card(&bytes, "BLANK   =                  254"));
+        assert_eq!(&bytes[BLOCK_SIZE..BLOCK_SIZE + 3], &[0, 254, 255]);
+
+        // The BLANK of the source is kept
+        let bytes = write(&[1.0, 2.0], Some(0)).unwrap();
+        assert!(has_card(&bytes, "BLANK   =                    0"));
+
+        let values = (0..=256).map(|v| v as f64).collect::<Vec<_>>();
+        assert!(write(&values, None).is_ok());
+        let mut values = (0..=255).map(|v| v as f64).collect::<Vec<_>>();
+        values.push(f64::NAN);
+        assert!(write(&values, None).is_err());
+    }
+
+    #[test]
+    fn wrong_dimensions() {
+        assert!(write_primary_hdu(
+            &ImageData {
+                bitpix: Bitpix::U8,
+                naxes: &[2, 2],
+                values: &[0.0; 3],
+                bscale: 1.0,
+                bzero: 0.0,
+                blank: None,
+            },
+            &Header::new(),
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,9 @@
+//! Serialization of FITS HDUs
+//!
+//! Only what is needed to export data from the viewer is covered here,
+//! parsing is done by the fitsrs crate.
+pub mod header;
+pub mod image;
+
+pub use header::Header;
+pub use image::{write_primary_hdu, Bitpix, ImageData};
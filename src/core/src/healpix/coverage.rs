@@ -77,6 +77,23 @@ impl HEALPixCoverage {
         }
     }
 
+    /// The cells covering a range of longitudes and latitudes given in radians,
+    /// crossing the meridian of longitude 0 if `lon_min` is greater than `lon_max`
+    pub fn from_zone(
+        [lon_min, lat_min]: [f64; 2],
+        [lon_max, lat_max]: [f64; 2],
+        depth: u8,
+    ) -> Self {
+        HEALPixCoverage(RangeMOC::from_zone(
+            lon_min,
+            lat_min,
+            lon_max,
+            lat_max,
+            depth,
+            CellSelection::All,
+        ))
+    }
+
     pub fn allsky(depth_max: u8) -> Self {
         let moc = RangeMOC::new_full_domain(depth_max);
         HEALPixCoverage(moc)
@@ -4,43 +4,46 @@ use crate::{
 };
 
 use std::ops::Range;
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IdxVec(Box<[(u32, u32)]>);
 
 use crate::math::lonlat::LonLat;
+
+/// The index of the HEALPix cell of depth 7 containing a sky coordinate, by which
+/// the coordinates are sorted
+pub fn cell_d7<T>(coo: &T) -> u64
+where
+    T: LonLat<f32>,
+{
+    let lonlat = coo.lonlat();
+    healpix::nested::hash(
+        7,
+        lonlat.lon().to_radians() as f64,
+        lonlat.lat().to_radians() as f64,
+    )
+}
+
 impl IdxVec {
-    /// Build a coordinate index vector from a list of sky coordinates sorted by HEALPix value
+    /// Build a coordinate index vector from a list of sky coordinates, sorting them by
+    /// HEALPix value
     pub fn from_coo<T>(coos: &mut [T]) -> Self
     where
         T: LonLat<f32>,
     {
-        coos.sort_unstable_by(|c1, c2| {
-            let ll1 = c1.lonlat();
-            let ll2 = c2.lonlat();
-
-            let h1 = healpix::nested::hash(
-                7,
-                ll1.lon().to_radians() as f64,
-                ll1.lat().to_radians() as f64,
-            );
-            let h2 = healpix::nested::hash(
-                7,
-                ll2.lon().to_radians() as f64,
-                ll2.lat().to_radians() as f64,
-            );
-
-            h1.cmp(&h2)
-        });
+        coos.sort_unstable_by_key(cell_d7);
 
+        Self::from_sorted_coo(coos)
+    }
+
+    /// Build a coordinate index vector from a list of sky coordinates sorted by HEALPix value
+    pub fn from_sorted_coo<T>(coos: &[T]) -> Self
+    where
+        T: LonLat<f32>,
+    {
         let mut coo_idx_vector = vec![(u32::MAX, u32::MAX); 196608];
 
         for (idx, s) in coos.iter().enumerate() {
-            let lonlat = s.lonlat();
-            let hash = healpix::nested::hash(
-                7,
-                lonlat.lon().to_radians() as f64,
-                lonlat.lat().to_radians() as f64,
-            ) as usize;
+            let hash = cell_d7(s) as usize;
 
             if coo_idx_vector[hash].0 == u32::MAX {
                 let idx_u32 = idx as u32;
@@ -0,0 +1,211 @@
+//! A FITS image held in memory, source of a HiPS generation
+//!
+//! Unlike the images displayed in the view, its pixels are read from the FITS data and not
+//! from the GPU textures, so that millions of them can be sampled.
+use std::io::Cursor;
+
+use fitsrs::card::Value as CardValue;
+use fitsrs::fits::Fits;
+use fitsrs::hdu::data::InMemData;
+use wcs::{ImgXY, WCS};
+
+use al_api::coo_system::CooSystem;
+
+use super::{Error, Source};
+use crate::fits_writer::Bitpix;
+use crate::math::lonlat::{ang_between_lonlat, LonLatT};
+use crate::renderable::image::fits_wcs_keywords;
+use crate::reproject::Sampler;
+
+enum Pixels {
+    U8(Vec<u8>),
+    I16(Vec<i16>),
+    I32(Vec<i32>),
+    I64(Vec<i64>),
+    F32(Vec<f32>),
+    F64(Vec<f64>),
+}
+
+impl Pixels {
+    fn get(&self, idx: usize) -> Option<f64> {
+        match self {
+            Pixels::U8(data) => data.get(idx).map(|v| *v as f64),
+            Pixels::I16(data) => data.get(idx).map(|v| *v as f64),
+            Pixels::I32(data) => data.get(idx).map(|v| *v as f64),
+            Pixels::I64(data) => data.get(idx).map(|v| *v as f64),
+            Pixels::F32(data) => data.get(idx).map(|v| *v as f64),
+            Pixels::F64(data) => data.get(idx).copied(),
+        }
+    }
+
+    fn bitpix(&self) -> Bitpix {
+        match self {
+            Pixels::U8(_) => Bitpix::U8,
+            Pixels::I16(_) => Bitpix::I16,
+            Pixels::I32(_) => Bitpix::I32,
+            Pixels::I64(_) => Bitpix::I64,
+            Pixels::F32(_) => Bitpix::F32,
+            Pixels::F64(_) => Bitpix::F64,
+        }
+    }
+}
+
+/// The first HDU of a FITS file, a 2D image with a celestial WCS
+pub struct FitsImage {
+    pixels: Pixels,
+    width: usize,
+    height: usize,
+    wcs: WCS,
+    frame: CooSystem,
+
+    bscale: f64,
+    bzero: f64,
+    blank: Option<i64>,
+}
+
+impl FitsImage {
+    pub fn new(bytes: &[u8]) -> Result<Self, Error> {
+        let mut reader = Cursor::new(bytes);
+        let Fits { hdu } = Fits::from_reader(&mut reader).map_err(|e| Error::Fits {
+            message: format!("{:?}", e),
+        })?;
+
+        let header = hdu.get_header();
+        let float = |key: &[u8; 8]| match header.get(key) {
+            Some(CardValue::Float(v)) => Some(*v),
+            Some(CardValue::Integer(v)) => Some(*v as f64),
+            _ => None,
+        };
+
+        let width = float(b"NAXIS1  ").unwrap_or(0.0) as usize;
+        let height = float(b"NAXIS2  ").unwrap_or(0.0) as usize;
+        if width == 0 || height == 0 {
+            return Err(Error::NoData);
+        }
+        let bscale = float(b"BSCALE  ").unwrap_or(1.0);
+        let bzero = float(b"BZERO   ").unwrap_or(0.0);
+        let blank = float(b"BLANK   ").map(|blank| blank as i64);
+
+        let keywords = fits_wcs_keywords(header);
+        let wcs = keywords.wcs().map_err(|e| Error::Fits {
+            message: e.as_string().unwrap_or_default(),
+        })?;
+        let frame = keywords.frame();
+
+        // Only the first plane of a cube is tiled
+        let num_pixels = width.checked_mul(height).ok_or(Error::Fits {
+            message: "the image is too big".to_string(),
+        })?;
+        let pixels = match hdu.get_data() {
+            InMemData::U8(data) => Pixels::U8(first_plane(data, num_pixels)?),
+            InMemData::I16(data) => Pixels::I16(first_plane(data, num_pixels)?),
+            InMemData::I32(data) => Pixels::I32(first_plane(data, num_pixels)?),
+            InMemData::I64(data) => Pixels::I64(first_plane(data, num_pixels)?),
+            InMemData::F32(data) => Pixels::F32(first_plane(data, num_pixels)?),
+            InMemData::F64(data) => Pixels::F64(first_plane(data, num_pixels)?),
+        };
+
+        Ok(Self {
+            pixels,
+            width,
+            height,
+            wcs,
+            frame,
+            bscale,
+            bzero,
+            blank,
+        })
+    }
+
+    // The integer positions are the centers of the pixels
+    fn unproj(&self, x: f64, y: f64) -> Option<LonLatT<f64>> {
+        self.wcs.unproj_lonlat(&ImgXY::new(x, y)).map(LonLatT::from)
+    }
+}
+
+// The pixels of the first plane of the data, which can be truncated
+fn first_plane<T: Clone>(data: &[T], num_pixels: usize) -> Result<Vec<T>, Error> {
+    data.get(..num_pixels)
+        .map(<[T]>::to_vec)
+        .ok_or_else(|| Error::Fits {
+            message: format!(
+                "{} pixels are expected, the data contains {}",
+                num_pixels,
+                data.len()
+            ),
+        })
+}
+
+impl Sampler for FitsImage {
+    fn frame(&self) -> CooSystem {
+        self.frame
+    }
+
+    fn bitpix(&self) -> Bitpix {
+        self.pixels.bitpix()
+    }
+
+    fn num_channels(&self) -> usize {
+        1
+    }
+
+    fn scaling(&self) -> (f64, f64, Option<i64>) {
+        (self.bscale, self.bzero, self.blank)
+    }
+
+    fn sample(&self, lonlat: &LonLatT<f64>, values: &mut [f64]) -> bool {
+        let lonlat = wcs::LonLat::new(lonlat.lon().to_radians(), lonlat.lat().to_radians());
+        let xy = if let Some(xy) = self.wcs.proj_lonlat(&lonlat) {
+            xy
+        } else {
+            return false;
+        };
+
+        let (x, y) = (xy.x().round(), xy.y().round());
+        if x < 0.0 || y < 0.0 || x >= self.width as f64 || y >= self.height as f64 {
+            return false;
+        }
+
+        match self.pixels.get((y as usize) * self.width + (x as usize)) {
+            Some(value) if !value.is_nan() => {
+                values[0] = value;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Source for FitsImage {
+    fn pixel_size(&self) -> f64 {
+        let (x, y) = ((self.width / 2) as f64, (self.height / 2) as f64);
+
+        match (
+            self.unproj(x, y),
+            self.unproj(x + 1.0, y),
+            self.unproj(x, y + 1.0),
+        ) {
+            (Some(center), Some(right), Some(up)) => ang_between_lonlat(center, right)
+                .0
+                .min(ang_between_lonlat(center, up).0),
+            // The size of the pixels of a survey of the whole sky
+            _ => 1.0_f64.to_radians(),
+        }
+    }
+
+    fn positions(&self, step: usize) -> Vec<LonLatT<f64>> {
+        let (w, h) = (self.width, self.height);
+
+        // A grid over the image and every pixel of its border
+        let grid = (0..h)
+            .step_by(step)
+            .flat_map(|y| (0..w).step_by(step).map(move |x| (x, y)));
+        let border = (0..w)
+            .flat_map(|x| [(x, 0), (x, h - 1)])
+            .chain((0..h).flat_map(|y| [(0, y), (w - 1, y)]));
+
+        grid.chain(border)
+            .filter_map(|(x, y)| self.unproj(x as f64, y as f64))
+            .collect()
+    }
+}
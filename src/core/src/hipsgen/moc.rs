@@ -0,0 +1,135 @@
+//! Serialization of the coverage of a generated HiPS as a MOC FITS file
+//!
+//! The MOC is written following the version 2.0 of the IVOA MOC standard: a binary table
+//! containing the NUNIQ indices of the cells, sorted by order then by index.
+use std::collections::BTreeSet;
+
+use crate::fits_writer::header::{pad_to_block, Header};
+
+/// Merge the groups of four sibling cells into their parent
+///
+/// # Arguments
+///
+/// * `order` - The order of the cells
+/// * `cells` - The indices of the cells
+///
+/// Returns the cells of each order, from 0 to `order`
+pub fn normalize(order: u8, cells: &BTreeSet<u64>) -> Vec<BTreeSet<u64>> {
+    let mut cells_by_order = vec![BTreeSet::new(); order as usize + 1];
+    let mut cur = cells.clone();
+
+    for d in (1..=order as usize).rev() {
+        let mut parents = BTreeSet::new();
+        for &idx in &cur {
+            let first = idx & !3;
+            if (first..(first + 4)).all(|sibling| cur.contains(&sibling)) {
+                parents.insert(idx >> 2);
+            } else {
+                cells_by_order[d].insert(idx);
+            }
+        }
+
+        cur = parents;
+    }
+    cells_by_order[0] = cur;
+
+    cells_by_order
+}
+
+/// Serialize the MOC of a set of cells
+///
+/// # Arguments
+///
+/// * `order` - The order of the cells, the order of the MOC
+/// * `cells` - The indices of the cells
+pub fn to_fits(order: u8, cells: &BTreeSet<u64>) -> Vec<u8> {
+    let uniqs = normalize(order, cells)
+        .iter()
+        .enumerate()
+        .flat_map(|(d, cells)| {
+            let offset = 4u64 << (2 * d);
+            cells.iter().map(move |idx| offset + idx)
+        })
+        .collect::<Vec<_>>();
+
+    let mut primary = Header::new();
+    primary.push("SIMPLE", true, Some("conforms to FITS standard"));
+    primary.push("BITPIX", 8i64, None);
+    primary.push("NAXIS", 0i64, None);
+    primary.push("EXTEND", true, None);
+    let mut bytes = primary.to_bytes();
+
+    let mut header = Header::new();
+    header.push("XTENSION", "BINTABLE", Some("binary table extension"));
+    header.push("BITPIX", 8i64, None);
+    header.push("NAXIS", 2i64, None);
+    header.push("NAXIS1", 8i64, Some("size of a row in bytes"));
+    header.push("NAXIS2", uniqs.len() as i64, Some("number of cells"));
+    header.push("PCOUNT", 0i64, None);
+    header.push("GCOUNT", 1i64, None);
+    header.push("TFIELDS", 1i64, None);
+    header.push("TTYPE1", "UNIQ", None);
+    header.push("TFORM1", "1K", None);
+    header.push("MOCVERS", "2.0", Some("MOC version"));
+    header.push("MOCDIM", "SPACE", Some("physical dimension"));
+    header.push("ORDERING", "NUNIQ", Some("NUNIQ coding method"));
+    header.push("COORDSYS", "C", Some("reference frame"));
+    header.push("MOCORD_S", order as i64, Some("MOC resolution"));
+    // For the readers of the version 1.1 of the standard
+    header.push("MOCORDER", order as i64, Some("MOC resolution"));
+    header.push("MOCTOOL", "Aladin Lite", None);
+    bytes.extend(header.to_bytes());
+
+    for uniq in uniqs {
+        bytes.extend_from_slice(&uniq.to_be_bytes());
+    }
+    pad_to_block(&mut bytes, 0);
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{normalize, to_fits};
+    use crate::fits_writer::header::BLOCK_SIZE;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn merge_siblings() {
+        // The four children of the cell 3 of order 1 and two cells of another parent
+        let cells = [12, 13, 14, 15, 16, 19]
+            .iter()
+            .copied()
+            .collect::<BTreeSet<u64>>();
+        let cells_by_order = normalize(2, &cells);
+
+        assert!(cells_by_order[0].is_empty());
+        assert_eq!(cells_by_order[1], [3].iter().copied().collect());
+        assert_eq!(cells_by_order[2], [16, 19].iter().copied().collect());
+
+        // A whole base cell
+        let cells = (16..32).collect::<BTreeSet<u64>>();
+        assert_eq!(normalize(2, &cells)[0], [1].iter().copied().collect());
+    }
+
+    #[test]
+    fn nuniq_table() {
+        let cells = [12, 13, 14, 15, 16]
+            .iter()
+            .copied()
+            .collect::<BTreeSet<u64>>();
+        let bytes = to_fits(2, &cells);
+        assert_eq!(bytes.len(), 3 * BLOCK_SIZE);
+
+        let header = std::str::from_utf8(&bytes[BLOCK_SIZE..(2 * BLOCK_SIZE)]).unwrap();
+        assert!(header.starts_with("XTENSION= 'BINTABLE'"));
+        assert!(header.contains("NAXIS2  =                    2"));
+        assert!(header.contains("MOCORD_S=                    2"));
+
+        let data = &bytes[(2 * BLOCK_SIZE)..];
+        // 16 + 3 for the cell 3 of order 1, 64 + 16 for the cell 16 of order 2
+        assert_eq!(&data[..8], &19u64.to_be_bytes());
+        assert_eq!(&data[8..16], &80u64.to_be_bytes());
+        assert!(data[16..].iter().all(|&b| b == 0));
+    }
+}
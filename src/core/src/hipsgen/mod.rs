@@ -0,0 +1,793 @@
+//! Generation of a HiPS from an image, in the spirit of hipsgen
+//!
+//! The image is resampled into the HEALPix tiles of the order matching its resolution.
+//! The tiles of the lower orders are computed by averaging their four children. The tree
+//! of tiles is traversed depth first so that only a few tiles are kept in memory at once,
+//! a step computing a part of a tile at a time.
+//!
+//! The result is a zip archive containing the FITS tiles, the `Norder3/Allsky.fits` file,
+//! the `Moc.fits` file and the `properties` file of the HiPS. It can be loaded back as a
+//! local HiPS (see `tile_fetcher::archive`).
+pub mod fits;
+pub mod moc;
+pub mod zip;
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::Range;
+
+use serde::Deserialize;
+use wasm_bindgen::JsValue;
+
+use al_api::coo_system::CooSystem;
+
+use crate::fits_writer::{self, Header, ImageData};
+use crate::healpix::cell::{HEALPixCell, NUM_HPX_TILES_DEPTH_ZERO};
+use crate::healpix::utils::MEAN_HPX_CELL_RES;
+use crate::math::angle::Angle;
+use crate::math::lonlat::{ang_between_lonlat, LonLatT};
+use crate::reproject::Sampler;
+use crate::Abort;
+use zip::ZipWriter;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Fits {
+        message: String,
+    },
+    MultiChannel,
+    InvalidTileWidth {
+        width: usize,
+    },
+    NoData,
+    TooManyTiles {
+        num_tiles: usize,
+        max_num_tiles: usize,
+    },
+    Write {
+        reason: &'static str,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Fits { message } => write!(f, "Invalid FITS image: {}", message),
+            Error::MultiChannel => write!(f, "Only single channel images can be tiled"),
+            Error::InvalidTileWidth { width } => write!(
+                f,
+                "Invalid tile width {}, a power of two between 8 and 4096 is expected",
+                width
+            ),
+            Error::NoData => write!(f, "The image does not contain any pixel"),
+            Error::TooManyTiles {
+                num_tiles,
+                max_num_tiles,
+            } => write!(
+                f,
+                "{} tiles would be generated, more than the maximum of {}. \
+                 Choose a lower order or a larger tile width",
+                num_tiles, max_num_tiles
+            ),
+            Error::Write { reason } => write!(f, "Could not write the HiPS: {}", reason),
+        }
+    }
+}
+
+impl From<Error> for JsValue {
+    fn from(err: Error) -> Self {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+/// The options of the HiPS generation, given by the javascript
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HiPSGenParams {
+    /// The IVOA identifier of the HiPS
+    pub creator_did: String,
+    pub title: String,
+    /// The width of the tiles in pixels, a power of two
+    pub tile_width: usize,
+    /// The deepest order of the tiles. It is deduced from the resolution of the image
+    /// if not given.
+    pub order: Option<u8>,
+    /// The maximum number of tiles of the deepest order. By default, the uncompressed tiles
+    /// of all the orders take at most 256 MB.
+    pub max_num_tiles: Option<usize>,
+}
+
+impl Default for HiPSGenParams {
+    fn default() -> Self {
+        Self {
+            creator_did: "ivo://aladin.lite/P/hipsgen".to_string(),
+            title: "HiPS generated by Aladin Lite".to_string(),
+            tile_width: 512,
+            order: None,
+            max_num_tiles: None,
+        }
+    }
+}
+
+/// An image that can be cut into HiPS tiles
+pub trait Source: Sampler {
+    /// The angular size of a pixel of the image in radians
+    fn pixel_size(&self) -> f64;
+
+    /// Positions spread over the image, `step` pixels apart, the border of the image included.
+    /// They are expressed in the frame of the sampler
+    fn positions(&self, step: usize) -> Vec<LonLatT<f64>>;
+}
+
+// The order of the tiles stored in the Allsky file
+const ALLSKY_ORDER: u8 = 3;
+const ALLSKY_MAX_TILE_WIDTH: usize = 64;
+// The tiles are laid out on 27 columns in the Allsky file
+const ALLSKY_NUM_COLS: usize = 27;
+// Fraction of the pixels of the deepest tiles used to compute the cuts
+const CUTS_SAMPLING_STEP: usize = 64;
+// The archive is held in memory, which limits the size of the tiles by default
+const MAX_TILES_SIZE: usize = 256 << 20;
+// Number of pixels sampled at each step of the generation
+const SAMPLES_PER_STEP: usize = 16384;
+
+/// The deepest order of the tiles whose pixels are as small as the pixels of an image
+///
+/// # Arguments
+///
+/// * `pixel_size` - The angular size of a pixel of the image in radians
+/// * `tile_width` - The width of the tiles in pixels, a power of two
+pub fn order_for_resolution(pixel_size: f64, tile_width: usize) -> u8 {
+    let delta_depth = tile_width.trailing_zeros() as usize;
+    let max_depth = MEAN_HPX_CELL_RES.len() - 1;
+
+    let depth = (0..=max_depth)
+        .find(|&d| MEAN_HPX_CELL_RES[d] <= pixel_size)
+        .unwrap_or(max_depth)
+        .max(delta_depth);
+
+    (depth - delta_depth) as u8
+}
+
+// A tile being computed
+struct Frame {
+    cell: HEALPixCell,
+    // The pixels are stored row by row, the rows following the dx axis of the cell and
+    // the columns its dy axis like in the JPEG/PNG tiles.
+    pixels: Vec<f64>,
+    // The next row to sample for a tile of the deepest order, the next child to compute
+    // for the others
+    next: usize,
+}
+
+/// The generation of a HiPS from an image, done step by step
+///
+/// Each step computes a few rows of a tile of the deepest order or a tile of a lower order
+/// from its children, so that the generation can be spread over several frames.
+pub struct HiPSGen<S> {
+    source: S,
+    params: HiPSGenParams,
+    order: u8,
+    tile_width: usize,
+    // The cells of each order from 0 to `order` that may contain data
+    cells: Vec<BTreeSet<u64>>,
+
+    // The tiles being computed, from a tile of order 0 to the current one
+    stack: Vec<Frame>,
+    next_base_cell: u64,
+    num_tiles: usize,
+    num_tiles_done: usize,
+
+    archive: ZipWriter,
+    // The cells of the deepest order containing data
+    moc: BTreeSet<u64>,
+    allsky: Vec<f64>,
+    allsky_tile_width: usize,
+
+    // Physical values used to compute the cuts
+    samples: Vec<f64>,
+    data_range: (f64, f64),
+}
+
+impl<S> HiPSGen<S>
+where
+    S: Source,
+{
+    pub fn new(source: S, params: HiPSGenParams) -> Result<Self, Error> {
+        if source.num_channels() != 1 {
+            return Err(Error::MultiChannel);
+        }
+
+        let tile_width = params.tile_width;
+        if !tile_width.is_power_of_two() || !(8..=4096).contains(&tile_width) {
+            return Err(Error::InvalidTileWidth { width: tile_width });
+        }
+        let delta_depth = tile_width.trailing_zeros() as u8;
+
+        let order = params
+            .order
+            .unwrap_or_else(|| order_for_resolution(source.pixel_size(), tile_width))
+            .min(29 - delta_depth);
+
+        // The tiles of the deepest order covered by the image, found from positions close
+        // enough so that every tile contains at least one of them
+        let tile_size_in_pixels = MEAN_HPX_CELL_RES[order as usize] / source.pixel_size();
+        let step = ((tile_size_in_pixels / 4.0) as usize).max(1);
+        let leaves = source
+            .positions(step)
+            .iter()
+            .map(|lonlat| healpix::nested::hash(order, lonlat.lon().0, lonlat.lat().0))
+            .collect::<BTreeSet<_>>();
+
+        if leaves.is_empty() {
+            return Err(Error::NoData);
+        }
+        let max_num_tiles = params.max_num_tiles.unwrap_or_else(|| {
+            // The tiles of the lower orders add a third to the size of the deepest ones
+            let tile_size =
+                tile_width * tile_width * source.bitpix().value().unsigned_abs() as usize / 8;
+            MAX_TILES_SIZE * 3 / (4 * tile_size)
+        });
+        if leaves.len() > max_num_tiles {
+            return Err(Error::TooManyTiles {
+                num_tiles: leaves.len(),
+                max_num_tiles,
+            });
+        }
+
+        let mut cells = vec![BTreeSet::new(); order as usize + 1];
+        cells[order as usize] = leaves;
+        for d in (0..(order as usize)).rev() {
+            cells[d] = cells[d + 1].iter().map(|idx| idx >> 2).collect();
+        }
+        let num_tiles = cells.iter().map(BTreeSet::len).sum();
+
+        let allsky_tile_width = tile_width.min(ALLSKY_MAX_TILE_WIDTH);
+        let num_allsky_rows =
+            (NUM_HPX_TILES_DEPTH_ZERO << (2 * ALLSKY_ORDER)) / ALLSKY_NUM_COLS + 1;
+        let allsky = vec![
+            f64::NAN;
+            ALLSKY_NUM_COLS * num_allsky_rows * allsky_tile_width * allsky_tile_width
+        ];
+
+        Ok(Self {
+            source,
+            params,
+            order,
+            tile_width,
+            cells,
+            stack: vec![],
+            next_base_cell: 0,
+            num_tiles,
+            num_tiles_done: 0,
+            archive: ZipWriter::new(),
+            moc: BTreeSet::new(),
+            allsky,
+            allsky_tile_width,
+            samples: vec![],
+            data_range: (f64::INFINITY, f64::NEG_INFINITY),
+        })
+    }
+
+    /// The fraction of the tiles computed
+    pub fn progress(&self) -> f32 {
+        self.num_tiles_done as f32 / self.num_tiles as f32
+    }
+
+    /// Compute the next part of a tile. Return true once all the tiles have been computed
+    pub fn step(&mut self) -> Result<bool, Error> {
+        let w = self.tile_width;
+
+        let frame = if let Some(frame) = self.stack.last_mut() {
+            frame
+        } else {
+            // Start the next tile of order 0
+            while (self.next_base_cell as usize) < NUM_HPX_TILES_DEPTH_ZERO {
+                let cell = HEALPixCell(0, self.next_base_cell);
+                self.next_base_cell += 1;
+                if self.push(cell) {
+                    return Ok(false);
+                }
+            }
+
+            return Ok(true);
+        };
+
+        let HEALPixCell(depth, idx) = frame.cell;
+        if depth == self.order && frame.next < w {
+            let rows = frame.next..(frame.next + (SAMPLES_PER_STEP / w).max(1)).min(w);
+            frame.next = rows.end;
+            sample_rows(&self.source, &frame.cell, w, rows, &mut frame.pixels);
+
+            return Ok(false);
+        } else if depth < self.order && frame.next < 4 {
+            let child = HEALPixCell(depth + 1, (idx << 2) + frame.next as u64);
+            frame.next += 1;
+            self.push(child);
+
+            return Ok(false);
+        }
+
+        // All the pixels of the tile are known
+        let Frame { cell, pixels, .. } = self.stack.pop().unwrap_abort();
+        self.num_tiles_done += 1;
+        if let (Some(pixels), Some(parent)) =
+            (self.write_tile(&cell, pixels)?, self.stack.last_mut())
+        {
+            let (x, y) = crate::utils::unmortonize(idx & 3);
+            let half = downsample(&pixels, w, (0, 0, w), w / 2);
+
+            for (row, values) in half.chunks(w / 2).enumerate() {
+                let start = (x as usize * w / 2 + row) * w + y as usize * w / 2;
+                parent.pixels[start..(start + w / 2)].copy_from_slice(values);
+            }
+        }
+
+        Ok(false)
+    }
+
+    // Start computing a tile if it may contain data
+    fn push(&mut self, cell: HEALPixCell) -> bool {
+        if !self.cells[cell.depth() as usize].contains(&cell.idx()) {
+            return false;
+        }
+
+        let w = self.tile_width;
+        self.stack.push(Frame {
+            cell,
+            pixels: vec![f64::NAN; w * w],
+            next: 0,
+        });
+
+        true
+    }
+
+    // Write a tile, returning its pixels unless it does not contain any data
+    fn write_tile(
+        &mut self,
+        cell: &HEALPixCell,
+        pixels: Vec<f64>,
+    ) -> Result<Option<Vec<f64>>, Error> {
+        if pixels.iter().all(|v| v.is_nan()) {
+            return Ok(None);
+        }
+
+        let HEALPixCell(depth, idx) = *cell;
+        if depth == self.order {
+            self.moc.insert(idx);
+            self.add_statistics(&pixels);
+        }
+        if depth == ALLSKY_ORDER || (depth == self.order && depth < ALLSKY_ORDER) {
+            self.add_to_allsky(cell, &pixels);
+        }
+
+        let path = format!(
+            "Norder{}/Dir{}/Npix{}.fits",
+            depth,
+            (idx / 10000) * 10000,
+            idx
+        );
+        let fits = self.to_fits(&pixels, self.tile_width)?;
+        self.archive
+            .add(&path, &fits)
+            .map_err(|reason| Error::Write { reason })?;
+
+        Ok(Some(pixels))
+    }
+
+    fn add_statistics(&mut self, pixels: &[f64]) {
+        let (bscale, bzero, _) = self.source.scaling();
+
+        for (i, raw) in pixels.iter().enumerate().filter(|(_, v)| !v.is_nan()) {
+            let value = raw * bscale + bzero;
+
+            self.data_range.0 = self.data_range.0.min(value);
+            self.data_range.1 = self.data_range.1.max(value);
+            if i % CUTS_SAMPLING_STEP == 0 {
+                self.samples.push(value);
+            }
+        }
+    }
+
+    // Copy the pixels of the cells of order 3 into the Allsky
+    fn add_to_allsky(&mut self, cell: &HEALPixCell, pixels: &[f64]) {
+        let w = self.tile_width;
+        let aw = self.allsky_tile_width;
+        let delta_depth = ALLSKY_ORDER - cell.depth();
+        let sub_width = w >> delta_depth;
+
+        for k in 0..(1u64 << (2 * delta_depth)) {
+            let (x, y) = crate::utils::unmortonize(k);
+            let sub_tile = downsample(
+                pixels,
+                w,
+                (x as usize * sub_width, y as usize * sub_width, sub_width),
+                aw,
+            );
+
+            let idx = ((cell.idx() << (2 * delta_depth)) + k) as usize;
+            let (col, row) = (idx % ALLSKY_NUM_COLS, idx / ALLSKY_NUM_COLS);
+            let allsky_width = ALLSKY_NUM_COLS * aw;
+            for (r, values) in sub_tile.chunks(aw).enumerate() {
+                let start = (row * aw + r) * allsky_width + col * aw;
+                self.allsky[start..(start + aw)].copy_from_slice(values);
+            }
+        }
+    }
+
+    // The rows of the FITS tiles are stored in the reverse order of the JPEG/PNG ones
+    fn to_fits(&self, pixels: &[f64], width: usize) -> Result<Vec<u8>, Error> {
+        let values = pixels
+            .chunks(width)
+            .rev()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+        let height = values.len() / width;
+
+        let (bscale, bzero, blank) = self.source.scaling();
+        fits_writer::write_primary_hdu(
+            &ImageData {
+                bitpix: self.source.bitpix(),
+                naxes: &[width, height],
+                values: &values,
+                bscale,
+                bzero,
+                blank,
+            },
+            &Header::new(),
+        )
+        .map_err(|reason| Error::Write { reason })
+    }
+
+    /// Write the Allsky, the MOC and the properties of the HiPS once all the tiles have been
+    /// computed, returning the zip archive
+    pub fn finish(mut self) -> Result<Vec<u8>, Error> {
+        if self.moc.is_empty() {
+            return Err(Error::NoData);
+        }
+
+        let allsky = std::mem::take(&mut self.allsky);
+        let allsky = self.to_fits(&allsky, ALLSKY_NUM_COLS * self.allsky_tile_width)?;
+        let path = format!("Norder{}/Allsky.fits", ALLSKY_ORDER);
+        self.archive
+            .add(&path, &allsky)
+            .map_err(|reason| Error::Write { reason })?;
+
+        let moc = moc::to_fits(self.order, &self.moc);
+        self.archive
+            .add("Moc.fits", &moc)
+            .map_err(|reason| Error::Write { reason })?;
+
+        let properties = self.properties();
+        self.archive
+            .add("properties", properties.as_bytes())
+            .map_err(|reason| Error::Write { reason })?;
+
+        Ok(self.archive.finish())
+    }
+
+    fn properties(&mut self) -> String {
+        let params = &self.params;
+        let order = self.order;
+        let delta_depth = self.tile_width.trailing_zeros() as usize;
+        let frame = match self.source.frame() {
+            CooSystem::ICRS => "equatorial",
+            CooSystem::GAL => "galactic",
+        };
+
+        // The view is centered on the tiles
+        let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+        for idx in &self.moc {
+            let (lon, lat) = HEALPixCell(order, *idx).center();
+            x += lat.cos() * lon.cos();
+            y += lat.cos() * lon.sin();
+            z += lat.sin();
+        }
+        let center_lon = y.atan2(x).rem_euclid(std::f64::consts::TAU);
+        let center_lat = z.atan2((x * x + y * y).sqrt());
+        let center = LonLatT::new(Angle(center_lon), Angle(center_lat));
+        let radius = self
+            .moc
+            .iter()
+            .map(|idx| {
+                let (lon, lat) = HEALPixCell(order, *idx).center();
+                ang_between_lonlat(center, LonLatT::new(Angle(lon), Angle(lat))).0
+            })
+            .fold(0.0, f64::max)
+            + MEAN_HPX_CELL_RES[order as usize];
+
+        let samples = &mut self.samples;
+        samples.sort_by(|a, b| a.total_cmp(b));
+        let percentile = |p: f64| samples[((samples.len() - 1) as f64 * p).round() as usize];
+        let cuts = if samples.is_empty() {
+            self.data_range
+        } else {
+            (percentile(0.005), percentile(0.995))
+        };
+
+        let sky_fraction =
+            self.moc.len() as f64 / ((NUM_HPX_TILES_DEPTH_ZERO as f64) * 4f64.powi(order as i32));
+
+        let keywords = [
+            ("creator_did", params.creator_did.clone()),
+            ("obs_title", params.title.clone()),
+            ("dataproduct_type", "image".to_string()),
+            ("hips_version", "1.4".to_string()),
+            ("hips_builder", "Aladin Lite".to_string()),
+            ("hips_status", "private master unclonable".to_string()),
+            ("hips_frame", frame.to_string()),
+            ("hips_order", order.to_string()),
+            ("hips_order_min", "0".to_string()),
+            ("hips_tile_width", self.tile_width.to_string()),
+            ("hips_tile_format", "fits".to_string()),
+            (
+                "hips_pixel_bitpix",
+                self.source.bitpix().value().to_string(),
+            ),
+            (
+                "hips_pixel_scale",
+                MEAN_HPX_CELL_RES[order as usize + delta_depth]
+                    .to_degrees()
+                    .to_string(),
+            ),
+            ("hips_pixel_cut", format!("{} {}", cuts.0, cuts.1)),
+            (
+                "hips_data_range",
+                format!("{} {}", self.data_range.0, self.data_range.1),
+            ),
+            ("hips_initial_ra", center_lon.to_degrees().to_string()),
+            ("hips_initial_dec", center_lat.to_degrees().to_string()),
+            ("hips_initial_fov", (2.0 * radius).to_degrees().to_string()),
+            ("moc_sky_fraction", sky_fraction.to_string()),
+        ];
+
+        keywords
+            .iter()
+            .map(|(keyword, value)| format!("{:<20} = {}\n", keyword, value))
+            .collect()
+    }
+}
+
+// Sample rows of pixels of a tile of the deepest order
+fn sample_rows<S: Source>(
+    source: &S,
+    cell: &HEALPixCell,
+    w: usize,
+    rows: Range<usize>,
+    pixels: &mut [f64],
+) {
+    let (_, _, blank) = source.scaling();
+
+    let mut value = [f64::NAN];
+    for row in rows {
+        let dx = (row as f64 + 0.5) / (w as f64);
+        for col in 0..w {
+            let dy = (col as f64 + 0.5) / (w as f64);
+
+            let (lon, lat) = healpix::nested::sph_coo(cell.depth(), cell.idx(), dx, dy);
+            let lonlat = LonLatT::new(Angle(lon), Angle(lat));
+            if source.sample(&lonlat, &mut value) {
+                let is_blank = matches!(blank, Some(blank) if blank as f64 == value[0]);
+                if !is_blank {
+                    pixels[row * w + col] = value[0];
+                }
+            }
+        }
+    }
+}
+
+// Resample a square part of a tile, averaging the pixels when reducing its size
+//
+// # Arguments
+//
+// * `pixels` - The pixels of the tile
+// * `width` - The width of the tile
+// * `(row, col, size)` - The first row, first column and width of the part to resample
+// * `new_width` - The width of the result
+fn downsample(
+    pixels: &[f64],
+    width: usize,
+    (row, col, size): (usize, usize, usize),
+    new_width: usize,
+) -> Vec<f64> {
+    let mut result = vec![f64::NAN; new_width * new_width];
+
+    // The pixels covered by a new pixel along an axis
+    let block = |i: usize| {
+        let start = i * size / new_width;
+        start..((i + 1) * size / new_width).max(start + 1)
+    };
+
+    for r in 0..new_width {
+        for c in 0..new_width {
+            let (cols, mut sum, mut num) = (block(c), 0.0, 0);
+            for i in block(r) {
+                let start = (row + i) * width + col;
+                for v in &pixels[(start + cols.start)..(start + cols.end)] {
+                    if !v.is_nan() {
+                        sum += v;
+                        num += 1;
+                    }
+                }
+            }
+
+            if num > 0 {
+                result[r * new_width + c] = sum / (num as f64);
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{downsample, order_for_resolution, Error, HiPSGen, HiPSGenParams, Source};
+    use crate::fits_writer::Bitpix;
+    use crate::healpix::cell::HEALPixCell;
+    use crate::math::angle::Angle;
+    use crate::math::lonlat::{ang_between_lonlat, LonLatT};
+    use crate::reproject::Sampler;
+    use crate::tile_fetcher::archive::HiPSArchive;
+    use al_api::coo_system::CooSystem;
+    use al_api::hips::properties::Properties;
+    use al_api::hips::{HiPSProperties, ImageExt};
+
+    // A disk of constant value
+    #[derive(Clone, Copy)]
+    struct Disk {
+        center: LonLatT<f64>,
+        radius: f64,
+        pixel_size: f64,
+    }
+
+    impl Sampler for Disk {
+        fn frame(&self) -> CooSystem {
+            CooSystem::ICRS
+        }
+
+        fn bitpix(&self) -> Bitpix {
+            Bitpix::I16
+        }
+
+        fn num_channels(&self) -> usize {
+            1
+        }
+
+        fn scaling(&self) -> (f64, f64, Option<i64>) {
+            (2.0, 1.0, Some(-1))
+        }
+
+        fn sample(&self, lonlat: &LonLatT<f64>, values: &mut [f64]) -> bool {
+            values[0] = 50.0;
+            ang_between_lonlat(self.center, *lonlat).0 <= self.radius
+        }
+    }
+
+    impl Source for Disk {
+        fn pixel_size(&self) -> f64 {
+            self.pixel_size
+        }
+
+        fn positions(&self, step: usize) -> Vec<LonLatT<f64>> {
+            let step = (step as f64) * self.pixel_size;
+            let n = (self.radius / step).ceil() as i64;
+            let (lon, lat) = (self.center.lon().0, self.center.lat().0);
+
+            (-n..=n)
+                .flat_map(|i| (-n..=n).map(move |j| (i, j)))
+                .map(|(i, j)| {
+                    LonLatT::new(
+                        Angle(lon + (i as f64) * step / lat.cos()),
+                        Angle(lat + (j as f64) * step),
+                    )
+                })
+                .filter(|lonlat| ang_between_lonlat(self.center, *lonlat).0 <= self.radius)
+                .collect()
+        }
+    }
+
+    fn generate(disk: Disk, params: HiPSGenParams) -> Result<Vec<u8>, Error> {
+        let mut generator = HiPSGen::new(disk, params)?;
+
+        let mut progress = 0.0;
+        while !generator.step()? {
+            assert!(generator.progress() >= progress);
+            progress = generator.progress();
+        }
+        assert_eq!(generator.progress(), 1.0);
+
+        generator.finish()
+    }
+
+    #[test]
+    fn resolution() {
+        let arcsec = (1.0_f64 / 3600.0).to_radians();
+        assert_eq!(order_for_resolution(arcsec, 512), 9);
+        assert_eq!(order_for_resolution(0.1_f64.to_radians(), 512), 1);
+        // Pixels bigger than the ones of the order 0
+        assert_eq!(order_for_resolution(0.5, 512), 0);
+    }
+
+    #[test]
+    fn average_blocks() {
+        let pixels = [1.0, 3.0, 5.0, f64::NAN, 2.0, 4.0, f64::NAN, f64::NAN];
+        let (width, height) = (4, 2);
+        assert_eq!(pixels.len(), width * height);
+
+        let half = downsample(&pixels, width, (0, 0, 2), 1);
+        assert_eq!(half, vec![2.5]);
+        let right = downsample(&pixels, width, (0, 2, 2), 1);
+        assert_eq!(right, vec![5.0]);
+        // Enlarged
+        let enlarged = downsample(&pixels, width, (0, 1, 1), 2);
+        assert_eq!(enlarged, vec![3.0; 4]);
+    }
+
+    #[test]
+    fn disk() {
+        let disk = Disk {
+            center: LonLatT::new(Angle(1.0), Angle(0.5)),
+            radius: 1.0_f64.to_radians(),
+            pixel_size: 0.03_f64.to_radians(),
+        };
+        let params = HiPSGenParams {
+            tile_width: 64,
+            ..Default::default()
+        };
+
+        let archive = generate(disk, params).unwrap();
+        let archive = HiPSArchive::new(archive.into_boxed_slice()).unwrap();
+
+        let properties = Properties::parse(&archive.get_properties().unwrap().unwrap()).unwrap();
+        let properties = HiPSProperties::from_properties("local", &properties).unwrap();
+        assert_eq!(properties.get_max_order(), 5);
+        assert_eq!(properties.get_tile_size(), 64);
+        assert_eq!(properties.get_bitpix(), Some(16));
+        assert_eq!(properties.get_formats(), &[ImageExt::Fits]);
+        assert_eq!(properties.get_data_range(), Some((101.0, 101.0)));
+        assert!((properties.get_initial_ra().unwrap() - 1.0_f64.to_degrees()).abs() < 1.0);
+        assert!((properties.get_initial_dec().unwrap() - 0.5_f64.to_degrees()).abs() < 1.0);
+
+        // The tile containing the center of the disk at every order
+        for order in 0..=5 {
+            let idx = healpix::nested::hash(order, 1.0, 0.5);
+            assert!(archive.contains_tile(&HEALPixCell(order, idx), ImageExt::Fits));
+        }
+        // The disk is smaller than a tile of order 3
+        assert!(archive.num_tiles() < 6 + 20);
+        assert!(archive.get_moc().is_some());
+
+        let idx = healpix::nested::hash(5, 1.0, 0.5);
+        let tile = archive.get_tile(&HEALPixCell(5, idx), ImageExt::Fits);
+        let tile = tile.unwrap().unwrap();
+        // Header and data of 64x64 16 bits pixels
+        assert_eq!(tile.len(), 2880 * 4);
+        let header = std::str::from_utf8(&tile[..2880]).unwrap();
+        assert!(header.contains("BITPIX  =                   16"));
+        assert!(header.contains("BLANK   =                   -1"));
+        assert!(header.contains(&format!("BZERO   = {:>20}", "1.0E0")));
+    }
+
+    #[test]
+    fn invalid() {
+        let disk = Disk {
+            center: LonLatT::new(Angle(1.0), Angle(0.5)),
+            radius: 1.0_f64.to_radians(),
+            pixel_size: 0.001_f64.to_radians(),
+        };
+
+        let params = HiPSGenParams {
+            tile_width: 100,
+            ..Default::default()
+        };
+        assert!(generate(disk, params).is_err());
+
+        let params = HiPSGenParams {
+            max_num_tiles: Some(10),
+            ..Default::default()
+        };
+        assert!(generate(disk, params).is_err());
+    }
+}
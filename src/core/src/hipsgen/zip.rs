@@ -0,0 +1,160 @@
+//! A minimal zip writer
+//!
+//! Files are deflated unless compressing them does not reduce their size, in which case they
+//! are stored. The archives produced can be read back with `tile_fetcher::archive`.
+
+const LOCAL_HEADER_SIGNATURE: &[u8] = b"PK\x03\x04";
+const CENTRAL_HEADER_SIGNATURE: &[u8] = b"PK\x01\x02";
+const EOCD_SIGNATURE: &[u8] = b"PK\x05\x06";
+
+// Version 2.0 of the specification, needed for deflate and directories
+const VERSION: u16 = 20;
+const DEFLATE_LEVEL: u8 = 6;
+
+#[derive(Default)]
+pub struct ZipWriter {
+    data: Vec<u8>,
+    central_directory: Vec<u8>,
+    num_entries: usize,
+}
+
+impl ZipWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file to the archive
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The path of the file in the archive, directories being separated by `/`
+    /// * `content` - The content of the file
+    pub fn add(&mut self, path: &str, content: &[u8]) -> Result<(), &'static str> {
+        // Zip64 is not supported
+        if content.len() >= u32::MAX as usize || self.num_entries >= u16::MAX as usize {
+            return Err("the archive is too big");
+        }
+
+        let deflated = miniz_oxide::deflate::compress_to_vec(content, DEFLATE_LEVEL);
+        let (method, stored): (u16, &[u8]) = if deflated.len() < content.len() {
+            (8, &deflated)
+        } else {
+            (0, content)
+        };
+
+        let offset = self.data.len();
+        if offset + stored.len() >= u32::MAX as usize {
+            return Err("the archive is too big");
+        }
+
+        // The fields common to the local and central headers
+        let mut header = Vec::with_capacity(26);
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        // Bit 11: the path is encoded in UTF-8
+        header.extend_from_slice(&(1u16 << 11).to_le_bytes());
+        header.extend_from_slice(&method.to_le_bytes());
+        // Modification time and date, 1980-01-01 00:00
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(&(1u16 << 5 | 1).to_le_bytes());
+        header.extend_from_slice(&crc32(content).to_le_bytes());
+        header.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(content.len() as u32).to_le_bytes());
+        header.extend_from_slice(&(path.len() as u16).to_le_bytes());
+
+        self.data.extend_from_slice(LOCAL_HEADER_SIGNATURE);
+        self.data.extend_from_slice(&header);
+        // No extra field
+        self.data.extend_from_slice(&0u16.to_le_bytes());
+        self.data.extend_from_slice(path.as_bytes());
+        self.data.extend_from_slice(stored);
+
+        self.central_directory
+            .extend_from_slice(CENTRAL_HEADER_SIGNATURE);
+        self.central_directory
+            .extend_from_slice(&VERSION.to_le_bytes());
+        self.central_directory.extend_from_slice(&header);
+        // Extra field and comment lengths, disk number, internal and external attributes
+        self.central_directory.extend_from_slice(&[0; 12]);
+        self.central_directory
+            .extend_from_slice(&(offset as u32).to_le_bytes());
+        self.central_directory.extend_from_slice(path.as_bytes());
+
+        self.num_entries += 1;
+
+        Ok(())
+    }
+
+    /// Write the central directory and get the bytes of the archive
+    pub fn finish(mut self) -> Vec<u8> {
+        let cd_offset = self.data.len() as u32;
+        let cd_size = self.central_directory.len() as u32;
+        let num_entries = self.num_entries as u16;
+
+        self.data.extend_from_slice(&self.central_directory);
+
+        self.data.extend_from_slice(EOCD_SIGNATURE);
+        // Disk numbers
+        self.data.extend_from_slice(&[0; 4]);
+        self.data.extend_from_slice(&num_entries.to_le_bytes());
+        self.data.extend_from_slice(&num_entries.to_le_bytes());
+        self.data.extend_from_slice(&cd_size.to_le_bytes());
+        self.data.extend_from_slice(&cd_offset.to_le_bytes());
+        // No comment
+        self.data.extend_from_slice(&0u16.to_le_bytes());
+
+        self.data
+    }
+}
+
+// CRC-32 (ISO-HDLC) of the content of a file
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &b in bytes {
+        crc ^= b as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{crc32, ZipWriter};
+    use crate::healpix::cell::HEALPixCell;
+    use crate::tile_fetcher::archive::HiPSArchive;
+    use al_api::hips::ImageExt;
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn read_back() {
+        // Compressible and incompressible contents
+        let tile = vec![7u8; 10_000];
+        let moc = (0..=255).collect::<Vec<u8>>();
+
+        let mut zip = ZipWriter::new();
+        zip.add("properties", b"hips_tile_format = fits\n").unwrap();
+        zip.add("Moc.fits", &moc).unwrap();
+        zip.add("Norder4/Dir0/Npix42.fits", &tile).unwrap();
+        let data = zip.finish();
+        assert!(data.len() < tile.len());
+
+        let archive = HiPSArchive::new(data.into_boxed_slice()).unwrap();
+        assert_eq!(archive.num_tiles(), 1);
+        assert_eq!(&*archive.get_moc().unwrap().unwrap(), &moc[..]);
+        assert_eq!(
+            archive.get_properties().unwrap().unwrap(),
+            "hips_tile_format = fits\n"
+        );
+
+        let fits = archive.get_tile(&HEALPixCell(4, 42), ImageExt::Fits);
+        assert_eq!(&*fits.unwrap().unwrap(), &tile[..]);
+    }
+}
@@ -95,18 +95,23 @@ use crate::math::angle::ToAngle;
 
 mod app;
 pub mod async_task;
+mod avm;
 mod camera;
 mod shaders;
 
 mod coosys;
 mod downloader;
-mod fifo_cache;
+mod lru_cache;
+mod fits_writer;
 mod healpix;
+mod hipsgen;
 mod inertia;
 pub mod math;
 pub mod renderable;
+mod reproject;
 mod shader;
 mod survey;
+mod table;
 mod tile_fetcher;
 mod time;
 
@@ -123,6 +128,7 @@ use std::io::Cursor;
 
 use al_api::color::{Color, ColorRGBA};
 use al_api::coo_system::CooSystem;
+use al_api::fov::CenteredFoV;
 use al_api::hips::HiPSProperties;
 
 use al_core::colormap::Colormaps;
@@ -219,8 +225,7 @@ impl WebClient {
     /// * `width` - The width in pixels of the view
     /// * `height` - The height in pixels of the view
     pub fn resize(&mut self, width: f32, height: f32) -> Result<(), JsValue> {
-        self.app.resize(width, height);
-        Ok(())
+        self.app.resize(width, height)
     }
 
     /// Set the type of projections
@@ -380,6 +385,49 @@ impl WebClient {
         Ok(())
     }
 
+    /// Add a HiPS from the content of its `properties` file
+    ///
+    /// # Arguments
+    ///
+    /// * `layer` - The layer name
+    /// * `url` - The root url of the HiPS
+    /// * `properties` - The text of the `properties` file
+    /// * `creator_did` - The identifier of the HiPS, replacing the one of the file
+    /// * `meta` - The color and blending configuration of the layer
+    /// * `files` - The tiles of a local HiPS
+    #[wasm_bindgen(js_name = addHiPSFromProperties)]
+    pub fn add_image_hips_from_properties(
+        &mut self,
+        layer: String,
+        url: String,
+        properties: String,
+        creator_did: String,
+        meta: JsValue,
+        files: Option<HiPSLocalFiles>,
+    ) -> Result<(), JsValue> {
+        use al_api::hips::{properties::Properties, HiPSCfg};
+
+        let mut properties = Properties::parse(&properties)?;
+        // The javascript identifies the HiPS even if its properties file does not
+        properties.set("creator_did", creator_did);
+        let properties = HiPSProperties::from_properties(&url, &properties)?;
+        let meta = serde_wasm_bindgen::from_value(meta)?;
+
+        self.app.add_image_hips(
+            HiPSCfg {
+                layer,
+                properties,
+                meta,
+            },
+            files,
+        )
+    }
+
+    /// Add the images of a FITS file to a layer
+    ///
+    /// Its binary and ASCII tables are added as catalogs named `<layer>/<EXTNAME>`, or after
+    /// the index of their extension, whose loading is reported by `popCatalogEvents`. The
+    /// coordinates of the sources are found from the UCDs or the names of the columns
     #[wasm_bindgen(js_name = addImageFITS)]
     pub fn add_image_fits(
         &mut self,
@@ -400,13 +448,120 @@ impl WebClient {
         cfg: JsValue,
         layer: String,
     ) -> Result<js_sys::Promise, JsValue> {
-        use wcs::{WCSParams, WCS};
+        use crate::renderable::image::wcs_keywords::WCSKeywords;
+        let cfg: ImageMetadata = serde_wasm_bindgen::from_value(cfg)?;
+        let keywords: serde_json::Map<String, serde_json::Value> =
+            serde_wasm_bindgen::from_value(wcs)?;
+
+        self.app
+            .add_image_from_blob_and_wcs(layer, stream, WCSKeywords::new(keywords), cfg)
+    }
+
+    /// Add a JPEG/PNG image whose WCS is given by the AVM tags it embeds
+    ///
+    /// # Arguments
+    ///
+    /// * `blob` - The image file
+    /// * `cfg` - The image metadata
+    /// * `layer` - The layer name
+    #[wasm_bindgen(js_name = addImageWithAVM)]
+    pub fn add_image_with_avm(
+        &mut self,
+        blob: web_sys::Blob,
+        cfg: JsValue,
+        layer: String,
+    ) -> Result<js_sys::Promise, JsValue> {
         let cfg: ImageMetadata = serde_wasm_bindgen::from_value(cfg)?;
-        let wcs_params: WCSParams = serde_wasm_bindgen::from_value(wcs)?;
-        let wcs = WCS::new(&wcs_params).map_err(|e| JsValue::from_str(&format!("{:?}", e)))?;
+
+        self.app.add_image_with_avm(layer, blob, cfg)
+    }
+
+    /// Move an image so that a sky position ends at another one
+    ///
+    /// Coordinates must be given in the ICRS coo system.
+    /// Returns the new location of the image
+    ///
+    /// # Arguments
+    ///
+    /// * `layer` - The image layer
+    /// * `ra1` - The right ascension of the position to move, in degrees
+    /// * `dec1` - The declination of the position to move, in degrees
+    /// * `ra2` - The right ascension of the destination, in degrees
+    /// * `dec2` - The declination of the destination, in degrees
+    #[wasm_bindgen(js_name = translateImageWCS)]
+    pub fn translate_image_wcs(
+        &mut self,
+        layer: String,
+        ra1: f64,
+        dec1: f64,
+        ra2: f64,
+        dec2: f64,
+    ) -> Result<CenteredFoV, JsValue> {
+        let from = LonLatT::new(ArcDeg(ra1).into(), ArcDeg(dec1).into());
+        let to = LonLatT::new(ArcDeg(ra2).into(), ArcDeg(dec2).into());
+
+        self.app.translate_image_wcs(&layer, from, to)
+    }
+
+    /// Rotate an image around a sky position
+    ///
+    /// Coordinates must be given in the ICRS coo system.
+    /// Returns the new location of the image
+    ///
+    /// # Arguments
+    ///
+    /// * `layer` - The image layer
+    /// * `ra` - The right ascension of the pivot, in degrees
+    /// * `dec` - The declination of the pivot, in degrees
+    /// * `angle` - The rotation angle in degrees, counted from the north towards the east
+    #[wasm_bindgen(js_name = rotateImageWCS)]
+    pub fn rotate_image_wcs(
+        &mut self,
+        layer: String,
+        ra: f64,
+        dec: f64,
+        angle: f64,
+    ) -> Result<CenteredFoV, JsValue> {
+        let pivot = LonLatT::new(ArcDeg(ra).into(), ArcDeg(dec).into());
 
         self.app
-            .add_image_from_blob_and_wcs(layer, stream, wcs, cfg)
+            .rotate_image_wcs(&layer, pivot, ArcDeg(angle).into())
+    }
+
+    /// Scale an image around a sky position
+    ///
+    /// Coordinates must be given in the ICRS coo system.
+    /// Returns the new location of the image
+    ///
+    /// # Arguments
+    ///
+    /// * `layer` - The image layer
+    /// * `ra` - The right ascension of the pivot, in degrees
+    /// * `dec` - The declination of the pivot, in degrees
+    /// * `factor` - The scale factor, the image grows if greater than 1
+    #[wasm_bindgen(js_name = scaleImageWCS)]
+    pub fn scale_image_wcs(
+        &mut self,
+        layer: String,
+        ra: f64,
+        dec: f64,
+        factor: f64,
+    ) -> Result<CenteredFoV, JsValue> {
+        let pivot = LonLatT::new(ArcDeg(ra).into(), ArcDeg(dec).into());
+
+        self.app.scale_image_wcs(&layer, pivot, factor)
+    }
+
+    /// Get the WCS of an image, adjustments included, as FITS header cards
+    ///
+    /// The 80 characters cards are separated by new lines
+    ///
+    /// # Arguments
+    ///
+    /// * `layer` - The image layer
+    #[wasm_bindgen(js_name = getImageWCSHeader)]
+    pub fn get_image_wcs_header(&self, layer: String) -> Result<String, JsValue> {
+        self.app.get_image_wcs_header(&layer)
     }
 
     #[wasm_bindgen(js_name = removeLayer)]
@@ -438,6 +593,127 @@ impl WebClient {
         self.app.set_hips_url(&cdid, new_url)
     }
 
+    /// Define the mirrors a HiPS can be retrieved from
+    ///
+    /// The latency of each mirror is measured and the fastest one is chosen. The
+    /// current mirror is changed as well if it fails to deliver too many tiles.
+    ///
+    /// # Arguments
+    ///
+    /// * `cdid` - The creator did of the HiPS
+    /// * `urls` - An array of the mirror urls
+    #[wasm_bindgen(js_name = setHiPSMirrors)]
+    pub fn set_hips_mirrors(&mut self, cdid: String, urls: JsValue) -> Result<(), JsValue> {
+        let urls: Vec<String> = serde_wasm_bindgen::from_value(urls)?;
+        self.app.set_hips_mirrors(&cdid, urls)
+    }
+
+    /// Get the HiPS mirror changes that occured since the last call
+    ///
+    /// Returns an array of objects with the `creatorDid` of the HiPS, its `previousUrl`,
+    /// its new `url` and the `reason` of the change
+    #[wasm_bindgen(js_name = popHiPSMirrorSwitches)]
+    pub fn pop_hips_mirror_switches(&mut self) -> Result<JsValue, JsValue> {
+        let switches = self.app.pop_hips_mirror_switches();
+        Ok(serde_wasm_bindgen::to_value(&switches)?)
+    }
+
+    /// Get statistics about the tile requests
+    ///
+    /// Returns an object with the number of tiles queued, in flight, requested, received,
+    /// retried, failed, cancelled and dropped, and the `meanLatency` of the requests in ms
+    #[wasm_bindgen(js_name = getTileFetcherMetrics)]
+    pub fn get_tile_fetcher_metrics(&self) -> Result<JsValue, JsValue> {
+        let metrics = self.app.get_tile_fetcher_metrics();
+        Ok(serde_wasm_bindgen::to_value(&metrics)?)
+    }
+
+    /// Set the duration of the cross-fade between the tiles of a HiPS
+    ///
+    /// A tile fades in over its parent once received, and the tiles of a new
+    /// image format fade in over the ones of the previous format.
+    ///
+    /// # Arguments
+    ///
+    /// * `duration` - The duration of the cross-fade in milliseconds, 0 disables it
+    #[wasm_bindgen(js_name = setTileBlendingDuration)]
+    pub fn set_tile_blending_duration(&mut self, duration: f32) {
+        self.app
+            .set_tile_blending_duration(DeltaTime::from_millis(duration.max(0.0)));
+    }
+
+    /// Set the memory budget of the tiles kept once downloaded
+    ///
+    /// The least recently used tiles are discarded once the budget is exceeded.
+    /// Tiles still kept are not downloaded again when they come back into the view.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_bytes` - The maximum size in bytes of the decoded tiles kept
+    #[wasm_bindgen(js_name = setResourceCacheBudget)]
+    pub fn set_resource_cache_budget(&mut self, num_bytes: usize) {
+        self.app.set_resource_cache_budget(num_bytes);
+    }
+
+    /// Get statistics about the tiles kept once downloaded
+    ///
+    /// Returns an object with the number of cache `numHits`, `numMisses` and `numEvictions`,
+    /// the `numEntries` and `numBytes` currently kept and the `budget` in bytes
+    #[wasm_bindgen(js_name = getResourceCacheStats)]
+    pub fn get_resource_cache_stats(&self) -> Result<JsValue, JsValue> {
+        let stats = self.app.get_resource_cache_stats();
+        Ok(serde_wasm_bindgen::to_value(&stats)?)
+    }
+
+    /// Set the GPU memory shared by the textures of the HiPS layers
+    ///
+    /// The number of textures each HiPS layer keeps on the GPU depends on the size of the
+    /// view and on its share of this budget.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_bytes` - The maximum size in bytes of the textures of all the HiPS layers
+    #[wasm_bindgen(js_name = setGPUTextureBudget)]
+    pub fn set_gpu_texture_budget(&mut self, num_bytes: usize) -> Result<(), JsValue> {
+        self.app.set_gpu_texture_budget(num_bytes)
+    }
+
+    /// Get the residency counters of the textures of a HiPS layer
+    ///
+    /// Returns an object with the `numSlices` of the texture array, the `numTexturesBySideSlice`,
+    /// the `numTextures` that can be stored, the `numTexturesUsed`, the `numEvictions`
+    /// and the `numBytes` of GPU memory allocated
+    ///
+    /// # Arguments
+    ///
+    /// * `layer` - The name of the HiPS layer
+    #[wasm_bindgen(js_name = getTextureResidency)]
+    pub fn get_texture_residency(&self, layer: String) -> Result<JsValue, JsValue> {
+        let residency = self.app.get_texture_residency(&layer)?;
+        Ok(serde_wasm_bindgen::to_value(&residency)?)
+    }
+
+    /// Display another frame of a HiPS cube
+    #[wasm_bindgen(js_name = setHiPSCubeFrame)]
+    pub fn set_hips_cube_frame(&mut self, layer: String, frame: u32) -> Result<(), JsValue> {
+        self.app.set_hips_cube_frame(&layer, frame)
+    }
+
+    #[wasm_bindgen(js_name = getHiPSCubeFrame)]
+    pub fn get_hips_cube_frame(&self, layer: String) -> Result<u32, JsValue> {
+        self.app.get_hips_cube_frame(&layer)
+    }
+
+    /// Get the coordinate of a frame along the third axis of a HiPS cube
+    #[wasm_bindgen(js_name = getHiPSCubeFrameCoordinate)]
+    pub fn get_hips_cube_frame_coordinate(
+        &self,
+        layer: String,
+        frame: u32,
+    ) -> Result<Option<f64>, JsValue> {
+        self.app.get_hips_cube_frame_coordinate(&layer, frame)
+    }
+
     #[wasm_bindgen(js_name = getImageMetadata)]
     pub fn get_layer_cfg(&self, layer: String) -> Result<ImageMetadata, JsValue> {
         self.app.get_layer_cfg(&layer)
@@ -799,10 +1075,17 @@ impl WebClient {
 
     /// Add a catalog rendered as a heatmap.
     ///
+    /// Its sources are parsed by chunks during the next frames, the progress of the
+    /// parsing being reported by `popCatalogEvents`. A catalog of the same name is replaced
+    /// at once, the catalog being drawable with `setCatalogCfg` while its sources are parsed.
+    ///
     /// # Arguments
     ///
     /// * `name_catalog` - The name of the catalog
-    /// * `data` - The list of the catalog sources.
+    /// * `data` - The list of the catalog sources, in degrees. It can be an array of `[ra, dec]`
+    ///   arrays or of `{ra, dec}` objects, a typed array of interleaved `ra` and `dec`, an object
+    ///   whose `ra` and `dec` are (typed) arrays, or a table returned by `parseVOTable`. The
+    ///   other numerical columns of the objects and tables can drive the markers.
     /// * `colormap` - The name of the colormap. Check out the list of possible colormaps names `getAvailableColormapList`.
     #[wasm_bindgen(js_name = addCatalog)]
     pub fn add_catalog(
@@ -811,27 +1094,62 @@ impl WebClient {
         data: JsValue,
         colormap: String,
     ) -> Result<(), JsValue> {
-        self.app.add_catalog(name_catalog, data, colormap);
-
-        Ok(())
+        self.app.add_catalog(name_catalog, data, colormap)
     }
 
-    /// Set the catalog heatmap colormap
+    /// Add a progressive catalogue (HiPS catalogue) rendered as a heatmap.
+    ///
+    /// Its tiles are fetched as they come into the view.
     ///
     /// # Arguments
     ///
-    /// * `name_catalog` - The name of the catalog to apply this change to
+    /// * `name_catalog` - The name of the catalog
+    /// * `url` - The root url of the progressive catalogue
+    /// * `max_order` - The deepest order of its tiles, given by the `hips_order` property
     /// * `colormap` - The name of the colormap. Check out the list of possible colormaps names `getAvailableColormapList`.
+    #[wasm_bindgen(js_name = addHiPSCatalog)]
+    pub fn add_hips_catalog(
+        &mut self,
+        name_catalog: String,
+        url: String,
+        max_order: u8,
+        colormap: String,
+    ) -> Result<(), JsValue> {
+        self.app.add_hips_catalog(name_catalog, url, max_order, colormap);
+
+        Ok(())
+    }
+
+    /// Remove a catalog
     ///
-    /// # Panics
+    /// # Arguments
     ///
-    /// If the catalog has not been found
+    /// * `name_catalog` - The name of the catalog to remove
+    #[wasm_bindgen(js_name = removeCatalog)]
+    pub fn remove_catalog(&mut self, name_catalog: String) -> Result<(), JsValue> {
+        self.app.remove_catalog(name_catalog);
+
+        Ok(())
+    }
+
+    /// Check whether catalogs have been added and all their sources have been parsed
     #[wasm_bindgen(js_name = isCatalogLoaded)]
     pub fn is_catalog_loaded(&mut self) -> Result<bool, JsValue> {
         let cat_loaded = self.app.is_catalog_loaded();
         Ok(cat_loaded)
     }
 
+    /// Get the catalog loading events that occured since the last call
+    ///
+    /// Returns an array of objects with the `name` of the catalog and their `type`:
+    /// * `progress` - Its sources are being parsed, `progress` giving the fraction parsed
+    /// * `loaded` - It is drawn, `numSources` giving its number of sources
+    #[wasm_bindgen(js_name = popCatalogEvents)]
+    pub fn pop_catalog_events(&mut self) -> Result<JsValue, JsValue> {
+        let events = self.app.pop_catalog_events();
+        Ok(serde_wasm_bindgen::to_value(&events)?)
+    }
+
     /// Set the catalog heatmap opacity
     ///
     /// # Arguments
@@ -853,6 +1171,38 @@ impl WebClient {
         Ok(())
     }
 
+    /// Set how the sources of a catalog are drawn
+    ///
+    /// # Arguments
+    ///
+    /// * `name_catalog` - The name of the catalog to apply this change to
+    /// * `cfg` - An object giving the `mode` (`heatmap` or `markers`), the `color`,
+    ///   `opacity`, `shape` (`circle`, `square`, `cross`, `plus`, `rhomb` or `triangle`),
+    ///   `size` and `sizeUnit` (`pixel` or `arcsec`) of the markers. Their size and color
+    ///   can be taken from numerical columns with `sizeColumn` (`column`, `sizes` and
+    ///   `range`) and `colorColumn` (`column`, `colormap`, `range` and `reversed`).
+    ///   The density map is set by `heatmap`: its `kernel` (`gaussian`, `epanechnikov` or
+    ///   `tophat`), `bandwidth` and `bandwidthUnit`, the `weightColumn` weighting the
+    ///   sources, the `stretch` of the colormap and the `range` of densities it covers.
+    ///   The selected sources are highlighted with the `selectionColor`. At wide fields of
+    ///   view, the markers can be grouped by HEALPix cells with `aggregation`: the `cellSize`
+    ///   in pixels, the `maxOrder` of the cells and the `sizes` of their circles.
+    ///   The sources can be hidden by a `filter`, a condition on the numerical columns
+    ///   such as `mag < 12 and (isnull(plx) or plx > 0)` combining arithmetic,
+    ///   comparisons, `and`, `or`, `not` and the `abs`, `sqrt`, `log10`, `ln`, `exp`,
+    ///   `pow`, `min` and `max` functions. The sources for which it is null are hidden
+    ///
+    /// # Errors
+    ///
+    /// If the catalog or one of its mapped columns has not been found, or if the filter
+    /// is not valid
+    #[wasm_bindgen(js_name = setCatalogCfg)]
+    pub fn set_catalog_cfg(&mut self, name_catalog: String, cfg: JsValue) -> Result<(), JsValue> {
+        let cfg = serde_wasm_bindgen::from_value(cfg)?;
+
+        self.app.set_catalog_cfg(name_catalog, cfg)
+    }
+
     /// Set the kernel strength for the catalog heatmap rendering
     ///
     /// # Arguments
@@ -874,6 +1224,150 @@ impl WebClient {
         Ok(())
     }
 
+    /// Pick the sources of a catalog under the cursor
+    ///
+    /// Returns the rows of the sources, in the table given to `addCatalog`, lying within
+    /// a radius around the screen position, the nearest first
+    ///
+    /// # Arguments
+    ///
+    /// * `name_catalog` - The name of the catalog
+    /// * `pos_x` - The x screen coordinate in pixels
+    /// * `pos_y` - The y screen coordinate in pixels
+    /// * `radius` - The radius in pixels
+    #[wasm_bindgen(js_name = pickCatalogSources)]
+    pub fn pick_catalog_sources(
+        &self,
+        name_catalog: String,
+        pos_x: f64,
+        pos_y: f64,
+        radius: f64,
+    ) -> Result<Box<[u32]>, JsValue> {
+        self.app
+            .pick_catalog_sources(&name_catalog, &Vector2::new(pos_x, pos_y), radius)
+    }
+
+    /// Select the sources of a catalog lying in a box of the sky
+    ///
+    /// The selection replaces the previous one and is highlighted with the `selectionColor`
+    /// of the catalog. Returns the sorted rows of the selected sources
+    ///
+    /// # Arguments
+    ///
+    /// * `name_catalog` - The name of the catalog
+    /// * `lon_min` - The minimum longitude in degrees
+    /// * `lat_min` - The minimum latitude in degrees
+    /// * `lon_max` - The maximum longitude in degrees. The box crosses the meridian of
+    ///   longitude 0 if it is lower than `lon_min`
+    /// * `lat_max` - The maximum latitude in degrees
+    #[wasm_bindgen(js_name = selectCatalogSourcesInBox)]
+    pub fn select_catalog_sources_in_box(
+        &mut self,
+        name_catalog: String,
+        lon_min: f64,
+        lat_min: f64,
+        lon_max: f64,
+        lat_max: f64,
+    ) -> Result<Box<[u32]>, JsValue> {
+        let min = LonLatT::new(ArcDeg(lon_min).into(), ArcDeg(lat_min).into());
+        let max = LonLatT::new(ArcDeg(lon_max).into(), ArcDeg(lat_max).into());
+
+        self.app
+            .select_catalog_sources_in_zone(&name_catalog, &min, &max)
+    }
+
+    /// Select the sources of a catalog lying in a polygon of the sky, e.g. a lasso
+    ///
+    /// The selection replaces the previous one and is highlighted with the `selectionColor`
+    /// of the catalog. Returns the sorted rows of the selected sources
+    ///
+    /// # Arguments
+    ///
+    /// * `name_catalog` - The name of the catalog
+    /// * `lon` - The longitudes of the vertices in degrees
+    /// * `lat` - The latitudes of the vertices in degrees
+    #[wasm_bindgen(js_name = selectCatalogSourcesInPolygon)]
+    pub fn select_catalog_sources_in_polygon(
+        &mut self,
+        name_catalog: String,
+        lon: &[f64],
+        lat: &[f64],
+    ) -> Result<Box<[u32]>, JsValue> {
+        let vertices = lon
+            .iter()
+            .zip(lat.iter())
+            .map(|(&lon, &lat)| LonLatT::new(ArcDeg(lon).into(), ArcDeg(lat).into()))
+            .collect::<Vec<_>>();
+
+        self.app
+            .select_catalog_sources_in_polygon(&name_catalog, &vertices)
+    }
+
+    /// Set the selected sources of a catalog
+    ///
+    /// # Arguments
+    ///
+    /// * `name_catalog` - The name of the catalog
+    /// * `rows` - The rows of the sources to select. An empty list clears the selection
+    #[wasm_bindgen(js_name = setCatalogSelection)]
+    pub fn set_catalog_selection(
+        &mut self,
+        name_catalog: String,
+        rows: &[u32],
+    ) -> Result<(), JsValue> {
+        self.app.set_catalog_selection(&name_catalog, rows.to_vec())
+    }
+
+    /// Cross-match the sources of two catalogs by their position
+    ///
+    /// The sources are matched in the background during the next frames. The promise returned
+    /// is resolved with an object giving the `rows` of the sources of the first catalog matched,
+    /// the `otherRows` of their counterparts in the second catalog and the `separations` of the
+    /// pairs in arcseconds. Only the `rows` without any counterpart are given for the `noMatch`
+    /// mode
+    ///
+    /// # Arguments
+    ///
+    /// * `name_catalog` - The name of the first catalog
+    /// * `name_other_catalog` - The name of the second catalog
+    /// * `cfg` - An object giving the `mode` (`best`, `all` or `noMatch`), the `radius` in
+    ///   arcseconds and the numerical columns giving the error radii of the sources of the
+    ///   first and second catalogs in arcseconds, `errorColumn` and `otherErrorColumn`. Two
+    ///   sources match if their separation is lower than the quadratic sum of the radius and
+    ///   of their error radii
+    /// * `name_matches` - If given, the pairs are added as a catalog of this name, drawn at the
+    ///   position of the sources of the first catalog. Its columns are the ones of the first
+    ///   catalog, the ones of the second catalog prefixed by its name and the `separation`
+    ///
+    /// # Panics
+    ///
+    /// If a catalog or an error column has not been found
+    #[wasm_bindgen(js_name = crossMatchCatalogs)]
+    pub fn cross_match_catalogs(
+        &mut self,
+        name_catalog: String,
+        name_other_catalog: String,
+        cfg: JsValue,
+        name_matches: Option<String>,
+    ) -> Result<js_sys::Promise, JsValue> {
+        let cfg = serde_wasm_bindgen::from_value(cfg)?;
+
+        self.app
+            .cross_match_catalogs(&name_catalog, &name_other_catalog, cfg, name_matches)
+    }
+
+    /// Get the sorted rows of the selected sources of a catalog
+    #[wasm_bindgen(js_name = getCatalogSelection)]
+    pub fn get_catalog_selection(&self, name_catalog: String) -> Result<Box<[u32]>, JsValue> {
+        self.app.get_catalog_selection(&name_catalog)
+    }
+
+    /// Get the sorted rows of the sources of a catalog passing its filter
+    #[wasm_bindgen(js_name = getCatalogRows)]
+    pub fn get_catalog_rows(&self, name_catalog: String) -> Result<Box<[u32]>, JsValue> {
+        self.app.get_catalog_rows(&name_catalog)
+    }
+
     /// Project a line to the screen
     ///
     /// # Returns
@@ -973,6 +1467,7 @@ impl WebClient {
     /// - An array of 3 items (rgb) for JPG tiles
     /// - An array of 4 items (rgba) for PNG tiles
     /// - A single value for FITS tiles
+    /// - A `{r, g, b}` object with the values of the channels for RGB composites
     ///
     /// # Arguments
     ///
@@ -985,6 +1480,56 @@ impl WebClient {
         Ok(pixel)
     }
 
+    /// Reproject a layer onto a WCS and export it as a FITS file
+    ///
+    /// The pixels are resampled on the CPU from the tiles/images currently
+    /// loaded and are written at the bit depth of the layer.
+    ///
+    /// # Arguments
+    ///
+    /// * `layer` - The layer to reproject
+    /// * `wcs` - The FITS keywords of the output WCS (NAXIS, CTYPE, CRVAL, CRPIX, CD...).
+    ///   If undefined, the WCS of the current view is taken
+    #[wasm_bindgen(js_name = reprojectToFITS)]
+    pub fn reproject_to_fits(&self, layer: String, wcs: JsValue) -> Result<Vec<u8>, JsValue> {
+        let wcs = if wcs.is_undefined() || wcs.is_null() {
+            None
+        } else {
+            Some(serde_wasm_bindgen::from_value(wcs)?)
+        };
+
+        self.app.reproject_to_fits(&layer, wcs)
+    }
+
+    /// Cut a FITS image into the tiles of a HiPS
+    ///
+    /// The deepest order of the tiles is the one whose pixels match the pixels of the image.
+    /// The tiles are computed in the background during the next frames. The promise returned
+    /// is resolved with the `HiPSLocalFiles` of a zip archive containing the tiles, the Allsky,
+    /// the MOC and the properties of the HiPS, that can be displayed as a local HiPS.
+    ///
+    /// # Arguments
+    ///
+    /// * `fits` - The bytes of the FITS file, a 2D image with a celestial WCS
+    /// * `params` - The options of the generation (creatorDid, title, tileWidth, order,
+    ///   maxNumTiles), all optional
+    /// * `on_progress` - Called with the fraction of the tiles computed
+    #[wasm_bindgen(js_name = generateHiPS)]
+    pub fn generate_hips(
+        &mut self,
+        fits: Box<[u8]>,
+        params: JsValue,
+        on_progress: Option<js_sys::Function>,
+    ) -> Result<js_sys::Promise, JsValue> {
+        let params: hipsgen::HiPSGenParams = if params.is_undefined() || params.is_null() {
+            Default::default()
+        } else {
+            serde_wasm_bindgen::from_value(params)?
+        };
+
+        self.app.generate_hips(&fits, params, on_progress)
+    }
+
     #[wasm_bindgen(js_name = getVisibleCells)]
     pub fn get_visible_cells(&self, depth: u8) -> Result<JsValue, JsValue> {
         let cells = self.app.get_visible_cells(depth);
@@ -1001,17 +1546,77 @@ impl WebClient {
         self.app.draw_grid_labels()
     }
 
+    /// Parse the tables of a VOTable, kept for the callers of this method
+    ///
+    /// See the `parseVOTable` function
     #[wasm_bindgen(js_name = parseVOTable)]
-    pub fn parse_votable(&mut self, _s: &str) -> Result<JsValue, JsValue> {
-        /*let votable: VOTableWrapper<votable::impls::mem::InMemTableDataRows> =
-            votable::votable::VOTableWrapper::from_ivoa_xml_str(s)
-                .map_err(|err| JsValue::from_str(&format!("Error parsing votable: {:?}", err)))?;
+    pub fn parse_votable(&self, votable: &str) -> Result<js_sys::Array, JsValue> {
+        parse_votable(votable)
+    }
+
+    /// Check whether catalogs have their markers aggregated, whose numbers of sources
+    /// are drawn by `drawCatalogLabels`
+    #[wasm_bindgen(js_name = hasCatalogLabels)]
+    pub fn has_catalog_labels(&self) -> bool {
+        self.app.has_catalog_labels()
+    }
+
+    /// Draw the numbers of sources of the aggregated markers onto the catalog canvas
+    #[wasm_bindgen(js_name = drawCatalogLabels)]
+    pub fn draw_catalog_labels(&mut self) -> Result<(), JsValue> {
+        self.app.draw_catalog_labels()
+    }
+
+    /// Write a table, or some of its rows, to a file to download
+    ///
+    /// The VOTables keep the units, the UCDs and the descriptions of the columns, the
+    /// FITS files their units and UCDs and the CSV files only their names.
+    ///
+    /// # Arguments
+    ///
+    /// * `table` - A table as returned by `parseVOTable`
+    /// * `format` - `votable` (TABLEDATA serialization), `votable-binary2`, `csv` (with a
+    ///   header line giving the names of the columns) or `fits` (BINTABLE)
+    /// * `rows` - The rows to write, e.g. given by `getCatalogSelection` or
+    ///   `getCatalogRows`. All the rows are written if not given
+    #[wasm_bindgen(js_name = writeTable)]
+    pub fn write_table(
+        &self,
+        table: JsValue,
+        format: &str,
+        rows: Option<Box<[u32]>>,
+    ) -> Result<Vec<u8>, JsValue> {
+        let mut table = table::Table::from_js(&table)?;
+        if let Some(rows) = rows {
+            table = table.select_rows(&rows);
+        }
 
-        let votable = serde_wasm_bindgen::to_value(&votable)
-            .map_err(|_| JsValue::from_str("cannot convert votable to js type"))?;
+        write_table_in(&table, format)
+    }
 
-        Ok(votable)*/
-        Ok(JsValue::null())
+    /// Write the table of a catalog, or some of its rows, to a file to download
+    ///
+    /// Only the catalogs whose table has been read by the backend, e.g. from a FITS
+    /// file, keep it. The other ones are written with `writeTable`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name_catalog` - The name of the catalog
+    /// * `format` - `votable`, `votable-binary2`, `csv` or `fits`, as for `writeTable`
+    /// * `rows` - The rows to write, e.g. given by `getCatalogSelection` or
+    ///   `getCatalogRows`. All the rows are written if not given
+    #[wasm_bindgen(js_name = writeCatalog)]
+    pub fn write_catalog(
+        &self,
+        name_catalog: String,
+        format: &str,
+        rows: Option<Box<[u32]>>,
+    ) -> Result<Vec<u8>, JsValue> {
+        let table = self.app.get_catalog_table(&name_catalog)?;
+        match rows {
+            Some(rows) => write_table_in(&table.select_rows(&rows), format),
+            None => write_table_in(table, format),
+        }
     }
 
     #[wasm_bindgen(js_name = addJSONMoc)]
@@ -1166,6 +1771,65 @@ impl WebClient {
             0.0
         }
     }
+
+    /// Add a contour overlay traced on an image or HiPS layer
+    ///
+    /// # Arguments
+    ///
+    /// * `cfg` - The contour options: `name`, source `layer`, `levels`
+    ///   (`{type: 'explicit', values}`, `{type: 'linear', n, min?, max?}`,
+    ///   `{type: 'log', n, min?, max?}` or `{type: 'sigma', values}`),
+    ///   HiPS tile `order`, `color`, `thickness` and `show`
+    #[wasm_bindgen(js_name = addContour)]
+    pub fn add_contour(&mut self, cfg: JsValue) -> Result<(), JsValue> {
+        let cfg = serde_wasm_bindgen::from_value(cfg)?;
+
+        self.app.add_contour(cfg)
+    }
+
+    #[wasm_bindgen(js_name = removeContour)]
+    pub fn remove_contour(&mut self, name: String) -> Result<(), JsValue> {
+        self.app.remove_contour(&name)
+    }
+
+    #[wasm_bindgen(js_name = setContourOptions)]
+    pub fn set_contour_cfg(&mut self, cfg: JsValue) -> Result<(), JsValue> {
+        let cfg = serde_wasm_bindgen::from_value(cfg)?;
+
+        self.app.set_contour_cfg(cfg)
+    }
+
+    /// Get the lines of a contour overlay
+    ///
+    /// Returns an array of `{level, closed, vertices}` objects, `vertices` being
+    /// an array of ICRS `[ra, dec]` positions in degrees
+    #[wasm_bindgen(js_name = getContourPolygons)]
+    pub fn get_contour_polygons(&self, name: String) -> Result<JsValue, JsValue> {
+        self.app.get_contour_polygons(&name)
+    }
+
+    /// Add a color composite of three grayscale layers
+    ///
+    /// The composite is removed as any other layer with `removeLayer`
+    ///
+    /// # Arguments
+    ///
+    /// * `cfg` - The composite options: its `layer` name, the `red`, `green`
+    ///   and `blue` channels (`{layer, minCut?, maxCut?, scale?}`), the `q` and
+    ///   `stretch` parameters of the asinh stretch and its `opacity`
+    #[wasm_bindgen(js_name = addRGBComposite)]
+    pub fn add_rgb_composite(&mut self, cfg: JsValue) -> Result<(), JsValue> {
+        let cfg = serde_wasm_bindgen::from_value(cfg)?;
+
+        self.app.add_rgb_composite(cfg)
+    }
+
+    #[wasm_bindgen(js_name = setRGBCompositeOptions)]
+    pub fn set_rgb_composite_cfg(&mut self, cfg: JsValue) -> Result<(), JsValue> {
+        let cfg = serde_wasm_bindgen::from_value(cfg)?;
+
+        self.app.set_rgb_composite_cfg(cfg)
+    }
 }
 
 #[wasm_bindgen]
@@ -1251,3 +1915,61 @@ pub fn hpx_ang2pix(nside: u32, lon: &[f64], lat: &[f64]) -> Result<Box<[u64]>, J
 
     Ok(vertices)
 }
+
+/* HiPS properties */
+#[wasm_bindgen(js_name = parseHiPSProperties)]
+pub fn parse_hips_properties(text: &str) -> Result<js_sys::Object, JsValue> {
+    let properties = al_api::hips::properties::Properties::parse(text)?;
+
+    let keywords = js_sys::Object::new();
+    for (keyword, value) in properties.iter() {
+        // A keyword given several times keeps its last value
+        js_sys::Reflect::set(&keywords, &keyword.into(), &value.into())?;
+    }
+
+    Ok(keywords)
+}
+
+/* VOTable */
+/// Parse the tables of a VOTable
+///
+/// The TABLEDATA, BINARY, BINARY2 and FITS serializations are read, the binary
+/// streams being embedded in the document.
+///
+/// Returns an array with an object per table giving its `name`, its `fields`
+/// (`name`, `ID`, `ucd`, `unit`, `datatype`, `arraysize` and `description`), its
+/// `numRows`, its `columns` as typed arrays (arrays of strings for the char columns,
+/// arrays of Float64Array for the array cells), the `nulls` of each column as an
+/// Uint8Array or null, and the indices `ra` and `dec` of the columns of the main
+/// equatorial position if their UCDs are found
+///
+/// # Arguments
+///
+/// * `votable` - The text of the VOTable document
+#[wasm_bindgen(js_name = parseVOTable)]
+pub fn parse_votable(votable: &str) -> Result<js_sys::Array, JsValue> {
+    table::votable::parse(votable)?
+        .iter()
+        .map(|table| table.to_js())
+        .collect()
+}
+
+// Write a table in one of the formats given to `writeTable`
+fn write_table_in(table: &table::Table, format: &str) -> Result<Vec<u8>, JsValue> {
+    match format {
+        "votable" => Ok(table::votable::write(
+            table,
+            table::votable::Serialization::TableData,
+        )),
+        "votable-binary2" => Ok(table::votable::write(
+            table,
+            table::votable::Serialization::Binary2,
+        )),
+        "csv" => Ok(table::csv::write(table)),
+        "fits" => Ok(table::fits::write_bintable(table)),
+        _ => Err(JsValue::from_str(&format!(
+            "{} is not a table format, expected votable, votable-binary2, csv or fits",
+            format
+        ))),
+    }
+}
@@ -0,0 +1,241 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// Statistics about the use of a cache, exposed to the javascript
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub num_hits: usize,
+    pub num_misses: usize,
+    pub num_evictions: usize,
+    pub num_entries: usize,
+    // Size in bytes of the values stored
+    pub num_bytes: usize,
+    // Maximum size in bytes of the values stored
+    pub budget: usize,
+}
+
+struct Entry<V> {
+    val: V,
+    // Size of the value in bytes
+    size: usize,
+    // The last time the value has been accessed
+    tick: u64,
+}
+
+/// A Least Recently Used cache bounded by the size in bytes of its values
+///
+/// The least recently accessed values are evicted once the total size of the values
+/// exceeds the budget of the cache.
+pub struct Cache<K, V> {
+    data: HashMap<K, Entry<V>>,
+    // The keys sorted by their last access, the least recently used first
+    order: BTreeMap<u64, K>,
+    tick: u64,
+
+    num_bytes: usize,
+    budget: usize,
+
+    num_hits: usize,
+    num_misses: usize,
+    num_evictions: usize,
+}
+
+impl<K, V> Cache<K, V>
+where
+    K: Clone + std::cmp::Eq + Hash,
+{
+    /// Create an empty cache
+    ///
+    /// # Arguments
+    ///
+    /// * `budget` - The maximum size in bytes of the values stored
+    pub fn new(budget: usize) -> Self {
+        Cache {
+            data: HashMap::new(),
+            order: BTreeMap::new(),
+            tick: 0,
+            num_bytes: 0,
+            budget,
+            num_hits: 0,
+            num_misses: 0,
+            num_evictions: 0,
+        }
+    }
+
+    /// Insert a value, evicting the least recently used ones to make room for it
+    ///
+    /// A value bigger than the whole budget is not stored.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key of the value
+    /// * `val` - The value to store
+    /// * `size` - The size in bytes of the value
+    pub fn insert(&mut self, key: K, val: V, size: usize) {
+        self.extract(&key);
+
+        if size > self.budget {
+            return;
+        }
+
+        self.num_bytes += size;
+        self.evict();
+
+        let tick = self.next_tick();
+        self.order.insert(tick, key.clone());
+        self.data.insert(key, Entry { val, size, tick });
+    }
+
+    /// Get a value, marking it as the most recently used one
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let tick = self.next_tick();
+
+        if let Some(entry) = self.data.get_mut(key) {
+            self.num_hits += 1;
+
+            self.order.remove(&entry.tick);
+            self.order.insert(tick, key.clone());
+            entry.tick = tick;
+
+            Some(&entry.val)
+        } else {
+            self.num_misses += 1;
+
+            None
+        }
+    }
+
+    /// Remove a value from the cache
+    pub fn extract(&mut self, key: &K) -> Option<V> {
+        let entry = self.data.remove(key)?;
+        self.order.remove(&entry.tick);
+        self.num_bytes -= entry.size;
+
+        Some(entry.val)
+    }
+
+    /// Tell whether a value is stored, without marking it as used
+    pub fn contains(&self, key: &K) -> bool {
+        self.data.contains_key(key)
+    }
+
+    /// Change the maximum size in bytes of the values stored
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict();
+    }
+
+    pub fn get_stats(&self) -> CacheStats {
+        CacheStats {
+            num_hits: self.num_hits,
+            num_misses: self.num_misses,
+            num_evictions: self.num_evictions,
+            num_entries: self.data.len(),
+            num_bytes: self.num_bytes,
+            budget: self.budget,
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    // Remove the least recently used values until the budget is respected
+    fn evict(&mut self) {
+        while self.num_bytes > self.budget {
+            if let Some((_, key)) = self.order.pop_first() {
+                if let Some(entry) = self.data.remove(&key) {
+                    self.num_bytes -= entry.size;
+                    self.num_evictions += 1;
+                }
+            } else {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cache;
+
+    #[test]
+    fn least_recently_used_evicted() {
+        let mut cache = Cache::new(100);
+
+        cache.insert("a", 'a', 40);
+        cache.insert("b", 'b', 40);
+        // "a" becomes more recent than "b"
+        assert_eq!(cache.get(&"a"), Some(&'a'));
+
+        cache.insert("c", 'c', 40);
+        assert!(cache.contains(&"a"));
+        assert!(!cache.contains(&"b"));
+        assert!(cache.contains(&"c"));
+
+        let stats = cache.get_stats();
+        assert_eq!(stats.num_evictions, 1);
+        assert_eq!(stats.num_entries, 2);
+        assert_eq!(stats.num_bytes, 80);
+    }
+
+    #[test]
+    fn evicted_by_size() {
+        let mut cache = Cache::new(100);
+
+        for i in 0..10 {
+            cache.insert(i, i, 10);
+        }
+        // A big value evicts several small ones
+        cache.insert(10, 10, 55);
+        assert_eq!(cache.get_stats().num_entries, 5);
+        assert!((0..6).all(|i| !cache.contains(&i)));
+        assert!((6..11).all(|i| cache.contains(&i)));
+
+        // Too big to be stored at all
+        cache.insert(11, 11, 101);
+        assert!(!cache.contains(&11));
+        assert_eq!(cache.get_stats().num_bytes, 95);
+    }
+
+    #[test]
+    fn replace_and_extract() {
+        let mut cache = Cache::new(100);
+
+        cache.insert("a", 1, 60);
+        cache.insert("a", 2, 30);
+        assert_eq!(cache.get_stats().num_bytes, 30);
+        assert_eq!(cache.get(&"a"), Some(&2));
+
+        assert_eq!(cache.extract(&"a"), Some(2));
+        assert_eq!(cache.extract(&"a"), None);
+        assert_eq!(cache.get_stats().num_bytes, 0);
+    }
+
+    #[test]
+    fn stats_and_budget() {
+        let mut cache = Cache::new(100);
+
+        cache.insert("a", 'a', 30);
+        cache.insert("b", 'b', 30);
+        cache.insert("c", 'c', 30);
+        cache.get(&"a");
+        cache.get(&"d");
+        cache.get(&"d");
+
+        // Shrinking the budget evicts the least recently used values
+        cache.set_budget(50);
+        assert!(cache.contains(&"a"));
+        assert!(!cache.contains(&"b"));
+        assert!(!cache.contains(&"c"));
+
+        let stats = cache.get_stats();
+        assert_eq!(stats.num_hits, 1);
+        assert_eq!(stats.num_misses, 2);
+        assert_eq!(stats.num_evictions, 2);
+        assert_eq!(stats.budget, 50);
+    }
+}
@@ -0,0 +1,215 @@
+use al_api::catalog::{Aggregation, Catalog as CatalogCfg, Shape, SizeUnit};
+use cgmath::Vector3;
+
+use super::filter::Mask;
+use super::marker::{Columns, Mapping};
+use super::Source;
+use crate::healpix::cell::HEALPixCell;
+use crate::healpix::index_vector::IdxVec;
+use crate::math::angle::Angle;
+use crate::math::lonlat::{self, LonLatT};
+
+// The deepest order of the cells, the sources being indexed at depth 7
+const MAX_ORDER: u8 = 7;
+
+/// The sources of a HEALPix cell drawn as a single marker
+#[derive(Clone, Copy, Debug)]
+pub struct Aggregate {
+    /// The mean position of the sources
+    pub lonlat: LonLatT<f32>,
+    pub count: u32,
+    /// The mean of the color column, None if all the sources are null
+    pub mean: Option<f32>,
+}
+
+/// The order of the cells grouping the sources, None if the sources are drawn individually
+///
+/// # Arguments
+///
+/// * `cfg` - The aggregation of the catalog
+/// * `px_size` - The size of a screen pixel in radians
+pub fn get_order(cfg: &Aggregation, px_size: f64) -> Option<u8> {
+    // The cells of order d are about sqrt(pi / 3) / 2^d radians wide
+    let cell_size = (cfg.cell_size as f64) * px_size;
+    let order = ((std::f64::consts::PI / 3.0).sqrt() / cell_size)
+        .log2()
+        .floor()
+        .max(0.0);
+
+    (order <= cfg.max_order.min(MAX_ORDER) as f64).then_some(order as u8)
+}
+
+/// Group the sources lying in cells of depth <= 7
+///
+/// # Arguments
+///
+/// * `mask` - The sources shown by the filter of the catalog
+/// * `values` - The values of the column giving the color of the markers
+pub fn get_aggregates(
+    sources: &[Source],
+    index_vec: &IdxVec,
+    mask: &Mask,
+    cells: impl Iterator<Item = HEALPixCell>,
+    values: Option<&[f32]>,
+) -> Vec<Aggregate> {
+    cells
+        .filter_map(|cell| {
+            let sources = &sources[index_vec.get_item_indices_inside_hpx_cell(&cell)];
+
+            let mut sum = Vector3::new(0.0, 0.0, 0.0);
+            let (mut sum_values, mut num_values) = (0.0, 0);
+            let mut count = 0;
+            for source in sources.iter().filter(|source| mask.is_shown(source.row)) {
+                count += 1;
+                let LonLatT(lon, lat) = source.lonlat;
+                sum += lonlat::radec_to_xyz(Angle(lon.0 as f64), Angle(lat.0 as f64));
+
+                let value = values.and_then(|values| values.get(source.row as usize));
+                if let Some(value) = value.filter(|value| value.is_finite()) {
+                    sum_values += *value as f64;
+                    num_values += 1;
+                }
+            }
+
+            if count == 0 {
+                return None;
+            }
+
+            let (lon, lat) = lonlat::xyz_to_radec(&sum);
+            let lonlat = LonLatT::new(Angle(lon.0 as f32), Angle(lat.0 as f32));
+
+            Some(Aggregate {
+                lonlat,
+                count,
+                mean: (num_values > 0).then_some((sum_values / num_values as f64) as f32),
+            })
+        })
+        .collect()
+}
+
+/// The config of the markers of the aggregates, drawn as circles whose size is in pixels
+pub fn get_markers_cfg(cfg: &CatalogCfg) -> CatalogCfg {
+    CatalogCfg {
+        shape: Shape::Circle,
+        size_unit: SizeUnit::Pixel,
+        ..cfg.clone()
+    }
+}
+
+/// Compute the data of the markers of the aggregates
+///
+/// Their size goes logarithmically with their number of sources and their color is given
+/// by the mean of the color column, like the markers of the sources
+pub fn get_markers(aggregates: &[Aggregate], columns: &Columns, cfg: &CatalogCfg) -> Vec<f32> {
+    let [s0, s1] = cfg
+        .aggregation
+        .as_ref()
+        .map(|aggregation| aggregation.sizes)
+        .unwrap_or_default();
+    let color = cfg.color_column.as_ref().and_then(|mapping| {
+        let values = columns.get(&mapping.column)?;
+        Some((Mapping::new(values, mapping.range), mapping.reversed))
+    });
+
+    let max_count = aggregates.iter().map(|a| a.count).max().unwrap_or(0);
+    let mut markers = Vec::with_capacity(aggregates.len() * 4);
+    for aggregate in aggregates {
+        let t = if max_count > 1 {
+            (aggregate.count as f32).ln() / (max_count as f32).ln()
+        } else {
+            0.0
+        };
+        let value = color
+            .as_ref()
+            .and_then(|(mapping, reversed)| {
+                let t = mapping.normalize(aggregate.mean?)?;
+                Some(if *reversed { 1.0 - t } else { t })
+            })
+            .unwrap_or(-1.0);
+
+        markers.extend([
+            aggregate.lonlat.0 .0,
+            aggregate.lonlat.1 .0,
+            s0 + t * (s1 - s0),
+            value,
+        ]);
+    }
+
+    markers
+}
+
+/// The label of the number of sources of an aggregate
+pub fn format_count(count: u32) -> String {
+    if count < 1_000 {
+        count.to_string()
+    } else if count < 1_000_000 {
+        format!("{:.1}k", count as f32 / 1e3)
+    } else {
+        format!("{:.1}M", count as f32 / 1e6)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_count, get_aggregates, get_markers, get_order};
+    use crate::healpix::cell::HEALPixCell;
+    use crate::healpix::index_vector::IdxVec;
+    use crate::math::angle::Angle;
+    use crate::math::lonlat::LonLatT;
+    use crate::renderable::catalog::filter::{Filter, Mask};
+    use crate::renderable::catalog::{marker::Columns, Source};
+    use al_api::catalog::{Aggregation, Catalog, ColorMapping};
+
+    #[test]
+    fn aggregate_sources() {
+        let mut sources = [(0.1, 0.1), (0.1, 0.3), (3.0, -1.0)]
+            .iter()
+            .enumerate()
+            .map(|(row, &(lon, lat))| Source::new(LonLatT::new(Angle(lon), Angle(lat)), row as u32))
+            .collect::<Vec<_>>();
+        let index_vec = IdxVec::from_coo(&mut sources);
+        let columns: Columns = [("bv".to_string(), vec![1.0, f32::NAN, 3.0].into())]
+            .iter()
+            .cloned()
+            .collect();
+
+        let values = columns.get("bv").map(|values| &values[..]);
+        let cells = HEALPixCell::allsky(0);
+        let mut aggregates = get_aggregates(&sources, &index_vec, &Mask::default(), cells, values);
+        aggregates.sort_by_key(|aggregate| aggregate.count);
+        assert_eq!(aggregates.len(), 2);
+        assert_eq!(aggregates[1].count, 2);
+        assert!((aggregates[1].lonlat.1 .0 - 0.2).abs() < 1e-3);
+        // The null values are not counted in the mean
+        assert_eq!(aggregates[1].mean, Some(1.0));
+
+        // The sources hidden by the filter are not counted, nor their cells
+        let mask = Mask::new(Some(&Filter::new("bv > 2").unwrap()), &columns, 3);
+        let cells = HEALPixCell::allsky(0);
+        let hidden = get_aggregates(&sources, &index_vec, &mask, cells, values);
+        assert_eq!(hidden.len(), 1);
+        assert_eq!(hidden[0].count, 1);
+
+        let cfg = Catalog {
+            aggregation: Some(Aggregation::default()),
+            color_column: Some(ColorMapping {
+                column: "bv".to_string(),
+                colormap: "viridis".to_string(),
+                range: Some([0.0, 4.0]),
+                reversed: false,
+            }),
+            ..Default::default()
+        };
+        let markers = get_markers(&aggregates, &columns, &cfg);
+        assert_eq!(markers[2..4], [12.0, 0.75]);
+        assert_eq!(markers[6..8], [40.0, 0.25]);
+
+        // 64 pixels of one arcminute give cells of order 5
+        let px_size = (1.0_f64 / 60.0).to_radians();
+        assert_eq!(get_order(&Aggregation::default(), px_size), Some(5));
+        assert_eq!(get_order(&Aggregation::default(), px_size / 8.0), None);
+
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(12_345), "12.3k");
+    }
+}
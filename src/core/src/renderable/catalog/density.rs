@@ -0,0 +1,288 @@
+use std::f64::consts::PI;
+use std::ops::Range;
+
+use al_api::catalog::{Heatmap, Kernel};
+use al_api::coo_system::CooSystem;
+
+use al_core::{SliceData, VecData, VertexArrayObject, WebGlContext};
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+use super::filter::Mask;
+use super::marker::Columns;
+use super::Source;
+use crate::{CameraViewPort, ProjectionType, ShaderManager};
+
+// The number of floats describing a kernel: the position of its source and its weight
+const NUM_F32_PER_KERNEL: usize = 3;
+
+// The weight of a source, the null values not contributing to the density
+fn get_weight(values: Option<&[f32]>, row: u32) -> f32 {
+    values.map_or(1.0, |values| {
+        values
+            .get(row as usize)
+            .copied()
+            .filter(|weight| weight.is_finite())
+            .unwrap_or(0.0)
+    })
+}
+
+/// The weights of the sources of a heatmap, all the sources weighting 1
+/// if they are not given by a column
+pub struct Weights {
+    // The sum of the weights of the sources before each one
+    cumulative: Box<[f64]>,
+    // The weight of the heaviest source
+    max: f32,
+}
+
+impl Weights {
+    /// The sources hidden by the mask weigh nothing
+    pub fn new(sources: &[Source], mask: &Mask, columns: &Columns, cfg: &Heatmap) -> Self {
+        let values = cfg
+            .weight_column
+            .as_ref()
+            .and_then(|column| columns.get(column))
+            .map(|values| &values[..]);
+
+        let mut cumulative = Vec::with_capacity(sources.len() + 1);
+        let (mut sum, mut max) = (0.0, 0.0_f32);
+        cumulative.push(sum);
+        for source in sources {
+            let weight = if mask.is_shown(source.row) {
+                get_weight(values, source.row)
+            } else {
+                0.0
+            };
+            sum += weight as f64;
+            max = max.max(weight);
+
+            cumulative.push(sum);
+        }
+
+        Self {
+            cumulative: cumulative.into_boxed_slice(),
+            max,
+        }
+    }
+
+    /// The factor bringing the weight of the heaviest source to 1
+    pub fn scale(&self) -> f32 {
+        if self.max > 0.0 {
+            1.0 / self.max
+        } else {
+            1.0
+        }
+    }
+
+    /// The sum of the weights of a range of sources
+    pub fn sum(&self, sources: Range<usize>) -> f64 {
+        self.cumulative[sources.end] - self.cumulative[sources.start]
+    }
+}
+
+/// Compute the data of the kernels of the sources, given by their position in
+/// radians and their weight
+pub fn get_kernels(sources: &[Source], columns: &Columns, cfg: &Heatmap) -> Vec<f32> {
+    let values = cfg
+        .weight_column
+        .as_ref()
+        .and_then(|column| columns.get(column))
+        .map(|values| &values[..]);
+
+    let mut kernels = Vec::with_capacity(sources.len() * NUM_F32_PER_KERNEL);
+    for source in sources {
+        let weight = get_weight(values, source.row);
+        // The sources without weight are not drawn
+        if weight != 0.0 {
+            kernels.extend([source.lonlat.0 .0, source.lonlat.1 .0, weight]);
+        }
+    }
+
+    kernels
+}
+
+/// Estimate the maximum density of the view
+///
+/// The sources are supposed to be uniformly spread in the HEALPix cells of depth 7,
+/// the maximum density being at least the weight of the heaviest source
+///
+/// # Arguments
+///
+/// * `max_cell_weight` - The sum of the weights of the sources of the heaviest cell of the view
+/// * `bandwidth` - The bandwidth of the kernel in pixels
+/// * `cell_area` - The area of a cell of depth 7 in square pixels
+pub fn get_max_density(
+    max_cell_weight: f64,
+    weights: &Weights,
+    kernel: Kernel,
+    bandwidth: f32,
+    cell_area: f64,
+) -> f32 {
+    let h2 = (bandwidth as f64) * (bandwidth as f64);
+    // The integral of the kernel, whose value is 1 at its center
+    let kernel_area = match kernel {
+        Kernel::Gaussian => 2.0 * PI * h2,
+        Kernel::Epanechnikov => 0.5 * PI * h2,
+        Kernel::Tophat => PI * h2,
+    };
+
+    ((max_cell_weight * kernel_area / cell_area) as f32).max(weights.max)
+}
+
+/// The kernels of the sources of a heatmap, summed in a float framebuffer
+pub struct Density {
+    vao: VertexArrayObject,
+    num_instances: i32,
+}
+
+impl Density {
+    pub fn new(gl: &WebGlContext) -> Self {
+        let mut vao = VertexArrayObject::new(gl);
+
+        vao.bind_for_update()
+            // The corners of the kernel
+            .add_array_buffer(
+                "vertices",
+                2 * std::mem::size_of::<f32>(),
+                &[2],
+                &[0],
+                WebGl2RenderingContext::STATIC_DRAW,
+                SliceData(&[-1.0_f32, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0]),
+            )
+            // The position and weight of each source
+            .add_instanced_array_buffer(
+                "kernels",
+                NUM_F32_PER_KERNEL * std::mem::size_of::<f32>(),
+                &[2, 1],
+                &[0, 2 * std::mem::size_of::<f32>()],
+                WebGl2RenderingContext::STATIC_DRAW,
+                SliceData(&[]),
+            )
+            .add_element_buffer(
+                WebGl2RenderingContext::STATIC_DRAW,
+                SliceData(&[0_u16, 1, 2, 0, 2, 3]),
+            )
+            .unbind();
+
+        Self {
+            vao,
+            num_instances: 0,
+        }
+    }
+
+    /// Send the kernels computed by `get_kernels` to the GPU
+    pub fn set_kernels(&mut self, kernels: Vec<f32>) {
+        self.num_instances = (kernels.len() / NUM_F32_PER_KERNEL) as i32;
+
+        self.vao.bind_for_update().update_instanced_array(
+            "kernels",
+            WebGl2RenderingContext::STATIC_DRAW,
+            VecData(&kernels),
+        );
+    }
+
+    /// Sum the kernels onto the framebuffer bound, their weights being multiplied by
+    /// `weight_scale`
+    pub fn draw(
+        &self,
+        gl: &WebGlContext,
+        shaders: &mut ShaderManager,
+        camera: &CameraViewPort,
+        proj: &ProjectionType,
+        cfg: &Heatmap,
+        bandwidth: f32,
+        weight_scale: f32,
+    ) -> Result<(), JsValue> {
+        let icrs2world = camera.get_m2w() * CooSystem::ICRS.to(camera.get_coo_system());
+        let kernel = match cfg.kernel {
+            Kernel::Gaussian => 0,
+            Kernel::Epanechnikov => 1,
+            Kernel::Tophat => 2,
+        };
+
+        gl.blend_func(WebGl2RenderingContext::ONE, WebGl2RenderingContext::ONE);
+
+        crate::shader::get_shader(
+            gl,
+            shaders,
+            "catalogs_density.vert",
+            "catalogs_density.frag",
+        )?
+        .bind(gl)
+        .attach_uniforms_from(camera)
+        .attach_uniform("u_2world", &icrs2world)
+        .attach_uniform("u_proj", proj)
+        .attach_uniform("u_screen_size", &camera.get_screen_size())
+        .attach_uniform("u_bandwidth", &bandwidth)
+        .attach_uniform("u_kernel", &kernel)
+        .attach_uniform("u_weight_scale", &weight_scale)
+        .bind_vertex_array_object_ref(&self.vao)
+        .draw_elements_instanced_with_i32(
+            WebGl2RenderingContext::TRIANGLES,
+            0,
+            self.num_instances,
+        );
+
+        // Restore the blending of the layers
+        gl.blend_func_separate(
+            WebGl2RenderingContext::SRC_ALPHA,
+            WebGl2RenderingContext::ONE,
+            WebGl2RenderingContext::ONE,
+            WebGl2RenderingContext::ONE,
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_kernels, get_max_density, Weights};
+    use crate::math::angle::Angle;
+    use crate::math::lonlat::LonLatT;
+    use crate::renderable::catalog::filter::{Filter, Mask};
+    use crate::renderable::catalog::{marker::Columns, Source};
+    use al_api::catalog::{Heatmap, Kernel};
+
+    #[test]
+    fn weight_sources() {
+        let sources = [2, 0, 1]
+            .iter()
+            .map(|&row| Source::new(LonLatT::new(Angle(0.1), Angle(0.2)), row))
+            .collect::<Vec<_>>();
+        let columns: Columns = [("flux".to_string(), vec![2.0, f32::NAN, 5.0].into())]
+            .iter()
+            .cloned()
+            .collect();
+
+        // Each source weights 1 by default
+        let cfg = Heatmap::default();
+        let weights = Weights::new(&sources, &Mask::default(), &columns, &cfg);
+        assert_eq!(weights.sum(0..3), 3.0);
+        // The sources hidden by the filter weigh nothing
+        let filter = Filter::new("flux > 3").unwrap();
+        let mask = Mask::new(Some(&filter), &columns, 3);
+        assert_eq!(Weights::new(&sources, &mask, &columns, &cfg).sum(0..3), 1.0);
+        assert_eq!(get_kernels(&sources, &columns, &cfg).len(), 3 * 3);
+
+        let cfg = Heatmap {
+            weight_column: Some("flux".to_string()),
+            ..Default::default()
+        };
+        let weights = Weights::new(&sources, &Mask::default(), &columns, &cfg);
+        assert_eq!(weights.sum(0..1), 5.0);
+        assert_eq!(weights.sum(1..3), 2.0);
+        // The heaviest source weighs 1 once scaled
+        assert_eq!(weights.scale(), 0.2);
+        // The source with a null weight is not drawn
+        let kernels = get_kernels(&sources, &columns, &cfg);
+        assert_eq!(kernels, [0.1, 0.2, 5.0, 0.1, 0.2, 2.0]);
+
+        // A cell of 100 square pixels with a weight of 50 and a tophat of radius 1
+        let density = get_max_density(50.0, &weights, Kernel::Tophat, 1.0, 100.0);
+        assert_eq!(density, 5.0);
+        let density = get_max_density(500.0, &weights, Kernel::Tophat, 1.0, 100.0);
+        assert!((density - 5.0 * std::f32::consts::PI).abs() < 1e-4);
+    }
+}
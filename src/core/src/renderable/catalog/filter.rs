@@ -0,0 +1,700 @@
+use std::fmt;
+
+use wasm_bindgen::JsValue;
+
+use super::marker::Columns;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Syntax { reason: String, position: usize },
+    UnknownColumn { column: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Syntax { reason, position } => {
+                write!(f, "Invalid filter at character {}: {}", position, reason)
+            }
+            Error::UnknownColumn { column } => {
+                write!(f, "{} is not a numerical column of the catalog", column)
+            }
+        }
+    }
+}
+
+impl From<Error> for JsValue {
+    fn from(err: Error) -> Self {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    // A keyword, a function or a column
+    Ident(String),
+    // A column whose name is quoted
+    Column(String),
+    Op(&'static str),
+}
+
+// The longest operators come first so that they are not split
+const OPERATORS: &[&str] = &[
+    "<=", ">=", "==", "!=", "<>", "&&", "||", "<", ">", "=", "+", "-", "*", "/", "%", "!", "(",
+    ")", ",",
+];
+
+// The length of the number at the start of a string
+fn get_number_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let digits = |mut i: usize| {
+        while i < bytes.len() && (bytes[i].is_ascii_digit() || bytes[i] == b'.') {
+            i += 1;
+        }
+        i
+    };
+
+    let len = digits(0);
+    if matches!(bytes.get(len), Some(b'e') | Some(b'E')) {
+        let mut exp = len + 1;
+        if matches!(bytes.get(exp), Some(b'+') | Some(b'-')) {
+            exp += 1;
+        }
+        if matches!(bytes.get(exp), Some(b) if b.is_ascii_digit()) {
+            return digits(exp);
+        }
+    }
+
+    len
+}
+
+fn tokenize(expr: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let error = |reason: &str, position| Error::Syntax {
+        reason: reason.to_string(),
+        position,
+    };
+
+    let mut tokens = vec![];
+    let mut pos = 0;
+    while let Some(c) = expr[pos..].chars().next() {
+        let rest = &expr[pos..];
+
+        let (token, len) = if c.is_whitespace() {
+            pos += c.len_utf8();
+            continue;
+        } else if c.is_ascii_digit() || (c == '.' && get_number_len(rest) > 1) {
+            let len = get_number_len(rest);
+            let value = rest[..len]
+                .parse()
+                .map_err(|_| error("invalid number", pos))?;
+
+            (Token::Number(value), len)
+        } else if c.is_alphabetic() || c == '_' {
+            let len = rest
+                .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+                .unwrap_or(rest.len());
+
+            (Token::Ident(rest[..len].to_string()), len)
+        } else if c == '"' {
+            let len = rest[1..]
+                .find('"')
+                .ok_or_else(|| error("unclosed quoted column", pos))?;
+
+            (Token::Column(rest[1..(len + 1)].to_string()), len + 2)
+        } else if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            (Token::Op(op), op.len())
+        } else {
+            return Err(error("unexpected character", pos));
+        };
+
+        tokens.push((token, pos));
+        pos += len;
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Arith {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Cmp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Func {
+    Abs,
+    Sqrt,
+    Log10,
+    Ln,
+    Exp,
+    Pow,
+    Min,
+    Max,
+}
+
+// A numerical expression, NaN being the null value
+#[derive(Debug)]
+enum Num {
+    Value(f64),
+    // The index of a column in the columns of the filter
+    Column(usize),
+    Neg(Box<Num>),
+    Arith(Arith, Box<Num>, Box<Num>),
+    Call(Func, Vec<Num>),
+}
+
+// A condition, None being the null value
+#[derive(Debug)]
+enum Cond {
+    Value(Option<bool>),
+    Compare(Cmp, Num, Num),
+    IsNull(Num),
+    Not(Box<Cond>),
+    And(Box<Cond>, Box<Cond>),
+    Or(Box<Cond>, Box<Cond>),
+}
+
+impl Num {
+    fn eval(&self, columns: &[&[f32]], row: usize) -> f64 {
+        match self {
+            Num::Value(value) => *value,
+            Num::Column(column) => columns[*column]
+                .get(row)
+                .map_or(f64::NAN, |&value| value as f64),
+            Num::Neg(num) => -num.eval(columns, row),
+            Num::Arith(op, left, right) => {
+                let (a, b) = (left.eval(columns, row), right.eval(columns, row));
+                match op {
+                    Arith::Add => a + b,
+                    Arith::Sub => a - b,
+                    Arith::Mul => a * b,
+                    Arith::Div => a / b,
+                    Arith::Rem => a % b,
+                }
+            }
+            Num::Call(func, args) => {
+                let a = args[0].eval(columns, row);
+                let b = || args[1].eval(columns, row);
+                // Unlike f64::min and f64::max, a null argument gives null
+                let min_max = |f: fn(f64, f64) -> f64| {
+                    let b = b();
+                    if a.is_nan() || b.is_nan() {
+                        f64::NAN
+                    } else {
+                        f(a, b)
+                    }
+                };
+
+                let value = match func {
+                    Func::Abs => a.abs(),
+                    Func::Sqrt => a.sqrt(),
+                    Func::Log10 => a.log10(),
+                    Func::Ln => a.ln(),
+                    Func::Exp => a.exp(),
+                    Func::Pow => a.powf(b()),
+                    Func::Min => min_max(f64::min),
+                    Func::Max => min_max(f64::max),
+                };
+
+                // The infinities, e.g. log10(0), are null
+                if value.is_finite() {
+                    value
+                } else {
+                    f64::NAN
+                }
+            }
+        }
+    }
+}
+
+impl Cond {
+    fn eval(&self, columns: &[&[f32]], row: usize) -> Option<bool> {
+        match self {
+            Cond::Value(value) => *value,
+            Cond::Compare(op, left, right) => {
+                let (a, b) = (left.eval(columns, row), right.eval(columns, row));
+                if a.is_nan() || b.is_nan() {
+                    return None;
+                }
+
+                Some(match op {
+                    Cmp::Lt => a < b,
+                    Cmp::Le => a <= b,
+                    Cmp::Gt => a > b,
+                    Cmp::Ge => a >= b,
+                    Cmp::Eq => a == b,
+                    Cmp::Ne => a != b,
+                })
+            }
+            Cond::IsNull(num) => Some(num.eval(columns, row).is_nan()),
+            Cond::Not(cond) => cond.eval(columns, row).map(|value| !value),
+            Cond::And(left, right) => match left.eval(columns, row) {
+                Some(false) => Some(false),
+                value => match right.eval(columns, row) {
+                    Some(false) => Some(false),
+                    Some(true) => value,
+                    None => None,
+                },
+            },
+            Cond::Or(left, right) => match left.eval(columns, row) {
+                Some(true) => Some(true),
+                value => match right.eval(columns, row) {
+                    Some(true) => Some(true),
+                    Some(false) => value,
+                    None => None,
+                },
+            },
+        }
+    }
+}
+
+// A parsed expression with the position where it starts
+enum Expr {
+    Num(Num, usize),
+    Cond(Cond, usize),
+}
+
+impl Expr {
+    fn into_num(self) -> Result<Num, Error> {
+        match self {
+            Expr::Num(num, _) => Ok(num),
+            Expr::Cond(_, position) => Err(Error::Syntax {
+                reason: "expected a number, found a condition".to_string(),
+                position,
+            }),
+        }
+    }
+
+    fn into_cond(self) -> Result<Cond, Error> {
+        match self {
+            Expr::Cond(cond, _) => Ok(cond),
+            Expr::Num(_, position) => Err(Error::Syntax {
+                reason: "expected a condition, found a number".to_string(),
+                position,
+            }),
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    // The index of the next token
+    next: usize,
+    // The length of the expression, where the errors about its end are located
+    len: usize,
+    // The names of the columns referred to
+    columns: Vec<String>,
+}
+
+impl Parser {
+    fn position(&self) -> usize {
+        self.tokens
+            .get(self.next)
+            .map_or(self.len, |(_, position)| *position)
+    }
+
+    fn error(&self, reason: &str) -> Error {
+        Error::Syntax {
+            reason: reason.to_string(),
+            position: self.position(),
+        }
+    }
+
+    // Consume the next token if it is one of these operators or keywords
+    fn eat(&mut self, ops: &[&str]) -> bool {
+        let found = match self.tokens.get(self.next) {
+            Some((Token::Op(op), _)) => ops.contains(op),
+            Some((Token::Ident(ident), _)) => ops.iter().any(|op| ident.eq_ignore_ascii_case(op)),
+            _ => false,
+        };
+        if found {
+            self.next += 1;
+        }
+
+        found
+    }
+
+    fn column(&mut self, name: String) -> Num {
+        let column = self
+            .columns
+            .iter()
+            .position(|column| column == &name)
+            .unwrap_or_else(|| {
+                self.columns.push(name);
+                self.columns.len() - 1
+            });
+
+        Num::Column(column)
+    }
+
+    fn or(&mut self) -> Result<Expr, Error> {
+        let mut left = self.and()?;
+        while self.eat(&["or", "||"]) {
+            let right = self.and()?;
+            let position = left_position(&left);
+            let cond = Cond::Or(Box::new(left.into_cond()?), Box::new(right.into_cond()?));
+            left = Expr::Cond(cond, position);
+        }
+
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, Error> {
+        let mut left = self.not()?;
+        while self.eat(&["and", "&&"]) {
+            let right = self.not()?;
+            let position = left_position(&left);
+            let cond = Cond::And(Box::new(left.into_cond()?), Box::new(right.into_cond()?));
+            left = Expr::Cond(cond, position);
+        }
+
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, Error> {
+        let position = self.position();
+        if self.eat(&["not", "!"]) {
+            let cond = self.not()?.into_cond()?;
+            Ok(Expr::Cond(Cond::Not(Box::new(cond)), position))
+        } else {
+            self.compare()
+        }
+    }
+
+    fn compare(&mut self) -> Result<Expr, Error> {
+        let left = self.add()?;
+
+        let op = match self.tokens.get(self.next) {
+            Some((Token::Op("<"), _)) => Cmp::Lt,
+            Some((Token::Op("<="), _)) => Cmp::Le,
+            Some((Token::Op(">"), _)) => Cmp::Gt,
+            Some((Token::Op(">="), _)) => Cmp::Ge,
+            Some((Token::Op("=" | "=="), _)) => Cmp::Eq,
+            Some((Token::Op("!=" | "<>"), _)) => Cmp::Ne,
+            _ => return Ok(left),
+        };
+        self.next += 1;
+
+        let right = self.add()?;
+        let position = left_position(&left);
+        let cond = Cond::Compare(op, left.into_num()?, right.into_num()?);
+
+        Ok(Expr::Cond(cond, position))
+    }
+
+    fn add(&mut self) -> Result<Expr, Error> {
+        let mut left = self.mul()?;
+        loop {
+            let op = if self.eat(&["+"]) {
+                Arith::Add
+            } else if self.eat(&["-"]) {
+                Arith::Sub
+            } else {
+                return Ok(left);
+            };
+
+            let right = self.mul()?;
+            let position = left_position(&left);
+            let num = Num::Arith(op, Box::new(left.into_num()?), Box::new(right.into_num()?));
+            left = Expr::Num(num, position);
+        }
+    }
+
+    fn mul(&mut self) -> Result<Expr, Error> {
+        let mut left = self.neg()?;
+        loop {
+            let op = if self.eat(&["*"]) {
+                Arith::Mul
+            } else if self.eat(&["/"]) {
+                Arith::Div
+            } else if self.eat(&["%"]) {
+                Arith::Rem
+            } else {
+                return Ok(left);
+            };
+
+            let right = self.neg()?;
+            let position = left_position(&left);
+            let num = Num::Arith(op, Box::new(left.into_num()?), Box::new(right.into_num()?));
+            left = Expr::Num(num, position);
+        }
+    }
+
+    fn neg(&mut self) -> Result<Expr, Error> {
+        let position = self.position();
+        if self.eat(&["-"]) {
+            let num = self.neg()?.into_num()?;
+            Ok(Expr::Num(Num::Neg(Box::new(num)), position))
+        } else {
+            self.primary()
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let position = self.position();
+        let token = if let Some((token, _)) = self.tokens.get(self.next) {
+            token.clone()
+        } else {
+            return Err(self.error("unexpected end of the filter"));
+        };
+        self.next += 1;
+
+        match token {
+            Token::Number(value) => Ok(Expr::Num(Num::Value(value), position)),
+            Token::Column(name) => Ok(Expr::Num(self.column(name), position)),
+            Token::Op("(") => {
+                let expr = self.or()?;
+                if !self.eat(&[")"]) {
+                    return Err(self.error("expected a closing parenthesis"));
+                }
+
+                // The expression starts at the opening parenthesis
+                Ok(match expr {
+                    Expr::Num(num, _) => Expr::Num(num, position),
+                    Expr::Cond(cond, _) => Expr::Cond(cond, position),
+                })
+            }
+            Token::Ident(ident) => match ident.to_ascii_lowercase().as_str() {
+                "null" => Ok(Expr::Num(Num::Value(f64::NAN), position)),
+                "true" => Ok(Expr::Cond(Cond::Value(Some(true)), position)),
+                "false" => Ok(Expr::Cond(Cond::Value(Some(false)), position)),
+                func if self.eat(&["("]) => self.call(func, position),
+                _ => Ok(Expr::Num(self.column(ident), position)),
+            },
+            Token::Op(_) => {
+                self.next -= 1;
+                Err(self.error("unexpected operator"))
+            }
+        }
+    }
+
+    // The arguments of a function have been opened
+    fn call(&mut self, name: &str, position: usize) -> Result<Expr, Error> {
+        let mut args = vec![];
+        if !self.eat(&[")"]) {
+            loop {
+                args.push(self.or()?.into_num()?);
+
+                if self.eat(&[")"]) {
+                    break;
+                } else if !self.eat(&[","]) {
+                    return Err(self.error("expected a comma or a closing parenthesis"));
+                }
+            }
+        }
+
+        let (func, num_args) = match name {
+            "isnull" if args.len() == 1 => {
+                return Ok(Expr::Cond(Cond::IsNull(args.remove(0)), position));
+            }
+            "isnull" => (None, 1),
+            "abs" => (Some(Func::Abs), 1),
+            "sqrt" => (Some(Func::Sqrt), 1),
+            "log10" => (Some(Func::Log10), 1),
+            "ln" => (Some(Func::Ln), 1),
+            "exp" => (Some(Func::Exp), 1),
+            "pow" => (Some(Func::Pow), 2),
+            "min" => (Some(Func::Min), 2),
+            "max" => (Some(Func::Max), 2),
+            _ => {
+                return Err(Error::Syntax {
+                    reason: format!("unknown function {}", name),
+                    position,
+                })
+            }
+        };
+
+        match func {
+            Some(func) if args.len() == num_args => Ok(Expr::Num(Num::Call(func, args), position)),
+            _ => Err(Error::Syntax {
+                reason: format!("{} expects {} argument(s)", name, num_args),
+                position,
+            }),
+        }
+    }
+}
+
+fn left_position(expr: &Expr) -> usize {
+    match expr {
+        Expr::Num(_, position) | Expr::Cond(_, position) => *position,
+    }
+}
+
+/// A condition on the columns of a catalog, the sources for which it is false or null
+/// being hidden
+///
+/// The condition combines the numerical columns, given by their name or quoted with `"`,
+/// with arithmetic (`+ - * / %`), comparisons (`< <= > >= = != <>`), boolean logic
+/// (`and or not`) and the `abs`, `sqrt`, `log10`, `ln`, `exp`, `pow`, `min` and `max`
+/// functions. As in SQL, any operation on a null value gives null, `isnull(x)` telling
+/// whether a value is null, e.g. `isnull(pmra) or abs(pmra) > 10`.
+#[derive(Debug)]
+pub struct Filter {
+    cond: Cond,
+    // The names of the columns referred to
+    columns: Vec<String>,
+}
+
+impl Filter {
+    /// Compile a condition
+    pub fn new(expr: &str) -> Result<Self, Error> {
+        let mut parser = Parser {
+            tokens: tokenize(expr)?,
+            next: 0,
+            len: expr.len(),
+            columns: vec![],
+        };
+
+        let cond = parser.or()?;
+        if parser.next < parser.tokens.len() {
+            return Err(parser.error("unexpected token"));
+        }
+
+        Ok(Self {
+            cond: cond.into_cond()?,
+            columns: parser.columns,
+        })
+    }
+
+    /// The names of the columns referred to by the condition
+    pub fn get_columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Evaluate the condition over the rows of a table, giving the rows to show
+    pub fn get_mask(&self, columns: &Columns, num_rows: usize) -> Result<Box<[bool]>, Error> {
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| {
+                columns
+                    .get(column)
+                    .map(|values| &values[..])
+                    .ok_or_else(|| Error::UnknownColumn {
+                        column: column.clone(),
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok((0..num_rows)
+            .map(|row| self.cond.eval(&columns, row) == Some(true))
+            .collect())
+    }
+}
+
+/// The rows of a table shown by its filter, all the rows being shown without filter
+///
+/// The sources hidden stay indexed, being skipped when the catalog is drawn or queried
+#[derive(Clone, Debug, Default)]
+pub struct Mask(Option<Box<[bool]>>);
+
+impl Mask {
+    pub fn new(filter: Option<&Filter>, columns: &Columns, num_rows: usize) -> Self {
+        // The columns of the filter have been checked when setting it
+        Self(filter.map(|filter| filter.get_mask(columns, num_rows).unwrap_or_default()))
+    }
+
+    /// Whether the source of a row is shown
+    pub fn is_shown(&self, row: u32) -> bool {
+        match &self.0 {
+            Some(mask) => mask.get(row as usize).copied().unwrap_or(false),
+            None => true,
+        }
+    }
+
+    /// Whether all the sources are shown
+    pub fn is_empty(&self) -> bool {
+        self.0.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Filter, Mask};
+    use crate::renderable::catalog::marker::Columns;
+
+    #[test]
+    fn filter_rows() {
+        let columns: Columns = [
+            ("mag".to_string(), vec![10.0, 15.0, f32::NAN, 20.0].into()),
+            ("pm ra".to_string(), vec![-12.0, 3.0, 50.0, f32::NAN].into()),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+        let mask = |expr: &str| Filter::new(expr).and_then(|f| f.get_mask(&columns, 4));
+
+        assert_eq!(mask("mag < 16").unwrap()[..], [true, true, false, false]);
+        // Arithmetic goes before comparisons and `and` before `or`
+        let expr = "mag * 2 - 1 >= 29 and mag != 20 or abs(\"pm ra\") > 40";
+        assert_eq!(mask(expr).unwrap()[..], [false, true, true, false]);
+        assert_eq!(
+            mask("-mag + 2 * 8 > 2").unwrap()[..],
+            [true, false, false, false]
+        );
+        assert_eq!(
+            mask("log10(mag) = 1").unwrap()[..],
+            [true, false, false, false]
+        );
+
+        // The comparisons with null values are null, hiding their rows
+        assert_eq!(
+            mask("not mag > 12").unwrap()[..],
+            [true, false, false, false]
+        );
+        assert_eq!(
+            mask("isnull(mag) || mag >= 20").unwrap()[..],
+            [false, false, true, true]
+        );
+        // A false operand of `and`, or a true operand of `or` prevails over null
+        let expr = "max(mag, 0) > 0 OR \"pm ra\" > 0";
+        assert_eq!(mask(expr).unwrap()[..], [true, true, true, true]);
+        assert_eq!(
+            mask("min(mag, 12) < 11").unwrap()[..],
+            [true, false, false, false]
+        );
+
+        assert_eq!(
+            mask("mag + (pm > 1)").unwrap_err(),
+            Error::Syntax {
+                reason: "expected a number, found a condition".to_string(),
+                position: 6
+            }
+        );
+        assert_eq!(
+            mask("mag < 16 and").unwrap_err(),
+            Error::Syntax {
+                reason: "unexpected end of the filter".to_string(),
+                position: 12
+            }
+        );
+        assert!(mask("sqrt(mag, 2) > 1").is_err());
+        assert!(mask("mag").is_err());
+        assert_eq!(
+            mask("parallax > 0").unwrap_err(),
+            Error::UnknownColumn {
+                column: "parallax".to_string()
+            }
+        );
+
+        // The rows beyond the columns are hidden, all the rows being shown without filter
+        let filter = Filter::new("mag < 16").unwrap();
+        let mask = Mask::new(Some(&filter), &columns, 4);
+        assert!(mask.is_shown(1) && !mask.is_shown(2) && !mask.is_shown(10));
+        assert!(Mask::new(None, &columns, 4).is_shown(10));
+    }
+}
@@ -0,0 +1,579 @@
+//! Progressive catalogues (HiPS-Cat)
+//!
+//! The sources of a progressive catalogue are split into HEALPix tiles, the tiles of an
+//! order holding sources that are not in the tiles of the lower orders. The sources of the
+//! orders 1 and 2 are also gathered in the `Norder1/Allsky.tsv` and `Norder2/Allsky.tsv`
+//! files. The tiles are TSV files whose columns are described by the FIELDs of the
+//! `Metadata.xml` VOTable.
+//!
+//! The tiles displayed are the ones of the cells of the view, and of their ancestors,
+//! from the order 3 up to the order of the view. The files that could not be fetched
+//! because of a transient error are fetched again a few times.
+use std::collections::HashSet;
+
+use crate::downloader::request::Failure;
+use crate::healpix::cell::HEALPixCell;
+use crate::lru_cache::Cache;
+use crate::math::angle::Angle;
+use crate::math::lonlat::LonLatT;
+use crate::table::{self, parse_coordinate, xml::parse_attributes};
+use crate::tile_fetcher::retry::Retries;
+use crate::time::Time;
+
+// The orders whose sources are gathered in an Allsky file
+const ALLSKY_ORDERS: [u8; 2] = [1, 2];
+// The order of the view is lowered until it contains fewer cells than that
+const MAX_NUM_CELLS_IN_VIEW: usize = 12;
+// Size in bytes of the tiles kept, the least recently displayed being evicted
+const TILES_BUDGET: usize = 32 << 20;
+
+/// A file of a progressive catalogue
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum File {
+    Metadata,
+    Allsky(u8),
+    Tile(HEALPixCell),
+}
+
+impl File {
+    /// The path of the file relative to the root url of the catalogue
+    pub fn path(&self) -> String {
+        match self {
+            File::Metadata => "Metadata.xml".to_string(),
+            File::Allsky(order) => format!("Norder{}/Allsky.tsv", order),
+            File::Tile(HEALPixCell(depth, idx)) => format!(
+                "Norder{}/Dir{}/Npix{}.tsv",
+                depth,
+                (idx / 10000) * 10000,
+                idx
+            ),
+        }
+    }
+}
+
+/// A column of the catalogue described in `Metadata.xml`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Field {
+    pub name: String,
+    pub ucd: Option<String>,
+    pub unit: Option<String>,
+    pub datatype: Option<String>,
+    pub arraysize: Option<String>,
+}
+
+/// Read the FIELDs of a VOTable
+pub fn parse_metadata(xml: &str) -> Vec<Field> {
+    xml.match_indices("<FIELD")
+        .filter_map(|(start, _)| {
+            let tag = &xml[(start + "<FIELD".len())..];
+            // Skip the FIELDref elements
+            if !tag.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+                return None;
+            }
+            let end = tag.find('>').unwrap_or(tag.len());
+
+            Some(parse_attributes(&tag[..end]))
+        })
+        .enumerate()
+        .map(|(k, attributes)| {
+            let get = |key: &str| attributes.get(key).cloned();
+
+            Field {
+                name: get("name")
+                    .or_else(|| get("ID"))
+                    .unwrap_or_else(|| format!("col_{}", k)),
+                ucd: get("ucd"),
+                unit: get("unit"),
+                datatype: get("datatype"),
+                arraysize: get("arraysize"),
+            }
+        })
+        .collect()
+}
+
+/// The indices of the columns of the equatorial coordinates, found from their UCD
+pub fn position_columns(fields: &[Field]) -> Option<(usize, usize)> {
+    table::position_columns(fields.iter().map(|f| f.ucd.as_deref()))
+}
+
+/// The sources of a tile
+#[derive(Debug, Default)]
+pub struct Tile {
+    /// The positions of the sources, in radians
+    pub sources: Box<[LonLatT<f32>]>,
+    /// True when the tile contains all the sources of its cell, so that it has no children
+    pub complete: bool,
+}
+
+/// Read the sources of a TSV tile
+///
+/// # Arguments
+///
+/// * `tsv` - The content of the tile. The first line that is not a comment gives the
+///   names of the columns
+/// * `(ra, dec)` - The indices of the columns of the coordinates, in degrees or sexagesimal
+pub fn parse_tile(tsv: &str, (ra, dec): (usize, usize)) -> Tile {
+    let mut complete = false;
+    let mut sources = vec![];
+
+    let mut lines = tsv.lines().filter(|line| !line.trim().is_empty());
+    // The comments before the names of the columns
+    for line in lines.by_ref() {
+        if let Some(comment) = line.strip_prefix('#') {
+            // e.g. "# Completeness = 903 / 90811"
+            if let Some((_, ratio)) = comment.split_once("Completeness =") {
+                if let Some((num, total)) = ratio.split_once('/') {
+                    complete = num.trim() == total.trim();
+                }
+            }
+        } else {
+            break;
+        }
+    }
+
+    for line in lines.filter(|line| !line.starts_with('#')) {
+        let values = line.split('\t').collect::<Vec<_>>();
+        let position = values.get(ra).zip(values.get(dec)).and_then(|(ra, dec)| {
+            Some((parse_coordinate(ra, true)?, parse_coordinate(dec, false)?))
+        });
+
+        if let Some((ra, dec)) = position {
+            sources.push(LonLatT::new(
+                Angle(ra.to_radians() as f32),
+                Angle(dec.to_radians() as f32),
+            ));
+        }
+    }
+
+    Tile {
+        sources: sources.into_boxed_slice(),
+        complete,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    // Waiting for Metadata.xml
+    Metadata,
+    // Waiting for the Allsky files
+    Allsky,
+    Ready,
+    // The catalogue cannot be displayed
+    Failed,
+}
+
+/// The tiles loaded of a progressive catalogue
+pub struct HiPSCatalog {
+    root_url: String,
+    max_order: u8,
+    state: State,
+    // The columns of the coordinates
+    position: (usize, usize),
+
+    // The sources of the Allsky files, None until received
+    allsky: [Option<Box<[LonLatT<f32>]>>; 2],
+    // The first order whose sources are loaded tile by tile, lowered when an Allsky
+    // file is missing
+    min_tile_order: u8,
+    // The tiles received, empty when missing
+    tiles: Cache<HEALPixCell, Tile>,
+    // The files that could not be fetched
+    failures: Retries<File>,
+
+    // The order of the view and the tiles it contains, sorted by order
+    view_order: u8,
+    in_view: Vec<HEALPixCell>,
+}
+
+impl HiPSCatalog {
+    /// # Arguments
+    ///
+    /// * `root_url` - The url of the catalogue
+    /// * `max_order` - The deepest order of its tiles, given by `hips_order`
+    pub fn new(root_url: String, max_order: u8) -> Self {
+        Self {
+            root_url: root_url.trim_end_matches('/').to_string(),
+            max_order,
+            state: State::Metadata,
+            position: (0, 1),
+            allsky: [None, None],
+            min_tile_order: ALLSKY_ORDERS[1] + 1,
+            tiles: Cache::new(TILES_BUDGET),
+            failures: Retries::default(),
+            view_order: 0,
+            in_view: vec![],
+        }
+    }
+
+    pub fn get_root_url(&self) -> &str {
+        &self.root_url
+    }
+
+    /// Move the view
+    ///
+    /// # Arguments
+    ///
+    /// * `depth` - The order of the view
+    /// * `cells` - Gives the cells of the view at an order
+    ///
+    /// Returns true if the sources to display have changed
+    pub fn set_view<F>(&mut self, depth: u8, cells: F) -> bool
+    where
+        F: Fn(u8) -> Vec<HEALPixCell>,
+    {
+        // Limit the number of tiles to fetch by looking at lower orders
+        let mut order = depth.min(self.max_order);
+        let mut cells_in_view = cells(order);
+        while cells_in_view.len() > MAX_NUM_CELLS_IN_VIEW && order >= self.min_tile_order {
+            order -= 1;
+            cells_in_view = cells(order);
+        }
+
+        let mut in_view = HashSet::new();
+        if self.state == State::Ready {
+            let min_tile_order = self.min_tile_order;
+            for cell in cells_in_view.iter().filter(|c| c.depth() >= min_tile_order) {
+                for d in min_tile_order..=cell.depth() {
+                    let tile = cell.ancestor(cell.depth() - d);
+                    in_view.insert(tile);
+
+                    // A complete tile has no children
+                    if self.tiles.get(&tile).is_some_and(|tile| tile.complete) {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // The ordering of the cells does not separate a cell from its first descendant
+        let mut in_view = in_view.into_iter().collect::<Vec<_>>();
+        in_view.sort_unstable_by_key(|cell| (cell.depth(), cell.idx()));
+
+        let changed = in_view != self.in_view || order.min(1) != self.view_order.min(1);
+        self.view_order = order;
+        self.in_view = in_view;
+
+        changed
+    }
+
+    /// The files to fetch to display the view, the shallowest first
+    ///
+    /// The files that failed are only fetched again once their retry delay is elapsed
+    pub fn get_files_to_fetch(&self, now: Time) -> Vec<File> {
+        let files = match self.state {
+            State::Metadata => vec![File::Metadata],
+            State::Allsky => ALLSKY_ORDERS
+                .iter()
+                .zip(&self.allsky)
+                .filter(|(order, sources)| sources.is_none() && **order < self.min_tile_order)
+                .map(|(order, _)| File::Allsky(*order))
+                .collect(),
+            State::Ready => self
+                .in_view
+                .iter()
+                .filter(|cell| !self.tiles.contains(cell))
+                .map(|cell| File::Tile(*cell))
+                .collect(),
+            State::Failed => vec![],
+        };
+
+        files
+            .into_iter()
+            .filter(|file| self.failures.can_fetch(&file.path(), now))
+            .collect()
+    }
+
+    /// Add a file received
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - The file received
+    /// * `content` - Its content, or the reason why it could not be fetched
+    /// * `now` - The time of the reception
+    ///
+    /// Returns the sources of the file to add to the ones displayed, or an error if the
+    /// catalogue cannot be displayed
+    pub fn add_file(
+        &mut self,
+        file: File,
+        content: Result<&str, Failure>,
+        now: Time,
+    ) -> Result<Vec<LonLatT<f32>>, &'static str> {
+        let content = match content {
+            Ok(content) => {
+                self.failures.succeeded(&file.path());
+                Some(content)
+            }
+            // The file is fetched again if it is still needed
+            Err(Failure::Aborted) => return Ok(vec![]),
+            Err(failure) => {
+                let transient = failure == Failure::Transient;
+                if self.failures.failed(&file.path(), file, transient, now) {
+                    return Ok(vec![]);
+                }
+
+                // The file is considered missing
+                None
+            }
+        };
+
+        match file {
+            File::Metadata => {
+                let position = content
+                    .map(parse_metadata)
+                    .and_then(|fields| position_columns(&fields));
+
+                if let Some(position) = position {
+                    self.position = position;
+                    self.state = State::Allsky;
+                } else {
+                    self.state = State::Failed;
+                    return Err("no equatorial coordinates found in Metadata.xml");
+                }
+
+                Ok(vec![])
+            }
+            File::Allsky(order) => {
+                let mut sources = vec![];
+                if let Some(i) = ALLSKY_ORDERS.iter().position(|o| *o == order) {
+                    if let Some(tsv) = content {
+                        let allsky = parse_tile(tsv, self.position).sources;
+                        // The sources of the order 2 are displayed from the order 1 of the view
+                        if self.state == State::Ready && order <= self.view_order + 1 {
+                            sources = allsky.to_vec();
+                        }
+                        self.allsky[i] = Some(allsky);
+                    } else {
+                        // The sources of the order are fetched tile by tile
+                        self.min_tile_order = self.min_tile_order.min(order);
+                    }
+                }
+
+                let allsky_received = ALLSKY_ORDERS
+                    .iter()
+                    .zip(&self.allsky)
+                    .all(|(order, sources)| sources.is_some() || *order >= self.min_tile_order);
+                if self.state == State::Allsky && allsky_received {
+                    self.state = State::Ready;
+                }
+
+                Ok(sources)
+            }
+            File::Tile(cell) => {
+                let tile = content
+                    .map(|tsv| parse_tile(tsv, self.position))
+                    .unwrap_or_default();
+
+                // The view may have moved since the tile was requested
+                let sources = if self.in_view.contains(&cell) {
+                    tile.sources.to_vec()
+                } else {
+                    vec![]
+                };
+                let size = std::mem::size_of::<Tile>()
+                    + std::mem::size_of_val::<[LonLatT<f32>]>(&tile.sources);
+                self.tiles.insert(cell, tile, size);
+
+                Ok(sources)
+            }
+        }
+    }
+
+    /// The sources to display, taken from the Allsky files and the tiles of the view
+    pub fn get_sources(&mut self) -> Vec<LonLatT<f32>> {
+        let view_order = self.view_order;
+        let mut sources = self
+            .allsky
+            .iter()
+            .zip(&ALLSKY_ORDERS)
+            // The sources of the order 2 are displayed from the order 1 of the view
+            .filter(|(_, order)| **order <= view_order + 1)
+            .filter_map(|(sources, _)| sources.as_deref())
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+
+        // Accessing the tiles of the view prevents them from being evicted
+        for cell in &self.in_view {
+            if let Some(tile) = self.tiles.get(cell) {
+                sources.extend_from_slice(&tile.sources);
+            }
+        }
+
+        sources
+    }
+
+    /// The number of tiles received
+    pub fn num_tiles(&self) -> usize {
+        self.tiles.get_stats().num_entries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_metadata, parse_tile, position_columns, File, HiPSCatalog};
+    use crate::downloader::request::Failure;
+    use crate::healpix::cell::HEALPixCell;
+    use crate::time::Time;
+
+    const METADATA: &str = r#"<?xml version="1.0"?>
+<VOTABLE version="1.2">
+<RESOURCE><TABLE name="Gaia">
+  <FIELD name="source_id" datatype="long" ucd="meta.id"/>
+  <FIELD name="ra_err" datatype="double" ucd="stat.error;pos.eq.ra" unit="mas"/>
+  <FIELD name="ra" datatype="double" ucd="pos.eq.ra;meta.main" unit="deg"/>
+  <FIELD ID="dec" datatype="double" ucd='pos.eq.dec;meta.main' unit="deg">
+    <DESCRIPTION>Declination &amp; more</DESCRIPTION>
+  </FIELD>
+  <FIELDref ref="ra"/>
+  <FIELD datatype="char" arraysize="*"/>
+</TABLE></RESOURCE>
+</VOTABLE>"#;
+
+    const TILE: &str = "# Completeness = 3 / 3\n\
+        source_id\tra_err\tra\tdec\tname\n\
+        1\t0.1\t10.5\t-20.25\ta\n\
+        2\t0.1\t00:42:44.3\t+41 16 09\tM31\n\
+        3\t0.1\t\t\tno position\n";
+
+    #[test]
+    fn metadata() {
+        let fields = parse_metadata(METADATA);
+        assert_eq!(fields.len(), 5);
+        assert_eq!(fields[2].name, "ra");
+        assert_eq!(fields[2].unit.as_deref(), Some("deg"));
+        assert_eq!(fields[3].name, "dec");
+        assert_eq!(fields[4].name, "col_4");
+        assert_eq!(fields[4].arraysize.as_deref(), Some("*"));
+
+        assert_eq!(position_columns(&fields), Some((2, 3)));
+        assert_eq!(position_columns(&fields[..3]), None);
+    }
+
+    #[test]
+    fn tile() {
+        let tile = parse_tile(TILE, (2, 3));
+        assert!(tile.complete);
+        assert_eq!(tile.sources.len(), 2);
+
+        let degrees = |i: usize| {
+            let lonlat = tile.sources[i];
+            (
+                (lonlat.lon().0 as f64).to_degrees(),
+                (lonlat.lat().0 as f64).to_degrees(),
+            )
+        };
+        let (ra, dec) = degrees(0);
+        assert!((ra - 10.5).abs() < 1e-4 && (dec + 20.25).abs() < 1e-4);
+        let (ra, dec) = degrees(1);
+        assert!((ra - 10.684583).abs() < 1e-4 && (dec - 41.269167).abs() < 1e-4);
+
+        let tile = parse_tile("# Completeness = 2 / 100\nra\tdec\n1\t2\n", (0, 1));
+        assert!(!tile.complete);
+        assert_eq!(tile.sources.len(), 1);
+    }
+
+    #[test]
+    fn progressive_loading() {
+        let now = Time(0.0);
+        let mut catalog = HiPSCatalog::new("http://cat/".to_string(), 6);
+        assert_eq!(catalog.get_files_to_fetch(now), vec![File::Metadata]);
+        catalog.add_file(File::Metadata, Ok(METADATA), now).unwrap();
+
+        assert_eq!(
+            catalog.get_files_to_fetch(now),
+            vec![File::Allsky(1), File::Allsky(2)]
+        );
+        catalog.add_file(File::Allsky(1), Ok(TILE), now).unwrap();
+        // Missing, the sources of the order 2 are loaded tile by tile
+        catalog
+            .add_file(File::Allsky(2), Err(Failure::Permanent), now)
+            .unwrap();
+        assert!(catalog.get_files_to_fetch(now).is_empty());
+
+        // 4 cells of order 5 in the view
+        let cell = HEALPixCell(5, 4 * 1000);
+        let cells = |order: u8| {
+            (0..4)
+                .map(|i| HEALPixCell(5, cell.idx() + i).ancestor(5 - order))
+                .collect::<Vec<_>>()
+        };
+        assert!(catalog.set_view(5, cells));
+
+        let files = catalog.get_files_to_fetch(now);
+        // The tiles of the orders 2 to 5, the tiles of order 2 to 4 being shared
+        assert_eq!(files.len(), 3 + 4);
+        assert_eq!(files[0], File::Tile(cell.ancestor(3)));
+        assert_eq!(files[0].path(), "Norder2/Dir0/Npix62.tsv");
+
+        // The sources of a tile of the view are returned to be displayed
+        let sources = catalog
+            .add_file(File::Tile(cell.ancestor(2)), Ok(TILE), now)
+            .unwrap();
+        assert_eq!(sources.len(), 2);
+        // A complete tile of order 3 hides its descendants
+        catalog
+            .add_file(File::Tile(cell.ancestor(3)), Err(Failure::Permanent), now)
+            .unwrap();
+        assert!(catalog.set_view(5, cells));
+        assert_eq!(catalog.get_files_to_fetch(now), vec![]);
+        assert_eq!(catalog.get_sources().len(), 2 + 2);
+
+        // Zooming out, the order 1 of the view only shows the Allsky sources of the order 1
+        assert!(catalog.set_view(0, |order| vec![cell.ancestor(5 - order)]));
+        assert_eq!(catalog.get_sources().len(), 2);
+        assert!(!catalog.set_view(0, |order| vec![cell.ancestor(5 - order)]));
+        assert_eq!(catalog.num_tiles(), 2);
+    }
+
+    #[test]
+    fn transient_failures_are_retried() {
+        let mut catalog = HiPSCatalog::new("http://cat/".to_string(), 6);
+
+        // The metadata are fetched again after a delay instead of failing the catalogue
+        catalog
+            .add_file(File::Metadata, Err(Failure::Transient), Time(0.0))
+            .unwrap();
+        assert!(catalog.get_files_to_fetch(Time(0.0)).is_empty());
+        assert_eq!(catalog.get_files_to_fetch(Time(1e4)), vec![File::Metadata]);
+        // An aborted request is only fetched again when still needed
+        catalog
+            .add_file(File::Metadata, Err(Failure::Aborted), Time(1e4))
+            .unwrap();
+        assert_eq!(catalog.get_files_to_fetch(Time(1e4)), vec![File::Metadata]);
+        catalog
+            .add_file(File::Metadata, Ok(METADATA), Time(1e4))
+            .unwrap();
+
+        catalog
+            .add_file(File::Allsky(1), Ok(TILE), Time(1e4))
+            .unwrap();
+        catalog
+            .add_file(File::Allsky(2), Ok(TILE), Time(1e4))
+            .unwrap();
+        let cell = HEALPixCell(3, 100);
+        assert!(catalog.set_view(3, |order| vec![cell.ancestor(3 - order)]));
+        assert_eq!(
+            catalog.get_files_to_fetch(Time(1e4)),
+            vec![File::Tile(cell)]
+        );
+
+        // A tile failing transiently is not stored as empty
+        let sources = catalog
+            .add_file(File::Tile(cell), Err(Failure::Transient), Time(1e4))
+            .unwrap();
+        assert!(sources.is_empty());
+        assert_eq!(catalog.num_tiles(), 0);
+        assert!(catalog.get_files_to_fetch(Time(1e4)).is_empty());
+        assert_eq!(
+            catalog.get_files_to_fetch(Time(2e4)),
+            vec![File::Tile(cell)]
+        );
+
+        let sources = catalog
+            .add_file(File::Tile(cell), Ok(TILE), Time(2e4))
+            .unwrap();
+        assert_eq!(sources.len(), 2);
+        assert_eq!(catalog.num_tiles(), 1);
+    }
+}
@@ -1,101 +1,85 @@
 use crate::ShaderManager;
 
 use al_api::coo_system::CooSystem;
-use al_api::resources::Resources;
 
-use al_core::colormap::Colormap;
+use al_core::image::format::R32F;
 use al_core::Colormaps;
 use al_core::FrameBufferObject;
-use al_core::{VecData, VertexArrayObject, WebGlContext};
+use al_core::{SliceData, VertexArrayObject, WebGlContext};
 
+use super::hips_cat::HiPSCatalog;
+use crate::downloader::{query, request::catalog::CatalogFile};
+use crate::math::angle::Angle;
+use crate::renderable::text::TextRenderManager;
+use crate::renderable::Renderer;
+use crate::time::Time;
+use crate::Abort;
 use crate::ProjectionType;
+use al_api::color::ColorRGBA;
+use cgmath::Rad;
+use std::borrow::Cow;
 use std::collections::HashMap;
 
-use web_sys::WebGl2RenderingContext;
+use web_sys::{HtmlElement, WebGl2RenderingContext};
 
 #[derive(Debug)]
 pub enum Error {
     CatalogNotPresent { message: String },
+    ColumnNotPresent { message: String },
+    FilterNotValid { message: String },
 }
 use wasm_bindgen::JsValue;
 impl From<Error> for JsValue {
     fn from(err: Error) -> Self {
         match err {
-            Error::CatalogNotPresent { message } => message.into(),
+            Error::CatalogNotPresent { message }
+            | Error::ColumnNotPresent { message }
+            | Error::FilterNotValid { message } => message.into(),
         }
     }
 }
 
-// Num of shapes
-const _NUM_SHAPES: usize = 5;
+// The float textures cannot be linearly interpolated
+const DENSITY_TEX_PARAMS: &[(u32, u32)] = &[
+    (
+        WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+        WebGl2RenderingContext::NEAREST,
+    ),
+    (
+        WebGl2RenderingContext::TEXTURE_MAG_FILTER,
+        WebGl2RenderingContext::NEAREST,
+    ),
+    // Prevents s-coordinate wrapping (repeating)
+    (
+        WebGl2RenderingContext::TEXTURE_WRAP_S,
+        WebGl2RenderingContext::CLAMP_TO_EDGE,
+    ),
+    // Prevents t-coordinate wrapping (repeating)
+    (
+        WebGl2RenderingContext::TEXTURE_WRAP_T,
+        WebGl2RenderingContext::CLAMP_TO_EDGE,
+    ),
+];
+
 pub struct Manager {
     gl: WebGlContext,
-    //kernels: HashMap<&'static str, Texture2D>,
-    fbo: FrameBufferObject,
+    // The float framebuffer where the kernels of the heatmaps are summed,
+    // created at the size of the screen
+    fbo: Option<FrameBufferObject>,
 
     // VAOs
     vertex_array_object_screen: VertexArrayObject,
 
     catalogs: HashMap<String, Catalog>,
-    kernel_size: Vector2<f32>,
+    // The progressive catalogues, whose sources are drawn by the catalog of the same name
+    hips_catalogs: HashMap<String, HiPSCatalog>,
+
+    // The counts of the aggregated markers
+    text_renderer: TextRenderManager,
 }
 
 impl Manager {
-    pub fn new(
-        gl: &WebGlContext,
-        _shaders: &mut ShaderManager,
-        camera: &CameraViewPort,
-        resources: &Resources,
-    ) -> Result<Self, JsValue> {
-        // Load the texture of the gaussian kernel
-        let _kernel_filename = resources.get_filename("kernel").unwrap_abort();
-        let _params = &[
-            (
-                WebGl2RenderingContext::TEXTURE_MIN_FILTER,
-                WebGl2RenderingContext::LINEAR,
-            ),
-            (
-                WebGl2RenderingContext::TEXTURE_MAG_FILTER,
-                WebGl2RenderingContext::LINEAR,
-            ),
-            // Prevents s-coordinate wrapping (repeating)
-            (
-                WebGl2RenderingContext::TEXTURE_WRAP_S,
-                WebGl2RenderingContext::CLAMP_TO_EDGE,
-            ),
-            // Prevents t-coordinate wrapping (repeating)
-            (
-                WebGl2RenderingContext::TEXTURE_WRAP_T,
-                WebGl2RenderingContext::CLAMP_TO_EDGE,
-            ),
-        ];
-        /*let kernels = [
-            (
-                "gaussian",
-                Texture2D::create_from_path::<_, RGBA8U>(gl, "kernel", &kernel_filename, params)?,
-            ),
-            (
-                "plus",
-                Texture2D::create_from_raw_pixels::<R8UI>(
-                    gl,
-                    3,
-                    3,
-                    params,
-                    Some(&[0, 0xff, 0, 0xff, 0xff, 0xff, 0, 0xff, 0]),
-                )?,
-            ),
-            (
-                "square",
-                Texture2D::create_from_raw_pixels::<R8UI>(
-                    gl,
-                    3,
-                    3,
-                    params,
-                    Some(&[0xff, 0xff, 0xff, 0xff, 0, 0xff, 0xff, 0xff, 0xff]),
-                )?,
-            ),
-        ]
-        .into();*/
+    pub fn new(gl: &WebGlContext, aladin_div: &HtmlElement) -> Result<Self, JsValue> {
         // Create the VAO for the screen
         let vertex_array_object_screen = {
             let vertices = [
@@ -157,39 +141,39 @@ impl Manager {
         };
 
         let catalogs = HashMap::new();
-        let kernel_size = Vector2::new(0.0, 0.0);
+        let hips_catalogs = HashMap::new();
 
-        let fbo = FrameBufferObject::new(gl, 768, 768).unwrap_abort();
+        let mut text_renderer = TextRenderManager::new(aladin_div)?;
+        text_renderer.set_font_size(12);
 
         let gl = gl.clone();
-        let mut manager = Manager {
+        Ok(Manager {
             gl,
-            //kernels,
-            fbo,
+            fbo: None,
 
             vertex_array_object_screen,
 
             catalogs,
-            kernel_size,
-        };
-
-        manager.set_kernel_size(camera);
+            hips_catalogs,
 
-        Ok(manager)
+            text_renderer,
+        })
     }
 
     // Private method adding a catalog into the manager
-    pub fn add_catalog<P: Projection>(
+    pub fn add_catalog(
         &mut self,
         name: String,
-        sources: Box<[LonLatT<f32>]>,
-        colormap: Colormap,
+        sources: Box<[Source]>,
+        positions: Box<[LonLatT<f64>]>,
+        columns: Columns,
+        colormap: String,
         camera: &mut CameraViewPort,
         proj: &ProjectionType,
     ) {
         // Create the HashMap storing the source indices with respect to the
         // HEALPix cell at depth 7 in which they are contained
-        let catalog = Catalog::new::<P>(&self.gl, colormap, sources);
+        let catalog = Catalog::new(&self.gl, colormap, sources, positions, columns);
 
         // Update the number of sources loaded
         //self.num_sources += num_instances_in_catalog as usize;
@@ -204,7 +188,7 @@ impl Manager {
         // at depth 7
     }
 
-    pub fn remove_catalog<P: Projection>(
+    pub fn remove_catalog(
         &mut self,
         name: String,
         camera: &mut CameraViewPort,
@@ -212,13 +196,98 @@ impl Manager {
     ) {
         // Update the number of sources loaded
         //self.num_sources += num_instances_in_catalog as usize;
-        self.catalogs.remove(&name);
-        camera.unregister_view_frame(CooSystem::ICRS, proj);
+        if self.catalogs.remove(&name).is_some() {
+            self.hips_catalogs.remove(&name);
+            camera.unregister_view_frame(CooSystem::ICRS, proj);
+        }
     }
 
-    pub fn set_kernel_size(&mut self, camera: &CameraViewPort) {
-        let size = camera.get_screen_size();
-        self.kernel_size = Vector2::new(32.0 / size.x, 32.0 / size.y);
+    /// Add a progressive catalogue, its sources being added as its tiles are received
+    pub fn add_hips_catalog(
+        &mut self,
+        name: String,
+        hips_catalog: HiPSCatalog,
+        colormap: String,
+        camera: &mut CameraViewPort,
+        proj: &ProjectionType,
+    ) {
+        // Replacing a catalogue keeps the frame registered once
+        self.remove_catalog(name.clone(), camera, proj);
+
+        self.add_catalog(
+            name.clone(),
+            Box::new([]),
+            Box::new([]),
+            Columns::new(),
+            colormap,
+            camera,
+            proj,
+        );
+        self.hips_catalogs.insert(name, hips_catalog);
+    }
+
+    /// The files of the progressive catalogues needed to display the view
+    pub fn get_hips_catalog_queries(&mut self, camera: &CameraViewPort) -> Vec<query::CatalogFile> {
+        let mut queries = vec![];
+
+        for (name, hips_catalog) in self.hips_catalogs.iter_mut() {
+            let depth = camera.get_texture_depth();
+            let changed =
+                hips_catalog.set_view(depth, |d| camera.get_hpx_cells(d, CooSystem::ICRS));
+
+            if changed {
+                if let Some(catalog) = self.catalogs.get_mut(name) {
+                    catalog.set_sources(hips_sources(hips_catalog.get_sources()));
+                }
+            }
+
+            let root_url = hips_catalog.get_root_url();
+            queries.extend(
+                hips_catalog
+                    .get_files_to_fetch(Time::now())
+                    .into_iter()
+                    .map(|file| query::CatalogFile::new(name.clone(), root_url, file)),
+            );
+        }
+
+        queries
+    }
+
+    /// Add a file of a progressive catalogue received
+    ///
+    /// Returns true if the sources of the catalogue have changed
+    pub fn add_hips_catalog_file(&mut self, file: CatalogFile) -> Result<bool, JsValue> {
+        let CatalogFile {
+            name,
+            file,
+            content,
+        } = file;
+
+        let hips_catalog = if let Some(hips_catalog) = self.hips_catalogs.get_mut(&name) {
+            hips_catalog
+        } else {
+            // The catalogue has been removed in the meantime
+            return Ok(false);
+        };
+
+        let sources = hips_catalog
+            .add_file(file, content.as_deref().map_err(|f| *f), Time::now())
+            .map_err(|e| JsValue::from_str(&format!("{}: {}", name, e)))?;
+
+        if sources.is_empty() {
+            return Ok(false);
+        }
+
+        // The sources already displayed are not indexed again
+        self.get_mut_catalog(&name)?.add_sources(sources);
+
+        Ok(true)
+    }
+
+    pub fn get_catalog(&self, name: &str) -> Result<&Catalog, Error> {
+        self.catalogs.get(name).ok_or(Error::CatalogNotPresent {
+            message: format!("{} catalog is not present!", name),
+        })
     }
 
     pub fn get_mut_catalog(&mut self, name: &str) -> Result<&mut Catalog, Error> {
@@ -227,12 +296,104 @@ impl Manager {
         })
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.catalogs.is_empty()
+    }
+
+    /// Prepare the cross-match of the sources of two catalogs by their position
+    ///
+    /// The sources are copied so that the catalogs can change while they are matched
+    pub fn cross_match(
+        &self,
+        name: &str,
+        other_name: &str,
+        cfg: &XMatch,
+    ) -> Result<CrossMatch, Error> {
+        let table = self
+            .get_catalog(name)?
+            .get_xmatch_table(cfg.error_column.as_ref())?;
+        let other = self
+            .get_catalog(other_name)?
+            .get_xmatch_table(cfg.other_error_column.as_ref())?;
+
+        Ok(CrossMatch::new(table, other, cfg))
+    }
+
+    /// Add a catalog whose sources are the pairs of a cross-match, drawn at the position of
+    /// the sources of the first catalog
+    ///
+    /// It keeps the columns of the first catalog, the columns of the second one prefixed by
+    /// its name and the `separation` of the pairs
+    pub fn add_matches_catalog(
+        &mut self,
+        name: String,
+        (catalog_name, other_name): (&str, &str),
+        matches: &Matches,
+        camera: &mut CameraViewPort,
+        proj: &ProjectionType,
+    ) -> Result<(), Error> {
+        let catalog = self.get_catalog(catalog_name)?;
+        let other = self.get_catalog(other_name)?;
+
+        // The positions of the sources of the first catalog indexed by their row
+        let mut lonlats = vec![None; catalog.sources.len()];
+        for source in catalog.sources.iter() {
+            if let Some(lonlat) = lonlats.get_mut(source.row as usize) {
+                *lonlat = Some(source.lonlat);
+            }
+        }
+        let sources = matches
+            .rows
+            .iter()
+            .enumerate()
+            .filter_map(|(row, &matched)| {
+                let lonlat = lonlats.get(matched as usize).copied().flatten()?;
+                Some(Source::new(lonlat, row as u32))
+            })
+            .collect::<Box<[_]>>();
+        // The positions of the first catalog are given for all its rows or for none of them
+        let positions = matches
+            .rows
+            .iter()
+            .filter_map(|&matched| catalog.positions.get(matched as usize).copied())
+            .collect::<Box<[_]>>();
+
+        let get_column = |values: &[f32], rows: &[u32]| {
+            rows.iter()
+                .map(|&row| values.get(row as usize).copied().unwrap_or(f32::NAN))
+                .collect::<Box<[_]>>()
+        };
+        let mut columns = catalog
+            .columns
+            .iter()
+            .map(|(column, values)| (column.clone(), get_column(values, &matches.rows)))
+            .collect::<Columns>();
+        columns.extend(other.columns.iter().map(|(column, values)| {
+            let column = format!("{}.{}", other_name, column);
+            (column, get_column(values, &matches.other_rows))
+        }));
+        if !matches.separations.is_empty() {
+            let separations = matches.separations.iter().map(|&s| s as f32).collect();
+            columns.insert("separation".to_string(), separations);
+        }
+
+        let colormap = catalog.colormap.clone();
+        self.remove_catalog(name.clone(), camera, proj);
+        self.add_catalog(name, sources, positions, columns, colormap, camera, proj);
+
+        Ok(())
+    }
+
     pub fn update(&mut self, camera: &mut CameraViewPort) {
         // Render only the sources in the current field of view
         // Cells that are of depth > 7 are not handled by the hashmap (limited to depth 7)
         // For these cells, we draw all the sources lying in the ancestor cell of depth 7 containing
         // this cell
         //if camera.get_aperture() > P::RASTER_THRESHOLD_ANGLE {
+        if self.catalogs.is_empty() {
+            return;
+        }
+
         if camera.get_field_of_view().is_allsky() {
             let cells = crate::healpix::cell::ALLSKY_HPX_CELLS_D0;
 
@@ -247,10 +408,61 @@ impl Manager {
                 catalog.update(&cells);
             }
         }
+
+        for catalog in self.catalogs.values_mut() {
+            catalog.update_aggregates(camera);
+        }
+    }
+
+    /// Whether some catalogs have their markers aggregated, their counts being drawn
+    /// by `draw_labels`
+    pub fn has_labels(&self) -> bool {
+        self.catalogs
+            .values()
+            .any(|catalog| catalog.aggregates.is_some() && catalog.alpha > 0.0)
+    }
+
+    /// Draw the numbers of sources of the aggregated markers
+    pub fn draw_labels(
+        &mut self,
+        camera: &CameraViewPort,
+        projection: &ProjectionType,
+    ) -> Result<(), JsValue> {
+        for catalog in self.catalogs.values() {
+            let aggregates = match &catalog.aggregates {
+                Some(aggregates) if catalog.alpha > 0.0 => aggregates,
+                _ => continue,
+            };
+
+            self.text_renderer.set_color(&ColorRGBA {
+                r: 1.0,
+                g: 1.0,
+                b: 1.0,
+                a: catalog.alpha,
+            });
+            self.text_renderer.begin();
+            for aggregate in aggregates {
+                let LonLatT(lon, lat) = aggregate.lonlat;
+                let lonlat = LonLatT::new(Angle(lon.0 as f64), Angle(lat.0 as f64));
+
+                if let Some(pos) =
+                    projection.icrs_celestial_to_screen_space(&lonlat.vector(), camera)
+                {
+                    self.text_renderer.add_label(
+                        &aggregate::format_count(aggregate.count),
+                        &pos.cast::<f32>().unwrap_abort(),
+                        Rad(0.0),
+                    )?;
+                }
+            }
+            self.text_renderer.end();
+        }
+
+        Ok(())
     }
 
     pub fn draw(
-        &self,
+        &mut self,
         gl: &WebGlContext,
         shaders: &mut ShaderManager,
         camera: &CameraViewPort,
@@ -258,6 +470,25 @@ impl Manager {
         fbo: Option<&FrameBufferObject>,
         projection: &ProjectionType,
     ) -> Result<(), JsValue> {
+        if self.catalogs.is_empty() {
+            return Ok(());
+        }
+
+        let size = camera.get_screen_size();
+        let (width, height) = (size.x as usize, size.y as usize);
+        let fbo_size = self
+            .fbo
+            .as_ref()
+            .map(|fbo| (fbo.texture.width() as usize, fbo.texture.height() as usize));
+        if fbo_size != Some((width, height)) {
+            self.fbo = Some(FrameBufferObject::new_with_format::<R32F>(
+                gl,
+                width,
+                height,
+                DENSITY_TEX_PARAMS,
+            )?);
+        }
+
         gl.enable(WebGl2RenderingContext::BLEND);
         for catalog in self.catalogs.values() {
             catalog.draw(gl, shaders, self, camera, colormaps, fbo, projection)?;
@@ -268,198 +499,429 @@ impl Manager {
     }
 }
 
-use crate::healpix::index_vector::IdxVec;
+// The sources of a progressive catalogue, which have no columns
+fn hips_sources(lonlat: Vec<LonLatT<f32>>) -> Box<[Source]> {
+    lonlat
+        .into_iter()
+        .enumerate()
+        .map(|(row, lonlat)| Source::new(lonlat, row as u32))
+        .collect()
+}
+
+use super::aggregate::{self, Aggregate};
+use super::density::{self, Density, Weights};
+use super::filter::{Filter, Mask};
+use super::marker::{self, Columns, Markers};
+use super::selection;
+use super::xmatch::{self, CrossMatch, Matches};
+use super::Source;
+use crate::healpix::index_vector::{cell_d7, IdxVec};
+use crate::table::Table;
 use crate::LonLatT;
+use al_api::catalog::{Catalog as CatalogCfg, RenderMode, SizeUnit, XMatch};
 
 pub struct Catalog {
-    colormap: Colormap,
-    num_instances: i32,
+    // The label of the colormap
+    colormap: String,
     index_vec: IdxVec,
     alpha: f32,
     strength: f32,
-    lonlat: Box<[LonLatT<f32>]>,
-    vertex_array_object_catalog: VertexArrayObject,
+    // The sources, indexed by their HEALPix cell
+    sources: Box<[Source]>,
+    // The rows of the sources shown by the filter
+    mask: Mask,
+    // The positions of the sources in double precision indexed by their row, to cross-match
+    // them. Empty for a progressive catalogue
+    positions: Box<[LonLatT<f64>]>,
+    // The table read in Rust, e.g. from a FITS file, kept to write the catalog back
+    table: Option<Table>,
+    filter: Option<Filter>,
+    // The numerical columns of the table, indexed by the rows of the sources
+    columns: Columns,
+    cfg: CatalogCfg,
+    markers: Markers,
+    density: Density,
+    weights: Weights,
+    // The sum of the weights of the heaviest HEALPix cell of depth 7 of the view
+    max_cell_weight: f64,
+    // The sorted rows of the selected sources
+    selection: Vec<u32>,
+    // The markers drawn over the selected sources
+    highlights: Markers,
+    // The sources grouped by the cells of the view, None if they are drawn individually
+    aggregates: Option<Vec<Aggregate>>,
+    aggregate_markers: Markers,
+    // The heatmap has been reported to be too dense for a 16 bits float framebuffer
+    half_float_reported: Cell<bool>,
 }
+use crate::camera::CameraViewPort;
 use crate::healpix::cell::HEALPixCell;
-use crate::{camera::CameraViewPort, math::projection::Projection, utils};
-use al_core::SliceData;
-use cgmath::Vector2;
+use std::cell::Cell;
 
-const MAX_SOURCES_PER_CATALOG: f32 = 50000.0;
+// Above this density, the kernel of the heaviest source added to a 16 bits float is lost
+const MAX_HALF_FLOAT_DENSITY: f32 = 2048.0;
+// The solid angle of a HEALPix cell of depth 7 in steradians
+const CELL_AREA_D7: f64 = 4.0 * std::f64::consts::PI / (12 * 4_u64.pow(7)) as f64;
 
-use crate::Abort;
 impl Catalog {
-    fn new<P: Projection>(
+    fn new(
         gl: &WebGlContext,
-        colormap: Colormap,
-        mut lonlat: Box<[LonLatT<f32>]>,
+        colormap: String,
+        mut sources: Box<[Source]>,
+        positions: Box<[LonLatT<f64>]>,
+        columns: Columns,
     ) -> Catalog {
         let alpha = 1_f32;
         let strength = 1_f32;
-        let index_vec = IdxVec::from_coo(&mut lonlat);
-        let num_instances = lonlat.len() as i32;
+        let index_vec = IdxVec::from_coo(&mut sources);
+        let cfg = CatalogCfg::default();
+        let markers = Markers::new(gl);
+        let highlights = Markers::new(gl);
+        let aggregate_markers = Markers::new(gl);
+        let density = Density::new(gl);
+        // All the sources are shown until a filter is set
+        let mask = Mask::default();
+        let weights = Weights::new(&sources, &mask, &columns, &cfg.heatmap);
 
-        //let sources = unsafe { utils::transmute_boxed_slice(sources) };
-
-        let vertex_array_object_catalog = {
-            #[cfg(feature = "webgl2")]
-            let vertices = [
-                -0.5_f32, -0.5_f32, 0.0_f32, 0.0_f32, 0.5_f32, -0.5_f32, 1.0_f32, 0.0_f32, 0.5_f32,
-                0.5_f32, 1.0_f32, 1.0_f32, -0.5_f32, 0.5_f32, 0.0_f32, 1.0_f32,
-            ];
-            #[cfg(feature = "webgl1")]
-            let offset = [
-                -0.5_f32, -0.5_f32, 0.5_f32, -0.5_f32, 0.5_f32, 0.5_f32, -0.5_f32, 0.5_f32,
-            ];
-            #[cfg(feature = "webgl1")]
-            let uv = [
-                0.0_f32, 0.0_f32, 1.0_f32, 0.0_f32, 1.0_f32, 1.0_f32, 0.0_f32, 1.0_f32,
-            ];
-
-            let indices = [0_u16, 1, 2, 0, 2, 3];
-
-            let mut vao = VertexArrayObject::new(gl);
-
-            //let shader = Orthographic::get_catalog_shader(gl, shaders);
-            #[cfg(feature = "webgl2")]
-            vao.bind_for_update()
-                // Store the UV and the offsets of the billboard in a VBO
-                .add_array_buffer(
-                    "vertices",
-                    4 * std::mem::size_of::<f32>(),
-                    &[2, 2],
-                    &[0, 2 * std::mem::size_of::<f32>()],
-                    WebGl2RenderingContext::STATIC_DRAW,
-                    SliceData(vertices.as_ref()),
-                )
-                // Store the cartesian position of the center of the source in the a instanced VBO
-                .add_instanced_array_buffer(
-                    "center",
-                    3 * std::mem::size_of::<f32>(),
-                    &[3],
-                    &[0],
-                    WebGl2RenderingContext::DYNAMIC_DRAW,
-                    SliceData(&[]),
-                )
-                // Set the element buffer
-                .add_element_buffer(
-                    WebGl2RenderingContext::STATIC_DRAW,
-                    SliceData(indices.as_ref()),
-                )
-                // Unbind the buffer
-                .unbind();
-            #[cfg(feature = "webgl1")]
-            vao.bind_for_update()
-                .add_instanced_array_buffer(
-                    3,
-                    "center",
-                    WebGl2RenderingContext::DYNAMIC_DRAW,
-                    SliceData(&[]),
-                )
-                // Store the UV and the offsets of the billboard in a VBO
-                .add_array_buffer(
-                    2,
-                    "offset",
-                    WebGl2RenderingContext::STATIC_DRAW,
-                    SliceData(offset.as_ref()),
-                )
-                .add_array_buffer(
-                    2,
-                    "uv",
-                    WebGl2RenderingContext::STATIC_DRAW,
-                    SliceData(uv.as_ref()),
-                )
-                // Store the cartesian position of the center of the source in the a instanced VBO
-                // Set the element buffer
-                .add_element_buffer(
-                    WebGl2RenderingContext::STATIC_DRAW,
-                    SliceData(indices.as_ref()),
-                )
-                // Unbind the buffer
-                .unbind();
-
-            vao
-        };
-        Self {
+        let mut catalog = Self {
             alpha,
             strength,
             colormap,
-            num_instances,
             index_vec,
-            lonlat,
+            sources,
+            mask,
+            positions,
+            table: None,
+            filter: None,
+            columns,
+            cfg,
 
-            vertex_array_object_catalog,
-        }
+            markers,
+            density,
+            weights,
+            max_cell_weight: 0.0,
+            selection: vec![],
+            highlights,
+            aggregates: None,
+            aggregate_markers,
+            half_float_reported: Cell::new(false),
+        };
+        catalog.update_instances();
+
+        catalog
+    }
+
+    pub fn set_table(&mut self, table: Table) {
+        self.table = Some(table);
+    }
+
+    pub fn get_table(&self) -> Option<&Table> {
+        self.table.as_ref()
     }
 
     pub fn set_strength(&mut self, strength: f32) {
         self.strength = strength;
     }
 
-    pub fn set_colormap(&mut self, colormap: Colormap) {
+    pub fn set_colormap(&mut self, colormap: String) {
         self.colormap = colormap;
     }
 
+    /// Replace the sources, the maximum density of the view being estimated at the next update
+    pub fn set_sources(&mut self, mut sources: Box<[Source]>) {
+        self.index_vec = IdxVec::from_coo(&mut sources);
+        self.sources = sources;
+        self.update_mask();
+
+        self.update_instances();
+    }
+
+    /// Replace the sources by ones already sorted by HEALPix cell of depth 7, e.g. by the
+    /// task parsing a table, which are not sorted again
+    ///
+    /// The positions of the rows are given in double precision
+    pub fn set_sorted_sources(&mut self, sources: Box<[Source]>, positions: Box<[LonLatT<f64>]>) {
+        self.index_vec = IdxVec::from_sorted_coo(&sources);
+        self.sources = sources;
+        self.positions = positions;
+        self.update_mask();
+
+        self.update_instances();
+    }
+
+    /// Add sources without columns, e.g. the ones of a tile of a progressive catalogue
+    pub fn add_sources(&mut self, lonlat: Vec<LonLatT<f32>>) {
+        // The rows follow the ones of the sources already added
+        let first_row = self.sources.len() as u32;
+        let mut added = lonlat
+            .into_iter()
+            .enumerate()
+            .map(|(i, lonlat)| Source::new(lonlat, first_row + i as u32))
+            .map(|source| (cell_d7(&source), source))
+            .collect::<Vec<_>>();
+        added.sort_unstable_by_key(|(hash, _)| *hash);
+
+        // Merge the sources added into the ones already sorted by HEALPix cell
+        let mut sources = Vec::with_capacity(self.sources.len() + added.len());
+        let mut added = added.into_iter().peekable();
+        for source in self.sources.iter() {
+            let hash = cell_d7(source);
+            while let Some((_, s)) = added.next_if(|(h, _)| *h < hash) {
+                sources.push(s);
+            }
+            sources.push(*source);
+        }
+        sources.extend(added.map(|(_, s)| s));
+
+        self.index_vec = IdxVec::from_sorted_coo(&sources);
+        self.sources = sources.into_boxed_slice();
+        self.update_mask();
+
+        self.update_instances();
+    }
+
+    // Evaluate the filter over the rows of the sources, which stay sorted and indexed
+    fn update_mask(&mut self) {
+        let num_rows = self
+            .sources
+            .iter()
+            .map(|s| s.row as usize + 1)
+            .max()
+            .unwrap_or(0);
+
+        self.mask = Mask::new(self.filter.as_ref(), &self.columns, num_rows);
+    }
+
+    // The sources shown by the filter, sorted by HEALPix cell
+    fn get_shown_sources(&self) -> Cow<'_, [Source]> {
+        if self.mask.is_empty() {
+            Cow::Borrowed(&self.sources)
+        } else {
+            let shown = self.sources.iter().filter(|s| self.mask.is_shown(s.row));
+            Cow::Owned(shown.copied().collect())
+        }
+    }
+
     pub fn set_alpha(&mut self, alpha: f32) {
         self.alpha = alpha;
+        self.cfg.opacity = alpha;
     }
 
-    fn get_total_num_sources_in_fov(&self, cells: &[HEALPixCell]) -> usize {
-        let mut total_sources = 0;
+    /// Set how the sources are drawn
+    ///
+    /// The columns mapped to the size and color of the markers, the column
+    /// weighting the sources of the heatmap and the columns of the filter must be
+    /// numerical columns of the table of the catalog
+    pub fn set_cfg(&mut self, cfg: CatalogCfg) -> Result<(), Error> {
+        let filter = cfg
+            .filter
+            .as_deref()
+            .map(Filter::new)
+            .transpose()
+            .map_err(|err| Error::FilterNotValid {
+                message: err.to_string(),
+            })?;
 
-        for cell in cells {
-            let sources_idx = self.index_vec.get_item_indices_inside_hpx_cell(cell);
-            total_sources += (sources_idx.end - sources_idx.start) as usize;
+        let size_column = cfg.size_column.as_ref().map(|mapping| &mapping.column);
+        let color_column = cfg.color_column.as_ref().map(|mapping| &mapping.column);
+        let weight_column = cfg.heatmap.weight_column.as_ref();
+        let filter_columns = filter.iter().flat_map(|filter| filter.get_columns());
+        for column in size_column
+            .into_iter()
+            .chain(color_column)
+            .chain(weight_column)
+            .chain(filter_columns)
+        {
+            if !self.columns.contains_key(column) {
+                return Err(Error::ColumnNotPresent {
+                    message: format!("{} is not a numerical column of the catalog!", column),
+                });
+            }
+        }
+
+        let filter_changed = cfg.filter != self.cfg.filter;
+        self.alpha = cfg.opacity;
+        self.cfg = cfg;
+        if filter_changed {
+            self.filter = filter;
+            self.update_mask();
         }
+        self.update_instances();
+
+        Ok(())
+    }
+
+    pub fn get_cfg(&self) -> &CatalogCfg {
+        &self.cfg
+    }
+
+    // Only the instances of the mode of the catalog are sent to the GPU
+    fn update_instances(&mut self) {
+        self.weights = Weights::new(&self.sources, &self.mask, &self.columns, &self.cfg.heatmap);
+
+        // The sources hidden by the filter are not drawn
+        let sources = self.get_shown_sources();
+        let (markers, kernels) = match self.cfg.mode {
+            RenderMode::Markers => (
+                marker::get_markers(&sources, &self.columns, &self.cfg),
+                vec![],
+            ),
+            RenderMode::Heatmap => (
+                vec![],
+                density::get_kernels(&sources, &self.columns, &self.cfg.heatmap),
+            ),
+        };
+
+        self.markers.set_markers(markers);
+        self.density.set_kernels(kernels);
+
+        self.update_highlights();
+    }
+
+    fn update_highlights(&mut self) {
+        let selected = self
+            .sources
+            .iter()
+            .filter(|source| self.mask.is_shown(source.row))
+            .filter(|source| self.selection.binary_search(&source.row).is_ok())
+            .copied()
+            .collect::<Vec<_>>();
+        let cfg = selection::get_highlight_cfg(&self.cfg);
+
+        self.highlights
+            .set_markers(marker::get_markers(&selected, &self.columns, &cfg));
+    }
+
+    /// The rows of the sources lying within `radius` radians of an ICRS position,
+    /// the nearest first
+    pub fn pick(&self, center: &LonLatT<f64>, radius: f64) -> Vec<u32> {
+        let mut rows =
+            selection::get_sources_in_cone(&self.sources, &self.index_vec, center, radius);
+        rows.retain(|&row| self.mask.is_shown(row));
 
-        total_sources
+        rows
+    }
+
+    /// Select the sources lying in a range of ICRS longitudes and latitudes in radians
+    pub fn select_in_zone(&mut self, min: [f64; 2], max: [f64; 2]) -> &[u32] {
+        let mut rows = selection::get_sources_in_zone(&self.sources, &self.index_vec, min, max);
+        rows.retain(|&row| self.mask.is_shown(row));
+        self.set_selection(rows);
+
+        &self.selection
+    }
+
+    /// Select the sources lying in a polygon whose vertices are given in ICRS
+    pub fn select_in_polygon(&mut self, vertices: &[LonLatT<f64>]) -> &[u32] {
+        let mut rows = selection::get_sources_in_polygon(&self.sources, &self.index_vec, vertices);
+        rows.retain(|&row| self.mask.is_shown(row));
+        self.set_selection(rows);
+
+        &self.selection
+    }
+
+    /// Select the sources given by their rows, an empty list clearing the selection
+    pub fn set_selection(&mut self, mut rows: Vec<u32>) {
+        rows.sort_unstable();
+        rows.dedup();
+        self.selection = rows;
+
+        self.update_highlights();
+    }
+
+    pub fn get_selection(&self) -> &[u32] {
+        &self.selection
+    }
+
+    /// The sorted rows of the sources not hidden by the filter
+    pub fn get_shown_rows(&self) -> Vec<u32> {
+        let mut rows = self
+            .sources
+            .iter()
+            .map(|s| s.row)
+            .filter(|&row| self.mask.is_shown(row))
+            .collect::<Vec<_>>();
+        rows.sort_unstable();
+
+        rows
+    }
+
+    // The sources to cross-match, their error radii being given by a numerical column
+    fn get_xmatch_table(&self, error_column: Option<&String>) -> Result<xmatch::Table, Error> {
+        let errors = error_column
+            .map(|column| {
+                self.columns
+                    .get(column)
+                    .map(|values| &values[..])
+                    .ok_or(Error::ColumnNotPresent {
+                        message: format!("{} is not a numerical column of the catalog!", column),
+                    })
+            })
+            .transpose()?;
+
+        Ok(xmatch::Table {
+            sources: self.sources.clone(),
+            index_vec: self.index_vec.clone(),
+            positions: self.positions.clone(),
+            mask: self.mask.clone(),
+            errors: errors.map(Into::into),
+        })
     }
 
     // Cells are of depth <= 7
     fn update(&mut self, cells: &[HEALPixCell]) {
-        let num_sources_in_fov = self.get_total_num_sources_in_fov(cells) as f32;
-        // reset the sources in the frame
-        let mut sources: Vec<_> = vec![];
-        // depth < 7
+        // The markers of all the sources are drawn
+        if self.cfg.mode == RenderMode::Markers {
+            return;
+        }
+
+        // All the sources are drawn, the cells of the view only giving the range of the heatmap
+        self.max_cell_weight = 0.0;
         for cell in cells {
             let delta_depth = (7_i8 - cell.depth() as i8).max(0);
 
             for c in cell.get_children_cells(delta_depth as u8) {
-                // Define the total number of sources being in this kernel depth tile
-                let sources_in_cell = self.index_vec.get_item_indices_inside_hpx_cell(&c);
-                let num_sources_in_kernel_cell =
-                    (sources_in_cell.end - sources_in_cell.start) as usize;
-                if num_sources_in_kernel_cell > 0 {
-                    let num_sources = (((num_sources_in_kernel_cell as f32) / num_sources_in_fov)
-                        * MAX_SOURCES_PER_CATALOG) as usize;
-
-                    let mut idx = self.index_vec.get_item_indices_inside_hpx_cell(&c);
-                    if num_sources < idx.end - idx.start {
-                        // use a selection of num_sources items
-                        idx = idx.start..(idx.start + num_sources);
-                    }
-
-                    sources.extend(&self.lonlat[idx]);
-                }
+                let idx = self.index_vec.get_item_indices_inside_hpx_cell(&c);
+                self.max_cell_weight = self.max_cell_weight.max(self.weights.sum(idx));
             }
         }
-        self.num_instances = sources.len() as i32;
-        let sources = unsafe { utils::transmute_vec::<LonLatT<f32>, f32>(sources).unwrap() };
-
-        // Update the vertex buffer
-        #[cfg(feature = "webgl1")]
-        self.vertex_array_object_catalog
-            .bind_for_update()
-            .update_instanced_array("center", VecData(&sources));
-
-        #[cfg(feature = "webgl2")]
-        self.vertex_array_object_catalog
-            .bind_for_update()
-            .update_instanced_array(
-                "center",
-                WebGl2RenderingContext::DYNAMIC_DRAW,
-                VecData(&sources),
-            );
+    }
+
+    // Group the sources by the cells of the view if the markers are aggregated at its field
+    fn update_aggregates(&mut self, camera: &CameraViewPort) {
+        // The size of a pixel of the page in radians
+        let px_size = camera.get_aperture().to_radians() * (camera.get_dpi() as f64)
+            / (camera.get_width() as f64);
+        let order = self
+            .cfg
+            .aggregation
+            .as_ref()
+            .filter(|_| self.cfg.mode == RenderMode::Markers)
+            .and_then(|aggregation| aggregate::get_order(aggregation, px_size));
+
+        self.aggregates = order.map(|order| {
+            let values = self
+                .cfg
+                .color_column
+                .as_ref()
+                .and_then(|mapping| self.columns.get(&mapping.column))
+                .map(|values| &values[..]);
+
+            if camera.get_field_of_view().is_allsky() {
+                let cells = HEALPixCell::allsky(order);
+                aggregate::get_aggregates(&self.sources, &self.index_vec, &self.mask, cells, values)
+            } else {
+                let cells = camera.get_hpx_cells(order, CooSystem::ICRS).into_iter();
+                aggregate::get_aggregates(&self.sources, &self.index_vec, &self.mask, cells, values)
+            }
+        });
+
+        let markers = self
+            .aggregates
+            .as_ref()
+            .map(|aggregates| aggregate::get_markers(aggregates, &self.columns, &self.cfg))
+            .unwrap_or_default();
+        self.aggregate_markers.set_markers(markers);
     }
 
     fn draw(
@@ -473,88 +935,135 @@ impl Catalog {
         projection: &ProjectionType,
     ) -> Result<(), JsValue> {
         // If the catalog is transparent, simply discard the draw
-        if self.alpha > 0_f32 {
-            // Render to the FRAMEBUFFER
-            // Render the scene
-            manager.fbo.draw_onto(
-                || {
-                    gl.clear_color(0.0, 0.0, 0.0, 1.0);
-                    gl.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
-
-                    let shader = match projection {
-                        ProjectionType::Sin(_) => {
-                            crate::shader::get_shader(gl, shaders, "CatalogOrtVS", "CatalogOrtFS")
-                        }
-                        ProjectionType::Ait(_) => {
-                            crate::shader::get_shader(gl, shaders, "CatalogAitVS", "CatalogFS")
-                        }
-                        ProjectionType::Mer(_) => {
-                            crate::shader::get_shader(gl, shaders, "CatalogMerVS", "CatalogFS")
-                        }
-                        ProjectionType::Mol(_) => {
-                            crate::shader::get_shader(gl, shaders, "CatalogMolVS", "CatalogFS")
-                        }
-                        /*ProjectionType::Arc(_) => {
-                            crate::shader::get_shader(gl, shaders, "CatalogArcVS", "CatalogFS")
-                        }*/
-                        ProjectionType::Tan(_) => {
-                            crate::shader::get_shader(gl, shaders, "CatalogTanVS", "CatalogFS")
-                        }
-                        /*ProjectionType::Hpx(_) => {
-                            crate::shader::get_shader(gl, shaders, "CatalogHpxVS", "CatalogFS")
-                        }*/
-                        _ => todo!(),
-                    }?;
-                    let shader_bound = shader.bind(gl);
-
-                    shader_bound
-                        .attach_uniforms_from(camera)
-                        // Attach catalog specialized uniforms
-                        //.attach_uniform("kernel_texture", &manager.kernels["gaussian"]) // Gaussian kernel texture
-                        .attach_uniform("strength", &self.strength) // Strengh of the kernel
-                        .attach_uniform("current_time", &utils::get_current_time())
-                        .attach_uniform("kernel_size", &manager.kernel_size)
-                        .bind_vertex_array_object_ref(&self.vertex_array_object_catalog)
-                        .draw_elements_instanced_with_i32(
-                            WebGl2RenderingContext::TRIANGLES,
-                            0,
-                            self.num_instances as i32,
-                        );
-                    Ok(())
-                },
-                fbo,
-            )?;
-
-            // Render to the heatmap to the screen
-            {
-                // Set the camera
-                let size = camera.get_screen_size();
-                gl.viewport(0, 0, size.x as i32, size.y as i32);
-
-                let shader = crate::shader::get_shader(
+        if self.alpha <= 0_f32 {
+            return Ok(());
+        }
+
+        match self.cfg.mode {
+            // One marker is drawn per cell while the sources are aggregated
+            RenderMode::Markers if self.aggregates.is_some() => {
+                let cfg = aggregate::get_markers_cfg(&self.cfg);
+                self.aggregate_markers
+                    .draw(gl, shaders, camera, colormaps, projection, &cfg)?;
+            }
+            RenderMode::Markers => {
+                self.markers
+                    .draw(gl, shaders, camera, colormaps, projection, &self.cfg)?;
+            }
+            RenderMode::Heatmap => {
+                self.draw_heatmap(gl, shaders, manager, camera, colormaps, fbo, projection)?;
+            }
+        }
+
+        // The selected sources are highlighted over the catalog
+        if !self.selection.is_empty() {
+            let cfg = selection::get_highlight_cfg(&self.cfg);
+            self.highlights
+                .draw(gl, shaders, camera, colormaps, projection, &cfg)?;
+        }
+
+        Ok(())
+    }
+
+    fn draw_heatmap(
+        &self,
+        gl: &WebGlContext,
+        shaders: &mut ShaderManager,
+        manager: &Manager,
+        camera: &CameraViewPort,
+        colormaps: &Colormaps,
+        fbo: Option<&FrameBufferObject>,
+        projection: &ProjectionType,
+    ) -> Result<(), JsValue> {
+        let density_fbo = if let Some(density_fbo) = &manager.fbo {
+            density_fbo
+        } else {
+            return Ok(());
+        };
+
+        // The size of a screen pixel in radians
+        let px_size = camera.get_aperture().to_radians() / (camera.get_width() as f64);
+        let heatmap = &self.cfg.heatmap;
+        let bandwidth = match heatmap.bandwidth_unit {
+            SizeUnit::Pixel => heatmap.bandwidth * camera.get_dpi(),
+            SizeUnit::Arcsec => {
+                let bandwidth = (heatmap.bandwidth as f64 / 3600.0).to_radians();
+                (bandwidth / px_size) as f32
+            }
+        };
+
+        // Sum the kernels of the sources in the float framebuffer, the heaviest source
+        // weighting 1 so that the sums stay in the range of the 16 bits floats
+        let weight_scale = self.weights.scale();
+        density_fbo.draw_onto(
+            || {
+                gl.clear_color(0.0, 0.0, 0.0, 0.0);
+                gl.clear(WebGl2RenderingContext::COLOR_BUFFER_BIT);
+
+                self.density.draw(
                     gl,
                     shaders,
-                    "ColormapCatalogVS",
-                    "ColormapCatalogFS",
-                )?;
-                //self.colormap.get_shader(gl, shaders);
-                let shaderbound = shader.bind(gl);
-                shaderbound
-                    .attach_uniform("texture_fbo", &manager.fbo.texture) // FBO density texture computed just above
-                    .attach_uniform("alpha", &self.alpha) // Alpha channel
-                    .attach_uniforms_with_params_from(&self.colormap, colormaps)
-                    .attach_uniforms_from(colormaps)
-                    .attach_uniform("reversed", &0.0_f32)
-                    .bind_vertex_array_object_ref(&manager.vertex_array_object_screen)
-                    .draw_elements_with_i32(
-                        WebGl2RenderingContext::TRIANGLES,
-                        None,
-                        WebGl2RenderingContext::UNSIGNED_SHORT,
-                        0,
-                    );
-            }
+                    camera,
+                    projection,
+                    heatmap,
+                    bandwidth,
+                    weight_scale,
+                )
+            },
+            fbo,
+        )?;
+
+        // Render the heatmap to the screen
+        let size = camera.get_screen_size();
+        gl.viewport(0, 0, size.x as i32, size.y as i32);
+        gl.scissor(0, 0, size.x as i32, size.y as i32);
+
+        let [min_density, max_density] = heatmap.range.unwrap_or_else(|| {
+            let cell_area = CELL_AREA_D7 / (px_size * px_size);
+            let max_density = density::get_max_density(
+                self.max_cell_weight,
+                &self.weights,
+                heatmap.kernel,
+                bandwidth,
+                cell_area,
+            );
+
+            [0.0, max_density]
+        });
+        let [min_density, max_density] = [min_density * weight_scale, max_density * weight_scale];
+
+        if density_fbo.is_half_float()
+            && max_density > MAX_HALF_FLOAT_DENSITY
+            && !self.half_float_reported.replace(true)
+        {
+            al_core::log::console_warn(
+                "The heatmap is too dense to be summed exactly without EXT_float_blend",
+            );
         }
 
+        crate::shader::get_shader(
+            gl,
+            shaders,
+            "colormaps_colormap.vert",
+            "catalogs_heatmap.frag",
+        )?
+        .bind(gl)
+        .attach_uniform("u_density", &density_fbo.texture)
+        .attach_uniform("u_strength", &self.strength)
+        .attach_uniform("u_min_density", &min_density)
+        .attach_uniform("u_max_density", &max_density)
+        .attach_uniform("u_opacity", &self.alpha)
+        .attach_uniforms_from(&heatmap.stretch)
+        .attach_uniforms_with_params_from(colormaps.get(&self.colormap), colormaps)
+        .attach_uniforms_from(colormaps)
+        .bind_vertex_array_object_ref(&manager.vertex_array_object_screen)
+        .draw_elements_with_i32(
+            WebGl2RenderingContext::TRIANGLES,
+            None,
+            WebGl2RenderingContext::UNSIGNED_SHORT,
+            0,
+        );
+
         Ok(())
     }
 }
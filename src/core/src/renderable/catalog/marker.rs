@@ -0,0 +1,286 @@
+use std::collections::HashMap;
+
+use al_api::catalog::{Catalog as CatalogCfg, Shape, SizeUnit};
+use al_api::coo_system::CooSystem;
+
+use al_core::{Colormaps, SliceData, VecData, VertexArrayObject, WebGlContext};
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+use super::Source;
+use crate::{CameraViewPort, ProjectionType, ShaderManager};
+
+/// The values of the numerical columns of a catalog, indexed by the row of the sources
+pub type Columns = HashMap<String, Box<[f32]>>;
+
+// The number of floats describing a marker: its position, size and color value
+const NUM_F32_PER_MARKER: usize = 4;
+
+// The values of a column mapped to [0, 1]
+pub(super) struct Mapping<'a> {
+    values: &'a [f32],
+    min: f32,
+    max: f32,
+}
+
+impl<'a> Mapping<'a> {
+    pub(super) fn new(values: &'a [f32], range: Option<[f32; 2]>) -> Self {
+        let [min, max] = range.unwrap_or_else(|| {
+            values
+                .iter()
+                .filter(|v| v.is_finite())
+                .fold([f32::INFINITY, f32::NEG_INFINITY], |[min, max], &v| {
+                    [min.min(v), max.max(v)]
+                })
+        });
+
+        Self { values, min, max }
+    }
+
+    // The normalized value of a row, None if the value is null
+    fn get(&self, row: u32) -> Option<f32> {
+        self.normalize(*self.values.get(row as usize)?)
+    }
+
+    // The normalized value of any value of the column, e.g. a mean
+    pub(super) fn normalize(&self, value: f32) -> Option<f32> {
+        if !value.is_finite() {
+            return None;
+        }
+
+        let t = if self.max != self.min {
+            (value - self.min) / (self.max - self.min)
+        } else {
+            0.0
+        };
+
+        Some(t.clamp(0.0, 1.0))
+    }
+}
+
+/// Compute the data of the markers of the sources
+///
+/// Each marker is given by its position in radians, its size in the unit of the
+/// config and the value given to the colormap. That value is negative for the
+/// markers drawn with the color of the catalog.
+pub fn get_markers(sources: &[Source], columns: &Columns, cfg: &CatalogCfg) -> Vec<f32> {
+    let size = cfg.size_column.as_ref().and_then(|mapping| {
+        let values = columns.get(&mapping.column)?;
+        Some((Mapping::new(values, mapping.range), mapping.sizes))
+    });
+    let color = cfg.color_column.as_ref().and_then(|mapping| {
+        let values = columns.get(&mapping.column)?;
+        Some((Mapping::new(values, mapping.range), mapping.reversed))
+    });
+
+    let mut markers = Vec::with_capacity(sources.len() * NUM_F32_PER_MARKER);
+    for source in sources {
+        let size = size
+            .as_ref()
+            .and_then(|(mapping, [s0, s1])| {
+                let t = mapping.get(source.row)?;
+                Some(s0 + t * (s1 - s0))
+            })
+            .unwrap_or(cfg.size);
+        let value = color
+            .as_ref()
+            .and_then(|(mapping, reversed)| {
+                let t = mapping.get(source.row)?;
+                Some(if *reversed { 1.0 - t } else { t })
+            })
+            .unwrap_or(-1.0);
+
+        markers.extend([source.lonlat.0 .0, source.lonlat.1 .0, size, value]);
+    }
+
+    markers
+}
+
+/// The markers of the sources of a catalog
+///
+/// All the sources are drawn, their instances being only sent to the GPU when the
+/// sources or the config of the catalog change
+pub struct Markers {
+    vao: VertexArrayObject,
+    num_instances: i32,
+}
+
+impl Markers {
+    pub fn new(gl: &WebGlContext) -> Self {
+        let mut vao = VertexArrayObject::new(gl);
+
+        vao.bind_for_update()
+            // The corners of the marker
+            .add_array_buffer(
+                "vertices",
+                2 * std::mem::size_of::<f32>(),
+                &[2],
+                &[0],
+                WebGl2RenderingContext::STATIC_DRAW,
+                SliceData(&[-1.0_f32, -1.0, 1.0, -1.0, 1.0, 1.0, -1.0, 1.0]),
+            )
+            // The position, size and color value of each marker
+            .add_instanced_array_buffer(
+                "markers",
+                NUM_F32_PER_MARKER * std::mem::size_of::<f32>(),
+                &[2, 1, 1],
+                &[
+                    0,
+                    2 * std::mem::size_of::<f32>(),
+                    3 * std::mem::size_of::<f32>(),
+                ],
+                WebGl2RenderingContext::STATIC_DRAW,
+                SliceData(&[]),
+            )
+            .add_element_buffer(
+                WebGl2RenderingContext::STATIC_DRAW,
+                SliceData(&[0_u16, 1, 2, 0, 2, 3]),
+            )
+            .unbind();
+
+        Self {
+            vao,
+            num_instances: 0,
+        }
+    }
+
+    /// Send the markers computed by `get_markers` to the GPU
+    pub fn set_markers(&mut self, markers: Vec<f32>) {
+        self.num_instances = (markers.len() / NUM_F32_PER_MARKER) as i32;
+
+        self.vao.bind_for_update().update_instanced_array(
+            "markers",
+            WebGl2RenderingContext::STATIC_DRAW,
+            VecData(&markers),
+        );
+    }
+
+    pub fn draw(
+        &self,
+        gl: &WebGlContext,
+        shaders: &mut ShaderManager,
+        camera: &CameraViewPort,
+        colormaps: &Colormaps,
+        proj: &ProjectionType,
+        cfg: &CatalogCfg,
+    ) -> Result<(), JsValue> {
+        if self.num_instances == 0 {
+            return Ok(());
+        }
+
+        let icrs2world = camera.get_m2w() * CooSystem::ICRS.to(camera.get_coo_system());
+        // The number of screen pixels covered by a unit of size
+        let size_scale = match cfg.size_unit {
+            SizeUnit::Pixel => camera.get_dpi(),
+            SizeUnit::Arcsec => {
+                let aperture = camera.get_aperture().to_radians().to_degrees() * 3600.0;
+                camera.get_width() / (aperture as f32)
+            }
+        };
+        let shape = match cfg.shape {
+            Shape::Circle => 0,
+            Shape::Square => 1,
+            Shape::Cross => 2,
+            Shape::Plus => 3,
+            Shape::Rhomb => 4,
+            Shape::Triangle => 5,
+        };
+        // The colormap is only used when the color is given by a column
+        let colormap = cfg
+            .color_column
+            .as_ref()
+            .map(|mapping| mapping.colormap.as_str())
+            .unwrap_or("grayscale");
+
+        gl.blend_func_separate(
+            WebGl2RenderingContext::SRC_ALPHA,
+            WebGl2RenderingContext::ONE_MINUS_SRC_ALPHA,
+            WebGl2RenderingContext::ONE,
+            WebGl2RenderingContext::ONE,
+        );
+
+        crate::shader::get_shader(gl, shaders, "catalogs_marker.vert", "catalogs_marker.frag")?
+            .bind(gl)
+            .attach_uniforms_from(camera)
+            .attach_uniform("u_2world", &icrs2world)
+            .attach_uniform("u_proj", proj)
+            .attach_uniform("u_screen_size", &camera.get_screen_size())
+            .attach_uniform("u_size_scale", &size_scale)
+            .attach_uniform("u_shape", &shape)
+            .attach_uniform("u_color", &cfg.color)
+            .attach_uniform("u_opacity", &cfg.opacity)
+            .attach_uniform("u_line_width", &(1.5 * camera.get_dpi()))
+            .attach_uniforms_with_params_from(colormaps.get(colormap), colormaps)
+            .attach_uniforms_from(colormaps)
+            .bind_vertex_array_object_ref(&self.vao)
+            .draw_elements_instanced_with_i32(
+                WebGl2RenderingContext::TRIANGLES,
+                0,
+                self.num_instances,
+            );
+
+        // Restore the blending of the layers
+        gl.blend_func_separate(
+            WebGl2RenderingContext::SRC_ALPHA,
+            WebGl2RenderingContext::ONE,
+            WebGl2RenderingContext::ONE,
+            WebGl2RenderingContext::ONE,
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_markers, Columns};
+    use crate::math::angle::Angle;
+    use crate::math::lonlat::LonLatT;
+    use crate::renderable::catalog::Source;
+    use al_api::catalog::{Catalog, ColorMapping, SizeMapping};
+
+    #[test]
+    fn map_columns_to_markers() {
+        let sources = (0..3)
+            .map(|row| Source::new(LonLatT::new(Angle(0.1), Angle(0.2)), row))
+            .collect::<Vec<_>>();
+        let columns: Columns = [
+            ("mag".to_string(), vec![10.0, 15.0, 20.0].into_boxed_slice()),
+            (
+                "bv".to_string(),
+                vec![0.0, f32::NAN, 2.0].into_boxed_slice(),
+            ),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        // Without any mapping, the markers take the size and color of the catalog
+        let cfg = Catalog::default();
+        let markers = get_markers(&sources, &columns, &cfg);
+        assert_eq!(markers.len(), 3 * 4);
+        assert_eq!(&markers[..4], &[0.1, 0.2, cfg.size, -1.0]);
+
+        let cfg = Catalog {
+            size_column: Some(SizeMapping {
+                column: "mag".to_string(),
+                range: None,
+                sizes: [16.0, 4.0],
+            }),
+            color_column: Some(ColorMapping {
+                column: "bv".to_string(),
+                colormap: "viridis".to_string(),
+                range: Some([0.0, 1.0]),
+                reversed: true,
+            }),
+            ..Default::default()
+        };
+        let markers = get_markers(&sources, &columns, &cfg);
+        let sizes = markers.iter().skip(2).step_by(4).collect::<Vec<_>>();
+        let values = markers.iter().skip(3).step_by(4).collect::<Vec<_>>();
+        assert_eq!(sizes, [&16.0, &10.0, &4.0]);
+        // The null values are drawn with the color of the catalog
+        // and the values are clamped to the range
+        assert_eq!(values, [&1.0, &-1.0, &0.0]);
+    }
+}
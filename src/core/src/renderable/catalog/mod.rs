@@ -1,2 +1,12 @@
+mod aggregate;
+mod density;
+mod filter;
+pub mod hips_cat;
 mod manager;
+pub mod marker;
+mod selection;
+mod source;
+pub mod xmatch;
+pub use hips_cat::HiPSCatalog;
 pub use manager::{Catalog, Manager};
+pub use source::Source;
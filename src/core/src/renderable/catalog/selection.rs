@@ -0,0 +1,196 @@
+use al_api::catalog::Catalog as CatalogCfg;
+use cgmath::Vector4;
+use healpix::sph_geom::coo3d::Coo3D;
+use healpix::sph_geom::{ContainsSouthPoleMethod, Polygon};
+use moclib::elem::cell::Cell;
+use moclib::moc::{RangeMOCIntoIterator, RangeMOCIterator};
+
+use super::Source;
+use crate::healpix::cell::HEALPixCell;
+use crate::healpix::coverage::HEALPixCoverage;
+use crate::healpix::index_vector::IdxVec;
+use crate::math::angle::Angle;
+use crate::math::lonlat::{self, LonLatT};
+
+// The depth of the HEALPix index of the sources
+const INDEX_DEPTH: u8 = 7;
+
+fn get_lonlat(source: &Source) -> LonLatT<f64> {
+    LonLatT::new(
+        Angle(source.lonlat.0 .0 as f64),
+        Angle(source.lonlat.1 .0 as f64),
+    )
+}
+
+// The sources lying in the cells of depth <= 7 covering a region
+pub(super) fn get_candidates<'a>(
+    sources: &'a [Source],
+    index_vec: &'a IdxVec,
+    coverage: &'a HEALPixCoverage,
+) -> impl Iterator<Item = &'a Source> + 'a {
+    (&coverage.0)
+        .into_range_moc_iter()
+        .cells()
+        .flat_map(move |Cell { depth, idx }| {
+            let range = index_vec.get_item_indices_inside_hpx_cell(&HEALPixCell(depth, idx));
+            sources[range].iter()
+        })
+}
+
+/// The rows of the sources lying within `radius` radians of a position, the nearest first
+pub fn get_sources_in_cone(
+    sources: &[Source],
+    index_vec: &IdxVec,
+    center: &LonLatT<f64>,
+    radius: f64,
+) -> Vec<u32> {
+    let coverage = HEALPixCoverage::from_cone(center, radius, INDEX_DEPTH);
+
+    let mut rows = get_candidates(sources, index_vec, &coverage)
+        .filter_map(|source| {
+            let d = lonlat::ang_between_lonlat(*center, get_lonlat(source)).0;
+            (d <= radius).then_some((d, source.row))
+        })
+        .collect::<Vec<_>>();
+    rows.sort_unstable_by(|(d1, _), (d2, _)| d1.total_cmp(d2));
+
+    rows.into_iter().map(|(_, row)| row).collect()
+}
+
+/// The rows of the sources lying in a range of longitudes and latitudes, in radians
+///
+/// The zone crosses the meridian of longitude 0 if `lon_min` is greater than `lon_max`
+pub fn get_sources_in_zone(
+    sources: &[Source],
+    index_vec: &IdxVec,
+    [lon_min, lat_min]: [f64; 2],
+    [lon_max, lat_max]: [f64; 2],
+) -> Vec<u32> {
+    let coverage = HEALPixCoverage::from_zone([lon_min, lat_min], [lon_max, lat_max], INDEX_DEPTH);
+    let contains_lon = |lon: f64| {
+        if lon_min <= lon_max {
+            (lon_min..=lon_max).contains(&lon)
+        } else {
+            lon >= lon_min || lon <= lon_max
+        }
+    };
+
+    let mut rows = get_candidates(sources, index_vec, &coverage)
+        .filter(|source| {
+            let LonLatT(lon, lat) = get_lonlat(source);
+            (lat_min..=lat_max).contains(&lat.0) && contains_lon(lon.0)
+        })
+        .map(|source| source.row)
+        .collect::<Vec<_>>();
+    rows.sort_unstable();
+
+    rows
+}
+
+/// The rows of the sources lying in a polygon, e.g. a lasso drawn by the user
+///
+/// The polygon is the region enclosed by its vertices containing their barycenter
+pub fn get_sources_in_polygon(
+    sources: &[Source],
+    index_vec: &IdxVec,
+    vertices: &[LonLatT<f64>],
+) -> Vec<u32> {
+    if vertices.len() < 3 {
+        return vec![];
+    }
+
+    let barycenter = vertices
+        .iter()
+        .map(|vertex| vertex.vector::<Vector4<f64>>())
+        .fold(Vector4::new(0.0, 0.0, 0.0, 0.0), |sum, v| sum + v);
+    let (lon, lat) = lonlat::xyzw_to_radec(&barycenter);
+    let inside = LonLatT::new(lon, lat);
+
+    let coverage = HEALPixCoverage::from_3d_coos(INDEX_DEPTH, vertices.iter().copied(), &inside);
+    let polygon = Polygon::new_custom_vec3(
+        vertices
+            .iter()
+            .map(|vertex| Coo3D::from_sph_coo(vertex.lon().0, vertex.lat().0))
+            .collect(),
+        &ContainsSouthPoleMethod::ControlPointIn(Coo3D::from_sph_coo(lon.0, lat.0)),
+    );
+
+    let mut rows = get_candidates(sources, index_vec, &coverage)
+        .filter(|source| {
+            let LonLatT(lon, lat) = get_lonlat(source);
+            polygon.contains(&Coo3D::from_sph_coo(lon.0, lat.0))
+        })
+        .map(|source| source.row)
+        .collect::<Vec<_>>();
+    rows.sort_unstable();
+
+    rows
+}
+
+/// The config of the markers highlighting the selected sources
+///
+/// They keep the shape and size of the markers of the catalog but are drawn with the
+/// selection color
+pub fn get_highlight_cfg(cfg: &CatalogCfg) -> CatalogCfg {
+    CatalogCfg {
+        color: cfg.selection_color,
+        color_column: None,
+        ..cfg.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_sources_in_cone, get_sources_in_polygon, get_sources_in_zone};
+    use crate::healpix::index_vector::IdxVec;
+    use crate::math::angle::Angle;
+    use crate::math::lonlat::LonLatT;
+    use crate::renderable::catalog::Source;
+
+    fn lonlat(lon: f64, lat: f64) -> LonLatT<f64> {
+        LonLatT::new(Angle(lon.to_radians()), Angle(lat.to_radians()))
+    }
+
+    #[test]
+    fn select_sources() {
+        let mut sources = [(10.0, 0.0), (10.5, 0.2), (200.0, -45.0), (359.9, 0.1)]
+            .iter()
+            .enumerate()
+            .map(|(row, &(lon, lat))| {
+                let LonLatT(lon, lat) = lonlat(lon, lat);
+                Source::new(
+                    LonLatT::new(Angle(lon.0 as f32), Angle(lat.0 as f32)),
+                    row as u32,
+                )
+            })
+            .collect::<Vec<_>>();
+        let index_vec = IdxVec::from_coo(&mut sources);
+
+        // The nearest source comes first
+        let center = lonlat(10.4, 0.1);
+        let rows = get_sources_in_cone(&sources, &index_vec, &center, 1_f64.to_radians());
+        assert_eq!(rows, [1, 0]);
+        let rows = get_sources_in_cone(&sources, &index_vec, &center, 0.2_f64.to_radians());
+        assert_eq!(rows, [1]);
+
+        // A zone crossing the meridian of longitude 0
+        let rows = get_sources_in_zone(
+            &sources,
+            &index_vec,
+            [350_f64.to_radians(), -1_f64.to_radians()],
+            [11_f64.to_radians(), 1_f64.to_radians()],
+        );
+        assert_eq!(rows, [0, 1, 3]);
+
+        let triangle = [
+            lonlat(195.0, -50.0),
+            lonlat(205.0, -50.0),
+            lonlat(200.0, -40.0),
+        ];
+        let rows = get_sources_in_polygon(&sources, &index_vec, &triangle);
+        assert_eq!(rows, [2]);
+        let triangle = [lonlat(9.0, -1.0), lonlat(10.2, -1.0), lonlat(10.2, 1.0)];
+        let rows = get_sources_in_polygon(&sources, &index_vec, &triangle);
+        assert_eq!(rows, [0]);
+    }
+}
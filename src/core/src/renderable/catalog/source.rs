@@ -0,0 +1,37 @@
+use crate::math::angle::Angle;
+use crate::math::lonlat::{LonLat, LonLatT};
+
+/// A source of a catalog
+///
+/// The index of its row in the table given by the user is kept so that the
+/// values of the other columns can be retrieved once the sources are sorted
+#[derive(Clone, Copy, Debug)]
+pub struct Source {
+    pub lonlat: LonLatT<f32>,
+    pub row: u32,
+}
+
+impl Source {
+    pub fn new(lonlat: LonLatT<f32>, row: u32) -> Self {
+        Self { lonlat, row }
+    }
+}
+
+impl LonLat<f32> for Source {
+    fn lon(&self) -> Angle<f32> {
+        self.lonlat.lon()
+    }
+
+    fn lat(&self) -> Angle<f32> {
+        self.lonlat.lat()
+    }
+
+    fn lonlat(&self) -> LonLatT<f32> {
+        self.lonlat
+    }
+
+    // A source built from a position alone does not refer to any row
+    fn from_lonlat(lonlat: &LonLatT<f32>) -> Self {
+        Self::new(*lonlat, u32::MAX)
+    }
+}
@@ -0,0 +1,369 @@
+use al_api::catalog::{XMatch, XMatchMode};
+use cgmath::{InnerSpace, Vector3};
+use serde::Serialize;
+
+use super::filter::Mask;
+use super::selection;
+use super::Source;
+use crate::healpix::cell::HEALPixCell;
+use crate::healpix::coverage::HEALPixCoverage;
+use crate::healpix::index_vector::IdxVec;
+use crate::math::angle::Angle;
+use crate::math::lonlat::{self, LonLatT};
+
+// The depth of the HEALPix index of the sources
+const INDEX_DEPTH: u8 = 7;
+
+/// The sources of a catalog to cross-match
+pub struct Table {
+    pub sources: Box<[Source]>,
+    pub index_vec: IdxVec,
+    /// The positions of the sources in radians, indexed by their row. The positions of the
+    /// sources are taken for the rows missing, e.g. for a progressive catalogue
+    pub positions: Box<[LonLatT<f64>]>,
+    /// The sources shown by the filter of the catalog, the other ones not being matched
+    pub mask: Mask,
+    /// The error radii of the sources in arcseconds, indexed by their row
+    pub errors: Option<Box<[f32]>>,
+}
+
+impl Table {
+    // The error radius of a source in radians, the null errors being zero
+    fn get_error(&self, row: u32) -> f64 {
+        let error = self
+            .errors
+            .as_deref()
+            .and_then(|errors| errors.get(row as usize))
+            .copied()
+            .filter(|error| error.is_finite())
+            .unwrap_or(0.0);
+
+        arcsec_to_rad(error as f64)
+    }
+
+    fn get_max_error(&self) -> f64 {
+        let max = self.errors.as_deref().map_or(0.0, |errors| {
+            errors
+                .iter()
+                .filter(|error| error.is_finite())
+                .fold(0.0_f32, |max, &error| max.max(error))
+        });
+
+        arcsec_to_rad(max as f64)
+    }
+
+    // The position of a source, given in double precision by its row
+    fn get_vector(&self, source: &Source) -> Vector3<f64> {
+        let LonLatT(lon, lat) = self
+            .positions
+            .get(source.row as usize)
+            .copied()
+            .unwrap_or_else(|| {
+                let LonLatT(lon, lat) = source.lonlat;
+                LonLatT::new(Angle(lon.0 as f64), Angle(lat.0 as f64))
+            });
+
+        lonlat::radec_to_xyz(lon, lat)
+    }
+}
+
+/// The pairs of sources of a cross-match, sorted by the rows of the first catalog
+/// and the separations
+#[derive(Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Matches {
+    /// The rows of the sources of the first catalog
+    pub rows: Vec<u32>,
+    /// The rows of their counterparts in the second catalog, empty for an anti-join
+    pub other_rows: Vec<u32>,
+    /// The separations of the pairs in arcseconds, empty for an anti-join
+    pub separations: Vec<f64>,
+}
+
+fn arcsec_to_rad(arcsec: f64) -> f64 {
+    (arcsec / 3600.0).to_radians()
+}
+
+// The angle between two unit vectors, precise for the small separations
+fn get_separation(v1: &Vector3<f64>, v2: &Vector3<f64>) -> f64 {
+    2.0 * (0.5 * (v1 - v2).magnitude()).min(1.0).asin()
+}
+
+// The largest distance between the center of a cell and its vertices
+fn get_cell_radius(cell: &HEALPixCell) -> (LonLatT<f64>, f64) {
+    let (lon, lat) = cell.center();
+    let center = LonLatT::new(Angle(lon), Angle(lat));
+
+    let radius = cell
+        .vertices()
+        .iter()
+        .map(|&(lon, lat)| {
+            let vertex = LonLatT::new(Angle(lon), Angle(lat));
+            lonlat::ang_between_lonlat(center, vertex).0
+        })
+        .fold(0.0, f64::max);
+
+    (center, radius)
+}
+
+/// A cross-match of the sources of two catalogs by their position
+///
+/// The sources of the first catalog are processed by HEALPix cells of depth 7, their
+/// counterparts being looked for in the cells of the second catalog around them
+pub struct CrossMatch {
+    table: Table,
+    other: Table,
+    mode: XMatchMode,
+    // The radius of the pairs in radians
+    radius: f64,
+    // The largest separation of a pair, given the largest error radii
+    max_threshold: f64,
+    // The hash of the next cell of depth 7 to process
+    hash: u64,
+    matches: Matches,
+}
+
+impl CrossMatch {
+    pub fn new(table: Table, other: Table, cfg: &XMatch) -> Self {
+        let radius = arcsec_to_rad(cfg.radius);
+        let max_other_error = other.get_max_error();
+        let max_error = table.get_max_error();
+        let max_threshold =
+            (radius * radius + max_error * max_error + max_other_error * max_other_error).sqrt();
+
+        Self {
+            table,
+            other,
+            mode: cfg.mode,
+            radius,
+            max_threshold,
+            hash: 0,
+            matches: Matches::default(),
+        }
+    }
+
+    /// Match the sources of the next cells, `num_cells` of them containing sources at most
+    ///
+    /// Returns true once all the cells have been processed
+    pub fn step(&mut self, num_cells: usize) -> bool {
+        let (table, other) = (&self.table, &self.other);
+        let matches = &mut self.matches;
+
+        let num_hashes = 12 << (2 * INDEX_DEPTH);
+        let mut num_matched = 0;
+        let mut candidates = vec![];
+        let mut pairs = vec![];
+        while self.hash < num_hashes && num_matched < num_cells {
+            let cell = HEALPixCell(INDEX_DEPTH, self.hash);
+            self.hash += 1;
+
+            let sources = &table.sources[table.index_vec.get_item_indices_inside_hpx_cell(&cell)];
+            if sources.is_empty() {
+                continue;
+            }
+            num_matched += 1;
+
+            // The counterparts of the sources of the cell
+            let (center, cell_radius) = get_cell_radius(&cell);
+            let coverage =
+                HEALPixCoverage::from_cone(&center, cell_radius + self.max_threshold, INDEX_DEPTH);
+            candidates.clear();
+            candidates.extend(
+                selection::get_candidates(&other.sources, &other.index_vec, &coverage)
+                    .filter(|source| other.mask.is_shown(source.row))
+                    .map(|source| {
+                        let error = other.get_error(source.row);
+                        (other.get_vector(source), source.row, error * error)
+                    }),
+            );
+
+            for source in sources.iter().filter(|s| table.mask.is_shown(s.row)) {
+                let v = table.get_vector(source);
+                let error = table.get_error(source.row);
+                let r2 = self.radius * self.radius + error * error;
+
+                pairs.clear();
+                pairs.extend(
+                    candidates
+                        .iter()
+                        .filter_map(|(other_v, other_row, other_error2)| {
+                            let separation = get_separation(&v, other_v);
+                            let threshold = (r2 + other_error2).sqrt();
+
+                            (separation <= threshold).then_some((separation, *other_row))
+                        }),
+                );
+
+                match self.mode {
+                    XMatchMode::NoMatch => {
+                        if pairs.is_empty() {
+                            matches.rows.push(source.row);
+                        }
+                    }
+                    XMatchMode::Best => {
+                        let best = pairs.iter().min_by(|(s1, _), (s2, _)| s1.total_cmp(s2));
+                        if let Some(&(separation, other_row)) = best {
+                            matches.rows.push(source.row);
+                            matches.other_rows.push(other_row);
+                            matches.separations.push(separation);
+                        }
+                    }
+                    XMatchMode::All => {
+                        pairs.sort_unstable_by(|(s1, _), (s2, _)| s1.total_cmp(s2));
+                        for &(separation, other_row) in &pairs {
+                            matches.rows.push(source.row);
+                            matches.other_rows.push(other_row);
+                            matches.separations.push(separation);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.hash == num_hashes
+    }
+
+    /// The pairs found, once all the cells have been processed
+    pub fn finish(self) -> Matches {
+        let mut matches = self.matches;
+
+        // Sort the pairs by the rows of the first catalog, keeping the order of the separations
+        let mut order = (0..matches.rows.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| matches.rows[i]);
+        let rows = order.iter().map(|&i| matches.rows[i]).collect();
+        if self.mode != XMatchMode::NoMatch {
+            matches.other_rows = order.iter().map(|&i| matches.other_rows[i]).collect();
+            matches.separations = order
+                .iter()
+                .map(|&i| matches.separations[i].to_degrees() * 3600.0)
+                .collect();
+        }
+        matches.rows = rows;
+
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CrossMatch, Matches, Table};
+    use crate::healpix::index_vector::IdxVec;
+    use crate::math::angle::Angle;
+    use crate::math::lonlat::LonLatT;
+    use crate::renderable::catalog::filter::{Filter, Mask};
+    use crate::renderable::catalog::marker::Columns;
+    use crate::renderable::catalog::Source;
+    use al_api::catalog::{XMatch, XMatchMode};
+
+    fn get_table(positions: &[(f64, f64)], errors: Option<&[f32]>) -> Table {
+        let positions = positions
+            .iter()
+            .map(|&(lon, lat)| LonLatT::new(Angle(lon.to_radians()), Angle(lat.to_radians())))
+            .collect::<Box<[_]>>();
+        let mut sources = positions
+            .iter()
+            .enumerate()
+            .map(|(row, &LonLatT(lon, lat))| {
+                let lonlat = LonLatT::new(Angle(lon.0 as f32), Angle(lat.0 as f32));
+                Source::new(lonlat, row as u32)
+            })
+            .collect::<Box<[_]>>();
+        let index_vec = IdxVec::from_coo(&mut sources);
+
+        Table {
+            sources,
+            index_vec,
+            positions,
+            mask: Mask::default(),
+            errors: errors.map(Into::into),
+        }
+    }
+
+    // The cells are processed a few at a time, like by the task of the app
+    fn cross_match(table: Table, other: Table, cfg: &XMatch) -> Matches {
+        let mut xmatch = CrossMatch::new(table, other, cfg);
+        while !xmatch.step(2) {}
+
+        xmatch.finish()
+    }
+
+    #[test]
+    fn cross_match_sources() {
+        let arcsec = 1.0 / 3600.0;
+        let sources = [(10.0, 20.0), (100.0, -30.0), (250.0, 60.0)];
+        // Two counterparts of the first source, one 3 arcseconds away from the second
+        // one and a source far from all the others
+        let others = [
+            (10.0 + 2.0 * arcsec, 20.0),
+            (10.0, 20.0 + 0.5 * arcsec),
+            (100.0, -30.0 + 3.0 * arcsec),
+            (0.0, -80.0),
+        ];
+        let mut cfg = XMatch {
+            mode: XMatchMode::Best,
+            radius: 2.5,
+            error_column: None,
+            other_error_column: None,
+        };
+
+        let matches = cross_match(get_table(&sources, None), get_table(&others, None), &cfg);
+        assert_eq!(matches.rows, [0]);
+        assert_eq!(matches.other_rows, [1]);
+        assert!((matches.separations[0] - 0.5).abs() < 1e-6);
+
+        cfg.mode = XMatchMode::All;
+        let matches = cross_match(get_table(&sources, None), get_table(&others, None), &cfg);
+        assert_eq!(matches.rows, [0, 0]);
+        assert_eq!(matches.other_rows, [1, 0]);
+
+        cfg.mode = XMatchMode::NoMatch;
+        let matches = cross_match(get_table(&sources, None), get_table(&others, None), &cfg);
+        assert_eq!(
+            matches,
+            Matches {
+                rows: vec![1, 2],
+                ..Default::default()
+            }
+        );
+
+        // The error radii widen the radius of the pairs
+        let errors = [0.0, 2.0, f32::NAN];
+        let table = get_table(&sources, Some(&errors));
+        let matches = cross_match(table, get_table(&others, None), &cfg);
+        assert_eq!(matches.rows, [2]);
+
+        // The sources hidden by the filter are not matched
+        cfg.mode = XMatchMode::Best;
+        let columns: Columns = [("flag".to_string(), vec![1.0, 0.0, 1.0, 1.0].into())]
+            .iter()
+            .cloned()
+            .collect();
+        let filter = Filter::new("flag > 0").unwrap();
+        let other = Table {
+            mask: Mask::new(Some(&filter), &columns, 4),
+            ..get_table(&others, None)
+        };
+        let matches = cross_match(get_table(&sources, None), other, &cfg);
+        assert_eq!(matches.other_rows, [0]);
+    }
+
+    #[test]
+    fn separations_below_float_precision() {
+        // 10 milliarcseconds apart, less than the precision of a longitude in single
+        // precision radians
+        let lon = 100.0;
+        let other_lon = lon + 0.01 / 3600.0 / 30.0_f64.to_radians().cos();
+        let cfg = XMatch {
+            mode: XMatchMode::Best,
+            radius: 0.015,
+            error_column: None,
+            other_error_column: None,
+        };
+
+        let table = get_table(&[(lon, 30.0)], None);
+        let other = get_table(&[(other_lon, 30.0)], None);
+        let matches = cross_match(table, other, &cfg);
+        assert_eq!(matches.rows, [0]);
+        assert!((matches.separations[0] - 0.01).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,201 @@
+//! Color composites of three grayscale layers
+//!
+//! Each channel is first rendered offscreen with a linear grayscale mapping
+//! between its cuts. A screen pass then combines the three renderings and
+//! applies the asinh stretch of Lupton et al. (2004) on their total intensity.
+//! Unlike the additive blending of colored layers, the hue of the bright
+//! sources is kept when they saturate.
+use std::collections::HashMap;
+
+use al_api::blend::BlendCfg;
+use al_api::composite::{ChannelCfg, RGBCompositeCfg};
+use al_api::hips::{ImageMetadata, TransferFunction};
+use al_core::image::format::RGBA32F;
+use al_core::webgl_ctx::GlWrapper;
+use al_core::{FrameBufferObject, VertexArrayObject, WebGlContext};
+use wasm_bindgen::JsValue;
+use web_sys::WebGl2RenderingContext;
+
+use crate::camera::CameraViewPort;
+use crate::shader::ShaderManager;
+
+// The channels are rendered at the size of the screen and sampled one texel per pixel
+const CHANNEL_TEX_PARAMS: &[(u32, u32)] = &[
+    (
+        WebGl2RenderingContext::TEXTURE_MIN_FILTER,
+        WebGl2RenderingContext::NEAREST,
+    ),
+    (
+        WebGl2RenderingContext::TEXTURE_MAG_FILTER,
+        WebGl2RenderingContext::NEAREST,
+    ),
+    // Prevents s-coordinate wrapping (repeating)
+    (
+        WebGl2RenderingContext::TEXTURE_WRAP_S,
+        WebGl2RenderingContext::CLAMP_TO_EDGE,
+    ),
+    // Prevents t-coordinate wrapping (repeating)
+    (
+        WebGl2RenderingContext::TEXTURE_WRAP_T,
+        WebGl2RenderingContext::CLAMP_TO_EDGE,
+    ),
+];
+
+pub struct RGBComposite {
+    cfg: RGBCompositeCfg,
+    // The offscreen renderings of the red, green and blue channels.
+    // They are (re)created at the size of the screen when drawing. Float targets
+    // keep the precision of the channels before the stretch, which expands the
+    // faint values
+    fbos: Vec<FrameBufferObject>,
+
+    gl: WebGlContext,
+}
+
+impl RGBComposite {
+    pub fn new(gl: &WebGlContext, cfg: RGBCompositeCfg) -> Self {
+        Self {
+            cfg,
+            fbos: vec![],
+            gl: gl.clone(),
+        }
+    }
+
+    pub fn get_cfg(&self) -> &RGBCompositeCfg {
+        &self.cfg
+    }
+
+    pub fn set_cfg(&mut self, cfg: RGBCompositeCfg) {
+        self.cfg = cfg;
+    }
+
+    /// The layers used as the red, green and blue channels
+    pub fn channel_layers(&self) -> [&str; 3] {
+        self.cfg.channels().map(|c| c.layer.as_str())
+    }
+
+    // The metadata a channel is rendered offscreen with
+    fn channel_meta(channel: &ChannelCfg, source: &ImageMetadata) -> ImageMetadata {
+        let mut meta = source.clone();
+
+        let color = &mut meta.color;
+        color.stretch = TransferFunction::Linear;
+        color.min_cut = channel.min_cut.or(source.color.min_cut);
+        color.max_cut = channel.max_cut.or(source.color.max_cut);
+        color.reversed = false;
+        color.cmap_name = "grayscale".into();
+        color.k_gamma = 1.0;
+        color.k_saturation = 0.0;
+        color.k_contrast = 0.0;
+        color.k_brightness = 0.0;
+
+        meta.opacity = 1.0;
+        meta.blend_cfg = BlendCfg::default();
+
+        meta
+    }
+
+    /// Draw the composite
+    ///
+    /// # Arguments
+    ///
+    /// * `draw_channel` - Draws a source layer with the given metadata
+    /// * `sources` - The metadata of the layers
+    /// * `meta` - The metadata of the composite layer (opacity, blending)
+    pub fn draw<F>(
+        &mut self,
+        mut draw_channel: F,
+        sources: &HashMap<String, ImageMetadata>,
+        shaders: &mut ShaderManager,
+        camera: &CameraViewPort,
+        screen_vao: &VertexArrayObject,
+        meta: &ImageMetadata,
+    ) -> Result<(), JsValue>
+    where
+        F: FnMut(&str, &ImageMetadata, &mut ShaderManager) -> Result<(), JsValue>,
+    {
+        let size = camera.get_screen_size();
+        let (width, height) = (size.x as usize, size.y as usize);
+
+        let fbo_size = self
+            .fbos
+            .first()
+            .map(|fbo| (fbo.texture.width() as usize, fbo.texture.height() as usize));
+        if fbo_size != Some((width, height)) {
+            self.fbos = (0..3)
+                .map(|_| {
+                    FrameBufferObject::new_with_format::<RGBA32F>(
+                        &self.gl,
+                        width,
+                        height,
+                        CHANNEL_TEX_PARAMS,
+                    )
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+        }
+
+        // 1. Render the channels offscreen
+        let rendered = self
+            .cfg
+            .channels()
+            .iter()
+            .zip(self.fbos.iter())
+            .try_for_each(|(channel, fbo)| {
+                let source = sources.get(&channel.layer).ok_or_else(|| {
+                    JsValue::from_str(&format!("Layer {} not found", channel.layer))
+                })?;
+
+                fbo.bind();
+                // The pixels not covered by the source remain transparent
+                self.gl.clear_bufferfv_with_f32_array(
+                    WebGl2RenderingContext::COLOR,
+                    0,
+                    &[0.0, 0.0, 0.0, 0.0],
+                );
+
+                draw_channel(
+                    channel.layer.as_str(),
+                    &Self::channel_meta(channel, source),
+                    shaders,
+                )
+            });
+
+        // Restore the rendering onto the screen
+        self.gl
+            .bind_framebuffer(WebGl2RenderingContext::FRAMEBUFFER, None);
+        self.gl.viewport(0, 0, width as i32, height as i32);
+
+        rendered?;
+
+        // 2. Combine the channels
+        let scales = self.cfg.channels().map(|c| c.scale);
+        let shader = crate::shader::get_shader(
+            &self.gl,
+            shaders,
+            "composite_lupton.vert",
+            "composite_lupton.frag",
+        )?;
+
+        self.gl.enable(WebGl2RenderingContext::BLEND);
+        meta.blend_cfg.enable(&self.gl, || {
+            shader
+                .bind(&self.gl)
+                .attach_uniform("tex_r", &self.fbos[0].texture)
+                .attach_uniform("tex_g", &self.fbos[1].texture)
+                .attach_uniform("tex_b", &self.fbos[2].texture)
+                .attach_uniform("scales", &scales)
+                .attach_uniform("Q", &self.cfg.q)
+                .attach_uniform("stretch", &self.cfg.stretch)
+                .attach_uniform("opacity", &meta.opacity)
+                .bind_vertex_array_object_ref(screen_vao)
+                .draw_elements_with_i32(
+                    WebGl2RenderingContext::TRIANGLES,
+                    None,
+                    WebGl2RenderingContext::UNSIGNED_SHORT,
+                    0,
+                );
+
+            Ok(())
+        })
+    }
+}
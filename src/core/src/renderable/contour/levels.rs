@@ -0,0 +1,151 @@
+use al_api::contour::Levels;
+
+// Scale factor between the median absolute deviation and the standard
+// deviation of a normal distribution
+const MAD_TO_SIGMA: f64 = 1.4826;
+
+fn median(sorted: &[f64]) -> f64 {
+    let n = sorted.len();
+    if n % 2 == 1 {
+        sorted[n / 2]
+    } else {
+        (sorted[n / 2 - 1] + sorted[n / 2]) * 0.5
+    }
+}
+
+fn linspace(min: f64, max: f64, n: usize) -> Vec<f64> {
+    match n {
+        0 => vec![],
+        1 => vec![min],
+        _ => (0..n)
+            .map(|i| min + (max - min) * (i as f64) / ((n - 1) as f64))
+            .collect(),
+    }
+}
+
+/// Compute the values of the contour levels from the data they are traced on
+///
+/// Undefined values are ignored. The levels returned are sorted.
+pub fn compute(levels: &Levels, values: &[f64]) -> Vec<f64> {
+    let mut data = values
+        .iter()
+        .filter(|v| v.is_finite())
+        .copied()
+        .collect::<Vec<_>>();
+    data.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    let mut levels = match levels {
+        Levels::Explicit { values } => values.clone(),
+        Levels::Linear { n, min, max } => {
+            if let (Some(min), Some(max)) = (
+                min.or_else(|| data.first().copied()),
+                max.or_else(|| data.last().copied()),
+            ) {
+                linspace(min, max, *n)
+            } else {
+                vec![]
+            }
+        }
+        Levels::Log { n, min, max } => {
+            let min = min.or_else(|| data.iter().find(|v| **v > 0.0).copied());
+            let max = max.or_else(|| data.last().copied());
+
+            match (min, max) {
+                (Some(min), Some(max)) if min > 0.0 && max > 0.0 => {
+                    linspace(min.ln(), max.ln(), *n)
+                        .into_iter()
+                        .map(f64::exp)
+                        .collect()
+                }
+                _ => vec![],
+            }
+        }
+        Levels::Sigma { values } => {
+            if data.is_empty() {
+                vec![]
+            } else {
+                // Robust estimation of the background and its noise
+                let background = median(&data);
+                let mut deviations = data
+                    .iter()
+                    .map(|v| (v - background).abs())
+                    .collect::<Vec<_>>();
+                deviations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                let sigma = MAD_TO_SIGMA * median(&deviations);
+
+                values.iter().map(|n| background + n * sigma).collect()
+            }
+        }
+    };
+
+    levels.retain(|l| l.is_finite());
+    levels.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    levels.dedup();
+
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::compute;
+    use al_api::contour::Levels;
+
+    #[test]
+    fn linear_and_log_levels() {
+        let data = [f64::NAN, 1.0, 100.0, 10.0, -5.0];
+
+        let levels = compute(
+            &Levels::Linear {
+                n: 3,
+                min: None,
+                max: None,
+            },
+            &data,
+        );
+        assert_eq!(levels, vec![-5.0, 47.5, 100.0]);
+
+        let levels = compute(
+            &Levels::Log {
+                n: 3,
+                min: None,
+                max: None,
+            },
+            &data,
+        );
+        assert_eq!(levels.len(), 3);
+        assert!((levels[0] - 1.0).abs() < 1e-9);
+        assert!((levels[1] - 10.0).abs() < 1e-9);
+        assert!((levels[2] - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sigma_levels() {
+        // A background alternating between 9 and 11 with a bright source
+        let mut data = vec![];
+        for i in 0..100 {
+            data.push(if i % 2 == 0 { 9.0 } else { 11.0 });
+        }
+        data.push(1000.0);
+
+        let levels = compute(
+            &Levels::Sigma {
+                values: vec![5.0, 3.0],
+            },
+            &data,
+        );
+        assert_eq!(levels.len(), 2);
+        assert!((levels[0] - (11.0 + 3.0 * 2.0 * 1.4826)).abs() < 1e-9);
+        assert!((levels[1] - (11.0 + 5.0 * 2.0 * 1.4826)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn explicit_levels_are_sorted() {
+        let levels = compute(
+            &Levels::Explicit {
+                values: vec![3.0, 1.0, 3.0, f64::NAN],
+            },
+            &[],
+        );
+        assert_eq!(levels, vec![1.0, 3.0]);
+    }
+}
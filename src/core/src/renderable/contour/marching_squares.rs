@@ -0,0 +1,227 @@
+//! Isolines extraction from a regular grid of values
+//!
+//! A node `(x, y)` of a grid of `width` columns is stored at `y * width + x`.
+//! Cells having an undefined (NaN) corner are skipped so that holes in the
+//! data do not produce spurious contours.
+use std::collections::HashMap;
+
+/// A contour line expressed in grid coordinates
+#[derive(Debug, Clone, PartialEq)]
+pub struct Isoline {
+    pub points: Vec<[f64; 2]>,
+    /// A closed line has its first point repeated at its end
+    pub closed: bool,
+}
+
+// Edges are identified by the node they start from and their direction:
+// even ids are the horizontal edges (x, y) -> (x + 1, y),
+// odd ids are the vertical edges (x, y) -> (x, y + 1)
+fn horizontal_edge(x: usize, y: usize, width: usize) -> usize {
+    2 * (y * width + x)
+}
+
+fn vertical_edge(x: usize, y: usize, width: usize) -> usize {
+    2 * (y * width + x) + 1
+}
+
+// The location where the level crosses an edge
+fn crossing(values: &[f64], width: usize, edge: usize, level: f64) -> [f64; 2] {
+    let idx = edge / 2;
+    let (x, y) = ((idx % width) as f64, (idx / width) as f64);
+    let (end, dx, dy) = if edge & 1 == 0 {
+        (idx + 1, 1.0, 0.0)
+    } else {
+        (idx + width, 0.0, 1.0)
+    };
+
+    let (va, vb) = (values[idx], values[end]);
+    let t = ((level - va) / (vb - va)).clamp(0.0, 1.0);
+
+    [x + t * dx, y + t * dy]
+}
+
+/// Trace the lines of a grid of values at a given level
+///
+/// # Arguments
+///
+/// * `values` - The grid values, row by row
+/// * `width` - The number of columns of the grid
+/// * `height` - The number of rows of the grid
+/// * `level` - The value of the isolines
+pub fn isolines(values: &[f64], width: usize, height: usize, level: f64) -> Vec<Isoline> {
+    if width < 2 || height < 2 || values.len() < width * height {
+        return vec![];
+    }
+
+    let above = |idx: usize| values[idx] >= level;
+
+    // 1. Compute the segments of each cell
+    let mut segments: Vec<[usize; 2]> = vec![];
+    for y in 0..(height - 1) {
+        for x in 0..(width - 1) {
+            let tl = y * width + x;
+            let tr = tl + 1;
+            let bl = tl + width;
+            let br = bl + 1;
+
+            if [tl, tr, br, bl].iter().any(|idx| values[*idx].is_nan()) {
+                continue;
+            }
+
+            let top = (horizontal_edge(x, y, width), above(tl) != above(tr));
+            let right = (vertical_edge(x + 1, y, width), above(tr) != above(br));
+            let bottom = (horizontal_edge(x, y + 1, width), above(bl) != above(br));
+            let left = (vertical_edge(x, y, width), above(tl) != above(bl));
+
+            let crossed = [top, right, bottom, left]
+                .iter()
+                .filter_map(|(edge, crossed)| if *crossed { Some(*edge) } else { None })
+                .collect::<Vec<_>>();
+
+            match crossed.len() {
+                2 => segments.push([crossed[0], crossed[1]]),
+                4 => {
+                    // Saddle point, the mean value of the cell decides which
+                    // pair of opposite corners is connected
+                    let center = (values[tl] + values[tr] + values[br] + values[bl]) * 0.25;
+                    if above(tl) != (center >= level) {
+                        // The top left and bottom right corners are isolated
+                        segments.push([top.0, left.0]);
+                        segments.push([right.0, bottom.0]);
+                    } else {
+                        // The top right and bottom left corners are isolated
+                        segments.push([top.0, right.0]);
+                        segments.push([bottom.0, left.0]);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
+    // 2. Join the segments sharing an edge into lines
+    let mut segments_by_edge: HashMap<usize, Vec<usize>> = HashMap::new();
+    for (idx, segment) in segments.iter().enumerate() {
+        for edge in segment {
+            segments_by_edge.entry(*edge).or_default().push(idx);
+        }
+    }
+
+    let mut used = vec![false; segments.len()];
+    let walk = |start_edge: usize, start_segment: usize, used: &mut Vec<bool>| {
+        let mut edges = vec![start_edge];
+        let mut edge = start_edge;
+        let mut segment = start_segment;
+
+        loop {
+            used[segment] = true;
+            let [a, b] = segments[segment];
+            edge = if a == edge { b } else { a };
+            edges.push(edge);
+
+            let next = segments_by_edge[&edge].iter().find(|s| !used[**s]).copied();
+            if let Some(next) = next {
+                segment = next;
+            } else {
+                break;
+            }
+        }
+
+        Isoline {
+            closed: edges.len() > 2 && edges[0] == edges[edges.len() - 1],
+            points: edges
+                .into_iter()
+                .map(|edge| crossing(values, width, edge, level))
+                .collect(),
+        }
+    };
+
+    let mut lines = vec![];
+    // Open lines start from an edge belonging to only one segment
+    for (idx, segment) in segments.iter().enumerate() {
+        for edge in segment {
+            if !used[idx] && segments_by_edge[edge].len() == 1 {
+                lines.push(walk(*edge, idx, &mut used));
+            }
+        }
+    }
+    // The remaining segments form closed lines
+    for (idx, segment) in segments.iter().enumerate() {
+        if !used[idx] {
+            lines.push(walk(segment[0], idx, &mut used));
+        }
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::isolines;
+
+    // A cone of height 2 centered on the middle of a 5x5 grid
+    fn peak() -> Vec<f64> {
+        let mut values = vec![];
+        for y in 0..5 {
+            for x in 0..5 {
+                let d = ((x as f64 - 2.0).powi(2) + (y as f64 - 2.0).powi(2)).sqrt();
+                values.push(2.0 - d);
+            }
+        }
+        values
+    }
+
+    #[test]
+    fn closed_line_around_a_peak() {
+        let lines = isolines(&peak(), 5, 5, 0.5);
+        assert_eq!(lines.len(), 1);
+
+        let line = &lines[0];
+        assert!(line.closed);
+        assert_eq!(line.points.first(), line.points.last());
+        // The 12 cells surrounding the 3x3 nodes above the level are crossed
+        assert_eq!(line.points.len(), 13);
+        for p in &line.points {
+            let d = ((p[0] - 2.0).powi(2) + (p[1] - 2.0).powi(2)).sqrt();
+            assert!(d > 1.0 && d < 1.6);
+        }
+    }
+
+    #[test]
+    fn open_line_across_a_ramp() {
+        // The value increases with x
+        let values = (0..12).map(|i| (i % 4) as f64).collect::<Vec<_>>();
+        let lines = isolines(&values, 4, 3, 1.5);
+
+        assert_eq!(lines.len(), 1);
+        assert!(!lines[0].closed);
+        assert_eq!(lines[0].points.len(), 3);
+        assert!(lines[0].points.iter().all(|p| p[0] == 1.5));
+    }
+
+    #[test]
+    fn holes_are_skipped() {
+        let mut values = peak();
+        values[12] = f64::NAN;
+
+        // The 4 cells touching the center are not traced anymore
+        let lines = isolines(&values, 5, 5, 1.5);
+        assert!(lines.is_empty());
+    }
+
+    #[test]
+    fn saddle_cell() {
+        // Two opposite corners above the level, the center is below
+        let values = [1.0, 0.0, 0.0, 1.0];
+        let lines = isolines(&values, 2, 2, 0.6);
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines.iter().all(|l| !l.closed && l.points.len() == 2));
+    }
+
+    #[test]
+    fn no_line_outside_the_range() {
+        assert!(isolines(&peak(), 5, 5, 10.0).is_empty());
+        assert!(isolines(&[], 0, 0, 0.0).is_empty());
+    }
+}
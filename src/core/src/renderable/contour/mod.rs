@@ -0,0 +1,362 @@
+//! Contour overlays extracted from an image or a HiPS layer
+//!
+//! The layer is sampled on a regular grid (the whole image for FITS images,
+//! each tile in view for HiPS) on which marching squares are run. The values
+//! are read from the textures block by block, each block being read back
+//! once per trace. The resulting lines are stored in ICRS so that they do not
+//! have to be traced again when the view moves, except for HiPS whose tiles in
+//! view change.
+pub mod levels;
+pub mod marching_squares;
+
+use al_api::contour::ContourCfg;
+use al_api::coo_system::CooSystem;
+use cgmath::{InnerSpace, Vector3, Vector4};
+use wasm_bindgen::JsValue;
+use wcs::ImgXY;
+
+use crate::camera::CameraViewPort;
+use crate::coo_space::CooSpace;
+use crate::healpix::cell::HEALPixCell;
+use crate::math::angle::Angle;
+use crate::math::lonlat::{LonLat, LonLatT};
+use crate::renderable::image::Image;
+use crate::renderable::line::{PathVertices, RasterizedLineRenderer, Style};
+use crate::renderable::Layers;
+use crate::reproject::{physical_value, Sampler};
+use crate::time::{DeltaTime, Time};
+
+// Maximum number of nodes sampled along a side of an image
+const MAX_IMAGE_GRID_SIZE: u64 = 256;
+// Maximum number of nodes sampled over all the HiPS tiles in view
+const MAX_HIPS_NUM_SAMPLES: usize = 1 << 16;
+// Maximum number of segments sampled along a side of a HiPS tile
+const MAX_TILE_GRID_SEGMENTS: u32 = 64;
+// Contours of HiPS are not traced more often than that while tiles arrive
+const HIPS_UPDATE_PERIOD: DeltaTime = DeltaTime::from_millis(500.0);
+
+/// A grid of values sampled on the sky
+struct SampledGrid {
+    width: usize,
+    height: usize,
+    values: Vec<f64>,
+    // Positions of the nodes on the unit sphere, in ICRS
+    positions: Vec<Vector3<f64>>,
+}
+
+impl SampledGrid {
+    // Sample a HiPS tile with `n_segments + 1` nodes along each side
+    fn from_hpx_cell<S: Sampler + ?Sized>(
+        cell: &HEALPixCell,
+        n_segments: u32,
+        sampler: &S,
+    ) -> Self {
+        let frame = sampler.frame();
+        let mut pixel = vec![0.0; sampler.num_channels()];
+
+        let (values, positions) = cell
+            .grid(n_segments)
+            .iter()
+            .map(|(lon, lat)| {
+                let lonlat = LonLatT::new(Angle(*lon), Angle(*lat));
+                let value = physical_value(sampler, &lonlat, &mut pixel);
+
+                let pos: Vector4<f64> = lonlat.vector();
+                let pos = crate::coosys::apply_coo_system(frame, CooSystem::ICRS, &pos);
+
+                (value, pos.truncate())
+            })
+            .unzip();
+
+        let size = (n_segments + 1) as usize;
+        Self {
+            width: size,
+            height: size,
+            values,
+            positions,
+        }
+    }
+
+    // Sample an image at its pixel centers, one every `step` pixels. The centers lie at
+    // integer positions as in the reprojection
+    fn from_image(image: &Image) -> Self {
+        let wcs = image.get_wcs();
+        let (width, height) = wcs.img_dimensions();
+
+        let step = width.max(height).div_ceil(MAX_IMAGE_GRID_SIZE).max(1);
+        let (grid_width, grid_height) = (width.div_ceil(step), height.div_ceil(step));
+
+        let sampler = image.sampler();
+        let mut pixel = vec![0.0; sampler.num_channels()];
+        let mut values = Vec::with_capacity((grid_width * grid_height) as usize);
+        let mut positions = Vec::with_capacity((grid_width * grid_height) as usize);
+        for y in 0..grid_height {
+            for x in 0..grid_width {
+                let xy = ImgXY::new((x * step) as f64, (y * step) as f64);
+
+                if let Some(lonlat) = wcs.unproj_lonlat(&xy) {
+                    let lonlat = LonLatT::from(lonlat);
+
+                    values.push(physical_value(&sampler, &lonlat, &mut pixel));
+                    positions.push(lonlat.vector());
+                } else {
+                    // The pixel does not lie on the sky
+                    values.push(f64::NAN);
+                    positions.push(Vector3::new(0.0, 0.0, 0.0));
+                }
+            }
+        }
+
+        Self {
+            width: grid_width as usize,
+            height: grid_height as usize,
+            values,
+            positions,
+        }
+    }
+
+    // The position of a point lying on an edge of the grid
+    fn position(&self, p: &[f64; 2]) -> Vector3<f64> {
+        let (x, y) = (p[0].floor(), p[1].floor());
+        let (dx, dy) = (p[0] - x, p[1] - y);
+
+        let a = (y as usize) * self.width + (x as usize);
+        let (b, t) = if dx > 0.0 {
+            (a + 1, dx)
+        } else if dy > 0.0 {
+            (a + self.width, dy)
+        } else {
+            (a, 0.0)
+        };
+
+        (self.positions[a] * (1.0 - t) + self.positions[b] * t).normalize()
+    }
+
+    fn isolines(&self, level: f64) -> impl Iterator<Item = ContourLine> + '_ {
+        marching_squares::isolines(&self.values, self.width, self.height, level)
+            .into_iter()
+            .map(move |line| ContourLine {
+                level,
+                closed: line.closed,
+                vertices: line
+                    .points
+                    .iter()
+                    .map(|p| {
+                        let lonlat = self.position(p).lonlat();
+                        [lonlat.lon().0 as f32, lonlat.lat().0 as f32]
+                    })
+                    .collect(),
+            })
+    }
+}
+
+/// A contour line
+#[derive(Debug, Clone)]
+pub struct ContourLine {
+    pub level: f64,
+    /// A closed line has its first vertex repeated at its end
+    pub closed: bool,
+    /// ICRS longitudes and latitudes in radians
+    pub vertices: Vec<[f32; 2]>,
+}
+
+struct Contour {
+    cfg: ContourCfg,
+    lines: Vec<ContourLine>,
+
+    // The lines must be traced again
+    dirty: bool,
+    // The lines have been traced from HiPS tiles and depend on the view
+    from_hips: bool,
+    time_last_trace: Time,
+}
+
+impl Contour {
+    fn trace(&mut self, layers: &Layers, camera: &CameraViewPort) -> Result<(), JsValue> {
+        let grids = if let Some(hips) = layers.get_hips_from_layer(&self.cfg.layer) {
+            let config = hips.get_config();
+            let depth = self
+                .cfg
+                .order
+                .unwrap_or_else(|| camera.get_texture_depth())
+                .min(config.get_max_depth_texture());
+            let sampler = hips.sampler_at_depth(depth);
+
+            let cells = camera.get_hpx_cells(depth, config.get_frame());
+            // Share the budget of samples between the tiles. All the tiles have the
+            // same number of segments so that neighbouring tiles share their border nodes
+            let max_nodes_by_side =
+                ((MAX_HIPS_NUM_SAMPLES / cells.len().max(1)) as f64).sqrt() as u32;
+            let max_segments = max_nodes_by_side
+                .saturating_sub(1)
+                .min(MAX_TILE_GRID_SEGMENTS)
+                .min(config.get_tile_size() as u32)
+                .max(2);
+            let n_segments = 1 << (31 - max_segments.leading_zeros());
+
+            self.from_hips = true;
+            cells
+                .iter()
+                .map(|cell| SampledGrid::from_hpx_cell(cell, n_segments, &sampler))
+                .collect::<Vec<_>>()
+        } else if let Some(images) = layers.get_image_from_layer(&self.cfg.layer) {
+            self.from_hips = false;
+            images
+                .iter()
+                .map(SampledGrid::from_image)
+                .collect::<Vec<_>>()
+        } else {
+            return Err(JsValue::from_str(&format!(
+                "Layer {} not found",
+                self.cfg.layer
+            )));
+        };
+
+        let values = grids
+            .iter()
+            .flat_map(|grid| grid.values.iter().copied())
+            .collect::<Vec<_>>();
+        let levels = levels::compute(&self.cfg.levels, &values);
+
+        self.lines = levels
+            .iter()
+            .flat_map(|level| grids.iter().flat_map(move |grid| grid.isolines(*level)))
+            .collect();
+        self.dirty = false;
+        self.time_last_trace = Time::now();
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub struct ContourRenderer {
+    contours: Vec<Contour>,
+}
+
+impl ContourRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a contour overlay and trace its lines
+    ///
+    /// An overlay with the same name is replaced.
+    pub fn push_back(
+        &mut self,
+        cfg: ContourCfg,
+        layers: &Layers,
+        camera: &CameraViewPort,
+    ) -> Result<(), JsValue> {
+        let mut contour = Contour {
+            cfg,
+            lines: vec![],
+            dirty: true,
+            from_hips: false,
+            time_last_trace: Time::now(),
+        };
+        contour.trace(layers, camera)?;
+
+        if let Some(idx) = self.position(&contour.cfg.name) {
+            self.contours[idx] = contour;
+        } else {
+            self.contours.push(contour);
+        }
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<ContourCfg> {
+        self.position(name).map(|idx| self.contours.remove(idx).cfg)
+    }
+
+    pub fn set_cfg(&mut self, cfg: ContourCfg) -> Option<ContourCfg> {
+        if let Some(idx) = self.position(&cfg.name) {
+            let contour = &mut self.contours[idx];
+            // Levels or sources may have changed
+            contour.dirty = true;
+
+            Some(std::mem::replace(&mut contour.cfg, cfg))
+        } else {
+            None
+        }
+    }
+
+    /// Tell that the HiPS tiles in view have changed
+    pub fn invalidate_hips(&mut self) {
+        for contour in self.contours.iter_mut().filter(|c| c.from_hips) {
+            contour.dirty = true;
+        }
+    }
+
+    /// Trace again the lines that are out of date
+    ///
+    /// Returns true if some lines have changed
+    pub fn update(&mut self, layers: &Layers, camera: &CameraViewPort) -> bool {
+        let mut updated = false;
+        for contour in self.contours.iter_mut().filter(|c| c.dirty) {
+            if contour.from_hips && Time::now() - contour.time_last_trace < HIPS_UPDATE_PERIOD {
+                continue;
+            }
+
+            // The layer may have been removed, the last lines are kept
+            if contour.trace(layers, camera).is_ok() {
+                updated = true;
+            } else {
+                contour.dirty = false;
+            }
+        }
+
+        updated
+    }
+
+    /// The lines of a contour overlay as a JS array of
+    /// `{level, closed, vertices: [[ra, dec], ...]}` objects
+    pub fn get_polygons(&self, name: &str) -> Result<JsValue, JsValue> {
+        let contour = self
+            .position(name)
+            .map(|idx| &self.contours[idx])
+            .ok_or_else(|| JsValue::from_str(&format!("Contour {} not found", name)))?;
+
+        let polygons = js_sys::Array::new();
+        for line in &contour.lines {
+            let vertices = line
+                .vertices
+                .iter()
+                .map(|v| {
+                    js_sys::Array::of2(
+                        &JsValue::from_f64((v[0] as f64).to_degrees()),
+                        &JsValue::from_f64((v[1] as f64).to_degrees()),
+                    )
+                })
+                .collect::<js_sys::Array>();
+
+            let polygon = js_sys::Object::new();
+            js_sys::Reflect::set(&polygon, &"level".into(), &JsValue::from_f64(line.level))?;
+            js_sys::Reflect::set(&polygon, &"closed".into(), &JsValue::from_bool(line.closed))?;
+            js_sys::Reflect::set(&polygon, &"vertices".into(), &vertices)?;
+
+            polygons.push(&polygon);
+        }
+
+        Ok(polygons.into())
+    }
+
+    pub fn draw(&self, rasterizer: &mut RasterizedLineRenderer) {
+        for contour in self.contours.iter().filter(|c| c.cfg.show) {
+            rasterizer.add_stroke_paths(
+                contour.lines.iter().map(|line| PathVertices {
+                    vertices: &line.vertices,
+                }),
+                contour.cfg.thickness,
+                &contour.cfg.color,
+                &Style::None,
+                CooSpace::LonLat,
+            );
+        }
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.contours.iter().position(|c| c.cfg.name == name)
+    }
+}
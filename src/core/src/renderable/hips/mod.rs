@@ -29,16 +29,22 @@ use crate::downloader::request::allsky::Allsky;
 use crate::healpix::{cell::HEALPixCell, coverage::HEALPixCoverage};
 use crate::math::lonlat::LonLat;
 use crate::renderable::utils::index_patch::DefaultPatchIndexIter;
-use crate::time::Time;
+use crate::time::{DeltaTime, Time};
 
 use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
 
 // Recursively compute the number of subdivision needed for a cell
 // to not be too much skewed
 
-use crate::survey::buffer::ImageSurveyTextures;
+use crate::survey::buffer::{ImageSurveyTextures, TextureResidency};
 use crate::survey::texture::Texture;
 
+use crate::lru_cache::Cache;
+use crate::survey::texture_layout::TextureLayout;
+use crate::Abort;
+use al_core::image::ImageType;
+
 use raytracing::RayTracer;
 use uv::{TileCorner, TileUVW};
 
@@ -112,6 +118,8 @@ pub struct TextureToDraw<'a, 'b> {
     pub starting_texture: &'a Texture,
     pub ending_texture: &'a Texture,
     pub cell: &'b HEALPixCell,
+    // No texture to blend from, the ending one fades in from transparent
+    pub starting_missing: bool,
 }
 
 impl<'a, 'b> TextureToDraw<'a, 'b> {
@@ -124,6 +132,16 @@ impl<'a, 'b> TextureToDraw<'a, 'b> {
             starting_texture,
             ending_texture,
             cell,
+            starting_missing: false,
+        }
+    }
+
+    fn fade_in(ending_texture: &'a Texture, cell: &'b HEALPixCell) -> TextureToDraw<'a, 'b> {
+        TextureToDraw {
+            starting_texture: ending_texture,
+            ending_texture,
+            cell,
+            starting_missing: true,
         }
     }
 }
@@ -247,10 +265,41 @@ pub struct HiPS {
 
     // A buffer storing the cells in the view
     hpx_cells_in_view: Vec<HEALPixCell>,
+    // The tile cells covering the view, with the delta depth and
+    // the cells in view at the tile depth they have been computed from
+    tile_cells_in_view: (u8, Vec<HEALPixCell>, Vec<HEALPixCell>),
+
+    // The tiles received for a HiPS cube, keyed by their cell and frame.
+    // They are kept on the CPU so that going back and forth
+    // between frames does not download them again
+    cube_tiles: Cache<(HEALPixCell, u32), CubeTile>,
+
+    // The duration of the cross-fade between the parent and child textures
+    blending_duration: DeltaTime,
+    // The textures of the previous image format, drawn beneath until
+    // the textures of the current format cover the view
+    fading_out: Option<Box<HiPS>>,
+    // The time at which the image format has been switched
+    time_format_switched: Time,
 }
 
+type CubeTile = (Arc<Mutex<Option<ImageType>>>, Time);
+// Memory budget of the tiles of a cube kept on the CPU, in bytes
+const CUBE_TILES_CACHE_BUDGET: usize = 128 * 1024 * 1024;
+// Default duration of the cross-fade from a parent texture to a child one
+pub const DEFAULT_BLENDING_DURATION: DeltaTime = DeltaTime::from_millis(200.0);
+// The textures of the previous image format are discarded after that time
+// even if some tiles of the new format are still missing
+const FADING_OUT_TIMEOUT: DeltaTime = DeltaTime::from_millis(5000.0);
+
 impl HiPS {
     pub fn new(config: HiPSConfig, gl: &WebGlContext) -> Result<Self, JsValue> {
+        let textures = ImageSurveyTextures::new(gl, config)?;
+
+        HiPS::from_textures(textures, gl)
+    }
+
+    fn from_textures(textures: ImageSurveyTextures, gl: &WebGlContext) -> Result<Self, JsValue> {
         let mut vao = VertexArrayObject::new(gl);
 
         // layout (location = 0) in vec2 lonlat;
@@ -362,11 +411,15 @@ impl HiPS {
             .unbind();
 
         let num_idx = 0;
-        let textures = ImageSurveyTextures::new(gl, config)?;
 
         let gl = gl.clone();
         let footprint_moc = None;
         let hpx_cells_in_view = vec![];
+        let tile_cells_in_view = (0, vec![], vec![]);
+        let cube_tiles = Cache::new(CUBE_TILES_CACHE_BUDGET);
+        let blending_duration = DEFAULT_BLENDING_DURATION;
+        let fading_out = None;
+        let time_format_switched = Time::now();
         // request the allsky texture
         Ok(HiPS {
             // The image survey texture buffer
@@ -388,6 +441,12 @@ impl HiPS {
 
             footprint_moc,
             hpx_cells_in_view,
+            tile_cells_in_view,
+            cube_tiles,
+
+            blending_duration,
+            fading_out,
+            time_format_switched,
         })
     }
 
@@ -396,44 +455,49 @@ impl HiPS {
         camera: &'a CameraViewPort,
         proj: &ProjectionType,
     ) -> Option<impl Iterator<Item = HEALPixCell> + 'a> {
+        let tile_cells_iter = self
+            .tile_cells_in_view(camera, proj)
+            .into_iter()
+            .filter(move |tile_cell| !self.tile_available(tile_cell));
+
+        Some(tile_cells_iter)
+    }
+
+    // The tile cells covering the view and the footprint of the HiPS
+    //
+    // They are only computed again when the cells in view change
+    pub fn tile_cells_in_view(
+        &mut self,
+        camera: &CameraViewPort,
+        proj: &ProjectionType,
+    ) -> Vec<HEALPixCell> {
         // do not add tiles if the view is already at depth 0
         let mut depth_tile = (camera.get_texture_depth() + self.get_config().delta_depth())
             .min(self.get_config().get_max_depth_tile())
             .max(self.get_config().get_min_depth_tile());
         let dd = self.get_config().delta_depth();
 
-        //let min_depth_tile = self.get_min_depth_tile();
-        //let delta_depth = self.get_config().delta_depth();
-
-        //let min_bound_depth = min_depth_tile.max(delta_depth);
-        // do not ask to query tiles that:
-        // * either do not exist because < to min_depth_tile
-        // * either are part of a base tile already handled i.e. tiles < delta_depth
-        //console_log(depth_tile);
-        //console_log(min_bound_depth);
-
-        //if depth_tile >= min_bound_depth {
-        //let depth_tile = depth_tile.max(min_bound_depth);
         let survey_frame = self.get_config().get_frame();
-        let mut already_considered_tiles = HashSet::new();
 
         // raytracer is rendering and the shader only renders HPX texture cells of depth 0
         if camera.is_raytracing(proj) {
             depth_tile = 0;
         }
 
-        let tile_cells_iter = camera
-            .get_hpx_cells(depth_tile, survey_frame)
-            //.flat_map(move |cell| {
-            //    let texture_cell = cell.get_texture_cell(delta_depth);
-            //    texture_cell.get_tile_cells(delta_depth)
-            //})
-            .into_iter()
+        let cells = camera.get_hpx_cells(depth_tile, survey_frame);
+        let (cached_dd, cached_cells, cached_tile_cells) = &self.tile_cells_in_view;
+        if *cached_dd == dd && *cached_cells == cells {
+            return cached_tile_cells.clone();
+        }
+
+        let mut already_considered_tiles = HashSet::new();
+        let tile_cells = cells
+            .iter()
             .flat_map(move |tile_cell| {
                 let tex_cell = tile_cell.get_texture_cell(dd);
                 tex_cell.get_tile_cells(dd)
             })
-            .filter(move |tile_cell| {
+            .filter(|tile_cell| {
                 if already_considered_tiles.contains(tile_cell) {
                     return false;
                 }
@@ -441,32 +505,16 @@ impl HiPS {
                 already_considered_tiles.insert(*tile_cell);
 
                 if let Some(moc) = self.footprint_moc.as_ref() {
-                    moc.intersects_cell(tile_cell) && !self.update_priority_tile(tile_cell)
+                    moc.intersects_cell(tile_cell)
                 } else {
-                    !self.update_priority_tile(tile_cell)
+                    true
                 }
-            });
-
-        /*if depth_tile >= min_depth_tile + 3 {
-            // Retrieve the grand-grand parent cells but not if it is root ones as it may interfere with already done requests
-            let tile_cells_ancestor_iter =
-                (&tile_cells_iter).map(|tile_cell| tile_cell.ancestor(3));
-
-            tile_cells_iter.chain(tile_cells_ancestor_iter);
-        }*/
+            })
+            .collect::<Vec<_>>();
 
-        /*let tile_cells: HashSet<_> = if let Some(moc) = survey.get_moc() {
-            tile_cells_iter
-                .filter(|tile_cell| moc.intersects_cell(tile_cell))
-                .collect()
-        } else {
-            tile_cells_iter.collect()
-        };*/
+        self.tile_cells_in_view = (dd, cells, tile_cells.clone());
 
-        Some(tile_cells_iter)
-        //} else {
-        //    None
-        //}
+        tile_cells
     }
 
     pub fn contains_tile(&self, cell: &HEALPixCell) -> bool {
@@ -474,9 +522,14 @@ impl HiPS {
     }
 
     pub fn update(&mut self, camera: &mut CameraViewPort, projection: &ProjectionType) {
+        if let Some(prev) = self.fading_out.as_mut() {
+            prev.update(camera, projection);
+        }
+
         let raytracing = camera.is_raytracing(projection);
 
         if raytracing {
+            self.discard_faded_out_textures(raytracing);
             return;
         }
 
@@ -487,6 +540,54 @@ impl HiPS {
         if new_cells_in_view || available_tiles {
             self.recompute_vertices(camera, projection);
         }
+
+        self.discard_faded_out_textures(raytracing);
+    }
+
+    // Drop the textures of the previous image format once the ones
+    // of the current format have faded in over the whole view
+    fn discard_faded_out_textures(&mut self, raytracing: bool) {
+        if self.fading_out.is_none() {
+            return;
+        }
+
+        let now = Time::now();
+        let faded_in = |cell: &HEALPixCell| {
+            let outside_moc = self
+                .footprint_moc
+                .as_ref()
+                .is_some_and(|moc| !moc.intersects_cell(cell));
+
+            outside_moc
+                || (self.textures.contains(cell)
+                    && self
+                        .textures
+                        .get(cell)
+                        .is_some_and(|tex| now - tex.start_time() >= self.blending_duration))
+        };
+
+        let covered = if raytracing {
+            // The raytracer only draws the root textures
+            (0..12)
+                .map(|idx| HEALPixCell(0, idx))
+                .all(|cell| faded_in(&cell))
+        } else {
+            self.hpx_cells_in_view.iter().all(faded_in)
+        };
+
+        if covered || now - self.time_format_switched >= FADING_OUT_TIMEOUT {
+            self.fading_out = None;
+        }
+    }
+
+    /// Set the duration of the cross-fade from a parent texture to a child one
+    pub fn set_blending_duration(&mut self, duration: DeltaTime) {
+        self.blending_duration = duration;
+    }
+
+    /// Tells whether the textures of a previous image format are still drawn
+    pub fn is_fading_out(&self) -> bool {
+        self.fading_out.is_some()
     }
 
     // returns a boolean if the view cells has changed with respect to the last frame
@@ -515,6 +616,8 @@ impl HiPS {
     #[inline]
     pub fn set_moc(&mut self, moc: HEALPixCoverage) {
         self.footprint_moc = Some(moc);
+        // The tiles outside the footprint are not in view anymore
+        self.tile_cells_in_view = (0, vec![], vec![]);
     }
 
     #[inline]
@@ -522,8 +625,54 @@ impl HiPS {
         self.footprint_moc.as_ref()
     }
 
+    /// Switch the image format of the tiles
+    ///
+    /// The textures of the previous format keep being drawn beneath
+    /// the new ones until those have faded in over the view. As they take
+    /// the GPU memory of the HiPS, the textures of the new format are stored
+    /// in a single slice until then.
     pub fn set_img_format(&mut self, ext: ImageExt) -> Result<(), JsValue> {
-        self.textures.set_format(&self.gl, ext)
+        let mut config = self.get_config().clone();
+        config.set_image_fmt(ext)?;
+        let layout = config.get_texture_layout();
+        config.set_texture_layout(TextureLayout {
+            num_slices: 1,
+            ..layout
+        });
+
+        let textures = ImageSurveyTextures::new(&self.gl, config)?;
+        let prev_textures = std::mem::replace(&mut self.textures, textures);
+
+        // If a previous switch is still fading out, keep the textures it shows
+        // rather than the ones of the format that has not covered the view yet
+        if self.fading_out.is_none() {
+            let mut prev = HiPS::from_textures(prev_textures, &self.gl)?;
+            prev.footprint_moc = self.footprint_moc.clone();
+            prev.blending_duration = self.blending_duration;
+
+            self.fading_out = Some(Box::new(prev));
+        }
+        self.time_format_switched = Time::now();
+
+        Ok(())
+    }
+
+    /// Change the number of textures stored on the GPU
+    ///
+    /// Returns true if the textures have been discarded and the base tiles must be
+    /// requested again
+    pub fn set_texture_layout(&mut self, layout: TextureLayout) -> Result<bool, JsValue> {
+        self.textures.set_layout(&self.gl, layout)
+    }
+
+    pub fn get_texture_residency(&self) -> TextureResidency {
+        let mut residency = self.textures.get_residency();
+        // The textures of the previous image format are still allocated
+        if let Some(prev) = &self.fading_out {
+            residency.num_bytes += prev.get_texture_residency().num_bytes;
+        }
+
+        residency
     }
 
     pub fn is_allsky(&self) -> bool {
@@ -568,6 +717,31 @@ impl HiPS {
         }
     }
 
+    /// A CPU sampler of the tiles currently stored in the GPU
+    ///
+    /// Pixels are looked for at the depth of the view and, if not yet
+    /// loaded, in the ancestor tiles.
+    pub fn sampler(&self, camera: &CameraViewPort) -> HiPSSampler<'_> {
+        let depth = camera
+            .get_texture_depth()
+            .min(self.textures.config().get_max_depth_texture());
+
+        self.sampler_at_depth(depth)
+    }
+
+    /// A CPU sampler looking for pixels in the tiles of a given depth
+    pub fn sampler_at_depth(&self, depth: u8) -> HiPSSampler<'_> {
+        // The textures of the tiles are read back whole
+        let texture_size = self.textures.config().get_texture_size();
+        let reader = TextureReader::new(&self.textures.get_texture_array().textures, texture_size);
+
+        HiPSSampler {
+            textures: &self.textures,
+            depth,
+            reader,
+        }
+    }
+
     pub fn recompute_vertices(&mut self, camera: &mut CameraViewPort, projection: &ProjectionType) {
         self.position.clear();
         self.uv_start.clear();
@@ -615,19 +789,11 @@ impl HiPS {
                                     cell,
                                 ))
                             } else {
-                                // no blending here
-                                Some(TextureToDraw::new(
-                                    ending_cell_in_tex,
-                                    ending_cell_in_tex,
-                                    cell,
-                                ))
+                                // no parent, fade in from transparent
+                                Some(TextureToDraw::fade_in(ending_cell_in_tex, cell))
                             }
                         } else {
-                            Some(TextureToDraw::new(
-                                ending_cell_in_tex,
-                                ending_cell_in_tex,
-                                cell,
-                            ))
+                            Some(TextureToDraw::fade_in(ending_cell_in_tex, cell))
                         }
                     } else {
                         None
@@ -647,19 +813,11 @@ impl HiPS {
                                         cell,
                                     ))
                                 } else {
-                                    // no blending
-                                    Some(TextureToDraw::new(
-                                        ending_cell_in_tex,
-                                        ending_cell_in_tex,
-                                        cell,
-                                    ))
+                                    // no grand parent, fade in from transparent
+                                    Some(TextureToDraw::fade_in(ending_cell_in_tex, cell))
                                 }
                             } else {
-                                Some(TextureToDraw::new(
-                                    ending_cell_in_tex,
-                                    ending_cell_in_tex,
-                                    cell,
-                                ))
+                                Some(TextureToDraw::fade_in(ending_cell_in_tex, cell))
                             }
                         } else {
                             unreachable!();
@@ -673,13 +831,14 @@ impl HiPS {
                     cell,
                     starting_texture,
                     ending_texture,
+                    starting_missing,
                 }) = texture_to_draw
                 {
                     let uv_0 = TileUVW::new(cell, starting_texture, cfg);
                     let uv_1 = TileUVW::new(cell, ending_texture, cfg);
                     let start_time = ending_texture.start_time().as_millis();
 
-                    let miss_0 = (false) as i32 as f32;
+                    let miss_0 = starting_missing as i32 as f32;
                     let miss_1 = (false) as i32 as f32;
 
                     let num_subdivision = num_subdivision(cell, camera, projection);
@@ -814,6 +973,49 @@ impl HiPS {
         }
     }
 
+    // Return a boolean to signal if the tile of the current frame is available,
+    // either already in the survey or in the tiles of the cube kept on the CPU
+    pub fn tile_available(&mut self, cell: &HEALPixCell) -> bool {
+        self.update_priority_tile(cell) || self.push_cube_tile(cell)
+    }
+
+    // Push the tile of the current frame from the cube tiles kept on the CPU
+    fn push_cube_tile(&mut self, cell: &HEALPixCell) -> bool {
+        let key = (*cell, self.get_config().get_cube_frame());
+        let (image, time_req) = match self.cube_tiles.get(&key) {
+            Some((image, time_req)) => (image.clone(), *time_req),
+            None => return false,
+        };
+
+        let pushed = match &*image.lock().unwrap_abort() {
+            Some(img) => self.textures.push(cell, img, time_req).is_ok(),
+            None => false,
+        };
+        pushed
+    }
+
+    pub fn add_cube_tile(
+        &mut self,
+        cell: &HEALPixCell,
+        frame: u32,
+        image: Arc<Mutex<Option<ImageType>>>,
+        time_request: Time,
+    ) {
+        // The missing tiles count for their entry so that their number is bounded too
+        let size = image
+            .lock()
+            .unwrap_abort()
+            .as_ref()
+            .map_or(0, |image| image.byte_size())
+            .max(std::mem::size_of::<((HEALPixCell, u32), CubeTile)>());
+        self.cube_tiles
+            .insert((*cell, frame), (image, time_request), size);
+    }
+
+    pub fn contains_cube_tile(&self, cell: &HEALPixCell, frame: u32) -> bool {
+        self.cube_tiles.contains(&(*cell, frame))
+    }
+
     pub fn add_tile<I: Image + Debug>(
         &mut self,
         cell: &HEALPixCell,
@@ -876,6 +1078,12 @@ impl HiPS {
         // Get the colormap from the color
         let cmap = colormaps.get(color.cmap_name.as_ref());
 
+        // The textures of the previous image format are drawn beneath
+        if let Some(prev) = &self.fading_out {
+            prev.draw(shaders, colormaps, camera, raytracer, cfg, proj)?;
+            self.gl.enable(WebGl2RenderingContext::BLEND);
+        }
+
         blend_cfg.enable(&self.gl, || {
             if raytracing {
                 let w2v = c * (*camera.get_w2m());
@@ -897,6 +1105,8 @@ impl HiPS {
                 raytracer.draw(&shader);
             } else {
                 let v2w = (*camera.get_m2w()) * c.transpose();
+                // Avoid dividing by zero in the shader when the fading is disabled
+                let blending_duration = self.blending_duration.as_millis().max(1.0);
 
                 // The rasterizer has a buffer containing:
                 // - The vertices of the HEALPix cells for the most refined survey
@@ -920,6 +1130,7 @@ impl HiPS {
                     .attach_uniforms_from(camera)
                     .attach_uniform("inv_model", &v2w)
                     .attach_uniform("current_time", &utils::get_current_time())
+                    .attach_uniform("blending_duration", &blending_duration)
                     .attach_uniform("opacity", opacity)
                     .attach_uniform("u_proj", proj)
                     .attach_uniforms_from(colormaps)
@@ -940,3 +1151,64 @@ impl HiPS {
         Ok(())
     }
 }
+
+pub struct HiPSSampler<'a> {
+    textures: &'a ImageSurveyTextures,
+    depth: u8,
+    reader: TextureReader<'a>,
+}
+
+use crate::fits_writer::Bitpix;
+use crate::reproject::{Sampler, TextureReader};
+use al_api::coo_system::CooSystem;
+impl<'a> Sampler for HiPSSampler<'a> {
+    fn frame(&self) -> CooSystem {
+        self.textures.config().get_frame()
+    }
+
+    fn bitpix(&self) -> Bitpix {
+        let cfg = self.textures.config();
+        if cfg.tex_storing_fits {
+            cfg.bitpix
+                .and_then(Bitpix::from_value)
+                .unwrap_or(Bitpix::F32)
+        } else {
+            Bitpix::U8
+        }
+    }
+
+    fn num_channels(&self) -> usize {
+        if self.textures.config().is_colored() {
+            3
+        } else {
+            1
+        }
+    }
+
+    fn scaling(&self) -> (f64, f64, Option<i64>) {
+        let cfg = self.textures.config();
+        if cfg.tex_storing_fits {
+            let blank = if cfg.blank.is_nan() {
+                None
+            } else {
+                Some(cfg.blank as i64)
+            };
+
+            (cfg.scale as f64, cfg.offset as f64, blank)
+        } else {
+            (1.0, 0.0, None)
+        }
+    }
+
+    fn sample(&self, lonlat: &LonLatT<f64>, values: &mut [f64]) -> bool {
+        for depth in (0..=self.depth).rev() {
+            if let Ok(pos_tex) = self.textures.get_pixel_position_in_texture(lonlat, depth) {
+                return self
+                    .reader
+                    .read(pos_tex.z as usize, pos_tex.x, pos_tex.y, values);
+            }
+        }
+
+        false
+    }
+}
@@ -1,6 +1,7 @@
 pub mod cuts;
 pub mod grid;
 pub mod subdivide_texture;
+pub mod wcs_keywords;
 
 use std::fmt::Debug;
 use std::marker::Unpin;
@@ -17,6 +18,7 @@ use web_sys::WebGl2RenderingContext;
 
 use fitsrs::hdu::data::stream;
 use wcs::{ImgXY, WCS};
+use wcs_keywords::WCSKeywords;
 
 use al_api::fov::CenteredFoV;
 use al_api::hips::ImageMetadata;
@@ -28,6 +30,9 @@ use al_core::WebGlContext;
 use al_core::{Texture2D, VertexArrayObject};
 
 use crate::camera::CameraViewPort;
+use crate::fits_writer::Header;
+use crate::math::angle::Angle;
+use crate::math::lonlat::LonLat;
 use crate::math::sph_geom::region::Region;
 use crate::Colormaps;
 use crate::ProjectionType;
@@ -48,6 +53,8 @@ pub struct Image {
 
     /// Parameters extracted from the fits
     wcs: WCS,
+    /// The keywords of the WCS, edited when adjusting it
+    keywords: WCSKeywords,
     blank: f32,
     scale: f32,
     offset: f32,
@@ -80,6 +87,7 @@ impl Image {
         gl: &WebGlContext,
         mut reader: R,
         wcs: WCS,
+        keywords: WCSKeywords,
         scale: Option<f32>,
         offset: Option<f32>,
         blank: Option<f32>,
@@ -208,48 +216,8 @@ impl Image {
         };
         let gl = gl.clone();
 
-        // Compute the fov
-        let center = wcs
-            .unproj_lonlat(&ImgXY::new(width as f64 / 2.0, height as f64 / 2.0))
-            .ok_or(JsValue::from_str("(w / 2, h / 2) px cannot be unprojected"))?;
-        let center_xyz = center.to_xyz();
-        let inside = crate::coosys::apply_coo_system(
-            CooSystem::ICRS,
-            coo_sys,
-            &Vector4::new(center_xyz.y(), center_xyz.z(), center_xyz.x(), 1.0),
-        );
-
-        let vertices = [
-            wcs.unproj_lonlat(&ImgXY::new(0.0, 0.0))
-                .ok_or(JsValue::from_str("(0, 0) does not lie in the sky"))?,
-            wcs.unproj_lonlat(&ImgXY::new(width as f64 - 1.0, 0.0))
-                .ok_or(JsValue::from_str("(w - 1, 0) does not lie in the sky"))?,
-            wcs.unproj_lonlat(&ImgXY::new(width as f64 - 1.0, height as f64 - 1.0))
-                .ok_or(JsValue::from_str("(w - 1, h - 1) does not lie in the sky"))?,
-            wcs.unproj_lonlat(&ImgXY::new(0.0, height as f64 - 1.0))
-                .ok_or(JsValue::from_str("(0, h - 1) does not lie in the sky"))?,
-        ]
-        .iter()
-        .map(|lonlat| {
-            let xyz = lonlat.to_xyz();
-
-            crate::coosys::apply_coo_system(
-                CooSystem::ICRS,
-                coo_sys,
-                &Vector4::new(xyz.y(), xyz.z(), xyz.x(), 1.0),
-            )
-        })
-        .collect::<Vec<_>>();
-
-        let reg = Region::from_vertices(&vertices, &inside);
-
-        // ra and dec must be given in ICRS coo system, which is the case because wcs returns
-        // only icrs coo
-        let centered_fov = CenteredFoV {
-            ra: center.lon().to_degrees(),
-            dec: center.lat().to_degrees(),
-            fov: wcs.field_of_view().0,
-        };
+        let reg = sky_region(&wcs, coo_sys)?;
+        let centered_fov = centered_fov(&wcs)?;
 
         let idx_tex = (0..textures.len()).collect();
 
@@ -265,6 +233,7 @@ impl Image {
 
             // Metadata extracted from the fits
             wcs,
+            keywords,
             // CooSystem of the wcs, this should belong to the WCS
             scale,
             offset,
@@ -320,6 +289,7 @@ impl Image {
         // Create a WCS from a specific header unit
         let wcs = WCS::from_fits_header(header)
             .map_err(|e| JsValue::from_str(&format!("WCS parsing error: reason: {}", e)))?;
+        let keywords = fits_wcs_keywords(header);
 
         let data = hdu.get_data_mut();
 
@@ -331,6 +301,7 @@ impl Image {
                     gl,
                     reader,
                     wcs,
+                    keywords,
                     Some(scale),
                     Some(offset),
                     Some(blank),
@@ -345,6 +316,7 @@ impl Image {
                     gl,
                     reader,
                     wcs,
+                    keywords,
                     Some(scale),
                     Some(offset),
                     Some(blank),
@@ -359,6 +331,7 @@ impl Image {
                     gl,
                     reader,
                     wcs,
+                    keywords,
                     Some(scale),
                     Some(offset),
                     Some(blank),
@@ -378,6 +351,7 @@ impl Image {
                     gl,
                     reader,
                     wcs,
+                    keywords,
                     Some(scale),
                     Some(offset),
                     Some(blank),
@@ -392,6 +366,7 @@ impl Image {
                     gl,
                     reader,
                     wcs,
+                    keywords,
                     Some(scale),
                     Some(offset),
                     Some(blank),
@@ -411,6 +386,7 @@ impl Image {
                     gl,
                     reader,
                     wcs,
+                    keywords,
                     Some(scale),
                     Some(offset),
                     Some(blank),
@@ -597,49 +573,10 @@ impl Image {
         if self.coo_sys != camera.get_coo_system() {
             self.coo_sys = camera.get_coo_system();
 
-            let (width, height) = self.wcs.img_dimensions();
-
             // the camera coo system is not sync with the one in which the region
             // has been defined
             // let's redefine the region
-            let center = self
-                .wcs
-                .unproj_lonlat(&ImgXY::new(width as f64 / 2.0, height as f64 / 2.0))
-                .ok_or(JsValue::from_str("(w / 2, h / 2) px cannot be unprojected"))?;
-            let center_xyz = center.to_xyz();
-            let inside = crate::coosys::apply_coo_system(
-                CooSystem::ICRS,
-                self.coo_sys,
-                &Vector4::new(center_xyz.y(), center_xyz.z(), center_xyz.x(), 1.0),
-            );
-
-            let vertices = [
-                self.wcs
-                    .unproj_lonlat(&ImgXY::new(0.0, 0.0))
-                    .ok_or(JsValue::from_str("(0, 0) does not lie in the sky"))?,
-                self.wcs
-                    .unproj_lonlat(&ImgXY::new(width as f64 - 1.0, 0.0))
-                    .ok_or(JsValue::from_str("(w - 1, 0) does not lie in the sky"))?,
-                self.wcs
-                    .unproj_lonlat(&ImgXY::new(width as f64 - 1.0, height as f64 - 1.0))
-                    .ok_or(JsValue::from_str("(w - 1, h - 1) does not lie in the sky"))?,
-                self.wcs
-                    .unproj_lonlat(&ImgXY::new(0.0, height as f64 - 1.0))
-                    .ok_or(JsValue::from_str("(0, h - 1) does not lie in the sky"))?,
-            ]
-            .iter()
-            .map(|lonlat| {
-                let xyz = lonlat.to_xyz();
-
-                crate::coosys::apply_coo_system(
-                    CooSystem::ICRS,
-                    self.coo_sys,
-                    &Vector4::new(xyz.y(), xyz.z(), xyz.x(), 1.0),
-                )
-            })
-            .collect::<Vec<_>>();
-
-            self.reg = Region::from_vertices(&vertices, &inside);
+            self.reg = sky_region(&self.wcs, self.coo_sys)?;
         }
 
         self.recompute_vertices(camera, projection)?;
@@ -735,4 +672,286 @@ impl Image {
     pub fn get_centered_fov(&self) -> &CenteredFoV {
         &self.centered_fov
     }
+
+    #[inline]
+    pub fn get_wcs(&self) -> &WCS {
+        &self.wcs
+    }
+
+    /// The WCS cards of the image, adjustments included
+    pub fn get_wcs_header(&self) -> Header {
+        self.keywords.header()
+    }
+
+    // Convert an ICRS position to the frame of the WCS keywords
+    fn to_wcs_frame(&self, lonlat: &LonLatT<f64>) -> LonLatT<f64> {
+        crate::coosys::apply_coo_system(
+            CooSystem::ICRS,
+            self.keywords.frame(),
+            &lonlat.vector::<Vector4<f64>>(),
+        )
+        .lonlat()
+    }
+
+    fn proj_icrs(&self, lonlat: &LonLatT<f64>) -> Result<ImgXY, JsValue> {
+        let lonlat = wcs::LonLat::new(lonlat.lon().to_radians(), lonlat.lat().to_radians());
+
+        self.wcs
+            .proj_lonlat(&lonlat)
+            .ok_or_else(|| JsValue::from_str("The position cannot be projected into the image"))
+    }
+
+    /// Move the image so that the sky position `from` ends at `to`
+    ///
+    /// Positions are given in ICRS
+    pub fn translate_wcs(&mut self, from: &LonLatT<f64>, to: &LonLatT<f64>) -> Result<(), JsValue> {
+        let (from, to) = (self.to_wcs_frame(from), self.to_wcs_frame(to));
+        self.keywords.translate(&from, &to);
+
+        self.update_wcs()
+    }
+
+    /// Rotate the image around an ICRS position, the angle being counted
+    /// from the north towards the east
+    pub fn rotate_wcs(&mut self, pivot: &LonLatT<f64>, angle: Angle<f64>) -> Result<(), JsValue> {
+        let pivot = self.to_wcs_frame(pivot);
+        self.keywords.rotate(&pivot, angle);
+
+        self.update_wcs()
+    }
+
+    /// Scale the image around an ICRS position
+    pub fn scale_wcs(&mut self, pivot: &LonLatT<f64>, factor: f64) -> Result<(), JsValue> {
+        if factor <= 0.0 || !factor.is_finite() {
+            return Err(JsValue::from_str("The scale factor must be positive"));
+        }
+
+        let crval = crate::coosys::apply_coo_system(
+            self.keywords.frame(),
+            CooSystem::ICRS,
+            &self.keywords.crval().vector::<Vector4<f64>>(),
+        )
+        .lonlat();
+
+        let pivot = self.proj_icrs(pivot)?;
+        let crpix = self.proj_icrs(&crval)?;
+        self.keywords
+            .scale(factor, [pivot.x() - crpix.x(), pivot.y() - crpix.y()]);
+
+        self.update_wcs()
+    }
+
+    // Rebuild the WCS from its keywords and the sky region it covers
+    fn update_wcs(&mut self) -> Result<(), JsValue> {
+        let wcs = self.keywords.wcs()?;
+
+        self.reg = sky_region(&wcs, self.coo_sys)?;
+        self.centered_fov = centered_fov(&wcs)?;
+        self.wcs = wcs;
+
+        Ok(())
+    }
+}
+
+// The polygonal region covered by an image, in the coo system of the view
+fn sky_region(wcs: &WCS, coo_sys: CooSystem) -> Result<Region, JsValue> {
+    let (width, height) = wcs.img_dimensions();
+
+    let center = wcs
+        .unproj_lonlat(&ImgXY::new(width as f64 / 2.0, height as f64 / 2.0))
+        .ok_or(JsValue::from_str("(w / 2, h / 2) px cannot be unprojected"))?;
+    let center_xyz = center.to_xyz();
+    let inside = crate::coosys::apply_coo_system(
+        CooSystem::ICRS,
+        coo_sys,
+        &Vector4::new(center_xyz.y(), center_xyz.z(), center_xyz.x(), 1.0),
+    );
+
+    let vertices = [
+        wcs.unproj_lonlat(&ImgXY::new(0.0, 0.0))
+            .ok_or(JsValue::from_str("(0, 0) does not lie in the sky"))?,
+        wcs.unproj_lonlat(&ImgXY::new(width as f64 - 1.0, 0.0))
+            .ok_or(JsValue::from_str("(w - 1, 0) does not lie in the sky"))?,
+        wcs.unproj_lonlat(&ImgXY::new(width as f64 - 1.0, height as f64 - 1.0))
+            .ok_or(JsValue::from_str("(w - 1, h - 1) does not lie in the sky"))?,
+        wcs.unproj_lonlat(&ImgXY::new(0.0, height as f64 - 1.0))
+            .ok_or(JsValue::from_str("(0, h - 1) does not lie in the sky"))?,
+    ]
+    .iter()
+    .map(|lonlat| {
+        let xyz = lonlat.to_xyz();
+
+        crate::coosys::apply_coo_system(
+            CooSystem::ICRS,
+            coo_sys,
+            &Vector4::new(xyz.y(), xyz.z(), xyz.x(), 1.0),
+        )
+    })
+    .collect::<Vec<_>>();
+
+    Ok(Region::from_vertices(&vertices, &inside))
+}
+
+// The center and size of an image
+fn centered_fov(wcs: &WCS) -> Result<CenteredFoV, JsValue> {
+    let (width, height) = wcs.img_dimensions();
+
+    let center = wcs
+        .unproj_lonlat(&ImgXY::new(width as f64 / 2.0, height as f64 / 2.0))
+        .ok_or(JsValue::from_str("(w / 2, h / 2) px cannot be unprojected"))?;
+
+    // ra and dec must be given in ICRS coo system, which is the case because wcs returns
+    // only icrs coo
+    Ok(CenteredFoV {
+        ra: center.lon().to_degrees(),
+        dec: center.lat().to_degrees(),
+        fov: wcs.field_of_view().0,
+    })
+}
+
+// The cards of a FITS header defining its WCS
+const WCS_CARDS: &[&[u8; 8]] = &[
+    b"NAXIS   ",
+    b"NAXIS1  ",
+    b"NAXIS2  ",
+    b"WCSAXES ",
+    b"CTYPE1  ",
+    b"CTYPE2  ",
+    b"CUNIT1  ",
+    b"CUNIT2  ",
+    b"CRPIX1  ",
+    b"CRPIX2  ",
+    b"CRVAL1  ",
+    b"CRVAL2  ",
+    b"CDELT1  ",
+    b"CDELT2  ",
+    b"CROTA2  ",
+    b"CD1_1   ",
+    b"CD1_2   ",
+    b"CD2_1   ",
+    b"CD2_2   ",
+    b"PC1_1   ",
+    b"PC1_2   ",
+    b"PC2_1   ",
+    b"PC2_2   ",
+    b"LONPOLE ",
+    b"LATPOLE ",
+    b"RADESYS ",
+    b"EQUINOX ",
+];
+
+pub(crate) fn fits_wcs_keywords(
+    header: &fitsrs::hdu::header::Header<extension::image::Image>,
+) -> WCSKeywords {
+    use fitsrs::card::Value as CardValue;
+    use serde_json::Value;
+
+    let keywords = WCS_CARDS
+        .iter()
+        .filter_map(|key| {
+            let value = match header.get(*key)? {
+                CardValue::Float(f) => Value::from(*f),
+                CardValue::Integer(i) => Value::from(*i),
+                CardValue::Logical(b) => Value::from(*b),
+                CardValue::String(s) => Value::from(s.trim()),
+                _ => return None,
+            };
+            let key = String::from_utf8_lossy(&key[..]).trim_end().to_string();
+
+            Some((key, value))
+        })
+        .collect();
+
+    WCSKeywords::new(keywords)
+}
+
+use crate::fits_writer::Bitpix;
+use crate::math::lonlat::LonLatT;
+use crate::reproject::{Sampler, TextureReader};
+
+// The size of the blocks of pixels read back from the textures
+const READ_BLOCK_SIZE: i32 = 512;
+
+impl Image {
+    /// A CPU sampler of the pixels of the image
+    pub fn sampler(&self) -> ImageSampler<'_> {
+        ImageSampler {
+            image: self,
+            reader: TextureReader::new(&self.textures, READ_BLOCK_SIZE),
+        }
+    }
+}
+
+pub struct ImageSampler<'a> {
+    image: &'a Image,
+    reader: TextureReader<'a>,
+}
+
+impl<'a> Sampler for ImageSampler<'a> {
+    fn frame(&self) -> CooSystem {
+        // The wcs returns only icrs coo
+        CooSystem::ICRS
+    }
+
+    fn bitpix(&self) -> Bitpix {
+        match self.image.channel {
+            ChannelType::RGBA8U => Bitpix::U8,
+            #[cfg(feature = "webgl2")]
+            ChannelType::R8UI => Bitpix::U8,
+            #[cfg(feature = "webgl2")]
+            ChannelType::R16I => Bitpix::I16,
+            #[cfg(feature = "webgl2")]
+            ChannelType::R32I => Bitpix::I32,
+            _ => Bitpix::F32,
+        }
+    }
+
+    fn num_channels(&self) -> usize {
+        if self.image.channel == ChannelType::RGBA8U {
+            3
+        } else {
+            1
+        }
+    }
+
+    fn scaling(&self) -> (f64, f64, Option<i64>) {
+        let image = self.image;
+        let blank = if image.blank.is_nan() {
+            None
+        } else {
+            Some(image.blank as i64)
+        };
+
+        (image.scale as f64, image.offset as f64, blank)
+    }
+
+    fn sample(&self, lonlat: &LonLatT<f64>, values: &mut [f64]) -> bool {
+        let image = self.image;
+        let lonlat = wcs::LonLat::new(lonlat.lon().to_radians(), lonlat.lat().to_radians());
+        let xy = if let Some(xy) = image.wcs.proj_lonlat(&lonlat) {
+            xy
+        } else {
+            return false;
+        };
+
+        // The centers of the pixels are at integer positions
+        let (width, height) = image.wcs.img_dimensions();
+        let (x, y) = (xy.x().round(), xy.y().round());
+        if x < 0.0 || y < 0.0 || x >= width as f64 || y >= height as f64 {
+            return false;
+        }
+
+        // Locate the texture chunk containing the pixel, see subdivide_texture::crop_image
+        let (x, y) = (x as u64, y as u64);
+        let (tex_size_x, tex_size_y) = (image.max_tex_size_x as u64, image.max_tex_size_y as u64);
+        let num_texture_y = height / tex_size_y + 1;
+        let idx_tex = (y / tex_size_y + (x / tex_size_x) * num_texture_y) as usize;
+
+        self.reader.read(
+            idx_tex,
+            (x % tex_size_x) as i32,
+            (y % tex_size_y) as i32,
+            values,
+        )
+    }
 }
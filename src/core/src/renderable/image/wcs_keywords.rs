@@ -0,0 +1,356 @@
+//! The WCS keywords of an image, that can be edited to refine its astrometry
+//!
+//! The linear part of the WCS is always stored as a CD matrix. The image is
+//! moved on the sky by rotating its reference point and turning its CD matrix
+//! by the change of direction of the north at that point. This is exact for the
+//! zenithal projections (TAN, SIN, ZEA, ...) whose reference point is the
+//! native pole.
+use cgmath::{InnerSpace, Matrix3, Rad, Vector3};
+use serde_json::{Map, Value};
+use wasm_bindgen::JsValue;
+use wcs::{WCSParams, WCS};
+
+use al_api::coo_system::CooSystem;
+
+use crate::fits_writer::Header;
+use crate::math::angle::Angle;
+use crate::math::lonlat::{radec_to_xyz, xyz_to_radec, LonLatT};
+
+const CD: [&str; 4] = ["CD1_1", "CD1_2", "CD2_1", "CD2_2"];
+const PC: [&str; 4] = ["PC1_1", "PC1_2", "PC2_1", "PC2_2"];
+
+// Order in which the cards are exported, the other ones following
+const CARDS_ORDER: &[&str] = &[
+    "WCSAXES", "CTYPE1", "CTYPE2", "CUNIT1", "CUNIT2", "CRPIX1", "CRPIX2", "CRVAL1", "CRVAL2",
+    "CD1_1", "CD1_2", "CD2_1", "CD2_2", "LONPOLE", "LATPOLE", "RADESYS", "EQUINOX",
+];
+// Cards describing the data and not the WCS
+const DATA_CARDS: &[&str] = &["NAXIS", "NAXIS1", "NAXIS2"];
+
+/// Keywords defining the WCS of an image
+#[derive(Debug, Clone)]
+pub struct WCSKeywords {
+    keywords: Map<String, Value>,
+}
+
+// Unit vectors pointing towards the east and the north at a position
+fn tangent_basis(lon: f64, lat: f64) -> (Vector3<f64>, Vector3<f64>) {
+    let (sin_lon, cos_lon) = lon.sin_cos();
+    let (sin_lat, cos_lat) = lat.sin_cos();
+
+    (
+        Vector3::new(cos_lon, 0.0, -sin_lon),
+        Vector3::new(-sin_lat * sin_lon, cos_lat, -sin_lat * cos_lon),
+    )
+}
+
+impl WCSKeywords {
+    /// Build the keywords from a set of FITS WCS cards, case insensitive
+    ///
+    /// CDELT, CROTA2 and PC cards are converted into a CD matrix
+    pub fn new(keywords: Map<String, Value>) -> Self {
+        let mut keywords = keywords
+            .into_iter()
+            .map(|(k, v)| (k.to_uppercase(), v))
+            .collect::<Map<_, _>>();
+
+        if !CD.iter().any(|k| keywords.contains_key(*k)) {
+            let float = |k: &str| keywords.get(k).and_then(Value::as_f64);
+            let cdelt1 = float("CDELT1").unwrap_or(1.0);
+            let cdelt2 = float("CDELT2").unwrap_or(1.0);
+
+            let cd = if PC.iter().any(|k| keywords.contains_key(*k)) {
+                [
+                    cdelt1 * float("PC1_1").unwrap_or(1.0),
+                    cdelt1 * float("PC1_2").unwrap_or(0.0),
+                    cdelt2 * float("PC2_1").unwrap_or(0.0),
+                    cdelt2 * float("PC2_2").unwrap_or(1.0),
+                ]
+            } else {
+                let (sin, cos) = float("CROTA2").unwrap_or(0.0).to_radians().sin_cos();
+                [cdelt1 * cos, -cdelt2 * sin, cdelt1 * sin, cdelt2 * cos]
+            };
+
+            for key in PC.iter().chain(&["CDELT1", "CDELT2", "CROTA1", "CROTA2"]) {
+                keywords.remove(*key);
+            }
+
+            let mut wcs = Self { keywords };
+            wcs.set_cd(cd);
+
+            wcs
+        } else {
+            Self { keywords }
+        }
+    }
+
+    /// The parameters of the WCS defined by the keywords
+    pub fn params(&self) -> Result<WCSParams, serde_json::Error> {
+        serde_json::from_value(Value::Object(self.keywords.clone()))
+    }
+
+    /// Build the WCS defined by the keywords
+    pub fn wcs(&self) -> Result<WCS, JsValue> {
+        let params = self
+            .params()
+            .map_err(|e| JsValue::from_str(&format!("Invalid WCS keywords: {}", e)))?;
+
+        WCS::new(&params)
+            .map_err(|e| JsValue::from_str(&format!("WCS parsing error: reason: {:?}", e)))
+    }
+
+    /// The frame of the celestial coordinates, given by CTYPE1
+    pub fn frame(&self) -> CooSystem {
+        match self.keywords.get("CTYPE1").and_then(Value::as_str) {
+            Some(ctype) if ctype.starts_with("GLON") => CooSystem::GAL,
+            _ => CooSystem::ICRS,
+        }
+    }
+
+    fn float(&self, key: &str) -> f64 {
+        self.keywords
+            .get(key)
+            .and_then(Value::as_f64)
+            .unwrap_or(0.0)
+    }
+
+    fn set_float(&mut self, key: &str, value: f64) {
+        self.keywords.insert(key.to_string(), value.into());
+    }
+
+    /// The reference point, in the frame of the WCS
+    pub fn crval(&self) -> LonLatT<f64> {
+        LonLatT::new(
+            Angle(self.float("CRVAL1").to_radians()),
+            Angle(self.float("CRVAL2").to_radians()),
+        )
+    }
+
+    fn cd(&self) -> [f64; 4] {
+        CD.map(|k| self.float(k))
+    }
+
+    fn set_cd(&mut self, cd: [f64; 4]) {
+        for (key, value) in CD.iter().zip(cd) {
+            self.set_float(key, value);
+        }
+    }
+
+    // Move the image by a rotation of the sphere
+    fn rotate_sky(&mut self, rot: &Matrix3<f64>) {
+        let crval = self.crval();
+        let (lon, lat) = (crval.lon().0, crval.lat().0);
+        let (_, north) = tangent_basis(lon, lat);
+
+        let pos = rot * radec_to_xyz(crval.lon(), crval.lat());
+        let north = rot * north;
+
+        let (lon, lat) = xyz_to_radec(&pos);
+        let (east_0, north_0) = tangent_basis(lon.0, lat.0);
+        // Angle of the rotated north from the north at the new position,
+        // towards the east
+        let angle = north.dot(east_0).atan2(north.dot(north_0));
+        let (sin, cos) = angle.sin_cos();
+
+        let [cd11, cd12, cd21, cd22] = self.cd();
+        self.set_cd([
+            cos * cd11 + sin * cd21,
+            cos * cd12 + sin * cd22,
+            -sin * cd11 + cos * cd21,
+            -sin * cd12 + cos * cd22,
+        ]);
+
+        self.set_float("CRVAL1", lon.0.to_degrees().rem_euclid(360.0));
+        self.set_float("CRVAL2", lat.0.to_degrees());
+    }
+
+    /// Move the image so that the point at `from` ends at `to`
+    ///
+    /// Positions are given in the frame of the WCS
+    pub fn translate(&mut self, from: &LonLatT<f64>, to: &LonLatT<f64>) {
+        let from = radec_to_xyz(from.lon(), from.lat());
+        let to = radec_to_xyz(to.lon(), to.lat());
+
+        let axis = from.cross(to);
+        let sin = axis.magnitude();
+        if sin < 1e-15 {
+            return;
+        }
+
+        let rot = Matrix3::from_axis_angle(axis / sin, Rad(sin.atan2(from.dot(to))));
+        self.rotate_sky(&rot);
+    }
+
+    /// Rotate the image around a pivot
+    ///
+    /// # Arguments
+    ///
+    /// * `pivot` - The center of the rotation, in the frame of the WCS
+    /// * `angle` - The rotation angle, counted from the north towards the east
+    pub fn rotate(&mut self, pivot: &LonLatT<f64>, angle: Angle<f64>) {
+        let axis = radec_to_xyz(pivot.lon(), pivot.lat());
+
+        let rot = Matrix3::from_axis_angle(axis, Rad(-angle.0));
+        self.rotate_sky(&rot);
+    }
+
+    /// Scale the image around a pivot
+    ///
+    /// # Arguments
+    ///
+    /// * `factor` - The scale factor, the image grows if greater than 1
+    /// * `offset` - The position of the pivot relative to the reference pixel,
+    ///   in pixels
+    pub fn scale(&mut self, factor: f64, offset: [f64; 2]) {
+        // The pivot keeps its intermediate coordinates
+        for (key, d) in ["CRPIX1", "CRPIX2"].iter().zip(offset) {
+            let crpix = self.float(key) + d * (1.0 - 1.0 / factor);
+            self.set_float(key, crpix);
+        }
+
+        self.set_cd(self.cd().map(|c| c * factor));
+    }
+
+    /// The WCS cards of a FITS header
+    pub fn header(&self) -> Header {
+        let mut keys = CARDS_ORDER
+            .iter()
+            .filter(|k| self.keywords.contains_key(**k))
+            .map(|k| k.to_string())
+            .collect::<Vec<_>>();
+        keys.extend(
+            self.keywords
+                .keys()
+                .filter(|k| !CARDS_ORDER.contains(&k.as_str()) && !DATA_CARDS.contains(&k.as_str()))
+                .cloned(),
+        );
+
+        let mut header = Header::new();
+        for key in keys {
+            match &self.keywords[&key] {
+                Value::Bool(b) => header.push(&key, *b, None),
+                Value::Number(n) => {
+                    if let Some(i) = n.as_i64() {
+                        header.push(&key, i, None)
+                    } else if let Some(f) = n.as_f64() {
+                        header.push(&key, f, None)
+                    }
+                }
+                Value::String(s) => header.push(&key, s.as_str(), None),
+                _ => (),
+            }
+        }
+
+        header
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WCSKeywords;
+    use crate::math::angle::Angle;
+    use crate::math::lonlat::LonLatT;
+
+    fn lonlat(lon: f64, lat: f64) -> LonLatT<f64> {
+        LonLatT::new(Angle(lon.to_radians()), Angle(lat.to_radians()))
+    }
+
+    fn keywords(json: serde_json::Value) -> WCSKeywords {
+        match json {
+            serde_json::Value::Object(keywords) => WCSKeywords::new(keywords),
+            _ => unreachable!(),
+        }
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn cdelt_and_crota_to_cd() {
+        let wcs = keywords(serde_json::json!({
+            "ctype1": "RA---TAN",
+            "CDELT1": -2.0,
+            "CDELT2": 1.0,
+            "CROTA2": 90.0,
+        }));
+
+        let [cd11, cd12, cd21, cd22] = wcs.cd();
+        assert_close(cd11, 0.0);
+        assert_close(cd12, -1.0);
+        assert_close(cd21, -2.0);
+        assert_close(cd22, 0.0);
+        assert!(!wcs.keywords.contains_key("CDELT1"));
+        assert!(!wcs.keywords.contains_key("CROTA2"));
+        assert!(wcs.keywords.contains_key("CTYPE1"));
+    }
+
+    #[test]
+    fn translate_and_rotate() {
+        let cards = serde_json::json!({
+            "CRVAL1": 10.0,
+            "CRVAL2": 0.0,
+            "CD1_1": -1.0,
+            "CD1_2": 0.0,
+            "CD2_1": 0.0,
+            "CD2_2": 1.0,
+        });
+
+        // Along the equator, the north keeps its direction
+        let mut wcs = keywords(cards.clone());
+        wcs.translate(&lonlat(0.0, 0.0), &lonlat(20.0, 0.0));
+        assert_close(wcs.float("CRVAL1"), 30.0);
+        assert_close(wcs.float("CRVAL2"), 0.0);
+        assert_close(wcs.cd()[0], -1.0);
+        assert_close(wcs.cd()[3], 1.0);
+
+        // A rotation of 90° around the reference point turns the north towards the east
+        let mut wcs = keywords(cards);
+        wcs.rotate(&lonlat(10.0, 0.0), Angle(90_f64.to_radians()));
+        assert_close(wcs.float("CRVAL1"), 10.0);
+        assert_close(wcs.float("CRVAL2"), 0.0);
+        // The pixels going up, previously going north, now go east
+        let [cd11, cd12, cd21, cd22] = wcs.cd();
+        assert_close(cd11, 0.0);
+        assert_close(cd12, 1.0);
+        assert_close(cd21, 1.0);
+        assert_close(cd22, 0.0);
+    }
+
+    #[test]
+    fn scale_around_a_pivot() {
+        let mut wcs = keywords(serde_json::json!({
+            "CRPIX1": 10.0,
+            "CRPIX2": 10.0,
+            "CD1_1": -0.5,
+            "CD1_2": 0.0,
+            "CD2_1": 0.0,
+            "CD2_2": 0.5,
+        }));
+
+        // The pivot is 10 pixels right of the reference pixel
+        wcs.scale(2.0, [10.0, 0.0]);
+        assert_close(wcs.float("CRPIX1"), 15.0);
+        assert_close(wcs.float("CRPIX2"), 10.0);
+        assert_close(wcs.cd()[0], -1.0);
+        // The pivot keeps its intermediate coordinates
+        assert_close(wcs.cd()[0] * (20.0 - wcs.float("CRPIX1")), -0.5 * 10.0);
+    }
+
+    #[test]
+    fn header_cards() {
+        let wcs = keywords(serde_json::json!({
+            "NAXIS1": 100,
+            "CRVAL1": 10.0,
+            "CTYPE1": "RA---TAN",
+            "RADESYS": "ICRS",
+        }));
+
+        let bytes = wcs.header().to_bytes();
+        let header = std::str::from_utf8(&bytes).unwrap();
+        assert!(header.starts_with("CTYPE1  = 'RA---TAN'"));
+        assert!(header.contains(&format!("CRVAL1  = {:>20}", "1.0E1")));
+        assert!(header.contains("CD2_2   ="));
+        assert!(!header.contains("NAXIS1"));
+    }
+}
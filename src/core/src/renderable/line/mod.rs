@@ -390,7 +390,9 @@ impl RasterizedLineRenderer {
                     )?
                     .bind(&self.gl)
                     .attach_uniform("u_color", &meta.color)
-                    .attach_uniform("u_width", &meta.thickness)
+                    .attach_uniform("u_width", &camera.get_width())
+                    .attach_uniform("u_height", &camera.get_height())
+                    .attach_uniform("u_thickness", &meta.thickness)
                     .bind_vertex_array_object_ref(&self.instanced_line_vaos[idx])
                     .draw_elements_instanced_with_i32(
                         WebGl2RenderingContext::TRIANGLES,
@@ -413,7 +415,9 @@ impl RasterizedLineRenderer {
                     .attach_uniforms_from(camera)
                     .attach_uniform("u_2world", &icrs2world)
                     .attach_uniform("u_color", &meta.color)
-                    .attach_uniform("u_width", &meta.thickness)
+                    .attach_uniform("u_width", &camera.get_width())
+                    .attach_uniform("u_height", &camera.get_height())
+                    .attach_uniform("u_thickness", &meta.thickness)
                     .attach_uniform("u_proj", proj)
                     .bind_vertex_array_object_ref(&self.instanced_line_vaos[idx])
                     .draw_elements_instanced_with_i32(
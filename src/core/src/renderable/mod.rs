@@ -1,4 +1,6 @@
 pub mod catalog;
+pub mod composite;
+pub mod contour;
 pub mod final_pass;
 pub mod grid;
 pub mod hips;
@@ -9,7 +11,9 @@ pub mod shape;
 pub mod text;
 pub mod utils;
 
+use crate::renderable::composite::RGBComposite;
 use crate::renderable::image::Image;
+use crate::reproject::Sampler;
 use crate::tile_fetcher::TileFetcherQueue;
 
 use al_core::image::format::ChannelType;
@@ -19,7 +23,9 @@ pub use hips::HiPS;
 pub use catalog::Manager;
 
 use al_api::color::ColorRGB;
+use al_api::composite::RGBCompositeCfg;
 use al_api::hips::HiPSCfg;
+use al_api::hips::ImageExt;
 use al_api::hips::ImageMetadata;
 use al_api::image::ImageParams;
 
@@ -31,6 +37,7 @@ use al_core::WebGlContext;
 
 use crate::camera::CameraViewPort;
 use crate::shader::ShaderId;
+use crate::time::DeltaTime;
 use crate::Abort;
 use crate::ProjectionType;
 use crate::{shader::ShaderManager, survey::config::HiPSConfig};
@@ -40,7 +47,7 @@ use crate::{shader::ShaderManager, survey::config::HiPSConfig};
 
 use hips::raytracing::RayTracer;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use wasm_bindgen::JsValue;
 use web_sys::WebGl2RenderingContext;
@@ -59,6 +66,8 @@ pub struct Layers {
     surveys: HashMap<CreatorDid, HiPS>,
     images: HashMap<Id, Vec<Image>>, // an url can contain multiple images i.e. a fits file can contain
     // multiple image extensions
+    // Color composites of other layers, indexed by their layer name
+    composites: HashMap<Id, RGBComposite>,
     // The meta data associated with a layer
     meta: HashMap<LayerId, ImageMetadata>,
     // Hashmap between FITS image urls/HiPS creatorDid and layers
@@ -71,6 +80,8 @@ pub struct Layers {
     screen_vao: VertexArrayObject,
 
     background_color: ColorRGB,
+    // The duration of the cross-fade between the textures of the HiPSes
+    blending_duration: DeltaTime,
 
     gl: WebGlContext,
 }
@@ -122,6 +133,7 @@ impl Layers {
     pub fn new(gl: &WebGlContext, projection: &ProjectionType) -> Result<Self, JsValue> {
         let surveys = HashMap::new();
         let images = HashMap::new();
+        let composites = HashMap::new();
         let meta = HashMap::new();
         let ids = HashMap::new();
         let layers = Vec::new();
@@ -153,9 +165,11 @@ impl Layers {
             .unbind();
 
         let background_color = DEFAULT_BACKGROUND_COLOR;
+        let blending_duration = hips::DEFAULT_BLENDING_DURATION;
         Ok(Layers {
             surveys,
             images,
+            composites,
 
             meta,
             ids,
@@ -165,6 +179,7 @@ impl Layers {
 
             background_color,
             screen_vao,
+            blending_duration,
 
             gl,
         })
@@ -262,10 +277,24 @@ impl Layers {
 
         // The first layer must be paint independently of its alpha channel
         self.gl.enable(WebGl2RenderingContext::BLEND);
+        // The channels of a visible composite are only drawn through it
+        let composited_layers = self
+            .layers
+            .iter()
+            .filter(|layer| self.meta[*layer].visible())
+            .filter_map(|layer| self.composites.get(&self.ids[layer]))
+            .flat_map(|composite| composite.channel_layers())
+            .map(String::from)
+            .collect::<HashSet<_>>();
+
         // Pre loop over the layers to see if a HiPS is entirely covering those behind
         // so that we do not have to render those
         let mut idx_start_layer = 0;
         for (idx_layer, layer) in self.layers.iter().enumerate().skip(1) {
+            if composited_layers.contains(layer) {
+                continue;
+            }
+
             let meta = self.meta.get(layer).expect("Meta should be found");
 
             let id = self.ids.get(layer).expect("Url should be found");
@@ -283,6 +312,10 @@ impl Layers {
 
         let rendered_layers = &self.layers[idx_start_layer..];
         for layer in rendered_layers {
+            if composited_layers.contains(layer) {
+                continue;
+            }
+
             let draw_opt = self.meta.get(layer).expect("Meta should be found");
             if draw_opt.visible() {
                 // 1. Update the survey if necessary
@@ -297,6 +330,61 @@ impl Layers {
                     for image in images {
                         image.draw(shaders, colormaps, draw_opt, camera, projection)?;
                     }
+                } else if let Some(composite) = self.composites.get_mut(id) {
+                    // A channel may have been removed or renamed
+                    let channels = composite.channel_layers();
+                    if !channels.iter().all(|l| self.meta.contains_key(*l)) {
+                        continue;
+                    }
+
+                    // 2. Update the channel surveys and draw them through the composite
+                    for channel_layer in channels {
+                        if let Some(survey) = self
+                            .ids
+                            .get(channel_layer)
+                            .and_then(|cdid| self.surveys.get_mut(cdid))
+                        {
+                            survey.update(camera, projection);
+                        }
+                    }
+
+                    let (surveys, images, ids) = (&self.surveys, &mut self.images, &self.ids);
+                    let camera: &CameraViewPort = camera;
+                    composite.draw(
+                        |channel_layer, channel_opt, shaders| {
+                            let id = ids.get(channel_layer).ok_or_else(|| {
+                                JsValue::from_str(&format!("Layer {} not found", channel_layer))
+                            })?;
+
+                            if let Some(survey) = surveys.get(id) {
+                                survey.draw(
+                                    shaders,
+                                    colormaps,
+                                    camera,
+                                    raytracer,
+                                    channel_opt,
+                                    projection,
+                                )?;
+                            } else if let Some(images) = images.get_mut(id) {
+                                for image in images {
+                                    image.draw(
+                                        shaders,
+                                        colormaps,
+                                        channel_opt,
+                                        camera,
+                                        projection,
+                                    )?;
+                                }
+                            }
+
+                            Ok(())
+                        },
+                        &self.meta,
+                        shaders,
+                        camera,
+                        &self.screen_vao,
+                        draw_opt,
+                    )?;
                 }
             }
         }
@@ -360,6 +448,9 @@ impl Layers {
             } else if let Some(_) = self.images.remove(&id) {
                 // A FITS image has been found and removed
                 Ok(id_layer)
+            } else if self.composites.remove(&id).is_some() {
+                // A color composite has been removed, its channels are kept
+                Ok(id_layer)
             } else {
                 Err(JsValue::from_str(&format!(
                     "Url found {:?} is associated to no surveys.",
@@ -428,9 +519,17 @@ impl Layers {
         let HiPSCfg {
             layer,
             properties,
-            meta,
+            mut meta,
         } = hips;
 
+        // Default cuts of the FITS tiles given by the hips_pixel_cut property
+        if meta.img_format == ImageExt::Fits {
+            if let Some((min_cut, max_cut)) = properties.get_cutouts() {
+                meta.color.min_cut = meta.color.min_cut.or(Some(min_cut));
+                meta.color.max_cut = meta.color.max_cut.or(Some(max_cut));
+            }
+        }
+
         let img_ext = meta.img_format;
 
         // 1. Add the layer name
@@ -478,7 +577,8 @@ impl Layers {
             }*/
             camera.register_view_frame(cfg.get_frame(), proj);
 
-            let hips = HiPS::new(cfg, gl)?;
+            let mut hips = HiPS::new(cfg, gl)?;
+            hips.set_blending_duration(self.blending_duration);
             // add the frame to the camera
 
             self.surveys.insert(creator_did.clone(), hips);
@@ -557,6 +657,96 @@ impl Layers {
         Ok(img.as_slice())
     }
 
+    // Check that the channels of a composite are grayscale layers
+    fn check_composite_channels(&self, cfg: &RGBCompositeCfg) -> Result<(), JsValue> {
+        for channel in cfg.channels() {
+            if channel.layer == cfg.layer {
+                return Err(JsValue::from_str(
+                    "A composite cannot be one of its own channels",
+                ));
+            }
+
+            let grayscale = if let Some(hips) = self.get_hips_from_layer(&channel.layer) {
+                hips.get_config().tex_storing_fits
+            } else if let Some(images) = self.get_image_from_layer(&channel.layer) {
+                images
+                    .iter()
+                    .all(|image| image.sampler().num_channels() == 1)
+            } else {
+                return Err(JsValue::from_str(&format!(
+                    "Layer {} not found",
+                    channel.layer
+                )));
+            };
+
+            if !grayscale {
+                return Err(JsValue::from_str(&format!(
+                    "Layer {} is not a grayscale layer",
+                    channel.layer
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn add_rgb_composite(
+        &mut self,
+        cfg: RGBCompositeCfg,
+        camera: &mut CameraViewPort,
+        proj: &ProjectionType,
+        tile_fetcher: &mut TileFetcherQueue,
+    ) -> Result<(), JsValue> {
+        self.check_composite_channels(&cfg)?;
+
+        let layer = cfg.layer.clone();
+        // The composite is drawn with the blending of its red channel
+        let mut meta = self.get_layer_cfg(&cfg.red.layer)?;
+        meta.opacity = cfg.opacity;
+
+        // 1. Add the layer name
+        let layer_already_found = self.layers.iter().any(|l| l == &layer);
+
+        let idx = if layer_already_found {
+            self.remove_layer(&layer, camera, proj, tile_fetcher)?
+        } else {
+            self.layers.len()
+        };
+
+        self.layers.insert(idx, layer.clone());
+
+        // 2. Add the meta information of the layer
+        self.meta.insert(layer.clone(), meta);
+
+        // 3. Add the composite. It is identified by its layer name
+        self.composites
+            .insert(layer.clone(), RGBComposite::new(&self.gl, cfg));
+        self.ids.insert(layer.clone(), layer);
+
+        Ok(())
+    }
+
+    pub fn set_rgb_composite_cfg(&mut self, cfg: RGBCompositeCfg) -> Result<(), JsValue> {
+        self.check_composite_channels(&cfg)?;
+
+        let meta = self
+            .meta
+            .get_mut(&cfg.layer)
+            .ok_or_else(|| JsValue::from_str(&format!("Layer {} not found", cfg.layer)))?;
+        let composite = self
+            .ids
+            .get(&cfg.layer)
+            .and_then(|id| self.composites.get_mut(id))
+            .ok_or_else(|| {
+                JsValue::from_str(&format!("Layer {} is not a composite", cfg.layer))
+            })?;
+
+        meta.opacity = cfg.opacity;
+        composite.set_cfg(cfg);
+
+        Ok(())
+    }
+
     pub fn get_layer_cfg(&self, layer: &str) -> Result<ImageMetadata, JsValue> {
         self.meta
             .get(layer)
@@ -648,6 +838,28 @@ impl Layers {
         self.surveys.values_mut()
     }
 
+    pub fn set_blending_duration(&mut self, duration: DeltaTime) {
+        self.blending_duration = duration;
+
+        for hips in self.surveys.values_mut() {
+            hips.set_blending_duration(duration);
+        }
+    }
+
+    pub fn get_blending_duration(&self) -> DeltaTime {
+        self.blending_duration
+    }
+
+    // Tells whether a HiPS still draws the textures of its previous image format
+    pub fn is_fading_out(&self) -> bool {
+        self.surveys.values().any(|hips| hips.is_fading_out())
+    }
+
+    // Color composites getters
+    pub fn get_composite_from_layer(&self, layer: &str) -> Option<&RGBComposite> {
+        self.ids.get(layer).and_then(|id| self.composites.get(id))
+    }
+
     // Fits images getters
     pub fn get_mut_image_from_layer(&mut self, layer: &str) -> Option<&mut [Image]> {
         if let Some(url) = self.ids.get(layer) {
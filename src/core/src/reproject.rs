@@ -0,0 +1,452 @@
+//! CPU reprojection of a layer onto a user defined WCS
+//!
+//! The pixels of the target grid are deprojected onto the sky and the raw
+//! value of the source layer found at that position is copied (nearest
+//! neighbour). Values are kept at their native bit depth so that the exported
+//! FITS file can be scaled back with its BSCALE/BZERO keywords.
+//!
+//! As for the images, the centers of the pixels are at the integer positions
+//! of the WCS.
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Vector2, Vector4};
+use serde_json::{Map, Value};
+use wasm_bindgen::JsValue;
+use wcs::{ImgXY, WCSParams, WCS};
+
+use al_api::coo_system::CooSystem;
+use al_core::Texture2D;
+
+use crate::camera::CameraViewPort;
+use crate::fits_writer::{self, Bitpix, Header, ImageData};
+use crate::math::lonlat::{LonLat, LonLatT};
+use crate::math::projection::ProjectionType;
+
+/// A source of raw pixel values that can be reprojected
+pub trait Sampler {
+    /// The frame in which the positions given to `sample` are expressed
+    fn frame(&self) -> CooSystem;
+    /// The storage type of the raw values
+    fn bitpix(&self) -> Bitpix;
+    /// 1 for FITS data, 3 for colored (RGB) data
+    fn num_channels(&self) -> usize;
+    /// BSCALE, BZERO and BLANK of the raw values
+    fn scaling(&self) -> (f64, f64, Option<i64>);
+
+    /// Write the raw values of the pixel located at `lonlat`
+    ///
+    /// Returns false if no data is available at that position
+    fn sample(&self, lonlat: &LonLatT<f64>, values: &mut [f64]) -> bool;
+}
+
+// A layer made of several images returns the first pixel found
+impl<S: Sampler> Sampler for [S] {
+    fn frame(&self) -> CooSystem {
+        self.first().map(|s| s.frame()).unwrap_or(CooSystem::ICRS)
+    }
+
+    fn bitpix(&self) -> Bitpix {
+        self.first().map(|s| s.bitpix()).unwrap_or(Bitpix::F32)
+    }
+
+    fn num_channels(&self) -> usize {
+        self.first().map(|s| s.num_channels()).unwrap_or(1)
+    }
+
+    fn scaling(&self) -> (f64, f64, Option<i64>) {
+        self.first()
+            .map(|s| s.scaling())
+            .unwrap_or((1.0, 0.0, None))
+    }
+
+    fn sample(&self, lonlat: &LonLatT<f64>, values: &mut [f64]) -> bool {
+        self.iter().any(|s| s.sample(lonlat, values))
+    }
+}
+
+/// The physical value of the pixel located at `lonlat`
+///
+/// NaN is returned for missing or blank pixels. Colored pixels are
+/// converted to their intensity.
+pub fn physical_value<S: Sampler + ?Sized>(
+    sampler: &S,
+    lonlat: &LonLatT<f64>,
+    pixel: &mut [f64],
+) -> f64 {
+    if !sampler.sample(lonlat, pixel) {
+        return f64::NAN;
+    }
+
+    if pixel.len() > 1 {
+        return pixel.iter().sum::<f64>() / (pixel.len() as f64);
+    }
+
+    let (bscale, bzero, blank) = sampler.scaling();
+    let raw = pixel[0];
+    if matches!(blank, Some(blank) if blank as f64 == raw) {
+        f64::NAN
+    } else {
+        raw * bscale + bzero
+    }
+}
+
+/// The pixels of GPU textures read back to the CPU block by block
+///
+/// A readback is costly, so each block is read once, the first time one of its
+/// pixels is sampled, and kept for the lifetime of the reader.
+pub struct TextureReader<'a> {
+    textures: &'a [Texture2D],
+    block_size: i32,
+    // The values of the blocks indexed by their texture and position, None if they
+    // could not be read
+    blocks: RefCell<HashMap<(usize, i32, i32), Option<Box<[f64]>>>>,
+}
+
+impl<'a> TextureReader<'a> {
+    pub fn new(textures: &'a [Texture2D], block_size: i32) -> Self {
+        Self {
+            textures,
+            block_size,
+            blocks: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Write the channels of the pixel `(x, y)` of a texture
+    ///
+    /// Returns false if the pixel cannot be read or is transparent
+    pub fn read(&self, idx: usize, x: i32, y: i32, values: &mut [f64]) -> bool {
+        let texture = match self.textures.get(idx) {
+            Some(texture) => texture,
+            None => return false,
+        };
+        let (width, height) = texture.get_size();
+        if x < 0 || y < 0 || x >= width as i32 || y >= height as i32 {
+            return false;
+        }
+
+        let size = self.block_size;
+        let (bx, by) = (x / size * size, y / size * size);
+        let (w, h) = (size.min(width as i32 - bx), size.min(height as i32 - by));
+
+        let mut blocks = self.blocks.borrow_mut();
+        let block = blocks.entry((idx, bx, by)).or_insert_with(|| {
+            texture
+                .read_pixels(bx, by, w, h)
+                .ok()
+                .map(Vec::into_boxed_slice)
+        });
+        let block = match block {
+            Some(block) => block,
+            None => return false,
+        };
+
+        let num_channels = block.len() / ((w * h) as usize);
+        let start = (((y - by) * w + (x - bx)) as usize) * num_channels;
+        let pixel = &block[start..(start + num_channels)];
+        if num_channels == 4 && pixel[3] == 0.0 {
+            return false;
+        }
+
+        for (value, &p) in values.iter_mut().zip(pixel) {
+            *value = p;
+        }
+
+        true
+    }
+}
+
+/// The WCS on which a layer is reprojected
+pub struct TargetWCS {
+    /// The keywords defining the WCS, written as is in the FITS header
+    keywords: Map<String, Value>,
+    wcs: WCS,
+    /// The frame of the WCS given by CTYPE1
+    frame: CooSystem,
+}
+
+// Cards computed from the data and not from the WCS keywords
+const RESERVED_KEYWORDS: &[&str] = &[
+    "SIMPLE", "BITPIX", "NAXIS", "NAXIS1", "NAXIS2", "NAXIS3", "BSCALE", "BZERO", "BLANK",
+    "EXTEND", "END",
+];
+
+impl TargetWCS {
+    /// Build the target from a set of FITS WCS keywords
+    /// (CTYPE/CRVAL/CRPIX/CD and NAXIS), case insensitive
+    pub fn new(keywords: Map<String, Value>) -> Result<Self, JsValue> {
+        let keywords = keywords
+            .into_iter()
+            .map(|(k, v)| (k.to_uppercase(), v))
+            .collect::<Map<_, _>>();
+
+        let params: WCSParams = serde_json::from_value(Value::Object(keywords.clone()))
+            .map_err(|e| JsValue::from_str(&format!("Invalid WCS keywords: {}", e)))?;
+        let wcs = WCS::new(&params)
+            .map_err(|e| JsValue::from_str(&format!("WCS parsing error: reason: {:?}", e)))?;
+
+        let frame = match keywords.get("CTYPE1").and_then(|v| v.as_str()) {
+            Some(ctype) if ctype.starts_with("GLON") => CooSystem::GAL,
+            _ => CooSystem::ICRS,
+        };
+
+        Ok(Self {
+            keywords,
+            wcs,
+            frame,
+        })
+    }
+
+    /// The WCS of the current view, one pixel of the output being one pixel of the screen
+    pub fn from_view(
+        camera: &CameraViewPort,
+        projection: &ProjectionType,
+    ) -> Result<Self, JsValue> {
+        let dpi = camera.get_dpi() as f64;
+        let width = (camera.get_width() as f64 / dpi).round();
+        let height = (camera.get_height() as f64 / dpi).round();
+
+        let code = match projection {
+            ProjectionType::Tan(_) => "TAN",
+            ProjectionType::Stg(_) => "STG",
+            ProjectionType::Sin(_) => "SIN",
+            ProjectionType::Zea(_) => "ZEA",
+            ProjectionType::Ait(_) => "AIT",
+            ProjectionType::Mol(_) => "MOL",
+            ProjectionType::Mer(_) => "MER",
+        };
+        let (ctype1, ctype2) = match camera.get_coo_system() {
+            CooSystem::ICRS => ("RA---", "DEC--"),
+            CooSystem::GAL => ("GLON-", "GLAT-"),
+        };
+
+        // The pixel scale is given by the angular distance between the center
+        // of the screen and its right neighbour
+        let center = Vector2::new(width / 2.0, height / 2.0);
+        let scale = projection
+            .screen_to_model_space(&center, camera)
+            .zip(projection.screen_to_model_space(&(center + Vector2::new(1.0, 0.0)), camera))
+            .map(|(p1, p2)| p1.truncate().angle(p2.truncate()).0.to_degrees())
+            .ok_or_else(|| JsValue::from_str("The center of the view is out of projection"))?;
+
+        let lonlat = camera.get_center().lonlat();
+        // The sky is seen from the inside, east being on the left
+        let cdelt1 = if camera.get_longitude_reversed() {
+            scale
+        } else {
+            -scale
+        };
+        let cdelt2 = scale;
+        let rot = camera.get_center_pos_angle().to_radians();
+
+        let keywords = serde_json::json!({
+            "NAXIS": 2,
+            "NAXIS1": width as i64,
+            "NAXIS2": height as i64,
+            "CTYPE1": format!("{}{}", ctype1, code),
+            "CTYPE2": format!("{}{}", ctype2, code),
+            "CRPIX1": width / 2.0,
+            "CRPIX2": height / 2.0,
+            "CRVAL1": lonlat.lon().to_degrees(),
+            "CRVAL2": lonlat.lat().to_degrees(),
+            "CD1_1": cdelt1 * rot.cos(),
+            "CD1_2": -cdelt2 * rot.sin(),
+            "CD2_1": cdelt1 * rot.sin(),
+            "CD2_2": cdelt2 * rot.cos(),
+        });
+
+        match keywords {
+            Value::Object(keywords) => Self::new(keywords),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Width and height of the output in pixels
+    pub fn dimensions(&self) -> (usize, usize) {
+        let (width, height) = self.wcs.img_dimensions();
+        (width as usize, height as usize)
+    }
+
+    /// The WCS cards of the output header
+    pub fn header(&self) -> Header {
+        let mut header = Header::new();
+        for (key, value) in self
+            .keywords
+            .iter()
+            .filter(|(key, _)| !RESERVED_KEYWORDS.contains(&key.as_str()))
+        {
+            match value {
+                Value::Bool(b) => header.push(key, *b, None),
+                Value::Number(n) => {
+                    if let Some(i) = n.as_i64() {
+                        header.push(key, i, None)
+                    } else if let Some(f) = n.as_f64() {
+                        header.push(key, f, None)
+                    }
+                }
+                Value::String(s) => header.push(key, s.as_str(), None),
+                _ => (),
+            }
+        }
+
+        header
+    }
+
+    /// Compute the raw values of the output pixels
+    ///
+    /// Values are stored channel by channel, each channel being stored row by
+    /// row. Pixels where no data has been found are set to NaN.
+    pub fn resample<S: Sampler + ?Sized>(&self, sampler: &S) -> Vec<f64> {
+        let (width, height) = self.dimensions();
+        let num_pixels = width * height;
+        let num_channels = sampler.num_channels();
+
+        let mut data = vec![std::f64::NAN; num_pixels * num_channels];
+        let mut values = vec![std::f64::NAN; num_channels];
+        let frame = sampler.frame();
+
+        for y in 0..height {
+            for x in 0..width {
+                let lonlat =
+                    if let Some(lonlat) = self.wcs.unproj_lonlat(&ImgXY::new(x as f64, y as f64)) {
+                        LonLatT::from(lonlat)
+                    } else {
+                        // (x, y) does not lie in the sky
+                        continue;
+                    };
+
+                let pos: Vector4<f64> = lonlat.vector();
+                let pos = crate::coosys::apply_coo_system(self.frame, frame, &pos);
+
+                if sampler.sample(&pos.lonlat(), &mut values) {
+                    let idx = y * width + x;
+                    for (c, v) in values.iter().enumerate() {
+                        data[c * num_pixels + idx] = *v;
+                    }
+                }
+            }
+        }
+
+        data
+    }
+
+    /// Reproject the layer and serialize the result as a FITS file
+    pub fn to_fits<S: Sampler + ?Sized>(&self, sampler: &S) -> Result<Vec<u8>, JsValue> {
+        let values = self.resample(sampler);
+
+        let (width, height) = self.dimensions();
+        let num_channels = sampler.num_channels();
+        let naxes = if num_channels == 1 {
+            vec![width, height]
+        } else {
+            vec![width, height, num_channels]
+        };
+
+        let (bscale, bzero, blank) = sampler.scaling();
+        fits_writer::write_primary_hdu(
+            &ImageData {
+                bitpix: sampler.bitpix(),
+                naxes: &naxes,
+                values: &values,
+                bscale,
+                bzero,
+                blank,
+            },
+            &self.header(),
+        )
+        .map_err(JsValue::from_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Sampler, TargetWCS};
+    use crate::fits_writer::header::BLOCK_SIZE;
+    use crate::fits_writer::Bitpix;
+    use crate::math::lonlat::LonLatT;
+    use al_api::coo_system::CooSystem;
+
+    // Returns the latitude in degrees for the northern hemisphere only
+    struct Latitude;
+
+    impl Sampler for Latitude {
+        fn frame(&self) -> CooSystem {
+            CooSystem::ICRS
+        }
+
+        fn bitpix(&self) -> Bitpix {
+            Bitpix::F32
+        }
+
+        fn num_channels(&self) -> usize {
+            1
+        }
+
+        fn scaling(&self) -> (f64, f64, Option<i64>) {
+            (1.0, 0.0, None)
+        }
+
+        fn sample(&self, lonlat: &LonLatT<f64>, values: &mut [f64]) -> bool {
+            let lat = lonlat.lat().to_degrees();
+            values[0] = lat;
+            lat >= 0.0
+        }
+    }
+
+    fn target() -> TargetWCS {
+        let keywords = serde_json::json!({
+            "NAXIS": 2,
+            "NAXIS1": 4,
+            "NAXIS2": 5,
+            "CTYPE1": "RA---TAN",
+            "CTYPE2": "DEC--TAN",
+            "CRPIX1": 2.0,
+            "CRPIX2": 3.0,
+            "CRVAL1": 10.0,
+            "CRVAL2": 0.0,
+            "CD1_1": -0.1,
+            "CD1_2": 0.0,
+            "CD2_1": 0.0,
+            "CD2_2": 0.1,
+        });
+
+        match keywords {
+            serde_json::Value::Object(keywords) => TargetWCS::new(keywords).unwrap(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn resample_grid() {
+        let target = target();
+        assert_eq!(target.dimensions(), (4, 5));
+
+        let data = target.resample(&Latitude);
+        assert_eq!(data.len(), 20);
+
+        // The latitude increases with the rows
+        for y in 1..5 {
+            let prev = data[(y - 1) * 4];
+            let cur = data[y * 4];
+            assert!(prev.is_nan() || cur > prev);
+        }
+        // The southern rows have not been sampled
+        assert!(data[0].is_nan());
+        assert!(!data[19].is_nan());
+    }
+
+    #[test]
+    fn export_fits() {
+        let bytes = target().to_fits(&Latitude).unwrap();
+        // header + data
+        assert_eq!(bytes.len(), 2 * BLOCK_SIZE);
+
+        let header = std::str::from_utf8(&bytes[..BLOCK_SIZE]).unwrap();
+        assert!(header.contains("BITPIX  =                  -32"));
+        assert!(header.contains("NAXIS1  =                    4"));
+        assert!(header.contains("CTYPE1  = 'RA---TAN'"));
+        assert!(header.contains("CD2_2   =               1.0E-1"));
+        // NAXIS are not written twice
+        assert_eq!(header.matches("NAXIS1").count(), 1);
+    }
+}
@@ -1,13 +1,10 @@
-use std::cmp::Ordering;
-use std::collections::BinaryHeap;
+use serde::Serialize;
 use std::collections::HashMap;
 
 use al_core::image::format::ChannelType;
 
 use cgmath::Vector3;
 
-use al_api::hips::ImageExt;
-
 use al_core::image::format::ImageFormat;
 #[cfg(feature = "webgl2")]
 use al_core::image::format::{R16I, R32I, R8UI};
@@ -19,8 +16,10 @@ use al_core::Texture2DArray;
 use al_core::WebGlContext;
 
 use super::config::HiPSConfig;
+use super::heap::{HEALPixCellHeap, TextureKey};
 use super::texture::Texture;
 use super::texture::TextureUniforms;
+use super::texture_layout::TextureLayout;
 use crate::downloader::request::allsky::Allsky;
 use crate::healpix::cell::HEALPixCell;
 use crate::healpix::cell::NUM_HPX_TILES_DEPTH_ZERO;
@@ -29,98 +28,19 @@ use crate::time::Time;
 use crate::Abort;
 use crate::JsValue;
 
-#[derive(Clone, Debug)]
-pub struct TextureCellItem {
-    cell: HEALPixCell,
-    time_request: Time,
-}
-
-impl TextureCellItem {
-    fn is_root(&self) -> bool {
-        self.cell.is_root()
-    }
-}
-
-impl PartialEq for TextureCellItem {
-    fn eq(&self, other: &Self) -> bool {
-        self.cell == other.cell
-    }
-}
-impl Eq for TextureCellItem {}
-
-// Ordering based on the time the tile has been requested
-impl PartialOrd for TextureCellItem {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        other.time_request.partial_cmp(&self.time_request)
-    }
-}
-impl Ord for TextureCellItem {
-    fn cmp(&self, other: &Self) -> Ordering {
-        self.partial_cmp(other).unwrap_abort()
-    }
-}
-
-impl From<Texture> for TextureCellItem {
-    fn from(texture: Texture) -> Self {
-        let time_request = texture.time_request();
-        let cell = *texture.cell();
-
-        Self { cell, time_request }
-    }
-}
-impl From<&Texture> for TextureCellItem {
-    fn from(texture: &Texture) -> Self {
-        let time_request = texture.time_request();
-        let cell = *texture.cell();
-
-        Self { cell, time_request }
-    }
-}
-impl From<&mut Texture> for TextureCellItem {
-    fn from(texture: &mut Texture) -> Self {
-        let time_request = texture.time_request();
-        let cell = *texture.cell();
-
-        Self { cell, time_request }
-    }
-}
-
-struct HEALPixCellHeap(BinaryHeap<TextureCellItem>);
-
-impl HEALPixCellHeap {
-    fn with_capacity(cap: usize) -> Self {
-        Self(BinaryHeap::with_capacity(cap))
-    }
-
-    fn push<E: Into<TextureCellItem>>(&mut self, item: E) {
-        let item = item.into();
-        self.0.push(item);
-    }
-
-    fn update_entry<E: Into<TextureCellItem>>(&mut self, item: E) {
-        let item = item.into();
-        self.0 = self
-            .0
-            .drain()
-            // Remove the cell
-            .filter(|texture_node| texture_node.cell != item.cell)
-            // Collect to a new binary heap that does not have cell anymore
-            .collect::<BinaryHeap<_>>();
-
-        self.push(item);
-    }
-
-    fn clear(&mut self) {
-        self.0.clear();
-    }
-
-    fn pop(&mut self) -> Option<TextureCellItem> {
-        self.0.pop()
-    }
-
-    fn len(&self) -> usize {
-        self.0.len()
-    }
+/// Counters about the textures of a HiPS stored on the GPU, exposed to the javascript
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextureResidency {
+    pub num_slices: i32,
+    pub num_textures_by_side_slice: i32,
+    // Number of textures the GPU buffer can store, including the base ones
+    pub num_textures: usize,
+    // Number of textures currently stored, including the base ones
+    pub num_textures_used: usize,
+    pub num_evictions: usize,
+    // GPU memory allocated for the textures in bytes
+    pub num_bytes: usize,
 }
 
 // Fixed sized binary heap
@@ -132,7 +52,10 @@ pub struct ImageSurveyTextures {
     //num_root_textures_available: usize,
     size: usize,
 
-    pub textures: HashMap<HEALPixCell, Texture>,
+    // The textures keyed by their cell and the frame of the cube their tiles belong to,
+    // so that the frames of a cube already viewed stay on the GPU until evicted
+    pub textures: HashMap<TextureKey, Texture>,
+    // The base textures are always stored and switch from frame to frame
     pub base_textures: [Texture; NUM_HPX_TILES_DEPTH_ZERO],
     //pub cutoff_values_tile: Rc<RefCell<HashMap<HEALPixCell, (f32, f32)>>>,
 
@@ -150,13 +73,13 @@ pub struct ImageSurveyTextures {
 }
 
 // Define a set of textures compatible with the HEALPix tile format and size
-fn create_texture_array<F: ImageFormat>(
+fn create_texture_slices<F: ImageFormat>(
     gl: &WebGlContext,
     config: &HiPSConfig,
+    num_slices: i32,
 ) -> Result<Texture2DArray, JsValue> {
     let texture_size = config.get_texture_size();
     let num_textures_by_side_slice = config.num_textures_by_side_slice();
-    let num_slices = config.num_slices();
     Texture2DArray::create_empty::<F>(
         gl,
         texture_size * num_textures_by_side_slice,
@@ -166,6 +89,50 @@ fn create_texture_array<F: ImageFormat>(
     )
 }
 
+fn create_texture_array(
+    gl: &WebGlContext,
+    config: &HiPSConfig,
+    num_slices: i32,
+) -> Result<Texture2DArray, JsValue> {
+    let channel = config.get_format().get_channel();
+
+    match channel {
+        ChannelType::RGBA32F => unimplemented!(),
+        ChannelType::RGB32F => unimplemented!(),
+        ChannelType::RGBA8U => create_texture_slices::<RGBA8U>(gl, config, num_slices),
+        ChannelType::RGB8U => create_texture_slices::<RGB8U>(gl, config, num_slices),
+        ChannelType::R32F => create_texture_slices::<R32F>(gl, config, num_slices),
+        #[cfg(feature = "webgl2")]
+        ChannelType::R8UI => create_texture_slices::<R8UI>(gl, config, num_slices),
+        #[cfg(feature = "webgl2")]
+        ChannelType::R16I => create_texture_slices::<R16I>(gl, config, num_slices),
+        #[cfg(feature = "webgl2")]
+        ChannelType::R32I => create_texture_slices::<R32I>(gl, config, num_slices),
+        #[cfg(feature = "webgl2")]
+        ChannelType::R64F => create_texture_slices::<R64F>(gl, config, num_slices),
+    }
+}
+
+fn create_base_textures(config: &HiPSConfig) -> [Texture; NUM_HPX_TILES_DEPTH_ZERO] {
+    let now = Time::now();
+    let frame = config.get_cube_frame();
+
+    [
+        Texture::new(&HEALPixCell(0, 0), 0, now, frame),
+        Texture::new(&HEALPixCell(0, 1), 1, now, frame),
+        Texture::new(&HEALPixCell(0, 2), 2, now, frame),
+        Texture::new(&HEALPixCell(0, 3), 3, now, frame),
+        Texture::new(&HEALPixCell(0, 4), 4, now, frame),
+        Texture::new(&HEALPixCell(0, 5), 5, now, frame),
+        Texture::new(&HEALPixCell(0, 6), 6, now, frame),
+        Texture::new(&HEALPixCell(0, 7), 7, now, frame),
+        Texture::new(&HEALPixCell(0, 8), 8, now, frame),
+        Texture::new(&HEALPixCell(0, 9), 9, now, frame),
+        Texture::new(&HEALPixCell(0, 10), 10, now, frame),
+        Texture::new(&HEALPixCell(0, 11), 11, now, frame),
+    ]
+}
+
 impl ImageSurveyTextures {
     pub fn new(gl: &WebGlContext, config: HiPSConfig) -> Result<ImageSurveyTextures, JsValue> {
         let size = config.num_textures() - NUM_HPX_TILES_DEPTH_ZERO;
@@ -175,38 +142,8 @@ impl ImageSurveyTextures {
         let heap = HEALPixCellHeap::with_capacity(size);
         let textures = HashMap::with_capacity(size);
 
-        let now = Time::now();
-        let base_textures = [
-            Texture::new(&HEALPixCell(0, 0), 0, now),
-            Texture::new(&HEALPixCell(0, 1), 1, now),
-            Texture::new(&HEALPixCell(0, 2), 2, now),
-            Texture::new(&HEALPixCell(0, 3), 3, now),
-            Texture::new(&HEALPixCell(0, 4), 4, now),
-            Texture::new(&HEALPixCell(0, 5), 5, now),
-            Texture::new(&HEALPixCell(0, 6), 6, now),
-            Texture::new(&HEALPixCell(0, 7), 7, now),
-            Texture::new(&HEALPixCell(0, 8), 8, now),
-            Texture::new(&HEALPixCell(0, 9), 9, now),
-            Texture::new(&HEALPixCell(0, 10), 10, now),
-            Texture::new(&HEALPixCell(0, 11), 11, now),
-        ];
-        let channel = config.get_format().get_channel();
-
-        let texture_2d_array = match channel {
-            ChannelType::RGBA32F => unimplemented!(),
-            ChannelType::RGB32F => unimplemented!(),
-            ChannelType::RGBA8U => create_texture_array::<RGBA8U>(gl, &config)?,
-            ChannelType::RGB8U => create_texture_array::<RGB8U>(gl, &config)?,
-            ChannelType::R32F => create_texture_array::<R32F>(gl, &config)?,
-            #[cfg(feature = "webgl2")]
-            ChannelType::R8UI => create_texture_array::<R8UI>(gl, &config)?,
-            #[cfg(feature = "webgl2")]
-            ChannelType::R16I => create_texture_array::<R16I>(gl, &config)?,
-            #[cfg(feature = "webgl2")]
-            ChannelType::R32I => create_texture_array::<R32I>(gl, &config)?,
-            #[cfg(feature = "webgl2")]
-            ChannelType::R64F => create_texture_array::<R64F>(gl, &config)?,
-        };
+        let base_textures = create_base_textures(&config);
+        let texture_2d_array = create_texture_array(gl, &config, config.num_slices())?;
         // The root textures have not been loaded
         //let ready = false;
         //let num_root_textures_available = 0;
@@ -230,42 +167,62 @@ impl ImageSurveyTextures {
         })
     }
 
-    pub fn set_format(&mut self, gl: &WebGlContext, ext: ImageExt) -> Result<(), JsValue> {
-        self.config.set_image_fmt(ext)?;
-
-        let channel = self.config.get_format().get_channel();
-
-        self.texture_2d_array = match channel {
-            ChannelType::RGBA32F => unimplemented!(),
-            ChannelType::RGB32F => unimplemented!(),
-            ChannelType::RGBA8U => create_texture_array::<RGBA8U>(gl, &self.config)?,
-            ChannelType::RGB8U => create_texture_array::<RGB8U>(gl, &self.config)?,
-            ChannelType::R32F => create_texture_array::<R32F>(gl, &self.config)?,
-            #[cfg(feature = "webgl2")]
-            ChannelType::R8UI => create_texture_array::<R8UI>(gl, &self.config)?,
-            #[cfg(feature = "webgl2")]
-            ChannelType::R16I => create_texture_array::<R16I>(gl, &self.config)?,
-            #[cfg(feature = "webgl2")]
-            ChannelType::R32I => create_texture_array::<R32I>(gl, &self.config)?,
-            #[cfg(feature = "webgl2")]
-            ChannelType::R64F => create_texture_array::<R64F>(gl, &self.config)?,
-        };
-
-        let now = Time::now();
-        self.base_textures = [
-            Texture::new(&HEALPixCell(0, 0), 0, now),
-            Texture::new(&HEALPixCell(0, 1), 1, now),
-            Texture::new(&HEALPixCell(0, 2), 2, now),
-            Texture::new(&HEALPixCell(0, 3), 3, now),
-            Texture::new(&HEALPixCell(0, 4), 4, now),
-            Texture::new(&HEALPixCell(0, 5), 5, now),
-            Texture::new(&HEALPixCell(0, 6), 6, now),
-            Texture::new(&HEALPixCell(0, 7), 7, now),
-            Texture::new(&HEALPixCell(0, 8), 8, now),
-            Texture::new(&HEALPixCell(0, 9), 9, now),
-            Texture::new(&HEALPixCell(0, 10), 10, now),
-            Texture::new(&HEALPixCell(0, 11), 11, now),
-        ];
+    /// Change the number of textures stored on the GPU
+    ///
+    /// Returns true if the textures stored have been discarded
+    pub fn set_layout(
+        &mut self,
+        gl: &WebGlContext,
+        layout: TextureLayout,
+    ) -> Result<bool, JsValue> {
+        let prev_layout = self.config.get_texture_layout();
+        if prev_layout == layout {
+            return Ok(false);
+        }
+
+        self.config.set_texture_layout(layout);
+        let size = self.config.num_textures() - NUM_HPX_TILES_DEPTH_ZERO;
+
+        if prev_layout.num_textures_by_side_slice != layout.num_textures_by_side_slice {
+            // The position of all the textures changes
+            self.size = size;
+            self.reset(gl)?;
+
+            return Ok(true);
+        }
+
+        if layout.num_slices > prev_layout.num_slices {
+            let num_new_slices = layout.num_slices - prev_layout.num_slices;
+            let new_slices = create_texture_array(gl, &self.config, num_new_slices)?;
+            self.texture_2d_array.textures.extend(new_slices.textures);
+        } else {
+            // Evict the textures stored in the slices removed
+            let num_textures = self.config.num_textures() as i32;
+            let evicted_keys = self
+                .textures
+                .iter()
+                .filter(|(_, texture)| texture.idx() >= num_textures)
+                .map(|(key, _)| *key)
+                .collect::<Vec<_>>();
+            for key in evicted_keys {
+                self.heap.remove(&key);
+                self.textures.remove(&key);
+            }
+
+            self.texture_2d_array
+                .textures
+                .truncate(layout.num_slices as usize);
+        }
+        self.size = size;
+
+        Ok(false)
+    }
+
+    // Discard all the textures and create the texture array again
+    fn reset(&mut self, gl: &WebGlContext) -> Result<(), JsValue> {
+        let num_slices = self.config.num_slices();
+        self.texture_2d_array = create_texture_array(gl, &self.config, num_slices)?;
+        self.base_textures = create_base_textures(&self.config);
 
         self.heap.clear();
         self.textures.clear();
@@ -282,9 +239,15 @@ impl ImageSurveyTextures {
             image,
             time_req,
             depth_tile,
+            frame,
             ..
         } = allsky;
 
+        // The frame of the cube has changed while downloading the allsky
+        if frame != self.config.get_cube_frame() {
+            return Ok(());
+        }
+
         {
             let mutex_locked = image.lock().unwrap_abort();
             let images = mutex_locked.as_ref().unwrap_abort();
@@ -315,21 +278,23 @@ impl ImageSurveyTextures {
     ) -> Result<(), JsValue> {
         if !self.contains_tile(cell) {
             let dd = self.config.delta_depth();
+            let frame = self.config.get_cube_frame();
             // Get the texture cell in which the tile has to be
             let tex_cell = cell.get_texture_cell(dd);
+            let key = (tex_cell, frame);
 
             let tex_cell_is_root = tex_cell.is_root();
-            if !tex_cell_is_root && !self.textures.contains_key(&tex_cell) {
+            if !tex_cell_is_root && !self.textures.contains_key(&key) {
                 // The texture is not among the essential ones
                 // (i.e. is not a root texture)
                 let texture = if self.is_heap_full() {
-                    // Pop the oldest requested texture
-                    let oldest_texture = self.heap.pop().unwrap_abort();
+                    // Pop the texture not needed for the longest time
+                    let oldest_texture_key = self.heap.pop().unwrap_abort();
                     // Ensure this is not a base texture
-                    debug_assert!(!oldest_texture.is_root());
+                    debug_assert!(!oldest_texture_key.0.is_root());
 
                     // Remove it from the textures HashMap
-                    let mut texture = self.textures.remove(&oldest_texture.cell).expect(
+                    let mut texture = self.textures.remove(&oldest_texture_key).expect(
                         "Texture (oldest one) has not been found in the buffer of textures",
                     );
                     // Clear and assign it to tex_cell
@@ -339,7 +304,7 @@ impl ImageSurveyTextures {
                     } else {
                         None
                     };*/
-                    texture.replace(&tex_cell, time_request);
+                    texture.replace(&tex_cell, time_request, frame);
 
                     texture
                 } else {
@@ -357,13 +322,13 @@ impl ImageSurveyTextures {
                     //let idx = NUM_HPX_TILES_DEPTH_ZERO + (self.heap.len() - self.num_base_textures);
                     let idx = NUM_HPX_TILES_DEPTH_ZERO + self.heap.len();
 
-                    Texture::new(&tex_cell, idx as i32, time_request)
+                    Texture::new(&tex_cell, idx as i32, time_request, frame)
                 };
 
                 // Push it to the buffer
-                self.heap.push(&texture);
+                self.heap.push(&key, time_request);
 
-                self.textures.insert(tex_cell, texture);
+                self.textures.insert(key, texture);
             }
 
             // At this point, the texture that should contain the tile
@@ -374,13 +339,18 @@ impl ImageSurveyTextures {
 
             let texture = if !tex_cell_is_root {
                 self.textures
-                    .get_mut(&tex_cell)
+                    .get_mut(&key)
                     .expect("the cell has to be in the tile buffer")
             } else {
                 let HEALPixCell(_, idx) = tex_cell;
                 &mut self.base_textures[idx as usize]
             };
 
+            // The base texture still contains tiles of another frame of the cube
+            if texture.frame() != frame {
+                texture.change_frame(frame);
+            }
+
             //let missing = image.is_none();
             send_to_gpu(
                 cell,
@@ -430,7 +400,7 @@ impl ImageSurveyTextures {
         // textures in the buffer
         let num_textures_heap = self.heap.len();
 
-        num_textures_heap == self.size
+        num_textures_heap >= self.size
     }
 
     // Tell if a texture is available meaning all its sub tiles
@@ -446,17 +416,20 @@ impl ImageSurveyTextures {
     // Check whether the buffer has a tile
     // For that purpose, we first need to verify that its
     // texture ancestor exists and then, it it contains the tile
+    // of the current frame
     pub fn contains_tile(&self, cell: &HEALPixCell) -> bool {
         let dd = self.config.delta_depth();
+        let frame = self.config.get_cube_frame();
 
         let texture_cell = cell.get_texture_cell(dd);
 
         let tex_cell_is_root = texture_cell.is_root();
         if tex_cell_is_root {
             let HEALPixCell(_, idx) = texture_cell;
-            self.base_textures[idx as usize].contains(cell)
+            let texture = &self.base_textures[idx as usize];
+            texture.frame() == frame && texture.contains(cell)
         } else {
-            if let Some(texture) = self.get(&texture_cell) {
+            if let Some(texture) = self.textures.get(&(texture_cell, frame)) {
                 // The texture is present in the buffer
                 // We must check whether it contains the tile
                 texture.contains(cell)
@@ -480,25 +453,12 @@ impl ImageSurveyTextures {
             return;
         }
 
-        let texture = self
-            .textures
-            .get_mut(&texture_cell)
-            .expect("Texture cell has not been found while the buffer contains one of its tile!");
-        // Reset the time the tile has been received if it is a new cell present in the fov
-        //if new_fov_cell {
-        //    texture.update_start_time(Time::now());
-        //}
-
-        // MAYBE WE DO NOT NEED TO UPDATE THE TIME REQUEST IN THE BHEAP
-        // BECAUSE IT INTRODUCES UNECESSARY CODE COMPLEXITY
+        let key = (texture_cell, self.config.get_cube_frame());
+        debug_assert!(self.textures.contains_key(&key));
         // Root textures are always in the buffer
-        // But other textures can be removed thanks to the heap
-        // data-structure. We have to update the time_request of the texture
-        // and push it again in the heap to update its position.
-        let mut tex_cell_item: TextureCellItem = texture.into();
-        tex_cell_item.time_request = Time::now();
-
-        self.heap.update_entry(tex_cell_item);
+        // But other textures can be evicted when the buffer is full, the ones
+        // not needed by the view for the longest time first.
+        self.heap.push(&key, Time::now());
     }
 
     // lonlat is given in the
@@ -539,12 +499,13 @@ impl ImageSurveyTextures {
 
             // Offset in the slice in pixels
             if self.config.tex_storing_fits {
-                let mut uvy = offset.y as f32 / 4096.0;
+                let slice_size = (cfg.num_textures_by_side_slice() * texture_size) as f32;
+                let mut uvy = offset.y as f32 / slice_size;
                 uvy = self.config.size_tile_uv
                     + 2.0 * self.config.size_tile_uv * (uvy / self.config.size_tile_uv).floor()
                     - uvy;
 
-                offset.y = (uvy * 4096.0) as i32;
+                offset.y = (uvy * slice_size) as i32;
             }
 
             Ok(offset)
@@ -557,12 +518,16 @@ impl ImageSurveyTextures {
     }
 
     /// Accessors
+    ///
+    /// The textures returned are the ones of the current frame of a cube, except the base
+    /// textures which keep the previous frame until the tiles of the new one replace it
     pub fn get(&self, texture_cell: &HEALPixCell) -> Option<&Texture> {
         if texture_cell.is_root() {
             let HEALPixCell(_, idx) = texture_cell;
             Some(&self.base_textures[*idx as usize])
         } else {
-            self.textures.get(texture_cell)
+            let frame = self.config.get_cube_frame();
+            self.textures.get(&(*texture_cell, frame))
         }
     }
 
@@ -624,6 +589,19 @@ impl ImageSurveyTextures {
     pub fn get_texture_array(&self) -> &Texture2DArray {
         &self.texture_2d_array
     }
+
+    pub fn get_residency(&self) -> TextureResidency {
+        let num_textures = self.config.num_textures();
+
+        TextureResidency {
+            num_slices: self.config.num_slices(),
+            num_textures_by_side_slice: self.config.num_textures_by_side_slice(),
+            num_textures,
+            num_textures_used: NUM_HPX_TILES_DEPTH_ZERO + self.heap.len(),
+            num_evictions: self.heap.num_evictions(),
+            num_bytes: num_textures * self.config.get_texture_byte_size(),
+        }
+    }
 }
 
 fn send_to_gpu<I: Image>(
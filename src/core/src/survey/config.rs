@@ -5,6 +5,15 @@ use al_core::{image::format::ImageFormat, image::raw::ImageBuffer};
 #[derive(Debug)]
 pub struct EmptyTileImage {
     inner: ImageType,
+    size: i32,
+    channel: ChannelType,
+}
+
+// The image is allocated again as the image types are not all clonable
+impl Clone for EmptyTileImage {
+    fn clone(&self) -> Self {
+        EmptyTileImage::new(self.size, self.channel)
+    }
 }
 
 use al_core::{image::ImageType, pixel::Pixel};
@@ -75,6 +84,8 @@ impl EmptyTileImage {
         };
         EmptyTileImage {
             inner,
+            size,
+            channel,
             //pixel_fill,
         }
     }
@@ -130,9 +141,10 @@ fn create_black_tile(format: FormatImageType, width: i32, value: f32) -> TileArr
     }
 }*/
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HiPSConfig {
-    pub root_url: String,
+    // The mirrors serving the HiPS, the root url being the one currently used
+    mirrors: Mirrors,
     // HiPS image format
     // TODO: Make that independant of the HiPS but of the ImageFormat
     pub empty_image: EmptyTileImage,
@@ -150,7 +162,8 @@ pub struct HiPSConfig {
     // Max depth of the current HiPS tiles
     max_depth_texture: u8,
     max_depth_tile: u8,
-    num_textures: usize,
+    // The arrangement of the textures on the GPU
+    texture_layout: TextureLayout,
 
     pub is_allsky: bool,
 
@@ -173,17 +186,22 @@ pub struct HiPSConfig {
     //dataproduct_subtype: Option<Vec<String>>,
     //colored: bool,
     pub creator_did: String,
+
+    // The number of frames of a HiPS cube
+    cube_depth: Option<u32>,
+    // The frame of the cube currently displayed
+    cube_frame: u32,
+    properties: HiPSProperties,
 }
 
+use super::mirror::{MirrorSwitch, Mirrors, SwitchReason};
+use super::texture_layout::TextureLayout;
+use crate::downloader::request::Failure;
 use crate::math;
 use crate::HiPSProperties;
 use al_api::coo_system::CooSystem;
 use wasm_bindgen::JsValue;
 
-const NUM_TEXTURES_BY_SIDE_SLICE: i32 = 8;
-const NUM_TEXTURES_BY_SLICE: i32 = NUM_TEXTURES_BY_SIDE_SLICE * NUM_TEXTURES_BY_SIDE_SLICE;
-const NUM_SLICES: i32 = 1;
-
 impl HiPSConfig {
     /// Define a HiPS configuration
     ///
@@ -194,9 +212,8 @@ impl HiPSConfig {
     pub fn new(properties: &HiPSProperties, img_ext: ImageExt) -> Result<HiPSConfig, JsValue> {
         let root_url = properties.get_url();
         let creator_did = properties.get_creator_did().to_string();
-        // Define the size of the 2d texture array depending on the
-        // characterics of the client
-        let num_textures = (NUM_TEXTURES_BY_SLICE * NUM_SLICES) as usize;
+        // The size of the 2d texture array is adapted to the view once the HiPS is added
+        let texture_layout = TextureLayout::default();
 
         let max_depth_tile = properties.get_max_order();
         let tile_size = properties.get_tile_size();
@@ -295,7 +312,8 @@ impl HiPSConfig {
         let num_tiles_per_texture = num_tile_per_side_texture * num_tile_per_side_texture;
 
         let max_depth_texture = max_depth_tile - delta_depth;
-        let size_tile_uv = 1_f32 / ((8 << delta_depth) as f32);
+        let size_tile_uv =
+            1_f32 / ((texture_layout.num_textures_by_side_slice << delta_depth) as f32);
 
         let frame = properties.get_frame();
         let sky_fraction = properties.get_sky_fraction().unwrap_or(1.0);
@@ -308,10 +326,20 @@ impl HiPSConfig {
         } else {
             0
         };
+
+        let cube_depth = properties.get_cube_depth();
+        let cube_frame = if let Some(cube_depth) = cube_depth {
+            properties.get_cube_first_frame().min(cube_depth - 1)
+        } else {
+            0
+        };
         let hips_config = HiPSConfig {
             creator_did,
             // HiPS name
-            root_url: root_url.to_string(),
+            mirrors: Mirrors::new(
+                root_url,
+                properties.get_service_urls().iter().map(String::as_str),
+            ),
             // Tile size & blank tile data
             empty_image,
             // Texture config
@@ -326,7 +354,7 @@ impl HiPSConfig {
             max_depth_tile,
             min_depth_texture,
             min_depth_tile,
-            num_textures,
+            texture_layout,
 
             is_allsky,
 
@@ -346,6 +374,9 @@ impl HiPSConfig {
             tile_size,
             //dataproduct_subtype,
             //colored,
+            cube_depth,
+            cube_frame,
+            properties: properties.clone(),
         };
 
         Ok(hips_config)
@@ -439,12 +470,52 @@ impl HiPSConfig {
 
     #[inline(always)]
     pub fn get_root_url(&self) -> &str {
-        &self.root_url
+        self.mirrors.get_current()
     }
 
     #[inline(always)]
     pub fn set_root_url(&mut self, root_url: String) {
-        self.root_url = root_url;
+        self.mirrors.select(&root_url);
+    }
+
+    #[inline(always)]
+    pub fn get_mirrors(&self) -> &Mirrors {
+        &self.mirrors
+    }
+
+    /// Define the mirrors the tiles can be retrieved from, the current one being kept
+    pub fn set_mirrors(&mut self, urls: &[String]) {
+        self.mirrors.set_urls(urls.iter().map(String::as_str));
+    }
+
+    /// Store the latency of a mirror and switch to it if it is faster than the current one
+    pub fn set_mirror_latency(&mut self, url: &str, latency: Option<f32>) -> Option<MirrorSwitch> {
+        let previous_url = self.get_root_url().to_string();
+        let reason = self.mirrors.set_latency(url, latency)?;
+
+        Some(self.mirror_switch(previous_url, reason))
+    }
+
+    /// Count the tiles the current mirror fails to deliver because of transient errors
+    /// and switch to another one if it fails too many times
+    pub fn register_tile(
+        &mut self,
+        tile_url: &str,
+        failure: Option<Failure>,
+    ) -> Option<MirrorSwitch> {
+        let previous_url = self.get_root_url().to_string();
+        let reason = self.mirrors.register_tile(tile_url, failure)?;
+
+        Some(self.mirror_switch(previous_url, reason))
+    }
+
+    fn mirror_switch(&self, previous_url: String, reason: SwitchReason) -> MirrorSwitch {
+        MirrorSwitch {
+            creator_did: self.creator_did.clone(),
+            previous_url,
+            url: self.get_root_url().to_string(),
+            reason,
+        }
     }
 
     #[inline(always)]
@@ -507,22 +578,55 @@ impl HiPSConfig {
 
     #[inline(always)]
     pub fn num_textures(&self) -> usize {
-        self.num_textures
+        self.texture_layout.num_textures()
     }
 
     #[inline(always)]
     pub fn num_textures_by_side_slice(&self) -> i32 {
-        NUM_TEXTURES_BY_SIDE_SLICE
+        self.texture_layout.num_textures_by_side_slice
     }
 
     #[inline(always)]
     pub fn num_textures_by_slice(&self) -> i32 {
-        NUM_TEXTURES_BY_SLICE
+        self.texture_layout.num_textures_by_slice()
     }
 
     #[inline(always)]
     pub fn num_slices(&self) -> i32 {
-        NUM_SLICES
+        self.texture_layout.num_slices
+    }
+
+    #[inline(always)]
+    pub fn get_texture_layout(&self) -> TextureLayout {
+        self.texture_layout
+    }
+
+    pub fn set_texture_layout(&mut self, layout: TextureLayout) {
+        self.texture_layout = layout;
+        self.size_tile_uv =
+            1_f32 / ((layout.num_textures_by_side_slice << self.delta_depth) as f32);
+    }
+
+    /// The size in bytes of a texture on the GPU
+    pub fn get_texture_byte_size(&self) -> usize {
+        let num_bytes_per_pixel = match self.format.get_channel() {
+            ChannelType::RGBA32F => 16,
+            ChannelType::RGB32F => 12,
+            ChannelType::RGBA8U => 4,
+            ChannelType::RGB8U => 3,
+            // Double precision tiles are stored in single precision
+            ChannelType::R32F => 4,
+            #[cfg(feature = "webgl2")]
+            ChannelType::R64F => 4,
+            #[cfg(feature = "webgl2")]
+            ChannelType::R8UI => 1,
+            #[cfg(feature = "webgl2")]
+            ChannelType::R16I => 2,
+            #[cfg(feature = "webgl2")]
+            ChannelType::R32I => 4,
+        };
+
+        (self.texture_size * self.texture_size) as usize * num_bytes_per_pixel
     }
 
     #[inline(always)]
@@ -539,6 +643,58 @@ impl HiPSConfig {
     pub fn get_default_image(&self) -> &EmptyTileImage {
         &self.empty_image
     }
+
+    /// The number of frames if the HiPS is a cube
+    #[inline(always)]
+    pub fn get_cube_depth(&self) -> Option<u32> {
+        self.cube_depth
+    }
+
+    /// The frame of the cube currently displayed, 0 if the HiPS is not a cube
+    #[inline(always)]
+    pub fn get_cube_frame(&self) -> u32 {
+        self.cube_frame
+    }
+
+    #[inline(always)]
+    pub fn set_cube_frame(&mut self, frame: u32) -> Result<(), JsValue> {
+        match self.cube_depth {
+            Some(depth) if frame < depth => {
+                self.cube_frame = frame;
+                Ok(())
+            }
+            Some(depth) => Err(JsValue::from_str(&format!(
+                "Frame {} out of the cube of {} frames",
+                frame, depth
+            ))),
+            None => Err(JsValue::from_str("The HiPS is not a cube")),
+        }
+    }
+
+    /// The frames to download in advance, the closest to the current one first
+    pub fn get_cube_neighbour_frames(&self, num_frames: u32) -> Vec<u32> {
+        let depth = if let Some(depth) = self.cube_depth {
+            depth
+        } else {
+            return vec![];
+        };
+
+        (1..=num_frames)
+            .flat_map(|d| {
+                [
+                    self.cube_frame.checked_add(d),
+                    self.cube_frame.checked_sub(d),
+                ]
+            })
+            .flatten()
+            .filter(|&frame| frame < depth)
+            .collect()
+    }
+
+    #[inline(always)]
+    pub fn get_properties(&self) -> &HiPSProperties {
+        &self.properties
+    }
 }
 
 use al_core::shader::{SendUniforms, ShaderBound};
@@ -549,6 +705,10 @@ impl SendUniforms for HiPSConfig {
         shader
             .attach_uniform("max_depth", &(self.max_depth_texture as i32))
             .attach_uniform("size_tile_uv", &self.size_tile_uv)
+            .attach_uniform(
+                "num_textures_by_side_slice",
+                &self.texture_layout.num_textures_by_side_slice,
+            )
             .attach_uniform("tex_storing_fits", &self.tex_storing_fits)
             .attach_uniform("scale", &self.scale)
             .attach_uniform("offset", &self.offset)
@@ -0,0 +1,171 @@
+use crate::healpix::cell::HEALPixCell;
+use crate::time::Time;
+
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+
+/// A texture is identified by its cell and the frame of the HiPS cube its tiles belong to
+pub type TextureKey = (HEALPixCell, u32);
+
+#[derive(Clone, Copy, Debug)]
+struct TextureCellItem {
+    key: TextureKey,
+    // The last time the texture has been needed by the view
+    time_request: Time,
+}
+
+impl PartialEq for TextureCellItem {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+impl Eq for TextureCellItem {}
+
+impl PartialOrd for TextureCellItem {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// The first item is the first to be evicted
+impl Ord for TextureCellItem {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (HEALPixCell(d1, i1), f1) = self.key;
+        let (HEALPixCell(d2, i2), f2) = other.key;
+
+        self.time_request
+            .0
+            .total_cmp(&other.time_request.0)
+            .then_with(|| d2.cmp(&d1))
+            .then_with(|| i1.cmp(&i2))
+            .then_with(|| f1.cmp(&f2))
+    }
+}
+
+/// The textures of a HiPS stored on the GPU, except its base textures
+///
+/// When the GPU buffer is full, the texture evicted is the one that has not been needed
+/// by the view for the longest time. Among textures needed at the same time, the deepest
+/// one is evicted first as it covers the smallest part of the sky.
+pub struct HEALPixCellHeap {
+    items: BTreeSet<TextureCellItem>,
+    time_requests: HashMap<TextureKey, Time>,
+
+    num_evictions: usize,
+}
+
+impl HEALPixCellHeap {
+    pub fn with_capacity(cap: usize) -> Self {
+        Self {
+            items: BTreeSet::new(),
+            time_requests: HashMap::with_capacity(cap),
+            num_evictions: 0,
+        }
+    }
+
+    /// Add a texture or update the last time it has been needed
+    pub fn push(&mut self, key: &TextureKey, time_request: Time) {
+        if let Some(time) = self.time_requests.insert(*key, time_request) {
+            self.items.remove(&TextureCellItem {
+                key: *key,
+                time_request: time,
+            });
+        }
+
+        self.items.insert(TextureCellItem {
+            key: *key,
+            time_request,
+        });
+    }
+
+    /// Remove the next texture to evict
+    pub fn pop(&mut self) -> Option<TextureKey> {
+        let item = self.items.pop_first()?;
+        self.time_requests.remove(&item.key);
+        self.num_evictions += 1;
+
+        Some(item.key)
+    }
+
+    /// Remove a given texture
+    pub fn remove(&mut self, key: &TextureKey) {
+        if let Some(time_request) = self.time_requests.remove(key) {
+            self.items.remove(&TextureCellItem {
+                key: *key,
+                time_request,
+            });
+            self.num_evictions += 1;
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.time_requests.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Number of textures evicted since the creation of the heap
+    pub fn num_evictions(&self) -> usize {
+        self.num_evictions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HEALPixCellHeap;
+    use crate::healpix::cell::HEALPixCell;
+    use crate::time::Time;
+
+    #[test]
+    fn least_recently_needed_first() {
+        let mut heap = HEALPixCellHeap::with_capacity(4);
+
+        heap.push(&(HEALPixCell(3, 0), 0), Time(0.0));
+        heap.push(&(HEALPixCell(3, 1), 0), Time(1.0));
+        heap.push(&(HEALPixCell(3, 2), 0), Time(2.0));
+        // Needed again by the view
+        heap.push(&(HEALPixCell(3, 0), 0), Time(3.0));
+        assert_eq!(heap.len(), 3);
+
+        assert_eq!(heap.pop(), Some((HEALPixCell(3, 1), 0)));
+        assert_eq!(heap.pop(), Some((HEALPixCell(3, 2), 0)));
+        assert_eq!(heap.pop(), Some((HEALPixCell(3, 0), 0)));
+        assert_eq!(heap.pop(), None);
+        assert_eq!(heap.num_evictions(), 3);
+    }
+
+    #[test]
+    fn deepest_first() {
+        let mut heap = HEALPixCellHeap::with_capacity(4);
+
+        heap.push(&(HEALPixCell(2, 7), 0), Time(1.0));
+        heap.push(&(HEALPixCell(4, 100), 0), Time(1.0));
+        heap.push(&(HEALPixCell(3, 30), 0), Time(1.0));
+        heap.remove(&(HEALPixCell(3, 30), 0));
+        heap.remove(&(HEALPixCell(3, 31), 0));
+
+        assert_eq!(heap.pop(), Some((HEALPixCell(4, 100), 0)));
+        assert_eq!(heap.pop(), Some((HEALPixCell(2, 7), 0)));
+        assert!(heap.is_empty());
+        assert_eq!(heap.num_evictions(), 3);
+    }
+
+    #[test]
+    fn frames_are_distinct_textures() {
+        let mut heap = HEALPixCellHeap::with_capacity(4);
+
+        heap.push(&(HEALPixCell(3, 0), 1), Time(1.0));
+        heap.push(&(HEALPixCell(3, 0), 0), Time(1.0));
+        assert_eq!(heap.len(), 2);
+
+        assert_eq!(heap.pop(), Some((HEALPixCell(3, 0), 0)));
+        assert_eq!(heap.pop(), Some((HEALPixCell(3, 0), 1)));
+    }
+}
@@ -0,0 +1,363 @@
+use serde::Serialize;
+
+use crate::downloader::request::Failure;
+
+/// Number of consecutive tiles a mirror can fail to deliver before another one is chosen
+const MAX_CONSECUTIVE_TILE_FAILURES: usize = 16;
+/// A mirror replaces the current one if its latency is lower than this fraction of
+/// the current mirror's latency. This avoids switching between mirrors with
+/// similar latencies.
+const LATENCY_RATIO_FOR_SWITCHING: f32 = 0.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SwitchReason {
+    // A faster mirror has been found by the latency probe
+    Latency,
+    // The current mirror failed to deliver too many tiles in a row
+    TileFailures,
+    // The current mirror did not respond to the latency probe
+    Unreachable,
+}
+
+/// A change of the mirror a HiPS is retrieved from, reported to the javascript
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MirrorSwitch {
+    pub creator_did: String,
+    pub previous_url: String,
+    pub url: String,
+    pub reason: SwitchReason,
+}
+
+#[derive(Debug, Clone)]
+struct Mirror {
+    url: String,
+    // Duration in ms of the probe request, None if the mirror has not been probed yet
+    latency: Option<f32>,
+    // The mirror did not respond to the probe or failed to deliver tiles
+    unavailable: bool,
+}
+
+impl Mirror {
+    fn new(url: &str) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            latency: None,
+            unavailable: false,
+        }
+    }
+}
+
+/// The mirrors serving the same HiPS, one of them being the one the tiles are
+/// currently retrieved from
+#[derive(Debug, Clone)]
+pub struct Mirrors {
+    mirrors: Vec<Mirror>,
+    current: usize,
+    num_consecutive_failures: usize,
+}
+
+impl Mirrors {
+    /// Create the mirror list of a HiPS
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The url the tiles are retrieved from first
+    /// * `others` - The other mirrors, duplicates of `url` are discarded
+    pub fn new<'a>(url: &str, others: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut mirrors = Self {
+            mirrors: vec![Mirror::new(url)],
+            current: 0,
+            num_consecutive_failures: 0,
+        };
+
+        for url in others {
+            mirrors.add(url);
+        }
+
+        mirrors
+    }
+
+    /// The url of the mirror tiles are retrieved from
+    #[inline]
+    pub fn get_current(&self) -> &str {
+        &self.mirrors[self.current].url
+    }
+
+    pub fn get_urls(&self) -> impl Iterator<Item = &str> {
+        self.mirrors.iter().map(|mirror| mirror.url.as_str())
+    }
+
+    fn len(&self) -> usize {
+        self.mirrors.len()
+    }
+
+    /// Replace the mirror list, the current mirror is kept if it is still part of it
+    pub fn set_urls<'a>(&mut self, urls: impl IntoIterator<Item = &'a str>) {
+        let current = self.get_current().to_string();
+        let mirrors = Mirrors::new(&current, urls);
+
+        self.mirrors = mirrors.mirrors;
+        self.current = 0;
+        self.num_consecutive_failures = 0;
+    }
+
+    /// Retrieve the tiles from a given mirror, added to the list if it is not part of it
+    pub fn select(&mut self, url: &str) {
+        self.current = self.add(url);
+        self.mirrors[self.current].unavailable = false;
+        self.num_consecutive_failures = 0;
+    }
+
+    /// Store the result of the latency probe of a mirror
+    ///
+    /// Returns the reason of the switch if another mirror has been chosen
+    ///
+    /// # Arguments
+    ///
+    /// * `url` - The mirror probed
+    /// * `latency` - The duration of the probe in ms, None if the mirror did not respond
+    pub fn set_latency(&mut self, url: &str, latency: Option<f32>) -> Option<SwitchReason> {
+        let idx = self.position(url)?;
+        let mirror = &mut self.mirrors[idx];
+        mirror.latency = latency;
+        mirror.unavailable = latency.is_none();
+
+        let current = &self.mirrors[self.current];
+        let (best_idx, best_latency) = self.fastest()?;
+
+        if current.unavailable {
+            self.switch_to(best_idx);
+            Some(SwitchReason::Unreachable)
+        } else if current
+            .latency
+            .is_some_and(|latency| best_latency < LATENCY_RATIO_FOR_SWITCHING * latency)
+        {
+            self.switch_to(best_idx);
+            Some(SwitchReason::Latency)
+        } else {
+            None
+        }
+    }
+
+    /// Tell whether a tile has been received or why it could not be
+    ///
+    /// Tiles coming from another mirror than the current one are not taken into account.
+    /// Only the transient failures count, a missing tile being answered by the mirror
+    /// and a cancelled request telling nothing about it.
+    /// Returns the reason of the switch if another mirror has been chosen
+    pub fn register_tile(
+        &mut self,
+        tile_url: &str,
+        failure: Option<Failure>,
+    ) -> Option<SwitchReason> {
+        let from_current = tile_url
+            .strip_prefix(self.get_current())
+            .is_some_and(|path| path.starts_with('/'));
+        if !from_current {
+            return None;
+        }
+
+        match failure {
+            None | Some(Failure::Permanent) => {
+                self.num_consecutive_failures = 0;
+                return None;
+            }
+            Some(Failure::Aborted) => return None,
+            Some(Failure::Transient) => (),
+        }
+
+        self.num_consecutive_failures += 1;
+        if self.num_consecutive_failures < MAX_CONSECUTIVE_TILE_FAILURES || self.len() == 1 {
+            return None;
+        }
+
+        self.mirrors[self.current].unavailable = true;
+        let next = if let Some((idx, _)) = self.fastest() {
+            idx
+        } else {
+            // All the mirrors seem to be down, we try them one after the other
+            let next = self.mirrors.iter().enumerate().find(|&(idx, mirror)| {
+                idx != self.current && !mirror.unavailable && mirror.latency.is_none()
+            });
+
+            if let Some((idx, _)) = next {
+                idx
+            } else {
+                for mirror in self.mirrors.iter_mut() {
+                    mirror.unavailable = false;
+                }
+
+                (self.current + 1) % self.len()
+            }
+        };
+
+        self.switch_to(next);
+        Some(SwitchReason::TileFailures)
+    }
+
+    fn switch_to(&mut self, idx: usize) {
+        self.current = idx;
+        self.num_consecutive_failures = 0;
+    }
+
+    // The available mirror with the lowest latency
+    fn fastest(&self) -> Option<(usize, f32)> {
+        self.mirrors
+            .iter()
+            .enumerate()
+            .filter(|(_, mirror)| !mirror.unavailable)
+            .filter_map(|(idx, mirror)| mirror.latency.map(|latency| (idx, latency)))
+            .min_by(|(_, l1), (_, l2)| l1.total_cmp(l2))
+    }
+
+    fn position(&self, url: &str) -> Option<usize> {
+        let url = url.trim_end_matches('/');
+        self.mirrors.iter().position(|mirror| mirror.url == url)
+    }
+
+    // Returns the index of the mirror
+    fn add(&mut self, url: &str) -> usize {
+        if let Some(idx) = self.position(url) {
+            idx
+        } else {
+            self.mirrors.push(Mirror::new(url));
+            self.mirrors.len() - 1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Mirrors, SwitchReason, MAX_CONSECUTIVE_TILE_FAILURES};
+    use crate::downloader::request::Failure;
+
+    const FAILED: Option<Failure> = Some(Failure::Transient);
+
+    const MAIN: &str = "https://alasky.cds.unistra.fr/DSS/DSSColor";
+    const BIS: &str = "https://alaskybis.cds.unistra.fr/DSS/DSSColor";
+    const OTHER: &str = "https://alasky.u-strasbg.fr/DSS/DSSColor";
+
+    fn tile(mirror: &str) -> String {
+        format!("{}/Norder3/Dir0/Npix42.jpg", mirror)
+    }
+
+    #[test]
+    fn duplicates() {
+        let mirrors = Mirrors::new(MAIN, [BIS, "https://alasky.cds.unistra.fr/DSS/DSSColor/"]);
+
+        assert_eq!(mirrors.get_current(), MAIN);
+        assert_eq!(mirrors.get_urls().collect::<Vec<_>>(), vec![MAIN, BIS]);
+    }
+
+    #[test]
+    fn fastest_mirror() {
+        let mut mirrors = Mirrors::new(MAIN, [BIS, OTHER]);
+
+        // The current mirror has not been probed yet
+        assert_eq!(mirrors.set_latency(BIS, Some(50.0)), None);
+        // Too close to the latency of BIS
+        assert_eq!(mirrors.set_latency(MAIN, Some(55.0)), None);
+        assert_eq!(mirrors.get_current(), MAIN);
+
+        assert_eq!(
+            mirrors.set_latency(OTHER, Some(20.0)),
+            Some(SwitchReason::Latency)
+        );
+        assert_eq!(mirrors.get_current(), OTHER);
+    }
+
+    #[test]
+    fn unreachable_mirror() {
+        let mut mirrors = Mirrors::new(MAIN, [BIS, OTHER]);
+
+        assert_eq!(mirrors.set_latency(BIS, Some(300.0)), None);
+        assert_eq!(
+            mirrors.set_latency(MAIN, None),
+            Some(SwitchReason::Unreachable)
+        );
+        assert_eq!(mirrors.get_current(), BIS);
+        // An unreachable mirror is never chosen
+        assert_eq!(mirrors.set_latency(OTHER, None), None);
+        assert_eq!(mirrors.get_current(), BIS);
+    }
+
+    #[test]
+    fn tile_failures() {
+        let mut mirrors = Mirrors::new(MAIN, [BIS, OTHER]);
+        mirrors.set_latency(OTHER, Some(100.0));
+
+        for _ in 0..MAX_CONSECUTIVE_TILE_FAILURES - 1 {
+            assert_eq!(mirrors.register_tile(&tile(MAIN), FAILED), None);
+        }
+        // A received tile resets the count of failures
+        assert_eq!(mirrors.register_tile(&tile(MAIN), None), None);
+        for _ in 0..MAX_CONSECUTIVE_TILE_FAILURES - 1 {
+            assert_eq!(mirrors.register_tile(&tile(MAIN), FAILED), None);
+        }
+        // And so does a missing tile, the mirror has answered
+        assert_eq!(
+            mirrors.register_tile(&tile(MAIN), Some(Failure::Permanent)),
+            None
+        );
+        for _ in 0..MAX_CONSECUTIVE_TILE_FAILURES - 1 {
+            assert_eq!(mirrors.register_tile(&tile(MAIN), FAILED), None);
+        }
+        // Cancelled requests and tiles requested to another mirror do not count
+        assert_eq!(
+            mirrors.register_tile(&tile(MAIN), Some(Failure::Aborted)),
+            None
+        );
+        assert_eq!(mirrors.register_tile(&tile(BIS), FAILED), None);
+        assert_eq!(
+            mirrors.register_tile("blob:http://localhost/42", FAILED),
+            None
+        );
+        assert_eq!(mirrors.get_current(), MAIN);
+
+        // The mirror probed is preferred
+        assert_eq!(
+            mirrors.register_tile(&tile(MAIN), FAILED),
+            Some(SwitchReason::TileFailures)
+        );
+        assert_eq!(mirrors.get_current(), OTHER);
+
+        let fail = |mirrors: &mut Mirrors| {
+            let current = mirrors.get_current().to_string();
+            (0..MAX_CONSECUTIVE_TILE_FAILURES)
+                .filter_map(|_| mirrors.register_tile(&tile(&current), FAILED))
+                .last()
+        };
+        assert_eq!(fail(&mut mirrors), Some(SwitchReason::TileFailures));
+        assert_eq!(mirrors.get_current(), BIS);
+        // All the mirrors failed, they are tried again in turn
+        assert_eq!(fail(&mut mirrors), Some(SwitchReason::TileFailures));
+        assert_eq!(mirrors.get_current(), OTHER);
+    }
+
+    #[test]
+    fn single_mirror() {
+        let mut mirrors = Mirrors::new(MAIN, []);
+
+        for _ in 0..2 * MAX_CONSECUTIVE_TILE_FAILURES {
+            assert_eq!(mirrors.register_tile(&tile(MAIN), FAILED), None);
+        }
+        assert_eq!(mirrors.get_current(), MAIN);
+    }
+
+    #[test]
+    fn manual_selection() {
+        let mut mirrors = Mirrors::new(MAIN, [BIS]);
+        mirrors.select(OTHER);
+        assert_eq!(mirrors.get_current(), OTHER);
+        assert_eq!(mirrors.len(), 3);
+
+        mirrors.set_urls([MAIN, BIS]);
+        assert_eq!(mirrors.get_current(), OTHER);
+        assert_eq!(
+            mirrors.get_urls().collect::<Vec<_>>(),
+            vec![OTHER, MAIN, BIS]
+        );
+    }
+}
@@ -1,4 +1,7 @@
 pub mod bitvector;
 pub mod buffer;
 pub mod config;
+pub mod heap;
+pub mod mirror;
 pub mod texture;
+pub mod texture_layout;
@@ -25,7 +25,14 @@ pub struct Texture {
     time_request: Time,
 
     // Full flag telling the texture has been filled
+    // It stays set when a base texture switches to another frame of a
+    // HiPS cube so that the previous frame keeps being drawn until the
+    // new one replaces it
     full: bool,
+    // The frame of the cube the tiles written belong to
+    frame: u32,
+    // Flag telling all the tiles of the current frame have been written
+    complete: bool,
 
     // Num tiles written for the gpu
     num_tiles_written: usize,
@@ -37,11 +44,12 @@ pub struct Texture {
 use super::config::HiPSConfig;
 
 impl Texture {
-    pub fn new(texture_cell: &HEALPixCell, idx: i32, time_request: Time) -> Texture {
+    pub fn new(texture_cell: &HEALPixCell, idx: i32, time_request: Time, frame: u32) -> Texture {
         let tiles = HashSet::new();
 
         let start_time = None;
         let full = false;
+        let complete = false;
         let texture_cell = *texture_cell;
         let uniq = texture_cell.uniq();
         //let missing = true;
@@ -54,6 +62,8 @@ impl Texture {
             idx,
             start_time,
             full,
+            frame,
+            complete,
             num_tiles_written,
             //missing,
         }
@@ -65,7 +75,7 @@ impl Texture {
     pub fn append(&mut self, cell: &HEALPixCell, cfg: &HiPSConfig /*, missing: bool */) {
         let texture_cell = cell.get_texture_cell(cfg.delta_depth());
         debug_assert!(texture_cell == self.texture_cell);
-        debug_assert!(!self.full);
+        debug_assert!(!self.complete);
 
         //self.missing &= missing;
         //self.start_time = Some(Time::now());
@@ -75,9 +85,7 @@ impl Texture {
 
         if c == texture_cell {
             self.num_tiles_written = num_tiles_per_texture;
-            self.full = true;
-
-            self.start_time = Some(Time::now());
+            self.set_complete();
         } else {
             // Sub-tile appending. This code is called for tile size is < 512
             // Cell has the good ancestor for this texture
@@ -90,15 +98,23 @@ impl Texture {
 
             if self.num_tiles_written == num_tiles_per_texture {
                 // The texture is full and available
-                self.full = true;
-                self.start_time = Some(Time::now());
+                self.set_complete();
             }
         }
     }
 
+    fn set_complete(&mut self) {
+        self.complete = true;
+        self.full = true;
+        // A texture switching to another frame of a cube is not blended again
+        if self.start_time.is_none() {
+            self.start_time = Some(Time::now());
+        }
+    }
+
     // Cell must be contained in the texture
     pub fn contains(&self, cell: &HEALPixCell) -> bool {
-        self.is_full() || self.tiles.contains(cell)
+        self.complete || self.tiles.contains(cell)
     }
 
     pub fn is_full(&self) -> bool {
@@ -127,12 +143,16 @@ impl Texture {
         self.idx
     }
 
+    pub fn frame(&self) -> u32 {
+        self.frame
+    }
+
     /*pub fn is_missing(&self) -> bool {
         self.missing
     }*/
 
     // Setter
-    pub fn replace(&mut self, texture_cell: &HEALPixCell, time_request: Time) {
+    pub fn replace(&mut self, texture_cell: &HEALPixCell, time_request: Time, frame: u32) {
         // Cancel the tasks copying the tiles contained in the texture
         // which have not yet been completed.
         //self.clear_tasks_in_progress(config, exec);
@@ -142,6 +162,14 @@ impl Texture {
         self.full = false;
         self.start_time = None;
         self.time_request = time_request;
+        self.change_frame(frame);
+    }
+
+    // Start writing the tiles of another frame of the cube.
+    // The texture stays drawable with the tiles of the previous frame
+    pub fn change_frame(&mut self, frame: u32) {
+        self.frame = frame;
+        self.complete = false;
         self.tiles.clear();
         //self.missing = true;
         self.num_tiles_written = 0;
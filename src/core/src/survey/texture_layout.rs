@@ -0,0 +1,137 @@
+use crate::healpix::cell::NUM_HPX_TILES_DEPTH_ZERO;
+
+/// Default GPU memory shared by the textures of all the HiPS layers, in bytes
+pub const DEFAULT_GPU_TEXTURE_BUDGET: usize = 512 * 1024 * 1024;
+/// Maximum number of slices of a HiPS, limited by the number of samplers of the HiPS shaders
+pub const MAX_NUM_SLICES: i32 = 4;
+
+// Slices are made of 8x8 textures unless the budget does not allow it
+const NUM_TEXTURES_BY_SIDE_LARGE_SLICE: i32 = 8;
+const NUM_TEXTURES_BY_SIDE_SMALL_SLICE: i32 = 4;
+// Ratio between the number of textures kept and the number of textures fitting in the view.
+// The view is covered by textures of two consecutive orders when zooming, and the ancestors
+// of the tiles and the textures around the view are kept as well.
+const VIEW_COVERAGE_FACTOR: f64 = 4.0;
+
+/// How the textures of a HiPS are arranged on the GPU
+///
+/// The textures are stored in slices, i.e. 2D textures containing a square grid of textures.
+/// The first slice contains the base textures of the HiPS.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextureLayout {
+    pub num_textures_by_side_slice: i32,
+    pub num_slices: i32,
+}
+
+impl Default for TextureLayout {
+    fn default() -> Self {
+        Self {
+            num_textures_by_side_slice: NUM_TEXTURES_BY_SIDE_LARGE_SLICE,
+            num_slices: 1,
+        }
+    }
+}
+
+impl TextureLayout {
+    /// Compute the layout of the textures of a HiPS
+    ///
+    /// Enough textures are kept to cover the view several times, as long as they fit
+    /// into the memory budget of the HiPS.
+    ///
+    /// # Arguments
+    ///
+    /// * `num_pixels_view` - The number of pixels of the view
+    /// * `texture_size` - The width of a texture in pixels
+    /// * `texture_byte_size` - The size of a texture on the GPU in bytes
+    /// * `budget` - The GPU memory allocated to the HiPS in bytes
+    pub fn new(
+        num_pixels_view: f64,
+        texture_size: i32,
+        texture_byte_size: usize,
+        budget: usize,
+    ) -> Self {
+        let num_textures_in_view = num_pixels_view / ((texture_size * texture_size) as f64);
+        let num_needed = NUM_HPX_TILES_DEPTH_ZERO
+            + (VIEW_COVERAGE_FACTOR * num_textures_in_view).ceil() as usize;
+        let num_affordable = budget / texture_byte_size.max(1);
+
+        let num_textures_large_slice =
+            (NUM_TEXTURES_BY_SIDE_LARGE_SLICE * NUM_TEXTURES_BY_SIDE_LARGE_SLICE) as usize;
+        if num_affordable >= num_textures_large_slice {
+            let max_num_slices = (num_affordable / num_textures_large_slice) as i32;
+            let num_slices = (num_needed.div_ceil(num_textures_large_slice) as i32)
+                .clamp(1, max_num_slices.min(MAX_NUM_SLICES));
+
+            Self {
+                num_textures_by_side_slice: NUM_TEXTURES_BY_SIDE_LARGE_SLICE,
+                num_slices,
+            }
+        } else {
+            let num_textures_small_slice =
+                (NUM_TEXTURES_BY_SIDE_SMALL_SLICE * NUM_TEXTURES_BY_SIDE_SMALL_SLICE) as usize;
+            // Two small slices at least so that there is room for more than the base textures
+            let num_slices = ((num_affordable / num_textures_small_slice) as i32).max(2);
+
+            Self {
+                num_textures_by_side_slice: NUM_TEXTURES_BY_SIDE_SMALL_SLICE,
+                num_slices,
+            }
+        }
+    }
+
+    #[inline]
+    pub fn num_textures_by_slice(&self) -> i32 {
+        self.num_textures_by_side_slice * self.num_textures_by_side_slice
+    }
+
+    #[inline]
+    pub fn num_textures(&self) -> usize {
+        (self.num_textures_by_slice() * self.num_slices) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TextureLayout, DEFAULT_GPU_TEXTURE_BUDGET, MAX_NUM_SLICES};
+
+    // A 512x512 RGB texture
+    const TEXTURE_SIZE: i32 = 512;
+    const TEXTURE_BYTE_SIZE: usize = 512 * 512 * 3;
+
+    fn layout(width: f64, height: f64, budget: usize) -> TextureLayout {
+        TextureLayout::new(width * height, TEXTURE_SIZE, TEXTURE_BYTE_SIZE, budget)
+    }
+
+    #[test]
+    fn view_size() {
+        // The former fixed layout for common screens
+        assert_eq!(
+            layout(1920.0, 1080.0, DEFAULT_GPU_TEXTURE_BUDGET),
+            TextureLayout::default()
+        );
+
+        let layout_4k = layout(3840.0, 2160.0, DEFAULT_GPU_TEXTURE_BUDGET);
+        assert_eq!(layout_4k.num_textures_by_side_slice, 8);
+        assert_eq!(layout_4k.num_slices, 3);
+
+        let layout_8k = layout(7680.0, 4320.0, DEFAULT_GPU_TEXTURE_BUDGET);
+        assert_eq!(layout_8k.num_slices, MAX_NUM_SLICES);
+    }
+
+    #[test]
+    fn budget() {
+        // Shared between 5 layers
+        let layout_4k = layout(3840.0, 2160.0, DEFAULT_GPU_TEXTURE_BUDGET / 5);
+        assert_eq!(layout_4k.num_slices, 2);
+        assert!(layout_4k.num_textures() * TEXTURE_BYTE_SIZE <= DEFAULT_GPU_TEXTURE_BUDGET / 5);
+
+        // A phone with a small budget
+        let layout_phone = layout(1170.0, 2532.0, 32 * 1024 * 1024);
+        assert_eq!(layout_phone.num_textures_by_side_slice, 4);
+        assert_eq!(layout_phone.num_slices, 2);
+        assert_eq!(layout_phone.num_textures(), 32);
+
+        // Not enough memory, there is still room for the base textures and a few other ones
+        assert_eq!(layout(1920.0, 1080.0, 0).num_textures(), 32);
+    }
+}
@@ -0,0 +1,133 @@
+//! Decoding and encoding of the base64 STREAMs of the VOTables
+use super::Error;
+
+const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn sextet(c: u8) -> Option<u32> {
+    match c {
+        b'A'..=b'Z' => Some((c - b'A') as u32),
+        b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+        b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+        b'+' => Some(62),
+        b'/' => Some(63),
+        _ => None,
+    }
+}
+
+/// A decoder of a base64 text received in several parts
+#[derive(Debug, Default)]
+pub struct Decoder {
+    group: u32,
+    num_sextets: u32,
+    // The padding has been reached
+    ended: bool,
+}
+
+impl Decoder {
+    /// Decode a part of the text, the whitespaces being ignored
+    ///
+    /// The bytes decoded are appended to `bytes`, the sextets of an incomplete group being
+    /// kept until the next part
+    pub fn push(&mut self, text: &str, bytes: &mut Vec<u8>) -> Result<(), Error> {
+        bytes.reserve(text.len() / 4 * 3);
+
+        for c in text.bytes() {
+            if self.ended || c.is_ascii_whitespace() {
+                continue;
+            }
+            if c == b'=' {
+                self.ended = true;
+                continue;
+            }
+
+            self.group = (self.group << 6) | sextet(c).ok_or(Error::Base64)?;
+            self.num_sextets += 1;
+
+            if self.num_sextets == 4 {
+                bytes.extend_from_slice(&self.group.to_be_bytes()[1..]);
+                self.group = 0;
+                self.num_sextets = 0;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Decode the last incomplete group once the whole text has been pushed
+    pub fn finish(self, bytes: &mut Vec<u8>) -> Result<(), Error> {
+        match self.num_sextets {
+            0 => (),
+            2 => bytes.push((self.group >> 4) as u8),
+            3 => bytes.extend_from_slice(&((self.group >> 2) as u16).to_be_bytes()),
+            _ => return Err(Error::Base64),
+        }
+
+        Ok(())
+    }
+}
+
+/// Encode bytes in base64, the lines being wrapped at 76 characters
+pub fn encode(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len().div_ceil(3) * 4 * 77 / 76);
+
+    for (i, chunk) in bytes.chunks(3).enumerate() {
+        if i > 0 && i % 19 == 0 {
+            text.push('\n');
+        }
+
+        let group = chunk
+            .iter()
+            .enumerate()
+            .fold(0_u32, |group, (i, &b)| group | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            text.push(if i <= chunk.len() {
+                ALPHABET[(group >> (18 - 6 * i) & 63) as usize] as char
+            } else {
+                '='
+            });
+        }
+    }
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{encode, Decoder};
+    use crate::table::Error;
+
+    fn decode(text: &str) -> Result<Vec<u8>, Error> {
+        let mut bytes = vec![];
+
+        let mut decoder = Decoder::default();
+        decoder.push(text, &mut bytes)?;
+        decoder.finish(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    #[test]
+    fn base64() {
+        assert_eq!(decode("QWxh\nZGlu").unwrap(), b"Aladin");
+        assert_eq!(decode("QWxhZGluIQ==").unwrap(), b"Aladin!");
+        assert_eq!(decode(" QWxhZGluIEw= ").unwrap(), b"Aladin L");
+        assert!(decode("QWxh*").is_err());
+
+        assert_eq!(encode(b"Aladin!"), "QWxhZGluIQ==");
+        assert_eq!(encode(b"Aladin L"), "QWxhZGluIEw=");
+        let bytes = (0..=255).collect::<Vec<u8>>();
+        let text = encode(&bytes);
+        assert!(text.lines().all(|line| line.len() <= 76));
+        assert_eq!(decode(&text).unwrap(), bytes);
+
+        // Decoded part by part, the groups being split between the parts
+        for split in 0..text.len() {
+            let mut decoded = vec![];
+            let mut decoder = Decoder::default();
+            decoder.push(&text[..split], &mut decoded).unwrap();
+            decoder.push(&text[split..], &mut decoded).unwrap();
+            decoder.finish(&mut decoded).unwrap();
+            assert_eq!(decoded, bytes);
+        }
+    }
+}
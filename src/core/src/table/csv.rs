@@ -0,0 +1,76 @@
+//! CSV writer
+//!
+//! The first line gives the names of the columns, and the values are written as in the
+//! TABLEDATA of a VOTable, the missing ones being empty. The fields are quoted as
+//! described in RFC 4180: https://www.rfc-editor.org/rfc/rfc4180
+use super::Table;
+
+/// Write a table in CSV, the lines ending with CRLF
+pub fn write(table: &Table) -> Vec<u8> {
+    let mut csv = String::new();
+
+    let names = table.fields.iter().map(|field| quote(&field.name));
+    csv.push_str(&names.collect::<Vec<_>>().join(","));
+    csv.push_str("\r\n");
+
+    for row in 0..table.num_rows {
+        let values = table
+            .fields
+            .iter()
+            .zip(&table.columns)
+            .map(|(field, column)| {
+                column
+                    .get_text(field, row)
+                    .map(|text| quote(&text))
+                    .unwrap_or_default()
+            });
+        csv.push_str(&values.collect::<Vec<_>>().join(","));
+        csv.push_str("\r\n");
+    }
+
+    csv.into_bytes()
+}
+
+// Quote a field containing a separator, a quote or a line break
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write;
+    use crate::table::{Column, DataType, Field, Table};
+
+    #[test]
+    fn csv() {
+        let mut name = Field::new("name".to_string(), DataType::Char);
+        name.arraysize = Some("*".to_string());
+        let mut flux = Field::new("flux, mJy".to_string(), DataType::Float);
+        flux.arraysize = Some("2".to_string());
+        let mag = Field::new("mag".to_string(), DataType::Double);
+        let fields = vec![name, flux, mag];
+
+        let mut columns = fields.iter().map(Column::new).collect::<Vec<_>>();
+        let rows = [["M31", "1.5 2", "3.25"], ["\"Big\", dipper", "3 NaN", ""]];
+        for row in &rows {
+            for ((field, column), value) in fields.iter().zip(&mut columns).zip(row) {
+                column.push_text(field, value);
+            }
+        }
+        let table = Table {
+            name: None,
+            fields,
+            columns,
+            num_rows: rows.len(),
+        };
+
+        assert_eq!(
+            String::from_utf8(write(&table)).unwrap(),
+            "name,\"flux, mJy\",mag\r\nM31,1.5 2,3.25\r\n\"\"\"Big\"\", dipper\",3 NaN,\r\n"
+        );
+    }
+}
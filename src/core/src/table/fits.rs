@@ -0,0 +1,1050 @@
+//! FITS binary (BINTABLE) and ASCII (TABLE) tables
+//!
+//! The rows of the binary tables are big endian like the BINARY serialization of the
+//! VOTables, their cells are read the same way. The variable length arrays are stored in
+//! the heap following the rows. The cells of the ASCII tables are texts read like the
+//! TABLEDATA of the VOTables. The tables are written as binary tables.
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+
+use super::{position_columns, ArraySize, Column, DataType, Error, Field, Table};
+
+const BLOCK_SIZE: usize = 2880;
+const CARD_SIZE: usize = 80;
+
+/// The keywords of a FITS header, the strings being unquoted
+pub struct Header {
+    cards: HashMap<String, String>,
+    // The size in bytes of the header blocks
+    size: usize,
+}
+
+// The keywords describing the columns of a table, suffixed by the index of the column
+const COLUMN_KEYWORDS: &[&str] = &[
+    "TTYPE", "TFORM", "TUNIT", "TUCD", "TNULL", "TSCAL", "TZERO", "TBCOL",
+];
+
+impl Header {
+    /// Gather the keywords of a table header read by another FITS parser
+    ///
+    /// # Arguments
+    ///
+    /// * `get` - Gives the value of a keyword, unquoted for the strings
+    pub fn from_keywords<F>(get: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let mut header = Self {
+            cards: HashMap::new(),
+            size: 0,
+        };
+        let insert = |header: &mut Self, key: String| {
+            if let Some(value) = get(&key) {
+                header.cards.insert(key, value);
+            }
+        };
+
+        for key in &[
+            "XTENSION", "BITPIX", "NAXIS", "NAXIS1", "NAXIS2", "PCOUNT", "GCOUNT", "TFIELDS",
+            "THEAP", "EXTNAME",
+        ] {
+            insert(&mut header, key.to_string());
+        }
+        let num_fields = header.get_int("TFIELDS").unwrap_or(0);
+        for i in 1..=num_fields {
+            for key in COLUMN_KEYWORDS {
+                insert(&mut header, format!("{}{}", key, i));
+            }
+        }
+
+        header
+    }
+
+    /// Read the header starting at the beginning of `bytes`
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let mut cards = HashMap::new();
+
+        for (i, card) in bytes.chunks(CARD_SIZE).enumerate() {
+            if card.len() < CARD_SIZE {
+                break;
+            }
+            let card = String::from_utf8_lossy(card);
+            let key = card[..8].trim_end();
+
+            if key == "END" {
+                let size = ((i + 1) * CARD_SIZE).div_ceil(BLOCK_SIZE) * BLOCK_SIZE;
+                return Ok(Self { cards, size });
+            }
+
+            if let Some(value) = card[8..].strip_prefix("= ") {
+                cards.insert(key.to_string(), parse_value(value));
+            }
+        }
+
+        Err(Error::Fits {
+            reason: "no END card found".to_string(),
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.cards.get(key).map(String::as_str)
+    }
+
+    pub fn get_int(&self, key: &str) -> Option<i64> {
+        self.get(key)
+            .and_then(|v| v.parse::<f64>().ok())
+            .map(|v| v as i64)
+    }
+
+    pub fn get_float(&self, key: &str) -> Option<f64> {
+        // The exponent of double precision numbers can be written with a D
+        self.get(key).and_then(|v| v.replace('D', "E").parse().ok())
+    }
+
+    fn required_int(&self, key: &str) -> Result<i64, Error> {
+        self.get_int(key).ok_or_else(|| Error::Fits {
+            reason: format!("missing keyword {}", key),
+        })
+    }
+
+    /// The size in bytes of the rows of a table, their number and the number of fields
+    ///
+    /// The rows must not be empty and the table must fit in memory
+    fn table_dims(&self) -> Result<(usize, usize, usize), Error> {
+        let row_size = self.required_int("NAXIS1")?;
+        let num_rows = self.required_int("NAXIS2")?;
+        let num_fields = self.required_int("TFIELDS")?;
+
+        let dims = usize::try_from(row_size)
+            .ok()
+            .filter(|&row_size| row_size > 0)
+            .zip(usize::try_from(num_rows).ok())
+            .filter(|(row_size, num_rows)| row_size.checked_mul(*num_rows).is_some())
+            .ok_or_else(|| Error::Fits {
+                reason: format!("invalid dimensions {} x {}", row_size, num_rows),
+            })?;
+        // TFIELDS is at most 999
+        let num_fields = usize::try_from(num_fields)
+            .ok()
+            .filter(|&num_fields| num_fields <= 999)
+            .ok_or_else(|| Error::Fits {
+                reason: format!("invalid number of fields {}", num_fields),
+            })?;
+
+        Ok((dims.0, dims.1, num_fields))
+    }
+
+    /// The size in bytes of the data following the header, padding included
+    fn data_size(&self) -> Result<usize, Error> {
+        let naxis = self.required_int("NAXIS")?;
+        if naxis == 0 {
+            return Ok(0);
+        }
+
+        let invalid = || Error::Fits {
+            reason: "invalid size of the data".to_string(),
+        };
+        let int = |key: &str, default: Option<i64>| -> Result<usize, Error> {
+            let value = match default {
+                Some(default) => self.get_int(key).unwrap_or(default),
+                None => self.required_int(key)?,
+            };
+            usize::try_from(value).map_err(|_| invalid())
+        };
+
+        let mut num_values = 1_usize;
+        for i in 1..=naxis {
+            num_values = num_values
+                .checked_mul(int(&format!("NAXIS{}", i), None)?)
+                .ok_or_else(invalid)?;
+        }
+        let bytes_per_value = self.required_int("BITPIX")?.unsigned_abs() as usize / 8;
+        bytes_per_value
+            .checked_mul(int("GCOUNT", Some(1))?)
+            .zip(int("PCOUNT", Some(0))?.checked_add(num_values))
+            .and_then(|(size, num_values)| size.checked_mul(num_values))
+            .and_then(|size| size.checked_next_multiple_of(BLOCK_SIZE))
+            .ok_or_else(invalid)
+    }
+}
+
+// The value of a card, without its comment
+fn parse_value(value: &str) -> String {
+    let value = value.trim_start();
+    match value.strip_prefix('\'') {
+        Some(string) => {
+            // A quote is escaped by doubling it
+            let mut unquoted = String::new();
+            let mut chars = string.chars().peekable();
+            while let Some(c) = chars.next() {
+                if c == '\'' {
+                    if chars.peek() == Some(&'\'') {
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                unquoted.push(c);
+            }
+
+            unquoted.trim_end().to_string()
+        }
+        None => value.split('/').next().unwrap_or("").trim().to_string(),
+    }
+}
+
+/// The HDUs of a FITS file, with their headers and their data
+pub fn hdus<'a>(bytes: &'a [u8]) -> impl Iterator<Item = Result<(Header, &'a [u8]), Error>> + 'a {
+    let mut offset = 0;
+    let mut failed = false;
+
+    std::iter::from_fn(move || {
+        if failed || offset >= bytes.len() {
+            return None;
+        }
+
+        let hdu = Header::parse(&bytes[offset..]).and_then(|header| {
+            let start = offset + header.size;
+            let end = start
+                .checked_add(header.data_size()?)
+                .ok_or(Error::Truncated)?;
+            // The padding of the last HDU may be missing
+            let data = bytes
+                .get(start..end.min(bytes.len()))
+                .ok_or(Error::Truncated)?;
+            offset = end;
+
+            Ok((header, data))
+        });
+        failed = hdu.is_err();
+
+        Some(hdu)
+    })
+}
+
+// The layout of a column in the rows
+struct ColumnFormat {
+    // Offset of the cell in the row
+    offset: usize,
+    // Number of values of the cell, or maximum for the variable length arrays
+    repeat: usize,
+    // The variable length arrays are described by a pair of 32 (P) or 64 (Q) bits integers
+    descriptor: Option<usize>,
+    // TSCALn and TZEROn
+    scaling: Option<(f64, f64)>,
+}
+
+// Parse a TFORMn e.g. `1D`, `20A`, `1PE(100)`
+fn parse_tform(tform: &str) -> Result<(usize, DataType, Option<usize>), Error> {
+    let invalid = || Error::Fits {
+        reason: format!("invalid TFORM {}", tform),
+    };
+    let tform = tform.trim();
+    let num_digits = tform.chars().take_while(|c| c.is_ascii_digit()).count();
+    let repeat = if num_digits == 0 {
+        1
+    } else {
+        tform[..num_digits].parse().map_err(|_| invalid())?
+    };
+
+    let mut code = tform[num_digits..].chars();
+    let (descriptor, code) = match code.next().ok_or_else(invalid)? {
+        'P' => (Some(4), code.next().ok_or_else(invalid)?),
+        'Q' => (Some(8), code.next().ok_or_else(invalid)?),
+        c => (None, c),
+    };
+
+    let datatype = match code {
+        'L' => DataType::Boolean,
+        'X' => DataType::Bit,
+        'B' => DataType::UnsignedByte,
+        'I' => DataType::Short,
+        'J' => DataType::Int,
+        'K' => DataType::Long,
+        'A' => DataType::Char,
+        'E' => DataType::Float,
+        'D' => DataType::Double,
+        'C' => DataType::FloatComplex,
+        'M' => DataType::DoubleComplex,
+        _ => return Err(invalid()),
+    };
+
+    Ok((repeat, datatype, descriptor))
+}
+
+// The letter of a datatype in a TFORMn
+fn tform_code(datatype: DataType) -> char {
+    match datatype {
+        DataType::Boolean => 'L',
+        DataType::Bit => 'X',
+        DataType::UnsignedByte => 'B',
+        DataType::Short => 'I',
+        DataType::Int => 'J',
+        DataType::Long => 'K',
+        DataType::Char | DataType::UnicodeChar => 'A',
+        DataType::Float => 'E',
+        DataType::Double => 'D',
+        DataType::FloatComplex => 'C',
+        DataType::DoubleComplex => 'M',
+    }
+}
+
+/// Read the BINTABLE of a FITS file
+///
+/// # Arguments
+///
+/// * `bytes` - The FITS file
+/// * `extnum` - The index of the HDU of the table, the primary HDU being 0
+pub fn parse_bintable(bytes: &[u8], extnum: usize) -> Result<Table, Error> {
+    let (header, data) = get_hdu(bytes, extnum)?;
+
+    if header.get("XTENSION") != Some("BINTABLE") {
+        return Err(Error::Fits {
+            reason: format!("the extension {} is not a BINTABLE", extnum),
+        });
+    }
+
+    read_bintable(&header, data)
+}
+
+/// Read the BINTABLE or ASCII TABLE of a FITS file
+///
+/// # Arguments
+///
+/// * `bytes` - The FITS file
+/// * `extnum` - The index of the HDU of the table, the primary HDU being 0
+pub fn parse_table(bytes: &[u8], extnum: usize) -> Result<Table, Error> {
+    let (header, data) = get_hdu(bytes, extnum)?;
+
+    match header.get("XTENSION") {
+        Some("BINTABLE") => read_bintable(&header, data),
+        Some("TABLE") => read_ascii_table(&header, data),
+        _ => Err(Error::Fits {
+            reason: format!("the extension {} is not a table", extnum),
+        }),
+    }
+}
+
+fn get_hdu(bytes: &[u8], extnum: usize) -> Result<(Header, &[u8]), Error> {
+    hdus(bytes).nth(extnum).ok_or_else(|| Error::Fits {
+        reason: format!("no extension {} found", extnum),
+    })?
+}
+
+// The name of a column, TTYPEn, or `col_n` if missing
+fn get_column_name(header: &Header, i: usize) -> String {
+    header
+        .get(&format!("TTYPE{}", i))
+        .map_or_else(|| format!("col_{}", i), str::to_string)
+}
+
+// TSCALn and TZEROn, None if the values are not scaled
+fn get_scaling(header: &Header, i: usize) -> Option<(f64, f64)> {
+    let scale = header.get_float(&format!("TSCAL{}", i)).unwrap_or(1.0);
+    let zero = header.get_float(&format!("TZERO{}", i)).unwrap_or(0.0);
+
+    (scale != 1.0 || zero != 0.0).then_some((scale, zero))
+}
+
+/// Read a BINTABLE from its header and its data, the heap included
+pub fn read_bintable(header: &Header, data: &[u8]) -> Result<Table, Error> {
+    let (row_size, num_rows, num_fields) = header.table_dims()?;
+    // The rows and the heap, without the padding of the data unit
+    let pcount = header.get_int("PCOUNT").unwrap_or(0);
+    let data_size = usize::try_from(pcount)
+        .ok()
+        .and_then(|pcount| (row_size * num_rows).checked_add(pcount))
+        .ok_or_else(|| Error::Fits {
+            reason: format!("invalid heap size {}", pcount),
+        })?;
+    let data = &data[..data_size.min(data.len())];
+    // The heap follows the rows, possibly after a gap
+    let heap_offset = match header.get_int("THEAP") {
+        Some(theap) => usize::try_from(theap)
+            .ok()
+            .filter(|&theap| theap >= row_size * num_rows)
+            .ok_or_else(|| Error::Fits {
+                reason: format!("invalid heap offset {}", theap),
+            })?,
+        None => row_size * num_rows,
+    };
+
+    let mut fields = Vec::with_capacity(num_fields);
+    let mut formats = Vec::with_capacity(num_fields);
+    let mut offset = 0;
+    for i in 1..=num_fields {
+        let key = |name: &str| format!("{}{}", name, i);
+        let string = |name: &str| header.get(&key(name)).map(str::to_string);
+
+        let tform = header.get(&key("TFORM")).ok_or_else(|| Error::Fits {
+            reason: format!("missing keyword TFORM{}", i),
+        })?;
+        let (repeat, datatype, descriptor) = parse_tform(tform)?;
+
+        let mut field = Field::new(get_column_name(header, i), datatype);
+        field.unit = string("TUNIT");
+        // Not a standard keyword but written by some tools
+        field.ucd = string("TUCD");
+        field.null = header.get_int(&key("TNULL"));
+        field.arraysize = if descriptor.is_some() {
+            Some("*".to_string())
+        } else if repeat != 1 || datatype == DataType::Char {
+            Some(repeat.to_string())
+        } else {
+            None
+        };
+
+        formats.push(ColumnFormat {
+            offset,
+            repeat,
+            descriptor,
+            scaling: get_scaling(header, i),
+        });
+        offset = offset.saturating_add(match descriptor {
+            Some(size) => 2 * size * repeat.min(1),
+            None => datatype.byte_size(repeat),
+        });
+        fields.push(field);
+    }
+
+    if offset > row_size || data.len() < row_size * num_rows {
+        return Err(Error::Truncated);
+    }
+
+    let mut columns = fields.iter().map(Column::new).collect::<Vec<_>>();
+    for row in data[..(row_size * num_rows)].chunks_exact(row_size) {
+        for ((field, format), column) in fields.iter().zip(&formats).zip(&mut columns) {
+            let cell = &row[format.offset..];
+
+            match format.descriptor {
+                Some(size) => {
+                    let int = |bytes: &[u8]| {
+                        let value = match size {
+                            4 => i32::from_be_bytes(bytes[..4].try_into().unwrap()) as i64,
+                            _ => i64::from_be_bytes(bytes[..8].try_into().unwrap()),
+                        };
+                        usize::try_from(value).map_err(|_| Error::Fits {
+                            reason: format!("invalid array descriptor {}", value),
+                        })
+                    };
+                    let count = int(cell)?;
+                    let start = heap_offset.checked_add(int(&cell[size..])?);
+                    // The array must lie in the heap
+                    let bytes = start
+                        .and_then(|start| {
+                            let end = start.checked_add(field.datatype.byte_size(count))?;
+                            data.get(start..end)
+                        })
+                        .ok_or(Error::Truncated)?;
+
+                    column.push_binary(field, bytes, count);
+                }
+                None => {
+                    let count = format.repeat;
+                    let bytes = &cell[..field.datatype.byte_size(count)];
+
+                    column.push_binary(field, bytes, count);
+                }
+            }
+        }
+    }
+
+    let scalings = formats.iter().map(|format| format.scaling);
+    scale_columns(&mut fields, &mut columns, scalings, num_rows);
+    guess_position_ucds(&mut fields);
+
+    Ok(Table {
+        name: header.get("EXTNAME").map(str::to_string),
+        fields,
+        columns,
+        num_rows,
+    })
+}
+
+// Parse a TFORMn of an ASCII table e.g. `A8`, `I6`, `F10.4`, `E15.7`, giving the width
+fn parse_ascii_tform(tform: &str) -> Result<(DataType, usize), Error> {
+    let invalid = || Error::Fits {
+        reason: format!("invalid TFORM {}", tform),
+    };
+    let tform = tform.trim();
+    let mut chars = tform.chars();
+    let code = chars.next().ok_or_else(invalid)?;
+    let width = chars
+        .as_str()
+        .split('.')
+        .next()
+        .and_then(|width| width.parse::<usize>().ok())
+        .ok_or_else(invalid)?;
+
+    let datatype = match code {
+        'A' => DataType::Char,
+        'I' if width < 10 => DataType::Int,
+        'I' => DataType::Long,
+        'E' => DataType::Float,
+        'F' | 'D' => DataType::Double,
+        _ => return Err(invalid()),
+    };
+
+    Ok((datatype, width))
+}
+
+/// Read an ASCII TABLE from its header and its data
+///
+/// The cells whose text is the TNULLn of their column are missing
+pub fn read_ascii_table(header: &Header, data: &[u8]) -> Result<Table, Error> {
+    let (row_size, num_rows, num_fields) = header.table_dims()?;
+
+    let mut fields = Vec::with_capacity(num_fields);
+    // The bytes of the cells in the rows and the text of their null value
+    let mut cells = Vec::with_capacity(num_fields);
+    for i in 1..=num_fields {
+        let missing = |key: &str| Error::Fits {
+            reason: format!("missing keyword {}{}", key, i),
+        };
+        let tform = header
+            .get(&format!("TFORM{}", i))
+            .ok_or_else(|| missing("TFORM"))?;
+        let (datatype, width) = parse_ascii_tform(tform)?;
+        // TBCOLn starts at 1
+        let start = header
+            .get_int(&format!("TBCOL{}", i))
+            .filter(|&tbcol| tbcol >= 1)
+            .ok_or_else(|| missing("TBCOL"))? as usize
+            - 1;
+        if start + width > row_size {
+            return Err(Error::Fits {
+                reason: format!("the column {} exceeds the rows", i),
+            });
+        }
+
+        let mut field = Field::new(get_column_name(header, i), datatype);
+        field.unit = header.get(&format!("TUNIT{}", i)).map(str::to_string);
+        field.ucd = header.get(&format!("TUCD{}", i)).map(str::to_string);
+        if datatype == DataType::Char {
+            field.arraysize = Some(width.to_string());
+        }
+
+        let null = header.get(&format!("TNULL{}", i)).map(str::trim);
+        cells.push((start..(start + width), null));
+        fields.push(field);
+    }
+
+    if data.len() < row_size * num_rows {
+        return Err(Error::Truncated);
+    }
+
+    let mut columns = fields.iter().map(Column::new).collect::<Vec<_>>();
+    for row in data[..(row_size * num_rows)].chunks_exact(row_size) {
+        for ((field, (range, null)), column) in fields.iter().zip(&cells).zip(&mut columns) {
+            let text = String::from_utf8_lossy(&row[range.clone()]);
+            let text = text.trim();
+
+            if Some(text) == *null {
+                column.push_null();
+            } else if field.datatype.is_text() {
+                column.push_text(field, text);
+            } else {
+                // The exponent of double precision numbers can be written with a D
+                column.push_text(field, &text.replace('D', "E"));
+            }
+        }
+    }
+
+    let scalings = (1..=num_fields).map(|i| get_scaling(header, i));
+    scale_columns(&mut fields, &mut columns, scalings, num_rows);
+    guess_position_ucds(&mut fields);
+
+    Ok(Table {
+        name: header.get("EXTNAME").map(str::to_string),
+        fields,
+        columns,
+        num_rows,
+    })
+}
+
+// The scaled columns, e.g. the unsigned integers, are given as doubles
+fn scale_columns<I>(fields: &mut [Field], columns: &mut [Column], scalings: I, num_rows: usize)
+where
+    I: Iterator<Item = Option<(f64, f64)>>,
+{
+    for ((field, scaling), column) in fields.iter_mut().zip(scalings).zip(columns) {
+        if let Some((scale, zero)) = scaling {
+            if field.array_size() == ArraySize::Scalar && !field.datatype.is_text() {
+                *column = scale_column(column, scale, zero, num_rows);
+                field.datatype = DataType::Double;
+                field.null = None;
+            }
+        }
+    }
+}
+
+// The usual names of the columns of the equatorial coordinates
+const RA_NAMES: &[&str] = &[
+    "RA",
+    "RA_ICRS",
+    "RAJ2000",
+    "RA_J2000",
+    "RA_DEG",
+    "RADEG",
+    "ALPHA_J2000",
+    "ALPHAWIN_J2000",
+];
+const DEC_NAMES: &[&str] = &[
+    "DEC",
+    "DE",
+    "DEC_ICRS",
+    "DE_ICRS",
+    "DEJ2000",
+    "DECJ2000",
+    "DEC_J2000",
+    "DEC_DEG",
+    "DEDEG",
+    "DELTA_J2000",
+    "DELTAWIN_J2000",
+];
+
+// Few FITS tables give the UCDs of their columns, the equatorial coordinates are
+// then found from their name
+fn guess_position_ucds(fields: &mut [Field]) {
+    if position_columns(fields.iter().map(|f| f.ucd.as_deref())).is_some() {
+        return;
+    }
+
+    let find = |names: &[&str]| {
+        fields.iter().position(|field| {
+            let is_scalar = field.array_size() == ArraySize::Scalar || field.datatype.is_text();
+            is_scalar
+                && names
+                    .iter()
+                    .any(|name| field.name.eq_ignore_ascii_case(name))
+        })
+    };
+    if let (Some(ra), Some(dec)) = (find(RA_NAMES), find(DEC_NAMES)) {
+        fields[ra].ucd = Some("pos.eq.ra;meta.main".to_string());
+        fields[dec].ucd = Some("pos.eq.dec;meta.main".to_string());
+    }
+}
+
+fn scale_column(column: &Column, scale: f64, zero: f64, num_rows: usize) -> Column {
+    let values = (0..num_rows)
+        .map(|row| {
+            column
+                .get_f64(row)
+                .map_or(f64::NAN, |value| zero + scale * value)
+        })
+        .collect();
+
+    Column {
+        values: super::Values::Double(values),
+        nulls: column.nulls.clone(),
+    }
+}
+
+/// Write a table in a FITS file, as a BINTABLE following an empty primary HDU
+///
+/// The texts are written in columns as wide as their longest value and the variable length
+/// arrays in the heap. The missing integers are given a TNULLn.
+pub fn write_bintable(table: &Table) -> Vec<u8> {
+    let mut fields = table.get_fields_with_nulls();
+    for field in &mut fields {
+        // The texts are written in ASCII
+        if field.datatype == DataType::UnicodeChar {
+            field.datatype = DataType::Char;
+        }
+    }
+
+    let rows = 0..table.num_rows;
+    let mut column_cards = vec![];
+    let mut columns = vec![];
+    for (i, (field, column)) in fields.iter().zip(&table.columns).enumerate() {
+        let key = |name: &str| format!("{}{}", name, i + 1);
+        let max_count = || {
+            rows.clone()
+                .map(|row| column.get_count(field, row))
+                .max()
+                .unwrap_or(0)
+        };
+
+        let code = tform_code(field.datatype);
+        let (tform, count) = match field.array_size() {
+            ArraySize::Fixed(count) => (format!("{}{}", count, code), Some(count)),
+            ArraySize::Scalar if field.datatype != DataType::Char => (code.to_string(), Some(1)),
+            // The texts are as wide as the longest
+            _ if field.datatype == DataType::Char => {
+                let width = max_count().max(1);
+                (format!("{}A", width), Some(width))
+            }
+            _ => (format!("1P{}({})", code, max_count()), None),
+        };
+
+        column_cards.push((key("TTYPE"), string_value(&field.name)));
+        column_cards.push((key("TFORM"), string_value(&tform)));
+        if let Some(unit) = &field.unit {
+            column_cards.push((key("TUNIT"), string_value(unit)));
+        }
+        if let Some(ucd) = &field.ucd {
+            column_cards.push((key("TUCD"), string_value(ucd)));
+        }
+        if let (Some(null), 'B' | 'I' | 'J' | 'K') = (field.null, code) {
+            column_cards.push((key("TNULL"), null.to_string()));
+        }
+
+        columns.push((field, column, count));
+    }
+
+    let mut data = vec![];
+    let mut heap = vec![];
+    for row in rows {
+        for &(field, column, count) in &columns {
+            match count {
+                Some(count) => column.write_binary(field, row, count, &mut data),
+                None => {
+                    let count = column.get_count(field, row);
+                    data.extend_from_slice(&(count as i32).to_be_bytes());
+                    data.extend_from_slice(&(heap.len() as i32).to_be_bytes());
+                    column.write_binary(field, row, count, &mut heap);
+                }
+            }
+        }
+    }
+    let row_size = data.len().checked_div(table.num_rows).unwrap_or_else(|| {
+        // The size of the rows of an empty table
+        columns
+            .iter()
+            .map(|(field, _, count)| count.map_or(8, |count| field.datatype.byte_size(count)))
+            .sum()
+    });
+
+    let mut cards = vec![
+        ("XTENSION".to_string(), string_value("BINTABLE")),
+        ("BITPIX".to_string(), "8".to_string()),
+        ("NAXIS".to_string(), "2".to_string()),
+        ("NAXIS1".to_string(), row_size.to_string()),
+        ("NAXIS2".to_string(), table.num_rows.to_string()),
+        ("PCOUNT".to_string(), heap.len().to_string()),
+        ("GCOUNT".to_string(), "1".to_string()),
+        ("TFIELDS".to_string(), fields.len().to_string()),
+    ];
+    cards.extend(column_cards);
+    if let Some(name) = &table.name {
+        cards.push(("EXTNAME".to_string(), string_value(name)));
+    }
+
+    let mut fits = write_header(&[
+        ("SIMPLE", "T".to_string()),
+        ("BITPIX", "8".to_string()),
+        ("NAXIS", "0".to_string()),
+        ("EXTEND", "T".to_string()),
+    ]);
+    fits.extend(write_header(&cards));
+    fits.extend(data);
+    fits.extend(heap);
+    fits.resize(fits.len().div_ceil(BLOCK_SIZE) * BLOCK_SIZE, 0);
+
+    fits
+}
+
+// A quoted string value, its characters not in ASCII being replaced
+fn string_value(value: &str) -> String {
+    let mut quoted = String::new();
+    for c in value.chars() {
+        let c = if c.is_ascii() && !c.is_ascii_control() {
+            c
+        } else {
+            '?'
+        };
+        // A quote is escaped by doubling it, the value must fit in the card
+        let width = if c == '\'' { 2 } else { 1 };
+        if quoted.len() + width > CARD_SIZE - 12 {
+            break;
+        }
+        if c == '\'' {
+            quoted.push(c);
+        }
+        quoted.push(c);
+    }
+
+    format!("'{:<8}'", quoted)
+}
+
+// The cards of a header followed by END, padded with spaces to a whole number of blocks
+fn write_header<K: AsRef<str>>(cards: &[(K, String)]) -> Vec<u8> {
+    let mut header = cards
+        .iter()
+        .map(|(key, value)| format!("{:<8}= {:<70}", key.as_ref(), value))
+        .collect::<String>();
+    header.push_str(&format!("{:<80}", "END"));
+    let padding = (BLOCK_SIZE - header.len() % BLOCK_SIZE) % BLOCK_SIZE;
+    header.push_str(&" ".repeat(padding));
+
+    header.into_bytes()
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::{
+        parse_bintable, parse_table, parse_value, read_ascii_table, write_bintable, Header,
+        BLOCK_SIZE,
+    };
+    use crate::table::{Column, DataType, Error, Field, Table, Values};
+
+    // Write a FITS file made of an empty primary HDU and a BINTABLE
+    pub fn bintable(cards: &[(&str, &str)], rows: &[u8]) -> Vec<u8> {
+        let header = |cards: &[(&str, &str)]| {
+            let mut header = cards
+                .iter()
+                .map(|(key, value)| format!("{:<8}= {:<70}", key, value))
+                .collect::<String>();
+            header.push_str(&format!("{:<80}", "END"));
+            let padding = (BLOCK_SIZE - header.len() % BLOCK_SIZE) % BLOCK_SIZE;
+            header.push_str(&" ".repeat(padding));
+
+            header.into_bytes()
+        };
+
+        let mut fits = header(&[("SIMPLE", "T"), ("BITPIX", "8"), ("NAXIS", "0")]);
+        fits.extend(header(cards));
+        fits.extend_from_slice(rows);
+        fits.resize(
+            fits.len() + (BLOCK_SIZE - rows.len() % BLOCK_SIZE) % BLOCK_SIZE,
+            0,
+        );
+
+        fits
+    }
+
+    #[test]
+    fn binary_table() {
+        let mut rows = vec![];
+        for &(ra, dec, id, name) in &[(10.5, -20.25, 1_i16, "M31 "), (200.0, 45.0, -1, "")] {
+            rows.extend_from_slice(&f64::to_be_bytes(ra));
+            rows.extend_from_slice(&f32::to_be_bytes(dec));
+            rows.extend_from_slice(&i16::to_be_bytes(id));
+            rows.extend_from_slice(format!("{:<4}", name).as_bytes());
+            // variable length array of 16 bits integers
+            rows.extend_from_slice(&i32::to_be_bytes(2));
+            rows.extend_from_slice(&i32::to_be_bytes(0));
+        }
+        // heap
+        rows.extend_from_slice(&[0, 1, 0, 2]);
+
+        let cards = [
+            ("XTENSION", "'BINTABLE'"),
+            ("BITPIX", "8"),
+            ("NAXIS", "2"),
+            ("NAXIS1", "26"),
+            ("NAXIS2", "2"),
+            ("PCOUNT", "4"),
+            ("GCOUNT", "1"),
+            ("TFIELDS", "5"),
+            ("TTYPE1", "'RA      '"),
+            ("TFORM1", "'D       '"),
+            ("TUCD1", "'pos.eq.ra;meta.main'"),
+            ("TTYPE2", "'DEC'"),
+            ("TFORM2", "'1E'"),
+            ("TUCD2", "'pos.eq.dec;meta.main'"),
+            ("TTYPE3", "'ID'"),
+            ("TFORM3", "'I'"),
+            ("TNULL3", "-1"),
+            ("TZERO3", "32768"),
+            ("TTYPE4", "'NAME'"),
+            ("TFORM4", "'4A'"),
+            ("TFORM5", "'1PI(2)'"),
+            ("EXTNAME", "'SOURCES'"),
+        ];
+        let fits = bintable(&cards, &rows);
+
+        let table = parse_bintable(&fits, 1).unwrap();
+        assert_eq!(table.name.as_deref(), Some("SOURCES"));
+        assert_eq!(table.num_rows, 2);
+        assert_eq!(table.position_columns(), Some((0, 1)));
+        assert_eq!(table.get_position(1, (0, 1)), Some((200.0, 45.0)));
+
+        assert_eq!(table.fields[2].datatype, DataType::Double);
+        assert_eq!(table.columns[2].get_f64(0), Some(32769.0));
+        assert!(table.columns[2].is_null(1));
+        assert_eq!(
+            table.columns[3].values,
+            Values::Str(vec!["M31".to_string(), "".to_string()])
+        );
+        assert_eq!(table.fields[4].name, "col_5");
+        assert_eq!(
+            table.columns[4].values,
+            Values::Array(vec![vec![1.0, 2.0], vec![1.0, 2.0]])
+        );
+
+        assert!(parse_bintable(&fits, 0).is_err());
+        assert!(parse_bintable(&fits, 2).is_err());
+        assert!(parse_bintable(&fits[..(2 * BLOCK_SIZE + 10)], 1).is_err());
+
+        // The heap offset and the array descriptors must point into the heap
+        for theap in &["-1", "51", "4", "9223372036854775807"] {
+            let mut cards = cards.to_vec();
+            cards.push(("THEAP", theap));
+            assert!(parse_bintable(&bintable(&cards, &rows), 1).is_err());
+        }
+        for &(count, offset) in &[(-1, 0), (2, -4), (2, 3), (i32::MAX, 0), (2, i32::MAX)] {
+            let mut rows = rows.clone();
+            rows[18..22].copy_from_slice(&count.to_be_bytes());
+            rows[22..26].copy_from_slice(&offset.to_be_bytes());
+            assert!(parse_bintable(&bintable(&cards, &rows), 1).is_err());
+        }
+    }
+
+    #[test]
+    fn ascii_table() {
+        let rows = format!(
+            "{:<6}{:>10}{:>8}{:>3}{:<6}{:>10}{:>8}{:>3}",
+            "M31", "10.68470", "4.127D1", "7", "NGC1", "200.0", "", "-99"
+        );
+        let cards = [
+            ("XTENSION", "'TABLE   '"),
+            ("BITPIX", "8"),
+            ("NAXIS", "2"),
+            ("NAXIS1", "27"),
+            ("NAXIS2", "2"),
+            ("PCOUNT", "0"),
+            ("GCOUNT", "1"),
+            ("TFIELDS", "4"),
+            ("TTYPE1", "'NAME'"),
+            ("TFORM1", "'A6'"),
+            ("TBCOL1", "1"),
+            ("TTYPE2", "'RA'"),
+            ("TFORM2", "'F10.5'"),
+            ("TBCOL2", "7"),
+            ("TTYPE3", "'DEJ2000'"),
+            ("TFORM3", "'E8.2'"),
+            ("TBCOL3", "17"),
+            ("TTYPE4", "'FLAG'"),
+            ("TFORM4", "'I3'"),
+            ("TBCOL4", "25"),
+            ("TNULL4", "'-99'"),
+            ("TSCAL4", "2.0"),
+        ];
+        let fits = bintable(&cards, rows.as_bytes());
+
+        let table = parse_table(&fits, 1).unwrap();
+        assert_eq!(table.num_rows, 2);
+        assert_eq!(
+            table.columns[0].values,
+            Values::Str(vec!["M31".to_string(), "NGC1".to_string()])
+        );
+        // The coordinates are found from the names of their columns
+        assert_eq!(table.position_columns(), Some((1, 2)));
+        let (ra, dec) = table.get_position(0, (1, 2)).unwrap();
+        assert_eq!(ra, 10.6847);
+        assert!((dec - 41.27).abs() < 1e-5);
+        assert!(table.columns[2].is_null(1));
+
+        assert_eq!(table.fields[3].datatype, DataType::Double);
+        assert_eq!(table.columns[3].get_f64(0), Some(14.0));
+        assert!(table.columns[3].is_null(1));
+
+        // The second row has no declination
+        let positions = table.get_positions().unwrap();
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].0, 0);
+        let columns = table.get_numerical_columns();
+        assert!(!columns.contains_key("NAME"));
+        assert_eq!(columns["FLAG"][0], 14.0);
+        assert!(columns["FLAG"][1].is_nan());
+
+        // The same table given by the keywords of its header
+        let header = Header::from_keywords(|key| {
+            cards
+                .iter()
+                .find(|(card, _)| *card == key)
+                .map(|(_, value)| parse_value(value))
+        });
+        let from_keywords = read_ascii_table(&header, rows.as_bytes()).unwrap();
+        assert_eq!(from_keywords.fields, table.fields);
+        assert_eq!(from_keywords.columns[3].get_f64(0), Some(14.0));
+
+        // The dimensions of the table are checked before reading its rows
+        for &(naxis1, naxis2) in &[("0", "2"), ("27", "-1"), ("4611686018427387904", "8")] {
+            let header = Header::from_keywords(|key| match key {
+                "NAXIS1" => Some(naxis1.to_string()),
+                "NAXIS2" => Some(naxis2.to_string()),
+                _ => cards
+                    .iter()
+                    .find(|(card, _)| *card == key)
+                    .map(|(_, value)| parse_value(value)),
+            });
+            assert!(matches!(
+                read_ascii_table(&header, rows.as_bytes()),
+                Err(Error::Fits { .. })
+            ));
+        }
+
+        assert!(parse_bintable(&fits, 1).is_err());
+        assert!(parse_table(&fits, 0).is_err());
+    }
+
+    #[test]
+    fn write_read() {
+        let field = |name: &str, datatype, arraysize: Option<&str>| {
+            let mut field = Field::new(name.to_string(), datatype);
+            field.arraysize = arraysize.map(str::to_string);
+            field
+        };
+        let mut fields = vec![
+            field("ra", DataType::Double, None),
+            field("id", DataType::Int, None),
+            field("name", DataType::UnicodeChar, Some("*")),
+            field("flux", DataType::Short, Some("*")),
+            field("flags", DataType::Bit, Some("3")),
+            field("flag", DataType::Boolean, None),
+        ];
+        fields[0].ucd = Some("pos.eq.ra;meta.main".to_string());
+        fields[0].unit = Some("deg".to_string());
+
+        let mut columns = fields.iter().map(Column::new).collect::<Vec<_>>();
+        let rows = [
+            ["10.5", "1", "M31", "1 2 3", "101", "T"],
+            ["-Inf", "", "Andromeda's", "NaN", "011", "F"],
+        ];
+        for row in &rows {
+            for ((field, column), value) in fields.iter().zip(&mut columns).zip(row) {
+                column.push_text(field, value);
+            }
+        }
+        let table = Table {
+            name: Some("sources".to_string()),
+            fields,
+            columns,
+            num_rows: rows.len(),
+        };
+
+        let fits = write_bintable(&table);
+        assert_eq!(fits.len() % BLOCK_SIZE, 0);
+        let read = parse_bintable(&fits, 1).unwrap();
+        assert_eq!(read.name.as_deref(), Some("sources"));
+        assert_eq!(read.num_rows, 2);
+        assert_eq!(read.fields[0].unit.as_deref(), Some("deg"));
+        assert_eq!(read.fields[0].ucd, table.fields[0].ucd);
+        assert_eq!(read.fields[1].null, Some(i32::MIN as i64));
+        assert_eq!(read.fields[2].datatype, DataType::Char);
+        assert_eq!(read.fields[2].arraysize.as_deref(), Some("11"));
+
+        assert_eq!(
+            read.columns[0].values,
+            Values::Double(vec![10.5, f64::NEG_INFINITY])
+        );
+        assert_eq!(read.columns[1].get_f64(0), Some(1.0));
+        assert!(read.columns[1].is_null(1));
+        assert_eq!(
+            read.columns[2].values,
+            Values::Str(vec!["M31".to_string(), "Andromeda's".to_string()])
+        );
+        assert_eq!(
+            read.columns[3].get_text(&read.fields[3], 0).unwrap(),
+            "1 2 3"
+        );
+        assert_eq!(read.columns[3].get_text(&read.fields[3], 1).unwrap(), "NaN");
+        assert_eq!(
+            read.columns[4].values,
+            Values::Array(vec![vec![1.0, 0.0, 1.0], vec![0.0, 1.0, 1.0]])
+        );
+        assert_eq!(read.columns[5].values, Values::Boolean(vec![1, 0]));
+    }
+}
@@ -0,0 +1,1100 @@
+//! Tables of sources read from VOTables and FITS binary tables
+//!
+//! The values are stored column by column, in vectors typed after the datatype of the
+//! column, so that they can be given to javascript as typed arrays. The equatorial
+//! coordinates are found from the UCDs of the columns. The tables can be written back
+//! to VOTables, CSV and FITS binary tables.
+mod base64;
+pub mod csv;
+pub mod fits;
+pub mod votable;
+pub mod xml;
+
+use std::collections::HashMap;
+use std::convert::{TryFrom, TryInto};
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::{JsCast, JsValue};
+
+use crate::math::angle::Angle;
+use crate::math::lonlat::LonLatT;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Xml {
+        reason: &'static str,
+        position: usize,
+    },
+    InvalidDatatype {
+        datatype: String,
+    },
+    UnsupportedStream {
+        reason: String,
+    },
+    Base64,
+    // A negative number of values in a binary stream
+    InvalidCount {
+        count: i32,
+    },
+    Truncated,
+    Fits {
+        reason: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Xml { reason, position } => {
+                write!(f, "Invalid XML at byte {}: {}", position, reason)
+            }
+            Error::InvalidDatatype { datatype } => write!(f, "Invalid datatype {:?}", datatype),
+            Error::UnsupportedStream { reason } => write!(f, "Unsupported stream: {}", reason),
+            Error::Base64 => write!(f, "Invalid base64 stream"),
+            Error::InvalidCount { count } => {
+                write!(f, "Invalid number of values {} in a binary stream", count)
+            }
+            Error::Truncated => write!(f, "The data of the table are truncated"),
+            Error::Fits { reason } => write!(f, "Invalid FITS table: {}", reason),
+        }
+    }
+}
+
+impl From<Error> for JsValue {
+    fn from(err: Error) -> Self {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+/// The VOTable datatypes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DataType {
+    Boolean,
+    Bit,
+    UnsignedByte,
+    Short,
+    Int,
+    Long,
+    Char,
+    UnicodeChar,
+    Float,
+    Double,
+    FloatComplex,
+    DoubleComplex,
+}
+
+impl DataType {
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        Ok(match name {
+            "boolean" => DataType::Boolean,
+            "bit" => DataType::Bit,
+            "unsignedByte" => DataType::UnsignedByte,
+            "short" => DataType::Short,
+            "int" => DataType::Int,
+            "long" => DataType::Long,
+            "char" => DataType::Char,
+            "unicodeChar" => DataType::UnicodeChar,
+            "float" => DataType::Float,
+            "double" => DataType::Double,
+            "floatComplex" => DataType::FloatComplex,
+            "doubleComplex" => DataType::DoubleComplex,
+            _ => {
+                return Err(Error::InvalidDatatype {
+                    datatype: name.to_string(),
+                })
+            }
+        })
+    }
+
+    /// The name of the datatype in a VOTable
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataType::Boolean => "boolean",
+            DataType::Bit => "bit",
+            DataType::UnsignedByte => "unsignedByte",
+            DataType::Short => "short",
+            DataType::Int => "int",
+            DataType::Long => "long",
+            DataType::Char => "char",
+            DataType::UnicodeChar => "unicodeChar",
+            DataType::Float => "float",
+            DataType::Double => "double",
+            DataType::FloatComplex => "floatComplex",
+            DataType::DoubleComplex => "doubleComplex",
+        }
+    }
+
+    /// The number of bytes of `count` values serialized in binary
+    pub fn byte_size(&self, count: usize) -> usize {
+        // Saturates for the counts read from a corrupted stream
+        match self {
+            DataType::Bit => count.div_ceil(8),
+            DataType::Boolean | DataType::UnsignedByte | DataType::Char => count,
+            DataType::Short | DataType::UnicodeChar => count.saturating_mul(2),
+            DataType::Int | DataType::Float => count.saturating_mul(4),
+            DataType::Long | DataType::Double | DataType::FloatComplex => count.saturating_mul(8),
+            DataType::DoubleComplex => count.saturating_mul(16),
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        matches!(self, DataType::Char | DataType::UnicodeChar)
+    }
+}
+
+/// The number of values of a cell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArraySize {
+    Scalar,
+    Fixed(usize),
+    /// Given for each row
+    Variable,
+}
+
+/// A column of a table
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "ID")]
+    pub id: Option<String>,
+    pub ucd: Option<String>,
+    pub unit: Option<String>,
+    pub datatype: DataType,
+    pub arraysize: Option<String>,
+    pub description: Option<String>,
+    /// The value standing for a missing integer
+    #[serde(skip)]
+    pub null: Option<i64>,
+}
+
+impl Field {
+    pub fn new(name: String, datatype: DataType) -> Self {
+        Self {
+            name,
+            id: None,
+            ucd: None,
+            unit: None,
+            datatype,
+            arraysize: None,
+            description: None,
+            null: None,
+        }
+    }
+
+    /// The number of values of a cell, `arraysize` being a product of dimensions
+    /// whose last one may be variable e.g. `3x*`
+    pub fn array_size(&self) -> ArraySize {
+        match self.arraysize.as_deref().map(str::trim) {
+            None | Some("") => ArraySize::Scalar,
+            Some(size) if size.contains('*') => ArraySize::Variable,
+            Some(size) => {
+                let size = size
+                    .split('x')
+                    .map(|dim| dim.trim().parse::<usize>().unwrap_or(1))
+                    .product();
+                ArraySize::Fixed(size)
+            }
+        }
+    }
+}
+
+/// The values of a column
+#[derive(Debug, Clone, PartialEq)]
+pub enum Values {
+    /// 1 for true, 0 for false
+    Boolean(Vec<u8>),
+    UnsignedByte(Vec<u8>),
+    Short(Vec<i16>),
+    Int(Vec<i32>),
+    Long(Vec<i64>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+    /// The char and unicodeChar columns
+    Str(Vec<String>),
+    /// The cells holding several numbers, the complex numbers being given
+    /// as their real and imaginary parts
+    Array(Vec<Vec<f64>>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Column {
+    pub values: Values,
+    /// Tells which rows have no value, only allocated once one is found
+    pub nulls: Option<Vec<bool>>,
+}
+
+impl Column {
+    pub fn new(field: &Field) -> Self {
+        let scalar = matches!(field.array_size(), ArraySize::Scalar | ArraySize::Fixed(1));
+        let values = match field.datatype {
+            DataType::Char | DataType::UnicodeChar => Values::Str(vec![]),
+            DataType::Boolean if scalar => Values::Boolean(vec![]),
+            DataType::Bit | DataType::UnsignedByte if scalar => Values::UnsignedByte(vec![]),
+            DataType::Short if scalar => Values::Short(vec![]),
+            DataType::Int if scalar => Values::Int(vec![]),
+            DataType::Long if scalar => Values::Long(vec![]),
+            DataType::Float if scalar => Values::Float(vec![]),
+            DataType::Double if scalar => Values::Double(vec![]),
+            _ => Values::Array(vec![]),
+        };
+
+        Self {
+            values,
+            nulls: None,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.values {
+            Values::Boolean(v) | Values::UnsignedByte(v) => v.len(),
+            Values::Short(v) => v.len(),
+            Values::Int(v) => v.len(),
+            Values::Long(v) => v.len(),
+            Values::Float(v) => v.len(),
+            Values::Double(v) => v.len(),
+            Values::Str(v) => v.len(),
+            Values::Array(v) => v.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Tells whether the value of a row is missing
+    pub fn is_null(&self, row: usize) -> bool {
+        self.nulls.as_ref().is_some_and(|nulls| nulls[row])
+    }
+
+    /// The value of a row as a number, None if it is missing or not a number
+    pub fn get_f64(&self, row: usize) -> Option<f64> {
+        if self.is_null(row) {
+            return None;
+        }
+
+        let value = match &self.values {
+            Values::Boolean(v) | Values::UnsignedByte(v) => v[row] as f64,
+            Values::Short(v) => v[row] as f64,
+            Values::Int(v) => v[row] as f64,
+            Values::Long(v) => v[row] as f64,
+            Values::Float(v) => v[row] as f64,
+            Values::Double(v) => v[row],
+            Values::Str(v) => v[row].trim().parse().ok()?,
+            Values::Array(_) => return None,
+        };
+
+        Some(value).filter(|v| !v.is_nan())
+    }
+
+    /// Add a missing value
+    pub fn push_null(&mut self) {
+        let row = self.len();
+        self.nulls
+            .get_or_insert_with(|| vec![false; row])
+            .push(true);
+
+        match &mut self.values {
+            Values::Boolean(v) | Values::UnsignedByte(v) => v.push(0),
+            Values::Short(v) => v.push(0),
+            Values::Int(v) => v.push(0),
+            Values::Long(v) => v.push(0),
+            Values::Float(v) => v.push(f32::NAN),
+            Values::Double(v) => v.push(f64::NAN),
+            Values::Str(v) => v.push(String::new()),
+            Values::Array(v) => v.push(vec![]),
+        }
+    }
+
+    fn push_valid(&mut self) {
+        if let Some(nulls) = self.nulls.as_mut() {
+            nulls.push(false);
+        }
+    }
+
+    /// Add a value given as a text, as in the TABLEDATA of a VOTable
+    ///
+    /// An empty text, or a text that cannot be parsed, gives a missing value
+    pub fn push_text(&mut self, field: &Field, text: &str) {
+        let value = text.trim();
+        if value.is_empty() && !field.datatype.is_text() {
+            return self.push_null();
+        }
+
+        let is_null_int = |v: i64| field.null == Some(v);
+        let pushed = match &mut self.values {
+            Values::Boolean(v) => parse_boolean(value).map(|b| v.push(b as u8)),
+            Values::UnsignedByte(v) => parse_int(value)
+                .filter(|&i| !is_null_int(i))
+                .map(|i| v.push(i as u8)),
+            Values::Short(v) => parse_int(value)
+                .filter(|&i| !is_null_int(i))
+                .map(|i| v.push(i as i16)),
+            Values::Int(v) => parse_int(value)
+                .filter(|&i| !is_null_int(i))
+                .map(|i| v.push(i as i32)),
+            Values::Long(v) => parse_int(value)
+                .filter(|&i| !is_null_int(i))
+                .map(|i| v.push(i)),
+            Values::Float(v) => value.parse::<f32>().ok().map(|f| v.push(f)),
+            Values::Double(v) => value.parse::<f64>().ok().map(|f| v.push(f)),
+            Values::Str(v) => {
+                v.push(value.to_string());
+                Some(())
+            }
+            Values::Array(v) => {
+                let values = match field.datatype {
+                    // The bits may not be separated
+                    DataType::Bit => value
+                        .chars()
+                        .filter(|c| !c.is_whitespace())
+                        .map(|c| if c == '1' { 1.0 } else { 0.0 })
+                        .collect(),
+                    DataType::Boolean => value
+                        .split_whitespace()
+                        .map(|b| parse_boolean(b).map_or(f64::NAN, |b| b as u8 as f64))
+                        .collect(),
+                    _ => value
+                        .split_whitespace()
+                        .map(|x| match parse_int(x) {
+                            Some(i) if !is_null_int(i) => i as f64,
+                            Some(_) => f64::NAN,
+                            None => x.parse::<f64>().unwrap_or(f64::NAN),
+                        })
+                        .collect(),
+                };
+                v.push(values);
+                Some(())
+            }
+        };
+
+        match pushed {
+            Some(()) => self.push_valid(),
+            None => self.push_null(),
+        }
+    }
+
+    /// Add a value serialized in big endian binary
+    ///
+    /// # Arguments
+    ///
+    /// * `field` - The field of the column
+    /// * `bytes` - The `field.datatype.byte_size(count)` bytes of the value
+    /// * `count` - The number of values of the cell
+    pub fn push_binary(&mut self, field: &Field, bytes: &[u8], count: usize) {
+        let is_null_int = |v: i64| field.null == Some(v);
+
+        if count == 0 && !field.datatype.is_text() {
+            return match &mut self.values {
+                Values::Array(v) => {
+                    v.push(vec![]);
+                    self.push_valid();
+                }
+                _ => self.push_null(),
+            };
+        }
+
+        let pushed = match &mut self.values {
+            Values::Boolean(v) => parse_boolean_byte(bytes[0]).map(|b| v.push(b as u8)),
+            Values::UnsignedByte(v) => {
+                let byte = if field.datatype == DataType::Bit {
+                    bytes[0] >> 7
+                } else {
+                    bytes[0]
+                };
+                Some(byte)
+                    .filter(|&b| !is_null_int(b as i64))
+                    .map(|b| v.push(b))
+            }
+            Values::Short(v) => Some(i16::from_be_bytes([bytes[0], bytes[1]]))
+                .filter(|&i| !is_null_int(i as i64))
+                .map(|i| v.push(i)),
+            Values::Int(v) => Some(i32::from_be_bytes(bytes[..4].try_into().unwrap()))
+                .filter(|&i| !is_null_int(i as i64))
+                .map(|i| v.push(i)),
+            Values::Long(v) => Some(i64::from_be_bytes(bytes[..8].try_into().unwrap()))
+                .filter(|&i| !is_null_int(i))
+                .map(|i| v.push(i)),
+            Values::Float(v) => {
+                v.push(f32::from_be_bytes(bytes[..4].try_into().unwrap()));
+                Some(())
+            }
+            Values::Double(v) => {
+                v.push(f64::from_be_bytes(bytes[..8].try_into().unwrap()));
+                Some(())
+            }
+            Values::Str(v) => {
+                let text: String = if field.datatype == DataType::UnicodeChar {
+                    let chars = bytes
+                        .chunks_exact(2)
+                        .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                        .take_while(|&c| c != 0);
+                    char::decode_utf16(chars)
+                        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                        .collect()
+                } else {
+                    // Latin-1
+                    bytes
+                        .iter()
+                        .take_while(|&&b| b != 0)
+                        .map(|&b| b as char)
+                        .collect()
+                };
+                v.push(text.trim_end().to_string());
+                Some(())
+            }
+            Values::Array(v) => {
+                v.push(binary_to_f64(field, bytes, count));
+                Some(())
+            }
+        };
+
+        match pushed {
+            Some(()) => self.push_valid(),
+            None => self.push_null(),
+        }
+    }
+
+    /// The number of values of a cell, the number of characters for the texts and of
+    /// complex numbers for the complex columns
+    pub fn get_count(&self, field: &Field, row: usize) -> usize {
+        match &self.values {
+            Values::Str(v) if field.datatype == DataType::UnicodeChar => {
+                v[row].encode_utf16().count()
+            }
+            Values::Str(v) => v[row].chars().count(),
+            Values::Array(v) => match field.datatype {
+                DataType::FloatComplex | DataType::DoubleComplex => v[row].len() / 2,
+                _ => v[row].len(),
+            },
+            _ => 1,
+        }
+    }
+
+    /// The value of a row as in the TABLEDATA of a VOTable, None if it is missing
+    pub fn get_text(&self, field: &Field, row: usize) -> Option<String> {
+        if self.is_null(row) {
+            return None;
+        }
+
+        match &self.values {
+            Values::Boolean(v) => Some(if v[row] != 0 { "T" } else { "F" }.to_string()),
+            Values::UnsignedByte(v) => Some(v[row].to_string()),
+            Values::Short(v) => Some(v[row].to_string()),
+            Values::Int(v) => Some(v[row].to_string()),
+            Values::Long(v) => Some(v[row].to_string()),
+            Values::Float(v) => Some(v[row]).filter(|v| !v.is_nan()).map(format_float),
+            Values::Double(v) => Some(v[row]).filter(|v| !v.is_nan()).map(format_float),
+            Values::Str(v) => Some(v[row].clone()),
+            Values::Array(v) => {
+                let values = v[row].iter().map(|&value| match field.datatype {
+                    DataType::Boolean if value.is_nan() => "?".to_string(),
+                    DataType::Boolean if value != 0.0 => "T".to_string(),
+                    DataType::Boolean => "F".to_string(),
+                    DataType::Float | DataType::FloatComplex => format_float(value as f32),
+                    _ => format_float(value),
+                });
+
+                Some(values.collect::<Vec<_>>().join(" "))
+            }
+        }
+    }
+
+    /// Write the value of a row serialized in big endian binary, as read by `push_binary`
+    ///
+    /// The `field.datatype.byte_size(count)` bytes of the value are written, the values
+    /// being truncated or padded with zeros. The missing integers are written as the
+    /// `null` of the field, or 0 if it has none.
+    pub fn write_binary(&self, field: &Field, row: usize, count: usize, bytes: &mut Vec<u8>) {
+        let start = bytes.len();
+        let null = self.is_null(row);
+        let int = |v: i64| if null { field.null.unwrap_or(0) } else { v };
+
+        match &self.values {
+            Values::Boolean(v) => bytes.push(match v[row] {
+                _ if null => 0,
+                0 => b'F',
+                _ => b'T',
+            }),
+            Values::UnsignedByte(v) => {
+                let byte = int(v[row] as i64) as u8;
+                bytes.push(if field.datatype == DataType::Bit {
+                    byte << 7
+                } else {
+                    byte
+                });
+            }
+            Values::Short(v) => bytes.extend_from_slice(&(int(v[row] as i64) as i16).to_be_bytes()),
+            Values::Int(v) => bytes.extend_from_slice(&(int(v[row] as i64) as i32).to_be_bytes()),
+            Values::Long(v) => bytes.extend_from_slice(&int(v[row]).to_be_bytes()),
+            Values::Float(v) => {
+                let value = if null { f32::NAN } else { v[row] };
+                bytes.extend_from_slice(&value.to_be_bytes());
+            }
+            Values::Double(v) => {
+                let value = if null { f64::NAN } else { v[row] };
+                bytes.extend_from_slice(&value.to_be_bytes());
+            }
+            Values::Str(v) => {
+                let text = if null { "" } else { &v[row] };
+                if field.datatype == DataType::UnicodeChar {
+                    for c in text.encode_utf16().take(count) {
+                        bytes.extend_from_slice(&c.to_be_bytes());
+                    }
+                } else {
+                    // Latin-1
+                    bytes.extend(
+                        text.chars()
+                            .take(count)
+                            .map(|c| u8::try_from(c as u32).unwrap_or(b'?')),
+                    );
+                }
+            }
+            Values::Array(v) => {
+                let values = if null { &[][..] } else { &v[row][..] };
+                f64_to_binary(field, values, count, bytes);
+            }
+        }
+
+        bytes.resize(start + field.datatype.byte_size(count), 0);
+    }
+}
+
+// Write `count` values of a cell, the missing ones being NaN
+fn f64_to_binary(field: &Field, values: &[f64], count: usize, bytes: &mut Vec<u8>) {
+    let num_values = match field.datatype {
+        DataType::FloatComplex | DataType::DoubleComplex => 2 * count,
+        _ => count,
+    };
+    let values = (0..num_values).map(|i| values.get(i).copied().unwrap_or(f64::NAN));
+    let int = |v: f64| {
+        if v.is_nan() {
+            field.null.unwrap_or(0)
+        } else {
+            v as i64
+        }
+    };
+
+    match field.datatype {
+        DataType::Bit => {
+            let bits = values.collect::<Vec<_>>();
+            for byte in bits.chunks(8) {
+                let byte = byte
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &bit)| bit == 1.0)
+                    .fold(0_u8, |byte, (i, _)| byte | (0x80 >> i));
+                bytes.push(byte);
+            }
+        }
+        DataType::Boolean => bytes.extend(values.map(|v| match v {
+            _ if v.is_nan() => 0,
+            _ if v != 0.0 => b'T',
+            _ => b'F',
+        })),
+        DataType::UnsignedByte => bytes.extend(values.map(|v| int(v) as u8)),
+        DataType::Short => {
+            values.for_each(|v| bytes.extend_from_slice(&(int(v) as i16).to_be_bytes()))
+        }
+        DataType::Int => {
+            values.for_each(|v| bytes.extend_from_slice(&(int(v) as i32).to_be_bytes()))
+        }
+        DataType::Long => values.for_each(|v| bytes.extend_from_slice(&int(v).to_be_bytes())),
+        DataType::Float | DataType::FloatComplex => {
+            values.for_each(|v| bytes.extend_from_slice(&(v as f32).to_be_bytes()))
+        }
+        DataType::Double | DataType::DoubleComplex => {
+            values.for_each(|v| bytes.extend_from_slice(&v.to_be_bytes()))
+        }
+        DataType::Char | DataType::UnicodeChar => (),
+    }
+}
+
+// A number as written in a VOTable, the infinities being +Inf and -Inf
+fn format_float<T: Into<f64> + ToString + Copy>(value: T) -> String {
+    match value.to_string() {
+        _ if value.into() == f64::INFINITY => "+Inf".to_string(),
+        _ if value.into() == f64::NEG_INFINITY => "-Inf".to_string(),
+        text => text,
+    }
+}
+
+impl Values {
+    // The values of some rows
+    fn select(&self, rows: &[usize]) -> Self {
+        fn select<T: Clone>(values: &[T], rows: &[usize]) -> Vec<T> {
+            rows.iter().map(|&row| values[row].clone()).collect()
+        }
+
+        match self {
+            Values::Boolean(v) => Values::Boolean(select(v, rows)),
+            Values::UnsignedByte(v) => Values::UnsignedByte(select(v, rows)),
+            Values::Short(v) => Values::Short(select(v, rows)),
+            Values::Int(v) => Values::Int(select(v, rows)),
+            Values::Long(v) => Values::Long(select(v, rows)),
+            Values::Float(v) => Values::Float(select(v, rows)),
+            Values::Double(v) => Values::Double(select(v, rows)),
+            Values::Str(v) => Values::Str(select(v, rows)),
+            Values::Array(v) => Values::Array(select(v, rows)),
+        }
+    }
+}
+
+fn binary_to_f64(field: &Field, bytes: &[u8], count: usize) -> Vec<f64> {
+    let int = |v: i64| {
+        if field.null == Some(v) {
+            f64::NAN
+        } else {
+            v as f64
+        }
+    };
+
+    match field.datatype {
+        DataType::Bit => (0..count)
+            .map(|i| ((bytes[i / 8] >> (7 - i % 8)) & 1) as f64)
+            .collect(),
+        DataType::Boolean => bytes
+            .iter()
+            .map(|&b| parse_boolean_byte(b).map_or(f64::NAN, |b| b as u8 as f64))
+            .collect(),
+        DataType::UnsignedByte => bytes.iter().map(|&b| int(b as i64)).collect(),
+        DataType::Short => bytes
+            .chunks_exact(2)
+            .map(|c| int(i16::from_be_bytes([c[0], c[1]]) as i64))
+            .collect(),
+        DataType::Int => bytes
+            .chunks_exact(4)
+            .map(|c| int(i32::from_be_bytes(c.try_into().unwrap()) as i64))
+            .collect(),
+        DataType::Long => bytes
+            .chunks_exact(8)
+            .map(|c| int(i64::from_be_bytes(c.try_into().unwrap())))
+            .collect(),
+        DataType::Float | DataType::FloatComplex => bytes
+            .chunks_exact(4)
+            .map(|c| f32::from_be_bytes(c.try_into().unwrap()) as f64)
+            .collect(),
+        DataType::Double | DataType::DoubleComplex => bytes
+            .chunks_exact(8)
+            .map(|c| f64::from_be_bytes(c.try_into().unwrap()))
+            .collect(),
+        DataType::Char | DataType::UnicodeChar => vec![],
+    }
+}
+
+fn parse_boolean(value: &str) -> Option<bool> {
+    match value {
+        "T" | "t" | "1" | "true" | "TRUE" | "True" => Some(true),
+        "F" | "f" | "0" | "false" | "FALSE" | "False" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_boolean_byte(byte: u8) -> Option<bool> {
+    match byte {
+        b'T' | b't' | b'1' => Some(true),
+        b'F' | b'f' | b'0' => Some(false),
+        _ => None,
+    }
+}
+
+// An integer in decimal or hexadecimal
+fn parse_int(value: &str) -> Option<i64> {
+    match value
+        .strip_prefix("0x")
+        .or_else(|| value.strip_prefix("0X"))
+    {
+        Some(hex) => u64::from_str_radix(hex, 16).ok().map(|u| u as i64),
+        None => value.parse::<i64>().ok(),
+    }
+}
+
+/// A coordinate in decimal degrees or sexagesimal, in hours for the right ascension
+pub fn parse_coordinate(value: &str, hours: bool) -> Option<f64> {
+    let value = value.trim();
+    if let Ok(degrees) = value.parse::<f64>() {
+        return Some(degrees);
+    }
+
+    let (sign, unsigned) = match value.strip_prefix('-') {
+        Some(unsigned) => (-1.0, unsigned),
+        None => (1.0, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let mut angle = 0.0;
+    let mut num_parts = 0;
+    for (i, part) in unsigned
+        .split(|c: char| c == ':' || c.is_whitespace())
+        .filter(|part| !part.is_empty())
+        .enumerate()
+    {
+        if i > 2 {
+            return None;
+        }
+        angle += part.parse::<f64>().ok()? / 60f64.powi(i as i32);
+        num_parts += 1;
+    }
+    if num_parts < 2 {
+        return None;
+    }
+
+    Some(sign * angle * if hours { 15.0 } else { 1.0 })
+}
+
+/// The indices of the columns of the equatorial coordinates, found from their UCD
+///
+/// The main position (`meta.main`) is preferred when several are given
+pub fn position_columns<'a, I>(ucds: I) -> Option<(usize, usize)>
+where
+    I: Iterator<Item = Option<&'a str>> + Clone,
+{
+    let find = |prefixes: &[&str]| {
+        let mut matching = ucds
+            .clone()
+            .map(|ucd| ucd.filter(|ucd| prefixes.iter().any(|prefix| ucd.starts_with(prefix))));
+
+        matching
+            .clone()
+            .position(|ucd| ucd.is_some_and(|ucd| ucd.contains("meta.main")))
+            .or_else(|| matching.position(|ucd| ucd.is_some()))
+    };
+
+    Some((
+        find(&["pos.eq.ra", "POS_EQ_RA"])?,
+        find(&["pos.eq.dec", "POS_EQ_DEC"])?,
+    ))
+}
+
+/// A table, its fields and the values of its columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub name: Option<String>,
+    pub fields: Vec<Field>,
+    pub columns: Vec<Column>,
+    pub num_rows: usize,
+}
+
+impl Table {
+    /// The indices of the columns of the right ascension and the declination
+    pub fn position_columns(&self) -> Option<(usize, usize)> {
+        position_columns(self.fields.iter().map(|f| f.ucd.as_deref()))
+    }
+
+    /// The position of a row, in degrees
+    pub fn get_position(&self, row: usize, (ra, dec): (usize, usize)) -> Option<(f64, f64)> {
+        let coordinate = |col: usize, hours: bool| {
+            let column = &self.columns[col];
+            match &column.values {
+                Values::Str(v) if !column.is_null(row) => parse_coordinate(&v[row], hours),
+                _ => column.get_f64(row),
+            }
+        };
+
+        Some((coordinate(ra, true)?, coordinate(dec, false)?))
+    }
+
+    /// The positions of the sources, in radians, for the catalog manager
+    ///
+    /// The rows without a position are skipped
+    pub fn get_sources(&self) -> Option<Box<[LonLatT<f32>]>> {
+        let sources = self
+            .get_positions()?
+            .into_iter()
+            .map(|(_, LonLatT(lon, lat))| LonLatT::new(Angle(lon.0 as f32), Angle(lat.0 as f32)))
+            .collect();
+
+        Some(sources)
+    }
+
+    /// The positions of the sources, in radians, with their row
+    ///
+    /// The rows without a position are skipped
+    pub fn get_positions(&self) -> Option<Vec<(u32, LonLatT<f64>)>> {
+        let columns = self.position_columns()?;
+
+        let positions = (0..self.num_rows)
+            .filter_map(|row| Some((row as u32, self.get_position(row, columns)?)))
+            .map(|(row, (ra, dec))| {
+                let lonlat = LonLatT::new(Angle(ra.to_radians()), Angle(dec.to_radians()));
+
+                (row, lonlat)
+            })
+            .collect();
+
+        Some(positions)
+    }
+
+    /// The numerical columns indexed by their name, for the catalog manager
+    ///
+    /// The null values are NaN, the text and array columns are skipped
+    pub fn get_numerical_columns(&self) -> HashMap<String, Box<[f32]>> {
+        self.fields
+            .iter()
+            .zip(&self.columns)
+            .filter(|(_, column)| !matches!(column.values, Values::Str(_) | Values::Array(_)))
+            .map(|(field, column)| {
+                let values = (0..self.num_rows)
+                    .map(|row| column.get_f64(row).map_or(f32::NAN, |value| value as f32))
+                    .collect();
+
+                (field.name.clone(), values)
+            })
+            .collect()
+    }
+
+    /// The fields, given a value standing for the missing integers of the columns that
+    /// have some but no such value, so that they can be written in binary
+    pub fn get_fields_with_nulls(&self) -> Vec<Field> {
+        self.fields
+            .iter()
+            .zip(&self.columns)
+            .map(|(field, column)| {
+                let has_nulls = match &column.values {
+                    Values::Array(v) => v.iter().flatten().any(|v| v.is_nan()),
+                    _ => column.nulls.as_ref().is_some_and(|n| n.contains(&true)),
+                };
+
+                let null = match field.datatype {
+                    DataType::UnsignedByte => u8::MAX as i64,
+                    DataType::Short => i16::MIN as i64,
+                    DataType::Int => i32::MIN as i64,
+                    DataType::Long => i64::MIN,
+                    _ => return field.clone(),
+                };
+
+                let mut field = field.clone();
+                if has_nulls && field.null.is_none() {
+                    field.null = Some(null);
+                }
+                field
+            })
+            .collect()
+    }
+
+    /// The table made of some of its rows, in the order given
+    ///
+    /// The rows that are not in the table are skipped
+    pub fn select_rows(&self, rows: &[u32]) -> Self {
+        let rows = rows
+            .iter()
+            .map(|&row| row as usize)
+            .filter(|&row| row < self.num_rows)
+            .collect::<Vec<_>>();
+
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| Column {
+                values: column.values.select(&rows),
+                nulls: column
+                    .nulls
+                    .as_ref()
+                    .map(|nulls| rows.iter().map(|&row| nulls[row]).collect()),
+            })
+            .collect();
+
+        Self {
+            name: self.name.clone(),
+            fields: self.fields.clone(),
+            columns,
+            num_rows: rows.len(),
+        }
+    }
+
+    /// Convert the table to a javascript object
+    ///
+    /// The object has the `name` of the table, its `fields`, the number of rows `numRows`,
+    /// the `columns` as typed arrays (arrays of strings for the text columns), the `nulls`
+    /// of each column as an Uint8Array or null, and the indices `ra` and `dec` of the
+    /// columns of the coordinates if found
+    pub fn to_js(&self) -> Result<JsValue, JsValue> {
+        let table = js_sys::Object::new();
+        let set = |key: &str, value: &JsValue| {
+            js_sys::Reflect::set(&table, &JsValue::from_str(key), value).map(|_| ())
+        };
+
+        set(
+            "name",
+            &self.name.as_deref().map_or(JsValue::NULL, JsValue::from),
+        )?;
+        set("numRows", &JsValue::from(self.num_rows as f64))?;
+        set("fields", &serde_wasm_bindgen::to_value(&self.fields)?)?;
+
+        let columns = self
+            .columns
+            .iter()
+            .map(|column| column_to_js(&column.values))
+            .collect::<js_sys::Array>();
+        set("columns", &columns)?;
+
+        let nulls = self
+            .columns
+            .iter()
+            .map(|column| match &column.nulls {
+                Some(nulls) => {
+                    let nulls = nulls.iter().map(|&null| null as u8).collect::<Vec<_>>();
+                    js_sys::Uint8Array::from(&nulls[..]).into()
+                }
+                None => JsValue::NULL,
+            })
+            .collect::<js_sys::Array>();
+        set("nulls", &nulls)?;
+
+        if let Some((ra, dec)) = self.position_columns() {
+            set("ra", &JsValue::from(ra as u32))?;
+            set("dec", &JsValue::from(dec as u32))?;
+        }
+
+        Ok(table.into())
+    }
+
+    /// Read back a table converted to a javascript object by `to_js`
+    pub fn from_js(table: &JsValue) -> Result<Self, JsValue> {
+        let get = |key: &str| js_sys::Reflect::get(table, &JsValue::from_str(key));
+
+        let name = get("name")?.as_string();
+        let fields: Vec<Field> = serde_wasm_bindgen::from_value(get("fields")?)?;
+        let num_rows = get("numRows")?
+            .as_f64()
+            .ok_or_else(|| JsValue::from_str("The table has no numRows"))?
+            as usize;
+        let values = get("columns")?
+            .dyn_into::<js_sys::Array>()
+            .map_err(|_| JsValue::from_str("The table has no columns"))?;
+        let nulls = get("nulls")?;
+
+        let columns = fields
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| {
+                let values = values.get(idx as u32);
+                let mut column = Column::new(field);
+                column.values = column_from_js(&column.values, &values);
+
+                let nulls = js_sys::Reflect::get(&nulls, &JsValue::from(idx as u32))
+                    .ok()
+                    .filter(js_sys::ArrayBuffer::is_view);
+                column.nulls = nulls.map(|nulls| {
+                    let nulls = js_sys::Uint8Array::new(&nulls).to_vec();
+                    nulls.into_iter().map(|null| null != 0).collect()
+                });
+
+                let num_nulls = column.nulls.as_ref().map_or(num_rows, Vec::len);
+                if column.len() != num_rows || num_nulls != num_rows {
+                    return Err(JsValue::from_str(&format!(
+                        "The column {} has not {} rows",
+                        field.name, num_rows
+                    )));
+                }
+
+                Ok(column)
+            })
+            .collect::<Result<Vec<_>, JsValue>>()?;
+
+        Ok(Self {
+            name,
+            fields,
+            columns,
+            num_rows,
+        })
+    }
+}
+
+// The values of a column given as returned by `column_to_js`
+fn column_from_js(values: &Values, column: &JsValue) -> Values {
+    match values {
+        Values::Boolean(_) => Values::Boolean(js_sys::Uint8Array::new(column).to_vec()),
+        Values::UnsignedByte(_) => Values::UnsignedByte(js_sys::Uint8Array::new(column).to_vec()),
+        Values::Short(_) => Values::Short(js_sys::Int16Array::new(column).to_vec()),
+        Values::Int(_) => Values::Int(js_sys::Int32Array::new(column).to_vec()),
+        Values::Long(_) => Values::Long(js_sys::BigInt64Array::new(column).to_vec()),
+        Values::Float(_) => Values::Float(js_sys::Float32Array::new(column).to_vec()),
+        Values::Double(_) => Values::Double(js_sys::Float64Array::new(column).to_vec()),
+        Values::Str(_) => Values::Str(
+            js_sys::Array::from(column)
+                .iter()
+                .map(|s| s.as_string().unwrap_or_default())
+                .collect(),
+        ),
+        Values::Array(_) => Values::Array(
+            js_sys::Array::from(column)
+                .iter()
+                .map(|a| js_sys::Float64Array::new(&a).to_vec())
+                .collect(),
+        ),
+    }
+}
+
+fn column_to_js(values: &Values) -> JsValue {
+    match values {
+        Values::Boolean(v) | Values::UnsignedByte(v) => js_sys::Uint8Array::from(&v[..]).into(),
+        Values::Short(v) => js_sys::Int16Array::from(&v[..]).into(),
+        Values::Int(v) => js_sys::Int32Array::from(&v[..]).into(),
+        Values::Long(v) => js_sys::BigInt64Array::from(&v[..]).into(),
+        Values::Float(v) => js_sys::Float32Array::from(&v[..]).into(),
+        Values::Double(v) => js_sys::Float64Array::from(&v[..]).into(),
+        Values::Str(v) => v
+            .iter()
+            .map(|s| JsValue::from_str(s))
+            .collect::<js_sys::Array>()
+            .into(),
+        Values::Array(v) => v
+            .iter()
+            .map(|a| js_sys::Float64Array::from(&a[..]))
+            .collect::<js_sys::Array>()
+            .into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{position_columns, ArraySize, Column, DataType, Field, Values};
+
+    #[test]
+    fn columns() {
+        let mut field = Field::new("mag".to_string(), DataType::Short);
+        field.null = Some(-1);
+        let mut column = Column::new(&field);
+        column.push_text(&field, " 12 ");
+        column.push_text(&field, "-1");
+        column.push_binary(&field, &[0x01, 0x00], 1);
+        column.push_text(&field, "");
+
+        assert_eq!(column.values, Values::Short(vec![12, 0, 256, 0]));
+        assert_eq!(column.nulls, Some(vec![false, true, false, true]));
+        assert_eq!(column.get_f64(2), Some(256.0));
+        assert_eq!(column.get_f64(3), None);
+
+        let mut field = Field::new("flags".to_string(), DataType::Bit);
+        field.arraysize = Some("2x*".to_string());
+        assert_eq!(field.array_size(), ArraySize::Variable);
+        let mut column = Column::new(&field);
+        column.push_binary(&field, &[0b1010_0000], 3);
+        column.push_text(&field, "0 1");
+        assert_eq!(
+            column.values,
+            Values::Array(vec![vec![1.0, 0.0, 1.0], vec![0.0, 1.0]])
+        );
+        assert_eq!(column.nulls, None);
+
+        let mut field = Field::new("name".to_string(), DataType::UnicodeChar);
+        field.arraysize = Some("4".to_string());
+        let mut column = Column::new(&field);
+        column.push_binary(&field, &[0, b'M', 0, b'3', 0, b'1', 0, 0], 4);
+        assert_eq!(column.values, Values::Str(vec!["M31".to_string()]));
+    }
+
+    #[test]
+    fn positions() {
+        let ucds = [
+            Some("pos.eq.ra"),
+            Some("pos.eq.dec"),
+            None,
+            Some("pos.eq.ra;meta.main"),
+            Some("pos.eq.dec;meta.main"),
+        ];
+        assert_eq!(position_columns(ucds.iter().copied()), Some((3, 4)));
+        assert_eq!(position_columns(ucds[..2].iter().copied()), Some((0, 1)));
+        assert_eq!(position_columns(ucds[2..4].iter().copied()), None);
+    }
+}
@@ -0,0 +1,744 @@
+//! VOTable reader and writer
+//!
+//! The document is read tag by tag and the values are written into the typed columns as
+//! soon as they are read, without building the tree of the elements. The TABLEDATA,
+//! BINARY, BINARY2 and FITS serializations are supported, the binary streams being
+//! embedded and base64 encoded. The rows of the BINARY and BINARY2 streams are read as
+//! soon as they are decoded.
+//!
+//! VOTable is defined at: https://www.ivoa.net/documents/VOTable/
+use super::xml::{escape, parse_attributes, unescape, Event, Reader};
+use super::{base64, fits, ArraySize, Column, DataType, Error, Field, Table};
+use std::convert::TryFrom;
+
+/// The serialization of the DATA of a table
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Serialization {
+    TableData,
+    Binary,
+    Binary2,
+    // The index of the HDU of the table
+    Fits(usize),
+}
+
+// The element whose text is read
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Text {
+    Description,
+    Cell,
+    Stream,
+}
+
+#[derive(Default)]
+struct Parser {
+    tables: Vec<Table>,
+    table: Option<Table>,
+    // The last opened element
+    parent: Vec<String>,
+
+    serialization: Option<Serialization>,
+    // The column of the next TD
+    column: usize,
+
+    reading: Option<Text>,
+    text: String,
+    // The bytes of the STREAM decoded and not read yet
+    decoder: base64::Decoder,
+    stream: Vec<u8>,
+}
+
+/// Read the tables of a VOTable
+pub fn parse(xml: &str) -> Result<Vec<Table>, Error> {
+    let mut parser = Parser::default();
+
+    let mut reader = Reader::new(xml);
+    while let Some(event) = reader.next_event()? {
+        match event {
+            Event::Start { name, attributes } => {
+                parser.start(name, attributes)?;
+                parser.parent.push(name.to_string());
+            }
+            Event::Empty { name, attributes } => {
+                parser.start(name, attributes)?;
+                parser.end(name)?;
+            }
+            Event::End { name } => {
+                parser.parent.pop();
+                parser.end(name)?;
+            }
+            Event::Text(text) => {
+                if parser.reading.is_some() {
+                    parser.text(&unescape(text))?;
+                }
+            }
+            Event::CData(text) => {
+                if parser.reading.is_some() {
+                    parser.text(text)?;
+                }
+            }
+        }
+    }
+
+    Ok(parser.tables)
+}
+
+impl Parser {
+    fn parent(&self) -> Option<&str> {
+        self.parent.last().map(String::as_str)
+    }
+
+    fn start(&mut self, name: &str, attributes: &str) -> Result<(), Error> {
+        if name == "TABLE" {
+            let attributes = parse_attributes(attributes);
+            self.table = Some(Table {
+                name: attributes
+                    .get("name")
+                    .or_else(|| attributes.get("ID"))
+                    .cloned(),
+                fields: vec![],
+                columns: vec![],
+                num_rows: 0,
+            });
+            return Ok(());
+        }
+
+        let in_field = self.parent() == Some("FIELD");
+        let table = match self.table.as_mut() {
+            Some(table) => table,
+            // Outside a TABLE
+            None => return Ok(()),
+        };
+
+        match name {
+            "FIELD" => {
+                let attributes = parse_attributes(attributes);
+                let get = |key: &str| attributes.get(key).cloned();
+
+                let datatype = get("datatype").unwrap_or_else(|| "char".to_string());
+                let mut field = Field::new(
+                    get("name")
+                        .or_else(|| get("ID"))
+                        .unwrap_or_else(|| format!("col_{}", table.fields.len() + 1)),
+                    DataType::from_name(&datatype)?,
+                );
+                field.id = get("ID");
+                field.ucd = get("ucd");
+                field.unit = get("unit");
+                field.arraysize = get("arraysize");
+
+                table.fields.push(field);
+            }
+            "DESCRIPTION" if in_field => {
+                self.reading = Some(Text::Description);
+                self.text.clear();
+            }
+            "VALUES" if in_field => {
+                if let Some(field) = table.fields.last_mut() {
+                    field.null = parse_attributes(attributes)
+                        .get("null")
+                        .and_then(|null| null.trim().parse::<f64>().ok())
+                        .map(|null| null as i64);
+                }
+            }
+            "DATA" => {
+                table.columns = table.fields.iter().map(Column::new).collect();
+            }
+            "TABLEDATA" => self.serialization = Some(Serialization::TableData),
+            "BINARY" => self.serialization = Some(Serialization::Binary),
+            "BINARY2" => self.serialization = Some(Serialization::Binary2),
+            "FITS" => {
+                let extnum = parse_attributes(attributes)
+                    .get("extnum")
+                    .and_then(|extnum| extnum.trim().parse().ok())
+                    .unwrap_or(1);
+                self.serialization = Some(Serialization::Fits(extnum));
+            }
+            "TR" => self.column = 0,
+            "TD" => {
+                self.reading = Some(Text::Cell);
+                self.text.clear();
+            }
+            "STREAM" => {
+                let attributes = parse_attributes(attributes);
+                if let Some(href) = attributes.get("href") {
+                    return Err(Error::UnsupportedStream {
+                        reason: format!("the stream is not embedded but located at {}", href),
+                    });
+                }
+                match attributes.get("encoding").map(String::as_str) {
+                    Some("base64") => (),
+                    encoding => {
+                        return Err(Error::UnsupportedStream {
+                            reason: format!("{:?} encoding", encoding.unwrap_or("no")),
+                        })
+                    }
+                }
+
+                self.reading = Some(Text::Stream);
+                self.decoder = base64::Decoder::default();
+                self.stream.clear();
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    fn text(&mut self, text: &str) -> Result<(), Error> {
+        if self.reading != Some(Text::Stream) {
+            self.text.push_str(text);
+            return Ok(());
+        }
+
+        self.decoder.push(text, &mut self.stream)?;
+
+        let null_flags = match self.serialization {
+            Some(Serialization::Binary) => false,
+            Some(Serialization::Binary2) => true,
+            // A FITS file is read once complete
+            _ => return Ok(()),
+        };
+        if let Some(table) = self.table.as_mut() {
+            let num_bytes_read = read_binary(table, &self.stream, null_flags)?;
+            self.stream.drain(..num_bytes_read);
+        }
+
+        Ok(())
+    }
+
+    fn end(&mut self, name: &str) -> Result<(), Error> {
+        let table = match self.table.as_mut() {
+            Some(table) => table,
+            None => return Ok(()),
+        };
+
+        match name {
+            "TABLE" => {
+                let mut table = self.table.take().unwrap();
+                if table.columns.len() != table.fields.len() {
+                    // The table has no DATA
+                    table.columns = table.fields.iter().map(Column::new).collect();
+                }
+                self.tables.push(table);
+            }
+            "DESCRIPTION" if self.reading == Some(Text::Description) => {
+                if let Some(field) = table.fields.last_mut() {
+                    field.description = Some(self.text.trim().to_string());
+                }
+                self.reading = None;
+            }
+            "TD" => {
+                self.reading = None;
+                if let (Some(field), Some(column)) = (
+                    table.fields.get(self.column),
+                    table.columns.get_mut(self.column),
+                ) {
+                    column.push_text(field, &self.text);
+                }
+                self.column += 1;
+            }
+            "TR" => {
+                // The missing cells
+                for (field, column) in table.fields.iter().zip(&mut table.columns) {
+                    if column.len() == table.num_rows {
+                        column.push_text(field, "");
+                    }
+                }
+                table.num_rows += 1;
+            }
+            "STREAM" => {
+                self.reading = None;
+                std::mem::take(&mut self.decoder).finish(&mut self.stream)?;
+                let bytes = std::mem::take(&mut self.stream);
+
+                let null_flags = match self.serialization {
+                    Some(Serialization::Fits(extnum)) => return read_fits(table, &bytes, extnum),
+                    Some(Serialization::Binary2) => true,
+                    _ => false,
+                };
+                // The last row is incomplete
+                if read_binary(table, &bytes, null_flags)? < bytes.len() {
+                    return Err(Error::Truncated);
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+struct Stream<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Stream<'a> {
+    fn take(&mut self, size: usize) -> Result<&'a [u8], Error> {
+        let slice = self
+            .offset
+            .checked_add(size)
+            .and_then(|end| self.bytes.get(self.offset..end))
+            .ok_or(Error::Truncated)?;
+        self.offset += size;
+
+        Ok(slice)
+    }
+}
+
+// Read the complete rows of a BINARY or BINARY2 stream
+//
+// Returns the number of bytes read, the ones of the last row being left if it is incomplete
+fn read_binary(table: &mut Table, bytes: &[u8], null_flags: bool) -> Result<usize, Error> {
+    let num_flag_bytes = if null_flags {
+        table.fields.len().div_ceil(8)
+    } else {
+        0
+    };
+
+    let mut stream = Stream { bytes, offset: 0 };
+    // The values of a row, pushed once the row is complete
+    let mut cells = Vec::with_capacity(table.fields.len());
+    while stream.offset < bytes.len() {
+        let row_offset = stream.offset;
+        let flags = match read_row(&table.fields, &mut stream, num_flag_bytes, &mut cells) {
+            Ok(flags) => flags,
+            Err(Error::Truncated) => return Ok(row_offset),
+            Err(err) => return Err(err),
+        };
+
+        let columns = table.columns.iter_mut();
+        for (i, ((field, column), &(values, count))) in
+            table.fields.iter().zip(columns).zip(&cells).enumerate()
+        {
+            let is_null = null_flags && (flags[i / 8] >> (7 - i % 8)) & 1 == 1;
+            if is_null {
+                column.push_null();
+            } else {
+                column.push_binary(field, values, count);
+            }
+        }
+
+        table.num_rows += 1;
+    }
+
+    Ok(stream.offset)
+}
+
+// Read the null flags of a row and the bytes and number of values of its cells
+fn read_row<'a>(
+    fields: &[Field],
+    stream: &mut Stream<'a>,
+    num_flag_bytes: usize,
+    cells: &mut Vec<(&'a [u8], usize)>,
+) -> Result<&'a [u8], Error> {
+    cells.clear();
+
+    let flags = stream.take(num_flag_bytes)?;
+    for field in fields {
+        let count = match field.array_size() {
+            ArraySize::Scalar => 1,
+            ArraySize::Fixed(count) => count,
+            ArraySize::Variable => {
+                let count = stream.take(4)?;
+                let count = i32::from_be_bytes([count[0], count[1], count[2], count[3]]);
+                usize::try_from(count).map_err(|_| Error::InvalidCount { count })?
+            }
+        };
+        let values = stream.take(field.datatype.byte_size(count))?;
+
+        cells.push((values, count));
+    }
+
+    Ok(flags)
+}
+
+// Read the rows of a BINTABLE, the FIELDs giving the metadata of its columns
+fn read_fits(table: &mut Table, bytes: &[u8], extnum: usize) -> Result<(), Error> {
+    let fits_table = fits::parse_bintable(bytes, extnum)?;
+
+    let mut fields = fits_table.fields;
+    if fields.len() == table.fields.len() {
+        for (fits_field, field) in fields.iter_mut().zip(&table.fields) {
+            fits_field.name = field.name.clone();
+            fits_field.id = field.id.clone();
+            fits_field.ucd = field.ucd.clone();
+            fits_field.description = field.description.clone();
+            fits_field.unit = field.unit.clone().or_else(|| fits_field.unit.take());
+        }
+    }
+
+    table.fields = fields;
+    table.columns = fits_table.columns;
+    table.num_rows = fits_table.num_rows;
+
+    Ok(())
+}
+
+/// Write a table in a VOTable
+///
+/// The FITS serialization embeds the table as the first extension of a FITS file.
+pub fn write(table: &Table, serialization: Serialization) -> Vec<u8> {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<VOTABLE version="1.4" xmlns="http://www.ivoa.net/xml/VOTable/v1.3">
+<RESOURCE type="results">
+"#,
+    );
+
+    xml.push_str("<TABLE");
+    if let Some(name) = &table.name {
+        xml.push_str(&format!(r#" name="{}""#, escape(name)));
+    }
+    xml.push_str(&format!(" nrows=\"{}\">\n", table.num_rows));
+
+    // The binary serializations without null flags need a value for the missing integers
+    let fields = match serialization {
+        Serialization::Binary => table.get_fields_with_nulls(),
+        _ => table.fields.clone(),
+    };
+    for field in &fields {
+        write_field(&mut xml, field);
+    }
+
+    xml.push_str("<DATA>\n");
+    match serialization {
+        Serialization::TableData => write_tabledata(&mut xml, table),
+        Serialization::Binary | Serialization::Binary2 => {
+            let null_flags = serialization == Serialization::Binary2;
+            let name = if null_flags { "BINARY2" } else { "BINARY" };
+            let bytes = write_binary(table, &fields, null_flags);
+            xml.push_str(&format!(
+                "<{0}><STREAM encoding=\"base64\">\n{1}\n</STREAM></{0}>\n",
+                name,
+                base64::encode(&bytes)
+            ));
+        }
+        Serialization::Fits(_) => {
+            let bytes = fits::write_bintable(table);
+            xml.push_str(&format!(
+                "<FITS extnum=\"1\"><STREAM encoding=\"base64\">\n{}\n</STREAM></FITS>\n",
+                base64::encode(&bytes)
+            ));
+        }
+    }
+    xml.push_str("</DATA>\n</TABLE>\n</RESOURCE>\n</VOTABLE>\n");
+
+    xml.into_bytes()
+}
+
+fn write_field(xml: &mut String, field: &Field) {
+    xml.push_str(&format!(r#"<FIELD name="{}""#, escape(&field.name)));
+    if let Some(id) = &field.id {
+        xml.push_str(&format!(r#" ID="{}""#, escape(id)));
+    }
+    xml.push_str(&format!(r#" datatype="{}""#, field.datatype.name()));
+    let attributes = [
+        ("arraysize", &field.arraysize),
+        ("ucd", &field.ucd),
+        ("unit", &field.unit),
+    ];
+    for (key, value) in attributes.iter() {
+        if let Some(value) = value {
+            xml.push_str(&format!(r#" {}="{}""#, key, escape(value)));
+        }
+    }
+
+    if field.description.is_none() && field.null.is_none() {
+        xml.push_str("/>\n");
+        return;
+    }
+
+    xml.push_str(">\n");
+    if let Some(description) = &field.description {
+        xml.push_str(&format!(
+            "  <DESCRIPTION>{}</DESCRIPTION>\n",
+            escape(description)
+        ));
+    }
+    if let Some(null) = field.null {
+        xml.push_str(&format!("  <VALUES null=\"{}\"/>\n", null));
+    }
+    xml.push_str("</FIELD>\n");
+}
+
+// Write the rows as TD elements, the missing values being empty
+fn write_tabledata(xml: &mut String, table: &Table) {
+    xml.push_str("<TABLEDATA>\n");
+    for row in 0..table.num_rows {
+        xml.push_str("<TR>");
+        for (field, column) in table.fields.iter().zip(&table.columns) {
+            match column.get_text(field, row) {
+                Some(text) => xml.push_str(&format!("<TD>{}</TD>", escape(&text))),
+                None => xml.push_str("<TD/>"),
+            }
+        }
+        xml.push_str("</TR>\n");
+    }
+    xml.push_str("</TABLEDATA>\n");
+}
+
+// Write the rows of a BINARY or BINARY2 stream
+fn write_binary(table: &Table, fields: &[Field], null_flags: bool) -> Vec<u8> {
+    let num_flag_bytes = if null_flags {
+        fields.len().div_ceil(8)
+    } else {
+        0
+    };
+
+    let mut bytes = vec![];
+    for row in 0..table.num_rows {
+        let flags = bytes.len();
+        bytes.resize(flags + num_flag_bytes, 0);
+
+        for (i, (field, column)) in fields.iter().zip(&table.columns).enumerate() {
+            if null_flags && column.is_null(row) {
+                bytes[flags + i / 8] |= 0x80 >> (i % 8);
+            }
+
+            let count = match field.array_size() {
+                ArraySize::Scalar => 1,
+                ArraySize::Fixed(count) => count,
+                ArraySize::Variable => {
+                    let count = column.get_count(field, row);
+                    bytes.extend_from_slice(&(count as i32).to_be_bytes());
+                    count
+                }
+            };
+            column.write_binary(field, row, count, &mut bytes);
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, write, Serialization};
+    use crate::table::fits::tests::bintable;
+    use crate::table::{DataType, Error, Values};
+
+    fn base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+        bytes
+            .chunks(3)
+            .flat_map(|chunk| {
+                let group = chunk
+                    .iter()
+                    .enumerate()
+                    .fold(0_u32, |group, (i, &b)| group | (b as u32) << (16 - 8 * i));
+                (0..4).map(move |i| {
+                    if i <= chunk.len() {
+                        ALPHABET[(group >> (18 - 6 * i) & 63) as usize] as char
+                    } else {
+                        '='
+                    }
+                })
+            })
+            .collect()
+    }
+
+    fn votable(fields: &str, data: &str) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<VOTABLE version="1.4" xmlns="http://www.ivoa.net/xml/VOTable/v1.3">
+<RESOURCE type="results">
+<INFO name="QUERY_STATUS" value="OK"/>
+<TABLE name="results">
+<DESCRIPTION>The sources</DESCRIPTION>
+{}
+<DATA>{}</DATA>
+</TABLE>
+</RESOURCE>
+</VOTABLE>"#,
+            fields, data
+        )
+    }
+
+    const FIELDS: &str = r#"
+<FIELD name="id" datatype="long" ucd="meta.id;meta.main"><VALUES null="-1"/></FIELD>
+<FIELD name="ra" datatype="double" ucd="pos.eq.ra;meta.main" unit="deg">
+  <DESCRIPTION>Right ascension &amp; more</DESCRIPTION>
+</FIELD>
+<FIELD name="dec" datatype="float" ucd="pos.eq.dec;meta.main" unit="deg"/>
+<FIELD name="name" datatype="char" arraysize="*"/>
+<FIELD name="flux" datatype="short" arraysize="2"/>
+<FIELD name="flag" datatype="boolean"/>"#;
+
+    fn check(tables: &[crate::table::Table]) {
+        assert_eq!(tables.len(), 1);
+        let table = &tables[0];
+        assert_eq!(table.name.as_deref(), Some("results"));
+        assert_eq!(table.num_rows, 2);
+        assert_eq!(
+            table.fields[1].description.as_deref(),
+            Some("Right ascension & more")
+        );
+        assert_eq!(table.fields[1].unit.as_deref(), Some("deg"));
+        assert_eq!(table.fields[4].datatype, DataType::Short);
+        assert_eq!(table.position_columns(), Some((1, 2)));
+
+        assert_eq!(table.columns[0].values, Values::Long(vec![4295128739, 0]));
+        assert!(table.columns[0].is_null(1));
+        assert_eq!(table.get_position(0, (1, 2)), Some((10.5, -20.25)));
+        assert_eq!(
+            table.columns[3].values,
+            Values::Str(vec!["M31".to_string(), "".to_string()])
+        );
+        assert_eq!(
+            table.columns[4].values,
+            Values::Array(vec![vec![1.0, 2.0], vec![3.0, 4.0]])
+        );
+        assert_eq!(table.columns[5].values, Values::Boolean(vec![1, 0]));
+        assert_eq!(table.get_sources().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn tabledata() {
+        let data = r#"<TABLEDATA>
+<TR><TD>4295128739</TD><TD>10.5</TD><TD>-20.25</TD><TD>M31</TD><TD>1 2</TD><TD>T</TD></TR>
+<TR><TD>-1</TD><TD/><TD>NaN</TD><TD></TD><TD>3 4</TD><TD>F</TD></TR>
+</TABLEDATA>"#;
+
+        check(&parse(&votable(FIELDS, data)).unwrap());
+    }
+
+    type Row<'a> = (i64, f64, f32, &'a str, [i16; 2], u8);
+
+    // The rows as written in a BINARY stream
+    fn rows(null_flags: bool) -> Vec<u8> {
+        let mut bytes = vec![];
+        let rows: [Row; 2] = [
+            (4295128739, 10.5, -20.25, "M31", [1, 2], b'T'),
+            (-1, f64::NAN, f32::NAN, "", [3, 4], b'F'),
+        ];
+        for (i, &(id, ra, dec, name, flux, flag)) in rows.iter().enumerate() {
+            if null_flags {
+                // The right ascension of the second row is null
+                bytes.push(if i == 1 { 0b0100_0000 } else { 0 });
+            }
+            bytes.extend_from_slice(&id.to_be_bytes());
+            bytes.extend_from_slice(&ra.to_be_bytes());
+            bytes.extend_from_slice(&dec.to_be_bytes());
+            bytes.extend_from_slice(&(name.len() as i32).to_be_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&flux[0].to_be_bytes());
+            bytes.extend_from_slice(&flux[1].to_be_bytes());
+            bytes.push(flag);
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn binary() {
+        let stream = |name: &str, bytes: &[u8]| {
+            format!(
+                r#"<{0}><STREAM encoding="base64">{1}</STREAM></{0}>"#,
+                name,
+                base64(bytes)
+            )
+        };
+
+        let tables = parse(&votable(FIELDS, &stream("BINARY", &rows(false)))).unwrap();
+        check(&tables);
+        assert_eq!(tables[0].columns[1].nulls, None);
+
+        let tables = parse(&votable(FIELDS, &stream("BINARY2", &rows(true)))).unwrap();
+        check(&tables);
+        assert!(tables[0].columns[1].is_null(1));
+
+        // Truncated stream
+        let bytes = rows(false);
+        assert!(parse(&votable(FIELDS, &stream("BINARY", &bytes[..40]))).is_err());
+        // Negative number of values
+        let mut bytes = rows(false);
+        bytes[20..24].copy_from_slice(&(-3_i32).to_be_bytes());
+        assert!(matches!(
+            parse(&votable(FIELDS, &stream("BINARY", &bytes))),
+            Err(Error::InvalidCount { count: -3 })
+        ));
+
+        // The rows are read part by part, a CDATA section splitting the first row
+        let text = base64(&rows(true));
+        let data = format!(
+            r#"<BINARY2><STREAM encoding="base64">{}<![CDATA[{}]]>{}</STREAM></BINARY2>"#,
+            &text[..10],
+            &text[10..50],
+            &text[50..]
+        );
+        check(&parse(&votable(FIELDS, &data)).unwrap());
+        let external = r#"<BINARY><STREAM href="http://data/table.bin"/></BINARY>"#;
+        assert!(parse(&votable(FIELDS, external)).is_err());
+    }
+
+    #[test]
+    fn fits() {
+        let mut rows = vec![];
+        for &(ra, dec) in &[(10.5_f64, -20.25_f64), (200.0, 45.0)] {
+            rows.extend_from_slice(&ra.to_be_bytes());
+            rows.extend_from_slice(&dec.to_be_bytes());
+        }
+        let fits = bintable(
+            &[
+                ("XTENSION", "'BINTABLE'"),
+                ("BITPIX", "8"),
+                ("NAXIS", "2"),
+                ("NAXIS1", "16"),
+                ("NAXIS2", "2"),
+                ("PCOUNT", "0"),
+                ("GCOUNT", "1"),
+                ("TFIELDS", "2"),
+                ("TFORM1", "'D'"),
+                ("TFORM2", "'D'"),
+            ],
+            &rows,
+        );
+
+        let fields = r#"
+<FIELD name="ra" datatype="double" ucd="pos.eq.ra;meta.main"/>
+<FIELD name="dec" datatype="double" ucd="pos.eq.dec;meta.main"/>"#;
+        let data = format!(
+            r#"<FITS extnum="1"><STREAM encoding="base64">{}</STREAM></FITS>"#,
+            base64(&fits)
+        );
+
+        let tables = parse(&votable(fields, &data)).unwrap();
+        let table = &tables[0];
+        assert_eq!(table.num_rows, 2);
+        assert_eq!(table.fields[0].name, "ra");
+        assert_eq!(
+            table.get_position(1, table.position_columns().unwrap()),
+            Some((200.0, 45.0))
+        );
+    }
+
+    #[test]
+    fn write_read() {
+        let data = r#"<TABLEDATA>
+<TR><TD>4295128739</TD><TD>10.5</TD><TD>-20.25</TD><TD>M31</TD><TD>1 2</TD><TD>T</TD></TR>
+<TR><TD>-1</TD><TD/><TD>NaN</TD><TD></TD><TD>3 4</TD><TD>F</TD></TR>
+</TABLEDATA>"#;
+        let table = &parse(&votable(FIELDS, data)).unwrap()[0];
+
+        for &serialization in &[
+            Serialization::TableData,
+            Serialization::Binary,
+            Serialization::Binary2,
+            Serialization::Fits(1),
+        ] {
+            let xml = String::from_utf8(write(table, serialization)).unwrap();
+            let tables = parse(&xml).unwrap();
+            check(&tables);
+            assert_eq!(tables[0].fields[0].ucd, table.fields[0].ucd);
+        }
+
+        // The selected rows
+        let xml = write(&table.select_rows(&[1, 5]), Serialization::Binary2);
+        let tables = parse(std::str::from_utf8(&xml).unwrap()).unwrap();
+        assert_eq!(tables[0].num_rows, 1);
+        assert!(tables[0].columns[0].is_null(0));
+        assert!(tables[0].columns[1].is_null(0));
+    }
+}
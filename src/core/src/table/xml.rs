@@ -0,0 +1,293 @@
+//! A minimal pull XML reader
+//!
+//! It only splits the document into tags and texts, borrowed from the document, which
+//! is all that is needed to read VOTables without building a tree of their elements.
+use std::collections::HashMap;
+
+use super::Error;
+
+#[derive(Debug, PartialEq)]
+pub enum Event<'a> {
+    /// An opening tag, with its raw attributes
+    Start {
+        name: &'a str,
+        attributes: &'a str,
+    },
+    /// A self-closing tag
+    Empty {
+        name: &'a str,
+        attributes: &'a str,
+    },
+    End {
+        name: &'a str,
+    },
+    /// A text, with its entities still escaped
+    Text(&'a str),
+    CData(&'a str),
+}
+
+pub struct Reader<'a> {
+    xml: &'a str,
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(xml: &'a str) -> Self {
+        Self { xml, pos: 0 }
+    }
+
+    /// The next tag or text of the document, None once it has been read entirely
+    pub fn next_event(&mut self) -> Result<Option<Event<'a>>, Error> {
+        loop {
+            let rest = &self.xml[self.pos..];
+            if rest.is_empty() {
+                return Ok(None);
+            }
+
+            if !rest.starts_with('<') {
+                let end = rest.find('<').unwrap_or(rest.len());
+                self.pos += end;
+
+                return Ok(Some(Event::Text(&rest[..end])));
+            }
+
+            // Skip the comments, the processing instructions and the doctype
+            if rest.starts_with("<!--") {
+                self.skip_after("-->")?;
+            } else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                let end = cdata
+                    .find("]]>")
+                    .ok_or_else(|| self.error("unclosed CDATA"))?;
+                self.pos += "<![CDATA[".len() + end + "]]>".len();
+
+                return Ok(Some(Event::CData(&cdata[..end])));
+            } else if rest.starts_with("<?") {
+                self.skip_after("?>")?;
+            } else if rest.starts_with("<!") {
+                // The doctype may contain an internal subset between brackets
+                let end = match (rest.find('['), rest.find('>')) {
+                    (Some(open), Some(close)) if open < close => rest[open..]
+                        .find("]>")
+                        .map(|end| open + end + 1)
+                        .ok_or_else(|| self.error("unclosed doctype"))?,
+                    (_, Some(close)) => close,
+                    _ => return Err(self.error("unclosed doctype")),
+                };
+                self.pos += end + 1;
+            } else {
+                return self.tag().map(Some);
+            }
+        }
+    }
+
+    fn tag(&mut self) -> Result<Event<'a>, Error> {
+        let rest = &self.xml[(self.pos + 1)..];
+
+        // The end of the tag, ignoring the '>' inside the attribute values
+        let mut quote = None;
+        let end = rest
+            .char_indices()
+            .find(|&(_, c)| match quote {
+                Some(q) => {
+                    if c == q {
+                        quote = None;
+                    }
+                    false
+                }
+                None => {
+                    if c == '"' || c == '\'' {
+                        quote = Some(c);
+                    }
+                    c == '>'
+                }
+            })
+            .map(|(end, _)| end)
+            .ok_or_else(|| self.error("unclosed tag"))?;
+        self.pos += end + 2;
+
+        let content = &rest[..end];
+        if let Some(name) = content.strip_prefix('/') {
+            return Ok(Event::End {
+                name: local_name(name.trim()),
+            });
+        }
+
+        let (content, empty) = match content.strip_suffix('/') {
+            Some(content) => (content, true),
+            None => (content, false),
+        };
+        let name_end = content
+            .find(|c: char| c.is_whitespace())
+            .unwrap_or(content.len());
+        let name = local_name(&content[..name_end]);
+        let attributes = &content[name_end..];
+
+        if name.is_empty() {
+            return Err(self.error("tag without a name"));
+        }
+
+        Ok(if empty {
+            Event::Empty { name, attributes }
+        } else {
+            Event::Start { name, attributes }
+        })
+    }
+
+    fn skip_after(&mut self, pattern: &str) -> Result<(), Error> {
+        let end = self.xml[self.pos..]
+            .find(pattern)
+            .ok_or_else(|| self.error("unexpected end of document"))?;
+        self.pos += end + pattern.len();
+
+        Ok(())
+    }
+
+    fn error(&self, reason: &'static str) -> Error {
+        Error::Xml {
+            reason,
+            position: self.pos,
+        }
+    }
+}
+
+// The name of an element without its namespace prefix
+fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// The `key="value"` attributes of an XML tag
+pub fn parse_attributes(tag: &str) -> HashMap<String, String> {
+    let mut attributes = HashMap::new();
+
+    let mut rest = tag;
+    while let Some(eq) = rest.find('=') {
+        let key = rest[..eq].trim().to_string();
+        let value = rest[(eq + 1)..].trim_start();
+
+        let quote = match value.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => quote,
+            _ => break,
+        };
+        let value = &value[1..];
+        let end = match value.find(quote) {
+            Some(end) => end,
+            None => break,
+        };
+
+        attributes.insert(key, unescape(&value[..end]));
+        rest = &value[(end + 1)..];
+    }
+
+    attributes
+}
+
+/// Replace the predefined and the character entities of a text
+pub fn unescape(s: &str) -> String {
+    if !s.contains('&') {
+        return s.to_string();
+    }
+
+    let mut unescaped = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        unescaped.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let c = entity.and_then(|(entity, _)| match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "amp" => Some('&'),
+            _ => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#')?.parse::<u32>().ok(),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+
+        match (c, entity) {
+            (Some(c), Some((_, end))) => {
+                unescaped.push(c);
+                rest = &rest[(end + 1)..];
+            }
+            // Not an entity, the ampersand is kept
+            _ => {
+                unescaped.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    unescaped.push_str(rest);
+
+    unescaped
+}
+
+/// Replace the characters that cannot be written as is in a text or an attribute
+pub fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '&' => escaped.push_str("&amp;"),
+            _ => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{escape, parse_attributes, unescape, Event, Reader};
+
+    #[test]
+    fn events() {
+        let xml = r#"<?xml version="1.0"?>
+<!DOCTYPE VOTABLE [ <!ENTITY e "x"> ]>
+<!-- a comment with a <TAG> -->
+<vot:TD a="1>0"/><TD>a &amp; b<![CDATA[<c>]]></TD>"#;
+        let mut reader = Reader::new(xml);
+        let mut events = vec![];
+        while let Some(event) = reader.next_event().unwrap() {
+            if event != Event::Text("\n") {
+                events.push(event);
+            }
+        }
+
+        assert_eq!(
+            events,
+            vec![
+                Event::Empty {
+                    name: "TD",
+                    attributes: r#" a="1>0""#
+                },
+                Event::Start {
+                    name: "TD",
+                    attributes: ""
+                },
+                Event::Text("a &amp; b"),
+                Event::CData("<c>"),
+                Event::End { name: "TD" },
+            ]
+        );
+
+        assert!(Reader::new("<TD").next_event().is_err());
+    }
+
+    #[test]
+    fn attributes() {
+        let attributes = parse_attributes(r#" name="ra" ucd='pos.eq.ra;meta.main' unit="&#176;""#);
+        assert_eq!(attributes["name"], "ra");
+        assert_eq!(attributes["ucd"], "pos.eq.ra;meta.main");
+        assert_eq!(attributes["unit"], "°");
+
+        assert_eq!(unescape("a &lt; b &#x26;&amp; c & d"), "a < b && c & d");
+        assert_eq!(escape(r#"a < "b" & c"#), "a &lt; &quot;b&quot; &amp; c");
+    }
+}
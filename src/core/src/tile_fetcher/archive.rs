@@ -0,0 +1,629 @@
+//! HiPS stored in a single zip or tar archive
+//!
+//! Small HiPS are often distributed as one archive file. The archive is kept as is in memory
+//! and only its table of contents is read: the tiles, the `properties` file and the
+//! `Moc.fits` file are located inside it so that they can be decoded one by one when requested.
+//!
+//! Zip entries can be either stored or compressed with deflate. Tar archives are not compressed.
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+use al_api::hips::ImageExt;
+use wasm_bindgen::JsValue;
+
+use crate::healpix::cell::HEALPixCell;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    UnknownFormat,
+    Truncated { what: &'static str },
+    InvalidSignature { what: &'static str },
+    Unsupported { reason: &'static str },
+    Inflate { path: String },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnknownFormat => write!(f, "The archive is neither a zip nor a tar file"),
+            Error::Truncated { what } => write!(f, "Truncated archive: {} is missing", what),
+            Error::InvalidSignature { what } => write!(f, "Invalid signature of the {}", what),
+            Error::Unsupported { reason } => write!(f, "Unsupported archive: {}", reason),
+            Error::Inflate { path } => write!(f, "Could not decompress {}", path),
+        }
+    }
+}
+
+impl From<Error> for JsValue {
+    fn from(err: Error) -> Self {
+        JsValue::from_str(&err.to_string())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compression {
+    Stored,
+    Deflate,
+}
+
+// The location of a file inside the archive
+#[derive(Debug, Clone)]
+struct Entry {
+    path: String,
+    // Offset of the first byte of the (compressed) content
+    offset: usize,
+    // Size of the (compressed) content in bytes
+    size: usize,
+    compression: Compression,
+}
+
+/// A HiPS archive held in memory
+#[derive(Debug)]
+pub struct HiPSArchive {
+    data: Box<[u8]>,
+
+    tiles: HashMap<(HEALPixCell, ImageExt), Entry>,
+    properties: Option<Entry>,
+    moc: Option<Entry>,
+}
+
+impl HiPSArchive {
+    /// Index the content of a zip or a tar archive
+    ///
+    /// The files that are not part of the HiPS are ignored.
+    pub fn new(data: Box<[u8]>) -> Result<Self, Error> {
+        let entries = if data.starts_with(ZIP_LOCAL_HEADER_SIGNATURE)
+            || data.starts_with(ZIP_EOCD_SIGNATURE)
+        {
+            read_zip_entries(&data)?
+        } else if data.len() >= TAR_BLOCK_SIZE && &data[257..262] == b"ustar" {
+            read_tar_entries(&data)?
+        } else {
+            return Err(Error::UnknownFormat);
+        };
+
+        let mut tiles = HashMap::new();
+        let mut properties = None;
+        let mut moc = None;
+        for entry in entries {
+            match classify(&entry.path) {
+                Some(HiPSFile::Tile(cell, ext)) => {
+                    tiles.insert((cell, ext), entry);
+                }
+                Some(HiPSFile::Properties) => {
+                    properties.get_or_insert(entry);
+                }
+                Some(HiPSFile::Moc) => {
+                    moc.get_or_insert(entry);
+                }
+                None => (),
+            }
+        }
+
+        Ok(Self {
+            data,
+            tiles,
+            properties,
+            moc,
+        })
+    }
+
+    /// The bytes of the archive
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn num_tiles(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn contains_tile(&self, cell: &HEALPixCell, ext: ImageExt) -> bool {
+        self.tiles.contains_key(&(*cell, ext))
+    }
+
+    /// Get the content of a tile, decompressing it if needed
+    pub fn get_tile(
+        &self,
+        cell: &HEALPixCell,
+        ext: ImageExt,
+    ) -> Option<Result<Cow<'_, [u8]>, Error>> {
+        self.tiles.get(&(*cell, ext)).map(|entry| self.read(entry))
+    }
+
+    /// Get the content of the `properties` file
+    pub fn get_properties(&self) -> Option<Result<String, Error>> {
+        self.properties.as_ref().map(|entry| {
+            self.read(entry)
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+        })
+    }
+
+    /// Get the content of the `Moc.fits` file
+    pub fn get_moc(&self) -> Option<Result<Cow<'_, [u8]>, Error>> {
+        self.moc.as_ref().map(|entry| self.read(entry))
+    }
+
+    fn read(&self, entry: &Entry) -> Result<Cow<'_, [u8]>, Error> {
+        let content = &self.data[entry.offset..(entry.offset + entry.size)];
+
+        match entry.compression {
+            Compression::Stored => Ok(Cow::Borrowed(content)),
+            Compression::Deflate => miniz_oxide::inflate::decompress_to_vec(content)
+                .map(Cow::Owned)
+                .map_err(|_| Error::Inflate {
+                    path: entry.path.clone(),
+                }),
+        }
+    }
+}
+
+enum HiPSFile {
+    Tile(HEALPixCell, ImageExt),
+    Properties,
+    Moc,
+}
+
+// Recognize the files of a HiPS from their path.
+// The HiPS can be in a sub directory of the archive
+fn classify(path: &str) -> Option<HiPSFile> {
+    let components = path.split('/').collect::<Vec<_>>();
+    let name = *components.last()?;
+
+    match name {
+        "properties" => return Some(HiPSFile::Properties),
+        "Moc.fits" => return Some(HiPSFile::Moc),
+        _ => (),
+    }
+
+    // Norder{depth}/Dir{dir}/Npix{idx}.{ext}
+    if components.len() < 3 {
+        return None;
+    }
+    let norder = components[components.len() - 3];
+    let dir = components[components.len() - 2];
+
+    let depth = norder.strip_prefix("Norder")?.parse::<u8>().ok()?;
+    dir.strip_prefix("Dir")?.parse::<u64>().ok()?;
+    let (idx, ext) = name.strip_prefix("Npix")?.split_once('.')?;
+    let idx = idx.parse::<u64>().ok()?;
+    let ext = match ext {
+        "fits" => ImageExt::Fits,
+        "jpg" | "jpeg" => ImageExt::Jpeg,
+        "png" => ImageExt::Png,
+        "webp" => ImageExt::Webp,
+        _ => return None,
+    };
+
+    if depth > 29 || idx >= 12 << (2 * depth as u64) {
+        return None;
+    }
+
+    Some(HiPSFile::Tile(HEALPixCell(depth, idx), ext))
+}
+
+const ZIP_LOCAL_HEADER_SIGNATURE: &[u8] = b"PK\x03\x04";
+const ZIP_CENTRAL_HEADER_SIGNATURE: &[u8] = b"PK\x01\x02";
+const ZIP_EOCD_SIGNATURE: &[u8] = b"PK\x05\x06";
+const ZIP_LOCAL_HEADER_SIZE: usize = 30;
+const ZIP_CENTRAL_HEADER_SIZE: usize = 46;
+const ZIP_EOCD_SIZE: usize = 22;
+
+fn read_u16(data: &[u8], offset: usize) -> usize {
+    u16::from_le_bytes([data[offset], data[offset + 1]]) as usize
+}
+
+fn read_u32(data: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ]) as usize
+}
+
+fn get_bytes<'a>(
+    data: &'a [u8],
+    offset: usize,
+    len: usize,
+    what: &'static str,
+) -> Result<&'a [u8], Error> {
+    offset
+        .checked_add(len)
+        .and_then(|end| data.get(offset..end))
+        .ok_or(Error::Truncated { what })
+}
+
+// Read the central directory located at the end of a zip file
+fn read_zip_entries(data: &[u8]) -> Result<Vec<Entry>, Error> {
+    if data.len() < ZIP_EOCD_SIZE {
+        return Err(Error::Truncated {
+            what: "end of central directory",
+        });
+    }
+
+    // The end of central directory record is followed by a comment of at most 65535 bytes
+    let min_eocd_offset = data.len().saturating_sub(ZIP_EOCD_SIZE + u16::MAX as usize);
+    let eocd_offset = (min_eocd_offset..=(data.len() - ZIP_EOCD_SIZE))
+        .rev()
+        .find(|&offset| data[offset..].starts_with(ZIP_EOCD_SIGNATURE))
+        .ok_or(Error::Truncated {
+            what: "end of central directory",
+        })?;
+
+    let eocd = &data[eocd_offset..];
+    let num_entries = read_u16(eocd, 10);
+    let cd_offset = read_u32(eocd, 16);
+    if num_entries == u16::MAX as usize || cd_offset == u32::MAX as usize {
+        return Err(Error::Unsupported { reason: "zip64" });
+    }
+
+    let mut entries = Vec::with_capacity(num_entries);
+    let mut offset = cd_offset;
+    for _ in 0..num_entries {
+        let header = get_bytes(data, offset, ZIP_CENTRAL_HEADER_SIZE, "central directory")?;
+        if !header.starts_with(ZIP_CENTRAL_HEADER_SIGNATURE) {
+            return Err(Error::InvalidSignature {
+                what: "zip central directory",
+            });
+        }
+
+        let flags = read_u16(header, 8);
+        let method = read_u16(header, 10);
+        let size = read_u32(header, 20);
+        let name_len = read_u16(header, 28);
+        let extra_len = read_u16(header, 30);
+        let comment_len = read_u16(header, 32);
+        let local_header_offset = read_u32(header, 42);
+
+        let name = get_bytes(
+            data,
+            offset + ZIP_CENTRAL_HEADER_SIZE,
+            name_len,
+            "central directory",
+        )?;
+        let path = String::from_utf8_lossy(name).into_owned();
+        offset += ZIP_CENTRAL_HEADER_SIZE + name_len + extra_len + comment_len;
+
+        // Directories and encrypted files
+        if path.ends_with('/') || flags & 0x1 != 0 {
+            continue;
+        }
+        if size == u32::MAX as usize || local_header_offset == u32::MAX as usize {
+            return Err(Error::Unsupported { reason: "zip64" });
+        }
+        let compression = match method {
+            0 => Compression::Stored,
+            8 => Compression::Deflate,
+            // The files that are not part of the HiPS are ignored whatever their compression
+            _ if classify(&path).is_none() => continue,
+            _ => {
+                return Err(Error::Unsupported {
+                    reason: "only stored and deflated zip entries are supported",
+                })
+            }
+        };
+
+        // The size of the extra field of the local header can differ from the central one
+        let local_header = get_bytes(
+            data,
+            local_header_offset,
+            ZIP_LOCAL_HEADER_SIZE,
+            "local header",
+        )?;
+        if !local_header.starts_with(ZIP_LOCAL_HEADER_SIGNATURE) {
+            return Err(Error::InvalidSignature {
+                what: "zip local header",
+            });
+        }
+        let content_offset = local_header_offset
+            + ZIP_LOCAL_HEADER_SIZE
+            + read_u16(local_header, 26)
+            + read_u16(local_header, 28);
+        get_bytes(data, content_offset, size, "file content")?;
+
+        entries.push(Entry {
+            path,
+            offset: content_offset,
+            size,
+            compression,
+        });
+    }
+
+    Ok(entries)
+}
+
+const TAR_BLOCK_SIZE: usize = 512;
+
+// Read a null terminated string of a tar header
+fn read_tar_str(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+// Read the path given by the records of a pax extended header.
+// Each record is written as `{length} {keyword}={value}\n`
+fn read_pax_path(content: &[u8]) -> Option<String> {
+    let mut records = content;
+    while !records.is_empty() {
+        let space = records.iter().position(|&b| b == b' ')?;
+        let len = std::str::from_utf8(&records[..space])
+            .ok()?
+            .parse::<usize>()
+            .ok()?;
+        if len <= space || len > records.len() {
+            return None;
+        }
+
+        let record = &records[(space + 1)..len];
+        if let Some(path) = record.strip_prefix(b"path=") {
+            let path = path.strip_suffix(b"\n").unwrap_or(path);
+            return Some(String::from_utf8_lossy(path).into_owned());
+        }
+        records = &records[len..];
+    }
+
+    None
+}
+
+// Read the headers preceding each file of a tar archive
+fn read_tar_entries(data: &[u8]) -> Result<Vec<Entry>, Error> {
+    let mut entries = vec![];
+    // Name given by a GNU long name or a pax extended header to the next file
+    let mut long_name = None;
+
+    let mut offset = 0;
+    while offset + TAR_BLOCK_SIZE <= data.len() {
+        let header = &data[offset..(offset + TAR_BLOCK_SIZE)];
+        // The archive ends with empty blocks
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+
+        let size_field = read_tar_str(&header[124..136]);
+        let size =
+            usize::from_str_radix(size_field.trim(), 8).map_err(|_| Error::InvalidSignature {
+                what: "tar header size",
+            })?;
+        let content_offset = offset + TAR_BLOCK_SIZE;
+        let content = get_bytes(data, content_offset, size, "file content")?;
+
+        match header[156] {
+            // Regular files
+            b'0' | 0 => {
+                let path = long_name.take().unwrap_or_else(|| {
+                    let name = read_tar_str(&header[..100]);
+                    let prefix = read_tar_str(&header[345..500]);
+                    if prefix.is_empty() {
+                        name
+                    } else {
+                        format!("{}/{}", prefix, name)
+                    }
+                });
+
+                entries.push(Entry {
+                    path,
+                    offset: content_offset,
+                    size,
+                    compression: Compression::Stored,
+                });
+            }
+            b'L' => long_name = Some(read_tar_str(content)),
+            b'x' => {
+                if let Some(path) = read_pax_path(content) {
+                    long_name = Some(path);
+                }
+            }
+            // Pax global headers
+            b'g' => (),
+            // Directories, links... which the name given by the previous header belongs to
+            _ => long_name = None,
+        }
+
+        offset = content_offset + size.div_ceil(TAR_BLOCK_SIZE) * TAR_BLOCK_SIZE;
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, HiPSArchive};
+    use crate::healpix::cell::HEALPixCell;
+    use al_api::hips::ImageExt;
+
+    const PROPERTIES: &[u8] = b"creator_did = ivo://test/hips\nhips_tile_format = png\n";
+
+    const STORED: u16 = 0;
+    const DEFLATED: u16 = 8;
+    const BZIP2: u16 = 12;
+
+    // Build a zip archive, the files being deflated or stored as they are
+    fn zip(files: &[(&str, &[u8], u16)]) -> Vec<u8> {
+        let mut data = vec![];
+        let mut central_directory = vec![];
+
+        for (path, content, method) in files {
+            let stored = if *method == DEFLATED {
+                miniz_oxide::deflate::compress_to_vec(content, 6)
+            } else {
+                content.to_vec()
+            };
+            let offset = data.len() as u32;
+
+            let mut header = vec![];
+            header.extend_from_slice(&20u16.to_le_bytes()); // version needed
+            header.extend_from_slice(&0u16.to_le_bytes()); // flags
+            header.extend_from_slice(&method.to_le_bytes());
+            header.extend_from_slice(&[0; 8]); // time, date, crc
+            header.extend_from_slice(&(stored.len() as u32).to_le_bytes());
+            header.extend_from_slice(&(content.len() as u32).to_le_bytes());
+            header.extend_from_slice(&(path.len() as u16).to_le_bytes());
+
+            data.extend_from_slice(b"PK\x03\x04");
+            data.extend_from_slice(&header);
+            // An extra field only present in the local header
+            data.extend_from_slice(&4u16.to_le_bytes());
+            data.extend_from_slice(path.as_bytes());
+            data.extend_from_slice(&[0xCA, 0xFE, 0, 0]);
+            data.extend_from_slice(&stored);
+
+            central_directory.extend_from_slice(b"PK\x01\x02");
+            central_directory.extend_from_slice(&20u16.to_le_bytes()); // version made by
+            central_directory.extend_from_slice(&header);
+            central_directory.extend_from_slice(&[0; 12]); // extra, comment, disk, attributes
+            central_directory.extend_from_slice(&offset.to_le_bytes());
+            central_directory.extend_from_slice(path.as_bytes());
+        }
+
+        let cd_offset = data.len() as u32;
+        data.extend_from_slice(&central_directory);
+        data.extend_from_slice(b"PK\x05\x06");
+        data.extend_from_slice(&[0; 4]); // disk numbers
+        data.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(files.len() as u16).to_le_bytes());
+        data.extend_from_slice(&(central_directory.len() as u32).to_le_bytes());
+        data.extend_from_slice(&cd_offset.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes()); // comment
+
+        data
+    }
+
+    // Build a ustar archive
+    fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        tar_with_types(&files.iter().map(|&(p, c)| (p, c, b'0')).collect::<Vec<_>>())
+    }
+
+    fn tar_with_types(files: &[(&str, &[u8], u8)]) -> Vec<u8> {
+        let mut data = vec![];
+
+        for (path, content, typeflag) in files {
+            let mut header = [0u8; 512];
+            let (prefix, name) = if path.len() > 100 {
+                path.rsplit_once('/').unwrap()
+            } else {
+                ("", *path)
+            };
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            let size = format!("{:011o}", content.len());
+            header[124..135].copy_from_slice(size.as_bytes());
+            header[156] = *typeflag;
+            header[257..263].copy_from_slice(b"ustar\0");
+            header[345..(345 + prefix.len())].copy_from_slice(prefix.as_bytes());
+
+            data.extend_from_slice(&header);
+            data.extend_from_slice(content);
+            data.resize(data.len().div_ceil(512) * 512, 0);
+        }
+        data.extend_from_slice(&[0; 1024]);
+
+        data
+    }
+
+    #[test]
+    fn zip_archive() {
+        let tile = vec![42u8; 1000];
+        let data = zip(&[
+            ("DSS/properties", PROPERTIES, STORED),
+            ("DSS/Moc.fits", b"SIMPLE", STORED),
+            ("DSS/Norder3/", b"", STORED),
+            ("DSS/Norder3/Dir0/Npix12.png", &tile, DEFLATED),
+            ("DSS/Norder3/Dir0/Npix13.jpg", b"jpeg", STORED),
+            ("DSS/Norder3/Allsky.png", b"allsky", STORED),
+            ("DSS/index.html", b"<html>", DEFLATED),
+            ("DSS/preview.jpg", b"bzip2", BZIP2),
+        ]);
+        let archive = HiPSArchive::new(data.into_boxed_slice()).unwrap();
+
+        assert_eq!(archive.num_tiles(), 2);
+        assert_eq!(
+            archive.get_properties().unwrap().unwrap().as_bytes(),
+            PROPERTIES
+        );
+        assert_eq!(&*archive.get_moc().unwrap().unwrap(), b"SIMPLE");
+
+        let png = archive.get_tile(&HEALPixCell(3, 12), ImageExt::Png);
+        assert_eq!(&*png.unwrap().unwrap(), &tile[..]);
+        let jpeg = archive.get_tile(&HEALPixCell(3, 13), ImageExt::Jpeg);
+        assert_eq!(&*jpeg.unwrap().unwrap(), b"jpeg");
+
+        assert!(archive
+            .get_tile(&HEALPixCell(3, 12), ImageExt::Jpeg)
+            .is_none());
+        assert!(!archive.contains_tile(&HEALPixCell(4, 12), ImageExt::Png));
+    }
+
+    #[test]
+    fn tar_archive() {
+        let long_dir = "a".repeat(90);
+        let long_path = format!("{}/Norder10/Dir40000/Npix40001.fits", long_dir);
+        let data = tar(&[
+            ("properties", PROPERTIES),
+            ("Norder0/Dir0/Npix5.webp", b"webp"),
+            (&long_path, b"fits"),
+            ("Norder0/Dir0/Npix12.webp", b"out of the sphere"),
+        ]);
+        let archive = HiPSArchive::new(data.into_boxed_slice()).unwrap();
+
+        assert_eq!(archive.num_tiles(), 2);
+        assert!(archive.get_moc().is_none());
+        assert_eq!(
+            archive.get_properties().unwrap().unwrap().as_bytes(),
+            PROPERTIES
+        );
+
+        let webp = archive.get_tile(&HEALPixCell(0, 5), ImageExt::Webp);
+        assert_eq!(&*webp.unwrap().unwrap(), b"webp");
+        let fits = archive.get_tile(&HEALPixCell(10, 40001), ImageExt::Fits);
+        assert_eq!(&*fits.unwrap().unwrap(), b"fits");
+    }
+
+    #[test]
+    fn tar_pax_headers() {
+        let long_path = format!("{}/Norder3/Dir0/Npix12.png", "b".repeat(200));
+        let record = format!(" path={}\n", long_path);
+        // The length of the record counts its own digits
+        let record = format!("{}{}", record.len() + 3, record);
+        let data = tar_with_types(&[
+            ("pax_global_header", b"21 comment=generated\n", b'g'),
+            ("PaxHeaders/Npix12.png", record.as_bytes(), b'x'),
+            ("truncated/Npix12.png", b"png", b'0'),
+            ("PaxHeaders/Norder3", b"20 path=DSS/Norder3\n", b'x'),
+            ("Norder3", b"", b'5'),
+            ("Norder3/Dir0/Npix13.png", b"next", b'0'),
+        ]);
+        let archive = HiPSArchive::new(data.into_boxed_slice()).unwrap();
+
+        assert_eq!(archive.num_tiles(), 2);
+        let png = archive.get_tile(&HEALPixCell(3, 12), ImageExt::Png);
+        assert_eq!(&*png.unwrap().unwrap(), b"png");
+        assert!(archive.contains_tile(&HEALPixCell(3, 13), ImageExt::Png));
+    }
+
+    #[test]
+    fn invalid_archives() {
+        assert_eq!(
+            HiPSArchive::new(Box::new(*b"not an archive")).unwrap_err(),
+            Error::UnknownFormat
+        );
+
+        let data = zip(&[("Norder3/Dir0/Npix12.png", b"bzip2", BZIP2)]);
+        assert!(matches!(
+            HiPSArchive::new(data.into_boxed_slice()).unwrap_err(),
+            Error::Unsupported { .. }
+        ));
+
+        let mut data = zip(&[("Norder3/Dir0/Npix12.png", b"png", STORED)]);
+        data.truncate(data.len() - 10);
+        assert!(HiPSArchive::new(data.into_boxed_slice()).is_err());
+
+        let mut data = tar(&[("Norder3/Dir0/Npix12.png", &[0; 2000])]);
+        data.truncate(1000);
+        assert_eq!(
+            HiPSArchive::new(data.into_boxed_slice()).unwrap_err(),
+            Error::Truncated {
+                what: "file content"
+            }
+        );
+    }
+}
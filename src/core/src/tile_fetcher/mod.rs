@@ -0,0 +1,539 @@
+pub mod archive;
+pub mod priority;
+pub mod retry;
+
+use crate::downloader::request::tile::Tile as TileResource;
+use crate::downloader::request::Failure;
+use crate::downloader::{query, Downloader, QueryId};
+use crate::math::angle::Angle;
+use crate::renderable::HiPS;
+use crate::time::{DeltaTime, Time};
+use crate::Abort;
+
+use archive::HiPSArchive;
+use cgmath::Vector3;
+use priority::TilePriority;
+use retry::Retries;
+use serde::Serialize;
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+
+const MAX_NUM_TILE_FETCHING: usize = 8;
+const MAX_QUERY_QUEUE_LENGTH: usize = 100;
+
+/// Statistics about the tile requests, exposed to the javascript
+#[derive(Debug, Default, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TileFetcherMetrics {
+    // Number of tiles waiting to be requested
+    pub num_queued: usize,
+    // Number of tiles requested and not received yet
+    pub num_in_flight: usize,
+    pub num_requested: usize,
+    pub num_received: usize,
+    // Number of failed requests that will be performed again
+    pub num_retried: usize,
+    // Number of tiles that could not be retrieved
+    pub num_failed: usize,
+    // Number of requests aborted because their tile went out of the view
+    pub num_cancelled: usize,
+    // Number of queries discarded because the queue was full
+    pub num_dropped: usize,
+    // Mean duration in ms between the request and the reception of a tile
+    pub mean_latency: f32,
+}
+
+pub struct TileFetcherQueue {
+    // The queries to fetch, the most urgent being at the end once sorted
+    queries: Vec<(TilePriority, query::Tile)>,
+    sorted: bool,
+    // The tiles needed by the current view
+    wanted: HashMap<QueryId, TilePriority>,
+    // The tiles requested and not received yet
+    in_flight: HashSet<QueryId>,
+    retries: Retries<query::Tile>,
+
+    base_tile_queries: Vec<query::Tile>,
+    tiles_fetched_time: Time,
+    num_tiles_fetched: usize,
+
+    hips_local_files: HashMap<CreatorDid, HiPSLocalFiles>,
+
+    metrics: TileFetcherMetrics,
+    // Sum of the latencies of the tiles received, in ms
+    total_latency: f32,
+}
+
+#[derive(Debug)]
+enum LocalSource {
+    // One file per tile, e.g. coming from a directory selected by the user
+    Files {
+        tiles: Box<[Box<[HashMap<u64, web_sys::File>]>; 4]>,
+        moc: web_sys::File,
+    },
+    // A zip or tar archive containing the whole HiPS
+    Archive(HiPSArchive),
+}
+
+#[derive(Debug)]
+#[wasm_bindgen]
+pub struct HiPSLocalFiles {
+    source: LocalSource,
+}
+
+use crate::tile_fetcher::query::Tile;
+use crate::HEALPixCell;
+use al_api::hips::ImageExt;
+use al_core::image::format::ImageFormatType;
+use wasm_bindgen::prelude::wasm_bindgen;
+use wasm_bindgen::JsValue;
+
+#[wasm_bindgen]
+impl HiPSLocalFiles {
+    #[wasm_bindgen(constructor)]
+    pub fn new(moc: web_sys::File) -> Self {
+        let tiles_per_fmt = vec![HashMap::new(); 30].into_boxed_slice();
+
+        Self {
+            source: LocalSource::Files {
+                tiles: Box::new([
+                    tiles_per_fmt.clone(),
+                    tiles_per_fmt.clone(),
+                    tiles_per_fmt.clone(),
+                    tiles_per_fmt,
+                ]),
+                moc,
+            },
+        }
+    }
+
+    /// Read a HiPS from the bytes of a zip or tar archive
+    ///
+    /// Only the table of contents of the archive is read. The tiles are extracted from the
+    /// archive when they are requested.
+    #[wasm_bindgen(js_name = fromArchive)]
+    pub fn from_archive(data: Box<[u8]>) -> Result<HiPSLocalFiles, JsValue> {
+        let archive = HiPSArchive::new(data)?;
+
+        Ok(Self {
+            source: LocalSource::Archive(archive),
+        })
+    }
+
+    /// Get the content of the properties file of a HiPS archive
+    #[wasm_bindgen(js_name = getProperties)]
+    pub fn get_properties(&self) -> Result<Option<String>, JsValue> {
+        match &self.source {
+            LocalSource::Files { .. } => Ok(None),
+            LocalSource::Archive(archive) => Ok(archive.get_properties().transpose()?),
+        }
+    }
+
+    /// Get the bytes of a HiPS archive, e.g. to save a generated HiPS
+    #[wasm_bindgen(js_name = getArchive)]
+    pub fn get_archive(&self) -> Option<Box<[u8]>> {
+        match &self.source {
+            LocalSource::Files { .. } => None,
+            LocalSource::Archive(archive) => Some(archive.data().into()),
+        }
+    }
+
+    pub fn insert(&mut self, depth: u8, ipix: u64, ext: ImageExt, file: web_sys::File) {
+        // The tiles of an archive are already indexed
+        if let LocalSource::Files { tiles, .. } = &mut self.source {
+            let tiles_per_fmt = match ext {
+                ImageExt::Fits => &mut tiles[0],
+                ImageExt::Jpeg => &mut tiles[1],
+                ImageExt::Png => &mut tiles[2],
+                ImageExt::Webp => &mut tiles[3],
+            };
+
+            tiles_per_fmt[depth as usize].insert(ipix, file);
+        }
+    }
+
+    fn get_tile(
+        &self,
+        cell: &HEALPixCell,
+        ext: ImageExt,
+    ) -> Option<Result<web_sys::Blob, JsValue>> {
+        match &self.source {
+            LocalSource::Files { tiles, .. } => {
+                let d = cell.depth() as usize;
+                let i = cell.idx();
+
+                let tiles_per_fmt = match ext {
+                    ImageExt::Fits => &tiles[0],
+                    ImageExt::Jpeg => &tiles[1],
+                    ImageExt::Png => &tiles[2],
+                    ImageExt::Webp => &tiles[3],
+                };
+
+                tiles_per_fmt[d].get(&i).map(|file| Ok(file.clone().into()))
+            }
+            LocalSource::Archive(archive) => archive
+                .get_tile(cell, ext)
+                .map(|bytes| bytes_to_blob(&bytes?)),
+        }
+    }
+
+    fn get_moc(&self) -> Option<Result<web_sys::Blob, JsValue>> {
+        match &self.source {
+            LocalSource::Files { moc, .. } => Some(Ok(moc.clone().into())),
+            LocalSource::Archive(archive) => archive.get_moc().map(|bytes| bytes_to_blob(&bytes?)),
+        }
+    }
+}
+
+// Copy bytes extracted from an archive into a javascript blob
+fn bytes_to_blob(bytes: &[u8]) -> Result<web_sys::Blob, JsValue> {
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+    web_sys::Blob::new_with_u8_array_sequence(&parts)
+}
+
+// The priority of a tile of the frame in view
+fn get_priority(query: &query::Tile, view_center: &Vector3<f64>) -> TilePriority {
+    let (lon, lat) = query.cell.center();
+    let pos = crate::math::lonlat::radec_to_xyz(Angle(lon), Angle(lat));
+    TilePriority::new(query.cell.depth(), &pos, view_center)
+}
+
+use crate::renderable::CreatorDid;
+impl TileFetcherQueue {
+    pub fn new() -> Self {
+        let queries = Vec::new();
+        let base_tile_queries = Vec::new();
+        let tiles_fetched_time = Time::now();
+        let num_tiles_fetched = 0;
+
+        Self {
+            queries,
+            sorted: true,
+            wanted: HashMap::new(),
+            in_flight: HashSet::new(),
+            retries: Retries::default(),
+            base_tile_queries,
+            tiles_fetched_time,
+            num_tiles_fetched,
+            hips_local_files: HashMap::new(),
+            metrics: TileFetcherMetrics::default(),
+            total_latency: 0.0,
+        }
+    }
+
+    pub fn insert_hips_local_files(&mut self, id: CreatorDid, local_files: HiPSLocalFiles) {
+        self.hips_local_files.insert(id, local_files);
+    }
+
+    pub fn delete_hips_local_files(&mut self, id: &str) {
+        self.hips_local_files.remove(id);
+    }
+
+    pub fn clear(&mut self) {
+        self.queries.clear();
+        self.wanted.clear();
+        self.sorted = true;
+    }
+
+    /// Add a tile needed by the current view
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The tile query
+    /// * `view_center` - The center of the view expressed in the frame of the HiPS
+    pub fn append(&mut self, query: query::Tile, view_center: &Vector3<f64>) {
+        let priority = get_priority(&query, view_center);
+        self.push(query, priority);
+    }
+
+    /// Add a tile of a neighbouring frame of a cube, fetched after the tiles of the view
+    ///
+    /// # Arguments
+    ///
+    /// * `query` - The tile query
+    /// * `view_center` - The center of the view expressed in the frame of the HiPS
+    /// * `frame_distance` - The number of frames between the tile and the frame in view
+    pub fn append_prefetched(
+        &mut self,
+        query: query::Tile,
+        view_center: &Vector3<f64>,
+        frame_distance: u32,
+    ) {
+        let priority = get_priority(&query, view_center).prefetched(frame_distance);
+        self.push(query, priority);
+    }
+
+    fn push(&mut self, query: query::Tile, priority: TilePriority) {
+        self.wanted.insert(query.id.clone(), priority);
+        self.queries.push((priority, query));
+        self.sorted = false;
+    }
+
+    // fetch the base tile
+    pub fn append_base_tile(&mut self, query: query::Tile) {
+        self.base_tile_queries.push(query);
+    }
+
+    pub fn notify(&mut self, downloader: Rc<RefCell<Downloader>>, dt: Option<DeltaTime>) {
+        // notify all the x ms
+        let now = Time::now();
+
+        if let Some(dt) = dt {
+            if now - self.tiles_fetched_time >= dt {
+                self.tiles_fetched_time = now;
+                self.fetch(downloader);
+            }
+        } else {
+            self.tiles_fetched_time = now;
+            self.fetch(downloader);
+        }
+    }
+
+    pub fn get_num_tile_fetched(&self) -> usize {
+        self.num_tiles_fetched
+    }
+
+    pub fn get_metrics(&self) -> TileFetcherMetrics {
+        let mut metrics = self.metrics.clone();
+        metrics.num_queued = self.queries.len();
+        metrics.num_in_flight = self.in_flight.len();
+        if metrics.num_received > 0 {
+            metrics.mean_latency = self.total_latency / (metrics.num_received as f32);
+        }
+
+        metrics
+    }
+
+    /// Abort the requests of the tiles that are not needed by the view anymore
+    pub fn cancel_unwanted(&mut self, downloader: &RefCell<Downloader>) {
+        let downloader = downloader.borrow();
+        let wanted = &self.wanted;
+        let metrics = &mut self.metrics;
+
+        self.in_flight.retain(|id| {
+            if !downloader.is_queried(id) {
+                // Already received
+                false
+            } else if wanted.contains_key(id) {
+                true
+            } else {
+                downloader.abort(id);
+                metrics.num_cancelled += 1;
+                false
+            }
+        });
+    }
+
+    /// Keep track of a tile received or that could not be retrieved
+    ///
+    /// # Arguments
+    ///
+    /// * `tile` - The tile resource
+    /// * `hips_url` - The url the HiPS is currently retrieved from
+    pub fn register_tile(&mut self, tile: &TileResource, hips_url: &str) {
+        let id = tile.get_id();
+        self.in_flight.remove(id);
+
+        // The url created for a local tile is not needed anymore
+        let url = tile.get_url();
+        if url.starts_with("blob:") {
+            let _ = web_sys::Url::revoke_object_url(url);
+        }
+
+        let now = Time::now();
+        match tile.failure() {
+            None => {
+                self.retries.succeeded(id);
+                self.metrics.num_received += 1;
+                self.total_latency += (now - tile.time_req).as_millis();
+            }
+            // Cancelled because not needed anymore
+            Some(Failure::Aborted) => (),
+            Some(failure) => {
+                let query = query::Tile::new(
+                    tile.cell(),
+                    tile.get_hips_cdid().clone(),
+                    hips_url.to_string(),
+                    tile.format,
+                    tile.frame,
+                );
+
+                if self
+                    .retries
+                    .failed(id, query, failure == Failure::Transient, now)
+                {
+                    self.metrics.num_retried += 1;
+                } else {
+                    self.metrics.num_failed += 1;
+                }
+            }
+        }
+    }
+
+    /// Forget the failed tiles of a HiPS so that they are requested again,
+    /// e.g. when its tiles are retrieved from another mirror
+    pub fn forget_failures(&mut self, hips_cdid: &str) {
+        self.retries.retain(|query| query.hips_cdid != hips_cdid);
+    }
+
+    fn check_in_file_list(&self, mut query: Tile) -> Result<Tile, JsValue> {
+        if let Some(local_hips) = self.hips_local_files.get(&query.hips_cdid) {
+            if let Some(tile) =
+                local_hips.get_tile(&query.cell, query.format.get_ext_file().clone())
+            {
+                if let Ok(url) = web_sys::Url::create_object_url_with_blob(&tile?) {
+                    // rewrite the url
+                    query.url = url;
+                    Ok(query)
+                } else {
+                    Err(JsValue::from_str("could not create an url from the tile"))
+                }
+            } else {
+                Ok(query)
+            }
+        } else {
+            Ok(query)
+        }
+    }
+
+    fn fetch(&mut self, downloader: Rc<RefCell<Downloader>>) {
+        // Fetch the base tiles with higher priority
+        while let Some(query) = self.base_tile_queries.pop() {
+            if let Ok(query) = self.check_in_file_list(query) {
+                fetch_tile(&mut downloader.borrow_mut(), query);
+            }
+        }
+
+        let now = Time::now();
+        // The tiles to request again, if they are still needed
+        let retried = self
+            .retries
+            .due(now)
+            .filter_map(|(id, query)| self.wanted.get(id).map(|p| (*p, query.clone())))
+            .collect::<Vec<_>>();
+        for (priority, query) in retried {
+            self.retries.requeued(&query.id);
+            self.queries.push((priority, query));
+            self.sorted = false;
+        }
+
+        if !self.sorted {
+            // The most urgent queries are put at the end of the stack
+            self.queries.sort_by(|(p1, _), (p2, _)| p2.cmp(p1));
+            self.sorted = true;
+        }
+
+        // Discard the less urgent queries
+        if self.queries.len() > MAX_QUERY_QUEUE_LENGTH {
+            let num_dropped = self.queries.len() - MAX_QUERY_QUEUE_LENGTH;
+            self.queries.drain(..num_dropped);
+            self.metrics.num_dropped += num_dropped;
+        }
+
+        let mut num_fetched_tile = 0;
+        while num_fetched_tile < MAX_NUM_TILE_FETCHING && !self.queries.is_empty() {
+            let (_, query) = self.queries.pop().unwrap_abort();
+
+            if !self.retries.can_fetch(&query.id, now) {
+                continue;
+            }
+
+            let id = query.id.clone();
+            if let Ok(query) = self.check_in_file_list(query) {
+                if fetch_tile(&mut downloader.borrow_mut(), query) {
+                    // The fetch has succeded
+                    num_fetched_tile += 1;
+                    self.in_flight.insert(id);
+                }
+            }
+        }
+
+        self.num_tiles_fetched += num_fetched_tile;
+        self.metrics.num_requested += num_fetched_tile;
+    }
+
+    pub fn launch_starting_hips_requests(
+        &mut self,
+        hips: &HiPS,
+        downloader: Rc<RefCell<Downloader>>,
+    ) {
+        let cfg = hips.get_config();
+        // Request for the allsky first
+        // The allsky is not mandatory present in a HiPS service but it is better to first try to search for it
+        //downloader.fetch(query::PixelMetadata::new(cfg));
+        // Try to fetch the MOC
+        let hips_cdid = cfg.get_creator_did();
+        let local_moc = self
+            .hips_local_files
+            .get(hips_cdid)
+            .and_then(|local_hips| local_hips.get_moc())
+            .and_then(|moc| moc.ok());
+        let moc_url = if let Some(moc) = local_moc {
+            if let Ok(url) = web_sys::Url::create_object_url_with_blob(&moc) {
+                url
+            } else {
+                format!("{}/Moc.fits", cfg.get_root_url())
+            }
+        } else {
+            format!("{}/Moc.fits", cfg.get_root_url())
+        };
+
+        let moc_fetched = downloader.borrow_mut().fetch(query::Moc::new(
+            moc_url.clone(),
+            cfg.get_creator_did().to_string(),
+            al_api::moc::MOC::default(),
+        ));
+        if !moc_fetched && moc_url.starts_with("blob:") {
+            let _ = web_sys::Url::revoke_object_url(&moc_url);
+        }
+
+        let tile_size = cfg.get_tile_size();
+        //Request the allsky for the small tile size or if base tiles are not available
+        if tile_size <= 128 || cfg.get_min_depth_tile() > 0 {
+            // Request the allsky
+            downloader.borrow_mut().fetch(query::Allsky::new(cfg));
+        } else if cfg.get_min_depth_tile() == 0 {
+            #[cfg(target_arch = "wasm32")]
+            {
+                let hips_cdid = cfg.get_creator_did().to_string();
+                let hips_url = cfg.get_root_url().to_string();
+                let hips_fmt = cfg.get_format();
+                let hips_frame = cfg.get_cube_frame();
+                let min_order = cfg.get_min_depth_texture();
+
+                for tile_cell in crate::healpix::cell::ALLSKY_HPX_CELLS_D0 {
+                    if let Ok(query) = self.check_in_file_list(query::Tile::new(
+                        tile_cell,
+                        hips_cdid.clone(),
+                        hips_url.clone(),
+                        hips_fmt,
+                        hips_frame,
+                    )) {
+                        let dl = downloader.clone();
+
+                        crate::utils::set_timeout(
+                            move || {
+                                fetch_tile(&mut dl.borrow_mut(), query);
+                            },
+                            2_000,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Fetch a tile, revoking the url created for a local tile if it is not requested
+fn fetch_tile(downloader: &mut Downloader, query: Tile) -> bool {
+    let blob_url = query.url.starts_with("blob:").then(|| query.url.clone());
+
+    let fetched = downloader.fetch(query);
+    if let (false, Some(url)) = (fetched, blob_url) {
+        let _ = web_sys::Url::revoke_object_url(&url);
+    }
+
+    fetched
+}
@@ -0,0 +1,112 @@
+use cgmath::{InnerSpace, Vector3};
+use std::cmp::Ordering;
+
+/// The urgency of a tile request, the lowest being fetched first
+///
+/// The tiles of the frame in view come before the ones of the neighbouring frames of a
+/// cube that are prefetched. Then the coarsest tiles come first as they quickly cover the
+/// view, then the tiles closest to the center of the view among the ones of the same order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TilePriority {
+    // The number of frames between the tile and the frame in view, 0 for a tile in view
+    frame_distance: u32,
+    depth: u8,
+    // Angular distance in radians between the tile center and the view center
+    dist: f64,
+}
+
+impl TilePriority {
+    /// # Arguments
+    ///
+    /// * `depth` - The order of the tile
+    /// * `pos` - The unit vector of the tile center
+    /// * `center` - The unit vector of the view center, both given in the HiPS frame
+    pub fn new(depth: u8, pos: &Vector3<f64>, center: &Vector3<f64>) -> Self {
+        // More precise than the arccosinus of the dot product for close positions
+        let dist = pos.cross(*center).magnitude().atan2(pos.dot(*center));
+
+        Self {
+            frame_distance: 0,
+            depth,
+            dist,
+        }
+    }
+
+    /// The priority of a tile of a neighbouring frame prefetched
+    ///
+    /// # Arguments
+    ///
+    /// * `frame_distance` - The number of frames between the tile and the frame in view
+    pub fn prefetched(self, frame_distance: u32) -> Self {
+        Self {
+            frame_distance,
+            ..self
+        }
+    }
+}
+
+impl Eq for TilePriority {}
+
+impl PartialOrd for TilePriority {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TilePriority {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.frame_distance
+            .cmp(&other.frame_distance)
+            .then_with(|| self.depth.cmp(&other.depth))
+            .then_with(|| self.dist.total_cmp(&other.dist))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TilePriority;
+    use cgmath::{InnerSpace, Vector3};
+
+    #[test]
+    fn coarse_and_central_tiles_first() {
+        let center = Vector3::new(0.0, 0.0, 1.0);
+        let near = Vector3::new(0.01, 0.0, 1.0).normalize();
+        let far = Vector3::new(0.0, 0.5, 1.0).normalize();
+        let opposite = Vector3::new(0.0, 0.0, -1.0);
+
+        let mut priorities = vec![
+            TilePriority::new(5, &far, &center),
+            TilePriority::new(5, &opposite, &center),
+            TilePriority::new(3, &opposite, &center),
+            TilePriority::new(5, &near, &center),
+            TilePriority::new(5, &center, &center),
+        ];
+        priorities.sort();
+
+        assert_eq!(
+            priorities,
+            vec![
+                TilePriority::new(3, &opposite, &center),
+                TilePriority::new(5, &center, &center),
+                TilePriority::new(5, &near, &center),
+                TilePriority::new(5, &far, &center),
+                TilePriority::new(5, &opposite, &center),
+            ]
+        );
+        assert!((priorities[4].dist - std::f64::consts::PI).abs() < 1e-12);
+        assert!((priorities[2].dist - 0.01f64.atan()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn prefetched_tiles_last() {
+        let center = Vector3::new(0.0, 0.0, 1.0);
+        let opposite = Vector3::new(0.0, 0.0, -1.0);
+
+        let in_view = TilePriority::new(9, &opposite, &center);
+        let next_frame = TilePriority::new(3, &center, &center).prefetched(1);
+        let far_frame = TilePriority::new(3, &center, &center).prefetched(2);
+
+        assert!(in_view < next_frame);
+        assert!(next_frame < far_frame);
+    }
+}
@@ -0,0 +1,172 @@
+use crate::downloader::QueryId;
+use crate::time::{DeltaTime, Time};
+
+use std::collections::HashMap;
+
+/// Number of times a tile is requested again after a transient error
+const MAX_NUM_RETRIES: u8 = 3;
+/// Delay before requesting a tile again, doubled after each failure
+const RETRY_BASE_DELAY: DeltaTime = DeltaTime::from_millis(500.0);
+/// The failures are forgotten when too many tiles are concerned
+const MAX_NUM_FAILED_TILES: usize = 4096;
+
+#[derive(Debug)]
+struct FailedTile<Q> {
+    query: Q,
+    num_failures: u8,
+    // The time from which the tile can be requested again, None if it will not be
+    retry_time: Option<Time>,
+    // The tile has been queued again and waits to be requested
+    requeued: bool,
+}
+
+/// The tiles that could not be retrieved
+///
+/// Tiles failing because of a transient error are requested again a few times
+/// with an exponential backoff. The other ones, e.g. missing tiles, are not requested
+/// anymore.
+#[derive(Debug)]
+pub struct Retries<Q> {
+    tiles: HashMap<QueryId, FailedTile<Q>>,
+}
+
+// Not derived as it would require the queries to implement Default
+impl<Q> Default for Retries<Q> {
+    fn default() -> Self {
+        Self {
+            tiles: HashMap::new(),
+        }
+    }
+}
+
+impl<Q> Retries<Q> {
+    /// Register the failure of a tile
+    ///
+    /// Returns true if the tile will be requested again
+    ///
+    /// # Arguments
+    ///
+    /// * `id` - The id of the tile query
+    /// * `query` - The query to perform to request the tile again
+    /// * `transient` - Whether the error is known to be transient, e.g. a network error or a
+    ///   server error. The failures whose cause is unknown are not retried
+    /// * `now` - The time of the failure
+    pub fn failed(&mut self, id: &QueryId, query: Q, transient: bool, now: Time) -> bool {
+        if self.tiles.len() >= MAX_NUM_FAILED_TILES && !self.tiles.contains_key(id) {
+            self.tiles.clear();
+        }
+
+        let num_failures = self.tiles.get(id).map_or(0, |tile| tile.num_failures) + 1;
+        let retry_time = if transient && num_failures <= MAX_NUM_RETRIES {
+            let backoff = (1 << (num_failures - 1)) as f32;
+            Some(now + RETRY_BASE_DELAY * backoff)
+        } else {
+            None
+        };
+
+        self.tiles.insert(
+            id.clone(),
+            FailedTile {
+                query,
+                num_failures,
+                retry_time,
+                requeued: false,
+            },
+        );
+
+        retry_time.is_some()
+    }
+
+    /// The tile has been received, its past failures are forgotten
+    pub fn succeeded(&mut self, id: &QueryId) {
+        self.tiles.remove(id);
+    }
+
+    /// Whether a tile can be requested: it never failed or it is time to request it again
+    pub fn can_fetch(&self, id: &QueryId, now: Time) -> bool {
+        match self.tiles.get(id) {
+            Some(tile) => tile.retry_time.is_some_and(|retry_time| retry_time <= now),
+            None => true,
+        }
+    }
+
+    /// The queries of the tiles to request again that have not been queued yet
+    pub fn due(&self, now: Time) -> impl Iterator<Item = (&QueryId, &Q)> {
+        self.tiles
+            .iter()
+            .filter(move |(_, tile)| {
+                !tile.requeued && tile.retry_time.is_some_and(|retry_time| retry_time <= now)
+            })
+            .map(|(id, tile)| (id, &tile.query))
+    }
+
+    /// The tile has been queued again, it is not due anymore until it fails again
+    pub fn requeued(&mut self, id: &QueryId) {
+        if let Some(tile) = self.tiles.get_mut(id) {
+            tile.requeued = true;
+        }
+    }
+
+    /// Only keep the failures of the tiles satisfying the predicate
+    pub fn retain(&mut self, mut f: impl FnMut(&Q) -> bool) {
+        self.tiles.retain(|_, tile| f(&tile.query));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Retries;
+    use crate::time::Time;
+
+    #[test]
+    fn transient_errors_are_retried_with_backoff() {
+        let mut retries = Retries::default();
+        let id = "ivo://CDS/P/DSS2/color342jpeg".to_string();
+
+        assert!(retries.can_fetch(&id, Time(0.0)));
+        assert!(retries.failed(&id, "query", true, Time(0.0)));
+        assert!(!retries.can_fetch(&id, Time(499.0)));
+        assert!(retries.can_fetch(&id, Time(500.0)));
+        assert_eq!(retries.due(Time(500.0)).count(), 1);
+        // Once queued again, the tile is not due anymore but can still be requested
+        retries.requeued(&id);
+        assert_eq!(retries.due(Time(600.0)).count(), 0);
+        assert!(retries.can_fetch(&id, Time(600.0)));
+
+        assert!(retries.failed(&id, "query", true, Time(1000.0)));
+        assert!(!retries.can_fetch(&id, Time(1999.0)));
+        assert!(retries.can_fetch(&id, Time(2000.0)));
+
+        assert!(retries.failed(&id, "query", true, Time(2000.0)));
+        assert!(retries.can_fetch(&id, Time(4000.0)));
+
+        // The number of retries is bounded by MAX_NUM_RETRIES
+        assert!(!retries.failed(&id, "query", true, Time(4000.0)));
+        assert!(!retries.can_fetch(&id, Time(1e9)));
+        assert_eq!(retries.due(Time(1e9)).count(), 0);
+    }
+
+    #[test]
+    fn permanent_errors_are_not_retried() {
+        let mut retries = Retries::default();
+        let id = "ivo://CDS/P/DSS2/color342jpeg".to_string();
+
+        assert!(!retries.failed(&id, "query", false, Time(0.0)));
+        assert!(!retries.can_fetch(&id, Time(1e9)));
+
+        // Forgotten for instance when the HiPS is retrieved from another mirror
+        retries.retain(|_| false);
+        assert!(retries.can_fetch(&id, Time(0.0)));
+    }
+
+    #[test]
+    fn success_forgets_the_failures() {
+        let mut retries = Retries::default();
+        let id = "ivo://CDS/P/DSS2/color342jpeg".to_string();
+
+        retries.failed(&id, "query", true, Time(0.0));
+        retries.succeeded(&id);
+        assert!(retries.can_fetch(&id, Time(0.0)));
+        assert_eq!(retries.due(Time(1e9)).count(), 0);
+    }
+}
@@ -0,0 +1,32 @@
+#version 300 es
+precision highp float;
+
+in vec2 frag_r;
+in float frag_weight;
+
+out vec4 color;
+
+uniform int u_kernel;
+
+void main() {
+    float r2 = dot(frag_r, frag_r);
+
+    float k = 0.0;
+    if (u_kernel == 0) {
+        // gaussian
+        k = r2 <= 9.0 ? exp(-0.5 * r2) : 0.0;
+    } else if (u_kernel == 1) {
+        // epanechnikov
+        k = max(1.0 - r2, 0.0);
+    } else {
+        // tophat
+        k = r2 <= 1.0 ? 1.0 : 0.0;
+    }
+
+    if (k == 0.0) {
+        discard;
+    }
+
+    // The kernels are summed in the red channel
+    color = vec4(frag_weight * k, 0.0, 0.0, 1.0);
+}
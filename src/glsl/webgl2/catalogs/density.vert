@@ -0,0 +1,44 @@
+#version 300 es
+precision highp float;
+layout (location = 0) in vec2 vertex;
+layout (location = 1) in vec2 lonlat;
+layout (location = 2) in float weight;
+
+uniform mat4 u_2world;
+uniform vec2 ndc_to_clip;
+uniform float czf;
+uniform vec2 u_screen_size;
+// The bandwidth of the kernel in pixels
+uniform float u_bandwidth;
+uniform int u_kernel;
+// Brings the weights to at most 1 so that their sums fit in 16 bits floats
+uniform float u_weight_scale;
+
+// The position in the kernel in units of bandwidth
+out vec2 frag_r;
+out float frag_weight;
+
+#include ../projection/projection.glsl;
+
+void main() {
+    // 1. Convert (lon, lat) into (x, y, z) space coo.
+    vec3 p_xyz = lonlat2xyz(lonlat);
+    // 2. Convert to the world coo system
+    vec4 p_w = u_2world * vec4(p_xyz, 1.0);
+
+    // The sources behind the sphere are not seen with the TAN and SIN projections
+    if ((u_proj == 0 || u_proj == 2) && p_w.z < 0.0) {
+        gl_Position = vec4(2.0, 2.0, 0.0, 1.0);
+        return;
+    }
+
+    // 3. Process the projection
+    vec2 p_ndc = proj(p_w.xyz) / (ndc_to_clip * czf);
+
+    // The gaussian kernel is cut at 3 sigmas
+    float radius = u_kernel == 0 ? 3.0 : 1.0;
+    gl_Position = vec4(p_ndc + 2.0 * vertex * radius * u_bandwidth / u_screen_size, 0.0, 1.0);
+
+    frag_r = vertex * radius;
+    frag_weight = weight * u_weight_scale;
+}
@@ -0,0 +1,30 @@
+#version 300 es
+precision highp float;
+precision highp sampler2D;
+
+in vec2 out_uv;
+out vec4 color;
+
+// The sum of the kernels of the sources
+uniform sampler2D u_density;
+uniform float u_strength;
+uniform float u_min_density;
+uniform float u_max_density;
+uniform float u_opacity;
+uniform int H;
+
+#include ../colormaps/colormap.glsl;
+#include ../hips/transfer_funcs.glsl;
+
+void main() {
+    float density = texture(u_density, out_uv).r * u_strength;
+    // The regions without sources are not drawn
+    if (density <= 0.0) {
+        discard;
+    }
+
+    float t = transfer_func(H, density, u_min_density, u_max_density);
+
+    color = colormap_f(t);
+    color.a *= u_opacity * smoothstep(0.0, 0.02, t);
+}
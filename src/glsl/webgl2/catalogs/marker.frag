@@ -0,0 +1,72 @@
+#version 300 es
+precision highp float;
+
+in vec2 frag_uv;
+in float frag_size;
+in float frag_value;
+
+out vec4 color;
+
+uniform int u_shape;
+uniform vec3 u_color;
+uniform float u_opacity;
+// Width of the outlines in pixels
+uniform float u_line_width;
+
+#include ../colormaps/colormap.glsl;
+
+const float SQRT_2 = 1.41421356237309504880168872420969808;
+const float SQRT_5 = 2.23606797749978969640917366873127623;
+
+// Distance to the segments of a plus of radius r
+float plus(vec2 p, float r) {
+    vec2 a = abs(p);
+    return min(
+        length(vec2(max(a.x - r, 0.0), a.y)),
+        length(vec2(a.x, max(a.y - r, 0.0)))
+    );
+}
+
+void main() {
+    // One pixel in the uv space of the marker, spanning [-1, 1]
+    float px = 2.0 / frag_size;
+    float half_width = 0.5 * u_line_width * px;
+    // The outline lies inside the marker
+    float r = 1.0 - half_width - px;
+
+    vec2 p = frag_uv;
+    vec2 a = abs(p);
+    float d = 0.0;
+    if (u_shape == 0) {
+        // circle
+        d = abs(length(p) - r);
+    } else if (u_shape == 1) {
+        // square
+        d = abs(max(a.x, a.y) - r);
+    } else if (u_shape == 2) {
+        // cross
+        d = plus(vec2(p.x + p.y, p.x - p.y) / SQRT_2, r);
+    } else if (u_shape == 3) {
+        // plus
+        d = plus(p, r);
+    } else if (u_shape == 4) {
+        // rhomb
+        d = abs(a.x + a.y - r) / SQRT_2;
+    } else {
+        // triangle pointing up
+        d = abs(max(-p.y - r, (2.0 * a.x + p.y - r) / SQRT_5));
+    }
+
+    float alpha = 1.0 - smoothstep(half_width, half_width + px, d);
+    if (alpha <= 0.0) {
+        discard;
+    }
+
+    // The sources without a value are drawn with the color of the catalog
+    if (frag_value < 0.0) {
+        color = vec4(u_color, 1.0);
+    } else {
+        color = colormap_f(frag_value);
+    }
+    color.a *= alpha * u_opacity;
+}
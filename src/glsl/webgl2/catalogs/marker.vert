@@ -0,0 +1,43 @@
+#version 300 es
+precision highp float;
+layout (location = 0) in vec2 vertex;
+layout (location = 1) in vec2 lonlat;
+layout (location = 2) in float size;
+layout (location = 3) in float value;
+
+uniform mat4 u_2world;
+uniform vec2 ndc_to_clip;
+uniform float czf;
+uniform vec2 u_screen_size;
+// Converts the size of the markers to pixels
+uniform float u_size_scale;
+
+out vec2 frag_uv;
+out float frag_size;
+out float frag_value;
+
+#include ../projection/projection.glsl;
+
+void main() {
+    // 1. Convert (lon, lat) into (x, y, z) space coo.
+    vec3 p_xyz = lonlat2xyz(lonlat);
+    // 2. Convert to the world coo system
+    vec4 p_w = u_2world * vec4(p_xyz, 1.0);
+
+    // The sources behind the sphere are not seen with the TAN and SIN projections.
+    // All the vertices are moved out of the clip space
+    if ((u_proj == 0 || u_proj == 2) && p_w.z < 0.0) {
+        gl_Position = vec4(2.0, 2.0, 0.0, 1.0);
+        return;
+    }
+
+    // 3. Process the projection
+    vec2 p_ndc = proj(p_w.xyz) / (ndc_to_clip * czf);
+
+    float size_px = size * u_size_scale;
+    gl_Position = vec4(p_ndc + vertex * size_px / u_screen_size, 0.0, 1.0);
+
+    frag_uv = vertex;
+    frag_size = size_px;
+    frag_value = value;
+}
@@ -0,0 +1,40 @@
+#version 300 es
+precision highp float;
+precision highp sampler2D;
+precision mediump int;
+
+in vec2 frag_uv;
+
+out vec4 out_frag_color;
+
+// The channels rendered with a linear stretch between their cuts
+uniform sampler2D tex_r;
+uniform sampler2D tex_g;
+uniform sampler2D tex_b;
+
+// Weights of the channels
+uniform vec3 scales;
+uniform float Q;
+uniform float stretch;
+uniform float opacity;
+
+void main() {
+    vec4 r = texture(tex_r, frag_uv);
+    vec4 g = texture(tex_g, frag_uv);
+    vec4 b = texture(tex_b, frag_uv);
+
+    vec3 color = vec3(r.r, g.r, b.r) * scales;
+
+    // Joint stretch of the total intensity (Lupton et al. 2004)
+    float I = (color.r + color.g + color.b) / 3.0;
+    float slope = 0.1 / asinh(0.1 * Q);
+    float f = I > 0.0 ? asinh(I * Q / stretch) * slope / I : 0.0;
+    color *= f;
+
+    // Saturated pixels keep their hue
+    float m = max(color.r, max(color.g, color.b));
+    color /= max(m, 1.0);
+
+    float alpha = max(r.a, max(g.a, b.a));
+    out_frag_color = vec4(color, alpha * opacity);
+}
@@ -1,7 +1,8 @@
 //const int MAX_NUM_TEX = 3;
 uniform sampler2D tex1;
 uniform sampler2D tex2;
-//uniform sampler2D tex3;
+uniform sampler2D tex3;
+uniform sampler2D tex4;
 
 uniform int num_tex;
 
@@ -32,7 +33,10 @@ vec4 get_pixels(vec3 uv) {
         return vec4(0.0, 1.0, 0.0, 1.0);
     }*/
     int idx_texture = int(uv.z);
-    return mix(texture(tex1, uv.xy), texture(tex2, uv.xy), float(idx_texture));
+    vec4 color = texture(tex1, uv.xy);
+    color = mix(color, texture(tex2, uv.xy), float(idx_texture == 1));
+    color = mix(color, texture(tex3, uv.xy), float(idx_texture == 2));
+    return mix(color, texture(tex4, uv.xy), float(idx_texture == 3));
 }
 
 vec3 reverse_uv(vec3 uv) {
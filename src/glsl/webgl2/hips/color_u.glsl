@@ -1,7 +1,8 @@
 //const int MAX_NUM_TEX = 3;
 uniform usampler2D tex1;
 uniform usampler2D tex2;
-//uniform usampler2D tex3;
+uniform usampler2D tex3;
+uniform usampler2D tex4;
 uniform int num_tex;
 
 uniform float scale;
@@ -35,7 +36,10 @@ uvec4 get_pixels(vec3 uv) {
     //return texture(tex1, uv.xy);
     //int idx_texture = int(uv.z);
     int idx_texture = int(uv.z);
-    return uvec4(mix(vec4(texture(tex1, uv.xy)), vec4(texture(tex2, uv.xy)), float(idx_texture)));
+    vec4 color = vec4(texture(tex1, uv.xy));
+    color = mix(color, vec4(texture(tex2, uv.xy)), float(idx_texture == 1));
+    color = mix(color, vec4(texture(tex3, uv.xy)), float(idx_texture == 2));
+    return uvec4(mix(color, vec4(texture(tex4, uv.xy)), float(idx_texture == 3)));
 }
 
 vec3 reverse_uv(vec3 uv) {
@@ -19,6 +19,9 @@ uniform float opacity;
 void main() {
     vec4 color_start = get_colormap_from_grayscale_texture(frag_uv_start);
     vec4 color_end = get_colormap_from_grayscale_texture(frag_uv_end);
+    // A missing texture is drawn transparent
+    color_start = mix(color_start, vec4(0.0), m_start);
+    color_end = mix(color_end, vec4(0.0), m_end);
 
     out_frag_color = mix(color_start, color_end, frag_blending_factor);
     out_frag_color.a = out_frag_color.a * opacity;
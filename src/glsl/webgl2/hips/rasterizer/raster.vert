@@ -21,6 +21,8 @@ uniform mat4 inv_model;
 uniform vec2 ndc_to_clip;
 uniform float czf;
 uniform float current_time;
+// duration of the cross-fade between two textures in ms
+uniform float blending_duration;
 
 #include ../../projection/projection.glsl;
 
@@ -35,7 +37,7 @@ void main() {
 
     frag_uv_start = uv_start;
     frag_uv_end = uv_end;
-    frag_blending_factor = min((current_time - time_tile_received) / 200.0, 1.0);
+    frag_blending_factor = min((current_time - time_tile_received) / blending_duration, 1.0);
     m_start = m0;
     m_end = m1;
 }
\ No newline at end of file
@@ -17,6 +17,8 @@ struct Tile {
 };
 
 uniform Tile textures_tiles[12];
+// The number of textures by side of a slice of the texture array
+uniform int num_textures_by_side_slice;
 
 uniform float opacity;
 struct TileColor {
@@ -37,12 +39,13 @@ vec4 get_tile_color(vec3 pos) {
 
     Tile tile = textures_tiles[idx];
 
-    int idx_texture = tile.texture_idx >> 6;
-    int off = tile.texture_idx & 0x3F;
-    float idx_row = float(off >> 3); // in [0; 7]
-    float idx_col = float(off & 0x7); // in [0; 7]
+    int num_textures_by_slice = num_textures_by_side_slice * num_textures_by_side_slice;
+    int idx_texture = tile.texture_idx / num_textures_by_slice;
+    int off = tile.texture_idx % num_textures_by_slice;
+    float idx_row = float(off / num_textures_by_side_slice);
+    float idx_col = float(off % num_textures_by_side_slice);
 
-    vec2 offset = (vec2(idx_col, idx_row) + uv)*0.125;
+    vec2 offset = (vec2(idx_col, idx_row) + uv) / float(num_textures_by_side_slice);
     vec3 UV = vec3(offset, float(idx_texture));
 
     vec4 color = get_colormap_from_grayscale_texture(UV);
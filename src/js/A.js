@@ -360,6 +360,8 @@ A.graphicOverlay = function (options) {
  *
  * @param {string} url - Root url of the catalog
  * @param {CatalogOptions} options - Options for configuring the catalogue.
+ *  Two more options are accepted: `webgl`, true to load the tiles and draw the sources as a heatmap
+ *  in the WebGL core, and `colormap`, the colormap of the heatmap ('inferno' by default).
  * @returns {ProgressiveCat} Returns a new Overlay object representing the graphic overlay.
  *
  * @example
@@ -1868,6 +1868,105 @@ export let Aladin = (function () {
         return survey;
     };
 
+    /**
+     * Add a color composite of three grayscale FITS image or HiPS layers.
+     * The channels are stretched jointly along their total intensity with an asinh
+     * function (Lupton et al. 2004) so that the colors of the bright sources are kept.
+     * The channel layers are not drawn on their own while the composite is visible.
+     *
+     * @memberof Aladin
+     * @param {string} layer - The name of the composite layer
+     * @param {Object} options
+     * @param {string|Object} options.red - The name of the red channel layer or an object
+     * <code>{layer, minCut, maxCut, scale}</code>. The cuts of the channel layer are taken if not given
+     * @param {string|Object} options.green - The green channel, see <code>options.red</code>
+     * @param {string|Object} options.blue - The blue channel, see <code>options.red</code>
+     * @param {number} [options.q=8] - The softening parameter of the asinh stretch
+     * @param {number} [options.stretch=0.2] - The linear range of the stretch, the channels being normalized between their cuts
+     * @param {number} [options.opacity=1.0] - The opacity of the composite
+     */
+    Aladin.prototype.addRGBComposite = function (layer, options) {
+        const cfg = Aladin._rgbCompositeCfg(layer, options);
+        this.view.wasm.addRGBComposite(cfg);
+
+        this.rgbComposites = this.rgbComposites || new Map();
+        this.rgbComposites.set(layer, cfg);
+    };
+
+    /**
+     * Change the options of a color composite
+     *
+     * @memberof Aladin
+     * @param {string} layer - The name of the composite layer
+     * @param {Object} options - The options to change, see {@link Aladin#addRGBComposite}
+     */
+    Aladin.prototype.setRGBCompositeOptions = function (layer, options) {
+        const cfg = this.rgbComposites && this.rgbComposites.get(layer);
+        if (!cfg) {
+            throw layer + " is not a RGB composite layer";
+        }
+
+        const newCfg = Aladin._rgbCompositeCfg(layer, {...cfg, ...options});
+        this.view.wasm.setRGBCompositeOptions(newCfg);
+        this.rgbComposites.set(layer, newCfg);
+    };
+
+    /**
+     * Remove a color composite. Its channel layers are drawn again.
+     *
+     * @memberof Aladin
+     * @param {string} layer - The name of the composite layer
+     */
+    Aladin.prototype.removeRGBComposite = function (layer) {
+        if (this.rgbComposites && this.rgbComposites.delete(layer)) {
+            this.view.wasm.removeLayer(layer);
+        }
+    };
+
+    /**
+     * Read the value of a pixel of a layer
+     *
+     * @memberof Aladin
+     * @param {number} x - The x screen coordinate in pixels
+     * @param {number} y - The y screen coordinate in pixels
+     * @param {string} [layer="base"] - The name of the layer
+     *
+     * @returns {number|Array|Object} - The value of a FITS pixel, the rgb(a) array of a colored pixel,
+     * or an object <code>{r, g, b}</code> with the values of the channels of a RGB composite
+     */
+    Aladin.prototype.readPixel = function (x, y, layer = "base") {
+        return this.view.wasm.readPixel(x, y, layer);
+    };
+
+    Aladin._rgbCompositeCfg = function (layer, options) {
+        const channel = (c) => {
+            if (typeof c === "string") {
+                return {layer: c};
+            }
+
+            return c;
+        };
+
+        let cfg = {
+            layer: layer,
+            red: channel(options.red),
+            green: channel(options.green),
+            blue: channel(options.blue),
+        };
+
+        if (options.q !== undefined) {
+            cfg.q = options.q;
+        }
+        if (options.stretch !== undefined) {
+            cfg.stretch = options.stretch;
+        }
+        if (options.opacity !== undefined) {
+            cfg.opacity = options.opacity;
+        }
+
+        return cfg;
+    };
+
     // @api
     Aladin.prototype.increaseZoom = function () {
         this.view.increaseZoom(0.01);
@@ -2304,6 +2403,33 @@ aladin.on("layerChanged", (layer, layerName, state) => {
         }
     }
 
+    /**
+     * Cut a FITS image into the tiles of a HiPS, in the browser
+     *
+     * The tiles are computed in the background during the next frames. They are stored in a zip
+     * archive with the Allsky, the MOC and the properties of the HiPS. The files returned can be
+     * displayed with A.HiPS(id, files) and the archive saved with files.getArchive().
+     *
+     * @memberof Aladin
+     * @param {ArrayBuffer|Uint8Array} fits - The bytes of the FITS file, a 2D image with a celestial WCS
+     * @param {Object} [options] - Options of the generation
+     * @param {string} [options.creatorDid] - The IVOA identifier of the HiPS
+     * @param {string} [options.title] - The title of the HiPS
+     * @param {number} [options.tileWidth=512] - The width of the tiles in pixels, a power of two
+     * @param {number} [options.order] - The deepest order of the tiles, deduced from the resolution of the image by default
+     * @param {number} [options.maxNumTiles] - The maximum number of tiles of the deepest order. By default, the uncompressed tiles take at most 256 MB
+     * @param {function} [options.onProgress] - Called with the fraction of the tiles computed
+     * @returns {Promise<HiPSLocalFiles>} - The files of the HiPS
+     */
+    Aladin.prototype.generateHiPS = function (fits, options) {
+        if (fits instanceof ArrayBuffer) {
+            fits = new Uint8Array(fits);
+        }
+
+        const {onProgress, ...params} = options || {};
+        return this.wasm.generateHiPS(fits, params, onProgress);
+    };
+
     /**
      * Return the current view WCS as a key-value dictionary
      * Can be useful in coordination with getViewDataURL
@@ -2926,6 +3052,18 @@ aladin.displayFITS(
 
     Aladin.prototype.displayPNG = Aladin.prototype.displayJPG;
 
+    /**
+     * Get statistics about the HiPS tile requests, useful to tune the tile fetching
+     *
+     * @memberof Aladin
+     *
+     * @returns {Object} - The number of tiles `numQueued`, `numInFlight`, `numRequested`, `numReceived`,
+     * `numRetried`, `numFailed`, `numCancelled` and `numDropped`, and the `meanLatency` of the requests in ms
+     */
+    Aladin.prototype.getTileFetcherMetrics = function() {
+        return this.view.wasm.getTileFetcherMetrics();
+    };
+
     /**
      * Add a custom colormap from a list of colors
      *
@@ -399,20 +399,20 @@ export let Catalog = (function () {
             url,
             (rsc) => {
                 let table = VOTable.parseRsc(rsc);
-                if (!table || !table.rows || !table.fields) {
+                if (!table || !table.fields || !(table.rows || table.columns)) {
                     errorCallback(
                         "Parsing error of the votable located at: " + url
                     );
                     return;
                 }
 
-                let { fields, rows } = table;
+                let fields;
                 try {
-                    fields = ObsCore.parseFields(fields);
+                    fields = ObsCore.parseFields(table.fields);
                     //fields.subtype = "ObsCore";
                 } catch (e) {
                     // It is not an ObsCore table
-                    fields = Catalog.parseFields(fields, raField, decField);
+                    fields = Catalog.parseFields(table.fields, raField, decField);
                 }
 
                 let sources = [];
@@ -420,25 +420,28 @@ export let Catalog = (function () {
 
                 var coo = new Coo();
 
-                rows.every((row) => {
+                // The values are read from the typed columns of the tables read by the backend
+                const numRows = VOTable.getNumRows(table);
+                for (let row = 0; row < numRows; row++) {
                     let ra, dec, region;
                     var mesures = {};
 
                     for (const [fieldName, field] of Object.entries(fields)) {
+                        const value = VOTable.getValue(table, field.idx, row);
                         if (fieldName === "s_region") {
                             // Obscore s_region param
-                            region = row[field.idx];
+                            region = value;
                         } else if (fieldName === "ra" || fieldName === "s_ra") {
-                            ra = row[field.idx];
+                            ra = value;
                         } else if (
                             fieldName === "dec" ||
                             fieldName === "s_dec"
                         ) {
-                            dec = row[field.idx];
+                            dec = value;
                         }
 
                         var key = field.name;
-                        mesures[key] = row[field.idx];
+                        mesures[key] = value;
                     }
 
                     let source = null;
@@ -458,13 +461,12 @@ export let Catalog = (function () {
 
                         sources.push(source);
                         if (maxNbSources && sources.length == maxNbSources) {
-                            return false;
+                            break;
                         }
                     }
 
                     rowIdx++;
-                    return true;
-                });
+                }
 
                 if (successCallback) {
                     successCallback({
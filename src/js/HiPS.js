@@ -28,7 +28,10 @@
 import { ALEvent } from "./events/ALEvent.js";
 import { ColorCfg } from "./ColorCfg.js";
 import { HiPSProperties } from "./HiPSProperties.js";
+import { HiPSDefinition } from "./HiPSDefinition.js";
 import { Aladin } from "./Aladin.js"; 
+import { Utils } from "./Utils";
+import { Color } from "./Color.js";
 let PropertyParser = {};
 // Utilitary functions for parsing the properties and giving default values
 /// Mandatory tileSize property
@@ -123,6 +126,20 @@ PropertyParser.bitpix = function (properties) {
     return bitpix;
 };
 
+/// HiPS cube properties
+PropertyParser.cube = function (properties) {
+    const toNumber = (value) => value !== undefined ? +value : undefined;
+
+    return {
+        depth: toNumber(properties && properties.hips_cube_depth),
+        firstFrame: toNumber(properties && properties.hips_cube_firstframe),
+        crpix3: toNumber(properties && properties.hips_cube_crpix3),
+        crval3: toNumber(properties && properties.hips_cube_crval3),
+        cdelt3: toNumber(properties && properties.hips_cube_cdelt3),
+        bunit3: properties && properties.hips_cube_bunit3,
+    };
+};
+
 PropertyParser.isPlanetaryBody = function (properties) {
     return properties && properties.hips_body !== undefined;
 };
@@ -152,6 +169,7 @@ PropertyParser.isPlanetaryBody = function (properties) {
  * @property {number} [saturation=0.0] - The saturation value for the color configuration.
  * @property {number} [brightness=0.0] - The brightness value for the color configuration.
  * @property {number} [contrast=0.0] - The contrast value for the color configuration.
+ * @property {string[]} [mirrors] - Urls of other servers delivering the same HiPS. The fastest one is chosen and tiles are retrieved from another one if it fails to deliver them.
  */
 
 /**
@@ -186,6 +204,8 @@ export let HiPS = (function () {
      * <li>A special ID pointing towards a HiPS. One can found the list of IDs {@link https://aladin.cds.unistra.fr/hips/list| here}</li>
      * <li>A dict storing a local HiPS files. This object contains a tile file: hips[order][ipix] = File and refers to the properties file like so: hips["properties"] = File. </li>
      *     A javascript {@link FileList} pointing to the opened webkit directory is also accepted.
+     * <li>The content of a zip or tar archive of a HiPS as an ArrayBuffer or a Uint8Array. The archive must contain the properties file of the HiPS.</li>
+     * <li>The HiPSLocalFiles of a HiPS generated by {@link Aladin#generateHiPS}. It can be given to one layer only.</li>
      * </ul>
      * @param {HiPSOptions} [options] - The option for the survey
      *
@@ -200,7 +220,14 @@ export let HiPS = (function () {
         this.name = (options && options.name) || undefined;
         this.startUrl = options.startUrl;
 
-        if (location instanceof FileList) {
+        if (location instanceof ArrayBuffer || location instanceof Uint8Array) {
+            // The tiles are extracted from the archive when they are requested
+            this.archive = new Uint8Array(location);
+        } else if (Aladin.wasmLibs.core
+            && location instanceof Aladin.wasmLibs.core.HiPSLocalFiles) {
+            // The archive of a generated HiPS, already indexed
+            this.archiveFiles = location;
+        } else if (location instanceof FileList) {
             let localFiles = {};
             for (var file of location) {
                 let path = file.webkitRelativePath;
@@ -251,6 +278,7 @@ export let HiPS = (function () {
         this.defaultFitsMaxCut = options.defaultFitsMaxCut;
         this.numBitsPerPixel = options.numBitsPerPixel;
         this.creatorDid = options.creatorDid;
+        this.mirrors = options.mirrors;
         this.errorCallback = options.errorCallback;
         this.successCallback = options.successCallback;
 
@@ -258,35 +286,40 @@ export let HiPS = (function () {
     };
 
     HiPS.prototype._fetchFasterUrlFromProperties = function(properties) {
-        let self = this;
-
-        HiPSProperties.getFasterMirrorUrl(properties)
-            .then((url) => {
-                if (self.url !== url) {
-                    console.info(
-                        "Change url of ",
-                        self.id,
-                        " to ",
-                        url
-                    );
-
-                    self.url = url;
-
-                    // If added to the backend, then we need to tell it the url has changed
-                    if (self.added) {
-                        self.view.wasm.setHiPSUrl(
-                            self.creatorDid,
-                            url
-                        );
-                    }
-                }
-            })
-            .catch((e) => {
-                console.error(self);
-                console.error(e);
-            });
+        // The backend measures the latency of each mirror and chooses the fastest one
+        this.setMirrors(HiPSProperties.getMirrorUrls(properties));
     }
 
+    /**
+     * Sets the mirrors the HiPS tiles can be retrieved from.
+     *
+     * The fastest mirror is chosen. Tiles are retrieved from another mirror when the current one fails to deliver them,
+     * in which case an "AL:HiPSLayer.mirrorChanged" event is dispatched.
+     *
+     * @memberof HiPS
+     *
+     * @param {string[]} urls - The urls of the servers delivering the HiPS
+     */
+    HiPS.prototype.setMirrors = function (urls) {
+        this.mirrors = urls.map((url) => Utils.fixURLForHTTPS(url));
+
+        // If added to the backend, then we need to tell it the mirrors
+        if (this.added) {
+            this.view.wasm.setHiPSMirrors(this.creatorDid, this.mirrors);
+        }
+    };
+
+    /**
+     * Gets the urls of the servers delivering the HiPS.
+     *
+     * @memberof HiPS
+     *
+     * @returns {string[]} The urls of the mirrors, the url the tiles are currently retrieved from included
+     */
+    HiPS.prototype.getMirrors = function () {
+        return this.mirrors || [this.url];
+    };
+
     HiPS.prototype._parseProperties = function(properties) {
         let self = this;
         self.creatorDid = properties.creator_did || self.creatorDid;
@@ -335,6 +368,9 @@ export let HiPS = (function () {
         self.numBitsPerPixel =
             PropertyParser.bitpix(properties) || self.numBitsPerPixel;
 
+        // HiPS cube
+        self.cube = PropertyParser.cube(properties);
+
         // HiPS body
         if (properties.hips_body) {
             self.hipsBody = properties.hips_body;
@@ -459,18 +495,39 @@ export let HiPS = (function () {
         }
         this.view = view;
 
+        if (this.archive || this.archiveFiles) {
+            // Read the properties file from the archive
+            self.query = (async () => {
+                // The archive is indexed once, its tiles are then given to the backend
+                self.archiveFiles = self.archiveFiles
+                    || Aladin.wasmLibs.core.HiPSLocalFiles.fromArchive(self.archive);
+                let properties = self.archiveFiles.getProperties();
+
+                if (!properties) {
+                    throw 'No properties file found in the HiPS archive';
+                }
+
+                self.propertiesText = properties;
+                self._parseProperties(HiPSProperties.parse(properties));
+                self.url = "local";
+
+                return self;
+            })();
+
+            return;
+        }
+
         if (this.localFiles) {
             // Fetch the properties file
             self.query = (async () => {
                 // look for the properties file
-                await HiPSProperties.fetchFromFile(self.localFiles["properties"])
-                    .then((p) => {
-                        self._parseProperties(p);
+                const text = await HiPSProperties.fetchTextFromFile(self.localFiles["properties"]);
+                self.propertiesText = text;
+                self._parseProperties(HiPSProperties.parse(text));
 
-                        self.url = "local";
+                self.url = "local";
 
-                        delete self.localFiles["properties"]
-                    })
+                delete self.localFiles["properties"]
 
                 return self;
             })();
@@ -508,10 +565,9 @@ export let HiPS = (function () {
                 // ID typed url
                 if (self.startUrl && isID) {
                     // First download the properties from the start url
-                    await HiPSProperties.fetchFromUrl(self.startUrl)
-                        .then((p) => {
-                            self._parseProperties(p);
-                        })
+                    const {text, serviceUrl} = await HiPSProperties.fetchTextFromUrl(self.startUrl);
+                    self.propertiesText = text;
+                    self._parseProperties(HiPSProperties.parseText(text, serviceUrl));
 
                     try {
                         // the url stores a "CDS ID" we take it prioritaly
@@ -555,10 +611,9 @@ export let HiPS = (function () {
                         throw e;
                     }
                 } else {
-                    await HiPSProperties.fetchFromUrl(self.url)
-                        .then((p) => {
-                            self._parseProperties(p);
-                        })
+                    const {text, serviceUrl} = await HiPSProperties.fetchTextFromUrl(self.url);
+                    self.propertiesText = text;
+                    self._parseProperties(HiPSProperties.parseText(text, serviceUrl));
                 }
             } else {
                 self._parseProperties({
@@ -889,6 +944,12 @@ export let HiPS = (function () {
                 hipsInitialDec: self.initialDec,
                 isPlanetaryBody: self.isPlanetaryBody(),
                 hipsBody: self.hipsBody,
+                hipsCubeDepth: self.cube && self.cube.depth,
+                hipsCubeFirstframe: self.cube && self.cube.firstFrame,
+                hipsCubeCrpix3: self.cube && self.cube.crpix3,
+                hipsCubeCrval3: self.cube && self.cube.crval3,
+                hipsCubeCdelt3: self.cube && self.cube.cdelt3,
+                hipsCubeBunit3: self.cube && self.cube.bunit3,
             },
             meta: {
                 ...this.colorCfg.get(),
@@ -898,7 +959,12 @@ export let HiPS = (function () {
         };
 
         let localFiles;
-        if (this.localFiles) {
+        if (this.archive || this.archiveFiles) {
+            // The backend takes the ownership of the index of the archive
+            localFiles = this.archiveFiles
+                || Aladin.wasmLibs.core.HiPSLocalFiles.fromArchive(this.archive);
+            this.archiveFiles = undefined;
+        } else if (this.localFiles) {
             localFiles = new Aladin.wasmLibs.core.HiPSLocalFiles(this.localFiles["moc"]);
 
             let fmt;
@@ -923,10 +989,26 @@ export let HiPS = (function () {
             }
         }
 
-        this.view.wasm.addHiPS(
-            config,
-            localFiles
-        );
+        if (this.propertiesText) {
+            // The backend parses the properties file itself so that no keyword is lost
+            this.view.wasm.addHiPSFromProperties(
+                layer,
+                this.url,
+                this.propertiesText,
+                this.creatorDid,
+                config.meta,
+                localFiles
+            );
+        } else {
+            this.view.wasm.addHiPS(
+                config,
+                localFiles
+            );
+        }
+
+        if (this.mirrors && this.url !== "local") {
+            this.view.wasm.setHiPSMirrors(this.creatorDid, this.mirrors);
+        }
 
         return Promise.resolve(this)
             .then((hips) => {
@@ -975,6 +1057,89 @@ export let HiPS = (function () {
         return this.view.wasm.readPixel(x, y, this.layer);
     };
 
+    // @api
+    // Number of frames of a HiPS cube, 1 if the HiPS is not a cube
+    HiPS.prototype.getNumFrames = function () {
+        return (this.cube && this.cube.depth) || 1;
+    };
+
+    // @api
+    // Display another frame of a HiPS cube. The current frame stays displayed
+    // until the tiles of the new one are received
+    HiPS.prototype.setFrame = function (frame) {
+        this.view.wasm.setHiPSCubeFrame(this.layer, frame);
+    };
+
+    // @api
+    HiPS.prototype.getFrame = function () {
+        return this.view.wasm.getHiPSCubeFrame(this.layer);
+    };
+
+    // @api
+    // Coordinate of a frame (the current one by default) along the third axis of the cube,
+    // in the unit given by hips_cube_bunit3
+    HiPS.prototype.getFrameCoordinate = function (frame) {
+        if (frame === undefined) {
+            frame = this.getFrame();
+        }
+
+        return this.view.wasm.getHiPSCubeFrameCoordinate(this.layer, frame);
+    };
+
+    // @api
+    // Reproject the layer onto a WCS (an object of FITS keywords: NAXIS1, CTYPE1, CRVAL1, CRPIX1, CD1_1...)
+    // or onto the current view if no WCS is given. Returns the bytes of the FITS file
+    HiPS.prototype.reprojectToFITS = function (wcs) {
+        return this.view.wasm.reprojectToFITS(this.layer, wcs);
+    };
+
+    // @api
+    // Trace contours on the layer. Options are:
+    // - levels: {type: 'explicit', values: [...]}, {type: 'linear', n, min, max},
+    //   {type: 'log', n, min, max} or {type: 'sigma', values: [3, 5, ...]}
+    // - order: for a HiPS, the order of the tiles the contours are computed on
+    // - color, opacity, lineWidth
+    // Returns the name of the contour overlay
+    HiPS.prototype.addContours = function (options) {
+        options = options || {};
+
+        const name = options.name || Utils.uuidv4();
+        this.view.wasm.addContour(HiPS._contourCfg(name, this.layer, options));
+
+        return name;
+    };
+
+    // @api
+    HiPS.prototype.setContoursOptions = function (name, options) {
+        this.view.wasm.setContourOptions(HiPS._contourCfg(name, this.layer, options || {}));
+    };
+
+    // @api
+    HiPS.prototype.removeContours = function (name) {
+        this.view.wasm.removeContour(name);
+    };
+
+    // @api
+    // Returns the contour lines as {level, closed, vertices: [[ra, dec], ...]} objects
+    HiPS.prototype.getContours = function (name) {
+        return this.view.wasm.getContourPolygons(name);
+    };
+
+    HiPS._contourCfg = function (name, layer, options) {
+        let color = Color.hexToRgb(Color.standardizeColor(options.color || '#00ff00'));
+        let opacity = options.opacity === undefined ? 1.0 : options.opacity;
+
+        return {
+            name: name,
+            layer: layer,
+            levels: options.levels || {type: 'sigma', values: [3, 5, 10]},
+            order: options.order,
+            color: {r: color.r / 255, g: color.g / 255, b: color.b / 255, a: opacity},
+            thickness: options.lineWidth || 1.0,
+            show: options.show === undefined ? true : options.show,
+        };
+    };
+
     HiPS.DEFAULT_SURVEY_ID = "P/DSS2/color";
 
     return HiPS;
@@ -28,7 +28,7 @@
  * 
  *****************************************************************************/
 import { Utils } from "./Utils";
-import { HiPSDefinition } from "./HiPSDefinition.js";
+import { Aladin } from "./Aladin.js";
 import { MocServer } from "./MocServer.js";
 
 export let HiPSProperties = {};
@@ -69,7 +69,8 @@ HiPSProperties.fetchFromID = async function(ID) {
     }
 }
 
-HiPSProperties.fetchFromUrl = async function(urlOrId) {
+// Fetch the text of the properties file of a HiPS
+HiPSProperties.fetchTextFromUrl = async function(urlOrId) {
     let url;
 
     try {
@@ -106,7 +107,7 @@ HiPSProperties.fetchFromUrl = async function(urlOrId) {
         init = { mode: 'cors' };
     }
 
-    let result = fetch(url, init)
+    return fetch(url, init)
         .then((response) => {
             if (response.status == 404) {
                 return Promise.reject("Url points to nothing")
@@ -114,51 +115,74 @@ HiPSProperties.fetchFromUrl = async function(urlOrId) {
                 return response.text();
             }
         })
-        .then(
-            (response) => new Promise((resolve, reject) => {
-                // We get the property here
-                let metadata = HiPSDefinition.parseHiPSProperties(response);
-                // 1. Ensure there is exactly one survey matching
-                if (metadata && Object.keys(metadata).length > 0) {
-                    // Set the service url if not found
-                    if (!metadata.hips_frame || !metadata.hips_order) {
-                        reject('Bad properties: do not contain the mandatory frame or order info')
-                    } else {
-                        if (!("hips_service_url" in metadata)) {
-                            metadata.hips_service_url = HiPSServiceUrl;
-                        }
-                        resolve(metadata);
-                    }
-                } else {
-                    reject('No surveys matching at this url: ' + rootURL);
-                }
-            })
-        )
+        .then((text) => ({text, serviceUrl: HiPSServiceUrl}));
+}
 
-    return result;
+// Parse the text of a properties file the same way the backend does
+HiPSProperties.parse = function(text) {
+    return Aladin.wasmLibs.core.parseHiPSProperties(text);
 }
 
-HiPSProperties.fetchFromFile = function(file) {
+HiPSProperties.parseText = function(text, serviceUrl) {
+    let metadata = HiPSProperties.parse(text);
+    // 1. Ensure there is exactly one survey matching
+    if (metadata && Object.keys(metadata).length > 0) {
+        // Set the service url if not found
+        if (!metadata.hips_frame || !metadata.hips_order) {
+            throw 'Bad properties: do not contain the mandatory frame or order info';
+        } else {
+            if (!("hips_service_url" in metadata)) {
+                metadata.hips_service_url = serviceUrl;
+            }
+            return metadata;
+        }
+    } else {
+        throw 'No surveys matching at this url: ' + serviceUrl;
+    }
+}
+
+HiPSProperties.fetchFromUrl = async function(urlOrId) {
+    const {text, serviceUrl} = await HiPSProperties.fetchTextFromUrl(urlOrId);
+    return HiPSProperties.parseText(text, serviceUrl);
+}
+
+// Read the text of a local properties file
+HiPSProperties.fetchTextFromFile = function(file) {
     let url = URL.createObjectURL(file);
     return fetch(url)
         .then((response) => response.text())
-        .then(
-            (response) => new Promise((resolve, reject) => {
-                // We get the property here
-                let metadata = HiPSDefinition.parseHiPSProperties(response);
-
-                URL.revokeObjectURL(url)
+        .finally(() => URL.revokeObjectURL(url));
+}
 
-                // 1. Ensure there is exactly one survey matching
-                if (metadata && Object.keys(metadata).length > 0) {
-                    resolve(metadata)
-                } else {
-                    reject('No surveys matching at this url: ' + rootURL);
-                }
-            })
-        );
+HiPSProperties.fetchFromFile = function(file) {
+    return HiPSProperties.fetchTextFromFile(file)
+        .then((text) => {
+            // We get the property here
+            let metadata = HiPSProperties.parse(text);
+
+            // 1. Ensure there is exactly one survey matching
+            if (metadata && Object.keys(metadata).length > 0) {
+                return metadata;
+            } else {
+                throw 'No surveys matching in the properties file: ' + file.name;
+            }
+        });
 }
 
+// Get all the possible hips_service_url urls
+HiPSProperties.getMirrorUrls = function (metadata) {
+    let urls = [metadata.hips_service_url];
+
+    let numHiPSServiceURL = 1;
+    while (metadata.hasOwnProperty("hips_service_url_" + numHiPSServiceURL.toString())) {
+        const key = "hips_service_url_" + numHiPSServiceURL.toString();
+        urls.push(metadata[key]);
+        numHiPSServiceURL += 1;
+    }
+
+    return urls;
+};
+
 HiPSProperties.getFasterMirrorUrl = function (metadata) {
     const pingHiPSServiceUrl = async (baseUrl) => {
         baseUrl = Utils.fixURLForHTTPS(baseUrl);
@@ -190,27 +214,13 @@ HiPSProperties.getFasterMirrorUrl = function (metadata) {
         return {duration, validRequest, baseUrl};
     };
 
-    // Get all the possible hips_service_url urls
-    let promises = [];
-    let urls = [metadata.hips_service_url];
-
-    promises.push(pingHiPSServiceUrl(metadata.hips_service_url));
-
-    let numHiPSServiceURL = 1;
-    while (metadata.hasOwnProperty("hips_service_url_" + numHiPSServiceURL.toString())) {
-        const key = "hips_service_url_" + numHiPSServiceURL.toString();
-
-        let curUrl = metadata[key];
-        promises.push(pingHiPSServiceUrl(curUrl))
-        numHiPSServiceURL += 1;
-
-        urls.push(curUrl)
-    }
-
-    if (numHiPSServiceURL === 1) {
+    const urls = HiPSProperties.getMirrorUrls(metadata);
+    if (urls.length === 1) {
         return Promise.resolve(urls[0]);
     }
 
+    const promises = urls.map((url) => pingHiPSServiceUrl(url));
+
     return Promise.all(promises)
         .then((responses) => {
             // filter the ones that failed to not choose them
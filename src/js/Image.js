@@ -28,8 +28,8 @@
 import { ColorCfg } from "./ColorCfg.js";
 import { Aladin } from "./Aladin.js";
 import { Utils } from "./Utils";
-import { AVM } from "./libs/avm.js";
 import { HiPS } from "./HiPS.js";
+import { View } from "./View.js";
 
 /**
  * @typedef {Object} WCS
@@ -200,6 +200,15 @@ export let Image = (function () {
         // @api
         readPixel: HiPS.prototype.readPixel,
 
+        // @api
+        reprojectToFITS: HiPS.prototype.reprojectToFITS,
+
+        // @api
+        addContours: HiPS.prototype.addContours,
+        setContoursOptions: HiPS.prototype.setContoursOptions,
+        removeContours: HiPS.prototype.removeContours,
+        getContours: HiPS.prototype.getContours,
+
         // Private method for updating the view with the new meta
         _updateMetadata: HiPS.prototype._updateMetadata,
 
@@ -208,6 +217,56 @@ export let Image = (function () {
             this._saveInCache();
         },
 
+        // @api
+        // Move the image so that the ICRS position (ra1, dec1) ends at (ra2, dec2)
+        translateWCS: function (ra1, dec1, ra2, dec2) {
+            this._updateLocation(this.view.wasm.translateImageWCS(this.layer, ra1, dec1, ra2, dec2));
+        },
+
+        // @api
+        // Rotate the image by an angle in degrees, counted from the north towards the east.
+        // The pivot is the center of the image if no ICRS position is given
+        rotateWCS: function (angle, ra, dec) {
+            ra = ra === undefined ? this.ra : ra;
+            dec = dec === undefined ? this.dec : dec;
+
+            this._updateLocation(this.view.wasm.rotateImageWCS(this.layer, ra, dec, angle));
+        },
+
+        // @api
+        // Scale the image by a factor, it grows if it is greater than 1.
+        // The pivot is the center of the image if no ICRS position is given
+        scaleWCS: function (factor, ra, dec) {
+            ra = ra === undefined ? this.ra : ra;
+            dec = dec === undefined ? this.dec : dec;
+
+            this._updateLocation(this.view.wasm.scaleImageWCS(this.layer, ra, dec, factor));
+        },
+
+        // @api
+        // The WCS of the image, adjustments included, as 80 characters FITS header cards
+        getWCSHeader: function () {
+            return this.view.wasm.getImageWCSHeader(this.layer).split('\n');
+        },
+
+        // @api
+        // Adjust the WCS with the mouse: drag to move the image,
+        // shift + drag to rotate it and ctrl + drag to scale it around its center
+        startWCSAdjustment: function () {
+            this.view.setMode(View.WCS_ADJUST, this);
+        },
+
+        // @api
+        stopWCSAdjustment: function () {
+            this.view.setMode(View.PAN);
+        },
+
+        _updateLocation: function (centeredFov) {
+            this.ra = centeredFov.ra;
+            this.dec = centeredFov.dec;
+            this.fov = centeredFov.fov;
+        },
+
         _addFITS: function(layer) {
             let self = this;
 
@@ -253,7 +312,64 @@ export let Image = (function () {
             })
         },
 
+        _addJPGOrPNGWithAVM: function(layer) {
+            let self = this;
+            let imgFormat;
+
+            const addImage = (blob) => {
+                imgFormat = self._getJPGOrPNGFormat(blob);
+
+                return self.view.wasm.addImageWithAVM(
+                    blob,
+                    {
+                        ...self.colorCfg.get(),
+                        longitudeReversed: this.longitudeReversed,
+                        imgFormat,
+                    },
+                    layer
+                )
+            };
+
+            return Utils.fetch({
+                url: this.url,
+                dataType: 'blob',
+                success: addImage,
+                error: (e) => {
+                    // try as cors
+                    const url = Aladin.JSONP_PROXY + '?url=' + self.url;
+
+                    return Utils.fetch({
+                        url: url,
+                        dataType: 'blob',
+                        success: addImage,
+                    });
+                }
+            })
+            .then((imageParams) => {
+                self.imgFormat = imgFormat;
+                return Promise.resolve(imageParams);
+            })
+        },
+
+        // The format of an image given by its MIME type, or else by the extension of its url
+        _getJPGOrPNGFormat: function(blob) {
+            if (blob.type === 'image/png') {
+                return 'png';
+            } else if (blob.type === 'image/jpeg') {
+                return 'jpeg';
+            } else if (this.imgFormat === 'jpeg' || this.imgFormat === 'png') {
+                return this.imgFormat;
+            }
+
+            return /\.png([?#]|$)/i.test(this.url) ? 'png' : 'jpeg';
+        },
+
         _addJPGOrPNG: function(layer) {
+            /* look for avm tags if no wcs is given */
+            if (!this.options.wcs) {
+                return this._addJPGOrPNGWithAVM(layer);
+            }
+
             let self = this;
             let img = document.createElement('img');
 
@@ -279,39 +395,7 @@ export let Image = (function () {
                         resolve(stream)
                     };
 
-                    if (!self.options.wcs) {
-                        /* look for avm tags if no wcs is given */
-                        let avm = new AVM(img);
-
-                        avm.load((obj) => {
-                            // obj contains the following information:
-                            // obj.id (string) = The ID provided for the image
-                            // obj.img (object) = The image object
-                            // obj.xmp (string) = The raw XMP header
-                            // obj.wcsdata (Boolean) = If WCS have been loaded
-                            // obj.tags (object) = An array containing all the loaded tags e.g. obj.tags['Headline']
-                            // obj.wcs (object) = The wcs parsed from the image
-                            if (obj.wcsdata) {
-                                if (img.width !== obj.wcs.NAXIS1) {
-                                    obj.wcs.NAXIS1 = img.width;
-                                }
-
-                                if (img.height !== obj.wcs.NAXIS2) {
-                                    obj.wcs.NAXIS2 = img.height;
-                                }
-
-                                self.options.wcs = obj.wcs;
-
-                                img2Blob()
-                            } else {
-                                // no tags found
-                                reject('No WCS have been found in the image')
-                                return;
-                            }
-                        })
-                    } else {
-                        img2Blob()
-                    }
+                    img2Blob()
                 }
 
                 let proxyUsed = false;
@@ -73,6 +73,10 @@ export let ProgressiveCat = (function() {
 
         this.onClick = options.onClick || undefined; // TODO: inherit from catalog
 
+        // The tiles can be loaded and drawn as a heatmap by the WebGL core
+        this.webgl = options.webgl || false;
+        this.colormap = options.colormap || 'inferno';
+
         // we cache the list of sources in each healpix tile. Key of the cache is norder+'-'+npix
         this.sourcesCache = new Utils.LRUCache(256);
         this.footprintsCache = new Utils.LRUCache(256);
@@ -246,6 +250,15 @@ export let ProgressiveCat = (function() {
 
         _loadMetadata: function() {
             var self = this;
+            if (this.webgl) {
+                // The WebGL core fetches the Metadata.xml file, the Allsky files and the tiles
+                this.isReady = true;
+                if (this.isShowing) {
+                    this.view.wasm.addHiPSCatalog(this.uuid, this.rootUrl, this.maxOrder, this.colormap);
+                }
+                return;
+            }
+
             let request = new Request(self.rootUrl + '/' + 'Metadata.xml', {
                 method: 'GET'
             })
@@ -366,7 +379,7 @@ export let ProgressiveCat = (function() {
         },
 
         draw: function(ctx, width, height) {
-            if (! this.isShowing || ! this.isReady) {
+            if (! this.isShowing || ! this.isReady || this.webgl) {
                 return;
             }
 
@@ -556,6 +569,9 @@ export let ProgressiveCat = (function() {
                 return;
             }
             this.isShowing = true;
+            if (this.webgl && this.isReady) {
+                this.view.wasm.addHiPSCatalog(this.uuid, this.rootUrl, this.maxOrder, this.colormap);
+            }
             this.loadNeededTiles();
             this.reportChange();
         },
@@ -564,6 +580,9 @@ export let ProgressiveCat = (function() {
                 return;
             }
             this.isShowing = false;
+            if (this.webgl && this.isReady) {
+                this.view.wasm.removeCatalog(this.uuid);
+            }
             this.reportChange();
         },
         reportChange: function() {
@@ -576,7 +595,7 @@ export let ProgressiveCat = (function() {
         },
     
         loadNeededTiles: function() {
-            if ( ! this.isShowing) {
+            if ( ! this.isShowing || this.webgl) {
                 return;
             }
             this.tilesInView = [];
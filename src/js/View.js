@@ -301,6 +301,7 @@ export let View = (function () {
     View.PAN = 0;
     View.SELECT = 1;
     View.TOOL_SIMBAD_POINTER = 2;
+    View.WCS_ADJUST = 3;
 
 
     // TODO: should be put as an option at layer level
@@ -440,8 +441,10 @@ export let View = (function () {
         this.selector.dispatch('start', {callback});
     }
 
-    View.prototype.setMode = function (mode) {
+    View.prototype.setMode = function (mode, image) {
         this.mode = mode;
+        // The image whose WCS is adjusted with the mouse
+        this.wcsAdjustedImage = this.mode == View.WCS_ADJUST ? image : null;
 
         if (this.mode == View.TOOL_SIMBAD_POINTER) {
             this.aladin.popup.hide();
@@ -455,10 +458,66 @@ export let View = (function () {
             this.setCursor('crosshair');
             this.aladin.showReticle(false)
         }
+        else if (this.mode == View.WCS_ADJUST) {
+            this.setCursor('grab');
+        }
 
         ALEvent.MODE.dispatchedTo(this.aladin.aladinDiv, {mode});
     };
 
+    // Adjust the WCS of an image from a mouse drag between the screen positions s1 and s2:
+    // - a drag moves the image
+    // - shift + drag rotates it around its center
+    // - ctrl + drag scales it around its center
+    View.prototype.adjustImageWCS = function (image, s1, s2, e) {
+        let c1, c2;
+        try {
+            c1 = this.aladin.pix2world(s1.x, s1.y, 'icrs');
+            c2 = this.aladin.pix2world(s2.x, s2.y, 'icrs');
+        } catch (err) {
+            // One of the positions is outside the projection
+            return;
+        }
+
+        if (e.shiftKey || e.ctrlKey || e.metaKey) {
+            let center;
+            try {
+                center = this.aladin.world2pix(image.ra, image.dec);
+            } catch (err) {
+                return;
+            }
+
+            if (!center) {
+                return;
+            }
+
+            const [cx, cy] = center;
+            if (e.shiftKey) {
+                // The screen y axis goes down
+                const a1 = Math.atan2(s1.y - cy, s1.x - cx);
+                const a2 = Math.atan2(s2.y - cy, s2.x - cx);
+                let angle = (a2 - a1) * 180.0 / Math.PI;
+                // The east is on the left of the north unless the longitudes are reversed,
+                // a counterclockwise drag then turns the image towards the east
+                const baseLayer = this.aladin.getBaseImageLayer();
+                if (!(baseLayer && baseLayer.longitudeReversed)) {
+                    angle = -angle;
+                }
+
+                image.rotateWCS(angle);
+            } else {
+                const d1 = Math.hypot(s1.x - cx, s1.y - cy);
+                const d2 = Math.hypot(s2.x - cx, s2.y - cy);
+
+                if (d1 > 0 && d2 > 0) {
+                    image.scaleWCS(d2 / d1);
+                }
+            }
+        } else {
+            image.translateWCS(c1[0], c1[1], c2[0], c2[1]);
+        }
+    };
+
     View.prototype.setCursor = function (cursor) {
         if (this.catalogCanvas.style.cursor == cursor) {
             return;
@@ -1127,6 +1186,10 @@ export let View = (function () {
                 // Apply position changed callback after the move
                 view.throttledPositionChanged(true);
             }
+
+            if (view.mode === View.WCS_ADJUST && view.wcsAdjustedImage) {
+                view.adjustImageWCS(view.wcsAdjustedImage, s1, s2, e);
+            }
         }); //// endof mousemove ////
 
         // disable text selection on IE
@@ -1293,6 +1356,8 @@ export let View = (function () {
         this.dt = elapsedTime;
 
         this.moving = this.wasm.update(elapsedTime);
+        this.handleHiPSMirrorSwitches();
+        this.handleCatalogEvents();
         
         // inertia run throttled position
         if (this.moving && this.aladin.callbacksByEventName && this.aladin.callbacksByEventName['positionChanged'] && this.wasm.isInerting()) {
@@ -1397,6 +1462,20 @@ export let View = (function () {
             this.wasm.drawGridLabels();
         }
 
+        // display the numbers of sources of the aggregated catalogs
+        const hasCatalogLabels = this.wasm.hasCatalogLabels();
+        if (hasCatalogLabels || this.catalogLabelsDrawn) {
+            if (!this.catalogCanvasCleared) {
+                ctx.clearRect(0, 0, this.width, this.height);
+                this.catalogCanvasCleared = true;
+            }
+
+            if (hasCatalogLabels) {
+                this.wasm.drawCatalogLabels();
+            }
+            this.catalogLabelsDrawn = hasCatalogLabels;
+        }
+
         if (this.mode === View.SELECT) {
             if (!this.catalogCanvasCleared) {
                 ctx.clearRect(0, 0, this.width, this.height);
@@ -1880,6 +1959,45 @@ export let View = (function () {
         return false;
     }
 
+    // The backend may have changed the mirror some HiPS are retrieved from
+    View.prototype.handleHiPSMirrorSwitches = function () {
+        const switches = this.wasm.popHiPSMirrorSwitches();
+
+        for (const mirrorSwitch of switches) {
+            for (const imageLayer of this.imageLayers.values()) {
+                if (imageLayer instanceof HiPS && imageLayer.creatorDid === mirrorSwitch.creatorDid) {
+                    imageLayer.url = mirrorSwitch.url;
+
+                    ALEvent.HIPS_LAYER_MIRROR_CHANGED.dispatchedTo(this.aladinDiv, {
+                        layer: imageLayer,
+                        url: mirrorSwitch.url,
+                        previousUrl: mirrorSwitch.previousUrl,
+                        reason: mirrorSwitch.reason,
+                    });
+                }
+            }
+        }
+    }
+
+    // Report the progress of the catalogs whose sources are parsed by the backend
+    View.prototype.handleCatalogEvents = function () {
+        const events = this.wasm.popCatalogEvents();
+
+        for (const event of events) {
+            if (event.type === 'progress') {
+                ALEvent.CATALOG_LOADING_PROGRESS.dispatchedTo(this.aladinDiv, {
+                    name: event.name,
+                    progress: event.progress,
+                });
+            } else if (event.type === 'loaded') {
+                ALEvent.CATALOG_LOADED.dispatchedTo(this.aladinDiv, {
+                    name: event.name,
+                    numSources: event.numSources,
+                });
+            }
+        }
+    }
+
     View.prototype.setHiPSUrl = function (pastUrl, newUrl) {
         try {
             this.wasm.setHiPSUrl(pastUrl, newUrl);
@@ -2037,6 +2155,11 @@ export let View = (function () {
     };
 
     View.prototype.removeOverlays = function () {
+        this.catalogs.forEach((catalog) => {
+            if (catalog.webgl) {
+                this.wasm.removeCatalog(catalog.uuid);
+            }
+        });
         this.catalogs = [];
         this.overlays = [];
         this.mocs = [];
@@ -2064,6 +2187,10 @@ export let View = (function () {
 
             this.catalogs.splice(indexToDelete, 1);
 
+            if (overlay.webgl) {
+                this.wasm.removeCatalog(overlay.uuid);
+            }
+
             this.unselectObjects();
         }
         else if (overlay.type == 'moc') {
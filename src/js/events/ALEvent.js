@@ -58,6 +58,10 @@ export class ALEvent {
   static HIPS_CACHE_UPDATED = new ALEvent("AL:HiPSCache.updated");
 
   static HIPS_LAYER_CHANGED  = new ALEvent("AL:HiPSLayer.changed");
+  static HIPS_LAYER_MIRROR_CHANGED  = new ALEvent("AL:HiPSLayer.mirrorChanged");
+
+  static CATALOG_LOADING_PROGRESS = new ALEvent("AL:Catalog.loadingProgress");
+  static CATALOG_LOADED = new ALEvent("AL:Catalog.loaded");
 
   static GRAPHIC_OVERLAY_LAYER_ADDED  = new ALEvent("AL:GraphicOverlayLayer.added");
   static GRAPHIC_OVERLAY_LAYER_REMOVED  = new ALEvent("AL:GraphicOverlayLayer.removed");
@@ -53,23 +53,22 @@ export let Datalink = (function() {
                 rsc = VOTable.parseRsc(rsc);
 
                 // It is a table
-                if (rsc && rsc.fields && rsc.rows) {
+                if (rsc && rsc.fields && (rsc.rows || rsc.columns)) {
                     let table = rsc;
-                    table.fields = Catalog.parseFields(table.fields);
+                    const fields = Catalog.parseFields(table.fields);
 
                     // Get the fields and the rows
                     let measures = [];
-                    const { fields, rows } = table;
-                    rows.forEach(row => {        
+                    for (let row = 0; row < VOTable.getNumRows(table); row++) {
                         let data = {};
 
                         for (const [_, field] of Object.entries(fields)) {
                             var key = field.name;
-                            data[key] = row[field.idx];
+                            data[key] = VOTable.getValue(table, field.idx, row);
                         }
 
                         measures.push({data: data})
-                    })
+                    }
                     let self = this;
                     let datalinkTable = {
                         name: 'Datalink:' + url,
@@ -27,225 +27,15 @@
  *****************************************************************************/
 
 import { Utils } from "./../Utils";
+import { Aladin } from "./../Aladin.js";
 
-/*
-/// VOTable-rust version ///
-export class VOTable {
-    constructor(url, successCallback, errorCallback, useProxy) {
-        Utils.fetch({url, useProxy})
-            .then((resp) => resp.text())
-            .then((text) => {
-                ALEvent.AL_USE_WASM.dispatchedTo(document.body, {
-                    callback: (wasm) => {
-                        let votable;
-                        try {
-                            votable = wasm.parseVOTable(xml);
-                        } catch (e) {
-                            errorCallback('Catalogue failed to be parsed: ' + e);
-                        }
-                        
-                        votable.votable.get("resources")
-                            .forEach((rsc) => successCallback(rsc))
-                    }
-                })
-            })
-            .catch((e) => {
-                if (errorCallback) {
-                    errorCallback(e);
-                } else {
-                    throw e;
-                }
-            })
-    };
-
-    static parseTableRsc(rsc) {
-        let tables = rsc.get("tables")
-        if (tables) {
-            // take only the first table
-            let table = tables[0];
-        
-            let fields = table.get("elems")
-                .filter((elem) => {
-                    const elemType = elem["elem_type"] || elem.get("elem_type")
-                    return elemType === "Field";
-                })
-                .map((field) => {
-                    // convert a map into a javascript object
-                    return Object.fromEntries(field);
-                });
-
-            let data = table.get("data");
-            if (data) {
-                let rows;
-                if (data.get("data_type") === "TableData") {
-                    rows = data.get("rows");
-                } else if(data.get("data_type") === "Binary") {
-                    rows = data.get("stream")
-                        .get("rows");
-                } else {
-                    throw 'VOTable has data type not handled:' + data.get("data_type");
-                }
-
-                return {fields: fields, rows: rows};
-            }
-        }
-    };
-
-    static parseSODAServiceRsc(rsc) {
-        let isSODAService = rsc.get("utype") === "adhoc:service";
-        if (isSODAService) {
-            let elems = rsc.get("elems");
-            let id = rsc.get("ID");
-
-            if (id && id.includes("async")) {
-                // First way to check if the resource refers to a async SODA service
-                return;
-            }
-
-            if (elems) {
-                let accessUrl;
-                let inputParams = {};
-    
-                elems.forEach((elem) => {
-                    if (elem instanceof Map) {
-                        elem = Object.fromEntries(elem.entries());
-                    }
-
-                    // SODA access url
-                    if (elem["elem_type"] === "Param" && (elem["ucd"] === "meta.ref.url" || elem["name"] === "accessURL")) {
-                        accessUrl = elem["value"];
-                    } else if (elem["elem_type"] === "Param" && elem["name"] === "standardID") {
-                        // Check if it is the sync service
-                        // discard the async
-                        if (elem["value"].includes("async")) {
-                            return;
-                        }
-                    // Input params group
-                    } else if (elem["name"] === "inputParams") {
-                        elem["elems"].forEach((inputParam) => {
-                            let name = inputParam.get("name");
-                            let utype = inputParam.get("utype");
-                            let values;
-                            switch (name) {
-                                case 'ID':
-                                    inputParams['ID'] = {
-                                        name: 'ID',
-                                        type: 'group',
-                                        description: inputParam.get("description"),
-                                        subInputs: [{
-                                            label: "ID",
-                                            name: 'ID',
-                                            type: "text",
-                                            value: inputParam.get("value")
-                                        }],
-                                    }
-                                    break;
-                                case 'CIRCLE':
-                                    if (inputParam.get("values")) {
-                                        values = inputParam.get("values")["max"]["value"].split(" ").map((v) => {return +v;});
-                                    }
-
-                                    inputParams['Circle'] = {
-                                        name: 'CIRCLE',
-                                        type: 'group',
-                                        description: inputParam.get("description"),
-                                        subInputs: [{
-                                            name: 'ra',
-                                            label: 'ra[' + utype + ']',
-                                            type: 'number',
-                                            value: values && values[0],
-                                        },
-                                        {
-                                            name: 'dec',
-                                            label: 'dec[' + utype + ']',
-                                            type: 'number',
-                                            value: values && values[1],
-                                        },
-                                        {
-                                            name: 'rad',
-                                            label: 'rad[' + utype + ']',
-                                            type: 'number',
-                                            value: values && values[2],
-                                        }]
-                                    };
-                                    break;
-                                case 'BAND':
-                                    if (inputParam.get("values")) {
-                                        values = inputParam.get("values")["max"]["value"].split(" ").map((v) => {return +v;});
-                                    }
-
-                                    inputParams['Band'] = {
-                                        name: 'BAND',
-                                        type: 'group',
-                                        description: inputParam.get("description"),
-                                        subInputs: [{
-                                            name: 'fmin',
-                                            label: 'fmin[' + utype + ']',
-                                            type: 'number',
-                                            value: values && values[0],
-                                        },
-                                        {
-                                            name: 'fmax',
-                                            label: 'fmax[' + utype + ']',
-                                            type: 'number',
-                                            value: values && values[1],
-                                        }]
-                                    };
-                                    break;
-                                case 'RANGE':
-                                    if (inputParam.get("values")) {
-                                        values = inputParam.get("values")["max"]["value"].split(" ").map((v) => {return +v;});
-                                    }    
-                                    inputParams['Range'] = {
-                                        name: 'RANGE',
-                                        type: 'group',
-                                        description: inputParam.get("description"),
-                                        subInputs: [{
-                                            name: 'ramin',
-                                            label: 'ramin[' + utype + ']',
-                                            type: 'number',
-                                            value: values && values[0],
-                                        },
-                                        {
-                                            name: 'ramax',
-                                            label: 'ramax[' + utype + ']',
-                                            type: 'number',
-                                            value: values && values[1],
-                                        },
-                                        {
-                                            name: 'decmin',
-                                            label: 'decmin[' + utype + ']',
-                                            type: 'number',
-                                            value: values && values[2],
-                                        },
-                                        {
-                                            name: 'decmax',
-                                            label: 'decmax[' + utype + ']',
-                                            type: 'number',
-                                            value: values && values[3],
-                                        }]
-                                    };
-                                default:
-                                    break;
-                            }
-                        })
-                    }
-                })
-    
-                return {
-                    baseUrl: accessUrl,
-                    inputParams: inputParams,
-                }
-            }
-        }
-    };
-};
-*/
-
-/// Pure Vanilla JS version (lighter)
+/// The tables are read by the backend, the other resources, e.g. the SODA services,
+/// by the DOM parser
 export class VOTable {
     static parser = new DOMParser();
     static textDecoder = new TextDecoder();
+    // The tables of the RESOURCE elements read by the backend
+    static tables = new WeakMap();
 
     constructor(url, successCallback, errorCallback, useProxy) {
         Utils.fetch({
@@ -254,8 +44,22 @@ export class VOTable {
             useProxy,
             success: data => {
                 try {
-                    let xml = VOTable.parser.parseFromString(data, "text/xml")
-                    xml.querySelectorAll("RESOURCE").forEach((rsc) => { successCallback(rsc) })
+                    const tables = VOTable._parseTables(data);
+                    // Only the metadata is parsed into a DOM when the backend has read the tables
+                    const text = tables ? VOTable._withoutData(data) : data;
+                    let xml = VOTable.parser.parseFromString(text, "text/xml")
+
+                    const tableElems = Array.from(xml.querySelectorAll("TABLE"));
+                    xml.querySelectorAll("RESOURCE").forEach((rsc) => {
+                        if (tables) {
+                            // The tables are given in the order of the document
+                            const rscTables = Array.from(rsc.querySelectorAll(":scope > TABLE"))
+                                .map((table) => tables[tableElems.indexOf(table)]);
+                            VOTable.tables.set(rsc, rscTables);
+                        }
+
+                        successCallback(rsc)
+                    })
                 } catch(e) {
                     if (errorCallback) {
                         errorCallback('Catalogue failed to be parsed: ' + e);
@@ -274,6 +78,27 @@ export class VOTable {
         })
     };
 
+    // Read the tables of a VOTable with the backend, null if it is not loaded
+    // or cannot read them, e.g. their streams are not embedded
+    static _parseTables(text) {
+        const wasm = Aladin.wasmLibs.core;
+        if (!wasm) {
+            return null;
+        }
+
+        try {
+            return wasm.parseVOTable(text);
+        } catch (e) {
+            console.warn('The VOTable is read by the javascript parser: ' + e);
+            return null;
+        }
+    }
+
+    // The text of a VOTable without the DATA elements of its tables
+    static _withoutData(text) {
+        return text.replace(/<(\w+:)?DATA[\s>][\s\S]*?<\/(\w+:)?DATA\s*>/g, "");
+    }
+
     static parseRsc(rsc) {
         // Case of a table
         if (rsc.querySelectorAll("TABLE").length > 0) {
@@ -291,6 +116,12 @@ export class VOTable {
     }
 
     static _parseTableRsc(rsc) {
+        // Take only the first table read by the backend
+        const tables = VOTable.tables.get(rsc);
+        if (tables && tables[0]) {
+            return VOTable._fromTable(tables[0]);
+        }
+
         var fields = [];
         var k = 0;
         const attributes = ["name", "ID", "ucd", "utype", "unit", "datatype", "arraysize", "width", "precision"];
@@ -410,6 +241,45 @@ export class VOTable {
         return {fields, rows};
     };
 
+    // The fields of a table read by the backend with its typed columns, no row being built
+    static _fromTable(table) {
+        const fields = table.fields.map((field, k) => {
+            let f = {};
+            for (const [key, value] of Object.entries(field)) {
+                if (value !== undefined && value !== null) {
+                    f[key] = value;
+                }
+            }
+            if (!f.ID) {
+                f.ID = "col_" + k;
+            }
+
+            return f;
+        });
+
+        return {...table, fields};
+    };
+
+    // The number of rows of a table returned by parseRsc
+    static getNumRows(table) {
+        return table.columns ? table.numRows : table.rows.length;
+    };
+
+    // The value of a cell of a table returned by parseRsc, null if it is undefined
+    static getValue(table, column, row) {
+        if (!table.columns) {
+            return table.rows[row][column];
+        }
+
+        const nulls = table.nulls[column];
+        if (nulls && nulls[row]) {
+            return null;
+        }
+
+        const value = table.columns[column][row];
+        return table.fields[column].datatype === 'boolean' ? value === 1 : value;
+    };
+
     static _parseServiceRsc(rsc) {
         // find the baseUrl
         const baseUrl = rsc.querySelectorAll('[name="accessURL"]')[0]
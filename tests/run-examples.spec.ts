@@ -55,5 +55,17 @@ const tests = [
         });
     } 
 })()
- 
+
+// The dev server stands in for two mirrors, the first one failing with a server error
+test("HiPS mirror failover", async ({ page }) => {
+    await page.route("**/data/hips/not-a-mirror/**", (route) =>
+        route.fulfill({ status: 503, body: "Service Unavailable" })
+    );
+    await open(page, "../examples/al-hips-mirrors");
+
+    await expect(page.locator('#mirror-status')).toContainText(
+        "to data/hips/CDS_P_DSS2_color",
+        { timeout: 20_000 }
+    );
+});
 